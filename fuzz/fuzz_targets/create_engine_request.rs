@@ -0,0 +1,13 @@
+//! Request bodies of the engine APIs.
+#![no_main]
+
+use ::libfuzzer_sys::fuzz_target;
+use ::rucat_common::{
+    engine::{CreateEngineRequest, EngineId},
+    serde_json,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_json::from_slice::<CreateEngineRequest>(data);
+    let _ = serde_json::from_slice::<EngineId>(data);
+});
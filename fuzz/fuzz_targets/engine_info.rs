@@ -0,0 +1,20 @@
+//! Engine records read back from the database.
+#![no_main]
+
+use ::libfuzzer_sys::fuzz_target;
+use ::rucat_common::{
+    engine::{EngineInfo, EngineState, EngineTime},
+    serde_json,
+};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(info) = serde_json::from_slice::<EngineInfo>(data) {
+        // serialize ∘ deserialize is identity for valid values
+        let json = serde_json::to_vec(&info).unwrap();
+        assert_eq!(serde_json::from_slice::<EngineInfo>(&json).unwrap(), info);
+    }
+    let _ = serde_json::from_slice::<EngineState>(data);
+    if let Ok(time) = serde_json::from_slice::<EngineTime>(data) {
+        time.elapsed_time();
+    }
+});
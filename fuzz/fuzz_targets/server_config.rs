@@ -0,0 +1,15 @@
+//! Config files of the server.
+#![no_main]
+
+use ::libfuzzer_sys::fuzz_target;
+use ::rucat_common::{
+    config::{BlobStoreVariant, DatabaseVariant},
+    serde_json,
+};
+use ::rucat_server::ServerConfig;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_json::from_slice::<ServerConfig>(data);
+    let _ = serde_json::from_slice::<DatabaseVariant>(data);
+    let _ = serde_json::from_slice::<BlobStoreVariant>(data);
+});
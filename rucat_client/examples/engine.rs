@@ -1,5 +1,5 @@
 use ::rucat_client::{resource_client, Credentials};
-use ::rucat_common::engine::{CreateEngineRequest, EngineState, EngineType};
+use ::rucat_common::engine::{CreateEngineRequest, CreateEngineResponse, EngineState, EngineType};
 use rucat_common::tokio;
 
 #[tokio::main]
@@ -9,11 +9,17 @@ async fn main() {
         engine_type: EngineType::Spark,
         version: "3.5.4".to_owned(),
         config: None,
+        start_in_progress_timeout_secs: None,
+        features: None,
+        start: None,
     };
     let credentials = Credentials::Bearer { token: "admin" };
     let client = resource_client::ResourceClient::new("http://localhost:3000", Some(credentials));
-    let id = client.create_engine(&request).await.unwrap();
+    let CreateEngineResponse { id, warnings } = client.create_engine(&request).await.unwrap();
     println!("Engine created with id: {}", id);
+    for warning in warnings {
+        println!("Warning: {}", warning);
+    }
     loop {
         let info = client.get_engine_info(&id).await.unwrap();
         println!("Engine {} is {:?}", id, info.state);
@@ -44,6 +50,6 @@ async fn main() {
     client.delete_engine(&id).await.unwrap();
     println!("Deleted engine");
 
-    let engines = client.list_engines().await.unwrap();
+    let engines = client.list_engines(None, None).await.unwrap();
     println!("Engines: {:?}", engines);
 }
@@ -0,0 +1,40 @@
+use ::std::fmt::Display;
+
+use ::reqwest::StatusCode;
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// Fail to send the request, or the server returns an error status.
+    Request(reqwest::Error),
+    /// The response body cannot be decoded, e.g. it has an unexpected content type.
+    Decode(String),
+}
+
+impl ClientError {
+    /// Status code of the response if the server returns an error status.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Request(e) => e.status(),
+            Self::Decode(_) => None,
+        }
+    }
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Request(e) => write!(f, "Request error: {}", e),
+            Self::Decode(msg) => write!(f, "Decode error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Request(e)
+    }
+}
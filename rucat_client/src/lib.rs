@@ -1,3 +1,4 @@
+pub mod error;
 pub mod resource_client;
 
 pub enum Credentials<'a> {
@@ -1,8 +1,19 @@
-use ::rucat_common::engine::{CreateEngineRequest, EngineId, EngineInfo};
+use ::reqwest::{header::CONTENT_TYPE, Response};
+use ::rucat_common::{
+    engine::{
+        CreateEngineRequest, CreateEngineResponse, EndpointKind, EngineId, EngineInfo,
+        ListEnginesResponse,
+    },
+    serde::de::DeserializeOwned,
+};
 
-use crate::Credentials;
+use crate::{
+    error::{ClientError, Result},
+    Credentials,
+};
 
-type Result<T> = std::result::Result<T, reqwest::Error>;
+/// Content type of the response bodies of Rucat server.
+const JSON_CONTENT_TYPE: &str = "application/json";
 
 /// Client for managing Rucat resources.
 pub struct ResourceClient<'a> {
@@ -24,25 +35,48 @@ impl<'a> ResourceClient<'a> {
         }
     }
 
-    pub async fn create_engine(&self, request: &CreateEngineRequest) -> Result<EngineId> {
+    /// Create an engine, the response carries warnings about it, e.g. its version is deprecated.
+    pub async fn create_engine(
+        &self,
+        request: &CreateEngineRequest,
+    ) -> Result<CreateEngineResponse> {
         let url = self.build_url("/engine");
         let builder = self.client.post(url).json(request);
         let builder = self.enable_auth_for_request(builder);
-        builder.send().await?.error_for_status()?.json().await
+        decode_json(builder.send().await?).await
     }
 
     pub async fn get_engine_info(&self, engine_id: &EngineId) -> Result<EngineInfo> {
         let url = self.build_url(&format!("/engine/{}", engine_id));
         let builder = self.client.get(url);
         let builder = self.enable_auth_for_request(builder);
-        builder.send().await?.error_for_status()?.json().await
+        decode_json(builder.send().await?).await
     }
 
-    pub async fn list_engines(&self) -> Result<Vec<EngineId>> {
+    /// Get the endpoint of the `kind` to connect to the engine, `None` if the engine does not have it,
+    /// e.g. the engine is not started yet.
+    pub async fn get_engine_endpoint(
+        &self,
+        engine_id: &EngineId,
+        kind: EndpointKind,
+    ) -> Result<Option<String>> {
+        let mut info = self.get_engine_info(engine_id).await?;
+        Ok(info.endpoints.remove(kind.as_str()))
+    }
+
+    /// List a page of engines, the server default is used for `offset` and `limit` if not set.
+    pub async fn list_engines(
+        &self,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<ListEnginesResponse> {
         let url = self.build_url("/engine");
-        let builder = self.client.get(url);
+        let builder = self
+            .client
+            .get(url)
+            .query(&[("offset", offset), ("limit", limit)]);
         let builder = self.enable_auth_for_request(builder);
-        builder.send().await?.error_for_status()?.json().await
+        decode_json(builder.send().await?).await
     }
 
     pub async fn stop_engine(&self, engine_id: &EngineId) -> Result<()> {
@@ -85,3 +119,29 @@ impl<'a> ResourceClient<'a> {
         }
     }
 }
+
+/// Decode the JSON body of a successful response.
+async fn decode_json<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let response = response.error_for_status()?;
+    check_content_type(&response, JSON_CONTENT_TYPE)?;
+    Ok(response.json().await?)
+}
+
+/// Check that the media type of the response is `expected`, ignoring parameters such as `charset`.
+fn check_content_type(response: &Response, expected: &str) -> Result<()> {
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap_or_default());
+    let media_type = content_type.and_then(|value| value.split(';').next());
+    if media_type.is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case(expected)) {
+        Ok(())
+    } else {
+        Err(ClientError::Decode(format!(
+            "Expected content type `{}` from {}, but got {}",
+            expected,
+            response.url(),
+            content_type.map_or("none".to_owned(), |value| format!("`{}`", value))
+        )))
+    }
+}
@@ -0,0 +1,41 @@
+//! The client only needs the engine types of `rucat_common`,
+//! the database client and the binary dependencies must not be compiled into it.
+
+use ::std::{collections::BTreeSet, process::Command};
+
+/// Dependencies only enabled by the `surrealdb`, `grpc` and `cli` features of `rucat_common`.
+const HEAVY_DEPENDENCIES: [&str; 5] = ["surrealdb", "tonic", "prost", "clap", "tracing-subscriber"];
+
+/// Names of the crates the client depends on, resolved with the features the client enables.
+fn get_dependencies() -> BTreeSet<String> {
+    let output = Command::new(env!("CARGO"))
+        .args(["tree", "--offline", "-p", "rucat_client", "-e", "normal"])
+        .args(["--prefix", "none", "--manifest-path"])
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+        .output()
+        .expect("cargo should run");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout)
+        .expect("cargo tree should print UTF-8")
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_owned)
+        .collect()
+}
+
+#[test]
+fn client_builds_without_heavy_dependencies() {
+    let dependencies = get_dependencies();
+    assert!(dependencies.contains("rucat_common"));
+    for dependency in HEAVY_DEPENDENCIES {
+        assert!(
+            !dependencies.contains(dependency),
+            "rucat_client depends on {}",
+            dependency
+        );
+    }
+}
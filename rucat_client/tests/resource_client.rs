@@ -1,8 +1,11 @@
 use ::httpmock::prelude::*;
 use ::reqwest::StatusCode;
-use ::rucat_client::{resource_client::ResourceClient, Credentials};
+use ::rucat_client::{error::ClientError, resource_client::ResourceClient, Credentials};
 use ::rucat_common::{
-    engine::{CreateEngineRequest, EngineInfo, EngineState, EngineTime, EngineType},
+    engine::{
+        CreateEngineRequest, EndpointKind, EngineInfo, EngineState, EngineTime, EngineType,
+        ListEnginesResponse,
+    },
     serde_json::json,
     tokio,
 };
@@ -19,6 +22,9 @@ async fn create_engine_success() {
             Cow::Borrowed("spark.executor.memory"),
             Cow::Borrowed("2g"),
         )])),
+        start_in_progress_timeout_secs: None,
+        features: None,
+        start: None,
     };
     let mock = server.mock(|when, then| {
         when.method(POST)
@@ -26,17 +32,48 @@ async fn create_engine_success() {
             .header_exists("Authorization")
             .json_body_obj(&request_body);
         then.status(200)
-            .header("content-type", "text/html; charset=UTF-8")
+            .header("content-type", "application/json")
             .json_body(json!({
                 "id": "abc",
             }));
     });
     let base_url = server.base_url();
     let client = ResourceClient::new(&base_url, Some(Credentials::Bearer { token: "admin" }));
-    let engine_id = client.create_engine(&request_body).await.unwrap();
+    let response = client.create_engine(&request_body).await.unwrap();
 
     mock.assert();
-    assert_eq!(engine_id.to_string(), "abc");
+    assert_eq!(response.id.to_string(), "abc");
+    assert!(response.warnings.is_empty());
+}
+
+#[tokio::test]
+async fn create_engine_with_warnings() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(POST).path("/engine");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({
+                "id": "abc",
+                "warnings": ["Spark version 3.5.3 is deprecated"],
+            }));
+    });
+    let base_url = server.base_url();
+    let client = ResourceClient::new(&base_url, None);
+    let request_body = CreateEngineRequest {
+        name: "engine1".to_owned(),
+        engine_type: EngineType::Spark,
+        version: "3.5.3".to_owned(),
+        config: None,
+        start_in_progress_timeout_secs: None,
+        features: None,
+        start: None,
+    };
+    let response = client.create_engine(&request_body).await.unwrap();
+
+    mock.assert();
+    assert_eq!(response.id.to_string(), "abc");
+    assert_eq!(response.warnings, ["Spark version 3.5.3 is deprecated"]);
 }
 
 #[tokio::test]
@@ -50,6 +87,9 @@ async fn create_engine_error() {
             Cow::Borrowed("spark.executor.memory"),
             Cow::Borrowed("2g"),
         )])),
+        start_in_progress_timeout_secs: None,
+        features: None,
+        start: None,
     };
     let mock = server.mock(|when, then| {
         when.method(POST)
@@ -102,7 +142,7 @@ async fn get_engine_info_success() {
             .path("/engine/abc")
             .header_exists("Authorization");
         then.status(200)
-            .header("content-type", "text/html; charset=UTF-8")
+            .header("content-type", "application/json")
             .json_body_obj(&engine_info);
     });
     let base_url = server.base_url();
@@ -116,6 +156,45 @@ async fn get_engine_info_success() {
     assert_eq!(response, engine_info);
 }
 
+#[tokio::test]
+async fn get_engine_endpoint() {
+    let server = MockServer::start();
+    let mut engine_info = EngineInfo::new(
+        "engine1".to_owned(),
+        EngineType::Spark,
+        "3.5.4".to_owned(),
+        EngineState::Running,
+        BTreeMap::new(),
+        EngineTime::now(),
+    );
+    engine_info.endpoints.insert(
+        "connect-internal".to_owned(),
+        "sc://engine:15002".to_owned(),
+    );
+    let mock = server.mock(|when, then| {
+        when.method(GET).path("/engine/abc");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body_obj(&engine_info);
+    });
+    let base_url = server.base_url();
+    let client = ResourceClient::new(&base_url, None);
+    let id = "abc".try_into().unwrap();
+
+    let internal = client
+        .get_engine_endpoint(&id, EndpointKind::ConnectInternal)
+        .await
+        .unwrap();
+    let external = client
+        .get_engine_endpoint(&id, EndpointKind::ConnectExternal)
+        .await
+        .unwrap();
+
+    mock.assert_hits(2);
+    assert_eq!(internal.as_deref(), Some("sc://engine:15002"));
+    assert_eq!(external, None);
+}
+
 #[tokio::test]
 async fn list_engines_error() {
     let server = MockServer::start();
@@ -127,7 +206,7 @@ async fn list_engines_error() {
     });
     let base_url = server.base_url();
     let client = ResourceClient::new(&base_url, Some(Credentials::Bearer { token: "admin" }));
-    let err = client.list_engines().await.unwrap_err();
+    let err = client.list_engines(None, None).await.unwrap_err();
 
     mock.assert();
     assert_eq!(err.status(), Some(StatusCode::INTERNAL_SERVER_ERROR));
@@ -136,21 +215,45 @@ async fn list_engines_error() {
 #[tokio::test]
 async fn list_engines_success() {
     let server = MockServer::start();
-    let engine_ids = vec!["abc".try_into().unwrap(), "def".try_into().unwrap()];
+    let page = ListEnginesResponse {
+        engines: vec!["abc".try_into().unwrap(), "def".try_into().unwrap()],
+        next_offset: Some(2),
+    };
     let mock = server.mock(|when, then| {
         when.method(GET)
             .path("/engine")
             .header_exists("Authorization");
         then.status(200)
-            .header("content-type", "text/html; charset=UTF-8")
-            .json_body_obj(&engine_ids);
+            .header("content-type", "application/json")
+            .json_body_obj(&page);
     });
     let base_url = server.base_url();
     let client = ResourceClient::new(&base_url, Some(Credentials::Bearer { token: "admin" }));
-    let response = client.list_engines().await.unwrap();
+    let response = client.list_engines(None, None).await.unwrap();
 
     mock.assert();
-    assert_eq!(response, engine_ids);
+    assert_eq!(response, page);
+}
+
+#[tokio::test]
+async fn list_engines_with_page() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET)
+            .path("/engine")
+            .query_param("offset", "2")
+            .query_param("limit", "10");
+        then.status(200)
+            .header("content-type", "application/json")
+            .json_body(json!({"engines": [], "next_offset": null}));
+    });
+    let base_url = server.base_url();
+    let client = ResourceClient::new(&base_url, None);
+    let response = client.list_engines(Some(2), Some(10)).await.unwrap();
+
+    mock.assert();
+    assert!(response.engines.is_empty());
+    assert_eq!(response.next_offset, None);
 }
 
 #[tokio::test]
@@ -269,3 +372,62 @@ async fn delete_engine_success() {
 
     mock.assert();
 }
+
+#[tokio::test]
+async fn get_engine_info_with_unexpected_content_type() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET).path("/engine/abc");
+        then.status(200)
+            .header("content-type", "text/html; charset=UTF-8")
+            .body("<html></html>");
+    });
+    let base_url = server.base_url();
+    let client = ResourceClient::new(&base_url, None);
+    let err = client
+        .get_engine_info(&"abc".try_into().unwrap())
+        .await
+        .unwrap_err();
+
+    mock.assert();
+    assert!(matches!(&err, ClientError::Decode(_)));
+    assert_eq!(err.status(), None);
+    assert!(err
+        .to_string()
+        .starts_with("Decode error: Expected content type `application/json` from "));
+    assert!(err
+        .to_string()
+        .ends_with("/engine/abc, but got `text/html; charset=UTF-8`"));
+}
+
+#[tokio::test]
+async fn list_engines_without_content_type() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET).path("/engine");
+        then.status(200).body("[]");
+    });
+    let base_url = server.base_url();
+    let client = ResourceClient::new(&base_url, None);
+    let err = client.list_engines(None, None).await.unwrap_err();
+
+    mock.assert();
+    assert!(err.to_string().ends_with("/engine, but got none"));
+}
+
+#[tokio::test]
+async fn list_engines_with_charset() {
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method(GET).path("/engine");
+        then.status(200)
+            .header("content-type", "application/json; charset=utf-8")
+            .body(r#"{"engines": [], "next_offset": null}"#);
+    });
+    let base_url = server.base_url();
+    let client = ResourceClient::new(&base_url, None);
+    let response = client.list_engines(None, None).await.unwrap();
+
+    mock.assert();
+    assert!(response.engines.is_empty());
+}
@@ -0,0 +1,52 @@
+//! Deserialization of the engines listed by the state monitor.
+//! Run with `cargo bench -p rucat_common --bench engine_info`.
+
+use ::std::{borrow::Cow, hint::black_box};
+
+use ::criterion::{criterion_group, criterion_main, Criterion};
+use ::rucat_common::{
+    engine::{EngineConfig, EngineInfo, EngineInfoSummary, EngineState, EngineTime, EngineType},
+    serde_json,
+};
+
+const ENGINES: usize = 10_000;
+
+/// Engines with a config similar to the ones in production.
+fn engine_records() -> String {
+    let config: EngineConfig = (0..10)
+        .map(|i| {
+            (
+                Cow::Owned(format!("spark.some.config.key{}", i)),
+                Cow::Owned(format!("value-of-config-{}", i)),
+            )
+        })
+        .collect();
+    let engines: Vec<_> = (0..ENGINES)
+        .map(|i| {
+            EngineInfo::new(
+                format!("engine-{}", i),
+                EngineType::Spark,
+                "3.5.4".to_owned(),
+                EngineState::Running,
+                config.clone(),
+                EngineTime::now(),
+            )
+        })
+        .collect();
+    serde_json::to_string(&engines).unwrap()
+}
+
+fn deserialize_engines(c: &mut Criterion) {
+    let records = engine_records();
+    let mut group = c.benchmark_group("deserialize 10k engines");
+    group.bench_function("EngineInfo", |b| {
+        b.iter(|| serde_json::from_str::<Vec<EngineInfo>>(black_box(&records)).unwrap())
+    });
+    group.bench_function("EngineInfoSummary", |b| {
+        b.iter(|| serde_json::from_str::<Vec<EngineInfoSummary>>(black_box(&records)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, deserialize_engines);
+criterion_main!(benches);
@@ -1,4 +1,46 @@
+use std::{
+    env,
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "grpc")]
     tonic_build::compile_protos("proto/client.proto")?;
+    embed_build_info()?;
+    Ok(())
+}
+
+/// Run git in the crate directory, `None` if git or the repository is not available,
+/// e.g. when building the crate downloaded from crates.io.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout)
+        .ok()
+        .map(|s| s.trim().to_owned())
+}
+
+/// Embed the git commit and the build time, read by `rucat_common::build_info`.
+fn embed_build_info() -> Result<(), Box<dyn std::error::Error>> {
+    let git_commit = git(&["rev-parse", "HEAD"]).unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=RUCAT_GIT_COMMIT={}", git_commit);
+    // Rebuild on new commits, the build time is only updated then.
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]) {
+        let git_dir = Path::new(&git_dir);
+        println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+        println!("cargo:rerun-if-changed={}", git_dir.join("refs").display());
+    }
+
+    // Reproducible builds set the build time, see <https://reproducible-builds.org/specs/source-date-epoch/>.
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    let build_timestamp = match env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch.parse::<u64>()?,
+        Err(_) => SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    };
+    println!("cargo:rustc-env=RUCAT_BUILD_TIMESTAMP={}", build_timestamp);
     Ok(())
 }
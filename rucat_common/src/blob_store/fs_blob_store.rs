@@ -0,0 +1,264 @@
+//! Blob store on the local file system.
+
+use ::std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use ::anyhow::anyhow;
+use ::tokio::{
+    fs::{self, File},
+    io::{AsyncRead, AsyncWrite, BufWriter},
+};
+
+use super::{copy_with_checksum, validate_key, verify_blob, BlobRef, BlobStore};
+use crate::error::{Result, RucatError};
+
+/// Blob store saving each blob as a file under the `root` directory, using the key as the relative path.
+pub struct FsBlobStore {
+    root: PathBuf,
+    /// Counter to generate unique names for the temporary files.
+    tmp_file_counter: AtomicU64,
+}
+
+impl FsBlobStore {
+    /// Directory under `root` for the blobs being written.
+    /// Blobs are written into temporary files first, and then renamed to their keys
+    /// so that readers never see partially written blobs.
+    const TMP_DIR: &'static str = ".tmp";
+
+    /// Create a new [FsBlobStore], the `root` directory is created if it does not exist.
+    pub async fn new(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(root.join(Self::TMP_DIR))
+            .await
+            .map_err(RucatError::fail_to_write_blob)?;
+        Ok(Self {
+            root,
+            tmp_file_counter: AtomicU64::new(0),
+        })
+    }
+
+    fn get_path(&self, key: &str) -> Result<PathBuf> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+
+    fn get_tmp_path(&self) -> PathBuf {
+        let n = self.tmp_file_counter.fetch_add(1, Ordering::Relaxed);
+        self.root
+            .join(Self::TMP_DIR)
+            .join(format!("{}-{}", std::process::id(), n))
+    }
+
+    async fn write_tmp_file<R>(path: &Path, reader: R) -> std::io::Result<(u64, String)>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let file = File::create(path).await?;
+        let mut writer = BufWriter::new(file);
+        let result = copy_with_checksum(reader, &mut writer).await?;
+        writer.into_inner().sync_all().await?;
+        Ok(result)
+    }
+}
+
+impl BlobStore for FsBlobStore {
+    async fn put<R>(&self, key: &str, reader: R) -> Result<BlobRef>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let path = self.get_path(key)?;
+        let tmp_path = self.get_tmp_path();
+        let (size, checksum) = match Self::write_tmp_file(&tmp_path, reader).await {
+            Ok(result) => result,
+            Err(e) => {
+                // best effort to clean the partially written file
+                let _ = fs::remove_file(&tmp_path).await;
+                return Err(RucatError::fail_to_write_blob(e));
+            }
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(RucatError::fail_to_write_blob)?;
+        }
+        fs::rename(&tmp_path, &path)
+            .await
+            .map_err(RucatError::fail_to_write_blob)?;
+        Ok(BlobRef {
+            key: key.to_owned(),
+            size,
+            checksum,
+        })
+    }
+
+    async fn get<W>(&self, blob: &BlobRef, writer: W) -> Result<()>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let path = self.get_path(&blob.key)?;
+        let file = File::open(&path).await.map_err(|e| {
+            if e.kind() == ErrorKind::NotFound {
+                RucatError::not_found(anyhow!("Blob {} not found", blob.key))
+            } else {
+                RucatError::fail_to_read_blob(e)
+            }
+        })?;
+        let (size, checksum) = copy_with_checksum(file, writer)
+            .await
+            .map_err(RucatError::fail_to_read_blob)?;
+        verify_blob(blob, size, &checksum)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.get_path(key)?;
+        match fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(RucatError::fail_to_delete_blob(e)),
+        }
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<()> {
+        // Keys are paths, so only prefixes of whole directories are supported on file system.
+        let path = self.get_path(prefix.trim_end_matches('/'))?;
+        match fs::remove_dir_all(&path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(RucatError::fail_to_delete_blob(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        blob_store::{delete_engine_blobs, engine_blob_key},
+        engine::EngineId,
+    };
+    use ::tempfile::TempDir;
+
+    async fn create_store() -> anyhow::Result<(TempDir, FsBlobStore)> {
+        let dir = TempDir::new()?;
+        let store = FsBlobStore::new(dir.path()).await?;
+        Ok((dir, store))
+    }
+
+    #[tokio::test]
+    async fn put_and_get_blob() -> anyhow::Result<()> {
+        let (_dir, store) = create_store().await?;
+        let blob = store.put("engines/abc/log_tail", &b"hello"[..]).await?;
+        assert_eq!(
+            blob,
+            BlobRef {
+                key: "engines/abc/log_tail".to_owned(),
+                size: 5,
+                checksum: "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+                    .to_owned()
+            }
+        );
+        let mut content = vec![];
+        store.get(&blob, &mut content).await?;
+        assert_eq!(content, b"hello");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn put_overwrites_existing_blob() -> anyhow::Result<()> {
+        let (_dir, store) = create_store().await?;
+        store.put("a/b", &b"hello"[..]).await?;
+        let blob = store.put("a/b", &b"world!"[..]).await?;
+        let mut content = vec![];
+        store.get(&blob, &mut content).await?;
+        assert_eq!(content, b"world!");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_corrupted_blob() -> anyhow::Result<()> {
+        let (dir, store) = create_store().await?;
+        let blob = store.put("a/b", &b"hello"[..]).await?;
+        fs::write(dir.path().join("a/b"), b"hellO").await?;
+        let result = store.get(&blob, &mut vec![]).await;
+        assert!(result.is_err_and(|e| e
+            .to_string()
+            .starts_with("Fail to read blob: Blob a/b is corrupted")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_truncated_blob() -> anyhow::Result<()> {
+        let (dir, store) = create_store().await?;
+        let blob = store.put("a/b", &b"hello"[..]).await?;
+        fs::write(dir.path().join("a/b"), b"hell").await?;
+        let result = store.get(&blob, &mut vec![]).await;
+        assert!(result.is_err_and(|e| e
+            .to_string()
+            .starts_with("Fail to read blob: Blob a/b is corrupted, expect size 5")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_nonexistent_blob() -> anyhow::Result<()> {
+        let (_dir, store) = create_store().await?;
+        let blob = BlobRef {
+            key: "a/b".to_owned(),
+            size: 0,
+            checksum: "".to_owned(),
+        };
+        let result = store.get(&blob, &mut vec![]).await;
+        assert!(result.is_err_and(|e| e.to_string().starts_with("Not found: Blob a/b not found")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn delete_blob() -> anyhow::Result<()> {
+        let (dir, store) = create_store().await?;
+        store.put("a/b", &b"hello"[..]).await?;
+        store.delete("a/b").await?;
+        assert!(!dir.path().join("a/b").exists());
+        // deleting again is fine
+        store.delete("a/b").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cascade_delete_engine_blobs() -> anyhow::Result<()> {
+        let (_dir, store) = create_store().await?;
+        let id1 = EngineId::try_from("1")?;
+        let id2 = EngineId::try_from("2")?;
+        let artifact = store
+            .put(&engine_blob_key(&id1, "artifact"), &b"artifact"[..])
+            .await?;
+        let log_tail = store
+            .put(&engine_blob_key(&id1, "logs/driver"), &b"log"[..])
+            .await?;
+        let other = store
+            .put(&engine_blob_key(&id2, "artifact"), &b"other"[..])
+            .await?;
+
+        delete_engine_blobs(&store, &id1).await?;
+
+        for blob in [artifact, log_tail] {
+            let result = store.get(&blob, &mut vec![]).await;
+            assert!(result.is_err_and(|e| e.to_string().starts_with("Not found")));
+        }
+        let mut content = vec![];
+        store.get(&other, &mut content).await?;
+        assert_eq!(content, b"other");
+        // deleting the blobs of an engine without blobs is fine
+        delete_engine_blobs(&store, &id1).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_key() -> anyhow::Result<()> {
+        let (_dir, store) = create_store().await?;
+        let result = store.put("../escape", &b"hello"[..]).await;
+        assert!(result.is_err_and(|e| e.to_string().starts_with("Not allowed: Invalid blob key")));
+        Ok(())
+    }
+}
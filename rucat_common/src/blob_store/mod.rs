@@ -0,0 +1,238 @@
+//! Storage for large blobs of engines, kept out of the metadata database.
+//!
+//! Only the references to the blobs are stored in [crate::engine::EngineInfo::blobs],
+//! and the blobs of an engine are deleted with the engine, see [delete_engine_blobs].
+
+pub mod fs_blob_store;
+#[cfg(feature = "s3")]
+pub mod s3_blob_store;
+
+use ::core::future::Future;
+
+use ::anyhow::anyhow;
+use ::serde::{Deserialize, Serialize};
+use ::sha2::{Digest, Sha256};
+use ::tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    config::BlobStoreVariant,
+    engine::EngineId,
+    error::{Result, RucatError},
+};
+use fs_blob_store::FsBlobStore;
+#[cfg(feature = "s3")]
+use s3_blob_store::S3BlobStore;
+
+/// Name of the blob of the archived tail of the engine driver logs.
+pub const LOG_TAIL_BLOB: &str = "log_tail";
+
+/// Reference to a stored blob, to read it back and verify its content by [BlobStore::get].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BlobRef {
+    /// Key of the blob in the blob store.
+    pub key: String,
+    /// Size of the blob in bytes.
+    pub size: u64,
+    /// Hex encoded SHA-256 checksum of the blob.
+    pub checksum: String,
+}
+
+/// Store for blobs, addressed by keys like `engines/<engine id>/<name>`.
+pub trait BlobStore: Send + Sync + 'static {
+    /// Stream the content of `reader` into the blob `key`. Existing blob with the same key is overwritten.
+    /// # Return
+    /// The reference to the stored blob.
+    fn put<R>(&self, key: &str, reader: R) -> impl Future<Output = Result<BlobRef>> + Send
+    where
+        R: AsyncRead + Unpin + Send;
+
+    /// Stream the content of the blob into `writer`.
+    /// The checksum and size of the content are verified against `blob` once all content is read,
+    /// an error is returned if they don't match.
+    fn get<W>(&self, blob: &BlobRef, writer: W) -> impl Future<Output = Result<()>> + Send
+    where
+        W: AsyncWrite + Unpin + Send;
+
+    /// Delete the blob `key`. Deleting a nonexistent blob is not an error.
+    fn delete(&self, key: &str) -> impl Future<Output = Result<()>> + Send;
+
+    /// Delete all blobs whose keys start with `prefix`.
+    fn delete_prefix(&self, prefix: &str) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Key prefix of all blobs belonging to the engine.
+pub fn engine_blob_prefix(id: &EngineId) -> String {
+    format!("engines/{}/", id)
+}
+
+/// Key of the blob `name` belonging to the engine.
+pub fn engine_blob_key(id: &EngineId, name: &str) -> String {
+    format!("{}{}", engine_blob_prefix(id), name)
+}
+
+/// Delete all blobs of the engine. This should be called when the engine is deleted.
+pub async fn delete_engine_blobs<BS: BlobStore>(blob_store: &BS, id: &EngineId) -> Result<()> {
+    blob_store.delete_prefix(&engine_blob_prefix(id)).await
+}
+
+/// Blob store created from [BlobStoreVariant].
+pub enum BlobStoreClient {
+    FileSystem(FsBlobStore),
+    #[cfg(feature = "s3")]
+    S3(S3BlobStore),
+}
+
+impl BlobStoreClient {
+    pub async fn new(variant: BlobStoreVariant) -> Result<Self> {
+        match variant {
+            BlobStoreVariant::FileSystem { root } => {
+                Ok(Self::FileSystem(FsBlobStore::new(root).await?))
+            }
+            #[cfg(feature = "s3")]
+            BlobStoreVariant::S3 {
+                bucket,
+                region,
+                endpoint,
+                credentials,
+            } => Ok(Self::S3(S3BlobStore::new(
+                bucket,
+                region,
+                endpoint,
+                credentials,
+            )?)),
+        }
+    }
+}
+
+impl BlobStore for BlobStoreClient {
+    async fn put<R>(&self, key: &str, reader: R) -> Result<BlobRef>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        match self {
+            Self::FileSystem(store) => store.put(key, reader).await,
+            #[cfg(feature = "s3")]
+            Self::S3(store) => store.put(key, reader).await,
+        }
+    }
+
+    async fn get<W>(&self, blob: &BlobRef, writer: W) -> Result<()>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        match self {
+            Self::FileSystem(store) => store.get(blob, writer).await,
+            #[cfg(feature = "s3")]
+            Self::S3(store) => store.get(blob, writer).await,
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        match self {
+            Self::FileSystem(store) => store.delete(key).await,
+            #[cfg(feature = "s3")]
+            Self::S3(store) => store.delete(key).await,
+        }
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<()> {
+        match self {
+            Self::FileSystem(store) => store.delete_prefix(prefix).await,
+            #[cfg(feature = "s3")]
+            Self::S3(store) => store.delete_prefix(prefix).await,
+        }
+    }
+}
+
+/// Keys are relative paths of non empty segments separated by `/`,
+/// segments `.` and `..` are not allowed so that keys cannot escape the store.
+fn validate_key(key: &str) -> Result<()> {
+    if key.is_empty()
+        || key
+            .split('/')
+            .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        Err(RucatError::not_allowed(anyhow!(
+            "Invalid blob key: {:?}",
+            key
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Copy all content from `reader` to `writer`.
+/// # Return
+/// The size and the hex encoded SHA-256 checksum of the content.
+async fn copy_with_checksum<R, W>(mut reader: R, mut writer: W) -> std::io::Result<(u64, String)>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut hasher = Sha256::new();
+    let mut size = 0;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buffer).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        writer.write_all(&buffer[..n]).await?;
+        size += n as u64;
+    }
+    writer.flush().await?;
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+/// Check the size and checksum of the read content against the reference.
+fn verify_blob(blob: &BlobRef, size: u64, checksum: &str) -> Result<()> {
+    if blob.size != size || blob.checksum != checksum {
+        Err(RucatError::fail_to_read_blob(anyhow!(
+            "Blob {} is corrupted, expect size {} and checksum {}, got size {} and checksum {}",
+            blob.key,
+            blob.size,
+            blob.checksum,
+            size,
+            checksum
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engine_blob_keys() -> Result<()> {
+        let id = EngineId::try_from("abc")?;
+        assert_eq!(engine_blob_prefix(&id), "engines/abc/");
+        assert_eq!(engine_blob_key(&id, "log_tail"), "engines/abc/log_tail");
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_keys() {
+        for key in ["", "/abc", "abc/", "a//b", "a/../b", "./a", ".."] {
+            assert!(validate_key(key)
+                .is_err_and(|e| e.to_string().starts_with("Not allowed: Invalid blob key")));
+        }
+        assert!(validate_key("engines/abc/log_tail").is_ok());
+    }
+
+    #[tokio::test]
+    async fn copy_with_checksum_of_empty_content() -> anyhow::Result<()> {
+        let mut output = vec![];
+        let (size, checksum) = copy_with_checksum(&b""[..], &mut output).await?;
+        assert_eq!(size, 0);
+        assert_eq!(
+            checksum,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert!(output.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+//! Blob store on any S3 compatible object storage.
+
+use ::std::sync::Arc;
+
+use ::anyhow::anyhow;
+use ::futures::{StreamExt, TryStreamExt};
+use ::object_store::{
+    aws::AmazonS3Builder,
+    buffered::{BufReader, BufWriter},
+    path::Path,
+    ObjectStore,
+};
+use ::tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+use super::{copy_with_checksum, validate_key, verify_blob, BlobRef, BlobStore};
+use crate::{
+    config::S3Credentials,
+    error::{Result, RucatError},
+};
+
+/// Blob store saving each blob as an object in the bucket, using the key as the object path.
+pub struct S3BlobStore {
+    store: Arc<dyn ObjectStore>,
+}
+
+impl S3BlobStore {
+    /// Create a new [S3BlobStore].
+    /// Options that are not set are loaded from the `AWS_*` environment variables.
+    pub fn new(
+        bucket: String,
+        region: Option<String>,
+        endpoint: Option<String>,
+        credentials: Option<S3Credentials>,
+    ) -> Result<Self> {
+        let mut builder = AmazonS3Builder::from_env().with_bucket_name(bucket);
+        if let Some(region) = region {
+            builder = builder.with_region(region);
+        }
+        if let Some(endpoint) = endpoint {
+            // S3 compatible storages deployed in the cluster are usually not behind TLS.
+            builder = builder
+                .with_allow_http(endpoint.starts_with("http://"))
+                .with_endpoint(endpoint);
+        }
+        if let Some(S3Credentials {
+            access_key_id,
+            secret_access_key,
+        }) = credentials
+        {
+            builder = builder
+                .with_access_key_id(access_key_id)
+                .with_secret_access_key(secret_access_key);
+        }
+        let store = builder.build().map_err(RucatError::fail_to_load_config)?;
+        Ok(Self {
+            store: Arc::new(store),
+        })
+    }
+
+    fn get_path(key: &str) -> Result<Path> {
+        validate_key(key)?;
+        Path::parse(key).map_err(|e| RucatError::not_allowed(anyhow!(e)))
+    }
+}
+
+impl BlobStore for S3BlobStore {
+    async fn put<R>(&self, key: &str, reader: R) -> Result<BlobRef>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let path = Self::get_path(key)?;
+        let mut writer = BufWriter::new(self.store.clone(), path);
+        let (size, checksum) = match copy_with_checksum(reader, &mut writer).await {
+            Ok(result) => result,
+            Err(e) => {
+                // best effort to abort the multipart upload
+                let _ = writer.abort().await;
+                return Err(RucatError::fail_to_write_blob(e));
+            }
+        };
+        // the object is only visible after the writer is shut down
+        writer
+            .shutdown()
+            .await
+            .map_err(RucatError::fail_to_write_blob)?;
+        Ok(BlobRef {
+            key: key.to_owned(),
+            size,
+            checksum,
+        })
+    }
+
+    async fn get<W>(&self, blob: &BlobRef, writer: W) -> Result<()>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let path = Self::get_path(&blob.key)?;
+        let meta = self.store.head(&path).await.map_err(|e| match e {
+            object_store::Error::NotFound { .. } => {
+                RucatError::not_found(anyhow!("Blob {} not found", blob.key))
+            }
+            e => RucatError::fail_to_read_blob(e),
+        })?;
+        let reader = BufReader::new(self.store.clone(), &meta);
+        let (size, checksum) = copy_with_checksum(reader, writer)
+            .await
+            .map_err(RucatError::fail_to_read_blob)?;
+        verify_blob(blob, size, &checksum)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = Self::get_path(key)?;
+        match self.store.delete(&path).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(RucatError::fail_to_delete_blob(e)),
+        }
+    }
+
+    async fn delete_prefix(&self, prefix: &str) -> Result<()> {
+        let prefix = Self::get_path(prefix.trim_end_matches('/'))?;
+        let locations = self
+            .store
+            .list(Some(&prefix))
+            .map_ok(|meta| meta.location)
+            .boxed();
+        self.store
+            .delete_stream(locations)
+            .try_collect::<Vec<_>>()
+            .await
+            .map_err(RucatError::fail_to_delete_blob)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+//! Version and build of the rucat binaries, embedded at compile time by the build script,
+//! e.g. to find out which version a running server is when debugging mismatched deployments.
+
+use ::std::{borrow::Cow, fmt::Display};
+
+use ::serde::{Deserialize, Serialize};
+use ::time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// Git commit of the build, `unknown` if it is not built in a git repository.
+pub const GIT_COMMIT: &str = env!("RUCAT_GIT_COMMIT");
+/// Seconds since the Unix epoch when the build script ran, or `SOURCE_DATE_EPOCH` if it is set.
+const BUILD_TIMESTAMP: &str = env!("RUCAT_BUILD_TIMESTAMP");
+
+/// Version and build of a rucat binary.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BuildInfo {
+    /// Version of the crate, e.g. `0.2.0`.
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub version: Cow<'static, str>,
+    /// Hash of the git commit.
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
+    pub git_commit: Cow<'static, str>,
+    /// Build time in RFC 3339, e.g. `2025-01-31T12:00:00Z`.
+    pub build_time: String,
+    /// Cargo features enabled in the build.
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<String>))]
+    pub features: Vec<Cow<'static, str>>,
+}
+
+impl BuildInfo {
+    /// Build info of the binary of `version`, usually `env!("CARGO_PKG_VERSION")` of the binary crate,
+    /// with its enabled cargo `features`.
+    pub fn new(version: &'static str, features: &[&'static str]) -> Self {
+        Self {
+            version: Cow::Borrowed(version),
+            git_commit: Cow::Borrowed(GIT_COMMIT),
+            build_time: get_build_time(),
+            features: features.iter().copied().map(Cow::Borrowed).collect(),
+        }
+    }
+}
+
+impl Display for BuildInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "version {}, git commit {}, built at {}, features {:?}",
+            self.version, self.git_commit, self.build_time, self.features
+        )
+    }
+}
+
+fn get_build_time() -> String {
+    BUILD_TIMESTAMP
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| OffsetDateTime::from_unix_timestamp(secs).ok())
+        .and_then(|time| time.format(&Rfc3339).ok())
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_info() -> anyhow::Result<()> {
+        let info = BuildInfo::new("1.2.3", &["metrics", "ui"]);
+        assert_eq!(info.version, "1.2.3");
+        assert_eq!(info.git_commit, GIT_COMMIT);
+        assert!(!info.git_commit.is_empty());
+        OffsetDateTime::parse(&info.build_time, &Rfc3339)?;
+        assert_eq!(info.features, ["metrics", "ui"]);
+        assert_eq!(
+            info.to_string(),
+            format!(
+                "version 1.2.3, git commit {}, built at {}, features [\"metrics\", \"ui\"]",
+                GIT_COMMIT, info.build_time
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn serialize_build_info() -> anyhow::Result<()> {
+        let info = BuildInfo::new("1.2.3", &[]);
+        let value = serde_json::to_value(&info)?;
+        assert_eq!(value["version"], "1.2.3");
+        assert_eq!(value["features"], serde_json::json!([]));
+        assert_eq!(serde_json::from_value::<BuildInfo>(value)?, info);
+        Ok(())
+    }
+}
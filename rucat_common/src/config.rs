@@ -1,24 +1,92 @@
 //! Configuration for rucat server and engine.
 
 use crate::error::{Result, RucatError};
-use clap::Parser;
+use anyhow::anyhow;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::from_reader;
 use std::{fs::File, io::BufReader};
 
-#[derive(Parser, Debug)]
+#[cfg(feature = "cli")]
+#[derive(clap::Parser, Debug)]
 #[command(version, about, long_about = None)]
 /// Command line arguments for rucat server.
 pub struct Args {
     /// path to the config file
     #[arg(long)]
     pub config_path: String,
+    /// migrate engines stored in the legacy database schema and exit
+    #[arg(long)]
+    pub migrate_legacy: bool,
+    /// only report what `--migrate-legacy` would do, without changing the database
+    #[arg(long, requires = "migrate_legacy")]
+    pub dry_run: bool,
 }
 
+#[cfg(feature = "cli")]
 impl Args {
     /// helper function for exporting the `clap::Parser::parse` function
     pub fn parse_args() -> Self {
-        Args::parse()
+        <Args as clap::Parser>::parse()
+    }
+}
+
+/// Secret in the config, either the literal value, `{"file": "<path>"}` or `{"env": "<variable>"}`,
+/// so that the secret does not have to be committed with the config.
+#[derive(Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum SecretValue {
+    Literal(String),
+    /// Content of the file, without the trailing newline, e.g. a mounted Kubernetes secret.
+    File {
+        file: String,
+    },
+    /// Value of the environment variable.
+    Env {
+        env: String,
+    },
+}
+
+impl SecretValue {
+    /// Read the secret from its source, which is done once on startup.
+    pub fn resolve(&self) -> Result<String> {
+        match self {
+            Self::Literal(value) => Ok(value.clone()),
+            Self::File { file } => {
+                let content = std::fs::read_to_string(file).map_err(|e| {
+                    RucatError::fail_to_load_config(anyhow!(
+                        "Fail to read the secret file `{}`: {}",
+                        file,
+                        e
+                    ))
+                })?;
+                let content = content.strip_suffix('\n').unwrap_or(&content);
+                Ok(content.strip_suffix('\r').unwrap_or(content).to_owned())
+            }
+            Self::Env { env } => std::env::var(env).map_err(|e| {
+                RucatError::fail_to_load_config(anyhow!(
+                    "Fail to read the secret from the environment variable `{}`: {}",
+                    env,
+                    e
+                ))
+            }),
+        }
+    }
+}
+
+impl From<&str> for SecretValue {
+    fn from(value: &str) -> Self {
+        Self::Literal(value.to_owned())
+    }
+}
+
+/// Literal secrets are not printed.
+impl std::fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Literal(_) => f.write_str("Literal(***)"),
+            Self::File { file } => f.debug_struct("File").field("file", file).finish(),
+            Self::Env { env } => f.debug_struct("Env").field("env", env).finish(),
+        }
     }
 }
 
@@ -27,16 +95,173 @@ impl Args {
 #[serde(deny_unknown_fields)]
 pub struct Credentials {
     pub username: String,
-    pub password: String,
+    pub password: SecretValue,
+}
+
+/// Retries of connecting to the database on startup,
+/// e.g. when the database is not ready yet during a cold start of the cluster.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ConnectRetry {
+    /// Attempts of connecting, including the first one.
+    #[serde(default = "ConnectRetry::default_max_attempts")]
+    pub max_attempts: u32,
+    /// Backoff in millisecond before the first retry, doubled before each of the following retries.
+    #[serde(default = "ConnectRetry::default_initial_backoff_millis")]
+    pub initial_backoff_millis: u64,
+    /// Maximum backoff in millisecond between two attempts.
+    #[serde(default = "ConnectRetry::default_max_backoff_millis")]
+    pub max_backoff_millis: u64,
+    /// Timeout in second of all attempts together,
+    /// after which connecting fails even if there are attempts left.
+    #[serde(default = "ConnectRetry::default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl ConnectRetry {
+    fn default_max_attempts() -> u32 {
+        10
+    }
+
+    fn default_initial_backoff_millis() -> u64 {
+        500
+    }
+
+    fn default_max_backoff_millis() -> u64 {
+        10_000
+    }
+
+    fn default_timeout_secs() -> u64 {
+        120
+    }
+}
+
+impl Default for ConnectRetry {
+    fn default() -> Self {
+        Self {
+            max_attempts: Self::default_max_attempts(),
+            initial_backoff_millis: Self::default_initial_backoff_millis(),
+            max_backoff_millis: Self::default_max_backoff_millis(),
+            timeout_secs: Self::default_timeout_secs(),
+        }
+    }
+}
+
+/// Config of SurrealDB
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SurrealConfig {
+    pub credentials: Option<Credentials>,
+    /// `host:port` of the database server.
+    pub uri: String,
+    /// Retries of connecting on startup, with the defaults of [ConnectRetry] if not set.
+    #[serde(default)]
+    pub connect_retry: ConnectRetry,
+    /// Namespace of the database, `rucat` by default.
+    /// Installations sharing a SurrealDB should use different namespaces or databases.
+    #[serde(default = "SurrealConfig::default_namespace")]
+    pub namespace: String,
+    /// Database in the namespace, `rucat` by default.
+    #[serde(default = "SurrealConfig::default_database")]
+    pub database: String,
+    /// Table of the engines in the database, `engines` by default.
+    /// The idempotency keys, the migration records and the engine history are in their own tables, whose names are fixed.
+    #[serde(default = "SurrealConfig::default_table")]
+    pub table: String,
+    /// Whether to connect by `wss://` instead of `ws://`.
+    #[serde(default)]
+    pub tls: bool,
+    /// PEM file of the CA certificate verifying the certificate of SurrealDB,
+    /// trusted in addition to the system CAs, e.g. a private CA. Needs `tls`.
+    pub ca_path: Option<String>,
+    /// Timeout in second of each query, after which the query fails with [RucatError::database_timeout].
+    #[serde(default = "SurrealConfig::default_query_timeout_secs")]
+    pub query_timeout_secs: u64,
+}
+
+impl SurrealConfig {
+    pub(crate) fn default_namespace() -> String {
+        "rucat".to_owned()
+    }
+
+    pub(crate) fn default_database() -> String {
+        "rucat".to_owned()
+    }
+
+    pub(crate) fn default_table() -> String {
+        "engines".to_owned()
+    }
+
+    pub(crate) fn default_query_timeout_secs() -> u64 {
+        30
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(deny_unknown_fields)]
 /// Database config
 pub enum DatabaseVariant {
-    Surreal {
+    Surreal(Box<SurrealConfig>),
+    /// PostgreSQL at `uri`, e.g. `postgres://localhost:5432/rucat`,
+    /// whose tables are created on startup if they do not exist.
+    #[cfg(feature = "postgres")]
+    Postgres {
+        uri: String,
+        /// Override the user and password in the `uri` if set.
         credentials: Option<Credentials>,
+        /// The maximum number of connections to the database.
+        #[serde(default = "DatabaseVariant::default_pool_size")]
+        pool_size: u32,
+    },
+    /// SQLite database file at `path` for single-node deployments, created with the tables on startup if it does not exist.
+    #[cfg(feature = "sqlite")]
+    Sqlite {
+        path: String,
+    },
+    /// Redis at `uri`, e.g. `redis://localhost:6379`, or `rediss://` with TLS.
+    /// Acknowledged updates can be lost depending on the persistence of Redis,
+    /// see [crate::database::redis_client] for the trade-offs.
+    #[cfg(feature = "redis")]
+    Redis {
         uri: String,
+        /// Override the user and password in the `uri` if set.
+        credentials: Option<Credentials>,
+    },
+    /// Engines in the memory of the process, see [crate::database::in_memory::InMemoryDatabase].
+    /// Only for tests and demos, as the engines are lost when the process exits,
+    /// and a server and a state monitor do not share the engines.
+    InMemory,
+}
+
+#[cfg(feature = "postgres")]
+impl DatabaseVariant {
+    fn default_pool_size() -> u32 {
+        10
+    }
+}
+
+/// Credentials for the S3 compatible object storage
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct S3Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+/// Blob store config
+pub enum BlobStoreVariant {
+    /// Store blobs under the `root` directory.
+    FileSystem { root: String },
+    /// Store blobs in an S3 compatible object storage.
+    /// Options that are not set are loaded from the `AWS_*` environment variables.
+    #[cfg(feature = "s3")]
+    S3 {
+        bucket: String,
+        region: Option<String>,
+        endpoint: Option<String>,
+        credentials: Option<S3Credentials>,
     },
 }
 
@@ -65,10 +290,17 @@ mod tests {
         let result = from_value::<DatabaseVariant>(config)?;
         assert_eq!(
             result,
-            DatabaseVariant::Surreal {
+            DatabaseVariant::Surreal(Box::new(SurrealConfig {
                 credentials: None,
-                uri: "".to_string()
-            }
+                uri: "".to_string(),
+                connect_retry: ConnectRetry::default(),
+                namespace: "rucat".to_owned(),
+                database: "rucat".to_owned(),
+                table: "engines".to_owned(),
+                tls: false,
+                ca_path: None,
+                query_timeout_secs: 30
+            }))
         );
         Ok(())
     }
@@ -98,8 +330,25 @@ mod tests {
         let result = from_value::<DatabaseVariant>(config);
         assert_eq!(
             result.unwrap_err().to_string(),
-            "unknown field `unknown_field`, expected `credentials` or `uri`"
+            "unknown field `unknown_field`, expected one of `credentials`, `uri`, `connect_retry`, `namespace`, `database`, `table`, `tls`, `ca_path`, `query_timeout_secs`"
+        );
+    }
+
+    #[test]
+    fn deserialize_file_system_blob_store_config() -> Result<()> {
+        let config = json!(
+            {
+                "FileSystem": { "root": "/var/lib/rucat/blobs" }
+            }
+        );
+        let result = from_value::<BlobStoreVariant>(config)?;
+        assert_eq!(
+            result,
+            BlobStoreVariant::FileSystem {
+                root: "/var/lib/rucat/blobs".to_string()
+            }
         );
+        Ok(())
     }
 
     #[test]
@@ -118,14 +367,275 @@ mod tests {
         let result = from_value::<DatabaseVariant>(config)?;
         assert_eq!(
             result,
-            DatabaseVariant::Surreal {
+            DatabaseVariant::Surreal(Box::new(SurrealConfig {
                 credentials: Some(Credentials {
                     username: "admin".to_string(),
-                    password: "pwd".to_string()
+                    password: "pwd".into()
+                }),
+                uri: "localhost:27017".to_string(),
+                connect_retry: ConnectRetry::default(),
+                namespace: "rucat".to_owned(),
+                database: "rucat".to_owned(),
+                table: "engines".to_owned(),
+                tls: false,
+                ca_path: None,
+                query_timeout_secs: 30
+            }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_connect_retry() -> Result<()> {
+        let config = json!(
+            {
+                "Surreal": {
+                    "credentials": null,
+                    "uri": "localhost:8000",
+                    "connect_retry": {"max_attempts": 3, "timeout_secs": 10}
+                }
+            }
+        );
+        let result = from_value::<DatabaseVariant>(config)?;
+        assert_eq!(
+            result,
+            DatabaseVariant::Surreal(Box::new(SurrealConfig {
+                credentials: None,
+                uri: "localhost:8000".to_string(),
+                connect_retry: ConnectRetry {
+                    max_attempts: 3,
+                    initial_backoff_millis: 500,
+                    max_backoff_millis: 10_000,
+                    timeout_secs: 10
+                },
+                namespace: "rucat".to_owned(),
+                database: "rucat".to_owned(),
+                table: "engines".to_owned(),
+                tls: false,
+                ca_path: None,
+                query_timeout_secs: 30
+            }))
+        );
+        let config =
+            json!({"Surreal": {"credentials": null, "uri": "", "connect_retry": {"attempts": 3}}});
+        assert!(from_value::<DatabaseVariant>(config).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_surreal_names() -> Result<()> {
+        let config = json!(
+            {
+                "Surreal": {
+                    "credentials": null,
+                    "uri": "localhost:8000",
+                    "namespace": "team_a",
+                    "database": "prod",
+                    "table": "spark_engines"
+                }
+            }
+        );
+        let result = from_value::<DatabaseVariant>(config)?;
+        assert_eq!(
+            result,
+            DatabaseVariant::Surreal(Box::new(SurrealConfig {
+                credentials: None,
+                uri: "localhost:8000".to_string(),
+                connect_retry: ConnectRetry::default(),
+                namespace: "team_a".to_owned(),
+                database: "prod".to_owned(),
+                table: "spark_engines".to_owned(),
+                tls: false,
+                ca_path: None,
+                query_timeout_secs: 30
+            }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_query_timeout() -> Result<()> {
+        let config = json!({"Surreal": {"credentials": null, "uri": "", "query_timeout_secs": 5}});
+        let DatabaseVariant::Surreal(config) = from_value::<DatabaseVariant>(config)? else {
+            panic!("expect SurrealDB config");
+        };
+        assert_eq!(config.query_timeout_secs, 5);
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn deserialize_sqlite_config() -> Result<()> {
+        let config = json!({"Sqlite": {"path": "/var/lib/rucat/rucat.db"}});
+        let result = from_value::<DatabaseVariant>(config)?;
+        assert_eq!(
+            result,
+            DatabaseVariant::Sqlite {
+                path: "/var/lib/rucat/rucat.db".to_owned()
+            }
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn deserialize_redis_config() -> Result<()> {
+        let config = json!({"Redis": {"uri": "redis://localhost:6379", "credentials": null}});
+        let result = from_value::<DatabaseVariant>(config)?;
+        assert_eq!(
+            result,
+            DatabaseVariant::Redis {
+                uri: "redis://localhost:6379".to_owned(),
+                credentials: None
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_in_memory_config() -> Result<()> {
+        let result = from_value::<DatabaseVariant>(json!("InMemory"))?;
+        assert_eq!(result, DatabaseVariant::InMemory);
+        Ok(())
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn deserialize_postgres_config() -> Result<()> {
+        let config = json!(
+            {
+                "Postgres": {
+                    "uri": "postgres://localhost:5432/rucat",
+                    "credentials": {
+                        "username": "rucat",
+                        "password": {"env": "RUCAT_DB_PASSWORD"}
+                    }
+                }
+            }
+        );
+        let result = from_value::<DatabaseVariant>(config)?;
+        assert_eq!(
+            result,
+            DatabaseVariant::Postgres {
+                uri: "postgres://localhost:5432/rucat".to_string(),
+                credentials: Some(Credentials {
+                    username: "rucat".to_string(),
+                    password: SecretValue::Env {
+                        env: "RUCAT_DB_PASSWORD".to_owned()
+                    }
                 }),
-                uri: "localhost:27017".to_string()
+                pool_size: 10
             }
         );
+
+        let config = json!({"Postgres": {"uri": "", "credentials": null, "pool_size": 2}});
+        assert!(matches!(
+            from_value::<DatabaseVariant>(config)?,
+            DatabaseVariant::Postgres { pool_size: 2, .. }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_secret_values() -> Result<()> {
+        for (config, expected) in [
+            (json!("pwd"), SecretValue::Literal("pwd".to_owned())),
+            (
+                json!({"file": "/var/run/secrets/pwd"}),
+                SecretValue::File {
+                    file: "/var/run/secrets/pwd".to_owned(),
+                },
+            ),
+            (
+                json!({"env": "RUCAT_PWD"}),
+                SecretValue::Env {
+                    env: "RUCAT_PWD".to_owned(),
+                },
+            ),
+        ] {
+            assert_eq!(from_value::<SecretValue>(config)?, expected);
+        }
+        assert!(from_value::<SecretValue>(json!({"path": "/pwd"})).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_literal_secret() -> Result<()> {
+        assert_eq!(SecretValue::from("pwd").resolve()?, "pwd");
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_secret_from_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        for (content, expected) in [
+            ("pwd", "pwd"),
+            ("pwd\n", "pwd"),
+            ("pwd\r\n", "pwd"),
+            ("pwd \n\n", "pwd \n"),
+        ] {
+            let file = dir.path().join("secret");
+            std::fs::write(&file, content)?;
+            let secret = SecretValue::File {
+                file: file.to_str().unwrap().to_owned(),
+            };
+            assert_eq!(secret.resolve()?, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_secret_from_missing_file() {
+        let secret = SecretValue::File {
+            file: "/not/exist/secret".to_owned(),
+        };
+        let error = secret.resolve().unwrap_err().to_string();
+        assert!(
+            error.starts_with(
+                "Fail to load config: Fail to read the secret file `/not/exist/secret`: "
+            ),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn resolve_secret_from_env() -> Result<()> {
+        // The variable is only used by this test, so that it does not race with the other tests.
+        std::env::set_var("RUCAT_TEST_RESOLVE_SECRET_FROM_ENV", "pwd");
+        let secret = SecretValue::Env {
+            env: "RUCAT_TEST_RESOLVE_SECRET_FROM_ENV".to_owned(),
+        };
+        assert_eq!(secret.resolve()?, "pwd");
         Ok(())
     }
+
+    #[test]
+    fn resolve_secret_from_missing_env() {
+        let secret = SecretValue::Env {
+            env: "RUCAT_TEST_NOT_SET".to_owned(),
+        };
+        let error = secret.resolve().unwrap_err().to_string();
+        assert!(
+            error.contains(
+                "Fail to load config: Fail to read the secret from the environment variable `RUCAT_TEST_NOT_SET`: environment variable not found"
+            ),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn literal_secrets_are_not_printed() {
+        assert_eq!(format!("{:?}", SecretValue::from("pwd")), "Literal(***)");
+        assert_eq!(
+            format!(
+                "{:?}",
+                SecretValue::Env {
+                    env: "RUCAT_PWD".to_owned()
+                }
+            ),
+            r#"Env { env: "RUCAT_PWD" }"#
+        );
+    }
 }
@@ -0,0 +1,1208 @@
+//! Database keeping the engines in the memory of the process, for tests and demos.
+
+use ::std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use ::anyhow::anyhow;
+use ::tokio::sync::RwLock;
+
+use super::{
+    get_last_error, in_project, in_states, of_types, Database, DatabaseHealth, EngineFilter,
+    EngineIdAndInfo, EngineInfoUpdate, EngineSort, IdempotencyKey, RemoveEngineResult,
+    TriggeredEngine, UpdateEngineStateResult, DEFAULT_EVENT_SOURCE,
+};
+use crate::{
+    engine::{
+        EngineActor, EngineEndpoints, EngineEvent, EngineId, EngineInfo, EngineState,
+        EngineStateKind, EngineSummary, EngineTime, EngineType,
+    },
+    error::{Result, RucatError},
+};
+
+/// Engine with the times only known by the database.
+#[derive(Clone)]
+struct StoredEngine {
+    info: EngineInfo,
+    /// See [Database::add_engine].
+    next_update_time: Option<SystemTime>,
+    /// The time when the engine was updated to the current state.
+    state_update_time: SystemTime,
+}
+
+/// State history of an engine, see [Database::get_engine_events].
+#[derive(Default)]
+struct EngineHistory {
+    /// Oldest first.
+    events: Vec<EngineEvent>,
+    /// The time when the engine was deleted, and its project.
+    deleted: Option<(SystemTime, Option<String>)>,
+}
+
+#[derive(Default)]
+struct Engines {
+    engines: BTreeMap<EngineId, StoredEngine>,
+    /// Idempotency keys of adding engines, with the added engines and the expire times.
+    idempotency_keys: BTreeMap<String, (EngineId, SystemTime)>,
+    /// Histories of the engines, including the deleted ones not purged yet.
+    histories: BTreeMap<EngineId, EngineHistory>,
+}
+
+impl Engines {
+    /// Remove the engine and mark its history as deleted at `deleted_time`.
+    fn remove(&mut self, id: &EngineId, deleted_time: SystemTime) -> Option<StoredEngine> {
+        let engine = self.engines.remove(id)?;
+        if let Some(history) = self.histories.get_mut(id) {
+            history.deleted = Some((deleted_time, engine.info.project.clone()));
+        }
+        Some(engine)
+    }
+
+    fn append_event(
+        &mut self,
+        id: &EngineId,
+        old_state: &EngineState,
+        new_state: &EngineState,
+        source: &str,
+        actor: Option<&EngineActor>,
+        timestamp: SystemTime,
+    ) {
+        self.histories
+            .entry(id.clone())
+            .or_default()
+            .events
+            .push(EngineEvent {
+                old_state: old_state.clone(),
+                new_state: new_state.clone(),
+                source: source.to_owned(),
+                actor: actor.map(|actor| actor.name.clone()),
+                impersonator: actor.and_then(|actor| actor.impersonator.clone()),
+                time: EngineTime::from_system_time(timestamp),
+            });
+    }
+
+    /// Ids of at most `limit` engines of `engine_types` that need to be updated at `now`,
+    /// see [Database::list_engines_need_update].
+    fn need_update(
+        &self,
+        engine_types: &[EngineType],
+        limit: usize,
+        now: SystemTime,
+    ) -> Vec<EngineId> {
+        // engines are iterated by id, so the ones with the same next update time stay sorted by id
+        let mut need_update = self
+            .engines
+            .iter()
+            .filter_map(|(id, engine)| {
+                engine
+                    .next_update_time
+                    .filter(|next_update_time| {
+                        *next_update_time < now && of_types(&engine.info, engine_types)
+                    })
+                    .map(|next_update_time| (next_update_time, id))
+            })
+            .collect::<Vec<_>>();
+        need_update.sort_by_key(|(next_update_time, _)| *next_update_time);
+        need_update
+            .into_iter()
+            .take(limit)
+            .map(|(_, id)| id.clone())
+            .collect()
+    }
+
+    fn get_id_and_info(&self, id: EngineId) -> EngineIdAndInfo {
+        let engine = &self.engines[&id];
+        EngineIdAndInfo {
+            info: engine.info.clone().into(),
+            state_update_time: Some(engine.state_update_time),
+            id,
+        }
+    }
+}
+
+/// [Database] keeping the engines in the memory of the process, without any dependency.
+/// The engines are lost when the process exits, and are not shared with other processes,
+/// so a server and a state monitor in different processes never see the engines of each other.
+/// Clones of the database share the same engines.
+#[derive(Clone)]
+pub struct InMemoryDatabase {
+    engines: Arc<RwLock<Engines>>,
+    /// Source of the events recorded by [Database::update_engine_state].
+    event_source: &'static str,
+}
+
+impl Default for InMemoryDatabase {
+    fn default() -> Self {
+        Self {
+            engines: Arc::default(),
+            event_source: DEFAULT_EVENT_SOURCE,
+        }
+    }
+}
+
+impl InMemoryDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the state changes of this client with `source`, e.g. `server`, see [EngineEvent::source].
+    pub fn with_event_source(mut self, source: &'static str) -> Self {
+        self.event_source = source;
+        self
+    }
+
+    /// Return an error if `expected_revision` is set but the engine is at another revision.
+    fn check_revision(
+        id: &EngineId,
+        info: &EngineInfo,
+        expected_revision: Option<u64>,
+    ) -> Result<()> {
+        match expected_revision {
+            Some(expected) if expected != info.revision => {
+                Err(RucatError::precondition_failed(anyhow!(
+                    "Engine {} is at revision {}, but revision {:?} is expected",
+                    id,
+                    info.revision,
+                    expected_revision
+                ))
+                .with_engine_id(id))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Return a page of the engines matching the `filter`, see [Database::list_engines].
+    async fn list_engine_page<T>(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+        sort: EngineSort,
+        f: impl Fn(&EngineId, &EngineInfo) -> T,
+    ) -> Vec<T> {
+        let engines = self.engines.read().await;
+        let mut page: Vec<_> = engines
+            .engines
+            .iter()
+            .filter(|(_, engine)| filter.matches(&engine.info))
+            .map(|(id, engine)| (id, &engine.info))
+            .collect();
+        sort.sort(&mut page);
+        page.into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(id, info)| f(id, info))
+            .collect()
+    }
+}
+
+impl Database for InMemoryDatabase {
+    async fn add_engine(
+        &self,
+        id: &EngineId,
+        info: EngineInfo,
+        next_update_time: Option<SystemTime>,
+        idempotency_key: Option<IdempotencyKey>,
+    ) -> Result<EngineId> {
+        let mut engines = self.engines.write().await;
+        let now = SystemTime::now();
+        if let Some(IdempotencyKey { key, .. }) = &idempotency_key {
+            engines
+                .idempotency_keys
+                .retain(|_, (_, expire_time)| *expire_time > now);
+            if let Some((added, _)) = engines.idempotency_keys.get(key) {
+                return Ok(added.clone());
+            }
+        }
+        if engines.engines.contains_key(id) {
+            return Err(RucatError::fail_to_update_database(anyhow!(
+                "Engine {} already exists",
+                id
+            )));
+        }
+        if let Some(IdempotencyKey { key, expire_time }) = idempotency_key {
+            engines
+                .idempotency_keys
+                .insert(key, (id.clone(), expire_time));
+        }
+        if let Some(history) = engines.histories.get_mut(id) {
+            history.deleted = None;
+        }
+        engines.engines.insert(
+            id.clone(),
+            StoredEngine {
+                info,
+                next_update_time,
+                state_update_time: now,
+            },
+        );
+        Ok(id.clone())
+    }
+
+    async fn remove_engine(
+        &self,
+        id: &EngineId,
+        current_state: &EngineState,
+        expected_revision: Option<u64>,
+        deleted_time: SystemTime,
+    ) -> Result<Option<RemoveEngineResult>> {
+        let mut engines = self.engines.write().await;
+        let Some(engine) = engines.engines.get(id) else {
+            return Ok(None);
+        };
+        Self::check_revision(id, &engine.info, expected_revision)?;
+        if &engine.info.state != current_state {
+            return Ok(Some(RemoveEngineResult::Fail {
+                current_state: engine.info.state.clone(),
+            }));
+        }
+        Ok(engines
+            .remove(id, deleted_time)
+            .map(|engine| RemoveEngineResult::Success(Box::new(engine.info))))
+    }
+
+    async fn update_engine_state(
+        &self,
+        id: &EngineId,
+        before: &EngineState,
+        after: &EngineState,
+        next_update_time: Option<SystemTime>,
+        expected_revision: Option<u64>,
+        actor: Option<&EngineActor>,
+    ) -> Result<Option<UpdateEngineStateResult>> {
+        let mut engines = self.engines.write().await;
+        let Some(engine) = engines.engines.get_mut(id) else {
+            return Ok(None);
+        };
+        Self::check_revision(id, &engine.info, expected_revision)?;
+        if &engine.info.state != before {
+            return Ok(Some(UpdateEngineStateResult::Fail {
+                current_state: engine.info.state.clone(),
+            }));
+        }
+        if let Some(last_error) = get_last_error(before, after) {
+            engine.info.last_error = Some(last_error.clone());
+        }
+        // only record the time and bump the revision when the state is changed, not when the engine is rechecked
+        let now = SystemTime::now();
+        if before != after {
+            engine.state_update_time = now;
+            engine.info.revision += 1;
+        }
+        engine.info.state = after.clone();
+        engine.next_update_time = next_update_time;
+        // appended after the engine is updated, which borrows the engines
+        if before != after {
+            engines.append_event(id, before, after, self.event_source, actor, now);
+        }
+        Ok(Some(UpdateEngineStateResult::Success))
+    }
+
+    async fn update_engine_info(
+        &self,
+        id: &EngineId,
+        update: EngineInfoUpdate,
+        expected_states: Option<&[EngineState]>,
+        expected_revision: Option<u64>,
+    ) -> Result<Option<UpdateEngineStateResult>> {
+        let mut engines = self.engines.write().await;
+        let Some(engine) = engines.engines.get_mut(id) else {
+            return Ok(None);
+        };
+        Self::check_revision(id, &engine.info, expected_revision)?;
+        if !in_states(&engine.info, expected_states) {
+            return Ok(Some(UpdateEngineStateResult::Fail {
+                current_state: engine.info.state.clone(),
+            }));
+        }
+        update.apply(&mut engine.info);
+        engine.info.revision += 1;
+        Ok(Some(UpdateEngineStateResult::Success))
+    }
+
+    async fn set_engine_acl(
+        &self,
+        id: &EngineId,
+        allowed_principals: BTreeSet<String>,
+        expected_revision: Option<u64>,
+    ) -> Result<Option<()>> {
+        let mut engines = self.engines.write().await;
+        let Some(engine) = engines.engines.get_mut(id) else {
+            return Ok(None);
+        };
+        Self::check_revision(id, &engine.info, expected_revision)?;
+        engine.info.allowed_principals = Some(allowed_principals);
+        engine.info.revision += 1;
+        Ok(Some(()))
+    }
+
+    async fn update_engine_endpoints(
+        &self,
+        id: &EngineId,
+        endpoints: EngineEndpoints,
+    ) -> Result<Option<()>> {
+        let mut engines = self.engines.write().await;
+        let Some(engine) = engines.engines.get_mut(id) else {
+            return Ok(None);
+        };
+        engine.info.endpoints.extend(endpoints);
+        engine.info.revision += 1;
+        Ok(Some(()))
+    }
+
+    async fn get_engine(&self, id: &EngineId) -> Result<Option<EngineInfo>> {
+        let engines = self.engines.read().await;
+        Ok(engines.engines.get(id).map(|engine| engine.info.clone()))
+    }
+
+    async fn get_engines(&self, ids: &[EngineId]) -> Result<Vec<(EngineId, Option<EngineInfo>)>> {
+        let engines = self.engines.read().await;
+        Ok(ids
+            .iter()
+            .map(|id| {
+                let info = engines.engines.get(id).map(|engine| engine.info.clone());
+                (id.clone(), info)
+            })
+            .collect())
+    }
+
+    async fn get_engine_state(&self, id: &EngineId) -> Result<Option<EngineState>> {
+        let engines = self.engines.read().await;
+        Ok(engines
+            .engines
+            .get(id)
+            .map(|engine| engine.info.state.clone()))
+    }
+
+    async fn list_engines(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+        sort: EngineSort,
+    ) -> Result<Vec<EngineId>> {
+        Ok(self
+            .list_engine_page(offset, limit, filter, sort, |id, _| id.clone())
+            .await)
+    }
+
+    async fn list_engine_summaries(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+        sort: EngineSort,
+    ) -> Result<Vec<EngineSummary>> {
+        Ok(self
+            .list_engine_page(offset, limit, filter, sort, |id, info| EngineSummary {
+                id: id.clone(),
+                name: info.name.clone(),
+                engine_type: info.engine_type.clone(),
+                state: info.state.clone(),
+                create_time: info.get_create_time().clone(),
+            })
+            .await)
+    }
+
+    async fn list_engines_need_update(
+        &self,
+        engine_types: &[EngineType],
+        limit: usize,
+    ) -> Result<Vec<EngineIdAndInfo>> {
+        let engines = self.engines.read().await;
+        Ok(engines
+            .need_update(engine_types, limit, SystemTime::now())
+            .into_iter()
+            .map(|id| engines.get_id_and_info(id))
+            .collect())
+    }
+
+    async fn claim_engines_need_update(
+        &self,
+        engine_types: &[EngineType],
+        limit: usize,
+        now: SystemTime,
+        claim_duration: Duration,
+    ) -> Result<Vec<EngineIdAndInfo>> {
+        let mut engines = self.engines.write().await;
+        let ids = engines.need_update(engine_types, limit, now);
+        for id in &ids {
+            if let Some(engine) = engines.engines.get_mut(id) {
+                engine.next_update_time = Some(now + claim_duration);
+            }
+        }
+        Ok(ids
+            .into_iter()
+            .map(|id| engines.get_id_and_info(id))
+            .collect())
+    }
+
+    async fn count_engines_by_state(
+        &self,
+        engine_types: &[EngineType],
+        project: Option<&str>,
+    ) -> Result<BTreeMap<EngineStateKind, usize>> {
+        let engines = self.engines.read().await;
+        let mut counts = BTreeMap::new();
+        for engine in engines.engines.values().filter(|engine| {
+            of_types(&engine.info, engine_types) && in_project(&engine.info, project)
+        }) {
+            *counts
+                .entry(EngineStateKind::from(&engine.info.state))
+                .or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+
+    async fn remove_engines_in_states(
+        &self,
+        states: &BTreeSet<EngineStateKind>,
+        older_than: SystemTime,
+        project: Option<&str>,
+        deleted_time: SystemTime,
+    ) -> Result<Vec<EngineId>> {
+        let mut engines = self.engines.write().await;
+        let removed: Vec<EngineId> = engines
+            .engines
+            .iter()
+            .filter(|(_, engine)| {
+                states.contains(&EngineStateKind::from(&engine.info.state))
+                    && engine.state_update_time < older_than
+                    && in_project(&engine.info, project)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &removed {
+            engines.remove(id, deleted_time);
+        }
+        Ok(removed)
+    }
+
+    async fn list_triggered_engines(&self) -> Result<Vec<TriggeredEngine>> {
+        let engines = self.engines.read().await;
+        // Engines in Trigger* states always have next_update_time set to the trigger timeout.
+        Ok(engines
+            .engines
+            .iter()
+            .filter(|(_, engine)| {
+                matches!(
+                    engine.info.state,
+                    EngineState::TriggerStart
+                        | EngineState::TriggerTermination
+                        | EngineState::ErrorTriggerClean(_)
+                )
+            })
+            .filter_map(|(id, engine)| {
+                Some(TriggeredEngine {
+                    id: id.clone(),
+                    info: engine.info.clone(),
+                    state_update_time: Some(engine.state_update_time),
+                    trigger_timeout_time: engine.next_update_time?,
+                })
+            })
+            .collect())
+    }
+
+    async fn append_engine_event(
+        &self,
+        id: &EngineId,
+        old_state: &EngineState,
+        new_state: &EngineState,
+        source: &str,
+        timestamp: SystemTime,
+    ) -> Result<()> {
+        let mut engines = self.engines.write().await;
+        engines.append_event(id, old_state, new_state, source, None, timestamp);
+        Ok(())
+    }
+
+    async fn get_engine_events(&self, id: &EngineId, limit: usize) -> Result<Vec<EngineEvent>> {
+        let engines = self.engines.read().await;
+        Ok(engines.histories.get(id).map_or_else(Vec::new, |history| {
+            history.events.iter().rev().take(limit).cloned().collect()
+        }))
+    }
+
+    async fn purge_engine_events(
+        &self,
+        deleted_before: SystemTime,
+        project: Option<&str>,
+    ) -> Result<usize> {
+        let mut engines = self.engines.write().await;
+        let mut purged = 0;
+        engines.histories.retain(|_, history| {
+            let retained = history
+                .deleted
+                .as_ref()
+                .is_none_or(|(deleted_time, engine_project)| {
+                    *deleted_time >= deleted_before
+                        || project.is_some_and(|project| engine_project.as_deref() != Some(project))
+                });
+            if !retained {
+                purged += history.events.len();
+            }
+            retained
+        });
+        Ok(purged)
+    }
+
+    async fn health_check(&self) -> Result<DatabaseHealth> {
+        Ok(DatabaseHealth {
+            latency: Duration::ZERO,
+            version: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blob_store::BlobRef;
+    use crate::database::EngineSortKey;
+    use crate::engine::{CreateEngineRequest, EngineConfig, IdGenerator, RandomIdGenerator};
+    use crate::error::RucatErrorType;
+    use ::std::{
+        borrow::Cow,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    async fn add_spark_engine_with_name(db: &InMemoryDatabase, name: &str) -> Result<EngineId> {
+        let request = CreateEngineRequest {
+            name: name.to_owned(),
+            engine_type: EngineType::Spark,
+            version: "3.5.4".to_owned(),
+            config: None,
+            start_in_progress_timeout_secs: None,
+            features: None,
+            start: None,
+        };
+        let id = RandomIdGenerator.generate();
+        let info = EngineInfo::from_create_request(request, EngineTime::now())?;
+        db.add_engine(&id, info, Some(UNIX_EPOCH), None).await?;
+        Ok(id)
+    }
+
+    async fn add_spark_engine(db: &InMemoryDatabase) -> Result<EngineId> {
+        add_spark_engine_with_name(db, "spark").await
+    }
+
+    #[tokio::test]
+    async fn add_engine_with_existing_id() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let id = add_spark_engine(&db).await?;
+        let info = db.get_engine(&id).await?.unwrap();
+
+        let result = db.add_engine(&id, info, None, None).await;
+        assert!(result.is_err_and(|e| e.get_error_type() == &RucatErrorType::FailToUpdateDatabase));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_engine_with_idempotency_key() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let first = add_spark_engine(&db).await?;
+        let info = db.get_engine(&first).await?.unwrap();
+        let key = |expire_time| {
+            Some(IdempotencyKey {
+                key: "key".to_owned(),
+                expire_time,
+            })
+        };
+        let later = SystemTime::now() + Duration::from_secs(60);
+
+        let added = db.add_engine(&first, info.clone(), None, key(later)).await;
+        // the engine is not added, so the key is not recorded
+        assert!(added.is_err());
+        let second = RandomIdGenerator.generate();
+        assert_eq!(
+            db.add_engine(&second, info.clone(), None, key(later))
+                .await?,
+            second
+        );
+        let third = RandomIdGenerator.generate();
+        assert_eq!(
+            db.add_engine(&third, info.clone(), None, key(later))
+                .await?,
+            second
+        );
+        assert_eq!(db.get_engine(&third).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_engine_with_expired_idempotency_key() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let first = add_spark_engine(&db).await?;
+        let info = db.get_engine(&first).await?.unwrap();
+        let expired = Some(IdempotencyKey {
+            key: "key".to_owned(),
+            expire_time: UNIX_EPOCH,
+        });
+
+        let second = RandomIdGenerator.generate();
+        db.add_engine(&second, info.clone(), None, expired.clone())
+            .await?;
+        let third = RandomIdGenerator.generate();
+        assert_eq!(db.add_engine(&third, info, None, expired).await?, third);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_engines_in_order() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let etl = add_spark_engine_with_name(&db, "etl").await?;
+        let ml = add_spark_engine_with_name(&db, "ml").await?;
+        let nonexistent = EngineId::try_from("nonexistent")?;
+
+        let engines = db
+            .get_engines(&[ml.clone(), nonexistent.clone(), etl.clone()])
+            .await?;
+        let names = engines
+            .iter()
+            .map(|(id, info)| (id, info.as_ref().map(|info| info.name.as_str())))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [(&ml, Some("ml")), (&nonexistent, None), (&etl, Some("etl"))]
+        );
+        assert!(db.get_engines(&[]).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_state_changes_as_events() -> Result<()> {
+        let db = InMemoryDatabase::new().with_event_source("server");
+        let id = add_spark_engine(&db).await?;
+        let states = [
+            EngineState::WaitToStart,
+            EngineState::TriggerStart,
+            EngineState::Running,
+        ];
+        for pair in states.windows(2) {
+            db.update_engine_state(&id, &pair[0], &pair[1], None, None, None)
+                .await?;
+        }
+        // a recheck without changing the state is not an event
+        db.update_engine_state(
+            &id,
+            &EngineState::Running,
+            &EngineState::Running,
+            None,
+            None,
+            None,
+        )
+        .await?;
+
+        let events = db.get_engine_events(&id, 10).await?;
+        let transitions = events
+            .iter()
+            .map(|event| (&event.old_state, &event.new_state, event.source.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            transitions,
+            [
+                (&states[1], &states[2], "server"),
+                (&states[0], &states[1], "server"),
+            ]
+        );
+        assert_eq!(db.get_engine_events(&id, 1).await?, events[..1]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_actor_of_state_changes() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let id = add_spark_engine(&db).await?;
+        let actor = EngineActor {
+            name: "alice".to_owned(),
+            impersonator: Some("admin".to_owned()),
+        };
+        db.update_engine_state(
+            &id,
+            &EngineState::WaitToStart,
+            &EngineState::TriggerStart,
+            None,
+            None,
+            Some(&actor),
+        )
+        .await?;
+        db.update_engine_state(
+            &id,
+            &EngineState::TriggerStart,
+            &EngineState::StartInProgress,
+            None,
+            None,
+            None,
+        )
+        .await?;
+
+        let events = db.get_engine_events(&id, 10).await?;
+        let actors = events
+            .iter()
+            .map(|event| (event.actor.as_deref(), event.impersonator.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(actors, [(None, None), (Some("alice"), Some("admin"))]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn purge_events_of_deleted_engines() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let kept = add_spark_engine(&db).await?;
+        let removed = add_spark_engine(&db).await?;
+        for id in [&kept, &removed] {
+            db.update_engine_state(
+                id,
+                &EngineState::WaitToStart,
+                &EngineState::Terminated,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        }
+        let deleted_time = UNIX_EPOCH + Duration::from_secs(60);
+        db.remove_engine(&removed, &EngineState::Terminated, None, deleted_time)
+            .await?;
+
+        // the history is kept for the ttl from the deletion time
+        assert_eq!(db.purge_engine_events(deleted_time, None).await?, 0);
+        assert_eq!(db.get_engine_events(&removed, 10).await?.len(), 1);
+
+        // the removed engine is not in the project
+        let after = deleted_time + Duration::from_secs(1);
+        assert_eq!(db.purge_engine_events(after, Some("project")).await?, 0);
+        assert_eq!(db.get_engine_events(&removed, 10).await?.len(), 1);
+
+        assert_eq!(db.purge_engine_events(after, None).await?, 1);
+        assert!(db.get_engine_events(&removed, 10).await?.is_empty());
+        assert_eq!(db.get_engine_events(&kept, 10).await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_engine_state() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let id = add_spark_engine(&db).await?;
+
+        let result = db
+            .update_engine_state(
+                &id,
+                &EngineState::Running,
+                &EngineState::Terminated,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        assert!(matches!(
+            result,
+            Some(UpdateEngineStateResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+
+        let result = db
+            .update_engine_state(
+                &id,
+                &EngineState::WaitToStart,
+                &EngineState::TriggerStart,
+                None,
+                Some(0),
+                None,
+            )
+            .await?;
+        assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+        let info = db.get_engine(&id).await?.unwrap();
+        assert_eq!(info.state, EngineState::TriggerStart);
+        assert_eq!(info.revision, 1);
+        assert!(db.list_engines_need_update(&[], 100).await?.is_empty());
+
+        // rechecking the engine does not bump the revision
+        db.update_engine_state(
+            &id,
+            &EngineState::TriggerStart,
+            &EngineState::TriggerStart,
+            None,
+            Some(1),
+            None,
+        )
+        .await?;
+        assert_eq!(db.get_engine(&id).await?.unwrap().revision, 1);
+
+        let nonexistent = RandomIdGenerator.generate();
+        assert!(db
+            .update_engine_state(
+                &nonexistent,
+                &EngineState::WaitToStart,
+                &EngineState::TriggerStart,
+                None,
+                None,
+                None
+            )
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_engine_at_unexpected_revision() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let id = add_spark_engine(&db).await?;
+
+        let result = db
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    name: Some("renamed".to_owned()),
+                    ..Default::default()
+                },
+                None,
+                Some(1),
+            )
+            .await;
+        assert!(result.is_err_and(|e| {
+            e.get_error_type() == &RucatErrorType::PreconditionFailed
+                && e.to_string().contains(&format!(
+                    "Engine {} is at revision 0, but revision Some(1) is expected",
+                    id
+                ))
+        }));
+        let result = db
+            .remove_engine(&id, &EngineState::WaitToStart, Some(1), UNIX_EPOCH)
+            .await;
+        assert!(result.is_err_and(|e| e.get_error_type() == &RucatErrorType::PreconditionFailed));
+
+        db.update_engine_info(
+            &id,
+            EngineInfoUpdate {
+                name: Some("renamed".to_owned()),
+                ..Default::default()
+            },
+            None,
+            Some(0),
+        )
+        .await?;
+        let info = db.get_engine(&id).await?.unwrap();
+        assert_eq!(info.name, "renamed");
+        assert_eq!(info.revision, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_name_and_config_in_expected_states() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let id = add_spark_engine(&db).await?;
+        let update = EngineInfoUpdate {
+            name: Some("renamed".to_owned()),
+            config: Some(EngineConfig::from([(
+                "spark.executor.instances".into(),
+                "2".into(),
+            )])),
+            blobs: Some(BTreeMap::from([(
+                "log_tail".to_owned(),
+                BlobRef {
+                    key: "engines/log_tail".to_owned(),
+                    size: 3,
+                    checksum: "abc".to_owned(),
+                },
+            )])),
+        };
+
+        let result = db
+            .update_engine_info(&id, update.clone(), Some(&[EngineState::Terminated]), None)
+            .await?;
+        assert!(matches!(
+            result,
+            Some(UpdateEngineStateResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+        assert_eq!(db.get_engine(&id).await?.unwrap().revision, 0);
+
+        let result = db
+            .update_engine_info(&id, update, Some(&[EngineState::WaitToStart]), Some(0))
+            .await?;
+        assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+        let info = db.get_engine(&id).await?.unwrap();
+        assert_eq!(info.name, "renamed");
+        assert_eq!(
+            info.config.get("spark.executor.instances"),
+            Some(&Cow::Borrowed("2"))
+        );
+        assert_eq!(info.blobs["log_tail"].size, 3);
+        assert_eq!(info.revision, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn return_removed_engine() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let id = add_spark_engine(&db).await?;
+        let info = db.get_engine(&id).await?.unwrap();
+        let result = db
+            .remove_engine(&id, &EngineState::Running, None, UNIX_EPOCH)
+            .await?;
+        assert!(matches!(
+            result,
+            Some(RemoveEngineResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+        let result = db
+            .remove_engine(&id, &EngineState::WaitToStart, None, UNIX_EPOCH)
+            .await?;
+        assert!(matches!(result, Some(RemoveEngineResult::Success(removed)) if *removed == info));
+        assert!(db
+            .remove_engine(&id, &EngineState::WaitToStart, None, UNIX_EPOCH)
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keep_last_error_when_leaving_error_state() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let id = add_spark_engine(&db).await?;
+        let error = EngineState::ErrorClean(Cow::Borrowed("fail to start"));
+        db.update_engine_state(&id, &EngineState::WaitToStart, &error, None, None, None)
+            .await?;
+        assert_eq!(db.get_engine(&id).await?.unwrap().last_error, None);
+
+        db.update_engine_state(&id, &error, &EngineState::WaitToStart, None, None, None)
+            .await?;
+        assert_eq!(
+            db.get_engine(&id).await?.unwrap().last_error,
+            Some(Cow::Borrowed("fail to start"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_engine_config_in_expected_states() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let id = add_spark_engine(&db).await?;
+        let config = EngineConfig::from([("spark.executor.instances".into(), "2".into())]);
+
+        let result = db
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    config: Some(config.clone()),
+                    ..Default::default()
+                },
+                Some(&[EngineState::Terminated]),
+                None,
+            )
+            .await?;
+        assert!(matches!(
+            result,
+            Some(UpdateEngineStateResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+
+        let result = db
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    config: Some(config.clone()),
+                    ..Default::default()
+                },
+                Some(&[EngineState::WaitToStart]),
+                None,
+            )
+            .await?;
+        assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+        let info = db.get_engine(&id).await?.unwrap();
+        assert_eq!(info.config, config);
+        assert_eq!(info.revision, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_engines_filtered_and_sorted() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let etl = add_spark_engine_with_name(&db, "Nightly ETL").await?;
+        let etl_test = add_spark_engine_with_name(&db, "etl-test").await?;
+        let adhoc = add_spark_engine_with_name(&db, "adhoc").await?;
+        db.update_engine_state(
+            &adhoc,
+            &EngineState::WaitToStart,
+            &EngineState::Terminated,
+            None,
+            None,
+            None,
+        )
+        .await?;
+
+        let filter = EngineFilter {
+            name_contains: "etl".to_owned(),
+            ..EngineFilter::default()
+        };
+        let by_name = EngineSort {
+            key: EngineSortKey::Name,
+            descending: true,
+        };
+        assert_eq!(
+            db.list_engines(0, 10, &filter, by_name).await?,
+            vec![etl_test.clone(), etl.clone()]
+        );
+        assert_eq!(
+            db.list_engines(1, 10, &filter, by_name).await?,
+            vec![etl.clone()]
+        );
+
+        let by_state = EngineSort {
+            key: EngineSortKey::State,
+            descending: false,
+        };
+        let summaries = db
+            .list_engine_summaries(0, 1, &EngineFilter::default(), by_state)
+            .await?;
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].id, adhoc);
+        assert_eq!(summaries[0].state, EngineState::Terminated);
+
+        let terminated = EngineFilter {
+            states: BTreeSet::from([EngineStateKind::Terminated]),
+            ..EngineFilter::default()
+        };
+        assert_eq!(
+            db.list_engines(0, 10, &terminated, EngineSort::default())
+                .await?,
+            vec![adhoc]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn count_and_remove_engines_in_states() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let running = add_spark_engine(&db).await?;
+        add_spark_engine(&db).await?;
+        db.update_engine_state(
+            &running,
+            &EngineState::WaitToStart,
+            &EngineState::Running,
+            None,
+            None,
+            None,
+        )
+        .await?;
+
+        assert_eq!(
+            db.count_engines_by_state(&[], None).await?,
+            BTreeMap::from([
+                (EngineStateKind::WaitToStart, 1),
+                (EngineStateKind::Running, 1)
+            ])
+        );
+        let states = BTreeSet::from([EngineStateKind::Running]);
+        assert!(db
+            .remove_engines_in_states(&states, UNIX_EPOCH, None, UNIX_EPOCH)
+            .await?
+            .is_empty());
+        let later = SystemTime::now() + Duration::from_secs(1);
+        assert_eq!(
+            db.remove_engines_in_states(&states, later, None, UNIX_EPOCH)
+                .await?,
+            vec![running.clone()]
+        );
+        assert_eq!(db.get_engine(&running).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_triggered_engines() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let triggered = add_spark_engine(&db).await?;
+        add_spark_engine(&db).await?;
+        let timeout = SystemTime::now() + Duration::from_secs(60);
+        db.update_engine_state(
+            &triggered,
+            &EngineState::WaitToStart,
+            &EngineState::TriggerStart,
+            Some(timeout),
+            None,
+            None,
+        )
+        .await?;
+
+        let engines = db.list_triggered_engines().await?;
+        assert_eq!(engines.len(), 1);
+        assert_eq!(engines[0].id, triggered);
+        assert_eq!(engines[0].trigger_timeout_time, timeout);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn clones_share_engines() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let id = add_spark_engine(&db.clone()).await?;
+        assert!(db.get_engine(&id).await?.is_some());
+        db.health_check().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_most_overdue_engines_first() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        for (id, secs) in [("a", 30), ("b", 10), ("c", 20), ("d", 10)] {
+            db.add_engine(
+                &EngineId::try_from(id)?,
+                EngineInfo::new(
+                    "spark".to_owned(),
+                    EngineType::Spark,
+                    "3.5.4".to_owned(),
+                    EngineState::WaitToStart,
+                    BTreeMap::new(),
+                    EngineTime::now(),
+                ),
+                Some(UNIX_EPOCH + Duration::from_secs(secs)),
+                None,
+            )
+            .await?;
+        }
+
+        let engines = db.list_engines_need_update(&[], 3).await?;
+        let ids = engines
+            .into_iter()
+            .map(|engine| engine.id.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["b", "d", "c"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn claim_engines_need_update() -> Result<()> {
+        let db = InMemoryDatabase::new();
+        let mut ids = BTreeSet::new();
+        for _ in 0..10 {
+            ids.insert(add_spark_engine(&db).await?);
+        }
+        let claim = || async {
+            let engines = db
+                .claim_engines_need_update(&[], 6, SystemTime::now(), Duration::from_secs(60))
+                .await?;
+            Ok::<_, RucatError>(
+                engines
+                    .into_iter()
+                    .map(|engine| engine.id)
+                    .collect::<BTreeSet<_>>(),
+            )
+        };
+
+        let (first, second) = ::tokio::join!(claim(), claim());
+        let (first, second) = (first?, second?);
+        assert!(first.is_disjoint(&second));
+        assert_eq!(first.len().max(second.len()), 6);
+        assert_eq!(first.union(&second).cloned().collect::<BTreeSet<_>>(), ids);
+        // claimed engines are not listed until the claim expires
+        assert!(db.list_engines_need_update(&[], 100).await?.is_empty());
+        assert!(claim().await?.is_empty());
+        Ok(())
+    }
+}
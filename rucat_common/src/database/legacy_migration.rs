@@ -0,0 +1,317 @@
+//! Migration of engines stored in the legacy schema.
+//!
+//! In the legacy schema, engine states were `Pending`, `Running`, `Stopped` and `Error(String)`,
+//! and `next_update_time` was not recorded, so that the state monitor never picks the engines up.
+
+use ::core::fmt::Display;
+use ::std::{borrow::Cow, time::SystemTime};
+
+use ::anyhow::anyhow;
+use ::serde::{Deserialize, Serialize};
+use ::serde_json::Value;
+
+use crate::{
+    engine::{EngineId, EngineState, EngineTime},
+    error::{Result, RucatError},
+};
+
+/// Engine record read for the migration.
+/// The state is kept as raw JSON because legacy states cannot be deserialized into [EngineState].
+#[derive(Debug, Deserialize)]
+pub struct LegacyEngineRecord {
+    pub id: String,
+    pub state: Value,
+    pub next_update_time: Option<u64>,
+}
+
+/// Engine to be rewritten into the current schema.
+#[derive(Debug, PartialEq)]
+pub struct MigratedEngine {
+    pub id: EngineId,
+    /// State stored in the legacy record, the record is only rewritten if it is still in this state.
+    pub legacy_state: Value,
+    pub state: EngineState,
+    pub next_update_time: Option<SystemTime>,
+}
+
+/// Engine that is neither in the legacy nor the current schema, which is left untouched.
+#[derive(Debug, PartialEq)]
+pub struct UnrecognizedEngine {
+    pub id: String,
+    pub reason: String,
+}
+
+/// Result of scanning the engines table.
+#[derive(Debug, Default, PartialEq)]
+pub struct MigrationSummary {
+    /// Engines in the legacy schema.
+    pub migrated: Vec<MigratedEngine>,
+    /// Number of engines already in the current schema.
+    pub up_to_date: usize,
+    pub unrecognized: Vec<UnrecognizedEngine>,
+}
+
+impl Display for MigrationSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} legacy engine(s), {} up to date engine(s), {} unrecognized engine(s)",
+            self.migrated.len(),
+            self.up_to_date,
+            self.unrecognized.len()
+        )?;
+        for engine in &self.migrated {
+            writeln!(
+                f,
+                "  migrate engine {}: {} -> {:?}",
+                engine.id, engine.legacy_state, engine.state
+            )?;
+        }
+        for engine in &self.unrecognized {
+            writeln!(f, "  skip engine {}: {}", engine.id, engine.reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Record written after the migration, so that it is never run twice.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MigrationMarker {
+    pub migrated_at: EngineTime,
+    /// Number of engines migrated.
+    pub migrated: usize,
+}
+
+/// Map a legacy state onto the current [EngineState].
+/// Return `None` if `state` is not a legacy state.
+fn map_legacy_state(state: &Value) -> Option<EngineState> {
+    match state {
+        Value::String(state) => match state.as_str() {
+            "Pending" => Some(EngineState::WaitToStart),
+            "Stopped" => Some(EngineState::Terminated),
+            _ => None,
+        },
+        // Resources of errored engines may still exist, let the state monitor clean them.
+        Value::Object(map) if map.len() == 1 => match map.get("Error") {
+            Some(Value::String(message)) => {
+                Some(EngineState::ErrorWaitToClean(Cow::Owned(message.clone())))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Engines in stable states do not need to be updated by the state monitor,
+/// others are checked as soon as possible.
+fn get_next_update_time(state: &EngineState, now: SystemTime) -> Option<SystemTime> {
+    match state {
+        EngineState::Terminated | EngineState::ErrorClean(_) => None,
+        _ => Some(now),
+    }
+}
+
+/// Decide how to rewrite each record, without touching the database.
+pub fn plan_migration(records: Vec<LegacyEngineRecord>, now: SystemTime) -> MigrationSummary {
+    let mut summary = MigrationSummary::default();
+    for LegacyEngineRecord {
+        id,
+        state: legacy_state,
+        next_update_time,
+    } in records
+    {
+        let state = match map_legacy_state(&legacy_state) {
+            Some(state) => state,
+            None => match serde_json::from_value::<EngineState>(legacy_state.clone()) {
+                // `Running` is in both schemas, but running engines always have `next_update_time` now.
+                Ok(EngineState::Running) if next_update_time.is_none() => EngineState::Running,
+                Ok(_) => {
+                    summary.up_to_date += 1;
+                    continue;
+                }
+                Err(_) => {
+                    summary.unrecognized.push(UnrecognizedEngine {
+                        id,
+                        reason: format!("unknown state {}", legacy_state),
+                    });
+                    continue;
+                }
+            },
+        };
+        match EngineId::try_from(id.clone()) {
+            Ok(id) => summary.migrated.push(MigratedEngine {
+                id,
+                legacy_state,
+                next_update_time: get_next_update_time(&state, now),
+                state,
+            }),
+            Err(e) => summary.unrecognized.push(UnrecognizedEngine {
+                id,
+                reason: e.get_message(),
+            }),
+        }
+    }
+    summary
+}
+
+/// Refuse to migrate again if the migration has been done.
+pub fn ensure_not_migrated(marker: Option<&MigrationMarker>) -> Result<()> {
+    match marker {
+        None => Ok(()),
+        Some(MigrationMarker {
+            migrated_at,
+            migrated,
+        }) => Err(RucatError::not_allowed(anyhow!(
+            "Legacy engines have already been migrated at {:?}, {} engine(s) were migrated",
+            migrated_at,
+            migrated
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::time::Duration;
+
+    use ::serde_json::json;
+
+    use super::*;
+
+    fn record(id: &str, state: Value, next_update_time: Option<u64>) -> LegacyEngineRecord {
+        LegacyEngineRecord {
+            id: id.to_owned(),
+            state,
+            next_update_time,
+        }
+    }
+
+    fn migrate_one(state: Value, next_update_time: Option<u64>) -> MigrationSummary {
+        plan_migration(
+            vec![record("123", state, next_update_time)],
+            SystemTime::UNIX_EPOCH + Duration::from_secs(100),
+        )
+    }
+
+    fn assert_migrated(
+        state: Value,
+        expected_state: EngineState,
+        expected_next_update_time: Option<u64>,
+    ) {
+        let summary = migrate_one(state.clone(), None);
+        assert_eq!(
+            summary,
+            MigrationSummary {
+                migrated: vec![MigratedEngine {
+                    id: EngineId::try_from("123").unwrap(),
+                    legacy_state: state,
+                    state: expected_state,
+                    next_update_time: expected_next_update_time
+                        .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+                }],
+                up_to_date: 0,
+                unrecognized: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn migrate_pending_engine() {
+        assert_migrated(json!("Pending"), EngineState::WaitToStart, Some(100));
+    }
+
+    #[test]
+    fn migrate_stopped_engine() {
+        assert_migrated(json!("Stopped"), EngineState::Terminated, None);
+    }
+
+    #[test]
+    fn migrate_error_engine() {
+        assert_migrated(
+            json!({"Error": "pod failed"}),
+            EngineState::ErrorWaitToClean(Cow::Borrowed("pod failed")),
+            Some(100),
+        );
+    }
+
+    #[test]
+    fn migrate_running_engine_without_next_update_time() {
+        assert_migrated(json!("Running"), EngineState::Running, Some(100));
+    }
+
+    #[test]
+    fn skip_up_to_date_engines() {
+        let summary = plan_migration(
+            vec![
+                record("1", json!("Running"), Some(10)),
+                record("2", json!("Terminated"), None),
+                record("3", json!({"ErrorClean": "error"}), None),
+            ],
+            SystemTime::now(),
+        );
+        assert_eq!(summary.up_to_date, 3);
+        assert!(summary.migrated.is_empty());
+        assert!(summary.unrecognized.is_empty());
+    }
+
+    #[test]
+    fn skip_unrecognized_engines() {
+        let summary = plan_migration(
+            vec![
+                record("1", json!("Starting"), None),
+                record("2", json!({"Error": 1}), None),
+                record("", json!("Pending"), None),
+            ],
+            SystemTime::now(),
+        );
+        assert!(summary.migrated.is_empty());
+        assert_eq!(
+            summary
+                .unrecognized
+                .iter()
+                .map(|engine| engine.reason.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "unknown state \"Starting\"",
+                "unknown state {\"Error\":1}",
+                "Engine id cannot be empty."
+            ]
+        );
+    }
+
+    #[test]
+    fn display_summary() {
+        let summary = plan_migration(
+            vec![
+                record("1", json!("Pending"), None),
+                record("2", json!("Running"), Some(10)),
+                record("3", json!("Starting"), None),
+            ],
+            SystemTime::now(),
+        );
+        assert_eq!(
+            summary.to_string(),
+            "1 legacy engine(s), 1 up to date engine(s), 1 unrecognized engine(s)\n  \
+                migrate engine 1: \"Pending\" -> WaitToStart\n  \
+                skip engine 3: unknown state \"Starting\"\n"
+        );
+    }
+
+    #[test]
+    fn migrate_once() -> anyhow::Result<()> {
+        ensure_not_migrated(None)?;
+        Ok(())
+    }
+
+    #[test]
+    fn refuse_to_migrate_twice() {
+        let marker = MigrationMarker {
+            migrated_at: serde_json::from_value(json!("2025-01-02 03:04:05 +00:00:00")).unwrap(),
+            migrated: 2,
+        };
+        let result = ensure_not_migrated(Some(&marker));
+        assert!(result.is_err_and(|e| e.to_string().starts_with(
+            "Not allowed: Legacy engines have already been migrated at \
+                EngineTime(\"2025-01-02 03:04:05 +00:00:00\"), 2 engine(s) were migrated"
+        )));
+    }
+}
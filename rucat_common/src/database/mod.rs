@@ -1,14 +1,94 @@
 //! Datastore to record engines' information
 
+pub mod in_memory;
+pub mod legacy_migration;
+#[cfg(feature = "postgres")]
+pub mod postgres_client;
+#[cfg(feature = "redis")]
+pub mod redis_client;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_client;
+#[cfg(feature = "surrealdb")]
 pub mod surrealdb_client;
-use ::core::future::Future;
-use ::std::time::SystemTime;
+use ::core::{
+    future::{Future, IntoFuture},
+    str::FromStr,
+};
+use ::std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, SystemTime},
+};
 
-use crate::engine::{CreateEngineRequest, EngineId};
-use crate::engine::{EngineInfo, EngineState};
-use crate::error::Result;
+use crate::blob_store::BlobRef;
+use crate::engine::{
+    EngineActor, EngineConfig, EngineEndpoints, EngineEvent, EngineId, EngineSummary,
+};
+use crate::engine::{EngineInfo, EngineInfoSummary, EngineState, EngineStateKind, EngineType};
+use crate::error::{Result, RucatError};
+use anyhow::anyhow;
 use serde::Deserialize;
 
+/// Error message to keep in [EngineInfo::last_error] if the engine leaves an error state, e.g. when it is restarted.
+fn get_last_error<'a>(
+    before: &'a EngineState,
+    after: &EngineState,
+) -> Option<&'a Cow<'static, str>> {
+    match before {
+        EngineState::ErrorWaitToClean(e)
+        | EngineState::ErrorTriggerClean(e)
+        | EngineState::ErrorCleanInProgress(e)
+        | EngineState::ErrorClean(e)
+            if !EngineStateKind::from(after).is_error() =>
+        {
+            Some(e)
+        }
+        _ => None,
+    }
+}
+
+/// Result of [Database::get_engines] from the `engines` read by id in any order.
+#[cfg(any(feature = "postgres", feature = "redis", feature = "sqlite"))]
+fn order_by_ids(
+    ids: &[EngineId],
+    engines: impl IntoIterator<Item = (String, EngineInfo)>,
+) -> Vec<(EngineId, Option<EngineInfo>)> {
+    let engines = engines.into_iter().collect::<BTreeMap<_, _>>();
+    ids.iter()
+        .map(|id| (id.clone(), engines.get(&id.to_string()).cloned()))
+        .collect()
+}
+
+/// Source of the events recorded by [Database::update_engine_state] of the clients without another one set,
+/// see [EngineEvent::source].
+pub const DEFAULT_EVENT_SOURCE: &str = "unknown";
+
+/// Run a query of the database, failing with [RucatError::database_timeout]
+/// if it is not done in `query_timeout`, e.g. when the database hangs without closing the connection.
+/// The query is dropped on timeout, so whether it has taken effect is unknown.
+pub async fn with_query_timeout<Q: IntoFuture>(
+    query_timeout: Duration,
+    query: Q,
+) -> Result<Q::Output> {
+    ::tokio::time::timeout(query_timeout, query)
+        .await
+        .map_err(|_| {
+            RucatError::database_timeout(anyhow!(
+                "Query is not done in {} seconds",
+                query_timeout.as_secs_f64()
+            ))
+        })
+}
+
+/// Health of the database reported by [Database::health_check].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DatabaseHealth {
+    /// Time taken by the health check query.
+    pub latency: Duration,
+    /// Version of the database server, `None` if it is not reported.
+    pub version: Option<String>,
+}
+
 /// Response of updating an engine state.
 /// # Variants
 /// - `Success`: The update is successful.
@@ -19,40 +99,221 @@ pub enum UpdateEngineStateResult {
     Fail { current_state: EngineState },
 }
 
+/// Response of removing an engine.
+/// # Variants
+/// - `Success`: The engine is removed. Return the info of the removed engine.
+/// - `Fail`: The removal fails on inconsistent state. Return the current state of the engine.
+pub enum RemoveEngineResult {
+    Success(Box<EngineInfo>),
+    Fail { current_state: EngineState },
+}
+
 #[derive(Deserialize)]
 pub struct EngineIdAndInfo {
+    pub id: EngineId,
+    pub info: EngineInfoSummary,
+    /// The time when the engine was updated to the current state.
+    /// `None` for engines whose state was last updated before the time was recorded.
+    pub state_update_time: Option<SystemTime>,
+}
+
+/// Engine in one of the `Trigger*` states, i.e. acquired by a state monitor.
+pub struct TriggeredEngine {
     pub id: EngineId,
     pub info: EngineInfo,
+    /// The time when the engine was updated to the current state.
+    /// `None` for engines whose state was last updated before the time was recorded.
+    pub state_update_time: Option<SystemTime>,
+    /// The time when the trigger times out, after which the engine is retried by the state monitor.
+    pub trigger_timeout_time: SystemTime,
+}
+
+/// Key of a request adding an engine, so that retries of the request do not add duplicate engines.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdempotencyKey {
+    pub key: String,
+    /// The key can be used to add another engine after this time.
+    pub expire_time: SystemTime,
+}
+
+/// Conditions of listing engines, only engines matching all of them are listed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EngineFilter {
+    /// Only engines in these states are listed, or engines in any state if it is empty.
+    pub states: BTreeSet<EngineStateKind>,
+    /// Only engines whose name contains it case-insensitively are listed.
+    pub name_contains: String,
+    /// Only engines created by this principal are listed if set, see [EngineInfo::created_by].
+    pub created_by: Option<String>,
+    /// Only engines in this project are listed if set, see [EngineInfo::project].
+    pub project: Option<String>,
+}
+
+impl EngineFilter {
+    /// Whether the engine matches all conditions, for databases filtering engines out of the queries.
+    fn matches(&self, info: &EngineInfo) -> bool {
+        (self.states.is_empty() || self.states.contains(&EngineStateKind::from(&info.state)))
+            && info
+                .name
+                .to_lowercase()
+                .contains(&self.name_contains.to_lowercase())
+            && self
+                .created_by
+                .as_ref()
+                .is_none_or(|created_by| info.created_by.as_ref() == Some(created_by))
+            && in_project(info, self.project.as_deref())
+    }
+}
+
+/// Fields of an engine updated by [Database::update_engine_info], the fields which are `None` are kept.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EngineInfoUpdate {
+    /// New name of the engine.
+    pub name: Option<String>,
+    /// Config merged into the config of the engine, overwriting the values of existing keys.
+    pub config: Option<EngineConfig>,
+    /// Blobs merged into the blobs of the engine, overwriting the references of existing names.
+    pub blobs: Option<BTreeMap<String, BlobRef>>,
+}
+
+impl EngineInfoUpdate {
+    /// Apply the update to the engine, for databases updating engines out of the queries.
+    fn apply(self, info: &mut EngineInfo) {
+        if let Some(name) = self.name {
+            info.name = name;
+        }
+        if let Some(config) = self.config {
+            info.config.extend(config);
+        }
+        if let Some(blobs) = self.blobs {
+            info.blobs.extend(blobs);
+        }
+    }
+}
+
+/// Whether the engine is in one of `states`, or in any state if it is `None`.
+fn in_states(info: &EngineInfo, states: Option<&[EngineState]>) -> bool {
+    states.is_none_or(|states| states.contains(&info.state))
+}
+
+/// Whether the engine is in `project`, or any engine if it is `None`.
+fn in_project(info: &EngineInfo, project: Option<&str>) -> bool {
+    project.is_none_or(|project| info.project.as_deref() == Some(project))
+}
+
+/// Whether the engine is of one of `engine_types`, or any engine if it is empty.
+fn of_types(info: &EngineInfo, engine_types: &[EngineType]) -> bool {
+    engine_types.is_empty() || engine_types.contains(&info.engine_type)
+}
+
+/// Key of sorting the listed engines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EngineSortKey {
+    #[default]
+    Id,
+    CreateTime,
+    Name,
+    /// Sort by the [EngineStateKind] name, error states match regardless of the error message.
+    State,
+}
+
+impl EngineSortKey {
+    const ALL: [(&'static str, Self); 4] = [
+        ("id", Self::Id),
+        ("create_time", Self::CreateTime),
+        ("name", Self::Name),
+        ("state", Self::State),
+    ];
+}
+
+impl FromStr for EngineSortKey {
+    type Err = RucatError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, key)| *key)
+            .ok_or_else(|| {
+                RucatError::invalid_argument(anyhow!(
+                    "Unknown sort key `{}`, expected one of {:?}",
+                    s,
+                    Self::ALL.map(|(name, _)| name)
+                ))
+            })
+    }
+}
+
+/// How to sort the listed engines.
+/// Engines with the same key are sorted by id ascendingly, so that the pages are stable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EngineSort {
+    pub key: EngineSortKey,
+    pub descending: bool,
+}
+
+impl EngineSort {
+    /// Sort engines as databases sort them in queries, engines with the same key are sorted by id.
+    fn sort(&self, engines: &mut [(&EngineId, &EngineInfo)]) {
+        engines.sort_by(|a, b| {
+            let ordering = match self.key {
+                EngineSortKey::Id => a.0.cmp(b.0),
+                EngineSortKey::CreateTime => a.1.get_create_time().cmp(b.1.get_create_time()),
+                EngineSortKey::Name => a.1.name.cmp(&b.1.name),
+                EngineSortKey::State => EngineStateKind::from(&a.1.state)
+                    .to_string()
+                    .cmp(&EngineStateKind::from(&b.1.state).to_string()),
+            };
+            let ordering = if self.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            ordering.then_with(|| a.0.cmp(b.0))
+        });
+    }
 }
 
 /// Database for storing the Engine metadata.
 /// Engine is stored in the format of using [EngineId] as key and [EngineInfo] as value.
 pub trait Database: Sized + Send + Sync + 'static {
     /// Add the metadata of a new engine in the database,
-    /// generate an id for the engine and return it.
+    /// including engines whose resources already exist, e.g. engines deployed without rucat.
     /// # Parameters
-    /// - `engine`: create engine request
+    /// - `id`: id of the engine.
+    /// - `info`: information of the engine.
     /// - `next_update_time`: The time when the engine should be updated by the state monitor.
-    ///                       `None` means the engine does not need to be updated anymore.
+    ///   `None` means the engine does not need to be updated anymore.
+    /// - `idempotency_key`: key of the request adding the engine, stored together with the engine.
+    ///   The key is unique among the keys not expired, even if the engines are added concurrently.
     /// # Return
-    /// - `Ok(EngineId)` if the engine is successfully added.
-    /// - `Err(_)` if any error occurs in the database.
+    /// - `Ok(id)` if the engine is successfully added.
+    /// - `Ok(other_id)` if an engine was added with the same idempotency key before it expired,
+    ///   in which case nothing is added.
+    /// - `Err(_)` if the engine with `id` already exists, or any error occurs in the database.
     fn add_engine(
         &self,
-        engine: CreateEngineRequest,
+        id: &EngineId,
+        info: EngineInfo,
         next_update_time: Option<SystemTime>,
+        idempotency_key: Option<IdempotencyKey>,
     ) -> impl Future<Output = Result<EngineId>> + Send;
 
-    /// Remove Engine.
+    /// Remove Engine, and mark its history as deleted at `deleted_time` in the same transaction,
+    /// see [Database::purge_engine_events].
     /// # Return
     /// - `Ok(None)` if the engine does not exist.
-    /// - `Ok(Some(UpdateEngineStateResponse))` if the engine exists.
+    /// - `Ok(Some(RemoveEngineResult))` if the engine exists, with the removed engine if it is removed.
+    /// - `Err(_)` with [crate::error::RucatErrorType::PreconditionFailed]
+    ///   if `expected_revision` is set but the engine is at another [EngineInfo::revision].
     /// - `Err(_)` if any error occurs in the database.
     fn remove_engine(
         &self,
         id: &EngineId,
         current_state: &EngineState,
-    ) -> impl Future<Output = Result<Option<UpdateEngineStateResult>>> + Send;
+        expected_revision: Option<u64>,
+        deleted_time: SystemTime,
+    ) -> impl Future<Output = Result<Option<RemoveEngineResult>>> + Send;
 
     /// Update the engine state to `after` only when
     /// the engine exists and the current state is `before`.
@@ -61,10 +322,18 @@ pub trait Database: Sized + Send + Sync + 'static {
     /// - `before`: The expected state of the engine before the update.
     /// - `after`: The state that engine is wanted to be updated to.
     /// - `next_update_time`: The time when the engine should be updated by the state monitor.
-    ///                              `None` means the engine does not need to be updated anymore.
+    ///   `None` means the engine does not need to be updated anymore.
+    /// - `expected_revision`: The expected [EngineInfo::revision] before the update, not checked if `None`.
+    ///   The revision is increased if the state is changed.
+    /// - `actor`: The principal requesting the update, `None` if the update is made by rucat itself, e.g. the state monitor.
+    ///
+    /// If the state is changed, the transition is appended to the history of the engine in the same transaction,
+    /// with the event source of the client and the `actor`, see [Database::append_engine_event].
     /// # Return
     /// - `Ok(None)` if the engine does not exist.
     /// - `Ok(Some(UpdateEngineStateResponse))` if the engine exists.
+    /// - `Err(_)` with [crate::error::RucatErrorType::PreconditionFailed]
+    ///   if `expected_revision` is set but the engine is at another [EngineInfo::revision].
     /// - `Err(_)` if any error occurs in the database.
     fn update_engine_state(
         &self,
@@ -72,15 +341,168 @@ pub trait Database: Sized + Send + Sync + 'static {
         before: &EngineState,
         after: &EngineState,
         next_update_time: Option<SystemTime>,
+        expected_revision: Option<u64>,
+        actor: Option<&EngineActor>,
     ) -> impl Future<Output = Result<Option<UpdateEngineStateResult>>> + Send;
 
+    /// Update the fields of the engine set in `update`, only when the engine exists
+    /// and the current state is one of `expected_states`, or in any state if it is `None`.
+    /// The state is checked and the fields are updated atomically, and the revision of the engine is increased.
+    /// # Return
+    /// - `Ok(None)` if the engine does not exist.
+    /// - `Ok(Some(UpdateEngineStateResponse))` if the engine exists.
+    /// - `Err(_)` with [crate::error::RucatErrorType::PreconditionFailed]
+    ///   if `expected_revision` is set but the engine is at another [EngineInfo::revision].
+    /// - `Err(_)` if any error occurs in the database.
+    fn update_engine_info(
+        &self,
+        id: &EngineId,
+        update: EngineInfoUpdate,
+        expected_states: Option<&[EngineState]>,
+        expected_revision: Option<u64>,
+    ) -> impl Future<Output = Result<Option<UpdateEngineStateResult>>> + Send;
+
+    /// Set the principals allowed to change the engine in any state, see [EngineInfo::allowed_principals].
+    /// The revision of the engine is increased.
+    /// # Return
+    /// - `Ok(None)` if the engine does not exist.
+    /// - `Ok(Some(()))` if the principals are set.
+    /// - `Err(_)` with [crate::error::RucatErrorType::PreconditionFailed]
+    ///   if `expected_revision` is set but the engine is at another [EngineInfo::revision].
+    /// - `Err(_)` if any error occurs in the database.
+    fn set_engine_acl(
+        &self,
+        id: &EngineId,
+        allowed_principals: BTreeSet<String>,
+        expected_revision: Option<u64>,
+    ) -> impl Future<Output = Result<Option<()>>> + Send;
+
+    /// Merge `endpoints` into the endpoints of the engine in any state,
+    /// overwriting the values of existing keys. The revision of the engine is increased.
+    /// # Return
+    /// - `Ok(None)` if the engine does not exist.
+    /// - `Ok(Some(()))` if the endpoints are updated.
+    /// - `Err(_)` if any error occurs in the database.
+    fn update_engine_endpoints(
+        &self,
+        id: &EngineId,
+        endpoints: EngineEndpoints,
+    ) -> impl Future<Output = Result<Option<()>>> + Send;
+
     /// Return `Ok(None)` if the engine does not exist
     fn get_engine(&self, id: &EngineId) -> impl Future<Output = Result<Option<EngineInfo>>> + Send;
 
-    /// Return a sorted list of all engine ids
-    fn list_engines(&self) -> impl Future<Output = Result<Vec<EngineId>>> + Send;
+    /// Same as [Database::get_engine], but read the engines of all `ids` in one query.
+    /// The result is in the order of `ids`, with `None` for the engines that do not exist.
+    fn get_engines(
+        &self,
+        ids: &[EngineId],
+    ) -> impl Future<Output = Result<Vec<(EngineId, Option<EngineInfo>)>>> + Send;
+
+    /// Same as [Database::get_engine], but only read the state of the engine.
+    /// Return `Ok(None)` if the engine does not exist
+    fn get_engine_state(
+        &self,
+        id: &EngineId,
+    ) -> impl Future<Output = Result<Option<EngineState>>> + Send;
+
+    /// Return a page of the ids of the engines matching the `filter` sorted by `sort`,
+    /// skipping the first `offset` engines and containing at most `limit` engines.
+    fn list_engines(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+        sort: EngineSort,
+    ) -> impl Future<Output = Result<Vec<EngineId>>> + Send;
+
+    /// Same as [Database::list_engines], but return the summaries of engines instead of ids.
+    fn list_engine_summaries(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+        sort: EngineSort,
+    ) -> impl Future<Output = Result<Vec<EngineSummary>>> + Send;
+
+    /// Return at most `limit` out-of-date engines that need to be updated,
+    /// the most overdue first, i.e. sorted by the next update time, and then by id.
+    /// Only engines of `engine_types` are listed, or all engines if `engine_types` is empty.
+    fn list_engines_need_update(
+        &self,
+        engine_types: &[EngineType],
+        limit: usize,
+    ) -> impl Future<Output = Result<Vec<EngineIdAndInfo>>> + Send;
+
+    /// Claim at most `limit` engines out of date at `now` in one atomic operation, in the same order as [Database::list_engines_need_update],
+    /// by postponing their next update time to `claim_duration` after `now`, so that other state monitors skip them meanwhile.
+    /// Engines claimed by others are not returned, and concurrent claims return disjoint engines.
+    /// The claim is released when the engine state is updated, or expires after `claim_duration`.
+    fn claim_engines_need_update(
+        &self,
+        engine_types: &[EngineType],
+        limit: usize,
+        now: SystemTime,
+        claim_duration: Duration,
+    ) -> impl Future<Output = Result<Vec<EngineIdAndInfo>>> + Send;
+
+    /// Return the number of engines in each state, states without engines are omitted.
+    /// Only engines of `engine_types` are counted, or all engines if `engine_types` is empty.
+    /// Only engines in `project` are counted if it is set, see [EngineInfo::project].
+    fn count_engines_by_state(
+        &self,
+        engine_types: &[EngineType],
+        project: Option<&str>,
+    ) -> impl Future<Output = Result<BTreeMap<EngineStateKind, usize>>> + Send;
+
+    /// Remove all engines in `states` in one query, if they have been in the state since before `older_than`,
+    /// i.e. their state was updated before it, or they were created before it if the update time is not recorded.
+    /// Only engines in `project` are removed if it is set, see [EngineInfo::project].
+    /// The histories of the removed engines are marked as deleted at `deleted_time`, as in [Database::remove_engine].
+    /// # Return
+    /// - `Ok(ids)` of the removed engines.
+    /// - `Err(_)` if any error occurs in the database.
+    fn remove_engines_in_states(
+        &self,
+        states: &BTreeSet<EngineStateKind>,
+        older_than: SystemTime,
+        project: Option<&str>,
+        deleted_time: SystemTime,
+    ) -> impl Future<Output = Result<Vec<EngineId>>> + Send;
+
+    /// Return all engines in `Trigger*` states, sorted by engine id.
+    fn list_triggered_engines(&self) -> impl Future<Output = Result<Vec<TriggeredEngine>>> + Send;
+
+    /// Append the transition of the engine from `old_state` to `new_state` by `source` at `timestamp`
+    /// to the history of the engine, whether the engine exists or not.
+    fn append_engine_event(
+        &self,
+        id: &EngineId,
+        old_state: &EngineState,
+        new_state: &EngineState,
+        source: &str,
+        timestamp: SystemTime,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Return the latest `limit` events in the history of the engine, newest first,
+    /// or an empty vector if there is none, e.g. the engine does not exist.
+    /// The history of a deleted engine is kept until it is purged by [Database::purge_engine_events].
+    fn get_engine_events(
+        &self,
+        id: &EngineId,
+        limit: usize,
+    ) -> impl Future<Output = Result<Vec<EngineEvent>>> + Send;
+
+    /// Remove the history of the engines deleted before `deleted_before`, and return the number of the removed events.
+    /// Only the histories of the engines in `project` are removed if it is set, see [EngineInfo::project].
+    /// An engine added again with the id of a deleted engine takes over its history, which is not purged anymore.
+    fn purge_engine_events(
+        &self,
+        deleted_before: SystemTime,
+        project: Option<&str>,
+    ) -> impl Future<Output = Result<usize>> + Send;
 
-    /// Return all out-of-date engines that need to be updated.
-    fn list_engines_need_update(&self)
-        -> impl Future<Output = Result<Vec<EngineIdAndInfo>>> + Send;
+    /// Check that the database can be reached by a cheap query, without reading any engine.
+    /// Return the latency of the query and the version of the database server if it is reported.
+    fn health_check(&self) -> impl Future<Output = Result<DatabaseHealth>> + Send;
 }
@@ -0,0 +1,1667 @@
+//! Client of Redis
+//!
+//! # Durability
+//! Redis acknowledges a write before it is persisted, so the latest updates of engines can be lost
+//! when Redis restarts, depending on its persistence config:
+//! - without persistence, all engines are lost, and their resources are left in Kubernetes.
+//! - with RDB snapshots, the updates since the last snapshot are lost.
+//! - with AOF and `appendfsync everysec` (the default of AOF), about one second of updates can be lost.
+//! - with AOF and `appendfsync always`, no acknowledged update is lost, at the cost of write latency.
+//!
+//! Replication is asynchronous as well, so a failover can lose the updates not replicated yet.
+//! A lost update makes an engine go back to an earlier state, e.g. a terminated engine is running again,
+//! which the state monitor fixes by checking the resources of the engine.
+//! Use AOF with `appendfsync always` if engines must never go back, or another database.
+
+use ::std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use ::anyhow::anyhow;
+use ::redis::{
+    aio::{ConnectionManager, ConnectionManagerConfig},
+    cmd, from_redis_value, pipe, Client, ConnectionInfo, IntoConnectionInfo, Script, Value,
+};
+use ::serde::{de::DeserializeOwned, Serialize};
+
+use crate::engine::{
+    EngineActor, EngineEndpoints, EngineEvent, EngineId, EngineSummary, EngineTime,
+};
+use crate::engine::{EngineInfo, EngineState, EngineStateKind, EngineType};
+use crate::error::{Result, RucatError};
+use crate::{config::Credentials, engine::EngineInfoSummary};
+
+use super::{
+    get_last_error, in_project, in_states, of_types, order_by_ids, Database, DatabaseHealth,
+    EngineFilter, EngineIdAndInfo, EngineInfoUpdate, EngineSort, IdempotencyKey,
+    RemoveEngineResult, TriggeredEngine, UpdateEngineStateResult, DEFAULT_EVENT_SOURCE,
+};
+
+/// Client to interact with the database.
+/// Every key is prefixed by `rucat:`:
+/// - `engine:<id>`: hash of an engine, with the fields:
+///   - `info`: [EngineInfo] as JSON, whose state, revision and last error are overridden by the fields below.
+///   - `state`: [EngineState] as JSON, compared as a string to update the engine state.
+///   - `state_kind`: [EngineStateKind] of the state.
+///   - `revision`: [EngineInfo::revision].
+///   - `last_error`: [EngineInfo::last_error], absent if not set.
+///   - `state_update_time`: seconds since epoch when the engine state was updated.
+/// - `engines`: sorted set of all engine ids, all with score 0 to be sorted by id.
+/// - `next_update_time`: sorted set of the ids of engines to be updated by the state monitor,
+///   scored by the seconds since epoch after which they should be updated.
+/// - `idempotency_key:<key>`: id of the engine added with the key, expiring with the key.
+/// - `engine_events:<id>`: list of the [EngineEvent]s of an engine as JSON, oldest first.
+/// - `deleted_engine_events`: sorted set of the ids of deleted engines with events,
+///   scored by the seconds since epoch when the engine was removed, see [Database::purge_engine_events].
+/// - `deleted_engine_projects`: hash of the ids of deleted engines with events to their projects.
+///
+/// Updates of the engine state are compare-and-swap Lua scripts.
+/// Other updates of an engine are written by a script only if the engine is still at the revision read before,
+/// and retried otherwise.
+/// Listing engines reads all of them, only the engines to be updated are found by their score.
+///
+/// The connection is reestablished by [ConnectionManager] when it is broken,
+/// and the requests sent meanwhile fail.
+#[derive(Clone)]
+pub struct RedisClient {
+    connection: ConnectionManager,
+    /// Prefix of every key, so that other applications can share the Redis.
+    prefix: String,
+    /// Source of the events recorded by [Database::update_engine_state].
+    event_source: &'static str,
+}
+
+/// Engine read with all fields of its hash.
+struct StoredEngine {
+    id: EngineId,
+    info: EngineInfo,
+    state_update_time: SystemTime,
+    next_update_time: Option<SystemTime>,
+}
+
+impl RedisClient {
+    /// Add an engine, and the idempotency key if it is `KEYS[6]`.
+    /// The history of a deleted engine with the same id is taken over.
+    /// Return `{'added', <id>}` with the id of the engine added with the same key before,
+    /// or `{'exists'}` if the engine already exists.
+    const ADD_ENGINE: &'static str = r#"
+        if #KEYS == 6 then
+            local added = redis.call('GET', KEYS[6])
+            if added then
+                return {'added', added}
+            end
+        end
+        if redis.call('EXISTS', KEYS[1]) == 1 then
+            return {'exists'}
+        end
+        redis.call('HSET', KEYS[1], 'info', ARGV[2], 'state', ARGV[3], 'state_kind', ARGV[4],
+            'revision', ARGV[5], 'state_update_time', ARGV[6])
+        redis.call('ZADD', KEYS[2], 0, ARGV[1])
+        if ARGV[7] ~= '' then
+            redis.call('ZADD', KEYS[3], ARGV[7], ARGV[1])
+        end
+        redis.call('ZREM', KEYS[4], ARGV[1])
+        redis.call('HDEL', KEYS[5], ARGV[1])
+        if #KEYS == 6 then
+            redis.call('SET', KEYS[6], ARGV[1], 'PXAT', ARGV[8])
+        end
+        return {'added', ARGV[1]}
+    "#;
+
+    /// Update the state of an engine from `ARGV[2]` to `ARGV[3]`,
+    /// only bumping the revision, recording the time and appending the event `ARGV[8]` when the state is changed.
+    /// The last error is set to `ARGV[9]` if it is passed.
+    const UPDATE_ENGINE_STATE: &'static str = r#"
+        local current = redis.call('HMGET', KEYS[1], 'state', 'revision')
+        if not current[1] then
+            return {'none'}
+        end
+        if ARGV[5] ~= '' and ARGV[5] ~= current[2] then
+            return {'revision', current[2]}
+        end
+        if current[1] ~= ARGV[2] then
+            return {'fail', current[1]}
+        end
+        redis.call('HSET', KEYS[1], 'state', ARGV[3], 'state_kind', ARGV[4])
+        if ARGV[2] ~= ARGV[3] then
+            redis.call('HSET', KEYS[1], 'revision', tonumber(current[2]) + 1, 'state_update_time', ARGV[7])
+            redis.call('RPUSH', KEYS[3], ARGV[8])
+        end
+        if ARGV[6] == '' then
+            redis.call('ZREM', KEYS[2], ARGV[1])
+        else
+            redis.call('ZADD', KEYS[2], ARGV[6], ARGV[1])
+        end
+        if #ARGV >= 9 then
+            redis.call('HSET', KEYS[1], 'last_error', ARGV[9])
+        end
+        return {'success'}
+    "#;
+
+    /// Remove the events of the engine `ARGV[1]` unless it is added again.
+    /// Return the number of removed events.
+    const PURGE_ENGINE_EVENTS: &'static str = r#"
+        if redis.call('EXISTS', KEYS[1]) == 1 then
+            return 0
+        end
+        local count = redis.call('LLEN', KEYS[2])
+        redis.call('DEL', KEYS[2])
+        redis.call('ZREM', KEYS[3], ARGV[1])
+        redis.call('HDEL', KEYS[4], ARGV[1])
+        return count
+    "#;
+
+    /// Remove an engine in the state `ARGV[2]`, and return the fields of the removed engine.
+    /// Its events are marked as deleted at `ARGV[4]`, with the project of the engine.
+    const REMOVE_ENGINE: &'static str = r#"
+        local current = redis.call('HMGET', KEYS[1], 'state', 'revision')
+        if not current[1] then
+            return {'none'}
+        end
+        if ARGV[3] ~= '' and ARGV[3] ~= current[2] then
+            return {'revision', current[2]}
+        end
+        if current[1] ~= ARGV[2] then
+            return {'fail', current[1]}
+        end
+        local fields = redis.call('HGETALL', KEYS[1])
+        if redis.call('EXISTS', KEYS[4]) == 1 then
+            redis.call('ZADD', KEYS[5], ARGV[4], ARGV[1])
+            local project = cjson.decode(redis.call('HGET', KEYS[1], 'info')).project
+            if type(project) == 'string' then
+                redis.call('HSET', KEYS[6], ARGV[1], project)
+            end
+        end
+        redis.call('DEL', KEYS[1])
+        redis.call('ZREM', KEYS[2], ARGV[1])
+        redis.call('ZREM', KEYS[3], ARGV[1])
+        return {'success', unpack(fields)}
+    "#;
+
+    /// Write the `info` of an engine at the revision `ARGV[1]`, and set its revision to `ARGV[3]`.
+    const SET_ENGINE_INFO: &'static str = r#"
+        local revision = redis.call('HGET', KEYS[1], 'revision')
+        if not revision then
+            return 'none'
+        end
+        if revision ~= ARGV[1] then
+            return 'changed'
+        end
+        redis.call('HSET', KEYS[1], 'info', ARGV[2], 'revision', ARGV[3])
+        return 'success'
+    "#;
+
+    /// Claim at most `ARGV[2]` engines due before `ARGV[1]` by scoring them `ARGV[3]`, the most overdue first.
+    /// Only engines of the types in the JSON array `ARGV[4]` are claimed, or all engines if it is empty,
+    /// read from the hashes whose keys are prefixed by `ARGV[5]`.
+    /// Return the ids of the claimed engines.
+    const CLAIM_ENGINES: &'static str = r#"
+        local limit = tonumber(ARGV[2])
+        local engine_types = cjson.decode(ARGV[4])
+        local claimed = {}
+        local offset = 0
+        while #claimed < limit do
+            local ids = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', '(' .. ARGV[1], 'LIMIT', offset, limit)
+            if #ids == 0 then
+                break
+            end
+            offset = offset + #ids
+            for _, id in ipairs(ids) do
+                local matched = #engine_types == 0
+                if not matched then
+                    local info = redis.call('HGET', ARGV[5] .. id, 'info')
+                    local engine_type = info and cjson.decode(info).engine_type
+                    for _, t in ipairs(engine_types) do
+                        matched = matched or t == engine_type
+                    end
+                end
+                if matched and #claimed < limit then
+                    table.insert(claimed, id)
+                end
+            end
+        end
+        for _, id in ipairs(claimed) do
+            redis.call('ZADD', KEYS[1], ARGV[3], id)
+        end
+        return claimed
+    "#;
+
+    /// Return the ids of the engines scheduled in `KEYS[1]` whose state kinds are in the JSON array `ARGV[2]`,
+    /// read from the hashes whose keys are prefixed by `ARGV[1]`.
+    const LIST_SCHEDULED_ENGINES_IN_STATES: &'static str = r#"
+        local states = {}
+        for _, state in ipairs(cjson.decode(ARGV[2])) do
+            states[state] = true
+        end
+        local listed = {}
+        for _, id in ipairs(redis.call('ZRANGE', KEYS[1], 0, -1)) do
+            local state_kind = redis.call('HGET', ARGV[1] .. id, 'state_kind')
+            if state_kind and states[state_kind] then
+                table.insert(listed, id)
+            end
+        end
+        return listed
+    "#;
+
+    /// Retries of connecting to Redis, on startup and after the connection is broken.
+    const CONNECTION_RETRIES: usize = 5;
+    /// Delay in millisecond between two attempts of connecting,
+    /// as the default delays of [ConnectionManager] grow to minutes.
+    const CONNECTION_RETRY_DELAY_MILLIS: u64 = 1000;
+    const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Create a new [RedisClient] to connect to the Redis at `uri`.
+    /// # Parameters
+    /// - `credentials`: override the user and password in the `uri` if set.
+    /// - `uri`: `redis://[[user]:password@]host[:port][/db]`, or `rediss://` to connect with TLS.
+    pub async fn new(credentials: Option<&Credentials>, uri: &str) -> Result<Self> {
+        let mut info: ConnectionInfo = uri
+            .into_connection_info()
+            .map_err(RucatError::fail_to_connect_database)?;
+        if let Some(Credentials { username, password }) = credentials {
+            info.redis.username = Some(username.clone());
+            info.redis.password = Some(password.resolve()?);
+        }
+        Self::connect(info, "rucat:".to_owned()).await
+    }
+
+    async fn connect(info: ConnectionInfo, prefix: String) -> Result<Self> {
+        let client = Client::open(info).map_err(RucatError::fail_to_connect_database)?;
+        let config = ConnectionManagerConfig::new()
+            .set_number_of_retries(Self::CONNECTION_RETRIES)
+            .set_factor(1)
+            .set_max_delay(Self::CONNECTION_RETRY_DELAY_MILLIS)
+            .set_connection_timeout(Self::CONNECTION_TIMEOUT);
+        let connection = ConnectionManager::new_with_config(client, config)
+            .await
+            .map_err(RucatError::fail_to_connect_database)?;
+        Ok(Self {
+            connection,
+            prefix,
+            event_source: DEFAULT_EVENT_SOURCE,
+        })
+    }
+
+    /// Record the state changes of this client with `source`, e.g. `server`, see [EngineEvent::source].
+    pub fn with_event_source(mut self, source: &'static str) -> Self {
+        self.event_source = source;
+        self
+    }
+
+    fn engine_key(&self, id: &EngineId) -> String {
+        format!("{}engine:{}", self.prefix, id)
+    }
+
+    fn engines_key(&self) -> String {
+        format!("{}engines", self.prefix)
+    }
+
+    fn next_update_time_key(&self) -> String {
+        format!("{}next_update_time", self.prefix)
+    }
+
+    fn idempotency_key(&self, key: &str) -> String {
+        format!("{}idempotency_key:{}", self.prefix, key)
+    }
+
+    fn events_key(&self, id: &EngineId) -> String {
+        format!("{}engine_events:{}", self.prefix, id)
+    }
+
+    fn deleted_engine_events_key(&self) -> String {
+        format!("{}deleted_engine_events", self.prefix)
+    }
+
+    fn deleted_engine_projects_key(&self) -> String {
+        format!("{}deleted_engine_projects", self.prefix)
+    }
+
+    fn event_to_json(
+        old_state: &EngineState,
+        new_state: &EngineState,
+        source: &str,
+        actor: Option<&EngineActor>,
+        timestamp: SystemTime,
+    ) -> Result<String> {
+        Self::to_json(&EngineEvent {
+            old_state: old_state.clone(),
+            new_state: new_state.clone(),
+            source: source.to_owned(),
+            actor: actor.map(|actor| actor.name.clone()),
+            impersonator: actor.and_then(|actor| actor.impersonator.clone()),
+            time: EngineTime::from_system_time(timestamp),
+        })
+    }
+
+    fn convert_system_time_to_secs(time: SystemTime) -> u64 {
+        time.duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    fn convert_secs_to_system_time(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn to_json<T: Serialize>(value: &T) -> Result<String> {
+        ::serde_json::to_string(value).map_err(RucatError::fail_to_update_database)
+    }
+
+    fn from_json<T: DeserializeOwned>(value: &str) -> Result<T> {
+        ::serde_json::from_str(value).map_err(RucatError::fail_to_read_database)
+    }
+
+    /// Get a field of the hash of the engine `id`.
+    fn get_field<'a>(
+        id: &EngineId,
+        fields: &'a HashMap<String, String>,
+        name: &str,
+    ) -> Result<&'a str> {
+        fields.get(name).map(String::as_str).ok_or_else(|| {
+            RucatError::fail_to_read_database(anyhow!("Engine {} has no `{}` field", id, name))
+        })
+    }
+
+    /// Read the engine from the fields of its hash, `None` if the hash does not exist.
+    fn parse_engine(
+        id: EngineId,
+        fields: HashMap<String, String>,
+        next_update_time: Option<u64>,
+    ) -> Result<Option<StoredEngine>> {
+        if fields.is_empty() {
+            return Ok(None);
+        }
+        let mut info: EngineInfo = Self::from_json(Self::get_field(&id, &fields, "info")?)?;
+        info.state = Self::from_json(Self::get_field(&id, &fields, "state")?)?;
+        info.revision = Self::get_field(&id, &fields, "revision")?
+            .parse()
+            .map_err(RucatError::fail_to_read_database)?;
+        info.last_error = fields.get("last_error").cloned().map(Cow::Owned);
+        let state_update_time = Self::get_field(&id, &fields, "state_update_time")?
+            .parse()
+            .map_err(RucatError::fail_to_read_database)?;
+        Ok(Some(StoredEngine {
+            id,
+            info,
+            state_update_time: Self::convert_secs_to_system_time(state_update_time),
+            next_update_time: next_update_time.map(Self::convert_secs_to_system_time),
+        }))
+    }
+
+    /// Read the engines of `ids` in one pipeline, skipping the engines removed meanwhile.
+    async fn get_stored_engines(&self, ids: Vec<String>) -> Result<Vec<StoredEngine>> {
+        let ids = ids
+            .into_iter()
+            .map(EngineId::try_from)
+            .collect::<Result<Vec<_>>>()?;
+        let mut pipeline = pipe();
+        for id in &ids {
+            pipeline
+                .hgetall(self.engine_key(id))
+                .zscore(self.next_update_time_key(), id.to_string());
+        }
+        let mut connection = self.connection.clone();
+        // decoded by pairs, as a vector of tuples cannot be decoded from nested arrays of other lengths
+        let results: Vec<Value> = pipeline
+            .query_async(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_read_database)?;
+        let mut engines = Vec::with_capacity(ids.len());
+        for (id, result) in ids.into_iter().zip(results.chunks_exact(2)) {
+            let fields = from_redis_value(&result[0]).map_err(RucatError::fail_to_read_database)?;
+            let next_update_time =
+                from_redis_value(&result[1]).map_err(RucatError::fail_to_read_database)?;
+            if let Some(engine) = Self::parse_engine(id, fields, next_update_time)? {
+                engines.push(engine);
+            }
+        }
+        Ok(engines)
+    }
+
+    /// Read all engines, sorted by id.
+    async fn get_all_engines(&self) -> Result<Vec<StoredEngine>> {
+        let mut connection = self.connection.clone();
+        let ids: Vec<String> = cmd("ZRANGE")
+            .arg(self.engines_key())
+            .arg(0)
+            .arg(-1)
+            .query_async(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_read_database)?;
+        self.get_stored_engines(ids).await
+    }
+
+    /// Convert the result of a compare-and-swap script into [UpdateEngineStateResult],
+    /// or an error if the engine is not at the `expected_revision`.
+    fn check_result(
+        id: &EngineId,
+        result: Vec<String>,
+        expected_revision: Option<u64>,
+    ) -> Result<Option<UpdateEngineStateResult>> {
+        match &result[..] {
+            [status] if status == "success" => Ok(Some(UpdateEngineStateResult::Success)),
+            _ => Ok(Self::check_failure(id, &result, expected_revision)?
+                .map(|current_state| UpdateEngineStateResult::Fail { current_state })),
+        }
+    }
+
+    /// Read why the engine is not changed by a script, from its `result` other than `success`:
+    /// `Ok(None)` if the engine does not exist, or `Ok(Some(current_state))` if it is in another state.
+    fn check_failure(
+        id: &EngineId,
+        result: &[String],
+        expected_revision: Option<u64>,
+    ) -> Result<Option<EngineState>> {
+        match result.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["none"] => Ok(None),
+            ["fail", current_state] => Ok(Some(Self::from_json(current_state)?)),
+            ["revision", current_revision] => Err(RucatError::precondition_failed(anyhow!(
+                "Engine {} is at revision {}, but revision {:?} is expected",
+                id,
+                current_revision,
+                expected_revision
+            ))
+            .with_engine_id(id)),
+            _ => Err(RucatError::fail_to_update_database(anyhow!(
+                "Unexpected result of updating engine {}: {:?}",
+                id,
+                result
+            ))),
+        }
+    }
+
+    /// Update the info of the engine by `update`, which returns `false` if the engine cannot be updated in its state.
+    /// The engine is read again and updated if it is changed concurrently, unless `expected_revision` is set.
+    async fn modify_engine_info(
+        &self,
+        id: &EngineId,
+        expected_revision: Option<u64>,
+        mut update: impl FnMut(&mut EngineInfo) -> bool,
+    ) -> Result<Option<UpdateEngineStateResult>> {
+        loop {
+            let Some(mut info) = self.get_engine(id).await? else {
+                return Ok(None);
+            };
+            if expected_revision.is_some_and(|revision| revision != info.revision) {
+                return Err(RucatError::precondition_failed(anyhow!(
+                    "Engine {} is at revision {}, but revision {:?} is expected",
+                    id,
+                    info.revision,
+                    expected_revision
+                ))
+                .with_engine_id(id));
+            }
+            if !update(&mut info) {
+                return Ok(Some(UpdateEngineStateResult::Fail {
+                    current_state: info.state,
+                }));
+            }
+            let read_revision = info.revision;
+            info.revision += 1;
+            let mut connection = self.connection.clone();
+            let result: String = Script::new(Self::SET_ENGINE_INFO)
+                .key(self.engine_key(id))
+                .arg(read_revision)
+                .arg(Self::to_json(&info)?)
+                .arg(info.revision)
+                .invoke_async(&mut connection)
+                .await
+                .map_err(RucatError::fail_to_update_database)?;
+            match result.as_str() {
+                "success" => return Ok(Some(UpdateEngineStateResult::Success)),
+                "none" => return Ok(None),
+                // changed concurrently
+                _ => continue,
+            }
+        }
+    }
+
+    /// Return a page of the engines matching the `filter`, see [Database::list_engines].
+    async fn list_engine_page(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+        sort: EngineSort,
+    ) -> Result<Vec<StoredEngine>> {
+        let engines = self.get_all_engines().await?;
+        let mut page: Vec<_> = engines
+            .iter()
+            .filter(|engine| filter.matches(&engine.info))
+            .map(|engine| (&engine.id, &engine.info))
+            .collect();
+        sort.sort(&mut page);
+        let ids: Vec<EngineId> = page
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(id, _)| id.clone())
+            .collect();
+        let mut engines: BTreeMap<EngineId, StoredEngine> = engines
+            .into_iter()
+            .map(|engine| (engine.id.clone(), engine))
+            .collect();
+        Ok(ids.iter().filter_map(|id| engines.remove(id)).collect())
+    }
+}
+
+impl Database for RedisClient {
+    async fn add_engine(
+        &self,
+        id: &EngineId,
+        info: EngineInfo,
+        next_update_time: Option<SystemTime>,
+        idempotency_key: Option<IdempotencyKey>,
+    ) -> Result<EngineId> {
+        let script = Script::new(Self::ADD_ENGINE);
+        let mut invocation = script.prepare_invoke();
+        invocation
+            .key(self.engine_key(id))
+            .key(self.engines_key())
+            .key(self.next_update_time_key())
+            .key(self.deleted_engine_events_key())
+            .key(self.deleted_engine_projects_key());
+        if let Some(IdempotencyKey { key, .. }) = &idempotency_key {
+            invocation.key(self.idempotency_key(key));
+        }
+        invocation
+            .arg(id.to_string())
+            .arg(Self::to_json(&info)?)
+            .arg(Self::to_json(&info.state)?)
+            .arg(EngineStateKind::from(&info.state).to_string())
+            .arg(info.revision)
+            .arg(Self::convert_system_time_to_secs(SystemTime::now()))
+            .arg(
+                next_update_time
+                    .map(|time| Self::convert_system_time_to_secs(time).to_string())
+                    .unwrap_or_default(),
+            )
+            .arg(
+                idempotency_key
+                    .map(|key| {
+                        key.expire_time
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap()
+                            .as_millis()
+                            .to_string()
+                    })
+                    .unwrap_or_default(),
+            );
+        let mut connection = self.connection.clone();
+        let result: Vec<String> = invocation
+            .invoke_async(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        match result.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["added", added] => EngineId::try_from(added.to_owned()),
+            _ => Err(RucatError::fail_to_update_database(anyhow!(
+                "Engine {} already exists",
+                id
+            ))),
+        }
+    }
+
+    async fn remove_engine(
+        &self,
+        id: &EngineId,
+        current_state: &EngineState,
+        expected_revision: Option<u64>,
+        deleted_time: SystemTime,
+    ) -> Result<Option<RemoveEngineResult>> {
+        let mut connection = self.connection.clone();
+        let result: Vec<String> = Script::new(Self::REMOVE_ENGINE)
+            .key(self.engine_key(id))
+            .key(self.engines_key())
+            .key(self.next_update_time_key())
+            .key(self.events_key(id))
+            .key(self.deleted_engine_events_key())
+            .key(self.deleted_engine_projects_key())
+            .arg(id.to_string())
+            .arg(Self::to_json(current_state)?)
+            .arg(
+                expected_revision
+                    .map(|revision| revision.to_string())
+                    .unwrap_or_default(),
+            )
+            .arg(Self::convert_system_time_to_secs(deleted_time))
+            .invoke_async(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        match result.split_first() {
+            Some((status, fields)) if status == "success" => {
+                let fields = fields
+                    .chunks_exact(2)
+                    .map(|field| (field[0].clone(), field[1].clone()))
+                    .collect();
+                Ok(Self::parse_engine(id.clone(), fields, None)?
+                    .map(|engine| RemoveEngineResult::Success(Box::new(engine.info))))
+            }
+            _ => Ok(Self::check_failure(id, &result, expected_revision)?
+                .map(|current_state| RemoveEngineResult::Fail { current_state })),
+        }
+    }
+
+    async fn update_engine_state(
+        &self,
+        id: &EngineId,
+        before: &EngineState,
+        after: &EngineState,
+        next_update_time: Option<SystemTime>,
+        expected_revision: Option<u64>,
+        actor: Option<&EngineActor>,
+    ) -> Result<Option<UpdateEngineStateResult>> {
+        let now = SystemTime::now();
+        let script = Script::new(Self::UPDATE_ENGINE_STATE);
+        let mut invocation = script.prepare_invoke();
+        invocation
+            .key(self.engine_key(id))
+            .key(self.next_update_time_key())
+            .key(self.events_key(id))
+            .arg(id.to_string())
+            .arg(Self::to_json(before)?)
+            .arg(Self::to_json(after)?)
+            .arg(EngineStateKind::from(after).to_string())
+            .arg(
+                expected_revision
+                    .map(|revision| revision.to_string())
+                    .unwrap_or_default(),
+            )
+            .arg(
+                next_update_time
+                    .map(|time| Self::convert_system_time_to_secs(time).to_string())
+                    .unwrap_or_default(),
+            )
+            .arg(Self::convert_system_time_to_secs(now))
+            .arg(Self::event_to_json(
+                before,
+                after,
+                self.event_source,
+                actor,
+                now,
+            )?);
+        if let Some(last_error) = get_last_error(before, after) {
+            invocation.arg(last_error.as_ref());
+        }
+        let mut connection = self.connection.clone();
+        let result: Vec<String> = invocation
+            .invoke_async(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        Self::check_result(id, result, expected_revision)
+    }
+
+    async fn update_engine_info(
+        &self,
+        id: &EngineId,
+        update: EngineInfoUpdate,
+        expected_states: Option<&[EngineState]>,
+        expected_revision: Option<u64>,
+    ) -> Result<Option<UpdateEngineStateResult>> {
+        self.modify_engine_info(id, expected_revision, |info| {
+            if !in_states(info, expected_states) {
+                return false;
+            }
+            update.clone().apply(info);
+            true
+        })
+        .await
+    }
+
+    async fn set_engine_acl(
+        &self,
+        id: &EngineId,
+        allowed_principals: BTreeSet<String>,
+        expected_revision: Option<u64>,
+    ) -> Result<Option<()>> {
+        Ok(self
+            .modify_engine_info(id, expected_revision, |info| {
+                info.allowed_principals = Some(allowed_principals.clone());
+                true
+            })
+            .await?
+            .map(|_| ()))
+    }
+
+    async fn update_engine_endpoints(
+        &self,
+        id: &EngineId,
+        endpoints: EngineEndpoints,
+    ) -> Result<Option<()>> {
+        Ok(self
+            .modify_engine_info(id, None, |info| {
+                info.endpoints.extend(endpoints.clone());
+                true
+            })
+            .await?
+            .map(|_| ()))
+    }
+
+    async fn get_engine(&self, id: &EngineId) -> Result<Option<EngineInfo>> {
+        let mut connection = self.connection.clone();
+        let fields: HashMap<String, String> = cmd("HGETALL")
+            .arg(self.engine_key(id))
+            .query_async(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_read_database)?;
+        Ok(Self::parse_engine(id.clone(), fields, None)?.map(|engine| engine.info))
+    }
+
+    async fn get_engines(&self, ids: &[EngineId]) -> Result<Vec<(EngineId, Option<EngineInfo>)>> {
+        let engines = self
+            .get_stored_engines(ids.iter().map(ToString::to_string).collect())
+            .await?;
+        Ok(order_by_ids(
+            ids,
+            engines
+                .into_iter()
+                .map(|engine| (engine.id.to_string(), engine.info)),
+        ))
+    }
+
+    async fn get_engine_state(&self, id: &EngineId) -> Result<Option<EngineState>> {
+        let mut connection = self.connection.clone();
+        let state: Option<String> = cmd("HGET")
+            .arg(self.engine_key(id))
+            .arg("state")
+            .query_async(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_read_database)?;
+        state.as_deref().map(Self::from_json).transpose()
+    }
+
+    async fn list_engines(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+        sort: EngineSort,
+    ) -> Result<Vec<EngineId>> {
+        let engines = self.list_engine_page(offset, limit, filter, sort).await?;
+        Ok(engines.into_iter().map(|engine| engine.id).collect())
+    }
+
+    async fn list_engine_summaries(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+        sort: EngineSort,
+    ) -> Result<Vec<EngineSummary>> {
+        let engines = self.list_engine_page(offset, limit, filter, sort).await?;
+        Ok(engines
+            .into_iter()
+            .map(|StoredEngine { id, info, .. }| EngineSummary {
+                id,
+                create_time: info.get_create_time().clone(),
+                name: info.name,
+                engine_type: info.engine_type,
+                state: info.state,
+            })
+            .collect())
+    }
+
+    async fn list_engines_need_update(
+        &self,
+        engine_types: &[EngineType],
+        limit: usize,
+    ) -> Result<Vec<EngineIdAndInfo>> {
+        let mut connection = self.connection.clone();
+        let now = Self::convert_system_time_to_secs(SystemTime::now());
+        let mut need_update = vec![];
+        // Engines of other types are filtered after being read,
+        // so they are read page by page until enough engines are found.
+        // Engines with the same score are sorted by id in the sorted set.
+        let mut offset = 0;
+        while need_update.len() < limit {
+            let ids: Vec<String> = cmd("ZRANGEBYSCORE")
+                .arg(self.next_update_time_key())
+                .arg("-inf")
+                .arg(format!("({}", now))
+                .arg("LIMIT")
+                .arg(offset)
+                .arg(limit)
+                .query_async(&mut connection)
+                .await
+                .map_err(RucatError::fail_to_read_database)?;
+            if ids.is_empty() {
+                break;
+            }
+            offset += ids.len();
+            let remaining = limit - need_update.len();
+            let engines = self.get_stored_engines(ids).await?;
+            need_update.extend(
+                engines
+                    .into_iter()
+                    .filter(|engine| of_types(&engine.info, engine_types))
+                    .take(remaining)
+                    .map(|engine| EngineIdAndInfo {
+                        id: engine.id,
+                        info: EngineInfoSummary::from(engine.info),
+                        state_update_time: Some(engine.state_update_time),
+                    }),
+            );
+        }
+        Ok(need_update)
+    }
+
+    async fn claim_engines_need_update(
+        &self,
+        engine_types: &[EngineType],
+        limit: usize,
+        now: SystemTime,
+        claim_duration: Duration,
+    ) -> Result<Vec<EngineIdAndInfo>> {
+        let script = Script::new(Self::CLAIM_ENGINES);
+        let mut invocation = script.prepare_invoke();
+        invocation
+            .key(self.next_update_time_key())
+            .arg(Self::convert_system_time_to_secs(now))
+            .arg(limit)
+            .arg(Self::convert_system_time_to_secs(now + claim_duration))
+            .arg(Self::to_json(&engine_types)?)
+            .arg(format!("{}engine:", self.prefix));
+        let mut connection = self.connection.clone();
+        let ids: Vec<String> = invocation
+            .invoke_async(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        // the claimed engines are read in the order they are claimed
+        let engines = self.get_stored_engines(ids).await?;
+        Ok(engines
+            .into_iter()
+            .map(|engine| EngineIdAndInfo {
+                id: engine.id,
+                info: EngineInfoSummary::from(engine.info),
+                state_update_time: Some(engine.state_update_time),
+            })
+            .collect())
+    }
+
+    async fn count_engines_by_state(
+        &self,
+        engine_types: &[EngineType],
+        project: Option<&str>,
+    ) -> Result<BTreeMap<EngineStateKind, usize>> {
+        let mut counts = BTreeMap::new();
+        for engine in self.get_all_engines().await? {
+            if of_types(&engine.info, engine_types) && in_project(&engine.info, project) {
+                *counts
+                    .entry(EngineStateKind::from(&engine.info.state))
+                    .or_insert(0) += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn remove_engines_in_states(
+        &self,
+        states: &BTreeSet<EngineStateKind>,
+        older_than: SystemTime,
+        project: Option<&str>,
+        deleted_time: SystemTime,
+    ) -> Result<Vec<EngineId>> {
+        let mut removed = vec![];
+        for engine in self.get_all_engines().await? {
+            if states.contains(&EngineStateKind::from(&engine.info.state))
+                && engine.state_update_time < older_than
+                && in_project(&engine.info, project)
+            {
+                // the engine is left if its state is changed meanwhile
+                let result = self
+                    .remove_engine(&engine.id, &engine.info.state, None, deleted_time)
+                    .await?;
+                if let Some(RemoveEngineResult::Success(_)) = result {
+                    removed.push(engine.id);
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    async fn list_triggered_engines(&self) -> Result<Vec<TriggeredEngine>> {
+        // Engines in Trigger* states always have next_update_time set to the trigger timeout,
+        // so only the engines scheduled to be updated are checked.
+        let mut connection = self.connection.clone();
+        let mut ids: Vec<String> = Script::new(Self::LIST_SCHEDULED_ENGINES_IN_STATES)
+            .key(self.next_update_time_key())
+            .arg(format!("{}engine:", self.prefix))
+            .arg(Self::to_json(&[
+                EngineStateKind::TriggerStart,
+                EngineStateKind::TriggerTermination,
+                EngineStateKind::ErrorTriggerClean,
+            ])?)
+            .invoke_async(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_read_database)?;
+        ids.sort();
+        Ok(self
+            .get_stored_engines(ids)
+            .await?
+            .into_iter()
+            .filter_map(|engine| {
+                Some(TriggeredEngine {
+                    trigger_timeout_time: engine.next_update_time?,
+                    id: engine.id,
+                    info: engine.info,
+                    state_update_time: Some(engine.state_update_time),
+                })
+            })
+            .collect())
+    }
+
+    async fn append_engine_event(
+        &self,
+        id: &EngineId,
+        old_state: &EngineState,
+        new_state: &EngineState,
+        source: &str,
+        timestamp: SystemTime,
+    ) -> Result<()> {
+        let mut connection = self.connection.clone();
+        cmd("RPUSH")
+            .arg(self.events_key(id))
+            .arg(Self::event_to_json(
+                old_state, new_state, source, None, timestamp,
+            )?)
+            .query_async::<()>(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_update_database)
+    }
+
+    async fn get_engine_events(&self, id: &EngineId, limit: usize) -> Result<Vec<EngineEvent>> {
+        if limit == 0 {
+            return Ok(vec![]);
+        }
+        let mut connection = self.connection.clone();
+        let events: Vec<String> = cmd("LRANGE")
+            .arg(self.events_key(id))
+            .arg(-(limit as i64))
+            .arg(-1)
+            .query_async(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_read_database)?;
+        events
+            .iter()
+            .rev()
+            .map(|event| Self::from_json(event))
+            .collect()
+    }
+
+    async fn purge_engine_events(
+        &self,
+        deleted_before: SystemTime,
+        project: Option<&str>,
+    ) -> Result<usize> {
+        let mut connection = self.connection.clone();
+        let mut deleted: Vec<String> = cmd("ZRANGEBYSCORE")
+            .arg(self.deleted_engine_events_key())
+            .arg("-inf")
+            .arg(format!(
+                "({}",
+                Self::convert_system_time_to_secs(deleted_before)
+            ))
+            .query_async(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_read_database)?;
+        if let Some(project) = project {
+            if deleted.is_empty() {
+                return Ok(0);
+            }
+            let projects: Vec<Option<String>> = cmd("HMGET")
+                .arg(self.deleted_engine_projects_key())
+                .arg(&deleted)
+                .query_async(&mut connection)
+                .await
+                .map_err(RucatError::fail_to_read_database)?;
+            deleted = deleted
+                .into_iter()
+                .zip(projects)
+                .filter(|(_, in_project)| in_project.as_deref() == Some(project))
+                .map(|(id, _)| id)
+                .collect();
+        }
+        let mut purged = 0;
+        for id in deleted {
+            let id = EngineId::try_from(id)?;
+            let count: usize = Script::new(Self::PURGE_ENGINE_EVENTS)
+                .key(self.engine_key(&id))
+                .key(self.events_key(&id))
+                .key(self.deleted_engine_events_key())
+                .key(self.deleted_engine_projects_key())
+                .arg(id.to_string())
+                .invoke_async(&mut connection)
+                .await
+                .map_err(RucatError::fail_to_update_database)?;
+            purged += count;
+        }
+        Ok(purged)
+    }
+
+    async fn health_check(&self) -> Result<DatabaseHealth> {
+        let start = Instant::now();
+        let mut connection = self.connection.clone();
+        let info: String = cmd("INFO")
+            .arg("server")
+            .query_async(&mut connection)
+            .await
+            .map_err(RucatError::fail_to_connect_database)?;
+        // the server section has a line of `redis_version:<version>`
+        let version = info
+            .lines()
+            .find_map(|line| line.strip_prefix("redis_version:"))
+            .map(|version| version.trim().to_owned());
+        Ok(DatabaseHealth {
+            latency: start.elapsed(),
+            version,
+        })
+    }
+}
+
+/// Run by `cargo test -p rucat_common --features redis-tests` against the Redis at `RUCAT_TEST_REDIS_URI`,
+/// e.g. started by `docker run -d -p 6379:6379 redis:7`.
+#[cfg(all(test, feature = "redis-tests"))]
+mod tests {
+    use super::*;
+    use crate::blob_store::BlobRef;
+    use crate::engine::{CreateEngineRequest, EngineConfig, IdGenerator, RandomIdGenerator};
+    use crate::error::RucatErrorType;
+
+    /// Connect with a new key prefix, so that the tests do not see the engines of each other.
+    async fn new_client() -> Result<RedisClient> {
+        let uri = std::env::var("RUCAT_TEST_REDIS_URI")
+            .unwrap_or_else(|_| "redis://localhost:6379".to_owned());
+        let info = uri
+            .into_connection_info()
+            .map_err(RucatError::fail_to_connect_database)?;
+        RedisClient::connect(info, format!("rucat_test_{:016x}:", rand::random::<u64>())).await
+    }
+
+    fn get_spark_engine_info(name: &str) -> Result<EngineInfo> {
+        let request = CreateEngineRequest {
+            name: name.to_owned(),
+            engine_type: EngineType::Spark,
+            version: "3.5.4".to_owned(),
+            config: None,
+            start_in_progress_timeout_secs: None,
+            features: None,
+            start: None,
+        };
+        EngineInfo::from_create_request(request, EngineTime::now())
+    }
+
+    async fn add_spark_engine_with_name(client: &RedisClient, name: &str) -> Result<EngineId> {
+        let id = RandomIdGenerator.generate();
+        client
+            .add_engine(&id, get_spark_engine_info(name)?, Some(UNIX_EPOCH), None)
+            .await?;
+        Ok(id)
+    }
+
+    async fn add_spark_engine(client: &RedisClient) -> Result<EngineId> {
+        add_spark_engine_with_name(client, "spark").await
+    }
+
+    fn idempotency_key(expire_time: SystemTime) -> Option<IdempotencyKey> {
+        Some(IdempotencyKey {
+            key: "key-1".to_owned(),
+            expire_time,
+        })
+    }
+
+    #[tokio::test]
+    async fn add_and_get_engine() -> Result<()> {
+        let client = new_client().await?;
+        let id = EngineId::try_from("spark")?;
+        let mut info = get_spark_engine_info("spark")?;
+        info.created_by = Some("alice".to_owned());
+
+        assert_eq!(client.add_engine(&id, info.clone(), None, None).await?, id);
+
+        assert_eq!(client.get_engine(&id).await?, Some(info.clone()));
+        assert_eq!(
+            client.get_engine_state(&id).await?,
+            Some(EngineState::WaitToStart)
+        );
+        let result = client.add_engine(&id, info, None, None).await;
+        assert!(result.is_err_and(|e| e.to_string().starts_with("Fail to update database:")));
+        let nonexistent = EngineId::try_from("nonexistent")?;
+        assert!(client.get_engine(&nonexistent).await?.is_none());
+        assert!(client.get_engine_state(&nonexistent).await?.is_none());
+        let health = client.health_check().await?;
+        assert!(health.version.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_engine_with_idempotency_key() -> Result<()> {
+        let client = new_client().await?;
+        let first = EngineId::try_from("first")?;
+        let second = EngineId::try_from("second")?;
+        let expire_time = SystemTime::now() + Duration::from_secs(3600);
+
+        for id in [&first, &second] {
+            let added = client
+                .add_engine(
+                    id,
+                    get_spark_engine_info("spark")?,
+                    None,
+                    idempotency_key(expire_time),
+                )
+                .await?;
+            assert_eq!(added, first);
+        }
+        assert!(client.get_engine(&second).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_engine_state() -> Result<()> {
+        let client = new_client().await?;
+        let id = add_spark_engine(&client).await?;
+        assert_eq!(client.list_engines_need_update(&[], 100).await?.len(), 1);
+
+        let result = client
+            .update_engine_state(
+                &id,
+                &EngineState::Running,
+                &EngineState::Terminated,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        assert!(matches!(
+            result,
+            Some(UpdateEngineStateResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+
+        let result = client
+            .update_engine_state(
+                &id,
+                &EngineState::WaitToStart,
+                &EngineState::TriggerStart,
+                Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+                Some(0),
+                None,
+            )
+            .await?;
+        assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+        assert_eq!(client.get_engine(&id).await?.unwrap().revision, 1);
+        let triggered = client.list_triggered_engines().await?;
+        assert_eq!(triggered.len(), 1);
+        assert_eq!(
+            triggered[0].trigger_timeout_time,
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+        );
+
+        let result = client
+            .update_engine_state(
+                &id,
+                &EngineState::TriggerStart,
+                &EngineState::StartInProgress,
+                None,
+                Some(0),
+                None,
+            )
+            .await;
+        assert!(result.is_err_and(|e| {
+            e.get_error_type() == &RucatErrorType::PreconditionFailed
+                && e.to_string().contains(&format!(
+                    "Engine {} is at revision 1, but revision Some(0) is expected",
+                    id
+                ))
+        }));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_engine_state_concurrently() -> Result<()> {
+        let client = new_client().await?;
+        let id = add_spark_engine(&client).await?;
+        let tasks = [EngineState::TriggerStart, EngineState::Terminated].map(|after| {
+            let client = client.clone();
+            let id = id.clone();
+            tokio::spawn(async move {
+                client
+                    .update_engine_state(&id, &EngineState::WaitToStart, &after, None, None, None)
+                    .await
+            })
+        });
+        let mut successes = 0;
+        for task in tasks {
+            if let Some(UpdateEngineStateResult::Success) =
+                task.await.map_err(RucatError::fail_to_update_database)??
+            {
+                successes += 1;
+            }
+        }
+        assert_eq!(successes, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_name_and_config_in_expected_states() -> Result<()> {
+        let client = new_client().await?;
+        let id = add_spark_engine(&client).await?;
+        let update = EngineInfoUpdate {
+            name: Some("renamed".to_owned()),
+            config: Some(EngineConfig::from([(
+                "spark.executor.instances".into(),
+                "2".into(),
+            )])),
+            blobs: Some(BTreeMap::from([(
+                "log_tail".to_owned(),
+                BlobRef {
+                    key: "engines/log_tail".to_owned(),
+                    size: 3,
+                    checksum: "abc".to_owned(),
+                },
+            )])),
+        };
+
+        let result = client
+            .update_engine_info(&id, update.clone(), Some(&[EngineState::Terminated]), None)
+            .await?;
+        assert!(matches!(
+            result,
+            Some(UpdateEngineStateResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+        assert_eq!(client.get_engine(&id).await?.unwrap().revision, 0);
+
+        let result = client
+            .update_engine_info(&id, update, Some(&[EngineState::WaitToStart]), Some(0))
+            .await?;
+        assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+        let info = client.get_engine(&id).await?.unwrap();
+        assert_eq!(info.name, "renamed");
+        assert_eq!(
+            info.config.get("spark.executor.instances"),
+            Some(&Cow::Borrowed("2"))
+        );
+        assert_eq!(info.blobs["log_tail"].size, 3);
+        assert_eq!(info.revision, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn return_removed_engine() -> Result<()> {
+        let client = new_client().await?;
+        let id = add_spark_engine(&client).await?;
+        let info = client.get_engine(&id).await?.unwrap();
+        let result = client
+            .remove_engine(&id, &EngineState::Running, None, UNIX_EPOCH)
+            .await?;
+        assert!(matches!(
+            result,
+            Some(RemoveEngineResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+        let result = client
+            .remove_engine(&id, &EngineState::WaitToStart, None, UNIX_EPOCH)
+            .await?;
+        assert!(matches!(result, Some(RemoveEngineResult::Success(removed)) if *removed == info));
+        assert!(client
+            .remove_engine(&id, &EngineState::WaitToStart, None, UNIX_EPOCH)
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keep_last_error_when_leaving_error_state() -> Result<()> {
+        let client = new_client().await?;
+        let id = add_spark_engine(&client).await?;
+        let error = EngineState::ErrorClean(Cow::Borrowed("fail to start"));
+        client
+            .update_engine_state(&id, &EngineState::WaitToStart, &error, None, None, None)
+            .await?;
+        client
+            .update_engine_state(&id, &error, &EngineState::WaitToStart, None, None, None)
+            .await?;
+        assert_eq!(
+            client.get_engine(&id).await?.unwrap().last_error,
+            Some(Cow::Borrowed("fail to start"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_engine_info() -> Result<()> {
+        let client = new_client().await?;
+        let id = add_spark_engine(&client).await?;
+        let config = EngineConfig::from([(
+            Cow::Borrowed("spark.executor.instances"),
+            Cow::Borrowed("2"),
+        )]);
+
+        let result = client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    config: Some(config.clone()),
+                    ..Default::default()
+                },
+                Some(&[EngineState::Terminated]),
+                None,
+            )
+            .await?;
+        assert!(matches!(
+            result,
+            Some(UpdateEngineStateResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+        client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    config: Some(config.clone()),
+                    ..Default::default()
+                },
+                Some(&[EngineState::WaitToStart]),
+                Some(0),
+            )
+            .await?;
+        client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    name: Some("renamed".to_owned()),
+                    ..Default::default()
+                },
+                None,
+                Some(1),
+            )
+            .await?;
+        let result = client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    name: Some("other".to_owned()),
+                    ..Default::default()
+                },
+                None,
+                Some(1),
+            )
+            .await;
+        assert!(result.is_err_and(|e| e.get_error_type() == &RucatErrorType::PreconditionFailed));
+
+        let info = client.get_engine(&id).await?.unwrap();
+        assert_eq!(info.config, config);
+        assert_eq!(info.name, "renamed");
+        assert_eq!(info.revision, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_count_and_remove_engines() -> Result<()> {
+        let client = new_client().await?;
+        let etl = add_spark_engine_with_name(&client, "etl").await?;
+        let adhoc = add_spark_engine_with_name(&client, "adhoc").await?;
+        client
+            .update_engine_state(
+                &adhoc,
+                &EngineState::WaitToStart,
+                &EngineState::Terminated,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let by_name = EngineSort {
+            key: super::super::EngineSortKey::Name,
+            descending: false,
+        };
+        assert_eq!(
+            client
+                .list_engines(0, 10, &EngineFilter::default(), by_name)
+                .await?,
+            vec![adhoc.clone(), etl.clone()]
+        );
+        assert_eq!(
+            client.count_engines_by_state(&[], None).await?,
+            BTreeMap::from([
+                (EngineStateKind::WaitToStart, 1),
+                (EngineStateKind::Terminated, 1)
+            ])
+        );
+        let states = BTreeSet::from([EngineStateKind::Terminated]);
+        let later = SystemTime::now() + Duration::from_secs(2);
+        assert_eq!(
+            client
+                .remove_engines_in_states(&states, later, None, UNIX_EPOCH)
+                .await?,
+            vec![adhoc]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_engines_in_order() -> Result<()> {
+        let client = new_client().await?;
+        for name in ["etl", "ml"] {
+            client
+                .add_engine(
+                    &EngineId::try_from(name)?,
+                    get_spark_engine_info(name)?,
+                    None,
+                    None,
+                )
+                .await?;
+        }
+
+        let ids = ["ml", "nonexistent", "etl"]
+            .map(EngineId::try_from)
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        let engines = client.get_engines(&ids).await?;
+        let names = engines
+            .iter()
+            .map(|(id, info)| (id.to_string(), info.as_ref().map(|info| info.name.as_str())))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                ("ml".to_owned(), Some("ml")),
+                ("nonexistent".to_owned(), None),
+                ("etl".to_owned(), Some("etl"))
+            ]
+        );
+        assert!(client.get_engines(&[]).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_state_changes_as_events() -> Result<()> {
+        let client = new_client().await?.with_event_source("server");
+        let id = add_spark_engine(&client).await?;
+        let states = [
+            EngineState::WaitToStart,
+            EngineState::TriggerStart,
+            EngineState::StartInProgress,
+            EngineState::Running,
+        ];
+        for pair in states.windows(2) {
+            client
+                .update_engine_state(&id, &pair[0], &pair[1], None, None, None)
+                .await?;
+        }
+        // a recheck without changing the state is not an event
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::Running,
+                &EngineState::Running,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let events = client.get_engine_events(&id, 10).await?;
+        let transitions = events
+            .iter()
+            .map(|event| (&event.old_state, &event.new_state, event.source.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            transitions,
+            [
+                (&states[2], &states[3], "server"),
+                (&states[1], &states[2], "server"),
+                (&states[0], &states[1], "server"),
+            ]
+        );
+        assert_eq!(client.get_engine_events(&id, 1).await?, events[..1]);
+
+        client
+            .append_engine_event(
+                &id,
+                &EngineState::Running,
+                &EngineState::WaitToTerminate,
+                "state_monitor",
+                SystemTime::now(),
+            )
+            .await?;
+        let latest = client.get_engine_events(&id, 1).await?;
+        assert_eq!(latest[0].new_state, EngineState::WaitToTerminate);
+        assert_eq!(latest[0].source, "state_monitor");
+
+        let nonexistent = EngineId::try_from("nonexistent")?;
+        assert!(client.get_engine_events(&nonexistent, 10).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_actor_of_state_changes() -> Result<()> {
+        let client = new_client().await?;
+        let id = add_spark_engine(&client).await?;
+        let actor = EngineActor {
+            name: "alice".to_owned(),
+            impersonator: Some("admin".to_owned()),
+        };
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::WaitToStart,
+                &EngineState::TriggerStart,
+                None,
+                None,
+                Some(&actor),
+            )
+            .await?;
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::TriggerStart,
+                &EngineState::StartInProgress,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let events = client.get_engine_events(&id, 10).await?;
+        let actors = events
+            .iter()
+            .map(|event| (event.actor.as_deref(), event.impersonator.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(actors, [(None, None), (Some("alice"), Some("admin"))]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn purge_events_of_deleted_engines() -> Result<()> {
+        let client = new_client().await?;
+        let kept = add_spark_engine(&client).await?;
+        let removed = add_spark_engine(&client).await?;
+        for id in [&kept, &removed] {
+            client
+                .update_engine_state(
+                    id,
+                    &EngineState::WaitToStart,
+                    &EngineState::Terminated,
+                    None,
+                    None,
+                    None,
+                )
+                .await?;
+        }
+        let deleted_time = UNIX_EPOCH + Duration::from_secs(60);
+        client
+            .remove_engine(&removed, &EngineState::Terminated, None, deleted_time)
+            .await?;
+
+        // the history is kept for the ttl from the deletion time
+        assert_eq!(client.purge_engine_events(deleted_time, None).await?, 0);
+        assert_eq!(client.get_engine_events(&removed, 10).await?.len(), 1);
+
+        // the removed engine is not in the project
+        let after = deleted_time + Duration::from_secs(1);
+        assert_eq!(client.purge_engine_events(after, Some("project")).await?, 0);
+        assert_eq!(client.get_engine_events(&removed, 10).await?.len(), 1);
+
+        assert_eq!(client.purge_engine_events(after, None).await?, 1);
+        assert!(client.get_engine_events(&removed, 10).await?.is_empty());
+        assert_eq!(client.get_engine_events(&kept, 10).await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_most_overdue_engines_first() -> Result<()> {
+        let client = new_client().await?;
+        for (id, secs) in [("a", 30), ("b", 10), ("c", 20), ("d", 10)] {
+            client
+                .add_engine(
+                    &EngineId::try_from(id)?,
+                    get_spark_engine_info(id)?,
+                    Some(UNIX_EPOCH + Duration::from_secs(secs)),
+                    None,
+                )
+                .await?;
+        }
+
+        let engines = client.list_engines_need_update(&[], 3).await?;
+        let ids = engines
+            .into_iter()
+            .map(|engine| engine.id.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["b", "d", "c"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn claim_engines_need_update() -> Result<()> {
+        let client = new_client().await?;
+        let mut ids = BTreeSet::new();
+        for _ in 0..10 {
+            ids.insert(add_spark_engine(&client).await?);
+        }
+        let claim = || async {
+            let engines = client
+                .claim_engines_need_update(&[], 6, SystemTime::now(), Duration::from_secs(60))
+                .await?;
+            Ok::<_, RucatError>(
+                engines
+                    .into_iter()
+                    .map(|engine| engine.id)
+                    .collect::<BTreeSet<_>>(),
+            )
+        };
+
+        let (first, second) = ::tokio::join!(claim(), claim());
+        let (first, second) = (first?, second?);
+        assert!(first.is_disjoint(&second));
+        assert_eq!(first.len().max(second.len()), 6);
+        assert_eq!(first.union(&second).cloned().collect::<BTreeSet<_>>(), ids);
+        // claimed engines are not listed until the claim expires
+        assert!(client.list_engines_need_update(&[], 100).await?.is_empty());
+        assert!(claim().await?.is_empty());
+        Ok(())
+    }
+}
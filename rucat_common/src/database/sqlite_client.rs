@@ -0,0 +1,1672 @@
+//! Client of SQLite
+
+use ::core::str::FromStr;
+use ::std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use ::anyhow::anyhow;
+use ::serde::{Deserialize, Serialize};
+use ::serde_json::Value;
+use ::sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions},
+    types::Json,
+    Sqlite, SqliteExecutor, Transaction,
+};
+
+use crate::engine::{
+    EngineActor, EngineEndpoints, EngineEvent, EngineId, EngineSummary, EngineTime,
+};
+use crate::engine::{EngineInfo, EngineInfoSummary, EngineState, EngineStateKind, EngineType};
+use crate::error::{Result, RucatError};
+
+use super::{
+    get_last_error, in_states, order_by_ids, Database, DatabaseHealth, EngineFilter,
+    EngineIdAndInfo, EngineInfoUpdate, EngineSort, EngineSortKey, IdempotencyKey,
+    RemoveEngineResult, TriggeredEngine, UpdateEngineStateResult, DEFAULT_EVENT_SOURCE,
+};
+
+/// Client to interact with the database.
+/// Store the metadata of Engines in the `engines` table:
+/// - `id`: id of the engine.
+/// - `info`: [EngineInfo] as JSON text.
+/// - `state`: [EngineStateKind] of `info.state`, for filtering, sorting and counting engines by state.
+/// - `next_update_time`: seconds since epoch after which the state monitor should update the engine,
+///   `NULL` if the engine does not need to be updated anymore.
+/// - `state_update_time`: seconds since epoch when the engine state was updated.
+///
+/// Idempotency keys of adding engines are stored in the `idempotency_keys` table,
+/// with the `engine` added with the key and the `expire_time` after which the key can be used again.
+///
+/// State changes of engines are stored in the `engine_events` table, ordered by the autoincrement `id`,
+/// with the `deleted_time` and `project` of the engine stamped when it is removed, see [Database::purge_engine_events].
+///
+/// Every update runs in an immediate transaction, which takes the write lock of the database when it begins,
+/// so the engine read by the transaction cannot be changed by others before it is written back.
+/// Concurrent writers wait for the lock for at most [SqliteClient::BUSY_TIMEOUT].
+#[derive(Clone)]
+pub struct SqliteClient {
+    pool: SqlitePool,
+    /// Source of the events recorded by [Database::update_engine_state].
+    event_source: &'static str,
+}
+
+/// Id, info and state update time of an engine selected by [SqliteClient::SELECT_ENGINES_NEED_UPDATE].
+type EngineNeedUpdate = (String, Json<EngineInfoSummary>, i64);
+
+/// Row of the `engines` table, read in a transaction to be updated.
+struct EngineRow {
+    info: EngineInfo,
+    next_update_time: Option<i64>,
+    state_update_time: i64,
+}
+
+impl SqliteClient {
+    /// Create the tables if they do not exist.
+    const SCHEMA: &'static str = r#"
+        CREATE TABLE IF NOT EXISTS engines (
+            id TEXT PRIMARY KEY,
+            info TEXT NOT NULL,
+            state TEXT NOT NULL,
+            next_update_time INTEGER,
+            state_update_time INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS engines_next_update_time_idx
+            ON engines (next_update_time) WHERE next_update_time IS NOT NULL;
+        CREATE INDEX IF NOT EXISTS engines_state_idx ON engines (state);
+        CREATE TABLE IF NOT EXISTS idempotency_keys (
+            key TEXT PRIMARY KEY,
+            engine TEXT NOT NULL,
+            expire_time INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS engine_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            engine TEXT NOT NULL,
+            old_state TEXT NOT NULL,
+            new_state TEXT NOT NULL,
+            source TEXT NOT NULL,
+            actor TEXT,
+            impersonator TEXT,
+            time INTEGER NOT NULL,
+            deleted_time INTEGER,
+            project TEXT
+        );
+        CREATE INDEX IF NOT EXISTS engine_events_engine_idx ON engine_events (engine, id);
+        CREATE INDEX IF NOT EXISTS engine_events_deleted_time_idx
+            ON engine_events (deleted_time) WHERE deleted_time IS NOT NULL;
+    "#;
+
+    /// How long an update waits for the write lock held by another connection or process.
+    const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Open the SQLite database file at `path` in WAL mode,
+    /// creating the file and the tables of rucat if they do not exist.
+    pub async fn new(path: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(Self::BUSY_TIMEOUT);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .map_err(RucatError::fail_to_connect_database)?;
+        ::sqlx::raw_sql(Self::SCHEMA)
+            .execute(&pool)
+            .await
+            .map_err(RucatError::fail_to_connect_database)?;
+        Ok(Self {
+            pool,
+            event_source: DEFAULT_EVENT_SOURCE,
+        })
+    }
+
+    /// Record the state changes of this client with `source`, e.g. `server`, see [EngineEvent::source].
+    pub fn with_event_source(mut self, source: &'static str) -> Self {
+        self.event_source = source;
+        self
+    }
+
+    fn convert_system_time_to_secs(time: SystemTime) -> i64 {
+        time.duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+    }
+
+    fn convert_secs_to_system_time(secs: i64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs as u64)
+    }
+
+    /// Names of the engine types as a JSON array, as stored in `info.engine_type`.
+    fn get_type_names(engine_types: &[EngineType]) -> Result<Json<Vec<String>>> {
+        engine_types
+            .iter()
+            .map(|engine_type| match ::serde_json::to_value(engine_type) {
+                Ok(Value::String(name)) => Ok(name),
+                Ok(value) => Err(RucatError::fail_to_read_database(anyhow!(
+                    "Engine type {:?} is not stored as a string",
+                    value
+                ))),
+                Err(e) => Err(RucatError::fail_to_read_database(e)),
+            })
+            .collect::<Result<_>>()
+            .map(Json)
+    }
+
+    /// Names of the states as a JSON array, as stored in the `state` column.
+    fn get_state_names<'a>(
+        states: impl IntoIterator<Item = &'a EngineStateKind>,
+    ) -> Json<Vec<String>> {
+        Json(states.into_iter().map(ToString::to_string).collect())
+    }
+
+    fn to_json<T: Serialize>(value: &T) -> Result<String> {
+        ::serde_json::to_string(value).map_err(RucatError::fail_to_update_database)
+    }
+
+    /// Query of the engines to update, see [Database::list_engines_need_update], as [EngineNeedUpdate].
+    /// The other fields of `info` are skipped when it is deserialized as [EngineInfoSummary].
+    const SELECT_ENGINES_NEED_UPDATE: &'static str = r#"
+        SELECT id, info, state_update_time FROM engines
+        WHERE next_update_time < ?1
+            AND (json_array_length(?2) = 0
+                OR info ->> '$.engine_type' IN (SELECT value FROM json_each(?2)))
+        ORDER BY next_update_time ASC, id ASC
+        LIMIT ?3;
+    "#;
+
+    fn convert_engine_need_update(
+        (id, Json(info), state_update_time): EngineNeedUpdate,
+    ) -> Result<EngineIdAndInfo> {
+        Ok(EngineIdAndInfo {
+            id: EngineId::try_from(id)?,
+            info,
+            state_update_time: Some(Self::convert_secs_to_system_time(state_update_time)),
+        })
+    }
+
+    /// Begin a transaction holding the write lock of the database.
+    async fn begin_immediate(&self) -> Result<Transaction<'static, Sqlite>> {
+        self.pool
+            .begin_with("BEGIN IMMEDIATE")
+            .await
+            .map_err(RucatError::fail_to_update_database)
+    }
+
+    /// Read the engine to update in the `transaction`:
+    /// - `Ok(None)` if the engine does not exist.
+    /// - `Err(_)` with [crate::error::RucatErrorType::PreconditionFailed]
+    ///   if `expected_revision` is set but the engine is at another revision.
+    async fn read_engine(
+        transaction: &mut Transaction<'static, Sqlite>,
+        id: &EngineId,
+        expected_revision: Option<u64>,
+    ) -> Result<Option<EngineRow>> {
+        let sql = r#"
+            SELECT info, next_update_time, state_update_time FROM engines WHERE id = ?1;
+        "#;
+        let row: Option<(Json<EngineInfo>, Option<i64>, i64)> = ::sqlx::query_as(sql)
+            .bind(id.to_string())
+            .fetch_optional(&mut **transaction)
+            .await
+            .map_err(RucatError::fail_to_read_database)?;
+        let Some((Json(info), next_update_time, state_update_time)) = row else {
+            return Ok(None);
+        };
+        if expected_revision.is_some_and(|revision| revision != info.revision) {
+            return Err(RucatError::precondition_failed(anyhow!(
+                "Engine {} is at revision {}, but revision {:?} is expected",
+                id,
+                info.revision,
+                expected_revision
+            ))
+            .with_engine_id(id));
+        }
+        Ok(Some(EngineRow {
+            info,
+            next_update_time,
+            state_update_time,
+        }))
+    }
+
+    /// Insert a state change of the engine into the `engine_events` table.
+    async fn insert_event<'e>(
+        executor: impl SqliteExecutor<'e>,
+        id: &EngineId,
+        old_state: &EngineState,
+        new_state: &EngineState,
+        source: &str,
+        actor: Option<&EngineActor>,
+        time: i64,
+    ) -> Result<()> {
+        let sql = r#"
+            INSERT INTO engine_events (engine, old_state, new_state, source, actor, impersonator, time)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7);
+        "#;
+        ::sqlx::query(sql)
+            .bind(id.to_string())
+            .bind(Self::to_json(old_state)?)
+            .bind(Self::to_json(new_state)?)
+            .bind(source)
+            .bind(actor.map(|actor| actor.name.as_str()))
+            .bind(actor.and_then(|actor| actor.impersonator.as_deref()))
+            .bind(time)
+            .execute(executor)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        Ok(())
+    }
+
+    /// Write the engine read by [Self::read_engine] back and commit the `transaction`.
+    async fn write_engine(
+        mut transaction: Transaction<'static, Sqlite>,
+        id: &EngineId,
+        row: EngineRow,
+    ) -> Result<()> {
+        let sql = r#"
+            UPDATE engines
+            SET info = ?2, state = ?3, next_update_time = ?4, state_update_time = ?5
+            WHERE id = ?1;
+        "#;
+        ::sqlx::query(sql)
+            .bind(id.to_string())
+            .bind(Self::to_json(&row.info)?)
+            .bind(EngineStateKind::from(&row.info.state).to_string())
+            .bind(row.next_update_time)
+            .bind(row.state_update_time)
+            .execute(&mut *transaction)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        transaction
+            .commit()
+            .await
+            .map_err(RucatError::fail_to_update_database)
+    }
+
+    /// Condition of listing engines in `?1` (all engines if empty),
+    /// with names containing `?2` case-insensitively, created by `?3` and in `?4` if they are set.
+    /// The states are bound as a JSON array, as SQLite cannot bind arrays.
+    const LIST_ENGINES_CONDITION: &'static str = r#"
+                (json_array_length(?1) = 0 OR state IN (SELECT value FROM json_each(?1)))
+                AND instr(lower(info ->> '$.name'), lower(?2)) > 0
+                AND (?3 IS NULL OR info ->> '$.created_by' = ?3)
+                AND (?4 IS NULL OR info ->> '$.project' = ?4)
+    "#;
+
+    /// `ORDER BY` clause of listing engines, engines with the same sort key are sorted by id.
+    /// Strings are compared byte-wise by the default collation of SQLite, as [EngineId]s are.
+    fn get_order_by(sort: EngineSort) -> String {
+        let key = match sort.key {
+            EngineSortKey::Id => "id",
+            EngineSortKey::CreateTime => "info ->> '$.create_time'",
+            EngineSortKey::Name => "info ->> '$.name'",
+            EngineSortKey::State => "state",
+        };
+        let order = if sort.descending { "DESC" } else { "ASC" };
+        format!("ORDER BY {} {}, id ASC", key, order)
+    }
+
+    /// Run the query of listing a page of engines, see [Self::LIST_ENGINES_CONDITION].
+    /// `?5` and `?6` are the limit and offset of the page.
+    async fn list_engine_page<T>(
+        &self,
+        sql: &str,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+    ) -> Result<Vec<T>>
+    where
+        T: for<'r> ::sqlx::FromRow<'r, ::sqlx::sqlite::SqliteRow> + Send + Unpin,
+    {
+        ::sqlx::query_as(sql)
+            .bind(Self::get_state_names(&filter.states))
+            .bind(&filter.name_contains)
+            .bind(filter.created_by.as_deref())
+            .bind(filter.project.as_deref())
+            .bind(limit as i64)
+            .bind(offset as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(RucatError::fail_to_read_database)
+    }
+}
+
+impl Database for SqliteClient {
+    async fn add_engine(
+        &self,
+        id: &EngineId,
+        info: EngineInfo,
+        next_update_time: Option<SystemTime>,
+        idempotency_key: Option<IdempotencyKey>,
+    ) -> Result<EngineId> {
+        let now = Self::convert_system_time_to_secs(SystemTime::now());
+        let mut transaction = self.begin_immediate().await?;
+        if let Some(IdempotencyKey { key, expire_time }) = idempotency_key {
+            ::sqlx::query("DELETE FROM idempotency_keys WHERE expire_time <= ?1;")
+                .bind(now)
+                .execute(&mut *transaction)
+                .await
+                .map_err(RucatError::fail_to_update_database)?;
+            let added: Option<(String,)> =
+                ::sqlx::query_as("SELECT engine FROM idempotency_keys WHERE key = ?1;")
+                    .bind(&key)
+                    .fetch_optional(&mut *transaction)
+                    .await
+                    .map_err(RucatError::fail_to_update_database)?;
+            if let Some((added,)) = added {
+                return EngineId::try_from(added);
+            }
+            ::sqlx::query(
+                "INSERT INTO idempotency_keys (key, engine, expire_time) VALUES (?1, ?2, ?3);",
+            )
+            .bind(&key)
+            .bind(id.to_string())
+            .bind(Self::convert_system_time_to_secs(expire_time))
+            .execute(&mut *transaction)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        }
+
+        // `INSERT` fails if the engine already exists.
+        let sql = r#"
+            INSERT INTO engines (id, info, state, next_update_time, state_update_time)
+            VALUES (?1, ?2, ?3, ?4, ?5);
+        "#;
+        ::sqlx::query(sql)
+            .bind(id.to_string())
+            .bind(Self::to_json(&info)?)
+            .bind(EngineStateKind::from(&info.state).to_string())
+            .bind(next_update_time.map(Self::convert_system_time_to_secs))
+            .bind(now)
+            .execute(&mut *transaction)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        // the history of a deleted engine with the same id is taken over
+        let sql = r#"
+            UPDATE engine_events SET deleted_time = NULL, project = NULL
+            WHERE engine = ?1 AND deleted_time IS NOT NULL;
+        "#;
+        ::sqlx::query(sql)
+            .bind(id.to_string())
+            .execute(&mut *transaction)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        transaction
+            .commit()
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        Ok(id.clone())
+    }
+
+    async fn remove_engine(
+        &self,
+        id: &EngineId,
+        current_state: &EngineState,
+        expected_revision: Option<u64>,
+        deleted_time: SystemTime,
+    ) -> Result<Option<RemoveEngineResult>> {
+        let mut transaction = self.begin_immediate().await?;
+        let Some(row) = Self::read_engine(&mut transaction, id, expected_revision).await? else {
+            return Ok(None);
+        };
+        if &row.info.state != current_state {
+            return Ok(Some(RemoveEngineResult::Fail {
+                current_state: row.info.state,
+            }));
+        }
+        ::sqlx::query("DELETE FROM engines WHERE id = ?1;")
+            .bind(id.to_string())
+            .execute(&mut *transaction)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        ::sqlx::query(
+            "UPDATE engine_events SET deleted_time = ?2, project = ?3 WHERE engine = ?1;",
+        )
+        .bind(id.to_string())
+        .bind(Self::convert_system_time_to_secs(deleted_time))
+        .bind(row.info.project.as_deref())
+        .execute(&mut *transaction)
+        .await
+        .map_err(RucatError::fail_to_update_database)?;
+        transaction
+            .commit()
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        Ok(Some(RemoveEngineResult::Success(Box::new(row.info))))
+    }
+
+    async fn update_engine_state(
+        &self,
+        id: &EngineId,
+        before: &EngineState,
+        after: &EngineState,
+        next_update_time: Option<SystemTime>,
+        expected_revision: Option<u64>,
+        actor: Option<&EngineActor>,
+    ) -> Result<Option<UpdateEngineStateResult>> {
+        let mut transaction = self.begin_immediate().await?;
+        let Some(mut row) = Self::read_engine(&mut transaction, id, expected_revision).await?
+        else {
+            return Ok(None);
+        };
+        if &row.info.state != before {
+            return Ok(Some(UpdateEngineStateResult::Fail {
+                current_state: row.info.state,
+            }));
+        }
+        if let Some(last_error) = get_last_error(before, after) {
+            row.info.last_error = Some(last_error.clone());
+        }
+        // only record the time, bump the revision and append the event when the state is changed,
+        // not when the engine is rechecked
+        if before != after {
+            row.state_update_time = Self::convert_system_time_to_secs(SystemTime::now());
+            row.info.revision += 1;
+            Self::insert_event(
+                &mut *transaction,
+                id,
+                before,
+                after,
+                self.event_source,
+                actor,
+                row.state_update_time,
+            )
+            .await?;
+        }
+        row.info.state = after.clone();
+        row.next_update_time = next_update_time.map(Self::convert_system_time_to_secs);
+        Self::write_engine(transaction, id, row).await?;
+        Ok(Some(UpdateEngineStateResult::Success))
+    }
+
+    async fn update_engine_info(
+        &self,
+        id: &EngineId,
+        update: EngineInfoUpdate,
+        expected_states: Option<&[EngineState]>,
+        expected_revision: Option<u64>,
+    ) -> Result<Option<UpdateEngineStateResult>> {
+        let mut transaction = self.begin_immediate().await?;
+        let Some(mut row) = Self::read_engine(&mut transaction, id, expected_revision).await?
+        else {
+            return Ok(None);
+        };
+        if !in_states(&row.info, expected_states) {
+            return Ok(Some(UpdateEngineStateResult::Fail {
+                current_state: row.info.state,
+            }));
+        }
+        update.apply(&mut row.info);
+        row.info.revision += 1;
+        Self::write_engine(transaction, id, row).await?;
+        Ok(Some(UpdateEngineStateResult::Success))
+    }
+
+    async fn set_engine_acl(
+        &self,
+        id: &EngineId,
+        allowed_principals: BTreeSet<String>,
+        expected_revision: Option<u64>,
+    ) -> Result<Option<()>> {
+        let mut transaction = self.begin_immediate().await?;
+        let Some(mut row) = Self::read_engine(&mut transaction, id, expected_revision).await?
+        else {
+            return Ok(None);
+        };
+        row.info.allowed_principals = Some(allowed_principals);
+        row.info.revision += 1;
+        Self::write_engine(transaction, id, row).await?;
+        Ok(Some(()))
+    }
+
+    async fn update_engine_endpoints(
+        &self,
+        id: &EngineId,
+        endpoints: EngineEndpoints,
+    ) -> Result<Option<()>> {
+        let mut transaction = self.begin_immediate().await?;
+        let Some(mut row) = Self::read_engine(&mut transaction, id, None).await? else {
+            return Ok(None);
+        };
+        row.info.endpoints.extend(endpoints);
+        row.info.revision += 1;
+        Self::write_engine(transaction, id, row).await?;
+        Ok(Some(()))
+    }
+
+    async fn get_engine(&self, id: &EngineId) -> Result<Option<EngineInfo>> {
+        let info: Option<(Json<EngineInfo>,)> =
+            ::sqlx::query_as("SELECT info FROM engines WHERE id = ?1;")
+                .bind(id.to_string())
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(RucatError::fail_to_read_database)?;
+        Ok(info.map(|(Json(info),)| info))
+    }
+
+    async fn get_engines(&self, ids: &[EngineId]) -> Result<Vec<(EngineId, Option<EngineInfo>)>> {
+        let engines: Vec<(String, Json<EngineInfo>)> = ::sqlx::query_as(
+            "SELECT id, info FROM engines WHERE id IN (SELECT value FROM json_each(?1));",
+        )
+        .bind(Json(
+            ids.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        ))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(RucatError::fail_to_read_database)?;
+        Ok(order_by_ids(
+            ids,
+            engines.into_iter().map(|(id, Json(info))| (id, info)),
+        ))
+    }
+
+    async fn get_engine_state(&self, id: &EngineId) -> Result<Option<EngineState>> {
+        let state: Option<(Json<EngineState>,)> =
+            ::sqlx::query_as("SELECT info -> '$.state' FROM engines WHERE id = ?1;")
+                .bind(id.to_string())
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(RucatError::fail_to_read_database)?;
+        Ok(state.map(|(Json(state),)| state))
+    }
+
+    async fn list_engines(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+        sort: EngineSort,
+    ) -> Result<Vec<EngineId>> {
+        let sql = format!(
+            r#"
+            SELECT id FROM engines
+            WHERE {}
+            {} LIMIT ?5 OFFSET ?6;
+        "#,
+            Self::LIST_ENGINES_CONDITION,
+            Self::get_order_by(sort)
+        );
+        let ids: Vec<(String,)> = self.list_engine_page(&sql, offset, limit, filter).await?;
+        ids.into_iter()
+            .map(|(id,)| EngineId::try_from(id))
+            .collect()
+    }
+
+    async fn list_engine_summaries(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+        sort: EngineSort,
+    ) -> Result<Vec<EngineSummary>> {
+        let sql = format!(
+            r#"
+            SELECT id, info FROM engines
+            WHERE {}
+            {} LIMIT ?5 OFFSET ?6;
+        "#,
+            Self::LIST_ENGINES_CONDITION,
+            Self::get_order_by(sort)
+        );
+
+        /// Fields of the stored [EngineInfo] in [EngineSummary].
+        #[derive(Deserialize)]
+        struct SummaryInfo {
+            name: String,
+            engine_type: EngineType,
+            state: EngineState,
+            create_time: EngineTime,
+        }
+
+        let summaries: Vec<(String, Json<SummaryInfo>)> =
+            self.list_engine_page(&sql, offset, limit, filter).await?;
+        summaries
+            .into_iter()
+            .map(|(id, Json(info))| {
+                Ok(EngineSummary {
+                    id: EngineId::try_from(id)?,
+                    name: info.name,
+                    engine_type: info.engine_type,
+                    state: info.state,
+                    create_time: info.create_time,
+                })
+            })
+            .collect()
+    }
+
+    async fn list_engines_need_update(
+        &self,
+        engine_types: &[EngineType],
+        limit: usize,
+    ) -> Result<Vec<EngineIdAndInfo>> {
+        let engines: Vec<EngineNeedUpdate> = ::sqlx::query_as(Self::SELECT_ENGINES_NEED_UPDATE)
+            .bind(Self::convert_system_time_to_secs(SystemTime::now()))
+            .bind(Self::get_type_names(engine_types)?)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(RucatError::fail_to_read_database)?;
+        engines
+            .into_iter()
+            .map(Self::convert_engine_need_update)
+            .collect()
+    }
+
+    async fn claim_engines_need_update(
+        &self,
+        engine_types: &[EngineType],
+        limit: usize,
+        now: SystemTime,
+        claim_duration: Duration,
+    ) -> Result<Vec<EngineIdAndInfo>> {
+        // the write lock is held from selecting the engines to claiming them
+        let mut transaction = self.begin_immediate().await?;
+        let engines: Vec<EngineNeedUpdate> = ::sqlx::query_as(Self::SELECT_ENGINES_NEED_UPDATE)
+            .bind(Self::convert_system_time_to_secs(now))
+            .bind(Self::get_type_names(engine_types)?)
+            .bind(limit as i64)
+            .fetch_all(&mut *transaction)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        let sql = r#"
+            UPDATE engines SET next_update_time = ?1
+            WHERE id IN (SELECT value FROM json_each(?2));
+        "#;
+        ::sqlx::query(sql)
+            .bind(Self::convert_system_time_to_secs(now + claim_duration))
+            .bind(Json(
+                engines.iter().map(|(id, _, _)| id).collect::<Vec<_>>(),
+            ))
+            .execute(&mut *transaction)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        transaction
+            .commit()
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        engines
+            .into_iter()
+            .map(Self::convert_engine_need_update)
+            .collect()
+    }
+
+    async fn count_engines_by_state(
+        &self,
+        engine_types: &[EngineType],
+        project: Option<&str>,
+    ) -> Result<BTreeMap<EngineStateKind, usize>> {
+        let sql = r#"
+            SELECT state, COUNT(*) FROM engines
+            WHERE (json_array_length(?1) = 0
+                    OR info ->> '$.engine_type' IN (SELECT value FROM json_each(?1)))
+                AND (?2 IS NULL OR info ->> '$.project' = ?2)
+            GROUP BY state;
+        "#;
+        let counts: Vec<(String, i64)> = ::sqlx::query_as(sql)
+            .bind(Self::get_type_names(engine_types)?)
+            .bind(project)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(RucatError::fail_to_read_database)?;
+        counts
+            .into_iter()
+            .map(|(state, count)| {
+                let state =
+                    EngineStateKind::from_str(&state).map_err(RucatError::fail_to_read_database)?;
+                Ok((state, count as usize))
+            })
+            .collect()
+    }
+
+    async fn remove_engines_in_states(
+        &self,
+        states: &BTreeSet<EngineStateKind>,
+        older_than: SystemTime,
+        project: Option<&str>,
+        deleted_time: SystemTime,
+    ) -> Result<Vec<EngineId>> {
+        let states = Self::get_state_names(states);
+        let older_than = Self::convert_system_time_to_secs(older_than);
+        let mut transaction = self.begin_immediate().await?;
+        let sql = r#"
+            UPDATE engine_events SET deleted_time = ?4, project = engines.info ->> '$.project'
+            FROM engines
+            WHERE engines.id = engine_events.engine
+                AND engines.state IN (SELECT value FROM json_each(?1))
+                AND engines.state_update_time < ?2
+                AND (?3 IS NULL OR engines.info ->> '$.project' = ?3);
+        "#;
+        ::sqlx::query(sql)
+            .bind(&states)
+            .bind(older_than)
+            .bind(project)
+            .bind(Self::convert_system_time_to_secs(deleted_time))
+            .execute(&mut *transaction)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        let sql = r#"
+            DELETE FROM engines
+            WHERE state IN (SELECT value FROM json_each(?1)) AND state_update_time < ?2
+                AND (?3 IS NULL OR info ->> '$.project' = ?3)
+            RETURNING id;
+        "#;
+        let ids: Vec<(String,)> = ::sqlx::query_as(sql)
+            .bind(&states)
+            .bind(older_than)
+            .bind(project)
+            .fetch_all(&mut *transaction)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        transaction
+            .commit()
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        ids.into_iter()
+            .map(|(id,)| EngineId::try_from(id))
+            .collect()
+    }
+
+    async fn list_triggered_engines(&self) -> Result<Vec<TriggeredEngine>> {
+        // Engines in Trigger* states always have next_update_time set to the trigger timeout.
+        let sql = r#"
+            SELECT id, info, state_update_time, next_update_time FROM engines
+            WHERE state IN (SELECT value FROM json_each(?1)) AND next_update_time IS NOT NULL
+            ORDER BY id;
+        "#;
+        let records: Vec<(String, Json<EngineInfo>, i64, i64)> = ::sqlx::query_as(sql)
+            .bind(Self::get_state_names(&[
+                EngineStateKind::TriggerStart,
+                EngineStateKind::TriggerTermination,
+                EngineStateKind::ErrorTriggerClean,
+            ]))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(RucatError::fail_to_read_database)?;
+        records
+            .into_iter()
+            .map(|(id, Json(info), state_update_time, next_update_time)| {
+                Ok(TriggeredEngine {
+                    id: EngineId::try_from(id)?,
+                    info,
+                    state_update_time: Some(Self::convert_secs_to_system_time(state_update_time)),
+                    trigger_timeout_time: Self::convert_secs_to_system_time(next_update_time),
+                })
+            })
+            .collect()
+    }
+
+    async fn append_engine_event(
+        &self,
+        id: &EngineId,
+        old_state: &EngineState,
+        new_state: &EngineState,
+        source: &str,
+        timestamp: SystemTime,
+    ) -> Result<()> {
+        Self::insert_event(
+            &self.pool,
+            id,
+            old_state,
+            new_state,
+            source,
+            None,
+            Self::convert_system_time_to_secs(timestamp),
+        )
+        .await
+    }
+
+    async fn get_engine_events(&self, id: &EngineId, limit: usize) -> Result<Vec<EngineEvent>> {
+        let sql = r#"
+            SELECT old_state, new_state, source, actor, impersonator, time FROM engine_events
+            WHERE engine = ?1
+            ORDER BY id DESC
+            LIMIT ?2;
+        "#;
+        type EventRow = (
+            Json<EngineState>,
+            Json<EngineState>,
+            String,
+            Option<String>,
+            Option<String>,
+            i64,
+        );
+        let records: Vec<EventRow> = ::sqlx::query_as(sql)
+            .bind(id.to_string())
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(RucatError::fail_to_read_database)?;
+        Ok(records
+            .into_iter()
+            .map(
+                |(Json(old_state), Json(new_state), source, actor, impersonator, time)| {
+                    EngineEvent {
+                        old_state,
+                        new_state,
+                        source,
+                        actor,
+                        impersonator,
+                        time: EngineTime::from_system_time(Self::convert_secs_to_system_time(time)),
+                    }
+                },
+            )
+            .collect())
+    }
+
+    async fn purge_engine_events(
+        &self,
+        deleted_before: SystemTime,
+        project: Option<&str>,
+    ) -> Result<usize> {
+        let sql = r#"
+            DELETE FROM engine_events
+            WHERE deleted_time < ?1 AND (?2 IS NULL OR project = ?2);
+        "#;
+        let purged = ::sqlx::query(sql)
+            .bind(Self::convert_system_time_to_secs(deleted_before))
+            .bind(project)
+            .execute(&self.pool)
+            .await
+            .map_err(RucatError::fail_to_update_database)?
+            .rows_affected();
+        Ok(purged as usize)
+    }
+
+    async fn health_check(&self) -> Result<DatabaseHealth> {
+        let start = Instant::now();
+        let (version,): (String,) = ::sqlx::query_as("SELECT sqlite_version();")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(RucatError::fail_to_connect_database)?;
+        Ok(DatabaseHealth {
+            latency: start.elapsed(),
+            version: Some(version),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::borrow::Cow;
+
+    use ::tempfile::TempDir;
+
+    use super::*;
+    use crate::blob_store::BlobRef;
+    use crate::engine::{CreateEngineRequest, EngineConfig, IdGenerator, RandomIdGenerator};
+    use crate::error::RucatErrorType;
+
+    /// Open a new database file in `dir`, which is removed when `dir` is dropped.
+    async fn new_client(dir: &TempDir) -> Result<SqliteClient> {
+        let path = dir.path().join("rucat.db");
+        SqliteClient::new(path.to_str().unwrap()).await
+    }
+
+    fn temp_dir() -> Result<TempDir> {
+        TempDir::new().map_err(RucatError::fail_to_connect_database)
+    }
+
+    fn get_spark_engine_info(name: &str) -> Result<EngineInfo> {
+        let request = CreateEngineRequest {
+            name: name.to_owned(),
+            engine_type: EngineType::Spark,
+            version: "3.5.4".to_owned(),
+            config: None,
+            start_in_progress_timeout_secs: None,
+            features: None,
+            start: None,
+        };
+        EngineInfo::from_create_request(request, EngineTime::now())
+    }
+
+    async fn add_spark_engine_with_name(client: &SqliteClient, name: &str) -> Result<EngineId> {
+        let id = RandomIdGenerator.generate();
+        client
+            .add_engine(&id, get_spark_engine_info(name)?, Some(UNIX_EPOCH), None)
+            .await?;
+        Ok(id)
+    }
+
+    async fn add_spark_engine(client: &SqliteClient) -> Result<EngineId> {
+        add_spark_engine_with_name(client, "spark").await
+    }
+
+    fn idempotency_key(expire_time: SystemTime) -> Option<IdempotencyKey> {
+        Some(IdempotencyKey {
+            key: "key-1".to_owned(),
+            expire_time,
+        })
+    }
+
+    #[tokio::test]
+    async fn add_and_get_engine() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let id = EngineId::try_from("spark")?;
+        let mut info = get_spark_engine_info("spark")?;
+        info.config = EngineConfig::from([(
+            Cow::Borrowed("spark.executor.instances"),
+            Cow::Borrowed("2"),
+        )]);
+        info.created_by = Some("alice".to_owned());
+
+        assert_eq!(client.add_engine(&id, info.clone(), None, None).await?, id);
+
+        assert_eq!(client.get_engine(&id).await?, Some(info));
+        assert_eq!(
+            client.get_engine_state(&id).await?,
+            Some(EngineState::WaitToStart)
+        );
+        let nonexistent = EngineId::try_from("nonexistent")?;
+        assert!(client.get_engine(&nonexistent).await?.is_none());
+        assert!(client.get_engine_state(&nonexistent).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn engines_persist_after_reopen() -> Result<()> {
+        let dir = temp_dir()?;
+        let id = add_spark_engine(&new_client(&dir).await?).await?;
+
+        let client = new_client(&dir).await?;
+
+        assert!(client.get_engine(&id).await?.is_some());
+        let health = client.health_check().await?;
+        assert!(health
+            .version
+            .is_some_and(|version| version.starts_with("3.")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_engine_with_existing_id() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let id = add_spark_engine(&client).await?;
+        let info = client.get_engine(&id).await?.unwrap();
+
+        let result = client.add_engine(&id, info, None, None).await;
+
+        assert!(result.is_err_and(|e| e.to_string().starts_with("Fail to update database:")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_engine_with_used_idempotency_key() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let expire_time = SystemTime::now() + Duration::from_secs(3600);
+        let first = EngineId::try_from("first")?;
+        let second = EngineId::try_from("second")?;
+
+        for id in [&first, &second] {
+            let added = client
+                .add_engine(
+                    id,
+                    get_spark_engine_info("spark")?,
+                    None,
+                    idempotency_key(expire_time),
+                )
+                .await?;
+            assert_eq!(added, first);
+        }
+        assert!(client.get_engine(&second).await?.is_none());
+
+        // the key is expired
+        let third = EngineId::try_from("third")?;
+        ::sqlx::query("UPDATE idempotency_keys SET expire_time = 0;")
+            .execute(&client.pool)
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        let added = client
+            .add_engine(
+                &third,
+                get_spark_engine_info("spark")?,
+                None,
+                idempotency_key(expire_time),
+            )
+            .await?;
+        assert_eq!(added, third);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_engine_state() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let id = add_spark_engine(&client).await?;
+
+        let result = client
+            .update_engine_state(
+                &id,
+                &EngineState::Running,
+                &EngineState::Terminated,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        assert!(matches!(
+            result,
+            Some(UpdateEngineStateResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+        assert_eq!(client.list_engines_need_update(&[], 100).await?.len(), 1);
+
+        let result = client
+            .update_engine_state(
+                &id,
+                &EngineState::WaitToStart,
+                &EngineState::TriggerStart,
+                None,
+                Some(0),
+                None,
+            )
+            .await?;
+        assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+        let info = client.get_engine(&id).await?.unwrap();
+        assert_eq!(info.state, EngineState::TriggerStart);
+        assert_eq!(info.revision, 1);
+        assert!(client.list_engines_need_update(&[], 100).await?.is_empty());
+
+        // rechecking the engine does not bump the revision
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::TriggerStart,
+                &EngineState::TriggerStart,
+                None,
+                Some(1),
+                None,
+            )
+            .await?;
+        assert_eq!(client.get_engine(&id).await?.unwrap().revision, 1);
+
+        let result = client
+            .update_engine_state(
+                &id,
+                &EngineState::TriggerStart,
+                &EngineState::StartInProgress,
+                None,
+                Some(0),
+                None,
+            )
+            .await;
+        assert!(result.is_err_and(|e| {
+            e.get_error_type() == &RucatErrorType::PreconditionFailed
+                && e.to_string().contains(&format!(
+                    "Engine {} is at revision 1, but revision Some(0) is expected",
+                    id
+                ))
+        }));
+
+        let nonexistent = EngineId::try_from("nonexistent")?;
+        assert!(client
+            .update_engine_state(
+                &nonexistent,
+                &EngineState::WaitToStart,
+                &EngineState::TriggerStart,
+                None,
+                None,
+                None
+            )
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_engine_state_concurrently() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        for _ in 0..16 {
+            let id = add_spark_engine(&client).await?;
+            // two state monitors try to acquire the same engine
+            let tasks = [EngineState::TriggerStart, EngineState::Terminated].map(|after| {
+                let client = client.clone();
+                let id = id.clone();
+                tokio::spawn(async move {
+                    client
+                        .update_engine_state(
+                            &id,
+                            &EngineState::WaitToStart,
+                            &after,
+                            None,
+                            None,
+                            None,
+                        )
+                        .await
+                })
+            });
+            let mut successes = 0;
+            for task in tasks {
+                match task.await.map_err(RucatError::fail_to_update_database)?? {
+                    Some(UpdateEngineStateResult::Success) => successes += 1,
+                    Some(UpdateEngineStateResult::Fail { current_state }) => {
+                        assert_ne!(current_state, EngineState::WaitToStart)
+                    }
+                    None => panic!("engine {} should exist", id),
+                }
+            }
+            assert_eq!(successes, 1);
+            assert_eq!(client.get_engine(&id).await?.unwrap().revision, 1);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_name_and_config_in_expected_states() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let id = add_spark_engine(&client).await?;
+        let update = EngineInfoUpdate {
+            name: Some("renamed".to_owned()),
+            config: Some(EngineConfig::from([(
+                "spark.executor.instances".into(),
+                "2".into(),
+            )])),
+            blobs: Some(BTreeMap::from([(
+                "log_tail".to_owned(),
+                BlobRef {
+                    key: "engines/log_tail".to_owned(),
+                    size: 3,
+                    checksum: "abc".to_owned(),
+                },
+            )])),
+        };
+
+        let result = client
+            .update_engine_info(&id, update.clone(), Some(&[EngineState::Terminated]), None)
+            .await?;
+        assert!(matches!(
+            result,
+            Some(UpdateEngineStateResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+        assert_eq!(client.get_engine(&id).await?.unwrap().revision, 0);
+
+        let result = client
+            .update_engine_info(&id, update, Some(&[EngineState::WaitToStart]), Some(0))
+            .await?;
+        assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+        let info = client.get_engine(&id).await?.unwrap();
+        assert_eq!(info.name, "renamed");
+        assert_eq!(
+            info.config.get("spark.executor.instances"),
+            Some(&Cow::Borrowed("2"))
+        );
+        assert_eq!(info.blobs["log_tail"].size, 3);
+        assert_eq!(info.revision, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn return_removed_engine() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let id = add_spark_engine(&client).await?;
+        let info = client.get_engine(&id).await?.unwrap();
+        let result = client
+            .remove_engine(&id, &EngineState::Running, None, UNIX_EPOCH)
+            .await?;
+        assert!(matches!(
+            result,
+            Some(RemoveEngineResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+        let result = client
+            .remove_engine(&id, &EngineState::WaitToStart, None, UNIX_EPOCH)
+            .await?;
+        assert!(matches!(result, Some(RemoveEngineResult::Success(removed)) if *removed == info));
+        assert!(client
+            .remove_engine(&id, &EngineState::WaitToStart, None, UNIX_EPOCH)
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keep_last_error_when_leaving_error_state() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let id = add_spark_engine(&client).await?;
+        let error = EngineState::ErrorClean(Cow::Borrowed("fail to start"));
+        client
+            .update_engine_state(&id, &EngineState::WaitToStart, &error, None, None, None)
+            .await?;
+        client
+            .update_engine_state(&id, &error, &EngineState::WaitToStart, None, None, None)
+            .await?;
+        assert_eq!(
+            client.get_engine(&id).await?.unwrap().last_error,
+            Some(Cow::Borrowed("fail to start"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_engine_config_rename_and_acl() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let id = add_spark_engine(&client).await?;
+        let config = EngineConfig::from([(
+            Cow::Borrowed("spark.executor.instances"),
+            Cow::Borrowed("2"),
+        )]);
+
+        let result = client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    config: Some(config.clone()),
+                    ..Default::default()
+                },
+                Some(&[EngineState::Terminated]),
+                None,
+            )
+            .await?;
+        assert!(matches!(
+            result,
+            Some(UpdateEngineStateResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+        let result = client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    config: Some(config.clone()),
+                    ..Default::default()
+                },
+                Some(&[EngineState::WaitToStart]),
+                Some(0),
+            )
+            .await?;
+        assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+        client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    name: Some("renamed".to_owned()),
+                    ..Default::default()
+                },
+                None,
+                Some(1),
+            )
+            .await?;
+        client
+            .set_engine_acl(&id, BTreeSet::from(["bob".to_owned()]), Some(2))
+            .await?;
+        client
+            .update_engine_endpoints(
+                &id,
+                EngineEndpoints::from([("connect".to_owned(), "sc://spark:15002".to_owned())]),
+            )
+            .await?;
+
+        let info = client.get_engine(&id).await?.unwrap();
+        assert_eq!(info.config, config);
+        assert_eq!(info.name, "renamed");
+        assert_eq!(
+            info.allowed_principals,
+            Some(BTreeSet::from(["bob".to_owned()]))
+        );
+        assert_eq!(info.endpoints.len(), 1);
+        assert_eq!(info.revision, 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_engines_filtered_and_sorted() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let etl = add_spark_engine_with_name(&client, "Nightly ETL").await?;
+        let etl_test = add_spark_engine_with_name(&client, "etl-test").await?;
+        let adhoc = add_spark_engine_with_name(&client, "adhoc").await?;
+        client
+            .update_engine_state(
+                &adhoc,
+                &EngineState::WaitToStart,
+                &EngineState::Terminated,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let filter = EngineFilter {
+            name_contains: "etl".to_owned(),
+            ..EngineFilter::default()
+        };
+        let by_name = EngineSort {
+            key: EngineSortKey::Name,
+            descending: true,
+        };
+        assert_eq!(
+            client.list_engines(0, 10, &filter, by_name).await?,
+            vec![etl_test.clone(), etl.clone()]
+        );
+        assert_eq!(
+            client.list_engines(1, 10, &filter, by_name).await?,
+            vec![etl.clone()]
+        );
+
+        let terminated = EngineFilter {
+            states: BTreeSet::from([EngineStateKind::Terminated]),
+            ..EngineFilter::default()
+        };
+        let summaries = client
+            .list_engine_summaries(0, 10, &terminated, EngineSort::default())
+            .await?;
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].id, adhoc);
+        assert_eq!(summaries[0].name, "adhoc");
+        assert_eq!(summaries[0].state, EngineState::Terminated);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn count_and_remove_engines_in_states() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let running = add_spark_engine(&client).await?;
+        add_spark_engine(&client).await?;
+        client
+            .update_engine_state(
+                &running,
+                &EngineState::WaitToStart,
+                &EngineState::Running,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        assert_eq!(
+            client.count_engines_by_state(&[], None).await?,
+            BTreeMap::from([
+                (EngineStateKind::WaitToStart, 1),
+                (EngineStateKind::Running, 1)
+            ])
+        );
+        assert!(client
+            .count_engines_by_state(&[], Some("analytics"))
+            .await?
+            .is_empty());
+        let states = BTreeSet::from([EngineStateKind::Running]);
+        assert!(client
+            .remove_engines_in_states(&states, UNIX_EPOCH, None, UNIX_EPOCH)
+            .await?
+            .is_empty());
+        let later = SystemTime::now() + Duration::from_secs(2);
+        let deleted_time = UNIX_EPOCH + Duration::from_secs(60);
+        assert_eq!(
+            client
+                .remove_engines_in_states(&states, later, None, deleted_time)
+                .await?,
+            vec![running.clone()]
+        );
+        assert!(client.get_engine(&running).await?.is_none());
+        // the history of the removed engine is marked as deleted
+        assert_eq!(client.purge_engine_events(deleted_time, None).await?, 0);
+        let after = deleted_time + Duration::from_secs(1);
+        assert_eq!(client.purge_engine_events(after, None).await?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_triggered_engines() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let triggered = add_spark_engine(&client).await?;
+        add_spark_engine(&client).await?;
+        let timeout = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        client
+            .update_engine_state(
+                &triggered,
+                &EngineState::WaitToStart,
+                &EngineState::TriggerStart,
+                Some(timeout),
+                None,
+                None,
+            )
+            .await?;
+
+        let engines = client.list_triggered_engines().await?;
+        assert_eq!(engines.len(), 1);
+        assert_eq!(engines[0].id, triggered);
+        assert_eq!(engines[0].trigger_timeout_time, timeout);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_engines_in_order() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        for name in ["etl", "ml"] {
+            client
+                .add_engine(
+                    &EngineId::try_from(name)?,
+                    get_spark_engine_info(name)?,
+                    None,
+                    None,
+                )
+                .await?;
+        }
+
+        let ids = ["ml", "nonexistent", "etl"]
+            .map(EngineId::try_from)
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        let engines = client.get_engines(&ids).await?;
+        let names = engines
+            .iter()
+            .map(|(id, info)| (id.to_string(), info.as_ref().map(|info| info.name.as_str())))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                ("ml".to_owned(), Some("ml")),
+                ("nonexistent".to_owned(), None),
+                ("etl".to_owned(), Some("etl"))
+            ]
+        );
+        assert!(client.get_engines(&[]).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_state_changes_as_events() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?.with_event_source("server");
+        let id = add_spark_engine(&client).await?;
+        let states = [
+            EngineState::WaitToStart,
+            EngineState::TriggerStart,
+            EngineState::StartInProgress,
+            EngineState::Running,
+        ];
+        for pair in states.windows(2) {
+            client
+                .update_engine_state(&id, &pair[0], &pair[1], None, None, None)
+                .await?;
+        }
+        // a recheck without changing the state is not an event
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::Running,
+                &EngineState::Running,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let events = client.get_engine_events(&id, 10).await?;
+        let transitions = events
+            .iter()
+            .map(|event| (&event.old_state, &event.new_state, event.source.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            transitions,
+            [
+                (&states[2], &states[3], "server"),
+                (&states[1], &states[2], "server"),
+                (&states[0], &states[1], "server"),
+            ]
+        );
+        assert_eq!(client.get_engine_events(&id, 1).await?, events[..1]);
+
+        client
+            .append_engine_event(
+                &id,
+                &EngineState::Running,
+                &EngineState::WaitToTerminate,
+                "state_monitor",
+                SystemTime::now(),
+            )
+            .await?;
+        let latest = client.get_engine_events(&id, 1).await?;
+        assert_eq!(latest[0].new_state, EngineState::WaitToTerminate);
+        assert_eq!(latest[0].source, "state_monitor");
+
+        let nonexistent = EngineId::try_from("nonexistent")?;
+        assert!(client.get_engine_events(&nonexistent, 10).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_actor_of_state_changes() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let id = add_spark_engine(&client).await?;
+        let actor = EngineActor {
+            name: "alice".to_owned(),
+            impersonator: Some("admin".to_owned()),
+        };
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::WaitToStart,
+                &EngineState::TriggerStart,
+                None,
+                None,
+                Some(&actor),
+            )
+            .await?;
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::TriggerStart,
+                &EngineState::StartInProgress,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let events = client.get_engine_events(&id, 10).await?;
+        let actors = events
+            .iter()
+            .map(|event| (event.actor.as_deref(), event.impersonator.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(actors, [(None, None), (Some("alice"), Some("admin"))]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn purge_events_of_deleted_engines() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let kept = add_spark_engine(&client).await?;
+        let removed = add_spark_engine(&client).await?;
+        for id in [&kept, &removed] {
+            client
+                .update_engine_state(
+                    id,
+                    &EngineState::WaitToStart,
+                    &EngineState::Terminated,
+                    None,
+                    None,
+                    None,
+                )
+                .await?;
+        }
+        let deleted_time = UNIX_EPOCH + Duration::from_secs(60);
+        client
+            .remove_engine(&removed, &EngineState::Terminated, None, deleted_time)
+            .await?;
+
+        // the history is kept for the ttl from the deletion time
+        assert_eq!(client.purge_engine_events(deleted_time, None).await?, 0);
+        assert_eq!(client.get_engine_events(&removed, 10).await?.len(), 1);
+
+        // the removed engine is not in the project
+        let after = deleted_time + Duration::from_secs(1);
+        assert_eq!(client.purge_engine_events(after, Some("project")).await?, 0);
+        assert_eq!(client.get_engine_events(&removed, 10).await?.len(), 1);
+
+        assert_eq!(client.purge_engine_events(after, None).await?, 1);
+        assert!(client.get_engine_events(&removed, 10).await?.is_empty());
+        assert_eq!(client.get_engine_events(&kept, 10).await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_most_overdue_engines_first() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        for (id, secs) in [("a", 30), ("b", 10), ("c", 20), ("d", 10)] {
+            client
+                .add_engine(
+                    &EngineId::try_from(id)?,
+                    get_spark_engine_info(id)?,
+                    Some(UNIX_EPOCH + Duration::from_secs(secs)),
+                    None,
+                )
+                .await?;
+        }
+
+        let engines = client.list_engines_need_update(&[], 3).await?;
+        let ids = engines
+            .into_iter()
+            .map(|engine| engine.id.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["b", "d", "c"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn claim_engines_need_update() -> Result<()> {
+        let dir = temp_dir()?;
+        let client = new_client(&dir).await?;
+        let mut ids = BTreeSet::new();
+        for _ in 0..10 {
+            ids.insert(add_spark_engine(&client).await?);
+        }
+        let claim = || async {
+            let engines = client
+                .claim_engines_need_update(&[], 6, SystemTime::now(), Duration::from_secs(60))
+                .await?;
+            Ok::<_, RucatError>(
+                engines
+                    .into_iter()
+                    .map(|engine| engine.id)
+                    .collect::<BTreeSet<_>>(),
+            )
+        };
+
+        let (first, second) = ::tokio::join!(claim(), claim());
+        let (first, second) = (first?, second?);
+        assert!(first.is_disjoint(&second));
+        assert_eq!(first.len().max(second.len()), 6);
+        assert_eq!(first.union(&second).cloned().collect::<BTreeSet<_>>(), ids);
+        // claimed engines are not listed until the claim expires
+        assert!(client.list_engines_need_update(&[], 100).await?.is_empty());
+        assert!(claim().await?.is_empty());
+        Ok(())
+    }
+}
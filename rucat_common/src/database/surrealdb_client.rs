@@ -1,23 +1,40 @@
 //! Client of SurrealDB
 
-use ::std::time::{SystemTime, UNIX_EPOCH};
+use ::std::{
+    collections::{BTreeMap, BTreeSet},
+    future::Future,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use ::serde::Deserialize;
+use ::serde::{de::DeserializeOwned, Deserialize};
 
-use crate::engine::{CreateEngineRequest, EngineId};
+use crate::engine::{
+    EngineActor, EngineEndpoints, EngineEvent, EngineId, EngineSummary, EngineTime,
+};
 use crate::error::{Result, RucatError};
 use crate::{
-    config::Credentials,
-    engine::{EngineInfo, EngineState},
+    config::{ConnectRetry, Credentials, SurrealConfig},
+    engine::{EngineInfo, EngineInfoSummary, EngineState, EngineStateKind, EngineType},
 };
 use ::anyhow::anyhow;
-use ::surrealdb::opt::auth::Root;
+use ::native_tls::{Certificate, TlsConnector};
+use ::surrealdb::opt::{auth::Root, Config};
+use ::tokio::time::{sleep, timeout, Instant};
+use ::tracing::{info, warn};
 use surrealdb::{
-    engine::remote::ws::{Client, Ws},
+    engine::any::{connect, Any},
     Surreal,
 };
 
-use super::{Database, EngineIdAndInfo, UpdateEngineStateResult};
+use super::{
+    get_last_error,
+    legacy_migration::{
+        ensure_not_migrated, plan_migration, LegacyEngineRecord, MigrationMarker, MigrationSummary,
+    },
+    with_query_timeout, Database, DatabaseHealth, EngineFilter, EngineIdAndInfo, EngineInfoUpdate,
+    EngineSort, EngineSortKey, IdempotencyKey, RemoveEngineResult, TriggeredEngine,
+    UpdateEngineStateResult, DEFAULT_EVENT_SOURCE,
+};
 
 /// Client to interact with the database.
 /// Store the metadata of Engines
@@ -26,51 +43,515 @@ use super::{Database, EngineIdAndInfo, UpdateEngineStateResult};
 /// {
 ///   "id": "record id",
 ///   "info": "engine info",
-///   "next_update_time": "timestamp that state monitor should do info update after it"
+///   "next_update_time": "timestamp that state monitor should do info update after it",
+///   "state_update_time": "timestamp when the engine state is updated"
+/// }
+/// Idempotency key of adding an engine, whose record id is the key:
+/// {
+///   "engine": "id of the engine added with the key",
+///   "expire_time": "timestamp after which the key can be used to add another engine"
+/// }
+/// State change of an engine:
+/// {
+///   "id": "record id",
+///   "engine": "id of the engine",
+///   "seq": "order of the event among the events of the engine, starting from 1",
+///   "old_state": "state before the change",
+///   "new_state": "state after the change",
+///   "source": "component changing the state",
+///   "actor": "principal requesting the change, if any",
+///   "impersonator": "admin acting on behalf of the actor, if any",
+///   "time": "timestamp of the change",
+///   "deleted_time": "timestamp when the engine was removed",
+///   "project": "project of the engine when it was removed"
 /// }
+/// Result of a conditional update of an engine, which may fail on the revision of the engine,
+/// see [SurrealDBClient::check_revision].
+#[derive(Deserialize)]
+enum ConditionalUpdateResult {
+    Success,
+    Fail { current_state: EngineState },
+    RevisionMismatch { current_revision: u64 },
+}
+
+/// Result of a conditional removal of an engine, see [ConditionalUpdateResult].
+#[derive(Deserialize)]
+enum ConditionalRemoveResult {
+    Success { info: Box<EngineInfo> },
+    Fail { current_state: EngineState },
+    RevisionMismatch { current_revision: u64 },
+}
+
+/// Engine to update selected by [SurrealDBClient::SELECT_ENGINES_NEED_UPDATE].
+#[derive(Deserialize)]
+struct EngineNeedUpdate {
+    id: String,
+    info: EngineInfoSummary,
+    state_update_time: Option<u64>,
+}
+
+impl TryFrom<EngineNeedUpdate> for EngineIdAndInfo {
+    type Error = RucatError;
+
+    fn try_from(engine: EngineNeedUpdate) -> Result<Self> {
+        Ok(Self {
+            id: EngineId::try_from(engine.id)?,
+            info: engine.info,
+            state_update_time: engine
+                .state_update_time
+                .map(SurrealDBClient::convert_secs_to_system_time),
+        })
+    }
+}
+
+/// Once connected, the client reconnects by itself every second when the connection drops,
+/// signing in and selecting the namespace and database again,
+/// so the queries sent after a drop wait for the reconnection instead of failing.
 #[derive(Clone)]
 pub struct SurrealDBClient {
-    client: Surreal<Client>,
+    client: Surreal<Any>,
+    /// Table of the engines, in the namespace and database selected by the `client`.
+    table: String,
+    /// Timeout of each query, see [SurrealConfig::query_timeout_secs].
+    query_timeout: Duration,
+    /// Source of the events recorded by [Database::update_engine_state].
+    event_source: &'static str,
 }
 
 impl SurrealDBClient {
-    const TABLE: &'static str = "engines";
-    const NAMESPACE: &'static str = "rucat";
-    const DATABASE: &'static str = "rucat";
+    const MIGRATION_TABLE: &'static str = "migrations";
+    /// Idempotency keys of adding engines, whose record ids are the keys.
+    const IDEMPOTENCY_KEY_TABLE: &'static str = "idempotency_keys";
+    /// State changes of engines, see [Database::get_engine_events].
+    const EVENT_TABLE: &'static str = "engine_events";
+    const LEGACY_MIGRATION_ID: &'static str = "legacy_schema";
+    /// Timeout of [Database::health_check] if the query timeout is longer, so that probes fail fast.
+    const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+    /// Attempts of [Database::claim_engines_need_update] conflicting with concurrent claims.
+    const MAX_CLAIM_ATTEMPTS: u32 = 5;
 
-    /// Create a new [SurrealDBClient] to connect to an existing surreal database.
-    pub async fn new(credentials: Option<&Credentials>, uri: String) -> Result<Self> {
-        let client = Surreal::new::<Ws>(&uri)
-            .await
-            .map_err(RucatError::fail_to_connect_database)?;
-        if let Some(Credentials { username, password }) = credentials {
-            client
-                .signin(Root { username, password })
+    /// Create a new [SurrealDBClient] to connect to an existing surreal database,
+    /// retrying by [SurrealConfig::connect_retry] if the database cannot be connected, e.g. it is not up yet.
+    /// The names of [SurrealConfig] are checked to be valid identifiers and the CA certificate is read before connecting.
+    ///
+    /// Failures of connecting, including the TLS handshake, are distinguished from failures of signing in by the error message,
+    /// and only the former are retried.
+    /// Signing in and the following queries fail with [RucatError::database_timeout] if not done in [SurrealConfig::query_timeout_secs].
+    pub async fn new(config: &SurrealConfig) -> Result<Self> {
+        let SurrealConfig {
+            credentials,
+            uri,
+            connect_retry,
+            namespace,
+            database,
+            table,
+            tls,
+            ca_path,
+            query_timeout_secs,
+        } = config;
+        Self::validate_names(namespace, database, table)?;
+        // a secret that cannot be resolved is not retried
+        let root = credentials
+            .as_ref()
+            .map(|Credentials { username, password }| Ok((username, password.resolve()?)))
+            .transpose()?;
+        let surreal_config = Self::surreal_config(*tls, ca_path.as_deref())?;
+        let url = format!("{}://{}", if *tls { "wss" } else { "ws" }, uri);
+        let client = connect_with_retry(connect_retry, || async {
+            connect((url.as_str(), surreal_config.clone()))
                 .await
-                .map_err(RucatError::fail_to_connect_database)?;
+                .map_err(|e| {
+                    let handshake = if *tls {
+                        ", including the TLS handshake"
+                    } else {
+                        ""
+                    };
+                    RucatError::fail_to_connect_database(anyhow!(
+                        "Fail to connect to `{}`{}: {}",
+                        url,
+                        handshake,
+                        e
+                    ))
+                })
+        })
+        .await?;
+        let query_timeout = Duration::from_secs(*query_timeout_secs);
+        if let Some((username, password)) = &root {
+            with_query_timeout(query_timeout, client.signin(Root { username, password }))
+                .await?
+                .map_err(|e| {
+                    RucatError::fail_to_connect_database(anyhow!(
+                        "Fail to sign in to `{}` as `{}`: {}",
+                        url,
+                        username,
+                        e
+                    ))
+                })?;
         }
-        client
-            .use_ns(Self::NAMESPACE)
-            .use_db(Self::DATABASE)
+        Self::use_database(client, namespace, database, table, query_timeout).await
+    }
+
+    /// Config of the connection, trusting the CA certificate at `ca_path` in addition to the system CAs.
+    fn surreal_config(tls: bool, ca_path: Option<&str>) -> Result<Config> {
+        let Some(ca_path) = ca_path else {
+            return Ok(Config::new());
+        };
+        if !tls {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "`ca_path` of SurrealDB needs `tls`"
+            )));
+        }
+        let ca = ::std::fs::read(ca_path).map_err(|e| {
+            RucatError::fail_to_load_config(anyhow!(
+                "Fail to read the CA certificate `{}` of SurrealDB: {}",
+                ca_path,
+                e
+            ))
+        })?;
+        let connector = Certificate::from_pem(&ca)
+            .and_then(|ca| TlsConnector::builder().add_root_certificate(ca).build())
+            .map_err(|e| {
+                RucatError::fail_to_load_config(anyhow!(
+                    "Fail to parse the CA certificate `{}` of SurrealDB: {}",
+                    ca_path,
+                    e
+                ))
+            })?;
+        Ok(Config::new().native_tls(connector))
+    }
+
+    /// Create a new [SurrealDBClient] with an empty in-memory database, for testing.
+    #[cfg(test)]
+    async fn new_in_memory() -> Result<Self> {
+        let client = connect("memory")
             .await
             .map_err(RucatError::fail_to_connect_database)?;
-        Ok(Self { client })
+        Self::use_database(
+            client,
+            &SurrealConfig::default_namespace(),
+            &SurrealConfig::default_database(),
+            &SurrealConfig::default_table(),
+            Duration::from_secs(SurrealConfig::default_query_timeout_secs()),
+        )
+        .await
+    }
+
+    async fn use_database(
+        client: Surreal<Any>,
+        namespace: &str,
+        database: &str,
+        table: &str,
+        query_timeout: Duration,
+    ) -> Result<Self> {
+        with_query_timeout(query_timeout, client.use_ns(namespace).use_db(database))
+            .await?
+            .map_err(RucatError::fail_to_connect_database)?;
+        // events are read by engine, and purged by the time when the engine was removed
+        let sql = format!(
+            r#"
+            DEFINE INDEX IF NOT EXISTS engine_idx ON TABLE {events_tb} FIELDS engine;
+            DEFINE INDEX IF NOT EXISTS deleted_time_idx ON TABLE {events_tb} FIELDS deleted_time;
+        "#,
+            events_tb = Self::EVENT_TABLE
+        );
+        with_query_timeout(query_timeout, client.query(sql))
+            .await?
+            .map_err(RucatError::fail_to_connect_database)?
+            .check()
+            .map_err(RucatError::fail_to_connect_database)?;
+        Ok(Self {
+            client,
+            table: table.to_owned(),
+            query_timeout,
+            event_source: DEFAULT_EVENT_SOURCE,
+        })
+    }
+
+    /// Record the state changes of this client with `source`, e.g. `server`, see [EngineEvent::source].
+    pub fn with_event_source(mut self, source: &'static str) -> Self {
+        self.event_source = source;
+        self
+    }
+
+    /// Check the names are identifiers of SurrealDB that need no escaping,
+    /// and the table of engines is not one of the other tables used by rucat.
+    fn validate_names(namespace: &str, database: &str, table: &str) -> Result<()> {
+        for (kind, name) in [
+            ("namespace", namespace),
+            ("database", database),
+            ("table", table),
+        ] {
+            let mut chars = name.chars();
+            let valid = chars
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                return Err(RucatError::fail_to_load_config(anyhow!(
+                    "Invalid SurrealDB {} `{}`, which should only contain ASCII letters, digits and underscores, \
+                        and not start with a digit",
+                    kind,
+                    name
+                )));
+            }
+        }
+        if [
+            Self::MIGRATION_TABLE,
+            Self::IDEMPOTENCY_KEY_TABLE,
+            Self::EVENT_TABLE,
+        ]
+        .contains(&table)
+        {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "SurrealDB table `{}` is used by rucat for other records",
+                table
+            )));
+        }
+        Ok(())
     }
 
     fn convert_system_time_to_secs(time: SystemTime) -> u64 {
         time.duration_since(UNIX_EPOCH).unwrap().as_secs()
     }
+
+    fn convert_secs_to_system_time(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    /// Convert the result of a conditional update into [UpdateEngineStateResult],
+    /// or an error if the engine is not at the `expected_revision`.
+    fn check_revision(
+        id: &EngineId,
+        result: Option<ConditionalUpdateResult>,
+        expected_revision: Option<u64>,
+    ) -> Result<Option<UpdateEngineStateResult>> {
+        match result {
+            None => Ok(None),
+            Some(ConditionalUpdateResult::Success) => Ok(Some(UpdateEngineStateResult::Success)),
+            Some(ConditionalUpdateResult::Fail { current_state }) => {
+                Ok(Some(UpdateEngineStateResult::Fail { current_state }))
+            }
+            Some(ConditionalUpdateResult::RevisionMismatch { current_revision }) => Err(
+                Self::revision_mismatch(id, current_revision, expected_revision),
+            ),
+        }
+    }
+
+    fn revision_mismatch(
+        id: &EngineId,
+        current_revision: u64,
+        expected_revision: Option<u64>,
+    ) -> RucatError {
+        RucatError::precondition_failed(anyhow!(
+            "Engine {} is at revision {}, but revision {:?} is expected",
+            id,
+            current_revision,
+            expected_revision
+        ))
+        .with_engine_id(id)
+    }
+
+    /// Condition of listing engines in `$states` (all engines if empty),
+    /// with names containing `$name_contains` case-insensitively, created by `$created_by` and in `$project` if they are set.
+    /// Error states are stored as `{"<state>": "<message>"}`, and other states as `"<state>"`.
+    const LIST_ENGINES_CONDITION: &'static str = r#"
+                (array::len($states) == 0
+                    || (IF type::is::object(info.state)
+                        THEN object::keys(info.state)[0]
+                        ELSE info.state
+                    END) INSIDE $states)
+                && string::contains(string::lowercase(info.name), string::lowercase($name_contains))
+                && ($created_by IS NONE || info.created_by == $created_by)
+                && ($project IS NONE || info.project == $project)
+    "#;
+
+    /// Query of the engines to update, see [Database::list_engines_need_update], as [EngineNeedUpdate].
+    /// `next_update_time` is selected to be ordered by.
+    const SELECT_ENGINES_NEED_UPDATE: &'static str = r#"
+            SELECT
+                record::id(id) AS id,
+                {
+                    state: info.state,
+                    start_in_progress_timeout_secs: info.start_in_progress_timeout_secs,
+                    features: info.features ?? [],
+                    project: info.project
+                } AS info,
+                state_update_time,
+                next_update_time
+            FROM type::table($tb)
+            WHERE next_update_time != None && next_update_time < $now
+                && (array::len($engine_types) == 0 || info.engine_type INSIDE $engine_types)
+            ORDER BY next_update_time ASC, id ASC
+            LIMIT $limit
+    "#;
+
+    /// Expression of the key of sorting engines, selected as `sort_key`.
+    /// The sort key and order are formatted into the query because SurrealDB cannot bind them.
+    fn get_sort_key(key: EngineSortKey) -> &'static str {
+        match key {
+            EngineSortKey::Id => "record::id(id)",
+            EngineSortKey::CreateTime => "info.create_time",
+            EngineSortKey::Name => "info.name",
+            EngineSortKey::State => {
+                "(IF type::is::object(info.state) THEN object::keys(info.state)[0] ELSE info.state END)"
+            }
+        }
+    }
+
+    /// `ORDER BY` clause of listing engines, engines with the same sort key are sorted by id.
+    fn get_order_by(sort: EngineSort) -> String {
+        let order = if sort.descending { "DESC" } else { "ASC" };
+        format!("ORDER BY sort_key {}, id ASC", order)
+    }
+
+    /// Run the query of listing a page of engines, see [Self::LIST_ENGINES_CONDITION].
+    async fn list_engine_page<T: DeserializeOwned>(
+        &self,
+        sql: String,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+    ) -> Result<Vec<T>> {
+        with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind(("offset", offset))
+                .bind(("limit", limit))
+                .bind(("states", filter.states.clone()))
+                .bind(("name_contains", filter.name_contains.clone()))
+                .bind(("created_by", filter.created_by.clone()))
+                .bind(("project", filter.project.clone())),
+        )
+        .await?
+        .map_err(RucatError::fail_to_read_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_read_database)
+    }
+
+    /// Rewrite engines stored in the legacy schema into the current schema,
+    /// see [super::legacy_migration].
+    /// Each engine is rewritten in its own transaction, only if it is still in the legacy state.
+    /// The migration is refused if it has been done before.
+    /// # Parameters
+    /// - `dry_run`: only return the summary without rewriting any engine.
+    pub async fn migrate_legacy_engines(&self, dry_run: bool) -> Result<MigrationSummary> {
+        let sql = r#"
+            SELECT * OMIT id FROM ONLY type::thing($tb, $id);
+        "#;
+        let marker: Option<MigrationMarker> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", Self::MIGRATION_TABLE))
+                .bind(("id", Self::LEGACY_MIGRATION_ID)),
+        )
+        .await?
+        .map_err(RucatError::fail_to_read_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_read_database)?;
+        ensure_not_migrated(marker.as_ref())?;
+
+        let sql = r#"
+            SELECT VALUE {id: record::id(id), state: info.state, next_update_time: next_update_time}
+            FROM type::table($tb);
+        "#;
+        let records: Vec<LegacyEngineRecord> = with_query_timeout(
+            self.query_timeout,
+            self.client.query(sql).bind(("tb", self.table.clone())),
+        )
+        .await?
+        .map_err(RucatError::fail_to_read_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_read_database)?;
+        let now = SystemTime::now();
+        let summary = plan_migration(records, now);
+        if dry_run {
+            return Ok(summary);
+        }
+
+        let sql = r#"
+            BEGIN TRANSACTION;
+            UPDATE type::thing($tb, $id)
+            SET info.state = $state, next_update_time = $next_update_time, state_update_time = $now
+            WHERE info.state == $legacy_state
+            RETURN VALUE record::id(id);
+            COMMIT TRANSACTION;
+        "#;
+        for engine in &summary.migrated {
+            let updated: Vec<String> = with_query_timeout(
+                self.query_timeout,
+                self.client
+                    .query(sql)
+                    .bind(("tb", self.table.clone()))
+                    .bind(("id", engine.id.to_string()))
+                    .bind(("state", engine.state.clone()))
+                    .bind((
+                        "next_update_time",
+                        engine
+                            .next_update_time
+                            .map(Self::convert_system_time_to_secs),
+                    ))
+                    .bind(("now", Self::convert_system_time_to_secs(now)))
+                    .bind(("legacy_state", engine.legacy_state.clone())),
+            )
+            .await?
+            .map_err(RucatError::fail_to_update_database)?
+            .take(0)
+            .map_err(RucatError::fail_to_update_database)?;
+            if updated.is_empty() {
+                return Err(RucatError::fail_to_update_database(anyhow!(
+                    "Engine {} is updated by others during the migration, \
+                        make sure no rucat server or state monitor is running",
+                    engine.id
+                )));
+            }
+            info!("Migrated engine {} to {:?}", engine.id, engine.state);
+        }
+
+        if summary.unrecognized.is_empty() {
+            let sql = r#"
+                CREATE type::thing($tb, $id) CONTENT $marker;
+            "#;
+            with_query_timeout(
+                self.query_timeout,
+                self.client
+                    .query(sql)
+                    .bind(("tb", Self::MIGRATION_TABLE))
+                    .bind(("id", Self::LEGACY_MIGRATION_ID))
+                    .bind((
+                        "marker",
+                        MigrationMarker {
+                            migrated_at: EngineTime::now(),
+                            migrated: summary.migrated.len(),
+                        },
+                    )),
+            )
+            .await?
+            .map_err(RucatError::fail_to_update_database)?
+            .check()
+            .map_err(RucatError::fail_to_update_database)?;
+        } else {
+            warn!(
+                "{} engine(s) are not recognized, fix them and run the migration again",
+                summary.unrecognized.len()
+            );
+        }
+        Ok(summary)
+    }
 }
 
 impl Database for SurrealDBClient {
     async fn add_engine(
         &self,
-        engine: CreateEngineRequest,
+        id: &EngineId,
+        info: EngineInfo,
         next_update_time: Option<SystemTime>,
+        idempotency_key: Option<IdempotencyKey>,
     ) -> Result<EngineId> {
-        let info: EngineInfo = engine.try_into()?;
-        // always set next_update_time to now  when adding a new engine,
-        // so that the state monitor will update the engine info immediately
+        // `CREATE` fails if the record already exists.
+        // Concurrent transactions writing the same key conflict, so only one of them adds the engine.
         let sql = r#"
             DEFINE FIELD IF NOT EXISTS info.state ON engines TYPE
                 'WaitToStart' |
@@ -84,66 +565,124 @@ impl Database for SurrealDBClient {
                 { ErrorWaitToClean: string} |
                 { ErrorTriggerClean: string } |
                 { ErrorCleanInProgress: string } |
-                { ErrorClean: string };
+                { ErrorClean: string };                                      // 0th return value
 
-            CREATE ONLY type::table($table)
-            SET info = $info, next_update_time = $next_update_time
-            RETURN VALUE record::id(id);
+            BEGIN TRANSACTION;
+            {
+                IF $key IS NOT NONE {
+                    DELETE type::table($key_tb) WHERE expire_time <= $now;
+                    LET $key_id = type::thing($key_tb, $key);
+                    LET $added = (SELECT VALUE engine FROM ONLY $key_id);
+                    IF $added IS NOT NONE {
+                        RETURN $added;                                       // 1st return value
+                    };
+                    CREATE ONLY $key_id SET engine = $id, expire_time = $expire_time;
+                };
+                CREATE ONLY type::thing($table, $id)
+                SET info = $info, next_update_time = $next_update_time, state_update_time = $now;
+                // the history of a deleted engine with the same id is taken over
+                UPDATE type::table($events_tb) SET deleted_time = NONE, project = NONE
+                WHERE engine = $id && deleted_time IS NOT NONE;
+                RETURN $id;                                                  // 1st return value
+            };
+            COMMIT TRANSACTION;
         "#;
 
-        let record: Option<String> = self
-            .client
-            .query(sql)
-            .bind(("table", Self::TABLE))
-            .bind(("info", info))
-            // the next_update_time field is not set in surreal when it is None
-            .bind((
-                "next_update_time",
-                next_update_time.map(Self::convert_system_time_to_secs),
-            ))
-            .await
-            .map_err(RucatError::fail_to_update_database)?
-            .take(1)
-            .map_err(RucatError::fail_to_update_database)?;
-        let id = record.map(EngineId::try_from);
-        id.unwrap_or_else(|| {
-            RucatError::fail_to_update_database(anyhow!("Failed to add engine")).into()
-        })
+        let (key, expire_time) = idempotency_key
+            .map(|IdempotencyKey { key, expire_time }| {
+                (key, Self::convert_system_time_to_secs(expire_time))
+            })
+            .unzip();
+        let added: Option<String> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("table", self.table.clone()))
+                .bind(("key_tb", Self::IDEMPOTENCY_KEY_TABLE))
+                .bind(("events_tb", Self::EVENT_TABLE))
+                .bind(("id", id.to_string()))
+                .bind(("info", info))
+                // the next_update_time field is not set in surreal when it is None
+                .bind((
+                    "next_update_time",
+                    next_update_time.map(Self::convert_system_time_to_secs),
+                ))
+                .bind(("key", key))
+                .bind(("expire_time", expire_time))
+                .bind(("now", Self::convert_system_time_to_secs(SystemTime::now()))),
+        )
+        .await?
+        .map_err(RucatError::fail_to_update_database)?
+        .take(1)
+        .map_err(RucatError::fail_to_update_database)?;
+        match added {
+            Some(added) => EngineId::try_from(added),
+            None => Err(RucatError::fail_to_update_database(anyhow!(
+                "Failed to add engine {}",
+                id
+            ))),
+        }
     }
 
     async fn remove_engine(
         &self,
         id: &EngineId,
         current_state: &EngineState,
-    ) -> Result<Option<UpdateEngineStateResult>> {
+        expected_revision: Option<u64>,
+        deleted_time: SystemTime,
+    ) -> Result<Option<RemoveEngineResult>> {
         let sql = r#"
             let $record_id = type::thing($tb, $id);             // 0th return value
 
             BEGIN TRANSACTION;
             {
-                LET $current_state = (SELECT VALUE info.state from only $record_id);
-                IF $current_state IS NONE {
+                LET $info = (SELECT VALUE info from only $record_id);
+                LET $current_revision = $info.revision ?? 0;
+                IF $info IS NONE {
                     RETURN NONE;                                                     // 1st return value
-                } ELSE IF $current_state == $before {
+                } ELSE IF $expected_revision IS NOT NONE && $current_revision != $expected_revision {
+                    RETURN {RevisionMismatch: {current_revision: $current_revision}}; // 1st return value
+                } ELSE IF $info.state == $before {
                     DELETE $record_id;
-                    RETURN "Success";                                                // 1st return value
+                    UPDATE type::table($events_tb) SET deleted_time = $deleted_time, project = $info.project
+                    WHERE engine = $id;
+                    RETURN {Success: {info: $info}};                                 // 1st return value
                 } ELSE {
-                    RETURN {Fail: {current_state: $current_state}};                 // 1st return value
+                    RETURN {Fail: {current_state: $info.state}};                    // 1st return value
                 }
             };
             COMMIT TRANSACTION;
         "#;
-        let result: Option<UpdateEngineStateResult> = self
-            .client
-            .query(sql)
-            .bind(("tb", Self::TABLE))
-            .bind(("id", id.to_string()))
-            .bind(("before", current_state.clone()))
-            .await
-            .map_err(RucatError::fail_to_update_database)?
-            .take(1)
-            .map_err(RucatError::fail_to_update_database)?;
-        Ok(result)
+        let result: Option<ConditionalRemoveResult> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind(("id", id.to_string()))
+                .bind(("before", current_state.clone()))
+                .bind(("expected_revision", expected_revision))
+                .bind(("events_tb", Self::EVENT_TABLE))
+                .bind((
+                    "deleted_time",
+                    Self::convert_system_time_to_secs(deleted_time),
+                )),
+        )
+        .await?
+        .map_err(RucatError::fail_to_update_database)?
+        .take(1)
+        .map_err(RucatError::fail_to_update_database)?;
+        match result {
+            None => Ok(None),
+            Some(ConditionalRemoveResult::Success { info }) => {
+                Ok(Some(RemoveEngineResult::Success(info)))
+            }
+            Some(ConditionalRemoveResult::Fail { current_state }) => {
+                Ok(Some(RemoveEngineResult::Fail { current_state }))
+            }
+            Some(ConditionalRemoveResult::RevisionMismatch { current_revision }) => Err(
+                Self::revision_mismatch(id, current_revision, expected_revision),
+            ),
+        }
     }
 
     async fn update_engine_state(
@@ -152,6 +691,8 @@ impl Database for SurrealDBClient {
         before: &EngineState,
         after: &EngineState,
         next_update_time: Option<SystemTime>,
+        expected_revision: Option<u64>,
+        actor: Option<&EngineActor>,
     ) -> Result<Option<UpdateEngineStateResult>> {
         let sql = r#"
             let $record_id = type::thing($tb, $id);             // 0th return value
@@ -159,10 +700,24 @@ impl Database for SurrealDBClient {
             BEGIN TRANSACTION;
             {
                 LET $current_state = (SELECT VALUE info.state from only $record_id);
+                LET $current_revision = (SELECT VALUE info.revision ?? 0 from only $record_id);
                 IF $current_state IS NONE {
                     RETURN NONE;                                                     // 1st return value
+                } ELSE IF $expected_revision IS NOT NONE && $current_revision != $expected_revision {
+                    RETURN {RevisionMismatch: {current_revision: $current_revision}}; // 1st return value
                 } ELSE IF $current_state == $before {
                     UPDATE ONLY $record_id SET info.state = $after, next_update_time = $next_update_time;
+                    // only record the time and bump the revision when the state is changed, not when the engine is rechecked
+                    IF $before != $after {
+                        UPDATE ONLY $record_id SET state_update_time = $now, info.revision = $current_revision + 1;
+                        LET $seq = (SELECT VALUE seq FROM type::table($events_tb) WHERE engine = $id ORDER BY seq DESC LIMIT 1)[0] ?? 0;
+                        CREATE type::table($events_tb)
+                        SET engine = $id, seq = $seq + 1, old_state = $before, new_state = $after, source = $source,
+                            actor = $actor, impersonator = $impersonator, time = $now;
+                    };
+                    IF $last_error IS NOT NONE {
+                        UPDATE ONLY $record_id SET info.last_error = $last_error;
+                    };
                     RETURN "Success";                  // 1st return value
                 } ELSE {
                     RETURN {Fail: {current_state: $current_state}};                 // 1st return value
@@ -170,23 +725,148 @@ impl Database for SurrealDBClient {
             };
             COMMIT TRANSACTION;
         "#;
-        let before_state: Option<UpdateEngineStateResult> = self
-            .client
-            .query(sql)
-            .bind(("tb", Self::TABLE))
-            .bind(("id", id.to_string()))
-            .bind(("before", before.clone()))
-            .bind(("after", after.clone()))
-            .bind((
-                "next_update_time",
-                next_update_time.map(Self::convert_system_time_to_secs),
-            ))
-            .await
-            .map_err(RucatError::fail_to_update_database)?
-            .take(1)
-            .map_err(RucatError::fail_to_update_database)?; // The 1st statement is the if-else which is what we want
+        let result: Option<ConditionalUpdateResult> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind(("events_tb", Self::EVENT_TABLE))
+                .bind(("id", id.to_string()))
+                .bind(("before", before.clone()))
+                .bind(("after", after.clone()))
+                .bind(("source", self.event_source))
+                .bind(("actor", actor.map(|actor| actor.name.clone())))
+                .bind((
+                    "impersonator",
+                    actor.and_then(|actor| actor.impersonator.clone()),
+                ))
+                .bind((
+                    "next_update_time",
+                    next_update_time.map(Self::convert_system_time_to_secs),
+                ))
+                .bind(("now", Self::convert_system_time_to_secs(SystemTime::now())))
+                .bind(("last_error", get_last_error(before, after).cloned()))
+                .bind(("expected_revision", expected_revision)),
+        )
+        .await?
+        .map_err(RucatError::fail_to_update_database)?
+        .take(1)
+        .map_err(RucatError::fail_to_update_database)?; // The 1st statement is the if-else which is what we want
+
+        Self::check_revision(id, result, expected_revision)
+    }
+
+    async fn update_engine_info(
+        &self,
+        id: &EngineId,
+        update: EngineInfoUpdate,
+        expected_states: Option<&[EngineState]>,
+        expected_revision: Option<u64>,
+    ) -> Result<Option<UpdateEngineStateResult>> {
+        // the fields which are not set are bound as NONE and kept
+        let sql = r#"
+            let $record_id = type::thing($tb, $id);             // 0th return value
+
+            BEGIN TRANSACTION;
+            {
+                LET $current_state = (SELECT VALUE info.state from only $record_id);
+                LET $current_revision = (SELECT VALUE info.revision ?? 0 from only $record_id);
+                IF $current_state IS NONE {
+                    RETURN NONE;                                                     // 1st return value
+                } ELSE IF $expected_revision IS NOT NONE && $current_revision != $expected_revision {
+                    RETURN {RevisionMismatch: {current_revision: $current_revision}}; // 1st return value
+                } ELSE IF $expected_states IS NONE || $current_state INSIDE $expected_states {
+                    UPDATE ONLY $record_id MERGE {info: {config: $config ?? {}, blobs: $blobs ?? {}, revision: $current_revision + 1}};
+                    UPDATE ONLY $record_id SET info.name = $name ?? info.name;
+                    RETURN "Success";                                                // 1st return value
+                } ELSE {
+                    RETURN {Fail: {current_state: $current_state}};                 // 1st return value
+                }
+            };
+            COMMIT TRANSACTION;
+        "#;
+        let result: Option<ConditionalUpdateResult> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind(("id", id.to_string()))
+                .bind(("name", update.name))
+                .bind(("config", update.config))
+                .bind(("blobs", update.blobs))
+                .bind(("expected_states", expected_states.map(<[_]>::to_vec)))
+                .bind(("expected_revision", expected_revision)),
+        )
+        .await?
+        .map_err(RucatError::fail_to_update_database)?
+        .take(1)
+        .map_err(RucatError::fail_to_update_database)?;
+        Self::check_revision(id, result, expected_revision)
+    }
+
+    async fn set_engine_acl(
+        &self,
+        id: &EngineId,
+        allowed_principals: BTreeSet<String>,
+        expected_revision: Option<u64>,
+    ) -> Result<Option<()>> {
+        let sql = r#"
+            let $record_id = type::thing($tb, $id);             // 0th return value
+
+            BEGIN TRANSACTION;
+            {
+                LET $current_revision = (SELECT VALUE info.revision ?? 0 from only $record_id);
+                IF $current_revision IS NONE {
+                    RETURN NONE;                                                     // 1st return value
+                } ELSE IF $expected_revision IS NOT NONE && $current_revision != $expected_revision {
+                    RETURN {RevisionMismatch: {current_revision: $current_revision}}; // 1st return value
+                } ELSE {
+                    UPDATE ONLY $record_id SET info.allowed_principals = $allowed_principals, info.revision = $current_revision + 1;
+                    RETURN "Success";                                                // 1st return value
+                }
+            };
+            COMMIT TRANSACTION;
+        "#;
+        let result: Option<ConditionalUpdateResult> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind(("id", id.to_string()))
+                .bind(("allowed_principals", allowed_principals))
+                .bind(("expected_revision", expected_revision)),
+        )
+        .await?
+        .map_err(RucatError::fail_to_update_database)?
+        .take(1)
+        .map_err(RucatError::fail_to_update_database)?;
+        Ok(Self::check_revision(id, result, expected_revision)?.map(|_| ()))
+    }
 
-        Ok(before_state)
+    async fn update_engine_endpoints(
+        &self,
+        id: &EngineId,
+        endpoints: EngineEndpoints,
+    ) -> Result<Option<()>> {
+        // `UPDATE` does not create the record if it does not exist.
+        let sql = r#"
+            UPDATE type::thing($tb, $id)
+            MERGE {info: {endpoints: $endpoints, revision: (info.revision ?? 0) + 1}}
+            RETURN VALUE info.endpoints;
+        "#;
+        let updated: Vec<EngineEndpoints> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind(("id", id.to_string()))
+                .bind(("endpoints", endpoints)),
+        )
+        .await?
+        .map_err(RucatError::fail_to_update_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_update_database)?;
+        Ok((!updated.is_empty()).then_some(()))
     }
 
     async fn get_engine(&self, id: &EngineId) -> Result<Option<EngineInfo>> {
@@ -194,70 +874,2375 @@ impl Database for SurrealDBClient {
             SELECT VALUE info
             FROM ONLY type::thing($tb, $id);
         "#;
-        let info: Option<EngineInfo> = self
-            .client
-            .query(sql)
-            .bind(("tb", Self::TABLE))
-            .bind(("id", id.to_string()))
-            .await
-            .map_err(RucatError::fail_to_read_database)?
-            .take(0)
-            .map_err(RucatError::fail_to_read_database)?;
+        let info: Option<EngineInfo> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind(("id", id.to_string())),
+        )
+        .await?
+        .map_err(RucatError::fail_to_read_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_read_database)?;
         Ok(info)
     }
 
-    async fn list_engines(&self) -> Result<Vec<EngineId>> {
+    async fn get_engines(&self, ids: &[EngineId]) -> Result<Vec<(EngineId, Option<EngineInfo>)>> {
+        // fetching the info through the record id of each id keeps the order of `ids` and the missing engines
         let sql = r#"
-            SELECT VALUE record::id(id) FROM type::table($tb);
+            SELECT VALUE type::thing($tb, $this).info FROM $ids;
         "#;
+        let infos: Vec<Option<EngineInfo>> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind((
+                    "ids",
+                    ids.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                )),
+        )
+        .await?
+        .map_err(RucatError::fail_to_read_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_read_database)?;
+        Ok(ids.iter().cloned().zip(infos).collect())
+    }
 
-        let ids: Vec<String> = self
-            .client
-            .query(sql)
-            .bind(("tb", Self::TABLE))
-            .await
-            .map_err(RucatError::fail_to_read_database)?
-            .take(0)
-            .map_err(RucatError::fail_to_read_database)?;
-        let mut ids: Vec<_> = ids
+    async fn get_engine_state(&self, id: &EngineId) -> Result<Option<EngineState>> {
+        let sql = r#"
+            SELECT VALUE info.state
+            FROM ONLY type::thing($tb, $id);
+        "#;
+        let state: Option<EngineState> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind(("id", id.to_string())),
+        )
+        .await?
+        .map_err(RucatError::fail_to_read_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_read_database)?;
+        Ok(state)
+    }
+
+    async fn list_engines(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+        sort: EngineSort,
+    ) -> Result<Vec<EngineId>> {
+        let sql = format!(
+            r#"
+            SELECT VALUE id FROM (
+                SELECT record::id(id) AS id, {} AS sort_key FROM type::table($tb)
+                WHERE {}
+                {} LIMIT $limit START $offset
+            );
+        "#,
+            Self::get_sort_key(sort.key),
+            Self::LIST_ENGINES_CONDITION,
+            Self::get_order_by(sort)
+        );
+        let ids: Vec<String> = self.list_engine_page(sql, offset, limit, filter).await?;
+        ids.into_iter().map(EngineId::try_from).collect()
+    }
+
+    async fn list_engine_summaries(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &EngineFilter,
+        sort: EngineSort,
+    ) -> Result<Vec<EngineSummary>> {
+        let sql = format!(
+            r#"
+            SELECT
+                record::id(id) AS id,
+                info.name AS name,
+                info.engine_type AS engine_type,
+                info.state AS state,
+                info.create_time AS create_time,
+                {} AS sort_key
+            FROM type::table($tb)
+            WHERE {}
+            {} LIMIT $limit START $offset;
+        "#,
+            Self::get_sort_key(sort.key),
+            Self::LIST_ENGINES_CONDITION,
+            Self::get_order_by(sort)
+        );
+
+        #[derive(Deserialize)]
+        struct EngineSummaryWithIdString {
+            id: String,
+            name: String,
+            engine_type: EngineType,
+            state: EngineState,
+            create_time: EngineTime,
+        }
+
+        let summaries: Vec<EngineSummaryWithIdString> =
+            self.list_engine_page(sql, offset, limit, filter).await?;
+        summaries
+            .into_iter()
+            .map(|summary| {
+                Ok(EngineSummary {
+                    id: EngineId::try_from(summary.id)?,
+                    name: summary.name,
+                    engine_type: summary.engine_type,
+                    state: summary.state,
+                    create_time: summary.create_time,
+                })
+            })
+            .collect()
+    }
+
+    async fn list_engines_need_update(
+        &self,
+        engine_types: &[EngineType],
+        limit: usize,
+    ) -> Result<Vec<EngineIdAndInfo>> {
+        let sql = format!("{};", Self::SELECT_ENGINES_NEED_UPDATE);
+        let engines: Vec<EngineNeedUpdate> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind(("now", Self::convert_system_time_to_secs(SystemTime::now())))
+                .bind(("engine_types", engine_types.to_vec()))
+                .bind(("limit", limit)),
+        )
+        .await?
+        .map_err(RucatError::fail_to_read_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_read_database)?;
+        engines.into_iter().map(EngineIdAndInfo::try_from).collect()
+    }
+
+    async fn claim_engines_need_update(
+        &self,
+        engine_types: &[EngineType],
+        limit: usize,
+        now: SystemTime,
+        claim_duration: Duration,
+    ) -> Result<Vec<EngineIdAndInfo>> {
+        // Concurrent transactions claiming the same engine conflict, so only one of them commits,
+        // and the others are retried to claim the engines left.
+        let sql = format!(
+            r#"
+            BEGIN TRANSACTION;
+            {{
+                LET $claimed = ({});
+                FOR $engine IN $claimed {{
+                    UPDATE type::thing($tb, $engine.id) SET next_update_time = $claim_until;
+                }};
+                RETURN $claimed;                                             // 0th return value
+            }};
+            COMMIT TRANSACTION;
+        "#,
+            Self::SELECT_ENGINES_NEED_UPDATE
+        );
+        let mut attempt = 1;
+        let engines: Vec<EngineNeedUpdate> = loop {
+            let result = with_query_timeout(
+                self.query_timeout,
+                self.client
+                    .query(&sql)
+                    .bind(("tb", self.table.clone()))
+                    .bind(("now", Self::convert_system_time_to_secs(now)))
+                    .bind(("engine_types", engine_types.to_vec()))
+                    .bind(("limit", limit))
+                    .bind((
+                        "claim_until",
+                        Self::convert_system_time_to_secs(now + claim_duration),
+                    )),
+            )
+            .await?
+            .map_err(RucatError::fail_to_update_database)?
+            .take(0);
+            match result {
+                Err(e) if attempt < Self::MAX_CLAIM_ATTEMPTS && is_conflict(&e) => {
+                    info!(
+                        "Claim conflicts with a concurrent one, retry (attempt {}/{})",
+                        attempt,
+                        Self::MAX_CLAIM_ATTEMPTS
+                    );
+                    attempt += 1;
+                }
+                result => break result.map_err(RucatError::fail_to_update_database)?,
+            }
+        };
+        engines.into_iter().map(EngineIdAndInfo::try_from).collect()
+    }
+
+    async fn count_engines_by_state(
+        &self,
+        engine_types: &[EngineType],
+        project: Option<&str>,
+    ) -> Result<BTreeMap<EngineStateKind, usize>> {
+        let sql = r#"
+            SELECT state, count() AS count FROM (
+                SELECT (IF type::is::object(info.state)
+                    THEN object::keys(info.state)[0]
+                    ELSE info.state
+                END) AS state
+                FROM type::table($tb)
+                WHERE (array::len($engine_types) == 0 || info.engine_type INSIDE $engine_types)
+                    && ($project IS NONE || info.project == $project)
+            )
+            GROUP BY state;
+        "#;
+
+        #[derive(Deserialize)]
+        struct StateCount {
+            state: EngineStateKind,
+            count: usize,
+        }
+
+        let counts: Vec<StateCount> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind(("engine_types", engine_types.to_vec()))
+                .bind(("project", project.map(str::to_owned))),
+        )
+        .await?
+        .map_err(RucatError::fail_to_read_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_read_database)?;
+        Ok(counts
             .into_iter()
-            .map(EngineId::try_from)
-            .collect::<Result<_>>()?;
-        ids.sort();
-        Ok(ids)
+            .map(|StateCount { state, count }| (state, count))
+            .collect())
+    }
+
+    async fn remove_engines_in_states(
+        &self,
+        states: &BTreeSet<EngineStateKind>,
+        older_than: SystemTime,
+        project: Option<&str>,
+        deleted_time: SystemTime,
+    ) -> Result<Vec<EngineId>> {
+        // `info.create_time` is stored as `YYYY-MM-DD hh:mm:ss +hh:mm:ss`, see [EngineTime],
+        // which is rewritten in RFC 3339 to be parsed as a datetime.
+        let sql = r#"
+            BEGIN TRANSACTION;
+            {
+                LET $removed = (DELETE type::table($tb)
+                    WHERE (IF type::is::object(info.state)
+                            THEN object::keys(info.state)[0]
+                            ELSE info.state
+                        END) INSIDE $states
+                        && (state_update_time ?? time::unix(<datetime> string::concat(
+                            string::slice(info.create_time, 0, 10),
+                            "T",
+                            string::slice(info.create_time, 11, 8),
+                            string::slice(info.create_time, 20, 6)
+                        ))) < $older_than
+                        && ($project IS NONE || info.project == $project)
+                    RETURN BEFORE);
+                FOR $engine IN $removed {
+                    UPDATE type::table($events_tb)
+                    SET deleted_time = $deleted_time, project = $engine.info.project
+                    WHERE engine = record::id($engine.id);
+                };
+                RETURN SELECT VALUE record::id(id) FROM $removed;                   // 0th return value
+            };
+            COMMIT TRANSACTION;
+        "#;
+        let ids: Vec<String> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind(("states", states.clone()))
+                .bind(("older_than", Self::convert_system_time_to_secs(older_than)))
+                .bind(("project", project.map(str::to_owned)))
+                .bind(("events_tb", Self::EVENT_TABLE))
+                .bind((
+                    "deleted_time",
+                    Self::convert_system_time_to_secs(deleted_time),
+                )),
+        )
+        .await?
+        .map_err(RucatError::fail_to_update_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_update_database)?;
+        ids.into_iter().map(EngineId::try_from).collect()
     }
 
-    async fn list_engines_need_update(&self) -> Result<Vec<EngineIdAndInfo>> {
+    async fn list_triggered_engines(&self) -> Result<Vec<TriggeredEngine>> {
+        // Engines in Trigger* states always have next_update_time set to the trigger timeout.
         let sql = r#"
-            SELECT VALUE {id: record::id(id), info: info}
+            SELECT VALUE {
+                id: record::id(id),
+                info: info,
+                state_update_time: state_update_time,
+                next_update_time: next_update_time
+            }
             FROM type::table($tb)
-            WHERE next_update_time != None && next_update_time < $now;
+            WHERE (IF type::is::object(info.state)
+                    THEN object::keys(info.state)[0]
+                    ELSE info.state
+                END) INSIDE $states
+                && next_update_time != None;
         "#;
 
         #[derive(Deserialize)]
-        struct EngineIdStringAndInfo {
+        struct Record {
             id: String,
             info: EngineInfo,
+            state_update_time: Option<u64>,
+            next_update_time: u64,
         }
 
-        let id_and_info: Vec<EngineIdStringAndInfo> = self
-            .client
-            .query(sql)
-            .bind(("tb", Self::TABLE))
-            .bind(("now", Self::convert_system_time_to_secs(SystemTime::now())))
-            .await
-            .map_err(RucatError::fail_to_read_database)?
-            .take(0)
-            .map_err(RucatError::fail_to_read_database)?;
+        let records: Vec<Record> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("tb", self.table.clone()))
+                .bind((
+                    "states",
+                    [
+                        EngineStateKind::TriggerStart,
+                        EngineStateKind::TriggerTermination,
+                        EngineStateKind::ErrorTriggerClean,
+                    ],
+                )),
+        )
+        .await?
+        .map_err(RucatError::fail_to_read_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_read_database)?;
 
-        id_and_info
+        let mut engines = records
             .into_iter()
-            .map(|EngineIdStringAndInfo { id, info }| {
-                Ok(EngineIdAndInfo {
-                    id: EngineId::try_from(id)?,
-                    info,
+            .map(|record| {
+                Ok(TriggeredEngine {
+                    id: EngineId::try_from(record.id)?,
+                    info: record.info,
+                    state_update_time: record
+                        .state_update_time
+                        .map(Self::convert_secs_to_system_time),
+                    trigger_timeout_time: Self::convert_secs_to_system_time(
+                        record.next_update_time,
+                    ),
                 })
             })
-            .collect()
+            .collect::<Result<Vec<_>>>()?;
+        engines.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(engines)
+    }
+
+    async fn append_engine_event(
+        &self,
+        id: &EngineId,
+        old_state: &EngineState,
+        new_state: &EngineState,
+        source: &str,
+        timestamp: SystemTime,
+    ) -> Result<()> {
+        let sql = r#"
+            BEGIN TRANSACTION;
+            LET $seq = (SELECT VALUE seq FROM type::table($events_tb) WHERE engine = $id ORDER BY seq DESC LIMIT 1)[0] ?? 0;
+            CREATE type::table($events_tb)
+            SET engine = $id, seq = $seq + 1, old_state = $old_state, new_state = $new_state, source = $source, time = $time;
+            COMMIT TRANSACTION;
+        "#;
+        with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("events_tb", Self::EVENT_TABLE))
+                .bind(("id", id.to_string()))
+                .bind(("old_state", old_state.clone()))
+                .bind(("new_state", new_state.clone()))
+                .bind(("source", source.to_owned()))
+                .bind(("time", Self::convert_system_time_to_secs(timestamp))),
+        )
+        .await?
+        .map_err(RucatError::fail_to_update_database)?
+        .check()
+        .map_err(RucatError::fail_to_update_database)?;
+        Ok(())
+    }
+
+    async fn get_engine_events(&self, id: &EngineId, limit: usize) -> Result<Vec<EngineEvent>> {
+        let sql = r#"
+            SELECT seq, old_state, new_state, source, actor, impersonator, time
+            FROM type::table($events_tb)
+            WHERE engine = $id
+            ORDER BY seq DESC
+            LIMIT $limit;
+        "#;
+
+        #[derive(Deserialize)]
+        struct Record {
+            old_state: EngineState,
+            new_state: EngineState,
+            source: String,
+            actor: Option<String>,
+            impersonator: Option<String>,
+            time: u64,
+        }
+
+        let records: Vec<Record> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("events_tb", Self::EVENT_TABLE))
+                .bind(("id", id.to_string()))
+                .bind(("limit", limit)),
+        )
+        .await?
+        .map_err(RucatError::fail_to_read_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_read_database)?;
+        Ok(records
+            .into_iter()
+            .map(|record| EngineEvent {
+                old_state: record.old_state,
+                new_state: record.new_state,
+                source: record.source,
+                actor: record.actor,
+                impersonator: record.impersonator,
+                time: EngineTime::from_system_time(Self::convert_secs_to_system_time(record.time)),
+            })
+            .collect())
+    }
+
+    async fn purge_engine_events(
+        &self,
+        deleted_before: SystemTime,
+        project: Option<&str>,
+    ) -> Result<usize> {
+        // NONE is less than any number, so it is excluded explicitly before comparing.
+        let sql = r#"
+            array::len(DELETE type::table($events_tb)
+                WHERE deleted_time IS NOT NONE && deleted_time < $deleted_before
+                    && ($project IS NONE || project == $project)
+                RETURN BEFORE);                                 // 0th return value
+        "#;
+        let purged: Option<usize> = with_query_timeout(
+            self.query_timeout,
+            self.client
+                .query(sql)
+                .bind(("events_tb", Self::EVENT_TABLE))
+                .bind((
+                    "deleted_before",
+                    Self::convert_system_time_to_secs(deleted_before),
+                ))
+                .bind(("project", project.map(str::to_owned))),
+        )
+        .await?
+        .map_err(RucatError::fail_to_update_database)?
+        .take(0)
+        .map_err(RucatError::fail_to_update_database)?;
+        Ok(purged.unwrap_or_default())
+    }
+
+    async fn health_check(&self) -> Result<DatabaseHealth> {
+        let timeout = self.query_timeout.min(Self::HEALTH_CHECK_TIMEOUT);
+        let start = Instant::now();
+        let _: Option<u8> = with_query_timeout(timeout, self.client.query("RETURN 1;"))
+            .await?
+            .map_err(RucatError::fail_to_connect_database)?
+            .take(0)
+            .map_err(RucatError::fail_to_connect_database)?;
+        let latency = start.elapsed();
+        // the version is only reported, so failing to get it does not fail the check
+        let version = with_query_timeout(timeout, self.client.version())
+            .await
+            .ok()
+            .and_then(|version| version.ok())
+            .map(|version| version.to_string());
+        Ok(DatabaseHealth { latency, version })
+    }
+}
+
+/// Whether the transaction failed by a read or write conflict with a concurrent one, so that it can be retried.
+/// SurrealDB only reports the conflict in the error message, both in the embedded and the remote database.
+fn is_conflict(error: &::surrealdb::Error) -> bool {
+    error
+        .to_string()
+        .contains("This transaction can be retried")
+}
+
+/// Connect by `connect`, retrying with backoff until it succeeds,
+/// the attempts are used up or the timeout of all attempts is reached.
+async fn connect_with_retry<T, F, Fut>(retry: &ConnectRetry, mut connect: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let deadline = Instant::now() + Duration::from_secs(retry.timeout_secs);
+    let mut backoff = Duration::from_millis(retry.initial_backoff_millis);
+    let max_backoff = Duration::from_millis(retry.max_backoff_millis);
+    let mut attempt = 1;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let error = match timeout(remaining, connect()).await {
+            Ok(Ok(connection)) => {
+                if attempt > 1 {
+                    info!("Connected to the database at attempt {}", attempt);
+                }
+                return Ok(connection);
+            }
+            Ok(Err(e)) => e,
+            Err(_) => RucatError::fail_to_connect_database(anyhow!(
+                "Timeout after {} seconds",
+                retry.timeout_secs
+            )),
+        };
+        warn!(
+            "Failed to connect to the database, attempt {}/{}: {}",
+            attempt, retry.max_attempts, error
+        );
+        if attempt >= retry.max_attempts || Instant::now() + backoff >= deadline {
+            return Err(error);
+        }
+        sleep(backoff).await;
+        backoff = (backoff * 2).min(max_backoff);
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blob_store::BlobRef;
+    use crate::engine::{CreateEngineRequest, EngineConfig, IdGenerator, RandomIdGenerator};
+    use crate::error::RucatErrorType;
+    use ::std::borrow::Cow;
+
+    async fn add_spark_engine(client: &SurrealDBClient) -> Result<EngineId> {
+        add_spark_engine_with_name(client, "spark").await
+    }
+
+    async fn add_spark_engine_with_name(client: &SurrealDBClient, name: &str) -> Result<EngineId> {
+        let request = CreateEngineRequest {
+            name: name.to_owned(),
+            engine_type: EngineType::Spark,
+            version: "3.5.4".to_owned(),
+            config: None,
+            start_in_progress_timeout_secs: None,
+            features: None,
+            start: None,
+        };
+        let id = RandomIdGenerator.generate();
+        let info = EngineInfo::from_create_request(request, EngineTime::now())?;
+        client.add_engine(&id, info, Some(UNIX_EPOCH), None).await?;
+        Ok(id)
+    }
+
+    /// Add an engine of a type unknown to this version of rucat,
+    /// as if it was created by another version for another state monitor.
+    async fn add_engine_of_other_type(client: &SurrealDBClient, id: &str) -> Result<()> {
+        let sql = r#"
+            CREATE type::thing($tb, $id)
+            SET info = {engine_type: "Ballista", state: "WaitToStart"}, next_update_time = 0;
+        "#;
+        client
+            .client
+            .query(sql)
+            .bind(("tb", client.table.clone()))
+            .bind(("id", id.to_owned()))
+            .await
+            .map_err(RucatError::fail_to_update_database)?
+            .check()
+            .map_err(RucatError::fail_to_update_database)?;
+        Ok(())
+    }
+
+    fn connect_retry(max_attempts: u32, timeout_secs: u64) -> ConnectRetry {
+        ConnectRetry {
+            max_attempts,
+            initial_backoff_millis: 1,
+            max_backoff_millis: 1,
+            timeout_secs,
+        }
+    }
+
+    /// Config of the SurrealDB at `uri`, retrying connecting once without delay.
+    fn surreal_config(uri: &str) -> SurrealConfig {
+        SurrealConfig {
+            credentials: None,
+            uri: uri.to_owned(),
+            connect_retry: connect_retry(2, 10),
+            namespace: SurrealConfig::default_namespace(),
+            database: SurrealConfig::default_database(),
+            table: SurrealConfig::default_table(),
+            tls: false,
+            ca_path: None,
+            query_timeout_secs: 30,
+        }
+    }
+
+    #[test]
+    fn detect_transaction_conflicts() {
+        use ::surrealdb::error::Db;
+        assert!(is_conflict(&Db::TxRetryable.into()));
+        // the conflict of a statement in a transaction, as returned by the query
+        assert!(is_conflict(
+            &Db::QueryNotExecutedDetail {
+                message: Db::TxRetryable.to_string(),
+            }
+            .into()
+        ));
+        assert!(!is_conflict(&Db::QueryNotExecuted.into()));
+        assert!(!is_conflict(&Db::TxFailure.into()));
+    }
+
+    #[tokio::test]
+    async fn retry_connecting_until_success() -> Result<()> {
+        let mut attempts = 0;
+        let result = connect_with_retry(&connect_retry(5, 10), || {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                if attempt < 3 {
+                    Err(RucatError::fail_to_connect_database(anyhow!("not ready")))
+                } else {
+                    Ok(attempt)
+                }
+            }
+        })
+        .await?;
+        assert_eq!(result, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn give_up_connecting_after_max_attempts() -> Result<()> {
+        let mut attempts = 0;
+        let result: Result<()> = connect_with_retry(&connect_retry(3, 10), || {
+            attempts += 1;
+            async { Err(RucatError::fail_to_connect_database(anyhow!("not ready"))) }
+        })
+        .await;
+        assert!(result.is_err_and(|e| e.to_string().contains("not ready")));
+        assert_eq!(attempts, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn give_up_connecting_after_timeout() -> Result<()> {
+        let result: Result<()> =
+            connect_with_retry(&connect_retry(5, 1), ::std::future::pending).await;
+        assert!(result.is_err_and(|e| {
+            e.get_error_type() == &RucatErrorType::FailToConnectDatabase
+                && e.to_string().contains("Timeout after 1 seconds")
+        }));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fail_to_connect_unreachable_database() -> Result<()> {
+        // nothing listens on port 1
+        let result = SurrealDBClient::new(&surreal_config("127.0.0.1:1")).await;
+        assert!(result.is_err_and(|e| e.get_error_type() == &RucatErrorType::FailToConnectDatabase));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_ca_certificate() -> Result<()> {
+        let dir = ::tempfile::TempDir::new().map_err(RucatError::fail_to_load_config)?;
+        let invalid = dir.path().join("invalid.crt");
+        ::std::fs::write(&invalid, "not a certificate").map_err(RucatError::fail_to_load_config)?;
+        for (tls, ca_path, message) in [
+            (
+                false,
+                "/etc/rucat/ca.crt",
+                "`ca_path` of SurrealDB needs `tls`",
+            ),
+            (
+                true,
+                "/nonexistent/ca.crt",
+                "Fail to read the CA certificate `/nonexistent/ca.crt`",
+            ),
+            (
+                true,
+                invalid.to_str().unwrap(),
+                "Fail to parse the CA certificate",
+            ),
+        ] {
+            let result = SurrealDBClient::new(&SurrealConfig {
+                tls,
+                ca_path: Some(ca_path.to_owned()),
+                ..surreal_config("127.0.0.1:1")
+            })
+            .await;
+            assert!(result.is_err_and(|e| {
+                e.get_error_type() == &RucatErrorType::FailToLoadConfig
+                    && e.to_string().contains(message)
+            }));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fail_tls_handshake_with_plaintext_server() -> Result<()> {
+        let listener = ::tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(RucatError::fail_to_connect_database)?;
+        let address = listener
+            .local_addr()
+            .map_err(RucatError::fail_to_connect_database)?;
+        ::tokio::spawn(async move {
+            use ::tokio::io::AsyncWriteExt;
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n").await;
+            }
+        });
+
+        let result = SurrealDBClient::new(&SurrealConfig {
+            tls: true,
+            ..surreal_config(&address.to_string())
+        })
+        .await;
+        assert!(result.is_err_and(|e| {
+            e.get_error_type() == &RucatErrorType::FailToConnectDatabase
+                && e.to_string().contains(&format!(
+                    "Fail to connect to `wss://{}`, including the TLS handshake",
+                    address
+                ))
+        }));
+        Ok(())
+    }
+
+    /// Config of the SurrealDB served with TLS at `RUCAT_TEST_SURREAL_TLS_URI`,
+    /// whose certificate is signed by the CA at `RUCAT_TEST_SURREAL_CA_PATH`, with the root user `root`.
+    /// Each test uses its own namespace.
+    #[cfg(feature = "surrealdb-tests")]
+    fn tls_config(password: &str) -> SurrealConfig {
+        let uri = std::env::var("RUCAT_TEST_SURREAL_TLS_URI")
+            .unwrap_or_else(|_| "localhost:8000".to_owned());
+        SurrealConfig {
+            credentials: Some(Credentials {
+                username: "root".to_owned(),
+                password: password.into(),
+            }),
+            namespace: format!("test_{:08x}", rand::random::<u32>()),
+            tls: true,
+            ca_path: std::env::var("RUCAT_TEST_SURREAL_CA_PATH").ok(),
+            ..surreal_config(&uri)
+        }
+    }
+
+    #[cfg(feature = "surrealdb-tests")]
+    #[tokio::test]
+    async fn connect_with_tls() -> Result<()> {
+        let client = SurrealDBClient::new(&tls_config("root")).await?;
+        let id = add_spark_engine(&client).await?;
+        assert_eq!(
+            client.get_engine_state(&id).await?,
+            Some(EngineState::WaitToStart)
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "surrealdb-tests")]
+    #[tokio::test]
+    async fn fail_to_sign_in_with_tls() -> Result<()> {
+        let config = tls_config("wrong password");
+        let result = SurrealDBClient::new(&config).await;
+        assert!(result.is_err_and(|e| {
+            e.get_error_type() == &RucatErrorType::FailToConnectDatabase
+                && e.to_string().contains(&format!(
+                    "Fail to sign in to `wss://{}` as `root`",
+                    config.uri
+                ))
+        }));
+        Ok(())
+    }
+
+    #[test]
+    fn validate_names() -> Result<()> {
+        SurrealDBClient::validate_names("rucat", "_test_1", "Engines")?;
+        for (namespace, database, table) in [
+            ("", "rucat", "engines"),
+            ("rucat", "1st", "engines"),
+            ("rucat", "rucat", "spark-engines"),
+            ("rucat", "rucat", "engines; REMOVE TABLE engines"),
+            ("rucat", "rucat", "migrations"),
+            ("rucat", "rucat", "idempotency_keys"),
+            ("rucat", "rucat", "engine_events"),
+        ] {
+            let result = SurrealDBClient::validate_names(namespace, database, table);
+            assert!(result.is_err_and(|e| e.get_error_type() == &RucatErrorType::FailToLoadConfig));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_names_before_connecting() -> Result<()> {
+        let result = SurrealDBClient::new(&SurrealConfig {
+            table: "spark-engines".to_owned(),
+            ..surreal_config("127.0.0.1:1")
+        })
+        .await;
+        assert!(result.is_err_and(|e| e.get_error_type() == &RucatErrorType::FailToLoadConfig));
+        Ok(())
+    }
+
+    /// Clients of different namespaces or tables in the same SurrealDB do not see the engines of each other.
+    /// As the namespace is selected for the connection shared by the clones of [Surreal],
+    /// each client is used right after its namespace is selected.
+    #[tokio::test]
+    async fn isolate_engines_by_namespace_and_table() -> Result<()> {
+        let connection = connect("memory")
+            .await
+            .map_err(RucatError::fail_to_connect_database)?;
+        let all = EngineFilter::default();
+        let sort = EngineSort::default();
+        let query_timeout = Duration::from_secs(30);
+
+        let team_a = SurrealDBClient::use_database(
+            connection.clone(),
+            "team_a",
+            "rucat",
+            "engines",
+            query_timeout,
+        )
+        .await?;
+        let a = add_spark_engine(&team_a).await?;
+        let team_a_ballista = SurrealDBClient::use_database(
+            connection.clone(),
+            "team_a",
+            "rucat",
+            "ballista",
+            query_timeout,
+        )
+        .await?;
+        assert!(team_a_ballista
+            .list_engines(0, 10, &all, sort)
+            .await?
+            .is_empty());
+        let ballista = add_spark_engine(&team_a_ballista).await?;
+
+        let team_b = SurrealDBClient::use_database(
+            connection.clone(),
+            "team_b",
+            "rucat",
+            "engines",
+            query_timeout,
+        )
+        .await?;
+        assert!(team_b.list_engines(0, 10, &all, sort).await?.is_empty());
+        assert!(team_b.get_engine(&a).await?.is_none());
+        let b = add_spark_engine(&team_b).await?;
+
+        let team_a =
+            SurrealDBClient::use_database(connection, "team_a", "rucat", "engines", query_timeout)
+                .await?;
+        assert_eq!(team_a.list_engines(0, 10, &all, sort).await?, vec![a]);
+        assert!(team_a.get_engine(&b).await?.is_none());
+        assert!(team_a.get_engine(&ballista).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fail_on_query_timeout() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let result =
+            with_query_timeout(Duration::from_millis(10), client.client.query("SLEEP 1s")).await;
+        assert!(result.is_err_and(|e| {
+            e.get_error_type() == &RucatErrorType::DatabaseTimeout
+                && e.to_string().contains("Query is not done in 0.01 seconds")
+        }));
+
+        // the client still works after a timeout
+        let id = add_spark_engine(&client).await?;
+        assert!(client.get_engine(&id).await?.is_some());
+        Ok(())
+    }
+
+    async fn list_ids_need_update(
+        client: &SurrealDBClient,
+        engine_types: &[EngineType],
+    ) -> Result<BTreeSet<EngineId>> {
+        let engines = client.list_engines_need_update(engine_types, 100).await?;
+        Ok(engines.into_iter().map(|engine| engine.id).collect())
+    }
+
+    #[tokio::test]
+    async fn engine_created_without_start_not_need_update() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let request = CreateEngineRequest {
+            name: "paused".to_owned(),
+            engine_type: EngineType::Spark,
+            version: "3.5.4".to_owned(),
+            config: None,
+            start_in_progress_timeout_secs: None,
+            features: None,
+            start: Some(false),
+        };
+        let info = EngineInfo::from_create_request(request, EngineTime::now())?;
+        let id = RandomIdGenerator.generate();
+        client.add_engine(&id, info, None, None).await?;
+
+        assert!(client.list_engines_need_update(&[], 100).await?.is_empty());
+        assert_eq!(
+            client.get_engine_state(&id).await?,
+            Some(EngineState::Terminated)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_engines_need_update_of_all_types() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let spark = add_spark_engine(&client).await?;
+        add_engine_of_other_type(&client, "ballista").await?;
+
+        let ids = list_ids_need_update(&client, &[]).await?;
+
+        assert_eq!(
+            ids,
+            BTreeSet::from([spark, EngineId::try_from("ballista")?])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_most_overdue_engines_first() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        for (id, secs) in [("a", 30), ("b", 10), ("c", 20), ("d", 10)] {
+            client
+                .add_engine(
+                    &EngineId::try_from(id)?,
+                    EngineInfo::new(
+                        "spark".to_owned(),
+                        EngineType::Spark,
+                        "3.5.4".to_owned(),
+                        EngineState::WaitToStart,
+                        BTreeMap::new(),
+                        EngineTime::now(),
+                    ),
+                    Some(UNIX_EPOCH + Duration::from_secs(secs)),
+                    None,
+                )
+                .await?;
+        }
+
+        let engines = client.list_engines_need_update(&[], 3).await?;
+        let ids = engines
+            .into_iter()
+            .map(|engine| engine.id.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["b", "d", "c"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_engines_need_update_of_filtered_types() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let spark = add_spark_engine(&client).await?;
+        add_engine_of_other_type(&client, "ballista").await?;
+
+        let ids = list_ids_need_update(&client, &[EngineType::Spark]).await?;
+
+        assert_eq!(ids, BTreeSet::from([spark]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn filtered_state_monitor_leaves_engines_of_other_types() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        add_spark_engine(&client).await?;
+        add_spark_engine(&client).await?;
+        add_engine_of_other_type(&client, "ballista").await?;
+
+        // the Spark state monitor acquires all engines it gets
+        let engines = client
+            .list_engines_need_update(&[EngineType::Spark], 100)
+            .await?;
+        assert_eq!(engines.len(), 2);
+        for engine in engines {
+            let result = client
+                .update_engine_state(
+                    &engine.id,
+                    &EngineState::WaitToStart,
+                    &EngineState::TriggerStart,
+                    None,
+                    None,
+                    None,
+                )
+                .await?;
+            assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+        }
+
+        // the engine of the other type is left to its own state monitor
+        let ids = list_ids_need_update(&client, &[]).await?;
+        assert_eq!(ids, BTreeSet::from([EngineId::try_from("ballista")?]));
+        assert!(client
+            .list_engines_need_update(&[EngineType::Spark], 100)
+            .await?
+            .is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_engines_by_name() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let etl = add_spark_engine_with_name(&client, "Nightly ETL").await?;
+        let etl_test = add_spark_engine_with_name(&client, "etl-test").await?;
+        add_spark_engine_with_name(&client, "adhoc").await?;
+        let list = |name_contains: &'static str| {
+            let client = client.clone();
+            async move {
+                let filter = EngineFilter {
+                    name_contains: name_contains.to_owned(),
+                    ..EngineFilter::default()
+                };
+                let ids = client
+                    .list_engines(0, 10, &filter, EngineSort::default())
+                    .await?;
+                Result::Ok(ids.into_iter().collect::<BTreeSet<_>>())
+            }
+        };
+
+        assert_eq!(list("etl").await?, BTreeSet::from([etl.clone(), etl_test]));
+        assert_eq!(list("NIGHTLY").await?, BTreeSet::from([etl]));
+        assert_eq!(list("").await?.len(), 3);
+        assert!(list("batch").await?.is_empty());
+        // the search string is bound as a value, not spliced into the query
+        assert!(list("' OR true; --").await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_engines_by_name_and_states() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let etl = add_spark_engine_with_name(&client, "etl").await?;
+        let running_etl = add_spark_engine_with_name(&client, "ETL").await?;
+        let running_adhoc = add_spark_engine_with_name(&client, "adhoc").await?;
+        for id in [&running_etl, &running_adhoc] {
+            client
+                .update_engine_state(
+                    id,
+                    &EngineState::WaitToStart,
+                    &EngineState::Running,
+                    None,
+                    None,
+                    None,
+                )
+                .await?;
+        }
+
+        let filter = |state| EngineFilter {
+            states: BTreeSet::from([state]),
+            name_contains: "etl".to_owned(),
+            ..EngineFilter::default()
+        };
+        let ids = client
+            .list_engines(
+                0,
+                10,
+                &filter(EngineStateKind::Running),
+                EngineSort::default(),
+            )
+            .await?;
+        assert_eq!(ids, vec![running_etl]);
+        let ids = client
+            .list_engines(
+                0,
+                10,
+                &filter(EngineStateKind::WaitToStart),
+                EngineSort::default(),
+            )
+            .await?;
+        assert_eq!(ids, vec![etl]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_engines_by_creator() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let mut ids = vec![];
+        for (id, created_by) in [
+            ("first", Some("alice")),
+            ("second", Some("bob")),
+            ("third", None),
+        ] {
+            let mut info = get_spark_engine_info()?;
+            info.created_by = created_by.map(str::to_owned);
+            ids.push(
+                client
+                    .add_engine(&EngineId::try_from(id)?, info, None, None)
+                    .await?,
+            );
+        }
+        let filter = |created_by: Option<&str>| EngineFilter {
+            created_by: created_by.map(str::to_owned),
+            ..EngineFilter::default()
+        };
+
+        let alice = client
+            .list_engines(0, 10, &filter(Some("alice")), EngineSort::default())
+            .await?;
+        assert_eq!(alice, vec![ids[0].clone()]);
+        let bob = client
+            .list_engines(0, 10, &filter(Some("bob")), EngineSort::default())
+            .await?;
+        assert_eq!(bob, vec![ids[1].clone()]);
+        let carol = client
+            .list_engines(0, 10, &filter(Some("carol")), EngineSort::default())
+            .await?;
+        assert!(carol.is_empty());
+        let all = client
+            .list_engines(0, 10, &filter(None), EngineSort::default())
+            .await?;
+        assert_eq!(all.len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn engines_scoped_by_project() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        for (id, project) in [
+            ("first", Some("team-a")),
+            ("second", Some("team-b")),
+            ("third", None),
+        ] {
+            let mut info = get_spark_engine_info()?;
+            info.state = EngineState::Terminated;
+            info.project = project.map(str::to_owned);
+            client
+                .add_engine(&EngineId::try_from(id)?, info, Some(UNIX_EPOCH), None)
+                .await?;
+        }
+        let filter = EngineFilter {
+            project: Some("team-a".to_owned()),
+            ..EngineFilter::default()
+        };
+
+        let team_a = client
+            .list_engines(0, 10, &filter, EngineSort::default())
+            .await?;
+        assert_eq!(team_a, vec![EngineId::try_from("first")?]);
+        assert_eq!(
+            client.count_engines_by_state(&[], Some("team-b")).await?,
+            BTreeMap::from([(EngineStateKind::Terminated, 1)])
+        );
+        let mut need_update = client.list_engines_need_update(&[], 100).await?;
+        need_update.sort_by(|a, b| a.id.cmp(&b.id));
+        let projects: Vec<_> = need_update
+            .into_iter()
+            .map(|engine| engine.info.project)
+            .collect();
+        assert_eq!(
+            projects,
+            vec![Some("team-a".to_owned()), Some("team-b".to_owned()), None]
+        );
+
+        let second = EngineId::try_from("second")?;
+        client
+            .append_engine_event(
+                &second,
+                &EngineState::Running,
+                &EngineState::Terminated,
+                "test",
+                UNIX_EPOCH,
+            )
+            .await?;
+        let deleted_time = UNIX_EPOCH + Duration::from_secs(60);
+        let removed = client
+            .remove_engines_in_states(
+                &BTreeSet::from([EngineStateKind::Terminated]),
+                SystemTime::now() + Duration::from_secs(60),
+                Some("team-b"),
+                deleted_time,
+            )
+            .await?;
+        assert_eq!(removed, vec![second.clone()]);
+        // histories are purged by the project of the removed engines
+        let after = deleted_time + Duration::from_secs(1);
+        assert_eq!(client.purge_engine_events(after, Some("team-a")).await?, 0);
+        assert_eq!(client.purge_engine_events(after, Some("team-b")).await?, 1);
+        assert!(client.get_engine_events(&second, 10).await?.is_empty());
+        assert_eq!(client.count_engines_by_state(&[], None).await?.len(), 1);
+        assert_eq!(
+            client
+                .list_engines(0, 10, &EngineFilter::default(), EngineSort::default())
+                .await?
+                .len(),
+            2
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_engines_sorted() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        // (id, name, state, seconds of create time)
+        let engines = [
+            ("a", "nightly", EngineState::Running, 3),
+            ("b", "adhoc", EngineState::ErrorClean("oom".into()), 1),
+            ("c", "etl", EngineState::WaitToStart, 2),
+            ("d", "etl", EngineState::Terminated, 2),
+        ];
+        for (id, name, state, secs) in engines {
+            let info = EngineInfo::new(
+                name.to_owned(),
+                EngineType::Spark,
+                "3.5.4".to_owned(),
+                state,
+                BTreeMap::new(),
+                EngineTime::from_system_time(UNIX_EPOCH + Duration::from_secs(secs)),
+            );
+            client
+                .add_engine(&EngineId::try_from(id)?, info, None, None)
+                .await?;
+        }
+        let list = |key, descending| {
+            let client = client.clone();
+            async move {
+                let sort = EngineSort { key, descending };
+                let ids = client
+                    .list_engines(0, 10, &EngineFilter::default(), sort)
+                    .await?;
+                Ok::<_, RucatError>(ids.iter().map(EngineId::to_string).collect::<String>())
+            }
+        };
+
+        assert_eq!(list(EngineSortKey::Id, false).await?, "abcd");
+        assert_eq!(list(EngineSortKey::Id, true).await?, "dcba");
+        assert_eq!(list(EngineSortKey::CreateTime, false).await?, "bcda");
+        assert_eq!(list(EngineSortKey::CreateTime, true).await?, "acdb");
+        assert_eq!(list(EngineSortKey::Name, false).await?, "bcda");
+        assert_eq!(list(EngineSortKey::State, false).await?, "badc");
+
+        let summaries = client
+            .list_engine_summaries(
+                1,
+                2,
+                &EngineFilter::default(),
+                EngineSort {
+                    key: EngineSortKey::CreateTime,
+                    descending: true,
+                },
+            )
+            .await?;
+        let ids = Vec::from_iter(summaries.iter().map(|summary| summary.id.to_string()));
+        assert_eq!(ids, ["c", "d"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_engine_summaries() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let etl = add_spark_engine_with_name(&client, "etl").await?;
+        add_spark_engine_with_name(&client, "adhoc").await?;
+
+        let filter = EngineFilter {
+            name_contains: "ETL".to_owned(),
+            ..EngineFilter::default()
+        };
+        let summaries = client
+            .list_engine_summaries(0, 10, &filter, EngineSort::default())
+            .await?;
+
+        assert_eq!(summaries.len(), 1);
+        let summary = &summaries[0];
+        assert_eq!(summary.id, etl);
+        assert_eq!(summary.name, "etl");
+        assert_eq!(summary.engine_type, EngineType::Spark);
+        assert_eq!(summary.state, EngineState::WaitToStart);
+        let info = client.get_engine(&etl).await?.unwrap();
+        assert_eq!(
+            serde_json::to_value(&summary.create_time).unwrap(),
+            serde_json::to_value(&info).unwrap()["create_time"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_engine_state() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        assert_eq!(
+            client.get_engine_state(&id).await?,
+            Some(EngineState::WaitToStart)
+        );
+
+        let error = EngineState::ErrorClean(Cow::Borrowed("fail to start"));
+        client
+            .update_engine_state(&id, &EngineState::WaitToStart, &error, None, None, None)
+            .await?;
+        assert_eq!(client.get_engine_state(&id).await?, Some(error));
+
+        let nonexistent = EngineId::try_from("nonexistent")?;
+        assert!(client.get_engine_state(&nonexistent).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_engines_in_order() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let etl = add_spark_engine_with_name(&client, "etl").await?;
+        let ml = add_spark_engine_with_name(&client, "ml").await?;
+        let nonexistent = EngineId::try_from("nonexistent")?;
+
+        let engines = client
+            .get_engines(&[ml.clone(), nonexistent.clone(), etl.clone()])
+            .await?;
+        let names = engines
+            .iter()
+            .map(|(id, info)| (id, info.as_ref().map(|info| info.name.as_str())))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [(&ml, Some("ml")), (&nonexistent, None), (&etl, Some("etl"))]
+        );
+        assert_eq!(engines[0].1, client.get_engine(&ml).await?);
+        assert!(client.get_engines(&[]).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_state_changes_as_events() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory()
+            .await?
+            .with_event_source("server");
+        let id = add_spark_engine(&client).await?;
+        let states = [
+            EngineState::WaitToStart,
+            EngineState::TriggerStart,
+            EngineState::StartInProgress,
+            EngineState::Running,
+        ];
+        for pair in states.windows(2) {
+            client
+                .update_engine_state(&id, &pair[0], &pair[1], None, None, None)
+                .await?;
+        }
+        // a recheck without changing the state is not an event
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::Running,
+                &EngineState::Running,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let events = client.get_engine_events(&id, 10).await?;
+        let transitions = events
+            .iter()
+            .map(|event| (&event.old_state, &event.new_state, event.source.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            transitions,
+            [
+                (&states[2], &states[3], "server"),
+                (&states[1], &states[2], "server"),
+                (&states[0], &states[1], "server"),
+            ]
+        );
+        assert_eq!(client.get_engine_events(&id, 1).await?, events[..1]);
+
+        client
+            .append_engine_event(
+                &id,
+                &EngineState::Running,
+                &EngineState::WaitToTerminate,
+                "state_monitor",
+                SystemTime::now(),
+            )
+            .await?;
+        let latest = client.get_engine_events(&id, 1).await?;
+        assert_eq!(latest[0].new_state, EngineState::WaitToTerminate);
+        assert_eq!(latest[0].source, "state_monitor");
+
+        let nonexistent = EngineId::try_from("nonexistent")?;
+        assert!(client.get_engine_events(&nonexistent, 10).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn record_actor_of_state_changes() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        let actor = EngineActor {
+            name: "alice".to_owned(),
+            impersonator: Some("admin".to_owned()),
+        };
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::WaitToStart,
+                &EngineState::TriggerStart,
+                None,
+                None,
+                Some(&actor),
+            )
+            .await?;
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::TriggerStart,
+                &EngineState::StartInProgress,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let events = client.get_engine_events(&id, 10).await?;
+        let actors = events
+            .iter()
+            .map(|event| (event.actor.as_deref(), event.impersonator.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(actors, [(None, None), (Some("alice"), Some("admin"))]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn purge_events_of_deleted_engines() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let kept = add_spark_engine(&client).await?;
+        let removed = add_spark_engine(&client).await?;
+        for id in [&kept, &removed] {
+            client
+                .update_engine_state(
+                    id,
+                    &EngineState::WaitToStart,
+                    &EngineState::Terminated,
+                    None,
+                    None,
+                    None,
+                )
+                .await?;
+        }
+        let deleted_time = UNIX_EPOCH + Duration::from_secs(60);
+        client
+            .remove_engine(&removed, &EngineState::Terminated, None, deleted_time)
+            .await?;
+
+        // the history is kept for the ttl from the deletion time
+        assert_eq!(client.purge_engine_events(deleted_time, None).await?, 0);
+        assert_eq!(client.get_engine_events(&removed, 10).await?.len(), 1);
+
+        // the removed engine is not in the project
+        let after = deleted_time + Duration::from_secs(1);
+        assert_eq!(client.purge_engine_events(after, Some("project")).await?, 0);
+        assert_eq!(client.get_engine_events(&removed, 10).await?.len(), 1);
+
+        assert_eq!(client.purge_engine_events(after, None).await?, 1);
+        assert!(client.get_engine_events(&removed, 10).await?.is_empty());
+        assert_eq!(client.get_engine_events(&kept, 10).await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_name_and_config_in_expected_states() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        let update = EngineInfoUpdate {
+            name: Some("renamed".to_owned()),
+            config: Some(EngineConfig::from([(
+                "spark.executor.instances".into(),
+                "2".into(),
+            )])),
+            blobs: Some(BTreeMap::from([(
+                "log_tail".to_owned(),
+                BlobRef {
+                    key: "engines/log_tail".to_owned(),
+                    size: 3,
+                    checksum: "abc".to_owned(),
+                },
+            )])),
+        };
+
+        let result = client
+            .update_engine_info(&id, update.clone(), Some(&[EngineState::Terminated]), None)
+            .await?;
+        assert!(matches!(
+            result,
+            Some(UpdateEngineStateResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+        assert_eq!(client.get_engine(&id).await?.unwrap().revision, 0);
+
+        let result = client
+            .update_engine_info(&id, update, Some(&[EngineState::WaitToStart]), Some(0))
+            .await?;
+        assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+        let info = client.get_engine(&id).await?.unwrap();
+        assert_eq!(info.name, "renamed");
+        assert_eq!(
+            info.config.get("spark.executor.instances"),
+            Some(&Cow::Borrowed("2"))
+        );
+        assert_eq!(info.blobs["log_tail"].size, 3);
+        assert_eq!(info.revision, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn return_removed_engine() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        let info = client.get_engine(&id).await?.unwrap();
+        let result = client
+            .remove_engine(&id, &EngineState::Running, None, UNIX_EPOCH)
+            .await?;
+        assert!(matches!(
+            result,
+            Some(RemoveEngineResult::Fail {
+                current_state: EngineState::WaitToStart
+            })
+        ));
+        let result = client
+            .remove_engine(&id, &EngineState::WaitToStart, None, UNIX_EPOCH)
+            .await?;
+        assert!(matches!(result, Some(RemoveEngineResult::Success(removed)) if *removed == info));
+        assert!(client
+            .remove_engine(&id, &EngineState::WaitToStart, None, UNIX_EPOCH)
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keep_last_error_when_leaving_error_state() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        let get_last_error =
+            || async { Ok::<_, RucatError>(client.get_engine(&id).await?.unwrap().last_error) };
+        assert_eq!(get_last_error().await?, None);
+
+        let wait_to_clean = EngineState::ErrorWaitToClean(Cow::Borrowed("start timed out"));
+        let error = EngineState::ErrorClean(Cow::Borrowed("start timed out"));
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::WaitToStart,
+                &wait_to_clean,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        client
+            .update_engine_state(&id, &wait_to_clean, &error, None, None, None)
+            .await?;
+        // moving between error states keeps no error
+        assert_eq!(get_last_error().await?, None);
+
+        let response = client
+            .update_engine_state(&id, &error, &EngineState::WaitToStart, None, None, None)
+            .await?;
+        assert!(matches!(response, Some(UpdateEngineStateResult::Success)));
+        assert_eq!(
+            get_last_error().await?,
+            Some(Cow::Borrowed("start timed out"))
+        );
+
+        // the last error is kept after the engine runs
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::WaitToStart,
+                &EngineState::Running,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        assert_eq!(
+            get_last_error().await?,
+            Some(Cow::Borrowed("start timed out"))
+        );
+        Ok(())
+    }
+
+    fn get_spark_engine_info() -> Result<EngineInfo> {
+        let request = CreateEngineRequest {
+            name: "spark".to_owned(),
+            engine_type: EngineType::Spark,
+            version: "3.5.4".to_owned(),
+            config: None,
+            start_in_progress_timeout_secs: None,
+            features: None,
+            start: None,
+        };
+        EngineInfo::from_create_request(request, EngineTime::now())
+    }
+
+    fn idempotency_key(expire_time: SystemTime) -> Option<IdempotencyKey> {
+        Some(IdempotencyKey {
+            key: "key-1".to_owned(),
+            expire_time,
+        })
+    }
+
+    #[tokio::test]
+    async fn add_engine_with_used_idempotency_key() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let expire_time = SystemTime::now() + Duration::from_secs(3600);
+        let first = EngineId::try_from("first")?;
+        let second = EngineId::try_from("second")?;
+
+        let added = client
+            .add_engine(
+                &first,
+                get_spark_engine_info()?,
+                None,
+                idempotency_key(expire_time),
+            )
+            .await?;
+        assert_eq!(added, first);
+        let added = client
+            .add_engine(
+                &second,
+                get_spark_engine_info()?,
+                None,
+                idempotency_key(expire_time),
+            )
+            .await?;
+        assert_eq!(added, first);
+
+        assert!(client.get_engine(&second).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_engine_with_expired_idempotency_key() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let first = EngineId::try_from("first")?;
+        let second = EngineId::try_from("second")?;
+        client
+            .add_engine(
+                &first,
+                get_spark_engine_info()?,
+                None,
+                idempotency_key(UNIX_EPOCH),
+            )
+            .await?;
+
+        let expire_time = SystemTime::now() + Duration::from_secs(3600);
+        let added = client
+            .add_engine(
+                &second,
+                get_spark_engine_info()?,
+                None,
+                idempotency_key(expire_time),
+            )
+            .await?;
+
+        assert_eq!(added, second);
+        assert!(client.get_engine(&first).await?.is_some());
+        assert!(client.get_engine(&second).await?.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_engines_concurrently_with_same_idempotency_key() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let expire_time = SystemTime::now() + Duration::from_secs(3600);
+        let mut tasks = tokio::task::JoinSet::new();
+        for i in 0..8 {
+            let client = client.clone();
+            let id = EngineId::try_from(format!("engine-{}", i))?;
+            let info = get_spark_engine_info()?;
+            tasks.spawn(async move {
+                client
+                    .add_engine(&id, info, None, idempotency_key(expire_time))
+                    .await
+            });
+        }
+
+        // Requests conflicting with the one adding the engine may fail, and succeed when retried.
+        let added = tasks
+            .join_all()
+            .await
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect::<BTreeSet<_>>();
+        assert_eq!(added.len(), 1);
+        let engines = client
+            .list_engines(0, 100, &EngineFilter::default(), EngineSort::default())
+            .await?;
+        assert_eq!(engines, Vec::from_iter(added));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_engine_with_existing_id() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        let info = client.get_engine(&id).await?.unwrap();
+
+        let result = client.add_engine(&id, info, None, None).await;
+
+        assert!(result.is_err_and(|e| e.to_string().starts_with("Fail to update database:")));
+        Ok(())
+    }
+    fn config(entries: &[(&'static str, &'static str)]) -> EngineConfig {
+        entries
+            .iter()
+            .map(|(key, value)| (Cow::Borrowed(*key), Cow::Borrowed(*value)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn merge_engine_config() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        let expected_states = [EngineState::WaitToStart, EngineState::Terminated];
+
+        let result = client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    config: Some(config(&[
+                        ("spark.executor.instances", "1"),
+                        ("spark.executor.memory", "1g"),
+                    ])),
+                    ..Default::default()
+                },
+                Some(&expected_states),
+                None,
+            )
+            .await?;
+        assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+        let result = client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    config: Some(config(&[
+                        ("spark.executor.instances", "4"),
+                        ("spark.driver.memory", "2g"),
+                    ])),
+                    ..Default::default()
+                },
+                Some(&expected_states),
+                None,
+            )
+            .await?;
+        assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+
+        let info = client.get_engine(&id).await?.unwrap();
+        assert_eq!(
+            info.config,
+            config(&[
+                ("spark.driver.memory", "2g"),
+                ("spark.executor.instances", "4"),
+                ("spark.executor.memory", "1g"),
+            ])
+        );
+        assert_eq!(info.state, EngineState::WaitToStart);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_engine_config_in_unexpected_state() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::WaitToStart,
+                &EngineState::Running,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let result = client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    config: Some(config(&[("spark.executor.instances", "4")])),
+                    ..Default::default()
+                },
+                Some(&[EngineState::WaitToStart, EngineState::Terminated]),
+                None,
+            )
+            .await?;
+
+        assert!(matches!(
+            result,
+            Some(UpdateEngineStateResult::Fail {
+                current_state: EngineState::Running
+            })
+        ));
+        assert!(client.get_engine(&id).await?.unwrap().config.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_config_of_nonexistent_engine() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+
+        let result = client
+            .update_engine_info(
+                &EngineId::try_from("nonexistent")?,
+                EngineInfoUpdate {
+                    config: Some(config(&[("spark.executor.instances", "4")])),
+                    ..Default::default()
+                },
+                Some(&[EngineState::WaitToStart]),
+                None,
+            )
+            .await?;
+
+        assert!(result.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn count_engines_by_state() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        assert!(client.count_engines_by_state(&[], None).await?.is_empty());
+
+        add_spark_engine(&client).await?;
+        let running = add_spark_engine(&client).await?;
+        client
+            .update_engine_state(
+                &running,
+                &EngineState::WaitToStart,
+                &EngineState::Running,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        let error = add_spark_engine(&client).await?;
+        client
+            .update_engine_state(
+                &error,
+                &EngineState::WaitToStart,
+                &EngineState::ErrorClean(Cow::Borrowed("error")),
+                None,
+                None,
+                None,
+            )
+            .await?;
+        add_engine_of_other_type(&client, "ballista").await?;
+
+        assert_eq!(
+            client.count_engines_by_state(&[], None).await?,
+            BTreeMap::from([
+                (EngineStateKind::WaitToStart, 2),
+                (EngineStateKind::Running, 1),
+                (EngineStateKind::ErrorClean, 1),
+            ])
+        );
+        assert_eq!(
+            client
+                .count_engines_by_state(&[EngineType::Spark], None)
+                .await?,
+            BTreeMap::from([
+                (EngineStateKind::WaitToStart, 1),
+                (EngineStateKind::Running, 1),
+                (EngineStateKind::ErrorClean, 1),
+            ])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn count_engines_in_error_states_with_different_messages() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        for state in [
+            EngineState::ErrorClean(Cow::Borrowed("error 1")),
+            EngineState::ErrorClean(Cow::Borrowed("error 2")),
+            EngineState::ErrorWaitToClean(Cow::Borrowed("error 3")),
+        ] {
+            let id = add_spark_engine(&client).await?;
+            let response = client
+                .update_engine_state(&id, &EngineState::WaitToStart, &state, None, None, None)
+                .await?;
+            assert!(matches!(response, Some(UpdateEngineStateResult::Success)));
+        }
+
+        assert_eq!(
+            client.count_engines_by_state(&[], None).await?,
+            BTreeMap::from([
+                (EngineStateKind::ErrorWaitToClean, 1),
+                (EngineStateKind::ErrorClean, 2),
+            ])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remove_engines_in_states() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let mut ids = vec![];
+        for state in [
+            EngineState::Terminated,
+            EngineState::ErrorClean(Cow::Borrowed("error")),
+            EngineState::Running,
+        ] {
+            let id = add_spark_engine(&client).await?;
+            client
+                .update_engine_state(&id, &EngineState::WaitToStart, &state, None, None, None)
+                .await?;
+            ids.push(id);
+        }
+        let states = BTreeSet::from([EngineStateKind::Terminated, EngineStateKind::ErrorClean]);
+
+        // engines are not old enough
+        assert!(client
+            .remove_engines_in_states(
+                &states,
+                SystemTime::now() - Duration::from_secs(60),
+                None,
+                UNIX_EPOCH,
+            )
+            .await?
+            .is_empty());
+
+        let older_than = SystemTime::now() + Duration::from_secs(60);
+        let mut removed = client
+            .remove_engines_in_states(&states, older_than, None, UNIX_EPOCH)
+            .await?;
+        removed.sort();
+        let mut expected = ids[..2].to_vec();
+        expected.sort();
+        assert_eq!(removed, expected);
+        assert!(client.get_engine(&ids[0]).await?.is_none());
+        assert!(client.get_engine(&ids[1]).await?.is_none());
+        assert_eq!(
+            client.get_engine_state(&ids[2]).await?,
+            Some(EngineState::Running)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_triggered_engines() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let timeout = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let error = || Cow::Borrowed("error");
+        // states that engines go through from `WaitToStart`, all engines are to be updated
+        let paths = [
+            vec![EngineState::TriggerStart],
+            vec![EngineState::ErrorWaitToClean(error())],
+            vec![
+                EngineState::ErrorWaitToClean(error()),
+                EngineState::ErrorTriggerClean(error()),
+            ],
+            vec![EngineState::Running],
+        ];
+        let mut ids = vec![];
+        for path in paths {
+            let id = add_spark_engine(&client).await?;
+            let mut before = EngineState::WaitToStart;
+            for after in path {
+                client
+                    .update_engine_state(&id, &before, &after, Some(timeout), None, None)
+                    .await?;
+                before = after;
+            }
+            ids.push(id);
+        }
+
+        let engines = client.list_triggered_engines().await?;
+        let mut expected = vec![ids[0].clone(), ids[2].clone()];
+        expected.sort();
+        assert_eq!(
+            engines
+                .iter()
+                .map(|engine| engine.id.clone())
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert!(engines
+            .iter()
+            .all(|engine| engine.trigger_timeout_time == timeout));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remove_engines_by_create_time_without_state_update_time() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let sql = r#"
+            CREATE type::thing($tb, $id)
+            SET info = {state: "Terminated", create_time: $create_time};
+        "#;
+        let create_time = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        client
+            .client
+            .query(sql)
+            .bind(("tb", client.table.clone()))
+            .bind(("id", "legacy"))
+            .bind(("create_time", EngineTime::from_system_time(create_time)))
+            .await
+            .map_err(RucatError::fail_to_update_database)?
+            .check()
+            .map_err(RucatError::fail_to_update_database)?;
+        let states = BTreeSet::from([EngineStateKind::Terminated]);
+
+        assert!(client
+            .remove_engines_in_states(&states, create_time, None, UNIX_EPOCH)
+            .await?
+            .is_empty());
+        assert_eq!(
+            client
+                .remove_engines_in_states(
+                    &states,
+                    create_time + Duration::from_secs(1),
+                    None,
+                    UNIX_EPOCH,
+                )
+                .await?,
+            vec![EngineId::try_from("legacy")?]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rename_engine_in_any_state() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::WaitToStart,
+                &EngineState::Running,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let result = client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    name: Some("renamed".to_owned()),
+                    ..Default::default()
+                },
+                None,
+                None,
+            )
+            .await?;
+
+        assert!(matches!(result, Some(UpdateEngineStateResult::Success)));
+        let info = client.get_engine(&id).await?.unwrap();
+        assert_eq!(info.name, "renamed");
+        assert_eq!(info.state, EngineState::Running);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_engine_acl() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        let allowed_principals = BTreeSet::from(["alice".to_owned(), "bob".to_owned()]);
+
+        let result = client
+            .set_engine_acl(&id, allowed_principals.clone(), Some(0))
+            .await?;
+
+        assert_eq!(result, Some(()));
+        let info = client.get_engine(&id).await?.unwrap();
+        assert_eq!(info.allowed_principals, Some(allowed_principals));
+        assert_eq!(info.revision, 1);
+        let result = client.set_engine_acl(&id, BTreeSet::new(), Some(0)).await;
+        assert!(result.is_err_and(|e| e.get_error_type() == &RucatErrorType::PreconditionFailed));
+        let result = client
+            .set_engine_acl(&EngineId::try_from("missing")?, BTreeSet::new(), None)
+            .await?;
+        assert_eq!(result, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_engine_endpoints() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        let endpoints = |pairs: &[(&str, &str)]| -> EngineEndpoints {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        let result = client
+            .update_engine_endpoints(&id, endpoints(&[("connect-internal", "sc://a:15002")]))
+            .await?;
+        assert_eq!(result, Some(()));
+        client
+            .update_engine_endpoints(&id, endpoints(&[("ui-external", "https://ui")]))
+            .await?;
+
+        let info = client.get_engine(&id).await?.unwrap();
+        assert_eq!(
+            info.endpoints,
+            endpoints(&[
+                ("connect-internal", "sc://a:15002"),
+                ("ui-external", "https://ui")
+            ])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_endpoints_of_nonexistent_engine() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = EngineId::try_from("nonexistent")?;
+
+        let result = client
+            .update_engine_endpoints(&id, EngineEndpoints::new())
+            .await?;
+
+        assert!(result.is_none());
+        assert!(client.get_engine(&id).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rename_nonexistent_engine() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = EngineId::try_from("nonexistent")?;
+
+        let result = client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    name: Some("renamed".to_owned()),
+                    ..Default::default()
+                },
+                None,
+                None,
+            )
+            .await?;
+
+        assert!(result.is_none());
+        assert!(client.get_engine(&id).await?.is_none());
+        Ok(())
+    }
+
+    async fn get_revision(client: &SurrealDBClient, id: &EngineId) -> Result<u64> {
+        Ok(client.get_engine(id).await?.unwrap().revision)
+    }
+
+    #[tokio::test]
+    async fn revision_increased_by_updates() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        assert_eq!(get_revision(&client, &id).await?, 0);
+
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::WaitToStart,
+                &EngineState::TriggerStart,
+                None,
+                Some(0),
+                None,
+            )
+            .await?;
+        assert_eq!(get_revision(&client, &id).await?, 1);
+        // rechecking the engine does not change it
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::TriggerStart,
+                &EngineState::TriggerStart,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        assert_eq!(get_revision(&client, &id).await?, 1);
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::TriggerStart,
+                &EngineState::Terminated,
+                None,
+                None,
+                None,
+            )
+            .await?;
+        assert_eq!(get_revision(&client, &id).await?, 2);
+        client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    config: Some(BTreeMap::new()),
+                    ..Default::default()
+                },
+                Some(&[EngineState::Terminated]),
+                Some(2),
+            )
+            .await?;
+        assert_eq!(get_revision(&client, &id).await?, 3);
+        client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    name: Some("renamed".to_owned()),
+                    ..Default::default()
+                },
+                None,
+                Some(3),
+            )
+            .await?;
+        assert_eq!(get_revision(&client, &id).await?, 4);
+        client
+            .update_engine_endpoints(&id, EngineEndpoints::new())
+            .await?;
+        assert_eq!(get_revision(&client, &id).await?, 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn updates_fail_on_revision_mismatch() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        client
+            .update_engine_state(
+                &id,
+                &EngineState::WaitToStart,
+                &EngineState::Terminated,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let errors = [
+            client
+                .update_engine_state(
+                    &id,
+                    &EngineState::Terminated,
+                    &EngineState::WaitToStart,
+                    None,
+                    Some(0),
+                    None,
+                )
+                .await
+                .err(),
+            client
+                .update_engine_info(
+                    &id,
+                    EngineInfoUpdate {
+                        config: Some(BTreeMap::new()),
+                        ..Default::default()
+                    },
+                    Some(&[EngineState::Terminated]),
+                    Some(0),
+                )
+                .await
+                .err(),
+            client
+                .update_engine_info(
+                    &id,
+                    EngineInfoUpdate {
+                        name: Some("renamed".to_owned()),
+                        ..Default::default()
+                    },
+                    None,
+                    Some(0),
+                )
+                .await
+                .err(),
+            client
+                .remove_engine(&id, &EngineState::Terminated, Some(0), UNIX_EPOCH)
+                .await
+                .err(),
+        ];
+        for error in errors {
+            let error = error.unwrap();
+            assert_eq!(error.get_error_type(), &RucatErrorType::PreconditionFailed);
+            assert!(error
+                .to_string()
+                .contains("is at revision 1, but revision Some(0) is expected"));
+        }
+        let info = client.get_engine(&id).await?.unwrap();
+        assert_eq!(info.state, EngineState::Terminated);
+        assert_eq!(info.name, "spark");
+        assert_eq!(info.revision, 1);
+
+        let result = client
+            .remove_engine(&id, &EngineState::Terminated, Some(1), UNIX_EPOCH)
+            .await?;
+        assert!(matches!(result, Some(RemoveEngineResult::Success(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_old_engine_without_revision() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let id = add_spark_engine(&client).await?;
+        client
+            .client
+            .query("UPDATE type::thing($tb, $id) UNSET info.revision;")
+            .bind(("tb", client.table.clone()))
+            .bind(("id", id.to_string()))
+            .await
+            .map_err(RucatError::fail_to_update_database)?;
+        assert_eq!(get_revision(&client, &id).await?, 0);
+
+        client
+            .update_engine_info(
+                &id,
+                EngineInfoUpdate {
+                    name: Some("renamed".to_owned()),
+                    ..Default::default()
+                },
+                None,
+                Some(0),
+            )
+            .await?;
+        assert_eq!(get_revision(&client, &id).await?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn health_check_in_memory_database() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let health = client.health_check().await?;
+        assert!(health.version.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn claim_engines_need_update() -> Result<()> {
+        let client = SurrealDBClient::new_in_memory().await?;
+        let mut ids = BTreeSet::new();
+        for _ in 0..10 {
+            ids.insert(add_spark_engine(&client).await?);
+        }
+        let claim = || async {
+            let engines = client
+                .claim_engines_need_update(&[], 6, SystemTime::now(), Duration::from_secs(60))
+                .await?;
+            Ok::<_, RucatError>(
+                engines
+                    .into_iter()
+                    .map(|engine| engine.id)
+                    .collect::<BTreeSet<_>>(),
+            )
+        };
+
+        // a claim conflicting with the concurrent one is retried
+        let (first, second) = ::tokio::join!(claim(), claim());
+        let (first, second) = (first?, second?);
+        assert!(first.is_disjoint(&second));
+        assert_eq!(first.len().max(second.len()), 6);
+        assert_eq!(first.union(&second).cloned().collect::<BTreeSet<_>>(), ids);
+        // claimed engines are not listed until the claim expires
+        assert!(client.list_engines_need_update(&[], 100).await?.is_empty());
+        assert!(claim().await?.is_empty());
+        Ok(())
     }
 }
@@ -0,0 +1,26 @@
+use ::std::collections::BTreeMap;
+
+/// Endpoints to connect to an engine, keyed by [EndpointKind::as_str].
+pub type EngineEndpoints = BTreeMap<String, String>;
+
+/// Well-known kinds of engine endpoints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndpointKind {
+    /// Spark connect endpoint reachable inside the cluster, e.g. the DNS name of the service.
+    ConnectInternal,
+    /// Spark connect endpoint reachable from outside the cluster, e.g. through an ingress.
+    ConnectExternal,
+    /// Web UI of the engine reachable from outside the cluster.
+    UiExternal,
+}
+
+impl EndpointKind {
+    /// Key of the endpoint in [EngineEndpoints].
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::ConnectInternal => "connect-internal",
+            Self::ConnectExternal => "connect-external",
+            Self::UiExternal => "ui-external",
+        }
+    }
+}
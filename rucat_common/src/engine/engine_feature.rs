@@ -0,0 +1,129 @@
+use ::std::collections::BTreeSet;
+
+use ::anyhow::anyhow;
+use ::serde::{Deserialize, Serialize};
+
+use crate::error::{Result, RucatError};
+
+/// Names of the features enabled for an engine.
+pub type EngineFeatures = BTreeSet<String>;
+
+/// Stability level of an engine feature.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum FeatureStability {
+    /// The behaviour may change or be removed in any release.
+    Experimental,
+    Stable,
+}
+
+/// Engine feature that can be enabled per engine.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct EngineFeature {
+    pub name: &'static str,
+    pub stability: FeatureStability,
+    pub description: &'static str,
+}
+
+impl EngineFeature {
+    /// Name of the feature that the engine is `Running` only after it is ready to serve requests,
+    /// rather than as soon as its resource is running.
+    pub const READINESS_PROBE: &'static str = "readiness_probe";
+
+    /// All known engine features.
+    pub const ALL: &'static [EngineFeature] = &[EngineFeature {
+        name: Self::READINESS_PROBE,
+        stability: FeatureStability::Experimental,
+        description: "Engine is Running only after it is ready to serve requests.",
+    }];
+
+    /// Check that all `features` are known.
+    pub fn validate(features: &EngineFeatures) -> Result<()> {
+        match features
+            .iter()
+            .find(|feature| !Self::ALL.iter().any(|known| known.name == *feature))
+        {
+            None => Ok(()),
+            Some(unknown) => {
+                let suggestion = Self::ALL
+                    .iter()
+                    .map(|known| (edit_distance(unknown, known.name), known.name))
+                    .filter(|(distance, _)| *distance <= Self::MAX_SUGGESTION_DISTANCE)
+                    .min()
+                    .map(|(_, name)| format!(" Did you mean `{}`?", name))
+                    .unwrap_or_default();
+                Err(RucatError::invalid_argument(anyhow!(
+                    "Unknown engine feature `{}`.{} Known features: {:?}",
+                    unknown,
+                    suggestion,
+                    Self::ALL.iter().map(|f| f.name).collect::<Vec<_>>()
+                )))
+            }
+        }
+    }
+
+    /// Unknown feature names within this edit distance to a known name are likely typos.
+    const MAX_SUGGESTION_DISTANCE: usize = 3;
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // distances between the prefix of `a` and all prefixes of `b`
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous_diagonal = distances[0];
+        distances[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous_diagonal + usize::from(ca != *cb);
+            previous_diagonal = distances[j + 1];
+            distances[j + 1] = substitution.min(distances[j] + 1).min(distances[j + 1] + 1);
+        }
+    }
+    distances[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("abc", "abc"), 0);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("readines_probe", "readiness_probe"), 1);
+    }
+
+    #[test]
+    fn validate_known_features() -> anyhow::Result<()> {
+        EngineFeature::validate(&EngineFeatures::new())?;
+        EngineFeature::validate(&EngineFeatures::from([
+            EngineFeature::READINESS_PROBE.to_owned()
+        ]))?;
+        Ok(())
+    }
+
+    #[test]
+    fn validate_unknown_feature_with_suggestion() {
+        let result = EngineFeature::validate(&EngineFeatures::from([
+            EngineFeature::READINESS_PROBE.to_owned(),
+            "readines_probe".to_owned(),
+        ]));
+        assert!(result.is_err_and(|e| {
+            e.to_string().starts_with(
+            "Invalid argument: Unknown engine feature `readines_probe`. Did you mean `readiness_probe`? \
+                Known features: [\"readiness_probe\"]"
+        )
+        }));
+    }
+
+    #[test]
+    fn validate_unknown_feature_without_suggestion() {
+        let result = EngineFeature::validate(&EngineFeatures::from(["suspend".to_owned()]));
+        assert!(result.is_err_and(|e| e.to_string().starts_with(
+            "Invalid argument: Unknown engine feature `suspend`. Known features: [\"readiness_probe\"]"
+        )));
+    }
+}
@@ -1,13 +1,18 @@
 use ::core::fmt::Display;
-use ::std::borrow::Cow;
+use ::std::{
+    borrow::Cow,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use ::anyhow::anyhow;
+use ::rand::seq::IndexedRandom;
 use ::serde::{de, Deserialize, Deserializer, Serialize};
 
 use crate::error::{Result, RucatError};
 
 /// Unique identifier for an engine.
 #[derive(Ord, PartialOrd, Eq, PartialEq, Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct EngineId {
     id: Cow<'static, str>,
 }
@@ -65,11 +70,78 @@ impl TryFrom<&'static str> for EngineId {
     }
 }
 
+/// Source of the ids of new engines.
+pub trait IdGenerator: Send + Sync + 'static {
+    fn generate(&self) -> EngineId;
+}
+
+/// Generate random ids of 20 lowercase letters and digits, same as the record ids generated by SurrealDB.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomIdGenerator;
+
+impl RandomIdGenerator {
+    const ID_LENGTH: usize = 20;
+    const ID_CHARS: &'static [u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+}
+
+impl IdGenerator for RandomIdGenerator {
+    fn generate(&self) -> EngineId {
+        let mut rng = ::rand::rng();
+        let id = (0..Self::ID_LENGTH)
+            .map(|_| *Self::ID_CHARS.choose(&mut rng).unwrap() as char)
+            .collect::<String>();
+        // Use `unwrap` because the id is never empty.
+        EngineId::try_from(id).unwrap()
+    }
+}
+
+/// Generate ids `<prefix><n>`, counting from the seed, for reproducible tests.
+#[derive(Debug)]
+pub struct SequentialIdGenerator {
+    prefix: Cow<'static, str>,
+    next: AtomicU64,
+}
+
+impl SequentialIdGenerator {
+    pub fn new(prefix: Cow<'static, str>, seed: u64) -> Self {
+        Self {
+            prefix,
+            next: AtomicU64::new(seed),
+        }
+    }
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn generate(&self) -> EngineId {
+        let n = self.next.fetch_add(1, Ordering::Relaxed);
+        // Use `unwrap` because the id is never empty.
+        EngineId::try_from(format!("{}{}", self.prefix, n)).unwrap()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use ::serde_json::json;
 
+    #[test]
+    fn generate_random_ids() {
+        let generator = RandomIdGenerator;
+        let id = generator.generate().to_string();
+        assert_eq!(id.len(), 20);
+        assert!(id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()));
+        assert_ne!(generator.generate().to_string(), id);
+    }
+
+    #[test]
+    fn generate_sequential_ids() {
+        let generator = SequentialIdGenerator::new(Cow::Borrowed("engine-"), 7);
+        assert_eq!(generator.generate().to_string(), "engine-7");
+        assert_eq!(generator.generate().to_string(), "engine-8");
+    }
+
     #[test]
     fn engine_id_cannot_be_empty() {
         let result = EngineId::try_from("");
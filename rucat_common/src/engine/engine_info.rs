@@ -1,23 +1,115 @@
 use super::*;
 use crate::{
-    engine::EngineState::WaitToStart,
-    error::{Result, RucatError},
+    blob_store::BlobRef,
+    engine::EngineState::{Terminated, WaitToStart},
+    error::Result,
 };
 use ::serde::{Deserialize, Serialize};
 
 /// Whole information of an engine.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(from = "StoredEngineInfo")]
 pub struct EngineInfo {
     pub name: String,
     pub engine_type: EngineType,
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
     pub version: EngineVersion,
     pub state: EngineState,
+    #[cfg_attr(feature = "openapi", schema(value_type = BTreeMap<String, String>))]
     pub config: EngineConfig,
+    /// Timeout in seconds for the engine to be running after it starts.
+    /// Use the `start_in_progress_timeout_secs` of the state monitor if not set.
+    pub start_in_progress_timeout_secs: Option<u32>,
+    /// Names of the features enabled for the engine.
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<String>))]
+    pub features: EngineFeatures,
+    /// Endpoints to connect to the engine, see [EndpointKind].
+    #[cfg_attr(feature = "openapi", schema(value_type = BTreeMap<String, String>))]
+    pub endpoints: EngineEndpoints,
+    /// Error message of the engine before it was restarted from an error state.
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>))]
+    pub last_error: Option<Cow<'static, str>>,
+    /// Authenticated principal who created the engine, `None` if the authentication is disabled.
+    pub created_by: Option<String>,
+    /// Principals allowed to stop, restart, update and delete the engine, besides its creator, see [EngineInfo::allows].
+    /// Only the creator is allowed if not set.
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Vec<String>>))]
+    pub allowed_principals: Option<BTreeSet<String>>,
+    /// Project of the engine, `None` if the server is not configured with projects.
+    /// The resources of the engine are in the Kubernetes namespace of the project, see [get_spark_namespace].
+    pub project: Option<String>,
+    /// References to the blobs of the engine in the blob store by their names, e.g. [crate::blob_store::LOG_TAIL_BLOB].
+    /// The blobs themselves are kept out of the database.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub blobs: BTreeMap<String, BlobRef>,
+    /// Revision of the engine, increased whenever the engine is updated.
+    /// Used as the `ETag` of the engine, so that concurrent updates do not overwrite each other.
+    pub revision: u64,
     /// time when the engine is created.
     /// Note, this is **not** the start time when the engine is RUNNING.
     create_time: EngineTime,
 }
 
+/// [EngineInfo] as stored in the database, including the fields of old versions of rucat.
+#[derive(Deserialize)]
+struct StoredEngineInfo {
+    name: String,
+    engine_type: EngineType,
+    version: EngineVersion,
+    state: EngineState,
+    config: EngineConfig,
+    start_in_progress_timeout_secs: Option<u32>,
+    #[serde(default)]
+    features: EngineFeatures,
+    #[serde(default)]
+    endpoints: EngineEndpoints,
+    /// The single in-cluster endpoint of the engine, replaced by `endpoints`.
+    endpoint: Option<String>,
+    last_error: Option<Cow<'static, str>>,
+    /// Not stored by old versions of rucat.
+    created_by: Option<String>,
+    /// Not stored by old versions of rucat.
+    allowed_principals: Option<BTreeSet<String>>,
+    /// Not stored by old versions of rucat.
+    project: Option<String>,
+    #[serde(default)]
+    blobs: BTreeMap<String, BlobRef>,
+    /// Not stored by old versions of rucat, whose engines are at revision 0.
+    #[serde(default)]
+    revision: u64,
+    create_time: EngineTime,
+}
+
+impl From<StoredEngineInfo> for EngineInfo {
+    fn from(value: StoredEngineInfo) -> Self {
+        let mut endpoints = value.endpoints;
+        if let Some(endpoint) = value.endpoint {
+            endpoints
+                .entry(EndpointKind::ConnectInternal.as_str().to_owned())
+                .or_insert(endpoint);
+        }
+        Self {
+            name: value.name,
+            engine_type: value.engine_type,
+            version: value.version,
+            state: value.state,
+            config: value.config,
+            start_in_progress_timeout_secs: value.start_in_progress_timeout_secs,
+            features: value.features,
+            endpoints,
+            last_error: value.last_error,
+            created_by: value.created_by,
+            allowed_principals: value.allowed_principals,
+            project: value.project,
+            blobs: value.blobs,
+            revision: value.revision,
+            create_time: value.create_time,
+        }
+    }
+}
+
 impl EngineInfo {
     pub fn new(
         name: String,
@@ -33,22 +125,245 @@ impl EngineInfo {
             version,
             state,
             config,
+            start_in_progress_timeout_secs: None,
+            features: EngineFeatures::new(),
+            endpoints: EngineEndpoints::new(),
+            last_error: None,
+            created_by: None,
+            allowed_principals: None,
+            project: None,
+            blobs: BTreeMap::new(),
+            revision: 0,
             create_time,
         }
     }
+
+    /// Time when the engine is created.
+    pub fn get_create_time(&self) -> &EngineTime {
+        &self.create_time
+    }
+}
+
+/// Fields of [EngineInfo] needed to sync the engine state.
+/// Deserializing it skips the engine config, which is the bulk of [EngineInfo].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EngineInfoSummary {
+    pub state: EngineState,
+    /// See [EngineInfo::start_in_progress_timeout_secs].
+    pub start_in_progress_timeout_secs: Option<u32>,
+    #[serde(default)]
+    pub features: EngineFeatures,
+    /// See [EngineInfo::project].
+    #[serde(default)]
+    pub project: Option<String>,
+}
+
+impl From<EngineInfo> for EngineInfoSummary {
+    fn from(value: EngineInfo) -> Self {
+        Self {
+            state: value.state,
+            start_in_progress_timeout_secs: value.start_in_progress_timeout_secs,
+            features: value.features,
+            project: value.project,
+        }
+    }
 }
 
-impl TryFrom<CreateEngineRequest> for EngineInfo {
-    type Error = RucatError;
+impl EngineInfo {
+    /// Whether the `principal` is allowed to change the engine, i.e. it is the creator or in [EngineInfo::allowed_principals].
+    /// Engines created without authentication are allowed to everyone unless their principals are set.
+    pub fn allows(&self, principal: &str) -> bool {
+        match (&self.created_by, &self.allowed_principals) {
+            (None, None) => true,
+            (created_by, allowed_principals) => {
+                created_by.as_deref() == Some(principal)
+                    || allowed_principals
+                        .as_ref()
+                        .is_some_and(|principals| principals.contains(principal))
+            }
+        }
+    }
 
-    fn try_from(value: CreateEngineRequest) -> Result<Self> {
-        Ok(EngineInfo::new(
+    /// Information of a new engine created by the request at `create_time`.
+    pub fn from_create_request(
+        value: CreateEngineRequest,
+        create_time: EngineTime,
+    ) -> Result<Self> {
+        let features = value.features.unwrap_or_default();
+        EngineFeature::validate(&features)?;
+        let state = if value.start.unwrap_or(true) {
+            WaitToStart
+        } else {
+            Terminated
+        };
+        let mut info = EngineInfo::new(
             value.name,
             value.engine_type,
             value.version,
-            WaitToStart,
+            state,
             value.config.unwrap_or_default(),
+            create_time,
+        );
+        info.start_in_progress_timeout_secs = value.start_in_progress_timeout_secs;
+        info.features = features;
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_engine_request(features: Option<EngineFeatures>) -> CreateEngineRequest {
+        CreateEngineRequest {
+            name: "engine".to_owned(),
+            engine_type: EngineType::Spark,
+            version: "3.5.4".to_owned(),
+            config: None,
+            start_in_progress_timeout_secs: None,
+            features,
+            start: None,
+        }
+    }
+
+    #[test]
+    fn engine_info_with_features() -> anyhow::Result<()> {
+        let features = EngineFeatures::from([EngineFeature::READINESS_PROBE.to_owned()]);
+        let info = EngineInfo::from_create_request(
+            create_engine_request(Some(features.clone())),
+            EngineTime::now(),
+        )?;
+        assert_eq!(info.features, features);
+        let info = EngineInfo::from_create_request(create_engine_request(None), EngineTime::now())?;
+        assert!(info.features.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn engine_info_created_without_start() -> anyhow::Result<()> {
+        let mut request = create_engine_request(None);
+        request.start = Some(false);
+        let info = EngineInfo::from_create_request(request, EngineTime::now())?;
+        assert_eq!(info.state, Terminated);
+        let mut request = create_engine_request(None);
+        request.start = Some(true);
+        let info = EngineInfo::from_create_request(request, EngineTime::now())?;
+        assert_eq!(info.state, WaitToStart);
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_summary_from_engine_info() -> anyhow::Result<()> {
+        let info = EngineInfo::from_create_request(create_engine_request(None), EngineTime::now())?;
+        let summary: EngineInfoSummary = serde_json::from_value(serde_json::to_value(&info)?)?;
+        assert_eq!(summary, EngineInfoSummary::from(info));
+        Ok(())
+    }
+
+    #[test]
+    fn engine_info_round_trip_with_endpoints() -> anyhow::Result<()> {
+        let mut info =
+            EngineInfo::from_create_request(create_engine_request(None), EngineTime::now())?;
+        info.endpoints.insert(
+            EndpointKind::ConnectInternal.as_str().to_owned(),
+            "sc://rucat-spark-abc.default.svc.cluster.local:15002".to_owned(),
+        );
+        let round_trip: EngineInfo = serde_json::from_value(serde_json::to_value(&info)?)?;
+        assert_eq!(round_trip, info);
+        Ok(())
+    }
+
+    #[test]
+    fn migrate_single_endpoint_on_read() -> anyhow::Result<()> {
+        let info = EngineInfo::from_create_request(create_engine_request(None), EngineTime::now())?;
+        let mut value = serde_json::to_value(&info)?;
+        let record = value.as_object_mut().unwrap();
+        record.remove("endpoints");
+        record.insert("endpoint".to_owned(), "sc://old:15002".into());
+
+        let migrated: EngineInfo = serde_json::from_value(value)?;
+        assert_eq!(
+            migrated.endpoints,
+            EngineEndpoints::from([("connect-internal".to_owned(), "sc://old:15002".to_owned())])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn endpoints_take_precedence_over_single_endpoint() -> anyhow::Result<()> {
+        let mut info =
+            EngineInfo::from_create_request(create_engine_request(None), EngineTime::now())?;
+        info.endpoints
+            .insert("connect-internal".to_owned(), "sc://new:15002".to_owned());
+        let mut value = serde_json::to_value(&info)?;
+        value
+            .as_object_mut()
+            .unwrap()
+            .insert("endpoint".to_owned(), "sc://old:15002".into());
+
+        let migrated: EngineInfo = serde_json::from_value(value)?;
+        assert_eq!(migrated.endpoints, info.endpoints);
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_engine_info_without_created_by() -> anyhow::Result<()> {
+        let mut info =
+            EngineInfo::from_create_request(create_engine_request(None), EngineTime::now())?;
+        info.created_by = Some("alice".to_owned());
+        let round_trip: EngineInfo = serde_json::from_value(serde_json::to_value(&info)?)?;
+        assert_eq!(round_trip, info);
+
+        let mut value = serde_json::to_value(&info)?;
+        value.as_object_mut().unwrap().remove("created_by");
+        let old: EngineInfo = serde_json::from_value(value)?;
+        assert_eq!(old.created_by, None);
+        Ok(())
+    }
+
+    #[test]
+    fn allow_creator_and_shared_principals() -> anyhow::Result<()> {
+        let mut info =
+            EngineInfo::from_create_request(create_engine_request(None), EngineTime::now())?;
+        assert!(info.allows("anyone"));
+
+        info.created_by = Some("alice".to_owned());
+        assert!(info.allows("alice"));
+        assert!(!info.allows("bob"));
+
+        info.allowed_principals = Some(BTreeSet::from(["bob".to_owned()]));
+        assert!(info.allows("alice") && info.allows("bob"));
+        assert!(!info.allows("carol"));
+
+        info.created_by = None;
+        assert!(!info.allows("alice") && info.allows("bob"));
+
+        let mut value = serde_json::to_value(&info)?;
+        value.as_object_mut().unwrap().remove("allowed_principals");
+        let old: EngineInfo = serde_json::from_value(value)?;
+        assert_eq!(old.allowed_principals, None);
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_engine_info_without_revision() -> anyhow::Result<()> {
+        let info = EngineInfo::from_create_request(create_engine_request(None), EngineTime::now())?;
+        let mut value = serde_json::to_value(&info)?;
+        value.as_object_mut().unwrap().remove("revision");
+        let old: EngineInfo = serde_json::from_value(value)?;
+        assert_eq!(old.revision, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn engine_info_with_unknown_features() {
+        let features = EngineFeatures::from(["unknown".to_owned()]);
+        let result = EngineInfo::from_create_request(
+            create_engine_request(Some(features)),
             EngineTime::now(),
-        ))
+        );
+        assert!(result.is_err_and(|e| e
+            .to_string()
+            .starts_with("Invalid argument: Unknown engine feature `unknown`.")));
     }
 }
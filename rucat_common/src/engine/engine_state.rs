@@ -1,7 +1,11 @@
+use ::core::{fmt::Display, str::FromStr};
 use ::std::borrow::Cow;
 
+use ::anyhow::anyhow;
 use ::serde::{Deserialize, Serialize};
 
+use crate::error::{Result, RucatError};
+
 /// States of Rucat engine
 /// The states can be described from 2 different perspectives:
 /// 1. The state flow perspective:
@@ -16,6 +20,7 @@ use ::serde::{Deserialize, Serialize};
 ///   `Running` is a special state that it is a `in progress` state because there are engine resources
 ///   associated with it, and engine resources are not stable.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum EngineState {
     WaitToStart,
     TriggerStart,
@@ -30,3 +35,167 @@ pub enum EngineState {
     ErrorCleanInProgress(Cow<'static, str>),
     ErrorClean(Cow<'static, str>),
 }
+
+/// Variant of [EngineState] without the error message, e.g. for filtering engines by state.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EngineStateKind {
+    WaitToStart,
+    TriggerStart,
+    StartInProgress,
+    Running,
+    WaitToTerminate,
+    TriggerTermination,
+    TerminateInProgress,
+    Terminated,
+    ErrorWaitToClean,
+    ErrorTriggerClean,
+    ErrorCleanInProgress,
+    ErrorClean,
+}
+
+impl EngineStateKind {
+    pub const ALL: &'static [Self] = &[
+        Self::WaitToStart,
+        Self::TriggerStart,
+        Self::StartInProgress,
+        Self::Running,
+        Self::WaitToTerminate,
+        Self::TriggerTermination,
+        Self::TerminateInProgress,
+        Self::Terminated,
+        Self::ErrorWaitToClean,
+        Self::ErrorTriggerClean,
+        Self::ErrorCleanInProgress,
+        Self::ErrorClean,
+    ];
+
+    /// Whether the engine is in one of the `Error*` states.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            Self::ErrorWaitToClean
+                | Self::ErrorTriggerClean
+                | Self::ErrorCleanInProgress
+                | Self::ErrorClean
+        )
+    }
+
+    /// Whether the engine is in one of the stable states, i.e. `Terminated` or `ErrorClean`,
+    /// which are not changed by the state monitor and have no resource to clean.
+    pub fn is_stable(&self) -> bool {
+        matches!(self, Self::Terminated | Self::ErrorClean)
+    }
+}
+
+impl From<&EngineState> for EngineStateKind {
+    fn from(state: &EngineState) -> Self {
+        match state {
+            EngineState::WaitToStart => Self::WaitToStart,
+            EngineState::TriggerStart => Self::TriggerStart,
+            EngineState::StartInProgress => Self::StartInProgress,
+            EngineState::Running => Self::Running,
+            EngineState::WaitToTerminate => Self::WaitToTerminate,
+            EngineState::TriggerTermination => Self::TriggerTermination,
+            EngineState::TerminateInProgress => Self::TerminateInProgress,
+            EngineState::Terminated => Self::Terminated,
+            EngineState::ErrorWaitToClean(_) => Self::ErrorWaitToClean,
+            EngineState::ErrorTriggerClean(_) => Self::ErrorTriggerClean,
+            EngineState::ErrorCleanInProgress(_) => Self::ErrorCleanInProgress,
+            EngineState::ErrorClean(_) => Self::ErrorClean,
+        }
+    }
+}
+
+impl Display for EngineStateKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for EngineStateKind {
+    type Err = RucatError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .iter()
+            .find(|kind| kind.to_string() == s)
+            .copied()
+            .ok_or_else(|| {
+                RucatError::invalid_argument(anyhow!(
+                    "Unknown engine state `{}`, expected one of {:?}",
+                    s,
+                    Self::ALL
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn kind_of_error_states_ignores_the_message() {
+        assert_eq!(
+            EngineStateKind::from(&EngineState::ErrorClean(Cow::Borrowed("error"))),
+            EngineStateKind::ErrorClean
+        );
+        assert_eq!(
+            EngineStateKind::from(&EngineState::Running),
+            EngineStateKind::Running
+        );
+    }
+
+    #[test]
+    fn kind_is_serialized_as_the_state_name() -> anyhow::Result<()> {
+        for kind in EngineStateKind::ALL {
+            assert_eq!(serde_json::to_value(kind)?, json!(kind.to_string()));
+            assert_eq!(&kind.to_string().parse::<EngineStateKind>()?, kind);
+        }
+        assert_eq!(
+            serde_json::to_value(EngineState::Running)?,
+            serde_json::to_value(EngineStateKind::Running)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn error_kinds() {
+        let errors = EngineStateKind::ALL
+            .iter()
+            .filter(|kind| kind.is_error())
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                "ErrorWaitToClean",
+                "ErrorTriggerClean",
+                "ErrorCleanInProgress",
+                "ErrorClean"
+            ]
+        );
+    }
+
+    #[test]
+    fn stable_kinds() {
+        let stable = EngineStateKind::ALL
+            .iter()
+            .filter(|kind| kind.is_stable())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stable,
+            [&EngineStateKind::Terminated, &EngineStateKind::ErrorClean]
+        );
+    }
+
+    #[test]
+    fn parse_unknown_kind() {
+        let result = "Pending".parse::<EngineStateKind>();
+        assert!(result.is_err_and(|e| e.to_string().starts_with(
+            "Invalid argument: Unknown engine state `Pending`, expected one of [WaitToStart,"
+        )));
+    }
+}
@@ -1,10 +1,14 @@
-use ::serde::{Deserialize, Serialize};
+use ::std::time::SystemTime;
+
+use ::serde::{de, Deserialize, Deserializer, Serialize};
 use ::time::{
     format_description::BorrowedFormatItem, macros::format_description, Duration, OffsetDateTime,
 };
 
 /// Type of time in engine.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Times are ordered as strings, the same as sorted by databases, which is chronological for times in UTC.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct EngineTime(String);
 
 impl EngineTime {
@@ -15,9 +19,16 @@ impl EngineTime {
 
     /// Return a new [EngineTime] with the current time.
     pub fn now() -> Self {
+        Self::from_system_time(SystemTime::now())
+    }
+
+    /// Return a new [EngineTime] with the given time, e.g. got from a [crate::clock::Clock].
+    pub fn from_system_time(time: SystemTime) -> Self {
         Self(
             // Use `unwrap` because the format is fixed.
-            OffsetDateTime::now_utc().format(Self::FORMAT_DESC).unwrap(),
+            OffsetDateTime::from(time)
+                .format(Self::FORMAT_DESC)
+                .unwrap(),
         )
     }
 
@@ -25,8 +36,65 @@ impl EngineTime {
     /// TODO: remove this if not used.
     pub fn elapsed_time(&self) -> Duration {
         let now = OffsetDateTime::now_utc();
-        // Use `unwrap` because the format is fixed.
+        // Use `unwrap` because the time is validated when the [EngineTime] is created.
         let time = OffsetDateTime::parse(&self.0, Self::FORMAT_DESC).unwrap();
         now - time
     }
 }
+
+/// Almost same as the derive macro generated implementation,
+/// except the string must be in the format of [EngineTime::FORMAT_DESC].
+/// Stored times are not trusted, so that [EngineTime::elapsed_time] never panics.
+impl<'de> Deserialize<'de> for EngineTime {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let time = String::deserialize(deserializer)?;
+        OffsetDateTime::parse(&time, Self::FORMAT_DESC).map_err(|e| {
+            de::Error::custom(format!(
+                "Failed to deserialize EngineTime {:?}: {}",
+                time, e
+            ))
+        })?;
+        Ok(Self(time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::serde_json::json;
+
+    #[test]
+    fn engine_time_ser_de_identity() -> anyhow::Result<()> {
+        let time = EngineTime::now();
+        let json = serde_json::to_value(&time)?;
+        let time2: EngineTime = serde_json::from_value(json)?;
+        assert_eq!(time, time2);
+        assert!(time2.elapsed_time() >= Duration::ZERO);
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_engine_time() -> anyhow::Result<()> {
+        let time: EngineTime = serde_json::from_value(json!("2025-01-02 03:04:05 +00:00:00"))?;
+        assert_eq!(time, EngineTime("2025-01-02 03:04:05 +00:00:00".to_owned()));
+        Ok(())
+    }
+
+    #[test]
+    fn cannot_deserialize_malformed_engine_time() {
+        for time in [
+            "",
+            "2025-01-02",
+            "2025-13-02 03:04:05 +00:00:00",
+            "not a time",
+        ] {
+            let result: std::result::Result<EngineTime, _> = serde_json::from_value(json!(time));
+            assert!(result.is_err_and(|e| e
+                .to_string()
+                .starts_with(&format!("Failed to deserialize EngineTime {:?}", time))));
+        }
+    }
+}
@@ -1,7 +1,26 @@
 use ::serde::{Deserialize, Serialize};
 
+use super::SPARK_RESERVED_CONFIGS;
+
 /// Type of engine.
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum EngineType {
     Spark,
 }
+
+impl EngineType {
+    /// Versions of the engine that rucat can run by default, see [super::EngineVersions].
+    pub fn get_supported_versions(&self) -> &'static [&'static str] {
+        match self {
+            Self::Spark => &["3.5.3", "3.5.4"],
+        }
+    }
+
+    /// Configs set by rucat, which users are not allowed to set.
+    pub fn get_reserved_configs(&self) -> &'static [&'static str] {
+        match self {
+            Self::Spark => SPARK_RESERVED_CONFIGS,
+        }
+    }
+}
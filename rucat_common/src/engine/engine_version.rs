@@ -0,0 +1,170 @@
+use ::std::collections::BTreeMap;
+
+use ::anyhow::anyhow;
+use ::serde::{Deserialize, Serialize};
+use ::time::Date;
+
+use super::{EngineType, EngineVersion};
+use crate::error::{Result, RucatError};
+
+::time::serde::format_description!(sunset_date_format, Date, "[year]-[month]-[day]");
+
+/// Lifecycle status of an engine version.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum VersionStatus {
+    /// Engines of the version can be created and started.
+    Supported,
+    /// Engines of the version can still be created and started, but with a warning.
+    Deprecated {
+        message: String,
+        /// Date in `YYYY-MM-DD` after which the version is planned to be blocked.
+        #[serde(with = "sunset_date_format")]
+        sunset_date: Date,
+    },
+    /// Engines of the version can neither be created nor restarted.
+    /// Running engines are not affected until they stop.
+    Blocked { message: String },
+}
+
+/// Allow-list of the engine versions that rucat can run, with the status of each version.
+/// Versions not in the list are rejected as invalid arguments, blocked versions are not allowed.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct EngineVersions(BTreeMap<EngineType, BTreeMap<EngineVersion, VersionStatus>>);
+
+impl Default for EngineVersions {
+    /// All the [EngineType::get_supported_versions] are supported.
+    fn default() -> Self {
+        Self(BTreeMap::from([(
+            EngineType::Spark,
+            EngineType::Spark
+                .get_supported_versions()
+                .iter()
+                .map(|version| (version.to_string(), VersionStatus::Supported))
+                .collect(),
+        )]))
+    }
+}
+
+impl EngineVersions {
+    /// Status of the version, `None` if it is not in the allow-list.
+    pub fn get_status(&self, engine_type: &EngineType, version: &str) -> Option<&VersionStatus> {
+        self.0.get(engine_type)?.get(version)
+    }
+
+    /// Check that engines of the version can be created or started.
+    /// Return the warning to show if the version is deprecated.
+    /// A blocked version is not allowed, while a version not in the allow-list is an invalid argument.
+    pub fn check(&self, engine_type: &EngineType, version: &str) -> Result<Option<String>> {
+        match self.get_status(engine_type, version) {
+            Some(VersionStatus::Supported) => Ok(None),
+            Some(VersionStatus::Deprecated {
+                message,
+                sunset_date,
+            }) => Ok(Some(format!(
+                "{:?} version {} is deprecated and will be blocked after {}: {}",
+                engine_type, version, sunset_date, message
+            ))),
+            Some(VersionStatus::Blocked { message }) => Err(RucatError::not_allowed(anyhow!(
+                "{:?} version {} is blocked: {}",
+                engine_type,
+                version,
+                message
+            ))),
+            None => Err(RucatError::invalid_argument(anyhow!(
+                "{:?} version {} is not supported. Supported versions: {:?}",
+                engine_type,
+                version,
+                self.get_usable_versions(engine_type)
+            ))),
+        }
+    }
+
+    /// Versions of the engine type that are not blocked.
+    pub(super) fn get_usable_versions(&self, engine_type: &EngineType) -> Vec<&str> {
+        self.0
+            .get(engine_type)
+            .into_iter()
+            .flatten()
+            .filter(|(_, status)| !matches!(status, VersionStatus::Blocked { .. }))
+            .map(|(version, _)| version.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::serde_json::json;
+    use ::time::macros::date;
+
+    use super::*;
+
+    fn versions() -> anyhow::Result<EngineVersions> {
+        Ok(serde_json::from_value(json!({
+            "Spark": {
+                "3.5.3": {"deprecated": {"message": "upgrade to 3.5.4", "sunset_date": "2025-06-30"}},
+                "3.5.4": "supported",
+                "3.4.0": {"blocked": {"message": "3.4 is not maintained"}}
+            }
+        }))?)
+    }
+
+    #[test]
+    fn deserialize_version_status() -> anyhow::Result<()> {
+        let versions = versions()?;
+        assert_eq!(
+            versions.get_status(&EngineType::Spark, "3.5.3"),
+            Some(&VersionStatus::Deprecated {
+                message: "upgrade to 3.5.4".to_owned(),
+                sunset_date: date!(2025 - 06 - 30)
+            })
+        );
+        assert_eq!(
+            versions.get_status(&EngineType::Spark, "3.5.4"),
+            Some(&VersionStatus::Supported)
+        );
+        assert_eq!(versions.get_status(&EngineType::Spark, "3.5.5"), None);
+        let round_trip: EngineVersions = serde_json::from_value(serde_json::to_value(&versions)?)?;
+        assert_eq!(round_trip, versions);
+        Ok(())
+    }
+
+    #[test]
+    fn sunset_date_must_be_a_date() {
+        let result = serde_json::from_value::<VersionStatus>(json!({
+            "deprecated": {"message": "upgrade", "sunset_date": "next year"}
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn default_versions_are_supported() {
+        let versions = EngineVersions::default();
+        for version in EngineType::Spark.get_supported_versions() {
+            assert_eq!(
+                versions.get_status(&EngineType::Spark, version),
+                Some(&VersionStatus::Supported)
+            );
+        }
+    }
+
+    #[test]
+    fn check_versions() -> anyhow::Result<()> {
+        let versions = versions()?;
+        assert_eq!(versions.check(&EngineType::Spark, "3.5.4")?, None);
+        assert_eq!(
+            versions.check(&EngineType::Spark, "3.5.3")?.as_deref(),
+            Some("Spark version 3.5.3 is deprecated and will be blocked after 2025-06-30: upgrade to 3.5.4")
+        );
+        assert!(versions.check(&EngineType::Spark, "3.4.0").is_err_and(|e| e
+            .to_string()
+            .starts_with("Not allowed: Spark version 3.4.0 is blocked: 3.4 is not maintained")));
+        assert!(versions
+            .check(&EngineType::Spark, "3.5.5")
+            .is_err_and(|e| e.to_string().starts_with(
+                "Invalid argument: Spark version 3.5.5 is not supported. Supported versions: [\"3.5.3\", \"3.5.4\"]"
+            )));
+        Ok(())
+    }
+}
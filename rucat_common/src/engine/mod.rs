@@ -1,29 +1,561 @@
+use ::anyhow::anyhow;
 use ::serde::{Deserialize, Serialize};
-use ::std::{borrow::Cow, collections::BTreeMap};
+use ::std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
 
+use crate::error::{Result, RucatError};
+
+mod engine_endpoint;
+mod engine_feature;
 mod engine_id;
 mod engine_info;
 mod engine_state;
 mod engine_time;
 mod engine_type;
+mod engine_version;
+mod spark_resource;
 
-pub use engine_id::EngineId;
-pub use engine_info::EngineInfo;
-pub use engine_state::EngineState;
+pub use engine_endpoint::{EndpointKind, EngineEndpoints};
+pub use engine_feature::{EngineFeature, EngineFeatures, FeatureStability};
+pub use engine_id::{EngineId, IdGenerator, RandomIdGenerator, SequentialIdGenerator};
+pub use engine_info::{EngineInfo, EngineInfoSummary};
+pub use engine_state::{EngineState, EngineStateKind};
 pub use engine_time::EngineTime;
 pub use engine_type::EngineType;
+pub use engine_version::{EngineVersions, VersionStatus};
+pub use spark_resource::{
+    get_spark_app_id, get_spark_connect_url, get_spark_driver_name, get_spark_namespace,
+    get_spark_service_host, get_spark_service_name, get_spark_ui_url, SPARK_CONNECT_PORT,
+    SPARK_NAMESPACE, SPARK_RESERVED_CONFIGS, SPARK_UI_PORT,
+};
 
 pub type EngineVersion = String;
+
+/// Label of the resources that belong to an engine, whose value is the [EngineId].
+pub const ENGINE_ID_LABEL: &str = "rucat.io/engine-id";
 pub type EngineConfig = BTreeMap<Cow<'static, str>, Cow<'static, str>>;
 
+/// Response body of listing engines.
+/// Engines are listed as [EngineId]s by default, or as [EngineSummary]s on request.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ListEnginesResponse<T = EngineId> {
+    /// Engines in the page, sorted by id unless sorted by another key on request.
+    pub engines: Vec<T>,
+    /// Offset of the next page, `None` if this is the last page.
+    pub next_offset: Option<usize>,
+}
+
+/// Fields of [EngineInfo] to show engines in a list, without the engine config.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EngineSummary {
+    pub id: EngineId,
+    pub name: String,
+    pub engine_type: EngineType,
+    pub state: EngineState,
+    pub create_time: EngineTime,
+}
+
 /// Request body to create an engine.
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(deny_unknown_fields)]
 pub struct CreateEngineRequest {
-    // The name of the engine
+    /// The name of the engine
     pub name: String,
     pub engine_type: EngineType,
+    #[cfg_attr(feature = "openapi", schema(value_type = String))]
     pub version: EngineVersion,
-    // Engine configurations
+    /// Engine configurations
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<BTreeMap<String, String>>))]
     pub config: Option<EngineConfig>,
+    /// Timeout in seconds for the engine to be running after it starts.
+    /// Override the `start_in_progress_timeout_secs` of the state monitor if set.
+    pub start_in_progress_timeout_secs: Option<u32>,
+    /// Names of the features enabled for the engine, see [EngineFeature::ALL].
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Vec<String>>))]
+    pub features: Option<EngineFeatures>,
+    /// Whether to start the engine after it is created, `true` by default.
+    /// If `false`, the engine is created in [EngineState::Terminated] state and started by restarting it later.
+    pub start: Option<bool>,
+}
+
+/// Default maximum number of characters in the name of an engine.
+pub const DEFAULT_MAX_ENGINE_NAME_LENGTH: usize = 256;
+
+/// Check the engine name has 1 to `max_length` characters,
+/// which are letters, digits, spaces, `-`, `_` or `.`.
+pub fn validate_engine_name(name: &str, max_length: usize) -> Result<()> {
+    let length = name.chars().count();
+    if length == 0 {
+        return Err(RucatError::invalid_argument(anyhow!(
+            "Engine name must not be empty"
+        )));
+    }
+    if length > max_length {
+        return Err(RucatError::invalid_argument(anyhow!(
+            "Engine name must be at most {} characters, but got {}",
+            max_length,
+            length
+        )));
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.')))
+    {
+        return Err(RucatError::invalid_argument(anyhow!(
+            "Engine name must only contain letters, digits, spaces, `-`, `_` or `.`, but got {:?}",
+            c
+        )));
+    }
+    Ok(())
+}
+
+impl CreateEngineRequest {
+    /// Check the fields of the request before the engine is added,
+    /// instead of failing the engine when the state monitor starts it.
+    pub fn validate(&self, max_name_length: usize, versions: &EngineVersions) -> Result<()> {
+        validate_engine_name(&self.name, max_name_length)?;
+        if versions
+            .get_status(&self.engine_type, &self.version)
+            .is_none()
+        {
+            return Err(RucatError::invalid_argument(anyhow!(
+                "{:?} version {} is not supported. Supported versions: {:?}",
+                self.engine_type,
+                self.version,
+                versions.get_usable_versions(&self.engine_type)
+            )));
+        }
+        self.config.as_ref().map_or(Ok(()), |config| {
+            validate_engine_config(&self.engine_type, config)
+        })
+    }
+}
+
+/// Check the config does not set any config reserved by rucat for the `engine_type`.
+pub fn validate_engine_config(engine_type: &EngineType, config: &EngineConfig) -> Result<()> {
+    let reserved_configs = engine_type.get_reserved_configs();
+    if let Some(key) = config
+        .keys()
+        .find(|key| reserved_configs.contains(&key.as_ref()))
+    {
+        return Err(RucatError::invalid_argument(anyhow!(
+            "Engine config `{}` is reserved by rucat. Reserved configs: {:?}",
+            key,
+            reserved_configs
+        )));
+    }
+    Ok(())
+}
+
+/// Response body of creating an engine.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateEngineResponse {
+    #[serde(flatten)]
+    pub id: EngineId,
+    /// Warnings about the created engine, e.g. its version is deprecated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Response body of getting how to connect to a running engine.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EngineConnectInfo {
+    /// URL of the Spark connect server, e.g. `sc://rucat-spark-<id>.default.svc.cluster.local:15002`.
+    pub spark_connect_url: String,
+    /// URL of the Spark web UI.
+    pub spark_ui_url: String,
+    /// Host of the service of the Spark driver inside the cluster.
+    pub driver_host: String,
+}
+
+/// Event of a resource of an engine reported by the resource manager,
+/// e.g. why the driver pod of the engine cannot be scheduled.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EngineResourceEvent {
+    /// Kind and name of the resource, e.g. `Pod/rucat-spark-<id>-driver`.
+    pub object: String,
+    /// Type of the event, e.g. `Normal` or `Warning`.
+    pub event_type: String,
+    /// Short reason of the event, e.g. `FailedScheduling`.
+    pub reason: String,
+    pub message: String,
+    /// Number of times the event occurred.
+    pub count: i32,
+    /// Time of the last occurrence of the event, if known.
+    pub last_timestamp: Option<EngineTime>,
+}
+
+/// Response body of getting the events of the resources of an engine.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EngineResourceEventsResponse {
+    /// Events sorted by the last occurrence, the most recent first.
+    pub events: Vec<EngineResourceEvent>,
+}
+
+/// Response body of getting the state of an engine.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EngineStateResponse {
+    pub state: EngineState,
+}
+
+/// Authenticated principal changing the state of an engine through the server, recorded in [EngineEvent].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineActor {
+    pub name: String,
+    /// Admin acting on behalf of the principal, if it is impersonated.
+    pub impersonator: Option<String>,
+}
+
+/// Transition of the state of an engine, recorded in the history of the engine.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EngineEvent {
+    pub old_state: EngineState,
+    pub new_state: EngineState,
+    /// Component changing the state, e.g. `server` or `state_monitor`.
+    pub source: String,
+    /// Principal requesting the change, see [EngineActor].
+    /// `None` if the state is changed by rucat itself, or the authentication is disabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    /// Admin acting on behalf of the `actor`, if it is impersonated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonator: Option<String>,
+    pub time: EngineTime,
+}
+
+/// Response body of getting the state history of an engine.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EngineHistoryResponse {
+    /// The latest events of the engine, newest first.
+    pub events: Vec<EngineEvent>,
+}
+
+/// Response body of counting the engines in each state.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EngineStatsResponse {
+    /// Number of engines in each non-error state, and in all the error states as [Self::ERROR_BUCKET].
+    /// States without engines are counted as 0.
+    pub states: BTreeMap<String, usize>,
+    /// Number of engines in each error state, breaking down the [Self::ERROR_BUCKET].
+    #[cfg_attr(feature = "openapi", schema(value_type = BTreeMap<String, usize>))]
+    pub errors: BTreeMap<EngineStateKind, usize>,
+    /// Number of all the engines.
+    pub total: usize,
+}
+
+impl EngineStatsResponse {
+    /// Name of the bucket of all the error states in [Self::states].
+    pub const ERROR_BUCKET: &'static str = "Error";
+}
+
+impl From<BTreeMap<EngineStateKind, usize>> for EngineStatsResponse {
+    /// Group the number of engines in each state, see [crate::database::Database::count_engines_by_state].
+    fn from(counts: BTreeMap<EngineStateKind, usize>) -> Self {
+        let mut states = BTreeMap::new();
+        let mut errors = BTreeMap::new();
+        for kind in EngineStateKind::ALL {
+            let count = counts.get(kind).copied().unwrap_or_default();
+            if kind.is_error() {
+                errors.insert(*kind, count);
+                *states.entry(Self::ERROR_BUCKET.to_owned()).or_default() += count;
+            } else {
+                states.insert(kind.to_string(), count);
+            }
+        }
+        Self {
+            states,
+            errors,
+            total: counts.values().sum(),
+        }
+    }
+}
+
+/// Request body to rename an engine.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(deny_unknown_fields)]
+pub struct RenameEngineRequest {
+    pub name: String,
+}
+
+/// Request body to set the principals allowed to change an engine, see [EngineInfo::allowed_principals].
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(deny_unknown_fields)]
+pub struct SetEngineAclRequest {
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<String>))]
+    pub allowed_principals: BTreeSet<String>,
+}
+
+/// Request body to delete engines in a batch.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(deny_unknown_fields)]
+pub struct BatchDeleteEnginesRequest {
+    pub ids: Vec<EngineId>,
+}
+
+/// Result of deleting an engine in a batch.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteEngineResult {
+    Deleted,
+    NotFound,
+    /// The engine is not deleted because it is in `state`, e.g. `Running`.
+    NotAllowed {
+        state: EngineState,
+    },
+}
+
+/// Response of deleting an engine with `force=true`.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ForceDeleteEngineResponse {
+    /// Whether the deletion needed the force, i.e. the engine was in a state that cannot be deleted
+    /// without it, so the cleanup of its resources was skipped and they may be orphaned.
+    pub forced: bool,
+    /// State of the engine when it was deleted.
+    pub state: EngineState,
+}
+
+/// Response of deleting engines in a batch.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchDeleteEnginesResponse {
+    /// Result of each engine, keyed by engine id.
+    pub results: BTreeMap<String, DeleteEngineResult>,
+}
+
+/// Request body to get engines in a batch.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(deny_unknown_fields)]
+pub struct BatchGetEnginesRequest {
+    pub ids: Vec<EngineId>,
+}
+
+/// Engine got in a batch.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchGetEngineResult {
+    pub id: String,
+    /// `None` if the engine is not found.
+    pub info: Option<EngineInfo>,
+}
+
+/// Response of getting engines in a batch.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchGetEnginesResponse {
+    /// Engines in the order of the ids in the request.
+    pub engines: Vec<BatchGetEngineResult>,
+}
+
+/// Request body to remove all the stopped or cleaned engines in some states.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(deny_unknown_fields)]
+pub struct CleanupEnginesRequest {
+    /// States of the engines to remove, which must be stable, see [EngineStateKind::is_stable].
+    #[cfg_attr(feature = "openapi", schema(value_type = Vec<String>))]
+    pub states: BTreeSet<EngineStateKind>,
+    /// Only remove engines that have been in the state for longer than this,
+    /// or created longer than this ago if the time of entering the state is not recorded.
+    pub older_than_secs: u64,
+}
+
+impl CleanupEnginesRequest {
+    /// Check that only the engines without resources are removed.
+    pub fn validate(&self) -> Result<()> {
+        if self.states.is_empty() {
+            return Err(RucatError::invalid_argument(anyhow!(
+                "`states` of the engines to clean up must not be empty"
+            )));
+        }
+        let unstable = Vec::from_iter(self.states.iter().filter(|kind| !kind.is_stable()));
+        if !unstable.is_empty() {
+            return Err(RucatError::invalid_argument(anyhow!(
+                "Only engines in stable states can be cleaned up, but got {:?}. Stable states: {:?}",
+                unstable,
+                Vec::from_iter(EngineStateKind::ALL.iter().filter(|kind| kind.is_stable()))
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Response of cleaning up engines.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CleanupEnginesResponse {
+    /// Number of the removed engines.
+    pub removed: usize,
+}
+
+/// Request body to adopt an engine that is deployed without rucat, e.g. by Helm.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(deny_unknown_fields)]
+pub struct AdoptEngineRequest {
+    /// Name of the pod running the engine.
+    pub pod: String,
+    /// Name of the service exposing the pod, if any. It is cleaned together with the pod.
+    pub service: Option<String>,
+    pub namespace: String,
+    /// Id of the engine. Generated if not set.
+    pub id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RucatErrorType;
+
+    fn create_engine_request(
+        name: &str,
+        version: &str,
+        config: EngineConfig,
+    ) -> CreateEngineRequest {
+        CreateEngineRequest {
+            name: name.to_owned(),
+            engine_type: EngineType::Spark,
+            version: version.to_owned(),
+            config: Some(config),
+            start_in_progress_timeout_secs: None,
+            features: None,
+            start: None,
+        }
+    }
+
+    fn assert_invalid(request: CreateEngineRequest, message: &str) {
+        let result = request.validate(16, &EngineVersions::default());
+        assert!(
+            result.is_err_and(|e| e.get_error_type() == &RucatErrorType::InvalidArgument
+                && e.get_message() == message)
+        );
+    }
+
+    #[test]
+    fn engine_stats_from_counts() -> anyhow::Result<()> {
+        let stats = EngineStatsResponse::from(BTreeMap::from([
+            (EngineStateKind::Running, 3),
+            (EngineStateKind::ErrorClean, 2),
+            (EngineStateKind::ErrorWaitToClean, 1),
+        ]));
+        assert_eq!(stats.total, 6);
+        assert_eq!(stats.states.len(), 9);
+        assert_eq!(stats.states["Running"], 3);
+        assert_eq!(stats.states["WaitToStart"], 0);
+        assert_eq!(stats.states["Error"], 3);
+        assert_eq!(
+            serde_json::to_value(&stats.errors)?,
+            serde_json::json!({
+                "ErrorWaitToClean": 1,
+                "ErrorTriggerClean": 0,
+                "ErrorCleanInProgress": 0,
+                "ErrorClean": 2
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn validate_cleanup_engines_request() -> Result<()> {
+        let request = |states: &[EngineStateKind]| CleanupEnginesRequest {
+            states: BTreeSet::from_iter(states.iter().copied()),
+            older_than_secs: 86400,
+        };
+        request(&[EngineStateKind::Terminated, EngineStateKind::ErrorClean]).validate()?;
+        assert!(request(&[]).validate().is_err_and(|e| e
+            .to_string()
+            .contains("Invalid argument: `states` of the engines to clean up must not be empty")));
+        assert!(request(&[
+            EngineStateKind::Running,
+            EngineStateKind::Terminated,
+            EngineStateKind::ErrorCleanInProgress
+        ])
+        .validate()
+        .is_err_and(|e| e.to_string().contains(
+            "Invalid argument: Only engines in stable states can be cleaned up, \
+                but got [Running, ErrorCleanInProgress]. Stable states: [Terminated, ErrorClean]"
+        )));
+        Ok(())
+    }
+
+    #[test]
+    fn validate_engine_name_chars() -> Result<()> {
+        validate_engine_name("My engine_1.0-rc 数据", 256)?;
+        for (name, c) in [("a/b", "'/'"), ("a\nb", "'\\n'"), ("a;b", "';'")] {
+            let result = validate_engine_name(name, 256);
+            assert!(result.is_err_and(|e| e.get_message().ends_with(&format!("but got {}", c))));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn validate_create_engine_request() -> Result<()> {
+        create_engine_request(
+            "engine",
+            "3.5.4",
+            BTreeMap::from([(
+                Cow::Borrowed("spark.executor.instances"),
+                Cow::Borrowed("1"),
+            )]),
+        )
+        .validate(16, &EngineVersions::default())?;
+        let mut request = create_engine_request("engine", "3.5.4", BTreeMap::new());
+        request.config = None;
+        request.validate(16, &EngineVersions::default())
+    }
+
+    #[test]
+    fn validate_create_engine_request_with_invalid_name() {
+        assert_invalid(
+            create_engine_request("", "3.5.4", BTreeMap::new()),
+            "Engine name must not be empty",
+        );
+        assert_invalid(
+            create_engine_request(&"a".repeat(17), "3.5.4", BTreeMap::new()),
+            "Engine name must be at most 16 characters, but got 17",
+        );
+    }
+
+    #[test]
+    fn validate_create_engine_request_with_unsupported_version() {
+        assert_invalid(
+            create_engine_request("engine", "3.4.0", BTreeMap::new()),
+            "Spark version 3.4.0 is not supported. Supported versions: [\"3.5.3\", \"3.5.4\"]",
+        );
+    }
+
+    #[test]
+    fn validate_create_engine_request_with_reserved_config() {
+        for key in SPARK_RESERVED_CONFIGS {
+            assert_invalid(
+                create_engine_request(
+                    "engine",
+                    "3.5.4",
+                    BTreeMap::from([(Cow::Borrowed(*key), Cow::Borrowed("value"))]),
+                ),
+                &format!(
+                    "Engine config `{}` is reserved by rucat. Reserved configs: {:?}",
+                    key, SPARK_RESERVED_CONFIGS
+                ),
+            );
+        }
+    }
 }
@@ -0,0 +1,123 @@
+//! Names and addresses of the Kubernetes resources of the Spark engines created by rucat,
+//! shared by the state monitor creating the resources and the server telling clients how to connect to them.
+
+use ::std::borrow::Cow;
+
+use super::EngineId;
+
+/// Namespace of the resources of the Spark engines without a project.
+pub const SPARK_NAMESPACE: &str = "default";
+/// Port of the Spark connect server.
+pub const SPARK_CONNECT_PORT: u16 = 15002;
+/// Port of the Spark web UI.
+pub const SPARK_UI_PORT: u16 = 4040;
+/// Spark configs set by rucat to run the engine on Kubernetes, which users are not allowed to set.
+pub const SPARK_RESERVED_CONFIGS: &[&str] = &[
+    "spark.app.id",
+    "spark.driver.extraJavaOptions",
+    "spark.driver.host",
+    "spark.kubernetes.container.image",
+    "spark.kubernetes.driver.pod.name",
+    "spark.kubernetes.executor.podNamePrefix",
+];
+
+/// Namespace of the resources of the Spark engines in `project`, which is named after the project.
+pub fn get_spark_namespace(project: Option<&str>) -> &str {
+    project.unwrap_or(SPARK_NAMESPACE)
+}
+
+pub fn get_spark_app_id(id: &EngineId) -> Cow<'static, str> {
+    Cow::Owned(format!("rucat-spark-{}", id))
+}
+
+pub fn get_spark_driver_name(id: &EngineId) -> Cow<'static, str> {
+    Cow::Owned(format!("{}-driver", get_spark_app_id(id)))
+}
+
+pub fn get_spark_service_name(id: &EngineId) -> Cow<'static, str> {
+    get_spark_app_id(id)
+}
+
+/// DNS name of the service of the Spark driver inside the cluster, see [get_spark_namespace] for `project`.
+pub fn get_spark_service_host(id: &EngineId, project: Option<&str>) -> String {
+    format!(
+        "{}.{}.svc.cluster.local",
+        get_spark_service_name(id),
+        get_spark_namespace(project)
+    )
+}
+
+/// URL of the Spark connect server, e.g. for `SparkSession.builder.remote`.
+pub fn get_spark_connect_url(id: &EngineId, project: Option<&str>) -> String {
+    format!(
+        "sc://{}:{}",
+        get_spark_service_host(id, project),
+        SPARK_CONNECT_PORT
+    )
+}
+
+/// URL of the Spark web UI.
+pub fn get_spark_ui_url(id: &EngineId, project: Option<&str>) -> String {
+    format!(
+        "http://{}:{}",
+        get_spark_service_host(id, project),
+        SPARK_UI_PORT
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Result;
+
+    #[test]
+    fn test_get_spark_app_id() -> Result<()> {
+        let id = EngineId::try_from("abc")?;
+        assert_eq!(get_spark_app_id(&id), "rucat-spark-abc");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_spark_driver_name() -> Result<()> {
+        let id = EngineId::try_from("abc")?;
+        assert_eq!(get_spark_driver_name(&id), "rucat-spark-abc-driver");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_spark_service_name() -> Result<()> {
+        let id = EngineId::try_from("abc")?;
+        assert_eq!(get_spark_service_name(&id), "rucat-spark-abc");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_spark_urls() -> Result<()> {
+        let id = EngineId::try_from("abc")?;
+        assert_eq!(
+            get_spark_service_host(&id, None),
+            "rucat-spark-abc.default.svc.cluster.local"
+        );
+        assert_eq!(
+            get_spark_connect_url(&id, None),
+            "sc://rucat-spark-abc.default.svc.cluster.local:15002"
+        );
+        assert_eq!(
+            get_spark_ui_url(&id, None),
+            "http://rucat-spark-abc.default.svc.cluster.local:4040"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_spark_urls_in_project() -> Result<()> {
+        let id = EngineId::try_from("abc")?;
+        assert_eq!(get_spark_namespace(Some("team-a")), "team-a");
+        assert_eq!(get_spark_namespace(None), SPARK_NAMESPACE);
+        assert_eq!(
+            get_spark_connect_url(&id, Some("team-a")),
+            "sc://rucat-spark-abc.team-a.svc.cluster.local:15002"
+        );
+        Ok(())
+    }
+}
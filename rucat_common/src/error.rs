@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
 use ::anyhow::anyhow;
+use ::serde::{Deserialize, Serialize};
 
 use RucatErrorType::*;
 
@@ -8,11 +9,25 @@ use crate::engine::EngineId;
 
 pub type Result<T> = std::result::Result<T, RucatError>;
 
-#[derive(Debug)]
+/// Type of [RucatError], serialized as a stable code in `SCREAMING_SNAKE_CASE`, e.g. `NOT_ALLOWED`,
+/// so that clients can match on it instead of the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum RucatErrorType {
     NotFound,
     Unauthorized,
     NotAllowed,
+    MethodNotAllowed,
+    Conflict,
+    PreconditionFailed,
+    InvalidRequest,
+    InvalidArgument,
+    ServiceUnavailable,
+    TooManyRequests,
+    PayloadTooLarge,
+    RequestTimeout,
+    Timeout,
     FailToStartServer,
     FailToStartStateMonitor,
     FailToStartEngine,
@@ -20,6 +35,13 @@ pub enum RucatErrorType {
     FailToConnectDatabase,
     FailToUpdateDatabase,
     FailToReadDatabase,
+    /// A query of the database is not done in time, which may or may not have taken effect.
+    DatabaseTimeout,
+    FailToWriteBlob,
+    FailToReadBlob,
+    FailToDeleteBlob,
+    FailToReadLogs,
+    FailToReadEvents,
     FailToLoadConfig,
 }
 
@@ -29,6 +51,16 @@ impl Display for RucatErrorType {
             NotFound => write!(f, "Not found"),
             Unauthorized => write!(f, "Unauthorized"),
             NotAllowed => write!(f, "Not allowed"),
+            MethodNotAllowed => write!(f, "Method not allowed"),
+            Conflict => write!(f, "Conflict"),
+            PreconditionFailed => write!(f, "Precondition failed"),
+            InvalidRequest => write!(f, "Invalid request"),
+            InvalidArgument => write!(f, "Invalid argument"),
+            ServiceUnavailable => write!(f, "Service unavailable"),
+            TooManyRequests => write!(f, "Too many requests"),
+            PayloadTooLarge => write!(f, "Payload too large"),
+            RequestTimeout => write!(f, "Request timeout"),
+            Timeout => write!(f, "Timeout"),
             FailToStartServer => write!(f, "Fail to start server"),
             FailToStartStateMonitor => write!(f, "Fail to start state monitor"),
             FailToStartEngine => write!(f, "Fail to start engine"),
@@ -36,6 +68,12 @@ impl Display for RucatErrorType {
             FailToConnectDatabase => write!(f, "Fail to connect to database"),
             FailToUpdateDatabase => write!(f, "Fail to update database"),
             FailToReadDatabase => write!(f, "Fail to read database"),
+            DatabaseTimeout => write!(f, "Database timeout"),
+            FailToWriteBlob => write!(f, "Fail to write blob"),
+            FailToReadBlob => write!(f, "Fail to read blob"),
+            FailToDeleteBlob => write!(f, "Fail to delete blob"),
+            FailToReadLogs => write!(f, "Fail to read logs"),
+            FailToReadEvents => write!(f, "Fail to read events"),
             FailToLoadConfig => write!(f, "Fail to load config"),
         }
     }
@@ -45,12 +83,43 @@ impl Display for RucatErrorType {
 pub struct RucatError {
     error_type: RucatErrorType,
     content: anyhow::Error,
+    /// Id of the engine that the error is about, if any.
+    engine_id: Option<EngineId>,
 }
 
 impl RucatError {
     pub fn get_error_type(&self) -> &RucatErrorType {
         &self.error_type
     }
+
+    /// Message of the error without the error type, in one line including the causes.
+    /// Nested [RucatError]s are formatted by their alternate [Display], so that no backtrace is included.
+    pub fn get_message(&self) -> String {
+        let mut message = String::new();
+        for (i, cause) in self.content.chain().enumerate() {
+            if i > 0 {
+                message.push_str(": ");
+            }
+            // The causes of a nested error are included in its own message.
+            if let Some(error) = cause.downcast_ref::<RucatError>() {
+                message.push_str(&format!("{:#}", error));
+                break;
+            }
+            message.push_str(&cause.to_string());
+        }
+        message
+    }
+
+    pub fn get_engine_id(&self) -> Option<&EngineId> {
+        self.engine_id.as_ref()
+    }
+
+    /// Set the id of the engine that the error is about.
+    pub fn with_engine_id(mut self, id: &EngineId) -> Self {
+        self.engine_id = Some(id.clone());
+        self
+    }
+
     pub fn unauthorized<E: Into<anyhow::Error>>(e: E) -> Self {
         Self::new(Unauthorized, e)
     }
@@ -59,8 +128,48 @@ impl RucatError {
         Self::new(NotAllowed, e)
     }
 
+    pub fn method_not_allowed<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(MethodNotAllowed, e)
+    }
+
+    pub fn conflict<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(Conflict, e)
+    }
+
+    pub fn precondition_failed<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(PreconditionFailed, e)
+    }
+
+    pub fn invalid_request<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(InvalidRequest, e)
+    }
+
+    pub fn invalid_argument<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(InvalidArgument, e)
+    }
+
+    pub fn service_unavailable<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(ServiceUnavailable, e)
+    }
+
+    pub fn too_many_requests<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(TooManyRequests, e)
+    }
+
+    pub fn payload_too_large<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(PayloadTooLarge, e)
+    }
+
+    pub fn request_timeout<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(RequestTimeout, e)
+    }
+
+    pub fn timeout<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(Timeout, e)
+    }
+
     pub fn engine_not_found(id: &EngineId) -> Self {
-        Self::not_found(anyhow!("Engine {} not found", id))
+        Self::not_found(anyhow!("Engine {} not found", id)).with_engine_id(id)
     }
 
     pub fn not_found<E: Into<anyhow::Error>>(e: E) -> Self {
@@ -98,17 +207,48 @@ impl RucatError {
         Self::new(FailToReadDatabase, e)
     }
 
+    pub fn database_timeout<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(DatabaseTimeout, e)
+    }
+
+    pub fn fail_to_write_blob<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(FailToWriteBlob, e)
+    }
+
+    pub fn fail_to_read_blob<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(FailToReadBlob, e)
+    }
+
+    pub fn fail_to_delete_blob<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(FailToDeleteBlob, e)
+    }
+
+    pub fn fail_to_read_logs<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(FailToReadLogs, e)
+    }
+
+    pub fn fail_to_read_events<E: Into<anyhow::Error>>(e: E) -> Self {
+        Self::new(FailToReadEvents, e)
+    }
+
     fn new<E: Into<anyhow::Error>>(error_type: RucatErrorType, content: E) -> Self {
         RucatError {
             error_type,
             content: content.into(),
+            engine_id: None,
         }
     }
 }
 
+/// The alternate format `{:#}` is the error type and [RucatError::get_message] in one line,
+/// while the default format includes the backtrace of the error if it is captured.
 impl Display for RucatError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}: {:?}", self.error_type, self.content)
+        if f.alternate() {
+            write!(f, "{}: {}", self.error_type, self.get_message())
+        } else {
+            write!(f, "{}: {:?}", self.error_type, self.content)
+        }
     }
 }
 
@@ -136,10 +276,77 @@ mod tests {
         assert!(error.to_string().contains("Not allowed: err_msg"));
     }
 
+    #[test]
+    fn method_not_allowed() {
+        let error = RucatError::method_not_allowed(anyhow!("err_msg"));
+        assert!(error.to_string().contains("Method not allowed: err_msg"));
+    }
+
+    #[test]
+    fn invalid_request() {
+        let error = RucatError::invalid_request(anyhow!("err_msg"));
+        assert!(error.to_string().contains("Invalid request: err_msg"));
+    }
+
+    #[test]
+    fn conflict() {
+        let error = RucatError::conflict(anyhow!("err_msg"));
+        assert!(error.to_string().contains("Conflict: err_msg"));
+    }
+
+    #[test]
+    fn precondition_failed() {
+        let error = RucatError::precondition_failed(anyhow!("err_msg"));
+        assert!(error.to_string().contains("Precondition failed: err_msg"));
+    }
+
+    #[test]
+    fn invalid_argument() {
+        let error = RucatError::invalid_argument(anyhow!("err_msg"));
+        assert!(error.to_string().contains("Invalid argument: err_msg"));
+    }
+
+    #[test]
+    fn service_unavailable() {
+        let error = RucatError::service_unavailable(anyhow!("err_msg"));
+        assert!(error.to_string().contains("Service unavailable: err_msg"));
+    }
+
+    #[test]
+    fn too_many_requests() {
+        let error = RucatError::too_many_requests(anyhow!("err_msg"));
+        assert!(error.to_string().contains("Too many requests: err_msg"));
+    }
+
+    #[test]
+    fn timeout() {
+        let error = RucatError::timeout(anyhow!("err_msg"));
+        assert!(error.to_string().contains("Timeout: err_msg"));
+    }
+
     #[test]
     fn engine_not_found() -> Result<()> {
-        let error = RucatError::engine_not_found(&EngineId::try_from("0")?);
+        let id = EngineId::try_from("0")?;
+        let error = RucatError::engine_not_found(&id);
         assert!(error.to_string().contains("Not found: Engine 0 not found"));
+        assert_eq!(error.get_message(), "Engine 0 not found");
+        assert_eq!(error.get_engine_id(), Some(&id));
+        Ok(())
+    }
+
+    #[test]
+    fn error_type_code() -> anyhow::Result<()> {
+        for (error_type, code) in [
+            (NotAllowed, "NOT_ALLOWED"),
+            (TooManyRequests, "TOO_MANY_REQUESTS"),
+            (FailToConnectDatabase, "FAIL_TO_CONNECT_DATABASE"),
+        ] {
+            assert_eq!(serde_json::to_value(error_type)?, code);
+            assert_eq!(
+                serde_json::from_value::<RucatErrorType>(code.into())?,
+                error_type
+            );
+        }
         Ok(())
     }
 
@@ -195,6 +402,30 @@ mod tests {
         assert!(error.to_string().contains("Fail to read database: err_msg"));
     }
 
+    #[test]
+    fn database_timeout() {
+        let error = RucatError::database_timeout(anyhow!("err_msg"));
+        assert!(error.to_string().contains("Database timeout: err_msg"));
+    }
+
+    #[test]
+    fn fail_to_write_blob() {
+        let error = RucatError::fail_to_write_blob(anyhow!("err_msg"));
+        assert!(error.to_string().contains("Fail to write blob: err_msg"));
+    }
+
+    #[test]
+    fn fail_to_read_blob() {
+        let error = RucatError::fail_to_read_blob(anyhow!("err_msg"));
+        assert!(error.to_string().contains("Fail to read blob: err_msg"));
+    }
+
+    #[test]
+    fn fail_to_delete_blob() {
+        let error = RucatError::fail_to_delete_blob(anyhow!("err_msg"));
+        assert!(error.to_string().contains("Fail to delete blob: err_msg"));
+    }
+
     #[test]
     fn nested_error() {
         let error = RucatError::fail_to_start_engine(RucatError::fail_to_update_database(anyhow!(
@@ -203,5 +434,19 @@ mod tests {
         assert!(error
             .to_string()
             .contains("Fail to start engine: Fail to update database: err_msg"));
+        assert_eq!(error.get_message(), "Fail to update database: err_msg");
+        assert_eq!(
+            format!("{:#}", error),
+            "Fail to start engine: Fail to update database: err_msg"
+        );
+        assert!(error.get_engine_id().is_none());
+    }
+
+    #[test]
+    fn nested_error_with_context() {
+        let error = RucatError::fail_to_start_engine(
+            anyhow::Error::from(RucatError::not_found(anyhow!("err_msg"))).context("outer"),
+        );
+        assert_eq!(error.get_message(), "outer: Not found: err_msg");
     }
 }
@@ -1,9 +1,17 @@
 //! Common types and utilities for the Rucat projects.
 
+pub mod blob_store;
+pub mod build_info;
+pub mod clock;
 pub mod config;
 pub mod database;
 pub mod engine;
 pub mod error;
+#[cfg(feature = "cli")]
+pub mod logging;
+#[cfg(feature = "webhooks")]
+pub mod webhook;
+#[cfg(feature = "grpc")]
 pub mod client_grpc {
     tonic::include_proto!("client_grpc");
 }
@@ -14,4 +22,5 @@ pub use serde;
 pub use serde_json;
 pub use tokio;
 pub use tracing;
+#[cfg(feature = "cli")]
 pub use tracing_subscriber;
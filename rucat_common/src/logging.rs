@@ -0,0 +1,99 @@
+//! Logging of the rucat binaries.
+//! Log lines are written by a background thread, so that a slow output, e.g. a container log under pressure,
+//! never blocks the caller. Lines are dropped if the output cannot keep up.
+
+use ::std::{io::Write, sync::OnceLock};
+
+use ::tracing_appender::non_blocking::{ErrorCounter, NonBlocking, NonBlockingBuilder};
+
+pub use ::tracing_appender::non_blocking::WorkerGuard;
+
+/// Maximum number of log lines waiting for the output, newer lines are dropped beyond it.
+pub const BUFFERED_LINES_LIMIT: usize = 128_000;
+
+/// Counter of the dropped lines of the logging set up by [init_tracing].
+static DROPPED_LINES: OnceLock<ErrorCounter> = OnceLock::new();
+
+/// Writer of log lines to `writer` in a background thread, buffering at most `buffered_lines_limit` lines.
+/// The lines are flushed when the returned guard is dropped.
+pub fn non_blocking<W>(writer: W, buffered_lines_limit: usize) -> (NonBlocking, WorkerGuard)
+where
+    W: Write + Send + 'static,
+{
+    NonBlockingBuilder::default()
+        .buffered_lines_limit(buffered_lines_limit)
+        .lossy(true)
+        .thread_name("rucat-logging")
+        .finish(writer)
+}
+
+/// Set up the global tracing subscriber writing to stdout without blocking.
+/// Return the guard that flushes the buffered lines when dropped, keep it until the binary exits.
+pub fn init_tracing() -> WorkerGuard {
+    let (writer, guard) = non_blocking(::std::io::stdout(), BUFFERED_LINES_LIMIT);
+    // `init_tracing` is called once by each binary, the counter of the first call is kept otherwise.
+    let _ = DROPPED_LINES.set(writer.error_counter());
+    ::tracing_subscriber::fmt().with_writer(writer).init();
+    guard
+}
+
+/// Number of log lines dropped since [init_tracing], 0 if it is not called.
+pub fn get_dropped_lines() -> usize {
+    DROPPED_LINES.get().map_or(0, ErrorCounter::dropped_lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::{
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    use ::tracing::info;
+
+    use super::*;
+
+    /// Writer recording the written bytes after a delay.
+    #[derive(Clone, Default)]
+    struct SlowWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SlowWriter {
+        fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+            thread::sleep(Duration::from_millis(50));
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> ::std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn drop_lines_beyond_the_buffer() {
+        let output = SlowWriter::default();
+        let (writer, guard) = non_blocking(output.clone(), 1);
+        let dropped = writer.error_counter();
+        let subscriber = ::tracing_subscriber::fmt()
+            .with_writer(writer)
+            .without_time()
+            .finish();
+        ::tracing::subscriber::with_default(subscriber, || {
+            for i in 0..10 {
+                info!("line {}", i);
+            }
+        });
+        drop(guard);
+
+        let written = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(written.contains("line 0"));
+        assert!(dropped.dropped_lines() > 0);
+        assert_eq!(written.lines().count() + dropped.dropped_lines(), 10);
+    }
+
+    #[test]
+    fn no_dropped_lines_without_init() {
+        assert_eq!(get_dropped_lines(), 0);
+    }
+}
@@ -0,0 +1,287 @@
+//! Webhook notifications of engine state transitions, e.g. for chat or ops tooling
+//! to be notified when an engine is running or fails.
+//!
+//! Notifications are delivered in the background, so that a slow or unreachable receiver
+//! never blocks the state updates. Failed deliveries are retried and then only logged.
+
+use ::std::{
+    collections::BTreeSet,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use ::anyhow::anyhow;
+use ::reqwest::{Client, Url};
+use ::serde::{Deserialize, Serialize};
+use ::tokio::task::JoinHandle;
+use ::tracing::{debug, error, warn};
+
+use crate::{
+    engine::{EngineId, EngineState, EngineStateKind, EngineTime},
+    error::{Result, RucatError},
+};
+
+/// Configuration of the webhooks notified when engines enter the target `states`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// URLs that the notifications are posted to.
+    pub urls: Vec<String>,
+    /// States that an engine entering them is notified.
+    pub states: BTreeSet<EngineStateKind>,
+    /// Timeout in second of each delivery.
+    #[serde(default = "WebhookConfig::default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Retries of a failed delivery, each after a doubled backoff.
+    #[serde(default = "WebhookConfig::default_max_retries")]
+    pub max_retries: u32,
+    /// Backoff in millisecond before the first retry.
+    #[serde(default = "WebhookConfig::default_retry_backoff_millis")]
+    pub retry_backoff_millis: u64,
+}
+
+impl WebhookConfig {
+    fn default_timeout_secs() -> u64 {
+        5
+    }
+
+    fn default_max_retries() -> u32 {
+        3
+    }
+
+    fn default_retry_backoff_millis() -> u64 {
+        1000
+    }
+
+    /// Check the URLs are valid and there is a state to notify.
+    pub fn validate(&self) -> Result<()> {
+        if self.urls.is_empty() || self.states.is_empty() {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "Webhooks need at least one URL and one state, remove them to disable the notifications"
+            )));
+        }
+        for url in &self.urls {
+            Url::parse(url).map_err(|e| {
+                RucatError::fail_to_load_config(anyhow!("Invalid webhook URL `{}`: {}", url, e))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Payload posted to the webhooks when an engine changes its state.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct StateChangeEvent {
+    pub engine_id: String,
+    pub old_state: EngineState,
+    pub new_state: EngineState,
+    /// Time of the state change.
+    pub timestamp: EngineTime,
+}
+
+/// Notifier posting [StateChangeEvent]s to the configured webhooks.
+#[derive(Clone)]
+pub struct WebhookNotifier {
+    client: Client,
+    config: Arc<WebhookConfig>,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: WebhookConfig) -> Result<Self> {
+        config.validate()?;
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(RucatError::fail_to_load_config)?;
+        Ok(Self {
+            client,
+            config: Arc::new(config),
+        })
+    }
+
+    /// Notify the webhooks that the engine is updated from `old_state` to `new_state` at `time`,
+    /// if the new state is one of the target states.
+    /// # Return
+    /// The background task delivering the notification, `None` if nothing is notified.
+    pub fn notify(
+        &self,
+        id: &EngineId,
+        old_state: &EngineState,
+        new_state: &EngineState,
+        time: SystemTime,
+    ) -> Option<JoinHandle<()>> {
+        if !self
+            .config
+            .states
+            .contains(&EngineStateKind::from(new_state))
+        {
+            return None;
+        }
+        let event = StateChangeEvent {
+            engine_id: id.to_string(),
+            old_state: old_state.clone(),
+            new_state: new_state.clone(),
+            timestamp: EngineTime::from_system_time(time),
+        };
+        let notifier = self.clone();
+        Some(::tokio::spawn(async move {
+            for url in &notifier.config.urls {
+                notifier.deliver(url, &event).await;
+            }
+        }))
+    }
+
+    /// Post the event to the url, retrying with backoff until it is accepted with a success status.
+    async fn deliver(&self, url: &str, event: &StateChangeEvent) {
+        let mut backoff = Duration::from_millis(self.config.retry_backoff_millis);
+        for attempt in 0..=self.config.max_retries {
+            if attempt > 0 {
+                ::tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            let response = self.client.post(url).json(event).send().await;
+            match response.and_then(|response| response.error_for_status()) {
+                Ok(_) => {
+                    debug!(
+                        "Notify webhook {} of engine {} in {:?} state",
+                        url, event.engine_id, event.new_state
+                    );
+                    return;
+                }
+                Err(e) => warn!(
+                    "Failed to notify webhook {} of engine {}, attempt {}/{}: {}",
+                    url,
+                    event.engine_id,
+                    attempt + 1,
+                    self.config.max_retries + 1,
+                    e
+                ),
+            }
+        }
+        error!(
+            "Give up notifying webhook {} of engine {} in {:?} state",
+            url, event.engine_id, event.new_state
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::borrow::Cow;
+
+    use ::httpmock::prelude::*;
+    use ::serde_json::json;
+
+    use super::*;
+
+    fn config(server: &MockServer, states: &[EngineStateKind]) -> WebhookConfig {
+        WebhookConfig {
+            urls: vec![server.url("/hook")],
+            states: states.iter().copied().collect(),
+            timeout_secs: 1,
+            max_retries: 2,
+            retry_backoff_millis: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn deliver_state_change() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.method(POST).path("/hook").json_body(json!({
+                    "engine_id": "123",
+                    "old_state": "StartInProgress",
+                    "new_state": "Running",
+                    "timestamp": "1970-01-01 00:00:00 +00:00:00"
+                }));
+                then.status(200);
+            })
+            .await;
+        let notifier = WebhookNotifier::new(config(&server, &[EngineStateKind::Running]))?;
+
+        notifier
+            .notify(
+                &EngineId::try_from("123")?,
+                &EngineState::StartInProgress,
+                &EngineState::Running,
+                SystemTime::UNIX_EPOCH,
+            )
+            .expect("Running should be notified")
+            .await?;
+
+        mock.assert_async().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn skip_states_not_configured() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.method(POST).path("/hook");
+                then.status(200);
+            })
+            .await;
+        let notifier = WebhookNotifier::new(config(&server, &[EngineStateKind::ErrorClean]))?;
+
+        let task = notifier.notify(
+            &EngineId::try_from("123")?,
+            &EngineState::StartInProgress,
+            &EngineState::Running,
+            SystemTime::UNIX_EPOCH,
+        );
+
+        assert!(task.is_none());
+        mock.assert_hits_async(0).await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn retry_failed_delivery() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.method(POST).path("/hook");
+                then.status(500);
+            })
+            .await;
+        let notifier = WebhookNotifier::new(config(&server, &[EngineStateKind::ErrorClean]))?;
+
+        notifier
+            .notify(
+                &EngineId::try_from("123")?,
+                &EngineState::TriggerStart,
+                &EngineState::ErrorClean(Cow::Borrowed("image not found")),
+                SystemTime::UNIX_EPOCH,
+            )
+            .expect("ErrorClean should be notified")
+            .await?;
+
+        // the first attempt and 2 retries
+        mock.assert_hits_async(3).await;
+        Ok(())
+    }
+
+    #[test]
+    fn validate_config() {
+        let mut config = WebhookConfig {
+            urls: vec!["http://localhost:8080/hook".to_owned()],
+            states: BTreeSet::from([EngineStateKind::Running]),
+            timeout_secs: 1,
+            max_retries: 0,
+            retry_backoff_millis: 0,
+        };
+        assert!(config.validate().is_ok());
+
+        config.urls = vec!["localhost".to_owned()];
+        assert!(config.validate().is_err_and(|e| e
+            .to_string()
+            .starts_with("Fail to load config: Invalid webhook URL `localhost`")));
+
+        config.urls = vec![];
+        assert!(config.validate().is_err_and(|e| e
+            .to_string()
+            .starts_with("Fail to load config: Webhooks need at least one URL and one state")));
+    }
+}
@@ -0,0 +1,359 @@
+//! Property based tests for the types deserialized from untrusted JSON,
+//! e.g. request bodies, config files and records stored in the database.
+
+use ::std::{borrow::Cow, fmt::Debug};
+
+use ::proptest::{
+    collection,
+    prelude::*,
+    strategy::{LazyJust, Union},
+};
+use ::rucat_common::{
+    config::{
+        BlobStoreVariant, ConnectRetry, Credentials, DatabaseVariant, SecretValue, SurrealConfig,
+    },
+    engine::{
+        CreateEngineRequest, EngineConfig, EngineFeatures, EngineId, EngineInfo, EngineState,
+        EngineTime, EngineType,
+    },
+    serde::{de::DeserializeOwned, Serialize},
+    serde_json::{self, Map, Value},
+};
+
+/// Strings that are likely to be field names or enum variants of the tested types,
+/// so that the generated JSON can reach deeper than the first level of deserialization.
+fn known_str() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just("id"),
+        Just("name"),
+        Just("engine_type"),
+        Just("version"),
+        Just("config"),
+        Just("state"),
+        Just("create_time"),
+        Just("start_in_progress_timeout_secs"),
+        Just("features"),
+        Just("readiness_probe"),
+        Just("Spark"),
+        Just("Running"),
+        Just("ErrorClean"),
+        Just("Surreal"),
+        Just("credentials"),
+        Just("username"),
+        Just("password"),
+        Just("uri"),
+        Just("FileSystem"),
+        Just("root"),
+        Just("2025-01-02 03:04:05 +00:00:00"),
+    ]
+    .prop_map(str::to_owned)
+}
+
+fn any_str() -> impl Strategy<Value = String> {
+    prop_oneof![known_str(), ".*"]
+}
+
+/// Arbitrary JSON value.
+fn any_json() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        any::<f64>().prop_map(Value::from),
+        any_str().prop_map(Value::from),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+            collection::btree_map(any_str(), inner, 0..8)
+                .prop_map(|map| Value::Object(map.into_iter().collect::<Map<_, _>>())),
+        ]
+    })
+}
+
+/// Deserializing any JSON value either succeeds or returns an error with a message.
+fn check_from_value<T: DeserializeOwned>(value: Value) {
+    if let Err(e) = serde_json::from_value::<T>(value) {
+        assert!(!e.to_string().is_empty());
+    }
+}
+
+/// Deserializing any text either succeeds or returns an error with a message.
+fn check_from_str<T: DeserializeOwned>(text: &str) {
+    if let Err(e) = serde_json::from_str::<T>(text) {
+        assert!(!e.to_string().is_empty());
+    }
+}
+
+/// serialize ∘ deserialize is identity for valid values.
+fn check_ser_de_identity<T>(value: T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let json = serde_json::to_value(&value).unwrap();
+    let result: T = serde_json::from_value(json).unwrap();
+    assert_eq!(result, value);
+}
+
+fn cow_str() -> impl Strategy<Value = Cow<'static, str>> {
+    any::<String>().prop_map(Cow::Owned)
+}
+
+fn engine_id() -> impl Strategy<Value = EngineId> {
+    ".+".prop_map(|id: String| EngineId::try_from(id).unwrap())
+}
+
+fn engine_type() -> impl Strategy<Value = EngineType> {
+    Just(EngineType::Spark)
+}
+
+fn engine_state() -> impl Strategy<Value = EngineState> {
+    use EngineState::*;
+    prop_oneof![
+        Just(WaitToStart),
+        Just(TriggerStart),
+        Just(StartInProgress),
+        Just(Running),
+        Just(WaitToTerminate),
+        Just(TriggerTermination),
+        Just(TerminateInProgress),
+        Just(Terminated),
+        cow_str().prop_map(ErrorWaitToClean),
+        cow_str().prop_map(ErrorTriggerClean),
+        cow_str().prop_map(ErrorCleanInProgress),
+        cow_str().prop_map(ErrorClean),
+    ]
+}
+
+fn engine_config() -> impl Strategy<Value = EngineConfig> {
+    collection::btree_map(cow_str(), cow_str(), 0..8)
+}
+
+/// Feature names are not validated in deserialization.
+fn engine_features() -> impl Strategy<Value = EngineFeatures> {
+    collection::btree_set(any_str(), 0..4)
+}
+
+/// Valid time strings in the format of [EngineTime].
+fn engine_time_str() -> impl Strategy<Value = String> {
+    (
+        (0..=9999i32, 1..=12u8, 1..=28u8),
+        (0..24u8, 0..60u8, 0..60u8),
+        (prop_oneof![Just('+'), Just('-')], 0..24u8, 0..60u8, 0..60u8),
+    )
+        .prop_map(
+            |((year, month, day), (hour, minute, second), (sign, oh, om, os))| {
+                format!(
+                    "{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}:{second:02} {sign}{oh:02}:{om:02}:{os:02}"
+                )
+            },
+        )
+}
+
+fn engine_time() -> impl Strategy<Value = EngineTime> {
+    engine_time_str().prop_map(|time| serde_json::from_value(Value::from(time)).unwrap())
+}
+
+fn create_engine_request() -> impl Strategy<Value = CreateEngineRequest> {
+    (
+        any::<String>(),
+        engine_type(),
+        any::<String>(),
+        proptest::option::of(engine_config()),
+        proptest::option::of(any::<u32>()),
+        proptest::option::of(engine_features()),
+        proptest::option::of(any::<bool>()),
+    )
+        .prop_map(
+            |(
+                name,
+                engine_type,
+                version,
+                config,
+                start_in_progress_timeout_secs,
+                features,
+                start,
+            )| {
+                CreateEngineRequest {
+                    name,
+                    engine_type,
+                    version,
+                    config,
+                    start_in_progress_timeout_secs,
+                    features,
+                    start,
+                }
+            },
+        )
+}
+
+fn engine_info() -> impl Strategy<Value = EngineInfo> {
+    (
+        any::<String>(),
+        engine_type(),
+        any::<String>(),
+        engine_state(),
+        engine_config(),
+        engine_time(),
+        proptest::option::of(any::<u32>()),
+        engine_features(),
+    )
+        .prop_map(
+            |(name, engine_type, version, state, config, create_time, timeout, features)| {
+                let mut info =
+                    EngineInfo::new(name, engine_type, version, state, config, create_time);
+                info.start_in_progress_timeout_secs = timeout;
+                info.features = features;
+                info
+            },
+        )
+}
+
+fn credentials() -> impl Strategy<Value = Credentials> {
+    (any::<String>(), any::<String>()).prop_map(|(username, password)| Credentials {
+        username,
+        password: SecretValue::Literal(password),
+    })
+}
+
+fn connect_retry() -> impl Strategy<Value = ConnectRetry> {
+    (any::<u32>(), any::<u64>(), any::<u64>(), any::<u64>()).prop_map(
+        |(max_attempts, initial_backoff_millis, max_backoff_millis, timeout_secs)| ConnectRetry {
+            max_attempts,
+            initial_backoff_millis,
+            max_backoff_millis,
+            timeout_secs,
+        },
+    )
+}
+
+fn database_variant() -> impl Strategy<Value = DatabaseVariant> {
+    Union::new([
+        (
+            proptest::option::of(credentials()),
+            any::<String>(),
+            connect_retry(),
+            (any::<String>(), any::<String>(), any::<String>()),
+            (any::<bool>(), proptest::option::of(any::<String>())),
+            any::<u64>(),
+        )
+            .prop_map(
+                |(
+                    credentials,
+                    uri,
+                    connect_retry,
+                    (namespace, database, table),
+                    (tls, ca_path),
+                    query_timeout_secs,
+                )| {
+                    DatabaseVariant::Surreal(Box::new(SurrealConfig {
+                        credentials,
+                        uri,
+                        connect_retry,
+                        namespace,
+                        database,
+                        table,
+                        tls,
+                        ca_path,
+                        query_timeout_secs,
+                    }))
+                },
+            )
+            .boxed(),
+        LazyJust::new(|| DatabaseVariant::InMemory).boxed(),
+        #[cfg(feature = "postgres")]
+        (
+            any::<String>(),
+            proptest::option::of(credentials()),
+            any::<u32>(),
+        )
+            .prop_map(|(uri, credentials, pool_size)| DatabaseVariant::Postgres {
+                uri,
+                credentials,
+                pool_size,
+            })
+            .boxed(),
+        #[cfg(feature = "sqlite")]
+        any::<String>()
+            .prop_map(|path| DatabaseVariant::Sqlite { path })
+            .boxed(),
+        #[cfg(feature = "redis")]
+        (any::<String>(), proptest::option::of(credentials()))
+            .prop_map(|(uri, credentials)| DatabaseVariant::Redis { uri, credentials })
+            .boxed(),
+    ])
+}
+
+fn blob_store_variant() -> impl Strategy<Value = BlobStoreVariant> {
+    any::<String>().prop_map(|root| BlobStoreVariant::FileSystem { root })
+}
+
+proptest! {
+    #[test]
+    fn deserialize_any_json(value in any_json()) {
+        check_from_value::<EngineId>(value.clone());
+        check_from_value::<EngineType>(value.clone());
+        check_from_value::<EngineState>(value.clone());
+        check_from_value::<EngineTime>(value.clone());
+        check_from_value::<CreateEngineRequest>(value.clone());
+        check_from_value::<EngineInfo>(value.clone());
+        check_from_value::<Credentials>(value.clone());
+        check_from_value::<DatabaseVariant>(value.clone());
+        check_from_value::<BlobStoreVariant>(value);
+    }
+
+    #[test]
+    fn deserialize_any_str(text in ".*") {
+        check_from_str::<EngineId>(&text);
+        check_from_str::<EngineState>(&text);
+        check_from_str::<EngineTime>(&text);
+        check_from_str::<CreateEngineRequest>(&text);
+        check_from_str::<EngineInfo>(&text);
+        check_from_str::<DatabaseVariant>(&text);
+        check_from_str::<BlobStoreVariant>(&text);
+    }
+
+    #[test]
+    fn deserialize_any_str_as_engine_time(time in any::<String>()) {
+        check_from_value::<EngineTime>(Value::from(time));
+    }
+
+    #[test]
+    fn engine_time_de_ser_identity(time in engine_time_str()) {
+        let json = Value::from(time);
+        let engine_time: EngineTime = serde_json::from_value(json.clone()).unwrap();
+        prop_assert_eq!(serde_json::to_value(&engine_time).unwrap(), json);
+        // must not panic on any valid time
+        engine_time.elapsed_time();
+    }
+
+    #[test]
+    fn engine_id_ser_de_identity(id in engine_id()) {
+        check_ser_de_identity(id);
+    }
+
+    #[test]
+    fn engine_state_ser_de_identity(state in engine_state()) {
+        check_ser_de_identity(state);
+    }
+
+    #[test]
+    fn create_engine_request_ser_de_identity(request in create_engine_request()) {
+        check_ser_de_identity(request);
+    }
+
+    #[test]
+    fn engine_info_ser_de_identity(info in engine_info()) {
+        check_ser_de_identity(info);
+    }
+
+    #[test]
+    fn database_variant_ser_de_identity(database in database_variant()) {
+        check_ser_de_identity(database);
+    }
+
+    #[test]
+    fn blob_store_variant_ser_de_identity(blob_store in blob_store_variant()) {
+        check_ser_de_identity(blob_store);
+    }
+}
@@ -0,0 +1,19 @@
+//! Tests of the error messages with backtraces enabled,
+//! which run in their own process as backtraces are enabled for the whole process.
+
+use ::rucat_common::{anyhow::anyhow, error::RucatError};
+
+#[test]
+fn message_of_nested_error_has_no_backtrace() {
+    // Set before any error is created, as whether to capture backtraces is only checked once.
+    ::std::env::set_var("RUST_LIB_BACKTRACE", "1");
+    let error =
+        RucatError::fail_to_start_engine(RucatError::fail_to_update_database(anyhow!("err_msg")));
+    // the backtraces are still in the default format for logging
+    assert!(error.to_string().contains("Stack backtrace"));
+    assert_eq!(error.get_message(), "Fail to update database: err_msg");
+    assert_eq!(
+        format!("{:#}", error),
+        "Fail to start engine: Fail to update database: err_msg"
+    );
+}
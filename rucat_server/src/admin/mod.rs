@@ -0,0 +1,3 @@
+//! Module that contains rest request handlers for operators to debug the rucat cluster.
+
+pub(crate) mod router;
@@ -0,0 +1,213 @@
+//! Restful API for debugging and operating the server.
+
+use ::std::time::SystemTime;
+
+use ::rucat_common::{
+    anyhow::anyhow,
+    database::{Database, TriggeredEngine},
+    engine::{EngineId, EngineState},
+    error::RucatError,
+    serde::{Deserialize, Serialize},
+};
+use axum::{
+    extract::State,
+    middleware,
+    routing::{get, post},
+    Json,
+};
+
+use crate::{
+    authentication::{registry::reload_auth_provider, role::require_admin},
+    error::RucatServerError,
+    extract::AppQuery,
+    route_index::IndexedRouter,
+    state::AppState,
+};
+
+type Result<T> = std::result::Result<T, RucatServerError>;
+
+/// Engine acquired by a state monitor, i.e. in one of the `Trigger*` states.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(crate = "rucat_common::serde")]
+struct InFlightEngine {
+    id: EngineId,
+    state: EngineState,
+    /// Seconds since the engine was updated to the current state.
+    /// `None` if the time was not recorded.
+    age_secs: Option<u64>,
+    /// Seconds until the trigger times out. 0 if it has already timed out.
+    remaining_timeout_secs: u64,
+    /// Whether the trigger has timed out but the engine has not been retried yet.
+    stale: bool,
+}
+
+impl InFlightEngine {
+    fn new(engine: TriggeredEngine, now: SystemTime) -> Self {
+        let TriggeredEngine {
+            id,
+            info,
+            state_update_time,
+            trigger_timeout_time,
+        } = engine;
+        // Clocks of different machines are not synchronized, saturate to 0 for times in the future.
+        let age_secs = state_update_time.map(|time| {
+            now.duration_since(time)
+                .map_or(0, |duration| duration.as_secs())
+        });
+        let remaining_timeout_secs = trigger_timeout_time
+            .duration_since(now)
+            .map_or(0, |duration| duration.as_secs());
+        Self {
+            id,
+            state: info.state,
+            age_secs,
+            remaining_timeout_secs,
+            stale: trigger_timeout_time < now,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rucat_common::serde")]
+struct InFlightEnginesQuery {
+    /// Only return the engines that are stale or not stale.
+    stale: Option<bool>,
+}
+
+/// List engines acquired by state monitors, for finding engines stuck in `Trigger*` states.
+async fn list_in_flight_engines<DB>(
+    State(state): State<AppState<DB>>,
+    AppQuery(query): AppQuery<InFlightEnginesQuery>,
+) -> Result<Json<Vec<InFlightEngine>>>
+where
+    DB: Database,
+{
+    let now = state.get_clock().now();
+    let engines = state
+        .get_db()
+        .list_triggered_engines()
+        .await?
+        .into_iter()
+        .map(|engine| InFlightEngine::new(engine, now))
+        .filter(|engine| query.stale.is_none_or(|stale| engine.stale == stale))
+        .collect();
+    Ok(Json(engines))
+}
+
+/// Read the auth provider config again and replace the auth provider by it, e.g. after the credentials are rotated.
+async fn reload_auth<DB>(State(state): State<AppState<DB>>) -> Result<()>
+where
+    DB: Database,
+{
+    let source = state.get_auth_config().ok_or_else(|| {
+        RucatError::not_found(anyhow!(
+            "The auth provider cannot be reloaded, as the server has no source of the auth config"
+        ))
+    })?;
+    reload_auth_provider(state.get_auth_provider(), source).await?;
+    Ok(())
+}
+
+pub(crate) fn get_admin_router<DB>() -> IndexedRouter<AppState<DB>>
+where
+    DB: Database,
+{
+    IndexedRouter::new()
+        .route("/in-flight", get(list_in_flight_engines::<DB>))
+        .route(
+            "/reload-auth",
+            post(reload_auth::<DB>).route_layer(middleware::from_fn(require_admin)),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::{borrow::Cow, collections::BTreeMap, time::Duration};
+
+    use ::rucat_common::engine::{EngineInfo, EngineState::*, EngineTime, EngineType};
+
+    use super::*;
+
+    fn triggered_engine(
+        state: EngineState,
+        state_update_time: Option<SystemTime>,
+        trigger_timeout_time: SystemTime,
+    ) -> TriggeredEngine {
+        TriggeredEngine {
+            id: EngineId::try_from("123").unwrap(),
+            info: EngineInfo::new(
+                "engine".to_owned(),
+                EngineType::Spark,
+                "3.5.3".to_owned(),
+                state,
+                BTreeMap::new(),
+                EngineTime::now(),
+            ),
+            state_update_time,
+            trigger_timeout_time,
+        }
+    }
+
+    #[test]
+    fn in_flight_engine_not_timed_out() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let engine = InFlightEngine::new(
+            triggered_engine(
+                TriggerStart,
+                Some(now - Duration::from_secs(10)),
+                now + Duration::from_secs(50),
+            ),
+            now,
+        );
+        assert_eq!(
+            engine,
+            InFlightEngine {
+                id: EngineId::try_from("123").unwrap(),
+                state: TriggerStart,
+                age_secs: Some(10),
+                remaining_timeout_secs: 50,
+                stale: false,
+            }
+        );
+    }
+
+    #[test]
+    fn in_flight_engine_timed_out() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let engine = InFlightEngine::new(
+            triggered_engine(
+                ErrorTriggerClean(Cow::Borrowed("error")),
+                Some(now - Duration::from_secs(80)),
+                now - Duration::from_secs(20),
+            ),
+            now,
+        );
+        assert_eq!(engine.age_secs, Some(80));
+        assert_eq!(engine.remaining_timeout_secs, 0);
+        assert!(engine.stale);
+    }
+
+    #[test]
+    fn in_flight_engine_without_state_update_time() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let engine = InFlightEngine::new(triggered_engine(TriggerTermination, None, now), now);
+        assert_eq!(engine.age_secs, None);
+        assert_eq!(engine.remaining_timeout_secs, 0);
+        assert!(!engine.stale);
+    }
+
+    #[test]
+    fn in_flight_engine_updated_in_the_future() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let engine = InFlightEngine::new(
+            triggered_engine(
+                TriggerStart,
+                Some(now + Duration::from_secs(5)),
+                now + Duration::from_secs(65),
+            ),
+            now,
+        );
+        assert_eq!(engine.age_secs, Some(0));
+        assert_eq!(engine.remaining_timeout_secs, 65);
+    }
+}
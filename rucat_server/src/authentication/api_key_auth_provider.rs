@@ -0,0 +1,199 @@
+//! Authenticate services by the long-lived API keys sent in the `X-Api-Key` header.
+
+use ::std::collections::{BTreeMap, BTreeSet};
+
+use ::rucat_common::{
+    anyhow::anyhow,
+    error::{Result, RucatError},
+    serde::Deserialize,
+};
+
+use super::{role::Role, secret::Secret, Credentials, Principal, SyncAuthenticate};
+
+/// Metadata of an API key, the client authenticated by it is identified by its name.
+#[derive(Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct ApiKeyMetadata {
+    pub name: String,
+    /// Roles of the key, admin if it is not set.
+    #[serde(default = "ApiKeyMetadata::default_roles")]
+    pub roles: BTreeSet<Role>,
+}
+
+impl ApiKeyMetadata {
+    fn default_roles() -> BTreeSet<Role> {
+        BTreeSet::from([Role::configured_default()])
+    }
+}
+
+/// API keys accepted by [ApiKeyAuthProvider].
+#[derive(Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct ApiKeyAuthConfig {
+    /// Metadata of each key.
+    pub keys: BTreeMap<String, ApiKeyMetadata>,
+}
+
+/// The keys are secrets, so they are not printed.
+impl ::std::fmt::Debug for ApiKeyAuthConfig {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("ApiKeyAuthConfig")
+            .field(
+                "keys",
+                &self.keys.values().map(|key| &key.name).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Authenticate clients by their API keys. Basic and bearer credentials are rejected.
+pub struct ApiKeyAuthProvider {
+    /// key and metadata of each API key
+    keys: Vec<(Secret, ApiKeyMetadata)>,
+}
+
+impl ApiKeyAuthProvider {
+    /// Check there is at least one key, and the names of the keys are unique.
+    pub fn new(config: ApiKeyAuthConfig) -> Result<Self> {
+        if config.keys.is_empty() {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "API key authentication needs at least one key"
+            )));
+        }
+        let mut names = BTreeSet::new();
+        for metadata in config.keys.values() {
+            if !names.insert(metadata.name.as_str()) {
+                return Err(RucatError::fail_to_load_config(anyhow!(
+                    "Duplicated API key name `{}`",
+                    metadata.name
+                )));
+            }
+        }
+        Ok(Self {
+            keys: config
+                .keys
+                .into_iter()
+                .map(|(key, metadata)| (Secret::Plain(key), metadata))
+                .collect(),
+        })
+    }
+
+    /// Roles of the API key named `name`.
+    pub fn get_roles(&self, name: &str) -> Option<&BTreeSet<Role>> {
+        self.keys
+            .iter()
+            .find(|(_, metadata)| metadata.name == name)
+            .map(|(_, metadata)| &metadata.roles)
+    }
+}
+
+impl SyncAuthenticate for ApiKeyAuthProvider {
+    fn validate_sync(&self, credentials: &Credentials) -> Option<Principal> {
+        let Credentials::ApiKey(api_key) = credentials else {
+            return None;
+        };
+        // Every key is checked, so that the time does not tell which key is closer to the credential.
+        self.keys.iter().fold(None, |found, (key, metadata)| {
+            let matched = key.verify(api_key);
+            found.or(matched
+                .then(|| Principal::new(metadata.name.clone(), metadata.roles.iter().copied())))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::rucat_common::serde_json::{from_value, json};
+    use axum_extra::headers::authorization::{Bearer, Credentials as _};
+    use http::HeaderValue;
+
+    use super::*;
+
+    fn get_provider() -> ApiKeyAuthProvider {
+        let config = from_value::<ApiKeyAuthConfig>(json!({
+            "keys": {
+                "key-ci": {"name": "ci", "roles": ["admin"]},
+                "key-dashboard": {"name": "dashboard", "roles": ["viewer"]}
+            }
+        }))
+        .unwrap();
+        ApiKeyAuthProvider::new(config).unwrap()
+    }
+
+    #[test]
+    fn validate_api_keys() {
+        let provider = get_provider();
+        assert_eq!(
+            provider.validate_sync(&Credentials::ApiKey("key-ci".to_owned())),
+            Some(Principal::new("ci", [Role::Admin]))
+        );
+        assert_eq!(
+            provider.validate_sync(&Credentials::ApiKey("key-dashboard".to_owned())),
+            Some(Principal::new("dashboard", [Role::Viewer]))
+        );
+        assert_eq!(
+            provider.validate_sync(&Credentials::ApiKey("key-c".to_owned())),
+            None
+        );
+        assert_eq!(
+            provider.validate_sync(&Credentials::ApiKey("ci".to_owned())),
+            None
+        );
+    }
+
+    #[test]
+    fn reject_bearer_credentials() {
+        let header = HeaderValue::from_static("Bearer key-ci");
+        let credentials = Credentials::Bearer(Bearer::decode(&header).unwrap());
+        assert_eq!(get_provider().validate_sync(&credentials), None);
+    }
+
+    #[test]
+    fn get_roles_of_keys() {
+        let provider = get_provider();
+        assert_eq!(
+            provider.get_roles("ci"),
+            Some(&BTreeSet::from([Role::Admin]))
+        );
+        assert_eq!(
+            provider.get_roles("dashboard"),
+            Some(&BTreeSet::from([Role::Viewer]))
+        );
+        assert_eq!(provider.get_roles("other"), None);
+    }
+
+    #[test]
+    fn reject_invalid_config() {
+        for (config, message) in [
+            (
+                json!({"keys": {}}),
+                "API key authentication needs at least one key",
+            ),
+            (
+                json!({"keys": {"a": {"name": "ci"}, "b": {"name": "ci"}}}),
+                "Duplicated API key name `ci`",
+            ),
+        ] {
+            let config = from_value::<ApiKeyAuthConfig>(config).unwrap();
+            let error = ApiKeyAuthProvider::new(config).err().unwrap();
+            assert_eq!(
+                error.to_string(),
+                format!("Fail to load config: {}", message)
+            );
+        }
+    }
+
+    #[test]
+    fn keys_are_not_printed() {
+        let config = from_value::<ApiKeyAuthConfig>(json!({
+            "keys": {"key-ci": {"name": "ci"}}
+        }))
+        .unwrap();
+        assert_eq!(
+            format!("{:?}", config),
+            r#"ApiKeyAuthConfig { keys: ["ci"] }"#
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! Authenticate users by their basic credentials against an Apache htpasswd file.
+//!
+//! The file is loaded on startup, and reloaded in the background when its modification time changes if the reload is enabled,
+//! so that users can be added or removed without restarting the server.
+
+use ::std::{
+    collections::HashMap,
+    sync::{Arc, RwLock, Weak},
+    time::{Duration, SystemTime},
+};
+
+use ::rucat_common::{
+    anyhow::{anyhow, Context},
+    error::{Result, RucatError},
+    serde::Deserialize,
+    tokio,
+    tracing::{info, warn},
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use sha1::{Digest, Sha1};
+use subtle::ConstantTimeEq;
+
+use super::{role::Role, Credentials, Principal, SyncAuthenticate};
+
+/// Prefix of the SHA-1 entries, followed by the base64 encoded digest of the password.
+const SHA1_PREFIX: &str = "{SHA}";
+
+/// Configuration of the htpasswd file authenticating the users.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct HtpasswdAuthConfig {
+    /// Path of the htpasswd file, e.g. `/etc/rucat/htpasswd`.
+    pub path: String,
+    /// Interval in second of checking whether the file is modified, the file is only loaded on startup if it is not set.
+    pub reload_interval_secs: Option<u64>,
+    /// Role of all the users in the file.
+    #[serde(default = "Role::configured_default")]
+    pub role: Role,
+}
+
+/// Password hash of a user in the htpasswd file.
+enum PasswordHash {
+    /// Bcrypt hash, e.g. `$2y$05$...` generated by `htpasswd -B`.
+    Bcrypt(String),
+    /// SHA-1 digest, generated by `htpasswd -s`.
+    Sha1(Vec<u8>),
+}
+
+impl PasswordHash {
+    fn parse(hash: &str) -> ::std::result::Result<Self, &'static str> {
+        if let Some(digest) = hash.strip_prefix(SHA1_PREFIX) {
+            let digest = STANDARD
+                .decode(digest)
+                .map_err(|_| "invalid base64 of the SHA-1 digest")?;
+            if digest.len() != Sha1::output_size() {
+                return Err("invalid length of the SHA-1 digest");
+            }
+            Ok(Self::Sha1(digest))
+        } else if hash.starts_with("$2") {
+            hash.parse::<bcrypt::HashParts>()
+                .map_err(|_| "invalid bcrypt hash")?;
+            Ok(Self::Bcrypt(hash.to_owned()))
+        } else {
+            Err("unsupported hash, expected bcrypt or SHA-1")
+        }
+    }
+
+    /// Whether the `password` matches the hash, in a time not depending on how much of it matches.
+    fn verify(&self, password: &str) -> bool {
+        match self {
+            Self::Bcrypt(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            Self::Sha1(digest) => Sha1::digest(password.as_bytes())
+                .as_slice()
+                .ct_eq(digest)
+                .into(),
+        }
+    }
+}
+
+/// Users in the htpasswd file and the modification time of the file when they were loaded.
+struct Users {
+    hashes: HashMap<String, PasswordHash>,
+    modified: Option<SystemTime>,
+}
+
+/// Authenticate users by the basic credentials in the htpasswd file.
+/// Bearer tokens are rejected.
+pub struct HtpasswdAuthProvider {
+    role: Role,
+    /// Last users loaded successfully, shared with the reloading task.
+    users: Arc<RwLock<Users>>,
+}
+
+impl HtpasswdAuthProvider {
+    /// Load the htpasswd file, so that a missing file fails the server on startup,
+    /// and reload it in the background until the provider is dropped if the reload is enabled.
+    pub async fn new(config: &HtpasswdAuthConfig) -> Result<Self> {
+        if config.reload_interval_secs == Some(0) {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "`reload_interval_secs` of the htpasswd file must be positive"
+            )));
+        }
+        let users = load_users(&config.path).await.map_err(|e| {
+            RucatError::fail_to_load_config(anyhow!(
+                "Fail to load the htpasswd file `{}`: {:#}",
+                config.path,
+                e
+            ))
+        })?;
+        info!(
+            "Loaded {} users of the htpasswd file `{}`",
+            users.hashes.len(),
+            config.path
+        );
+        let users = Arc::new(RwLock::new(users));
+        if let Some(reload_interval_secs) = config.reload_interval_secs {
+            tokio::spawn(reload_users(
+                config.path.clone(),
+                Duration::from_secs(reload_interval_secs),
+                Arc::downgrade(&users),
+            ));
+        }
+        Ok(Self {
+            role: config.role,
+            users,
+        })
+    }
+}
+
+impl SyncAuthenticate for HtpasswdAuthProvider {
+    fn validate_sync(&self, credentials: &Credentials) -> Option<Principal> {
+        let Credentials::Basic(basic) = credentials else {
+            return None;
+        };
+        let users = self.users.read().unwrap_or_else(|e| e.into_inner());
+        users
+            .hashes
+            .get(basic.username())
+            .filter(|hash| hash.verify(basic.password()))
+            .map(|_| Principal::new(basic.username(), [self.role]))
+    }
+}
+
+/// Reload the users every `interval` if the file is modified, until the provider owning them is dropped.
+/// The last users are kept if a reload fails, so that authentication survives a file being rewritten.
+async fn reload_users(path: String, interval: Duration, users: Weak<RwLock<Users>>) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let modified = get_modified(&path).await;
+        let Some(users) = users.upgrade() else {
+            return;
+        };
+        let unchanged = users.read().is_ok_and(|users| {
+            modified
+                .as_ref()
+                .is_ok_and(|m| m.is_some() && users.modified == *m)
+        });
+        if unchanged {
+            continue;
+        }
+        match load_users(&path).await {
+            Ok(new_users) => {
+                info!(
+                    "Reloaded {} users of the modified htpasswd file `{}`",
+                    new_users.hashes.len(),
+                    path
+                );
+                *users.write().unwrap_or_else(|e| e.into_inner()) = new_users;
+            }
+            Err(e) => warn!(
+                "Fail to reload the htpasswd file `{}`, keep using the last users: {:#}",
+                path, e
+            ),
+        }
+    }
+}
+
+async fn get_modified(path: &str) -> ::rucat_common::anyhow::Result<Option<SystemTime>> {
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .with_context(|| format!("fail to read the metadata of `{}`", path))?;
+    // The file is reloaded every time on the platforms without the modification time.
+    Ok(metadata.modified().ok())
+}
+
+/// Load the users of the htpasswd file, skipping the malformed lines.
+async fn load_users(path: &str) -> ::rucat_common::anyhow::Result<Users> {
+    // Read the time before the content, so that a modification in between is reloaded next time.
+    let modified = get_modified(path).await?;
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("fail to read `{}`", path))?;
+    Ok(Users {
+        hashes: parse_htpasswd(path, &content),
+        modified,
+    })
+}
+
+/// Parse the `username:hash` lines of the htpasswd file, empty lines and comments starting with `#` are ignored.
+/// Malformed lines are logged and skipped, without the hashes in them.
+fn parse_htpasswd(path: &str, content: &str) -> HashMap<String, PasswordHash> {
+    let mut hashes = HashMap::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let result = match line.split_once(':') {
+            Some(("", _)) => Err("empty username"),
+            Some((username, _)) if hashes.contains_key(username) => {
+                Err("duplicated username, the first line of it is used")
+            }
+            Some((username, hash)) => PasswordHash::parse(hash).map(|hash| (username, hash)),
+            None => Err("expected `username:hash`"),
+        };
+        match result {
+            Ok((username, hash)) => {
+                hashes.insert(username.to_owned(), hash);
+            }
+            Err(reason) => warn!(
+                "Skip the malformed line {} of the htpasswd file `{}`: {}",
+                index + 1,
+                path,
+                reason
+            ),
+        }
+    }
+    hashes
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::{fs::File, io::Write};
+
+    use axum_extra::headers::authorization::{Basic, Bearer, Credentials as _};
+    use http::HeaderValue;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    /// Hashes of `secret-password`.
+    const BCRYPT_HASH: &str = "$2b$04$alThWVPxWUvyalThWVOvLePeWHDKa9Ri/Ol92ENSwnX8n6blLGgVi";
+    const SHA1_HASH: &str = "{SHA}avPBIe1KdSk2wpfN37ewA5TqvxA=";
+
+    fn basic(username: &str, password: &str) -> Credentials {
+        let header = axum_extra::headers::Authorization::basic(username, password)
+            .0
+            .encode();
+        Credentials::Basic(Basic::decode(&header).unwrap())
+    }
+
+    fn write_htpasswd(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file
+    }
+
+    fn get_config(file: &NamedTempFile) -> HtpasswdAuthConfig {
+        HtpasswdAuthConfig {
+            path: file.path().to_str().unwrap().to_owned(),
+            reload_interval_secs: None,
+            role: Role::Operator,
+        }
+    }
+
+    #[tokio::test]
+    async fn validate_users() -> Result<()> {
+        let file = write_htpasswd(&format!(
+            "# users of rucat\nalice:{}\n\nbob:{}\n",
+            BCRYPT_HASH, SHA1_HASH
+        ));
+        let provider = HtpasswdAuthProvider::new(&get_config(&file)).await?;
+        for username in ["alice", "bob"] {
+            assert_eq!(
+                provider.validate_sync(&basic(username, "secret-password")),
+                Some(Principal::new(username, [Role::Operator]))
+            );
+            assert_eq!(
+                provider.validate_sync(&basic(username, "wrong-password")),
+                None
+            );
+            assert_eq!(provider.validate_sync(&basic(username, "")), None);
+        }
+        assert_eq!(
+            provider.validate_sync(&basic("carol", "secret-password")),
+            None
+        );
+        let bearer = HeaderValue::from_static("Bearer secret-password");
+        assert_eq!(
+            provider.validate_sync(&Credentials::Bearer(Bearer::decode(&bearer).unwrap())),
+            None
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn skip_malformed_lines() -> Result<()> {
+        let file = write_htpasswd(&format!(
+            "no-colon\n:{bcrypt}\nmd5:$apr1$salt$hash\nplain:secret-password\nshort:{{SHA}}YWJj\nbad-bcrypt:$2y$05$short\nalice:{bcrypt}\nalice:{sha1}\n",
+            bcrypt = BCRYPT_HASH,
+            sha1 = SHA1_HASH
+        ));
+        let provider = HtpasswdAuthProvider::new(&get_config(&file)).await?;
+        assert_eq!(provider.users.read().unwrap().hashes.len(), 1);
+        assert!(provider
+            .validate_sync(&basic("alice", "secret-password"))
+            .is_some());
+        assert_eq!(
+            provider.validate_sync(&basic("plain", "secret-password")),
+            None
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn missing_file() {
+        let result = HtpasswdAuthProvider::new(&HtpasswdAuthConfig {
+            path: "/not/exist/htpasswd".to_owned(),
+            reload_interval_secs: None,
+            role: Role::Admin,
+        })
+        .await;
+        assert!(result.is_err_and(|e| e.to_string().starts_with(
+            "Fail to load config: Fail to load the htpasswd file `/not/exist/htpasswd`"
+        )));
+    }
+
+    #[tokio::test]
+    async fn reload_modified_file() -> Result<()> {
+        let file = write_htpasswd(&format!("alice:{}\n", BCRYPT_HASH));
+        let provider = HtpasswdAuthProvider::new(&get_config(&file)).await?;
+        tokio::spawn(reload_users(
+            get_config(&file).path,
+            Duration::from_millis(10),
+            Arc::downgrade(&provider.users),
+        ));
+
+        let mut rewritten = File::create(file.path()).unwrap();
+        rewritten
+            .write_all(format!("bob:{}\n", SHA1_HASH).as_bytes())
+            .unwrap();
+        // the modification time may not change within its resolution
+        rewritten
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        drop(rewritten);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(
+            provider.validate_sync(&basic("alice", "secret-password")),
+            None
+        );
+        assert!(provider
+            .validate_sync(&basic("bob", "secret-password"))
+            .is_some());
+
+        // the last users are kept if the file is removed
+        file.close().unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(provider
+            .validate_sync(&basic("bob", "secret-password"))
+            .is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_zero_reload_interval() {
+        let file = write_htpasswd("");
+        let result = HtpasswdAuthProvider::new(&HtpasswdAuthConfig {
+            reload_interval_secs: Some(0),
+            ..get_config(&file)
+        })
+        .await;
+        assert!(result.is_err_and(|e| {
+            e.to_string()
+            == "Fail to load config: `reload_interval_secs` of the htpasswd file must be positive"
+        }));
+    }
+}
@@ -0,0 +1,299 @@
+//! Authenticate clients by the JSON web tokens issued by an identity service, sent as bearer tokens.
+
+use ::rucat_common::{
+    anyhow::anyhow,
+    error::{Result, RucatError},
+    serde::Deserialize,
+    tokio,
+    tracing::debug,
+};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+
+use super::{role::Role, Credentials, Principal, SyncAuthenticate};
+
+/// How the tokens are verified.
+/// Tokens must be signed by the key, not expired, and not used before their `nbf` time if they have one.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct JwtAuthConfig {
+    /// Algorithm of the signatures, e.g. `HS256`, `RS256` or `ES256`.
+    pub algorithm: Algorithm,
+    /// Shared secret of the HMAC algorithms `HS256`, `HS384` and `HS512`.
+    pub secret: Option<String>,
+    /// Path of the PEM public key of the other algorithms.
+    pub public_key_path: Option<String>,
+    /// Tokens must have the audience in their `aud` claim if it is set.
+    pub audience: Option<String>,
+    /// Tokens must be issued by the issuer in their `iss` claim if it is set.
+    pub issuer: Option<String>,
+}
+
+/// Claims of the token used by rucat.
+#[derive(Deserialize)]
+#[serde(crate = "rucat_common::serde")]
+struct Claims {
+    /// The client is authenticated as the subject of the token.
+    sub: String,
+}
+
+/// Authenticate clients by the `sub` claim of their bearer tokens.
+/// Basic credentials are rejected.
+#[derive(Clone)]
+pub struct JwtAuthProvider {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+impl JwtAuthProvider {
+    /// Read the key of the `config`, so that a wrong key fails the server on startup.
+    pub async fn new(config: &JwtAuthConfig) -> Result<Self> {
+        let is_hmac = matches!(
+            config.algorithm,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        );
+        let key = match (is_hmac, &config.secret, &config.public_key_path) {
+            (true, Some(secret), None) => DecodingKey::from_secret(secret.as_bytes()),
+            (false, None, Some(path)) => {
+                let pem = tokio::fs::read(path).await.map_err(|e| {
+                    RucatError::fail_to_load_config(anyhow!(
+                        "Fail to read JWT public key `{}`: {}",
+                        path,
+                        e
+                    ))
+                })?;
+                Self::parse_public_key(config.algorithm, &pem).map_err(|e| {
+                    RucatError::fail_to_load_config(anyhow!(
+                        "Invalid JWT public key `{}`: {}",
+                        path,
+                        e
+                    ))
+                })?
+            }
+            (true, _, _) => {
+                return Err(RucatError::fail_to_load_config(anyhow!(
+                    "JWT algorithm {:?} needs `secret` and no `public_key_path`",
+                    config.algorithm
+                )))
+            }
+            (false, _, _) => {
+                return Err(RucatError::fail_to_load_config(anyhow!(
+                    "JWT algorithm {:?} needs `public_key_path` and no `secret`",
+                    config.algorithm
+                )))
+            }
+        };
+        Ok(Self::with_key(key, config))
+    }
+
+    fn parse_public_key(
+        algorithm: Algorithm,
+        pem: &[u8],
+    ) -> jsonwebtoken::errors::Result<DecodingKey> {
+        match algorithm {
+            Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(pem),
+            Algorithm::EdDSA => DecodingKey::from_ed_pem(pem),
+            _ => DecodingKey::from_rsa_pem(pem),
+        }
+    }
+
+    fn with_key(key: DecodingKey, config: &JwtAuthConfig) -> Self {
+        let validation = get_validation(
+            config.algorithm,
+            config.audience.as_deref(),
+            config.issuer.as_deref(),
+        );
+        Self { key, validation }
+    }
+}
+
+/// Validation of the tokens signed by `algorithm`, with the audience and the issuer checked if they are set.
+pub(super) fn get_validation(
+    algorithm: Algorithm,
+    audience: Option<&str>,
+    issuer: Option<&str>,
+) -> Validation {
+    let mut validation = Validation::new(algorithm);
+    validation.validate_nbf = true;
+    let mut required_claims = vec!["exp", "sub"];
+    // Tokens without the claims are accepted by `jsonwebtoken` if they are not required.
+    match audience {
+        Some(audience) => {
+            validation.set_audience(&[audience]);
+            required_claims.push("aud");
+        }
+        None => validation.validate_aud = false,
+    }
+    if let Some(issuer) = issuer {
+        validation.set_issuer(&[issuer]);
+        required_claims.push("iss");
+    }
+    validation.set_required_spec_claims(&required_claims);
+    validation
+}
+
+/// Subject of the `token` if it is valid.
+pub(super) fn decode_subject(
+    token: &str,
+    key: &DecodingKey,
+    validation: &Validation,
+) -> Option<String> {
+    decode::<Claims>(token, key, validation)
+        .inspect_err(|e| debug!("Invalid JWT: {}", e))
+        .ok()
+        .map(|token| token.claims.sub)
+}
+
+impl SyncAuthenticate for JwtAuthProvider {
+    fn validate_sync(&self, credentials: &Credentials) -> Option<Principal> {
+        let Credentials::Bearer(bearer) = credentials else {
+            return None;
+        };
+        decode_subject(bearer.token(), &self.key, &self.validation)
+            .map(|subject| Principal::new(subject, [Role::configured_default()]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::rucat_common::serde_json::{json, Value};
+    use axum_extra::headers::authorization::{Basic, Bearer, Credentials as _};
+    use http::HeaderValue;
+    use jsonwebtoken::{encode, get_current_timestamp, EncodingKey, Header};
+
+    use super::*;
+
+    const SECRET: &str = "jwt-secret";
+
+    fn get_config() -> JwtAuthConfig {
+        JwtAuthConfig {
+            algorithm: Algorithm::HS256,
+            secret: Some(SECRET.to_owned()),
+            public_key_path: None,
+            audience: Some("rucat".to_owned()),
+            issuer: Some("https://id.example.com".to_owned()),
+        }
+    }
+
+    /// Token signed by `secret` with the claims of a valid token, overridden by `claims`.
+    fn sign(secret: &str, claims: Value) -> Credentials {
+        let mut all_claims = json!({
+            "sub": "alice",
+            "aud": "rucat",
+            "iss": "https://id.example.com",
+            "exp": get_current_timestamp() + 600,
+        });
+        for (name, value) in claims.as_object().unwrap() {
+            match value {
+                Value::Null => all_claims.as_object_mut().unwrap().remove(name),
+                _ => all_claims
+                    .as_object_mut()
+                    .unwrap()
+                    .insert(name.clone(), value.clone()),
+            };
+        }
+        let token = encode(
+            &Header::new(Algorithm::HS256),
+            &all_claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap();
+        let header = HeaderValue::from_str(&format!("Bearer {}", token)).unwrap();
+        Credentials::Bearer(Bearer::decode(&header).unwrap())
+    }
+
+    #[tokio::test]
+    async fn accept_valid_token() -> Result<()> {
+        let provider = JwtAuthProvider::new(&get_config()).await?;
+        assert_eq!(
+            provider.validate_sync(&sign(SECRET, json!({}))),
+            Some(Principal::new("alice", [Role::Admin]))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_tokens() -> Result<()> {
+        let provider = JwtAuthProvider::new(&get_config()).await?;
+        let now = get_current_timestamp();
+        for (case, credentials) in [
+            ("expired", sign(SECRET, json!({"exp": now - 3600}))),
+            ("not before", sign(SECRET, json!({"nbf": now + 3600}))),
+            ("wrong signature", sign("other-secret", json!({}))),
+            ("missing audience", sign(SECRET, json!({"aud": null}))),
+            ("wrong audience", sign(SECRET, json!({"aud": "other"}))),
+            ("missing issuer", sign(SECRET, json!({"iss": null}))),
+            ("missing subject", sign(SECRET, json!({"sub": null}))),
+            ("missing expiry", sign(SECRET, json!({"exp": null}))),
+        ] {
+            assert_eq!(provider.validate_sync(&credentials), None, "{}", case);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn audience_and_issuer_are_optional() -> Result<()> {
+        let provider = JwtAuthProvider::new(&JwtAuthConfig {
+            audience: None,
+            issuer: None,
+            ..get_config()
+        })
+        .await?;
+        assert_eq!(
+            provider.validate_sync(&sign(SECRET, json!({"aud": null, "iss": null}))),
+            Some(Principal::new("alice", [Role::Admin]))
+        );
+        assert_eq!(
+            provider.validate_sync(&sign(SECRET, json!({"aud": "other"}))),
+            Some(Principal::new("alice", [Role::Admin]))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_basic_credentials() -> Result<()> {
+        let provider = JwtAuthProvider::new(&get_config()).await?;
+        let header = axum_extra::headers::Authorization::basic("alice", SECRET)
+            .0
+            .encode();
+        let credentials = Credentials::Basic(Basic::decode(&header).unwrap());
+        assert_eq!(provider.validate_sync(&credentials), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_keys() {
+        for (config, message) in [
+            (
+                JwtAuthConfig {
+                    secret: None,
+                    ..get_config()
+                },
+                "Fail to load config: JWT algorithm HS256 needs `secret` and no `public_key_path`",
+            ),
+            (
+                JwtAuthConfig {
+                    algorithm: Algorithm::RS256,
+                    ..get_config()
+                },
+                "Fail to load config: JWT algorithm RS256 needs `public_key_path` and no `secret`",
+            ),
+            (
+                JwtAuthConfig {
+                    algorithm: Algorithm::RS256,
+                    secret: None,
+                    public_key_path: Some("/nonexistent/key.pem".to_owned()),
+                    ..get_config()
+                },
+                "Fail to load config: Fail to read JWT public key `/nonexistent/key.pem`: ",
+            ),
+        ] {
+            let error = JwtAuthProvider::new(&config)
+                .await
+                .err()
+                .unwrap()
+                .to_string();
+            assert!(error.starts_with(message), "{}", error);
+        }
+    }
+}
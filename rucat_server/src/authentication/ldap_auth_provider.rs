@@ -0,0 +1,411 @@
+//! Authenticate users by binding to an LDAP server with their basic credentials.
+//!
+//! Each request opens its own connection, and the whole bind is limited by the bind timeout,
+//! so that a slow LDAP server only delays the requests authenticated by it.
+
+use ::std::time::Duration;
+
+use ::rucat_common::{
+    anyhow::anyhow,
+    error::{Result, RucatError},
+    serde::Deserialize,
+    tokio,
+    tracing::{debug, warn},
+};
+use ldap3::{dn_escape, ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+
+use super::{role::Role, Authenticate, Credentials, Principal};
+
+/// Placeholder of the username in [LdapAuthConfig::user_dn_template] and [LdapSearchConfig::filter].
+const USERNAME_PLACEHOLDER: &str = "{username}";
+
+/// Result code of a bind with wrong credentials.
+const INVALID_CREDENTIALS: u32 = 49;
+
+/// Search of the DN of a user, for directories where the DN cannot be built from the username.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct LdapSearchConfig {
+    /// DN under which the user is searched, e.g. `ou=people,dc=example,dc=com`.
+    pub base: String,
+    /// Filter of the user, `{username}` is replaced by the escaped username.
+    #[serde(default = "LdapSearchConfig::default_filter")]
+    pub filter: String,
+    /// DN to bind as before searching, the search is anonymous if it is not set.
+    pub bind_dn: Option<String>,
+    pub bind_password: Option<String>,
+}
+
+impl LdapSearchConfig {
+    fn default_filter() -> String {
+        "(uid={username})".to_owned()
+    }
+}
+
+/// Configuration of the LDAP server authenticating the users.
+/// The DN of a user is either built by [LdapAuthConfig::user_dn_template] or found by [LdapAuthConfig::search].
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct LdapAuthConfig {
+    /// URL of the server, e.g. `ldap://ldap.example.com:389` or `ldaps://ldap.example.com:636`.
+    pub url: String,
+    /// DN of a user, `{username}` is replaced by the escaped username, e.g. `uid={username},ou=people,dc=example,dc=com`.
+    pub user_dn_template: Option<String>,
+    pub search: Option<LdapSearchConfig>,
+    /// Whether to upgrade the `ldap://` connection by StartTLS.
+    #[serde(default)]
+    pub starttls: bool,
+    /// Timeout in second of connecting to the server and binding as the user.
+    #[serde(default = "LdapAuthConfig::default_bind_timeout_secs")]
+    pub bind_timeout_secs: u64,
+    /// Role of all the users authenticated by the server.
+    #[serde(default = "Role::configured_default")]
+    pub role: Role,
+}
+
+impl LdapAuthConfig {
+    fn default_bind_timeout_secs() -> u64 {
+        5
+    }
+}
+
+/// How to get the DN of a user.
+#[derive(Debug)]
+enum UserDn {
+    Template(String),
+    Search(LdapSearchConfig),
+}
+
+/// Authenticate users by binding to the LDAP server as them. Bearer credentials and API keys are rejected.
+#[derive(Debug)]
+pub struct LdapAuthProvider {
+    url: String,
+    user_dn: UserDn,
+    starttls: bool,
+    bind_timeout: Duration,
+    role: Role,
+}
+
+impl LdapAuthProvider {
+    /// Check the config, the server is not connected until the first request.
+    pub fn new(config: LdapAuthConfig) -> Result<Self> {
+        let config_error = |message: String| RucatError::fail_to_load_config(anyhow!(message));
+        let is_ldaps = config.url.starts_with("ldaps://");
+        if !is_ldaps && !config.url.starts_with("ldap://") {
+            return Err(config_error(format!(
+                "LDAP url `{}` must start with `ldap://` or `ldaps://`",
+                config.url
+            )));
+        }
+        if is_ldaps && config.starttls {
+            return Err(config_error(
+                "StartTLS cannot be used with `ldaps://`, which is already TLS".to_owned(),
+            ));
+        }
+        if config.bind_timeout_secs == 0 {
+            return Err(config_error(
+                "LDAP `bind_timeout_secs` must be positive".to_owned(),
+            ));
+        }
+        let user_dn = match (config.user_dn_template, config.search) {
+            (Some(template), None) if template.contains(USERNAME_PLACEHOLDER) => {
+                UserDn::Template(template)
+            }
+            (None, Some(search)) if search.filter.contains(USERNAME_PLACEHOLDER) => {
+                if search.bind_dn.is_some() != search.bind_password.is_some() {
+                    return Err(config_error(
+                        "LDAP search `bind_dn` and `bind_password` must be set together".to_owned(),
+                    ));
+                }
+                UserDn::Search(search)
+            }
+            (Some(_), None) => {
+                return Err(config_error(format!(
+                    "LDAP `user_dn_template` must contain `{}`",
+                    USERNAME_PLACEHOLDER
+                )))
+            }
+            (None, Some(_)) => {
+                return Err(config_error(format!(
+                    "LDAP search `filter` must contain `{}`",
+                    USERNAME_PLACEHOLDER
+                )))
+            }
+            _ => {
+                return Err(config_error(
+                    "Exactly one of LDAP `user_dn_template` and `search` must be set".to_owned(),
+                ))
+            }
+        };
+        Ok(Self {
+            url: config.url,
+            user_dn,
+            starttls: config.starttls,
+            bind_timeout: Duration::from_secs(config.bind_timeout_secs),
+            role: config.role,
+        })
+    }
+
+    /// Connect to the server, the connection is driven in the background until it is dropped.
+    async fn connect(&self) -> ::std::result::Result<Ldap, ldap3::LdapError> {
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(self.bind_timeout)
+            .set_starttls(self.starttls);
+        let (connection, ldap) = LdapConnAsync::with_settings(settings, &self.url).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.drive().await {
+                debug!("LDAP connection is closed: {}", e);
+            }
+        });
+        Ok(ldap)
+    }
+
+    /// Bind as the user, return whether the password is right.
+    async fn bind(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> ::std::result::Result<bool, ldap3::LdapError> {
+        let mut ldap = self.connect().await?;
+        let dn = match &self.user_dn {
+            UserDn::Template(template) => {
+                Some(template.replace(USERNAME_PLACEHOLDER, &dn_escape(username)))
+            }
+            UserDn::Search(search) => find_user_dn(&mut ldap, search, username).await?,
+        };
+        let authenticated = match dn {
+            Some(dn) => {
+                let result = ldap.simple_bind(&dn, password).await?;
+                match result.rc {
+                    0 => true,
+                    INVALID_CREDENTIALS => false,
+                    _ => return Err(result.success().unwrap_err()),
+                }
+            }
+            None => false,
+        };
+        if let Err(e) = ldap.unbind().await {
+            debug!("Fail to unbind from the LDAP server: {}", e);
+        }
+        Ok(authenticated)
+    }
+}
+
+/// DN of the only user matching the search filter, `None` if there is no or more than one such user.
+async fn find_user_dn(
+    ldap: &mut Ldap,
+    search: &LdapSearchConfig,
+    username: &str,
+) -> ::std::result::Result<Option<String>, ldap3::LdapError> {
+    if let (Some(dn), Some(password)) = (&search.bind_dn, &search.bind_password) {
+        ldap.simple_bind(dn, password).await?.success()?;
+    }
+    let filter = search
+        .filter
+        .replace(USERNAME_PLACEHOLDER, &ldap_escape(username));
+    // `1.1` asks for no attribute, only the DN is needed.
+    let (entries, _) = ldap
+        .search(&search.base, Scope::Subtree, &filter, vec!["1.1"])
+        .await?
+        .success()?;
+    match <[_; 1]>::try_from(entries) {
+        Ok([entry]) => Ok(Some(SearchEntry::construct(entry).dn)),
+        Err(entries) => {
+            debug!(
+                "{} LDAP entries match the filter `{}`, expect exactly one",
+                entries.len(),
+                filter
+            );
+            Ok(None)
+        }
+    }
+}
+
+impl Authenticate for LdapAuthProvider {
+    async fn validate(&self, credentials: &Credentials) -> Result<Option<Principal>> {
+        let Credentials::Basic(basic) = credentials else {
+            return Ok(None);
+        };
+        // An empty password is an unauthenticated bind, which succeeds for any DN.
+        if basic.username().is_empty() || basic.password().is_empty() {
+            return Ok(None);
+        }
+        match tokio::time::timeout(
+            self.bind_timeout,
+            self.bind(basic.username(), basic.password()),
+        )
+        .await
+        {
+            Ok(Ok(authenticated)) => {
+                Ok(authenticated.then(|| Principal::new(basic.username(), [self.role])))
+            }
+            Ok(Err(e)) => {
+                warn!("Fail to bind to the LDAP server `{}`: {}", self.url, e);
+                Err(RucatError::service_unavailable(anyhow!(
+                    "LDAP server is unavailable"
+                )))
+            }
+            Err(_) => {
+                warn!(
+                    "Binding to the LDAP server `{}` timed out after {:?}",
+                    self.url, self.bind_timeout
+                );
+                Err(RucatError::service_unavailable(anyhow!(
+                    "LDAP server is unavailable"
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::rucat_common::serde_json::{from_value, json};
+    use axum_extra::headers::authorization::{Basic, Bearer, Credentials as _};
+    use http::HeaderValue;
+
+    use super::*;
+
+    fn get_config() -> LdapAuthConfig {
+        from_value(json!({
+            "url": "ldap://127.0.0.1:1",
+            "user_dn_template": "uid={username},ou=people,dc=example,dc=com"
+        }))
+        .unwrap()
+    }
+
+    fn basic(username: &str, password: &str) -> Credentials {
+        let header = ::headers::Authorization::basic(username, password)
+            .0
+            .encode();
+        Credentials::Basic(Basic::decode(&header).unwrap())
+    }
+
+    #[test]
+    fn reject_invalid_config() {
+        for (config, message) in [
+            (
+                json!({"url": "http://ldap", "user_dn_template": "uid={username}"}),
+                "LDAP url `http://ldap` must start with `ldap://` or `ldaps://`",
+            ),
+            (
+                json!({"url": "ldaps://ldap", "user_dn_template": "uid={username}", "starttls": true}),
+                "StartTLS cannot be used with `ldaps://`, which is already TLS",
+            ),
+            (
+                json!({"url": "ldap://ldap", "user_dn_template": "uid={username}", "bind_timeout_secs": 0}),
+                "LDAP `bind_timeout_secs` must be positive",
+            ),
+            (
+                json!({"url": "ldap://ldap"}),
+                "Exactly one of LDAP `user_dn_template` and `search` must be set",
+            ),
+            (
+                json!({"url": "ldap://ldap", "user_dn_template": "uid={username}", "search": {"base": "dc=example"}}),
+                "Exactly one of LDAP `user_dn_template` and `search` must be set",
+            ),
+            (
+                json!({"url": "ldap://ldap", "user_dn_template": "uid=admin"}),
+                "LDAP `user_dn_template` must contain `{username}`",
+            ),
+            (
+                json!({"url": "ldap://ldap", "search": {"base": "dc=example", "filter": "(uid=admin)"}}),
+                "LDAP search `filter` must contain `{username}`",
+            ),
+            (
+                json!({"url": "ldap://ldap", "search": {"base": "dc=example", "bind_dn": "cn=rucat"}}),
+                "LDAP search `bind_dn` and `bind_password` must be set together",
+            ),
+        ] {
+            let config = from_value::<LdapAuthConfig>(config).unwrap();
+            let error = LdapAuthProvider::new(config).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!("Fail to load config: {}", message)
+            );
+        }
+    }
+
+    #[test]
+    fn default_config() {
+        let config = from_value::<LdapAuthConfig>(json!({
+            "url": "ldap://ldap",
+            "search": {"base": "dc=example"}
+        }))
+        .unwrap();
+        assert_eq!(
+            config,
+            LdapAuthConfig {
+                url: "ldap://ldap".to_owned(),
+                user_dn_template: None,
+                search: Some(LdapSearchConfig {
+                    base: "dc=example".to_owned(),
+                    filter: "(uid={username})".to_owned(),
+                    bind_dn: None,
+                    bind_password: None,
+                }),
+                starttls: false,
+                bind_timeout_secs: 5,
+                role: Role::Admin,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_other_credentials_without_connecting() -> Result<()> {
+        let provider = LdapAuthProvider::new(get_config())?;
+        let header = HeaderValue::from_static("Bearer abc");
+        let bearer = Credentials::Bearer(Bearer::decode(&header).unwrap());
+        assert_eq!(provider.validate(&bearer).await?, None);
+        assert_eq!(
+            provider
+                .validate(&Credentials::ApiKey("abc".to_owned()))
+                .await?,
+            None
+        );
+        // unauthenticated binds
+        assert_eq!(provider.validate(&basic("alice", "")).await?, None);
+        assert_eq!(provider.validate(&basic("", "pwd")).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn slow_server_times_out() -> Result<()> {
+        // The server accepts the connection but never answers the bind.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let _server = tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+        let provider = LdapAuthProvider::new(LdapAuthConfig {
+            url: format!("ldap://{}", address),
+            bind_timeout_secs: 1,
+            ..get_config()
+        })?;
+
+        let start = tokio::time::Instant::now();
+        let error = provider.validate(&basic("alice", "pwd")).await.unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(3));
+        assert_eq!(
+            error.to_string(),
+            "Service unavailable: LDAP server is unavailable"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unreachable_server_is_unavailable() -> Result<()> {
+        let provider = LdapAuthProvider::new(get_config())?;
+        let error = provider.validate(&basic("alice", "pwd")).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Service unavailable: LDAP server is unavailable"
+        );
+        Ok(())
+    }
+}
@@ -1,45 +1,302 @@
 //! authentication middleware
 
-use ::std::sync::Arc;
+use ::std::{
+    collections::BTreeSet,
+    convert::Infallible,
+    fmt::{Display, Formatter},
+    future::Future,
+    net::SocketAddr,
+    sync::Arc,
+};
 use std::panic::catch_unwind;
 
 use ::axum::extract::State;
-use axum::{extract::Request, http::HeaderMap, middleware::Next, response::Response};
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Request},
+    http::{request::Parts, HeaderMap, HeaderName},
+    middleware::Next,
+    response::Response,
+};
 use axum_extra::headers::authorization::{Basic, Bearer, Credentials as _};
 use rucat_common::anyhow::anyhow;
+use rucat_common::engine::EngineActor;
 use rucat_common::error::RucatError;
+use rucat_common::tracing::{info, warn};
 
-use crate::error::RucatServerError;
+use crate::{
+    error::RucatServerError,
+    login_lockout::LoginLockout,
+    metrics::{AuthOutcome, AuthScheme, Metrics},
+    rate_limit::reject_too_many_requests,
+};
+use role::Role;
 
+pub mod api_key_auth_provider;
+pub mod htpasswd_auth_provider;
+pub mod jwt_auth_provider;
+pub mod ldap_auth_provider;
+pub mod oidc_auth_provider;
+pub mod registry;
+pub mod role;
+pub(crate) mod secret;
 pub mod static_auth_provider;
 
 type Result<T> = std::result::Result<T, RucatServerError>;
 
+/// Header of the API keys, checked before the `Authorization` header.
+pub(crate) const API_KEY_HEADER: HeaderName = HeaderName::from_static("x-api-key");
+
+/// Header of the user that an admin acts on behalf of, see [Principal::impersonate].
+pub(crate) const IMPERSONATE_USER_HEADER: HeaderName =
+    HeaderName::from_static("x-rucat-impersonate-user");
+
 pub enum Credentials {
     Basic(Basic),
     Bearer(Bearer),
+    /// Long-lived key of a service, sent in the `X-Api-Key` header.
+    ApiKey(String),
+    /// Certificate of the client, verified in the TLS handshake. It takes precedence over the headers.
+    ClientCert(ClientCertificate),
+}
+
+/// Client certificate of a TLS connection, verified against the configured CA.
+/// Inserted in the request extensions by [crate::tls::ClientCertAcceptor].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientCertificate {
+    /// Common name of the subject, or the first DNS or URI subject alternative name without a common name.
+    pub name: String,
+}
+
+impl Credentials {
+    fn get_scheme(&self) -> AuthScheme {
+        match self {
+            Credentials::Basic(_) => AuthScheme::Basic,
+            Credentials::Bearer(_) => AuthScheme::Bearer,
+            Credentials::ApiKey(_) => AuthScheme::ApiKey,
+            Credentials::ClientCert(_) => AuthScheme::ClientCert,
+        }
+    }
+
+    /// Identity of the client authenticated as `principal` by the credentials.
+    /// Users and bearer tokens of the same name are different clients, e.g. to be rate limited separately.
+    fn get_identity(&self, principal: &str) -> ClientIdentity {
+        match self {
+            Credentials::Basic(_) => ClientIdentity(format!("user:{}", principal)),
+            Credentials::Bearer(_) => ClientIdentity(format!("token:{}", principal)),
+            Credentials::ApiKey(_) => ClientIdentity(format!("api_key:{}", principal)),
+            Credentials::ClientCert(_) => ClientIdentity(format!("cert:{}", principal)),
+        }
+    }
+
+    /// Keys of the [LoginLockout] of the client sending the credentials from `remote_address`:
+    /// the username of basic credentials, and the remote IP if it is known, e.g. not in tests.
+    /// Client certificates are verified by the TLS layer, so they are never locked out.
+    fn get_lockout_keys(&self, remote_address: Option<&SocketAddr>) -> Vec<String> {
+        let username = match self {
+            Credentials::ClientCert(_) => return vec![],
+            Credentials::Basic(basic) => Some(format!("user:{}", basic.username())),
+            Credentials::Bearer(_) | Credentials::ApiKey(_) => None,
+        };
+        username
+            .into_iter()
+            .chain(remote_address.map(|address| format!("ip:{}", address.ip())))
+            .collect()
+    }
+}
+
+/// Identity of the authenticated client of a request, e.g. to rate limit each client.
+/// Inserted in the request extensions by [auth].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ClientIdentity(pub(crate) String);
+
+/// Prefix of the engine routes, which are always authenticated.
+const ENGINE_PATH: &str = "/engine";
+
+/// Whether the request `path` is the exempt `path` or under it, e.g. `/admin/` and `/admin` both exempt `/admin/x` but not `/administrator`.
+fn is_exempt(path: &str, exempt_path: &str) -> bool {
+    let exempt_path = exempt_path.trim_end_matches('/');
+    path.strip_prefix(exempt_path)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Check each path exempt from authentication is absolute, and does not cover the engine routes.
+pub fn validate_exempt_paths(exempt_paths: &[String]) -> ::rucat_common::error::Result<()> {
+    for path in exempt_paths {
+        if !path.starts_with('/') {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "Auth exempt path `{}` must start with `/`",
+                path
+            )));
+        }
+        if is_exempt(ENGINE_PATH, path) || is_exempt(path, ENGINE_PATH) {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "Auth exempt path `{}` covers `{}`, whose routes are always authenticated",
+                path,
+                ENGINE_PATH
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// State of [auth].
+pub(crate) struct AuthState<AuthProvider> {
+    auth_provider: AuthProvider,
+    /// Requests to the paths or under them are not authenticated.
+    exempt_paths: Vec<String>,
+    /// Clients failing to log in too many times are locked out, they are never locked out if it is not set.
+    login_lockout: Option<Arc<LoginLockout>>,
+    /// Metrics counting the authentication attempts, they are not counted if it is not set.
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl<AuthProvider> AuthState<AuthProvider> {
+    pub(crate) fn new(
+        auth_provider: AuthProvider,
+        exempt_paths: Vec<String>,
+        login_lockout: Option<Arc<LoginLockout>>,
+        metrics: Option<Arc<Metrics>>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            auth_provider,
+            exempt_paths,
+            login_lockout,
+            metrics,
+        })
+    }
 }
 
 /// authentication
 pub(crate) async fn auth<AuthProvider>(
-    State(auth_provider): State<Arc<AuthProvider>>,
+    State(state): State<Arc<AuthState<AuthProvider>>>,
     headers: HeaderMap,
-    request: Request,
+    mut request: Request,
     next: Next,
 ) -> Result<Response>
 where
     AuthProvider: Authenticate,
 {
-    let credentials = get_credentials(&headers)?;
-    if auth_provider.validate(&credentials) {
-        Ok(next.run(request).await)
-    } else {
-        Err(RucatError::unauthorized(anyhow!("wrong credentials")).into())
+    let path = request.uri().path();
+    if state
+        .exempt_paths
+        .iter()
+        .any(|exempt_path| is_exempt(path, exempt_path))
+    {
+        return Ok(next.run(request).await);
+    }
+    let credentials = get_credentials(&headers, request.extensions().get::<ClientCertificate>())?;
+    let remote_address = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| *address);
+    let lockout_keys = credentials.get_lockout_keys(remote_address.as_ref());
+    let lockout_keys = || lockout_keys.iter().map(String::as_str);
+    if let Some(login_lockout) = &state.login_lockout {
+        // Checked before the credentials, so that a locked out client cannot keep guessing.
+        if let Err(wait) = login_lockout.check(lockout_keys()) {
+            return Ok(reject_too_many_requests("Too many failed logins", wait));
+        }
+    }
+    let principal = match (
+        state.auth_provider.validate(&credentials).await?,
+        &credentials,
+    ) {
+        (Some(principal), _) => Some(principal),
+        // The certificate is verified by the TLS layer, so it is authenticated as its name unless the provider knows it.
+        (None, Credentials::ClientCert(certificate)) => Some(Principal::new(
+            certificate.name.clone(),
+            [Role::configured_default()],
+        )),
+        (None, _) => None,
+    };
+    if let Some(login_lockout) = &state.login_lockout {
+        match principal {
+            Some(_) => login_lockout.record_success(lockout_keys()),
+            None => login_lockout.record_failure(lockout_keys()),
+        }
+    }
+    let scheme = credentials.get_scheme();
+    if principal.is_none() {
+        // The credentials are secrets, so only their scheme is logged.
+        warn!(
+            "Failed to authenticate {} credentials from {}",
+            scheme.as_str(),
+            remote_address.map_or_else(
+                || "unknown address".to_owned(),
+                |address| address.to_string()
+            )
+        );
+    }
+    if let Some(metrics) = &state.metrics {
+        let outcome = match principal {
+            Some(_) => AuthOutcome::Success,
+            None => AuthOutcome::Failure,
+        };
+        metrics.record_auth_attempt(scheme, outcome);
+    }
+    match principal {
+        Some(principal) => {
+            // the client is rate limited as itself, not as the user it impersonates
+            request
+                .extensions_mut()
+                .insert(credentials.get_identity(&principal.name));
+            let principal = match get_impersonated_user(&headers)? {
+                Some(user) => {
+                    let principal = principal.impersonate(user)?;
+                    info!(
+                        "{} sends {} {}",
+                        principal,
+                        request.method(),
+                        request.uri().path()
+                    );
+                    principal
+                }
+                None => principal,
+            };
+            request.extensions_mut().insert(principal);
+            Ok(next.run(request).await)
+        }
+        None => Err(RucatError::unauthorized(anyhow!("wrong credentials")).into()),
+    }
+}
+
+/// Get the user in the [IMPERSONATE_USER_HEADER] of the request, if it is set.
+fn get_impersonated_user(headers: &HeaderMap) -> Result<Option<&str>> {
+    let Some(user) = headers.get(&IMPERSONATE_USER_HEADER) else {
+        return Ok(None);
+    };
+    match user.to_str() {
+        Ok(user) if !user.is_empty() => Ok(Some(user)),
+        _ => Err(RucatError::invalid_request(anyhow!(
+            "`{}` must be a non-empty visible ASCII string",
+            IMPERSONATE_USER_HEADER
+        ))
+        .into()),
     }
 }
 
-/// Get credentials from headers
-fn get_credentials(headers: &HeaderMap) -> Result<Credentials> {
+/// Get credentials of the request, the client certificate if there is one,
+/// otherwise the API key if it is set, otherwise the `Authorization` header.
+fn get_credentials(
+    headers: &HeaderMap,
+    client_certificate: Option<&ClientCertificate>,
+) -> Result<Credentials> {
+    if let Some(certificate) = client_certificate {
+        return Ok(Credentials::ClientCert(certificate.clone()));
+    }
+    if let Some(api_key) = headers.get(&API_KEY_HEADER) {
+        // The key is not printed, as it is a secret.
+        return api_key
+            .to_str()
+            .map(|api_key| Credentials::ApiKey(api_key.to_owned()))
+            .map_err(|_| {
+                RucatError::unauthorized(anyhow!(
+                    "`{}` must be a visible ASCII string",
+                    API_KEY_HEADER
+                ))
+                .into()
+            });
+    }
     let token = headers
         .get(http::header::AUTHORIZATION)
         .ok_or_else(|| RucatError::unauthorized(anyhow!("Not found authorization header")))?;
@@ -55,8 +312,194 @@ fn get_credentials(headers: &HeaderMap) -> Result<Credentials> {
         .ok_or_else(|| RucatError::unauthorized(anyhow!("Unsupported credentials type")).into())
 }
 
-/// Trait for authentication
+/// Client authenticated by an [Authenticate] provider.
+/// Inserted in the request extensions by [auth], and extracted by handlers as [Principal::anonymous] if the authentication is disabled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Principal {
+    /// Name of the client, e.g. the username or the name of the bearer token.
+    pub name: String,
+    /// Roles of the client, deciding the engine requests it is allowed to send.
+    pub roles: BTreeSet<Role>,
+    /// Whether the client is not authenticated, which is not the same as a client named `anonymous`.
+    anonymous: bool,
+    /// Name of the admin acting on behalf of the client, see [Principal::impersonate].
+    impersonator: Option<String>,
+}
+
+impl Principal {
+    /// Name of the [Principal::anonymous] client.
+    pub const ANONYMOUS: &str = "anonymous";
+
+    pub fn new(name: impl Into<String>, roles: impl IntoIterator<Item = Role>) -> Self {
+        Self {
+            name: name.into(),
+            roles: roles.into_iter().collect(),
+            anonymous: false,
+            impersonator: None,
+        }
+    }
+
+    /// Client of the requests while the authentication is disabled, who is allowed to do everything.
+    pub fn anonymous() -> Self {
+        Self {
+            anonymous: true,
+            ..Self::new(Self::ANONYMOUS, [Role::Admin])
+        }
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        self.anonymous
+    }
+
+    /// Whether the client has the `role` or a role allowed to do more.
+    pub fn has_role(&self, role: Role) -> bool {
+        self.roles.iter().any(|r| *r >= role)
+    }
+
+    /// Act on behalf of the `user`, who owns the engines created and is checked for the engines changed.
+    /// The roles of the user are unknown, so it is an operator, allowed to change only its own engines and the engines shared with it.
+    /// # Return
+    /// - `Ok(Principal)` of the user, recording this client as the impersonator.
+    /// - `Err(_)` with 403 if this client is not an admin.
+    pub fn impersonate(self, user: &str) -> ::rucat_common::error::Result<Self> {
+        if !self.has_role(Role::Admin) {
+            return Err(RucatError::not_allowed(anyhow!(
+                "`{}` is not allowed to impersonate `{}`, which needs the `admin` role",
+                self.name,
+                user
+            )));
+        }
+        Ok(Self {
+            impersonator: Some(self.name),
+            ..Self::new(user, [Role::Operator])
+        })
+    }
+
+    /// Name of the admin acting on behalf of the client, if the client is impersonated.
+    pub fn get_impersonator(&self) -> Option<&str> {
+        self.impersonator.as_deref()
+    }
+
+    /// The client recorded in the history of the engines it changes, `None` if the authentication is disabled.
+    pub fn get_engine_actor(&self) -> Option<EngineActor> {
+        (!self.anonymous).then(|| EngineActor {
+            name: self.name.clone(),
+            impersonator: self.impersonator.clone(),
+        })
+    }
+}
+
+/// Name of the client, with the admin acting on behalf of it if it is impersonated, e.g. in the logs.
+impl Display for Principal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> ::std::fmt::Result {
+        match &self.impersonator {
+            Some(impersonator) => write!(f, "{} (impersonated by {})", self.name, impersonator),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for Principal
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<Principal>()
+            .cloned()
+            .unwrap_or_else(Principal::anonymous))
+    }
+}
+
+/// Trait for authentication.
+/// Providers without I/O can implement [SyncAuthenticate] instead.
+/// Providers chosen at runtime are wrapped in [registry::DynAuthProvider].
 pub trait Authenticate: Send + Sync + 'static {
-    /// Validate the credentials
-    fn validate(&self, credentials: &Credentials) -> bool;
+    /// Validate the credentials, return the authenticated client,
+    /// `Ok(None)` if the credentials are wrong, or an error if they cannot be validated, e.g. the identity service is down.
+    fn validate(
+        &self,
+        credentials: &Credentials,
+    ) -> impl Future<Output = ::rucat_common::error::Result<Option<Principal>>> + Send;
+}
+
+/// Synchronous authentication, which is [Authenticate] by the blanket implementation.
+/// Unlike [Authenticate], it can be used as a trait object, e.g. `Arc<dyn SyncAuthenticate>`.
+pub trait SyncAuthenticate: Send + Sync + 'static {
+    /// Validate the credentials, return the authenticated client, or `None` if the credentials are wrong.
+    fn validate_sync(&self, credentials: &Credentials) -> Option<Principal>;
+}
+
+impl<AuthProvider> Authenticate for AuthProvider
+where
+    AuthProvider: SyncAuthenticate + ?Sized,
+{
+    async fn validate(
+        &self,
+        credentials: &Credentials,
+    ) -> ::rucat_common::error::Result<Option<Principal>> {
+        Ok(self.validate_sync(credentials))
+    }
+}
+
+/// Shared providers, e.g. `Arc<dyn SyncAuthenticate>`.
+impl<AuthProvider> SyncAuthenticate for Arc<AuthProvider>
+where
+    AuthProvider: SyncAuthenticate + ?Sized,
+{
+    fn validate_sync(&self, credentials: &Credentials) -> Option<Principal> {
+        (**self).validate_sync(credentials)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_exempt_paths() {
+        for (path, exempt_path, expected) in [
+            ("/admin", "/admin", true),
+            ("/admin/engines", "/admin", true),
+            ("/admin/engines", "/admin/", true),
+            ("/administrator", "/admin", false),
+            ("/capabilities", "/admin", false),
+            ("/engine", "/", true),
+        ] {
+            assert_eq!(
+                is_exempt(path, exempt_path),
+                expected,
+                "{} {}",
+                path,
+                exempt_path
+            );
+        }
+    }
+
+    #[test]
+    fn engine_paths_are_not_exemptable() {
+        assert!(validate_exempt_paths(&["/capabilities".to_owned(), "/admin/".to_owned()]).is_ok());
+        assert!(validate_exempt_paths(&["/engines".to_owned()]).is_ok());
+        for path in ["/", "/engine", "/engine/", "/engine/123"] {
+            let error = validate_exempt_paths(&[path.to_owned()]).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "Fail to load config: Auth exempt path `{}` covers `/engine`, whose routes are always authenticated",
+                    path
+                )
+            );
+        }
+        let error = validate_exempt_paths(&["admin".to_owned()]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Fail to load config: Auth exempt path `admin` must start with `/`"
+        );
+    }
 }
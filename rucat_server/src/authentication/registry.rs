@@ -0,0 +1,361 @@
+//! Auth providers chosen at runtime by [AuthProviderVariant], as trait objects of [Authenticate].
+//!
+//! The provider can be replaced without restarting the server by [reload_auth_provider],
+//! e.g. after the credentials are rotated in the config.
+
+use ::std::sync::{Arc, RwLock};
+
+use ::futures::future::BoxFuture;
+use ::rucat_common::{
+    anyhow::anyhow,
+    config::load_config,
+    error::{Result, RucatError},
+    tracing::info,
+};
+
+use super::{
+    api_key_auth_provider::ApiKeyAuthProvider, htpasswd_auth_provider::HtpasswdAuthProvider,
+    jwt_auth_provider::JwtAuthProvider, ldap_auth_provider::LdapAuthProvider,
+    oidc_auth_provider::OidcAuthProvider, static_auth_provider::StaticAuthProvider, Authenticate,
+    Credentials, Principal,
+};
+use crate::{AuthProviderVariant, ServerConfig};
+
+/// [Authenticate] as a trait object, which is implemented for every provider.
+trait DynAuthenticate: Send + Sync + 'static {
+    fn validate_boxed<'a>(
+        &'a self,
+        credentials: &'a Credentials,
+    ) -> BoxFuture<'a, Result<Option<Principal>>>;
+}
+
+impl<AuthProvider> DynAuthenticate for AuthProvider
+where
+    AuthProvider: Authenticate,
+{
+    fn validate_boxed<'a>(
+        &'a self,
+        credentials: &'a Credentials,
+    ) -> BoxFuture<'a, Result<Option<Principal>>> {
+        Box::pin(self.validate(credentials))
+    }
+}
+
+/// Any auth provider, e.g. built from the config by [build_auth_provider].
+/// Cloning it shares the provider, which is replaced for all the clones by [DynAuthProvider::replace].
+#[derive(Clone)]
+pub struct DynAuthProvider(Arc<RwLock<Arc<dyn DynAuthenticate>>>);
+
+impl DynAuthProvider {
+    pub fn new<AuthProvider>(auth_provider: AuthProvider) -> Self
+    where
+        AuthProvider: Authenticate,
+    {
+        Self(Arc::new(RwLock::new(Arc::new(auth_provider))))
+    }
+
+    fn get_current(&self) -> Arc<dyn DynAuthenticate> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the provider of all the clones by the current provider of `auth_provider`.
+    /// The requests being validated keep using the old provider.
+    pub fn replace(&self, auth_provider: &DynAuthProvider) {
+        let current = auth_provider.get_current();
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = current;
+    }
+}
+
+impl Authenticate for DynAuthProvider {
+    async fn validate(&self, credentials: &Credentials) -> Result<Option<Principal>> {
+        // not holding the lock while validating, which may do I/O
+        let current = self.get_current();
+        current.validate_boxed(credentials).await
+    }
+}
+
+/// Source of the auth provider config, which is read again when the provider is reloaded.
+pub trait AuthConfigSource: Send + Sync + 'static {
+    /// The auth provider config, `None` if the authentication is disabled.
+    fn load(&self) -> Result<Option<AuthProviderVariant>>;
+}
+
+/// `auth_provider` of the [ServerConfig] in the config file.
+pub struct AuthConfigFile {
+    path: String,
+}
+
+impl AuthConfigFile {
+    pub fn new(path: String) -> Self {
+        Self { path }
+    }
+}
+
+impl AuthConfigSource for AuthConfigFile {
+    fn load(&self) -> Result<Option<AuthProviderVariant>> {
+        let config = load_config::<ServerConfig>(&self.path)?;
+        Ok(config.auth_provider)
+    }
+}
+
+/// Build the auth provider from the `source`, and replace the `current` provider by it.
+/// The `current` provider is kept if the new one cannot be built.
+/// The authentication cannot be enabled or disabled by reloading, as the middleware is only added on startup.
+pub async fn reload_auth_provider(
+    current: Option<&DynAuthProvider>,
+    source: &dyn AuthConfigSource,
+) -> Result<()> {
+    let keep_current = |e: RucatError| {
+        RucatError::fail_to_load_config(anyhow!(
+            "The auth provider is not reloaded, the current one is kept: {}",
+            e.get_message()
+        ))
+    };
+    let variant = source.load().map_err(keep_current)?;
+    let new = build_auth_provider(variant.as_ref())
+        .await
+        .map_err(keep_current)?;
+    match (current, new) {
+        (Some(current), Some(new)) => {
+            current.replace(&new);
+            info!("The auth provider is reloaded");
+            Ok(())
+        }
+        (None, None) => Ok(()),
+        (Some(_), None) | (None, Some(_)) => Err(RucatError::fail_to_load_config(anyhow!(
+            "The auth provider is not reloaded, the authentication cannot be enabled or disabled without restarting the server"
+        ))),
+    }
+}
+
+/// Provider trying each provider in order, the credentials are accepted by the first provider accepting them.
+/// If no provider accepts them, the first error of the providers is returned, e.g. an identity service is down.
+pub struct AnyAuthProvider(Vec<DynAuthProvider>);
+
+impl AnyAuthProvider {
+    pub fn new(auth_providers: Vec<DynAuthProvider>) -> Self {
+        Self(auth_providers)
+    }
+}
+
+impl Authenticate for AnyAuthProvider {
+    async fn validate(&self, credentials: &Credentials) -> Result<Option<Principal>> {
+        let mut error = None;
+        for auth_provider in &self.0 {
+            match auth_provider.validate(credentials).await {
+                Ok(Some(principal)) => return Ok(Some(principal)),
+                Ok(None) => {}
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        error.map_or(Ok(None), Err)
+    }
+}
+
+/// Build the auth provider of the `variant`, `None` if the authentication is disabled, i.e. no variant is configured.
+pub async fn build_auth_provider(
+    variant: Option<&AuthProviderVariant>,
+) -> Result<Option<DynAuthProvider>> {
+    match variant {
+        Some(variant) => build_variant(variant).await.map(Some),
+        None => {
+            info!("Authentication is disabled");
+            Ok(None)
+        }
+    }
+}
+
+/// Boxed for the recursion of [AuthProviderVariant::Any].
+fn build_variant(variant: &AuthProviderVariant) -> BoxFuture<'_, Result<DynAuthProvider>> {
+    Box::pin(async move {
+        let auth_provider = match variant {
+            AuthProviderVariant::StaticAuthProviderConfig(config) => {
+                info!("Static authentication is enabled");
+                DynAuthProvider::new(StaticAuthProvider::from_config(config.clone())?)
+            }
+            AuthProviderVariant::JwtAuthProviderConfig(config) => {
+                info!("JWT authentication is enabled: {:?}", config.algorithm);
+                DynAuthProvider::new(JwtAuthProvider::new(config).await?)
+            }
+            AuthProviderVariant::OidcAuthProviderConfig(config) => {
+                info!("OIDC authentication is enabled: {}", config.issuer_url);
+                DynAuthProvider::new(OidcAuthProvider::new(config).await?)
+            }
+            AuthProviderVariant::ApiKeyAuthProviderConfig(config) => {
+                info!("API key authentication is enabled: {:?}", config);
+                DynAuthProvider::new(ApiKeyAuthProvider::new(config.clone())?)
+            }
+            AuthProviderVariant::Ldap(config) => {
+                info!("LDAP authentication is enabled: {}", config.url);
+                DynAuthProvider::new(LdapAuthProvider::new(config.clone())?)
+            }
+            AuthProviderVariant::Htpasswd(config) => {
+                info!("htpasswd authentication is enabled: {}", config.path);
+                DynAuthProvider::new(HtpasswdAuthProvider::new(config).await?)
+            }
+            AuthProviderVariant::Any(variants) => {
+                if variants.is_empty() {
+                    return Err(RucatError::fail_to_load_config(anyhow!(
+                        "`Any` auth provider needs at least one provider"
+                    )));
+                }
+                info!(
+                    "Credentials are accepted by any of {} auth providers in order",
+                    variants.len()
+                );
+                let mut auth_providers = Vec::with_capacity(variants.len());
+                for variant in variants {
+                    auth_providers.push(build_variant(variant).await?);
+                }
+                DynAuthProvider::new(AnyAuthProvider::new(auth_providers))
+            }
+        };
+        Ok(auth_provider)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ::rucat_common::{serde_json::json, tokio};
+    use axum_extra::headers::authorization::{Basic, Bearer, Credentials as _};
+    use http::HeaderValue;
+
+    use super::*;
+    use crate::authentication::role::Role;
+
+    fn basic(username: &str, password: &str) -> Credentials {
+        let header = axum_extra::headers::Authorization::basic(username, password)
+            .0
+            .encode();
+        Credentials::Basic(Basic::decode(&header).unwrap())
+    }
+
+    fn bearer(token: &str) -> Credentials {
+        let header = HeaderValue::from_str(&format!("Bearer {}", token)).unwrap();
+        Credentials::Bearer(Bearer::decode(&header).unwrap())
+    }
+
+    /// Provider failing to validate every credentials, as if its identity service is down.
+    struct UnavailableAuthProvider;
+
+    impl Authenticate for UnavailableAuthProvider {
+        async fn validate(&self, _: &Credentials) -> Result<Option<Principal>> {
+            Err(RucatError::service_unavailable(anyhow!("down")))
+        }
+    }
+
+    async fn build(config: ::rucat_common::serde_json::Value) -> Result<DynAuthProvider> {
+        let variant = ::rucat_common::serde_json::from_value(config).unwrap();
+        Ok(build_auth_provider(Some(&variant)).await?.unwrap())
+    }
+
+    #[tokio::test]
+    async fn build_without_variant() -> Result<()> {
+        assert!(build_auth_provider(None).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn build_static_provider() -> Result<()> {
+        let auth_provider = build(json!({
+            "StaticAuthProviderConfig": {
+                "username": "admin",
+                "password": "pwd",
+                "bearer_token": "token"
+            }
+        }))
+        .await?;
+        assert_eq!(
+            auth_provider.validate(&basic("admin", "pwd")).await?,
+            Some(Principal::new("admin", [Role::Admin]))
+        );
+        assert_eq!(
+            auth_provider.validate(&basic("admin", "wrong")).await?,
+            None
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replace_provider_of_clones() -> Result<()> {
+        let get_provider = |token: &str| {
+            DynAuthProvider::new(StaticAuthProvider::new(
+                "admin".to_owned(),
+                "pwd".to_owned(),
+                token.to_owned(),
+            ))
+        };
+        let auth_provider = get_provider("old");
+        let clone = auth_provider.clone();
+        clone.replace(&get_provider("new"));
+        assert_eq!(auth_provider.validate(&bearer("old")).await?, None);
+        assert_eq!(
+            auth_provider.validate(&bearer("new")).await?,
+            Some(Principal::new("admin", [Role::Admin]))
+        );
+
+        // replaced by the current provider of itself, rather than itself
+        auth_provider.replace(&clone);
+        assert!(auth_provider.validate(&bearer("new")).await?.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn any_provider_accepts_credentials_of_each_provider() -> Result<()> {
+        let auth_provider = build(json!({
+            "Any": [
+                {
+                    "StaticAuthProviderConfig": {
+                        "users": [{"username": "alice", "password": "pwd-a"}],
+                        "bearer_tokens": []
+                    }
+                },
+                {
+                    "StaticAuthProviderConfig": {
+                        "users": [],
+                        "bearer_tokens": [{"name": "ci", "token": "token-ci", "role": "viewer"}]
+                    }
+                }
+            ]
+        }))
+        .await?;
+        assert_eq!(
+            auth_provider.validate(&basic("alice", "pwd-a")).await?,
+            Some(Principal::new("alice", [Role::Admin]))
+        );
+        assert_eq!(
+            auth_provider.validate(&bearer("token-ci")).await?,
+            Some(Principal::new("ci", [Role::Viewer]))
+        );
+        assert_eq!(auth_provider.validate(&bearer("wrong")).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn any_provider_returns_error_if_none_accepts() -> Result<()> {
+        let auth_provider = AnyAuthProvider::new(vec![
+            DynAuthProvider::new(UnavailableAuthProvider),
+            DynAuthProvider::new(StaticAuthProvider::new(
+                "admin".to_owned(),
+                "pwd".to_owned(),
+                "token".to_owned(),
+            )),
+        ]);
+        assert_eq!(
+            auth_provider.validate(&bearer("token")).await?,
+            Some(Principal::new("admin", [Role::Admin]))
+        );
+        let result = auth_provider.validate(&bearer("wrong")).await;
+        assert!(result.is_err_and(|e| e.to_string() == "Service unavailable: down"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn any_provider_needs_providers() {
+        let result = build(json!({"Any": []})).await;
+        assert!(result.is_err_and(|e| e.to_string()
+            == "Fail to load config: `Any` auth provider needs at least one provider"));
+    }
+}
@@ -0,0 +1,124 @@
+//! Roles of the authenticated clients, which decide the engine requests they are allowed to send.
+
+use ::rucat_common::{anyhow::anyhow, error::RucatError, serde::Deserialize};
+use axum::{
+    extract::{OriginalUri, Request},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+
+use super::Principal;
+use crate::{engine::router::BATCH_GET_PATH, error::RucatServerError};
+
+/// Role of a client, a role is allowed to do everything that the roles before it are allowed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[serde(crate = "rucat_common::serde")]
+pub enum Role {
+    /// List and inspect engines.
+    Viewer,
+    /// Also create engines, and stop, restart, update and delete the engines shared with them, see [rucat_common::engine::EngineInfo::allows].
+    Operator,
+    /// Also change every engine, and delete engines in batches.
+    Admin,
+}
+
+impl Role {
+    /// Role of the clients configured without one, who were allowed to do everything before the roles.
+    pub(crate) fn configured_default() -> Self {
+        Self::Admin
+    }
+
+    /// Role needed to send a request of the `method` to the engine `path`,
+    /// only the safe methods and getting engines in a batch are allowed to viewers.
+    fn required_by(method: &Method, path: &str) -> Self {
+        match *method {
+            Method::GET | Method::HEAD | Method::OPTIONS => Self::Viewer,
+            Method::POST if path.strip_prefix('/') == Some(BATCH_GET_PATH) => Self::Viewer,
+            _ => Self::Operator,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Operator => "operator",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+/// Reject the request with 403 if the authenticated client does not have the role needed by its method.
+/// Requests are not checked if the authentication is disabled, as there is no [Principal].
+pub(crate) async fn authorize(request: Request, next: Next) -> Result<Response, RucatServerError> {
+    // the prefix of the nested router is not in the uri of the request
+    let required = Role::required_by(request.method(), request.uri().path());
+    check_role(request, next, required).await
+}
+
+/// Reject the request with 403 unless the authenticated client is an admin,
+/// e.g. for the requests changing the engines of others in a batch.
+pub(crate) async fn require_admin(
+    request: Request,
+    next: Next,
+) -> Result<Response, RucatServerError> {
+    check_role(request, next, Role::Admin).await
+}
+
+async fn check_role(
+    request: Request,
+    next: Next,
+    required: Role,
+) -> Result<Response, RucatServerError> {
+    if let Some(principal) = request.extensions().get::<Principal>() {
+        if !principal.has_role(required) {
+            // the prefix of the nested router is not in the uri of the request
+            let path = request
+                .extensions()
+                .get::<OriginalUri>()
+                .map_or(request.uri().path(), |uri| uri.path());
+            return Err(RucatError::not_allowed(anyhow!(
+                "`{}` is not allowed to {} {}, which needs the `{}` role",
+                principal.name,
+                request.method(),
+                path,
+                required.as_str()
+            ))
+            .into());
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admin_has_viewer_role() {
+        let admin = Principal::new("alice", [Role::Admin]);
+        let operator = Principal::new("dave", [Role::Operator]);
+        let viewer = Principal::new("bob", [Role::Viewer]);
+        let nobody = Principal::new("carol", []);
+        assert!(admin.has_role(Role::Viewer) && admin.has_role(Role::Admin));
+        assert!(operator.has_role(Role::Viewer) && !operator.has_role(Role::Admin));
+        assert!(viewer.has_role(Role::Viewer) && !viewer.has_role(Role::Operator));
+        assert!(!nobody.has_role(Role::Viewer));
+    }
+
+    #[test]
+    fn only_safe_methods_are_allowed_to_viewers() {
+        for method in [Method::GET, Method::HEAD, Method::OPTIONS] {
+            assert_eq!(Role::required_by(&method, "/123"), Role::Viewer);
+        }
+        for method in [Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            assert_eq!(Role::required_by(&method, "/123"), Role::Operator);
+        }
+        assert_eq!(Role::required_by(&Method::POST, "/batch-get"), Role::Viewer);
+        assert_eq!(
+            Role::required_by(&Method::POST, "/batch-delete"),
+            Role::Operator
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Secrets of the credentials in the config, either in plaintext or hashed.
+
+use ::rucat_common::{
+    anyhow::anyhow,
+    config::SecretValue,
+    error::{Result, RucatError},
+};
+use argon2::{
+    password_hash::{PasswordHash, PasswordVerifier},
+    Argon2,
+};
+use subtle::ConstantTimeEq;
+
+/// Secret that the credentials of a client are checked against.
+#[derive(Clone)]
+pub(crate) enum Secret {
+    Plain(String),
+    /// Argon2 hash in the PHC string format, e.g. `$argon2id$v=19$...`.
+    Argon2(String),
+    /// Bcrypt hash, e.g. `$2b$12$...`.
+    Bcrypt(String),
+}
+
+impl Secret {
+    /// Secret set by exactly one of the plaintext `field` or its hashed form `<field>_hash` in the config,
+    /// each of which is read from its source.
+    /// - `owner`: what the secret belongs to, e.g. user `alice`, only used in the error messages.
+    pub(crate) fn from_config(
+        owner: &str,
+        field: &str,
+        plain: Option<SecretValue>,
+        hash: Option<SecretValue>,
+    ) -> Result<Self> {
+        match (plain, hash) {
+            (Some(plain), None) => Ok(Self::Plain(plain.resolve()?)),
+            (None, Some(hash)) => Self::from_hash(hash.resolve()?).map_err(|e| {
+                RucatError::fail_to_load_config(anyhow!(
+                    "Invalid `{}_hash` of {}: {}",
+                    field,
+                    owner,
+                    e
+                ))
+            }),
+            _ => Err(RucatError::fail_to_load_config(anyhow!(
+                "Exactly one of `{}` and `{}_hash` must be set for {}",
+                field,
+                field,
+                owner
+            ))),
+        }
+    }
+
+    fn from_hash(hash: String) -> ::std::result::Result<Self, String> {
+        if hash.starts_with("$2") {
+            hash.parse::<bcrypt::HashParts>()
+                .map_err(|e| e.to_string())?;
+            return Ok(Self::Bcrypt(hash));
+        }
+        let algorithm = PasswordHash::new(&hash)
+            .map_err(|e| e.to_string())?
+            .algorithm;
+        if algorithm.as_str().starts_with("argon2") {
+            Ok(Self::Argon2(hash))
+        } else {
+            Err(format!(
+                "unsupported algorithm `{}`, expected argon2 or bcrypt",
+                algorithm
+            ))
+        }
+    }
+
+    /// Whether the `input` matches the secret, in a time not depending on how much of it matches.
+    pub(crate) fn verify(&self, input: &str) -> bool {
+        match self {
+            Self::Plain(secret) => secret.as_bytes().ct_eq(input.as_bytes()).into(),
+            Self::Argon2(hash) => PasswordHash::new(hash).is_ok_and(|hash| {
+                Argon2::default()
+                    .verify_password(input.as_bytes(), &hash)
+                    .is_ok()
+            }),
+            Self::Bcrypt(hash) => bcrypt::verify(input, hash).unwrap_or(false),
+        }
+    }
+
+    /// The plaintext of the secret, `None` if it is hashed.
+    pub(crate) fn as_plain(&self) -> Option<&str> {
+        match self {
+            Self::Plain(secret) => Some(secret),
+            Self::Argon2(_) | Self::Bcrypt(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hashes of `secret-password`.
+    const ARGON2_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$cnVjYXRzYWx0cnVjYXQ$KiECDyzdN4ZoX7jpsTt50yFzrOa1oZjU72jP8YtOH4U";
+    const BCRYPT_HASH: &str = "$2b$04$alThWVPxWUvyalThWVOvLePeWHDKa9Ri/Ol92ENSwnX8n6blLGgVi";
+
+    fn from_hash(hash: &str) -> Result<Secret> {
+        Secret::from_config("user `alice`", "password", None, Some(hash.into()))
+    }
+
+    #[test]
+    fn verify_plain_secret() -> Result<()> {
+        let secret = Secret::from_config(
+            "user `alice`",
+            "password",
+            Some("secret-password".into()),
+            None,
+        )?;
+        assert!(secret.verify("secret-password"));
+        assert!(!secret.verify("secret-passwor"));
+        assert!(!secret.verify("secret-password2"));
+        assert_eq!(secret.as_plain(), Some("secret-password"));
+        Ok(())
+    }
+
+    #[test]
+    fn verify_hashed_secrets() -> Result<()> {
+        for hash in [ARGON2_HASH, BCRYPT_HASH] {
+            let secret = from_hash(hash)?;
+            assert!(secret.verify("secret-password"), "{}", hash);
+            assert!(!secret.verify("wrong-password"), "{}", hash);
+            assert!(!secret.verify(""), "{}", hash);
+            assert_eq!(secret.as_plain(), None);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reject_invalid_hashes() {
+        for (hash, message) in [
+            ("$2b$04$short", "Invalid `password_hash` of user `alice`: "),
+            ("plaintext", "Invalid `password_hash` of user `alice`: "),
+            (
+                "$pbkdf2-sha256$i=1000$c2FsdHNhbHQ$aGFzaGhhc2hoYXNoaGFzaA",
+                "Invalid `password_hash` of user `alice`: unsupported algorithm `pbkdf2-sha256`, expected argon2 or bcrypt",
+            ),
+        ] {
+            let error = from_hash(hash).err().unwrap().to_string();
+            assert!(
+                error.starts_with(&format!("Fail to load config: {}", message)),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn exactly_one_of_plain_and_hash() {
+        for (plain, hash) in [
+            (None, None),
+            (Some("secret".into()), Some(BCRYPT_HASH.into())),
+        ] {
+            let error = Secret::from_config("user `alice`", "password", plain, hash)
+                .err()
+                .unwrap();
+            assert_eq!(
+                error.to_string(),
+                "Fail to load config: Exactly one of `password` and `password_hash` must be set for user `alice`"
+            );
+        }
+    }
+}
@@ -1,28 +1,411 @@
-use super::{Authenticate, Credentials};
+use ::std::collections::BTreeSet;
 
+use ::rucat_common::{
+    anyhow::anyhow,
+    config::SecretValue,
+    error::{Result, RucatError},
+    serde::Deserialize,
+};
+
+use super::{role::Role, secret::Secret, Credentials, Principal, SyncAuthenticate};
+
+/// User authenticated by basic auth, with exactly one of `password` and `password_hash`.
+/// Each secret is either literal, or read from a file or an environment variable on startup.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct StaticUser {
+    pub username: String,
+    pub password: Option<SecretValue>,
+    /// Argon2 or bcrypt hash of the password, e.g. `$argon2id$v=19$...` or `$2b$12$...`.
+    pub password_hash: Option<SecretValue>,
+    #[serde(default = "Role::configured_default")]
+    pub role: Role,
+}
+
+/// Bearer token with exactly one of `token` and `token_hash`,
+/// clients authenticated by it are identified by its name.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct NamedBearerToken {
+    pub name: String,
+    pub token: Option<SecretValue>,
+    /// Argon2 or bcrypt hash of the token.
+    pub token_hash: Option<SecretValue>,
+    #[serde(default = "Role::configured_default")]
+    pub role: Role,
+}
+
+/// A single user, whose bearer token is named after the user.
+/// The password and the bearer token are either in plaintext or hashed.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct SingleUserConfig {
+    pub username: String,
+    pub password: Option<SecretValue>,
+    pub password_hash: Option<SecretValue>,
+    pub bearer_token: Option<SecretValue>,
+    pub bearer_token_hash: Option<SecretValue>,
+    /// Role of both the user and the bearer token.
+    #[serde(default = "Role::configured_default")]
+    pub role: Role,
+}
+
+/// Users and bearer tokens of different clients, e.g. one per team.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct MultipleUsersConfig {
+    #[serde(default)]
+    pub users: Vec<StaticUser>,
+    #[serde(default)]
+    pub bearer_tokens: Vec<NamedBearerToken>,
+}
+
+/// Credentials accepted by [StaticAuthProvider], in either shape.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+#[serde(crate = "rucat_common::serde")]
+pub enum StaticAuthConfig {
+    SingleUser(SingleUserConfig),
+    MultipleUsers(MultipleUsersConfig),
+}
+
+/// Authenticate clients by the users and the bearer tokens in the config.
+/// Clients configured without a role are admins.
+#[derive(Clone)]
 pub struct StaticAuthProvider {
-    username: String,
-    password: String,
-    bearer_token: String,
+    /// username, password and role of each user
+    users: Vec<(String, Secret, Role)>,
+    /// name, token and role of each bearer token
+    bearer_tokens: Vec<(String, Secret, Role)>,
 }
 
 impl StaticAuthProvider {
+    /// Provider of a single admin, whose bearer token is named after the user.
     pub fn new(username: String, password: String, bearer_token: String) -> Self {
         Self {
-            username,
-            password,
-            bearer_token,
+            bearer_tokens: vec![(username.clone(), Secret::Plain(bearer_token), Role::Admin)],
+            users: vec![(username, Secret::Plain(password), Role::Admin)],
+        }
+    }
+
+    /// Check there is at least one credential, each secret is set either in plaintext or hashed,
+    /// and the usernames, the token names and the plaintext tokens are unique.
+    pub fn from_config(config: StaticAuthConfig) -> Result<Self> {
+        let MultipleUsersConfig {
+            users,
+            bearer_tokens,
+        } = match config {
+            StaticAuthConfig::SingleUser(SingleUserConfig {
+                username,
+                password,
+                password_hash,
+                bearer_token,
+                bearer_token_hash,
+                role,
+            }) => {
+                let owner = format!("user `{}`", username);
+                return Ok(Self {
+                    users: vec![(
+                        username.clone(),
+                        Secret::from_config(&owner, "password", password, password_hash)?,
+                        role,
+                    )],
+                    bearer_tokens: vec![(
+                        username,
+                        Secret::from_config(
+                            &owner,
+                            "bearer_token",
+                            bearer_token,
+                            bearer_token_hash,
+                        )?,
+                        role,
+                    )],
+                });
+            }
+            StaticAuthConfig::MultipleUsers(config) => config,
+        };
+        if users.is_empty() && bearer_tokens.is_empty() {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "Static authentication needs at least one user or bearer token"
+            )));
+        }
+        check_unique("username", users.iter().map(|user| user.username.as_str()))?;
+        check_unique(
+            "bearer token name",
+            bearer_tokens.iter().map(|token| token.name.as_str()),
+        )?;
+        let users = users
+            .into_iter()
+            .map(|user| {
+                let owner = format!("user `{}`", user.username);
+                let password =
+                    Secret::from_config(&owner, "password", user.password, user.password_hash)?;
+                Ok((user.username, password, user.role))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let bearer_tokens = bearer_tokens
+            .into_iter()
+            .map(|token| {
+                let owner = format!("bearer token `{}`", token.name);
+                let secret = Secret::from_config(&owner, "token", token.token, token.token_hash)?;
+                Ok((token.name, secret, token.role))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // The duplicated token is not printed, as it is a secret.
+        let plain_tokens = bearer_tokens
+            .iter()
+            .filter_map(|(_, token, _)| token.as_plain())
+            .collect::<Vec<_>>();
+        if plain_tokens.iter().collect::<BTreeSet<_>>().len() < plain_tokens.len() {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "Bearer tokens must be unique"
+            )));
         }
+        Ok(Self {
+            users,
+            bearer_tokens,
+        })
     }
 }
 
-impl Authenticate for StaticAuthProvider {
-    fn validate(&self, credentials: &Credentials) -> bool {
+fn check_unique<'a>(field: &str, values: impl Iterator<Item = &'a str>) -> Result<()> {
+    let mut seen = BTreeSet::new();
+    for value in values {
+        if !seen.insert(value) {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "Duplicated {} `{}`",
+                field,
+                value
+            )));
+        }
+    }
+    Ok(())
+}
+
+impl SyncAuthenticate for StaticAuthProvider {
+    fn validate_sync(&self, credentials: &Credentials) -> Option<Principal> {
         match credentials {
-            Credentials::Basic(basic) => {
-                basic.username().eq(&self.username) && basic.password().eq(&self.password)
+            Credentials::Basic(basic) => self
+                .users
+                .iter()
+                .find(|(username, _, _)| username == basic.username())
+                .filter(|(_, password, _)| password.verify(basic.password()))
+                .map(|(username, _, role)| Principal::new(username.clone(), [*role])),
+            // Every token is checked, so that the time does not tell which token is closer to the credential.
+            Credentials::Bearer(bearer) => {
+                self.bearer_tokens
+                    .iter()
+                    .fold(None, |found, (name, token, role)| {
+                        let matched = token.verify(bearer.token());
+                        found.or(matched.then(|| Principal::new(name.clone(), [*role])))
+                    })
             }
-            Credentials::Bearer(bearer) => bearer.token().eq(&self.bearer_token),
+            Credentials::ApiKey(_) | Credentials::ClientCert(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::rucat_common::serde_json::{from_value, json};
+    use axum_extra::headers::authorization::{Basic, Bearer, Credentials as _};
+    use http::HeaderValue;
+
+    use super::*;
+
+    fn basic(username: &str, password: &str) -> Credentials {
+        let header = axum_extra::headers::Authorization::basic(username, password)
+            .0
+            .encode();
+        Credentials::Basic(Basic::decode(&header).unwrap())
+    }
+
+    fn bearer(token: &str) -> Credentials {
+        let header = HeaderValue::from_str(&format!("Bearer {}", token)).unwrap();
+        Credentials::Bearer(Bearer::decode(&header).unwrap())
+    }
+
+    #[test]
+    fn deserialize_single_user() {
+        let config = from_value::<StaticAuthConfig>(json!({
+            "username": "admin",
+            "password": "123",
+            "bearer_token": "abc"
+        }))
+        .unwrap();
+        let provider = StaticAuthProvider::from_config(config).unwrap();
+        assert_eq!(
+            provider.validate_sync(&basic("admin", "123")),
+            Some(Principal::new("admin", [Role::Admin]))
+        );
+        assert_eq!(
+            provider.validate_sync(&bearer("abc")),
+            Some(Principal::new("admin", [Role::Admin]))
+        );
+        assert_eq!(provider.validate_sync(&basic("admin", "abc")), None);
+    }
+
+    #[test]
+    fn validate_multiple_users() {
+        let config = from_value::<StaticAuthConfig>(json!({
+            "users": [
+                {"username": "alice", "password": "pwd-a"},
+                {"username": "bob", "password": "pwd-b"}
+            ],
+            "bearer_tokens": [{"name": "ci", "token": "token-ci"}]
+        }))
+        .unwrap();
+        let provider = StaticAuthProvider::from_config(config).unwrap();
+        assert_eq!(
+            provider.validate_sync(&basic("alice", "pwd-a")),
+            Some(Principal::new("alice", [Role::Admin]))
+        );
+        assert_eq!(
+            provider.validate_sync(&basic("bob", "pwd-b")),
+            Some(Principal::new("bob", [Role::Admin]))
+        );
+        assert_eq!(provider.validate_sync(&basic("alice", "pwd-b")), None);
+        assert_eq!(
+            provider.validate_sync(&bearer("token-ci")),
+            Some(Principal::new("ci", [Role::Admin]))
+        );
+        assert_eq!(provider.validate_sync(&bearer("ci")), None);
+    }
+
+    #[test]
+    fn validate_hashed_credentials() {
+        let config = from_value::<StaticAuthConfig>(json!({
+            "users": [{
+                "username": "alice",
+                // argon2 hash of `secret-password`
+                "password_hash": "$argon2id$v=19$m=19456,t=2,p=1$cnVjYXRzYWx0cnVjYXQ$KiECDyzdN4ZoX7jpsTt50yFzrOa1oZjU72jP8YtOH4U"
+            }],
+            "bearer_tokens": [{
+                "name": "ci",
+                // bcrypt hash of `secret-token`
+                "token_hash": "$2b$04$alThWVPxWUvyalThWVOvLemy6pkkimSm7qdH61nKmj3y5KanncEIS"
+            }]
+        }))
+        .unwrap();
+        let provider = StaticAuthProvider::from_config(config).unwrap();
+        assert_eq!(
+            provider.validate_sync(&basic("alice", "secret-password")),
+            Some(Principal::new("alice", [Role::Admin]))
+        );
+        assert_eq!(
+            provider.validate_sync(&basic("alice", "wrong-password")),
+            None
+        );
+        assert_eq!(
+            provider.validate_sync(&bearer("secret-token")),
+            Some(Principal::new("ci", [Role::Admin]))
+        );
+        assert_eq!(provider.validate_sync(&bearer("wrong-token")), None);
+    }
+
+    #[test]
+    fn reject_invalid_config() {
+        for (config, message) in [
+            (
+                json!({"users": []}),
+                "Static authentication needs at least one user or bearer token",
+            ),
+            (
+                json!({"users": [
+                    {"username": "alice", "password": "a"},
+                    {"username": "alice", "password": "b"}
+                ]}),
+                "Duplicated username `alice`",
+            ),
+            (
+                json!({"bearer_tokens": [
+                    {"name": "ci", "token": "a"},
+                    {"name": "ci", "token": "b"}
+                ]}),
+                "Duplicated bearer token name `ci`",
+            ),
+            (
+                json!({"bearer_tokens": [
+                    {"name": "ci", "token": "a"},
+                    {"name": "cd", "token": "a"}
+                ]}),
+                "Bearer tokens must be unique",
+            ),
+            (
+                json!({"users": [{"username": "alice", "password": "a", "password_hash": "$2b$04$alThWVPxWUvyalThWVOvLemy6pkkimSm7qdH61nKmj3y5KanncEIS"}]}),
+                "Exactly one of `password` and `password_hash` must be set for user `alice`",
+            ),
+            (
+                json!({"bearer_tokens": [{"name": "ci"}]}),
+                "Exactly one of `token` and `token_hash` must be set for bearer token `ci`",
+            ),
+        ] {
+            let config = from_value::<StaticAuthConfig>(config).unwrap();
+            let error = StaticAuthProvider::from_config(config).err().unwrap();
+            assert_eq!(
+                error.to_string(),
+                format!("Fail to load config: {}", message)
+            );
         }
     }
+
+    #[test]
+    fn load_secrets_from_file_and_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("password");
+        ::std::fs::write(&file, "pwd-a\n").unwrap();
+        // The variable is only used by this test, so that it does not race with the other tests.
+        ::std::env::set_var("RUCAT_TEST_STATIC_AUTH_TOKEN", "token-a");
+        let config = from_value::<StaticAuthConfig>(json!({
+            "username": "alice",
+            "password": {"file": file},
+            "bearer_token": {"env": "RUCAT_TEST_STATIC_AUTH_TOKEN"}
+        }))
+        .unwrap();
+        let provider = StaticAuthProvider::from_config(config).unwrap();
+        assert_eq!(
+            provider.validate_sync(&basic("alice", "pwd-a")),
+            Some(Principal::new("alice", [Role::Admin]))
+        );
+        assert_eq!(
+            provider.validate_sync(&bearer("token-a")),
+            Some(Principal::new("alice", [Role::Admin]))
+        );
+    }
+
+    #[test]
+    fn reject_missing_secret_sources() {
+        for (config, message) in [
+            (
+                json!({"users": [{"username": "alice", "password": {"file": "/not/exist/password"}}]}),
+                "Fail to read the secret file `/not/exist/password`: No such file or directory (os error 2)",
+            ),
+            (
+                json!({"bearer_tokens": [{"name": "ci", "token_hash": {"env": "RUCAT_TEST_NOT_SET"}}]}),
+                "Fail to read the secret from the environment variable `RUCAT_TEST_NOT_SET`: environment variable not found",
+            ),
+        ] {
+            let config = from_value::<StaticAuthConfig>(config).unwrap();
+            let error = StaticAuthProvider::from_config(config).err().unwrap();
+            assert_eq!(
+                error.to_string(),
+                format!("Fail to load config: {}", message)
+            );
+        }
+    }
+
+    #[test]
+    fn reject_mixed_config() {
+        let result = from_value::<StaticAuthConfig>(json!({
+            "username": "admin",
+            "password": "123",
+            "bearer_token": "abc",
+            "users": []
+        }));
+        assert!(result.is_err());
+    }
 }
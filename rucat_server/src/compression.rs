@@ -0,0 +1,26 @@
+//! Compression of the responses with gzip or brotli, negotiated by the `Accept-Encoding` header of each request,
+//! e.g. to shorten the engine lists with big configs over slow links.
+
+use axum::Router;
+use tower_http::compression::{
+    predicate::{NotForContentType, Predicate},
+    CompressionLayer, DefaultPredicate,
+};
+
+/// Compress the responses of the `router`, except the small ones and the streamed ones.
+pub(crate) fn compress_responses<S>(router: Router<S>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    // Server-sent events are excluded by the default predicate, and the followed engine logs as plain text,
+    // otherwise they are buffered by the encoder instead of sent as soon as they happen.
+    let predicate = DefaultPredicate::new().and(NotForContentType::const_new("text/plain"));
+    router.layer(
+        CompressionLayer::new()
+            .gzip(true)
+            .br(true)
+            .no_deflate()
+            .no_zstd()
+            .compress_when(predicate),
+    )
+}
@@ -0,0 +1,302 @@
+//! Cross-origin resource sharing (CORS), for browser applications calling the REST API from other origins.
+
+use ::rucat_common::serde::{de, Deserialize, Deserializer};
+use axum::{
+    http::{header, HeaderName, HeaderValue, Method},
+    Router,
+};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+
+/// Wildcard allowing any value.
+const ANY: &str = "*";
+
+/// Values allowed by the CORS configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Allowed<T> {
+    /// Any value, configured as `["*"]`.
+    Any,
+    Only(Vec<T>),
+}
+
+/// Configuration of CORS, requests from the origins not allowed are served without the CORS headers,
+/// so that the browser rejects their responses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsConfig {
+    pub allowed_origins: Allowed<HeaderValue>,
+    pub allowed_methods: Allowed<Method>,
+    pub allowed_headers: Allowed<HeaderName>,
+    /// Whether the browser sends the credentials, e.g. cookies, with the requests.
+    /// It cannot be combined with any wildcard.
+    pub allow_credentials: bool,
+}
+
+impl CorsConfig {
+    /// Methods of the REST API.
+    fn default_allowed_methods() -> Vec<String> {
+        ["GET", "POST", "PUT", "PATCH", "DELETE"]
+            .map(str::to_owned)
+            .to_vec()
+    }
+
+    /// Headers needed by the REST API.
+    fn default_allowed_headers() -> Vec<String> {
+        ["authorization", "content-type", "x-request-id"]
+            .map(str::to_owned)
+            .to_vec()
+    }
+}
+
+/// Parse the allowed values of `field`, `*` must be the only value if it is used.
+fn parse_allowed<T, E, F>(field: &str, values: Vec<String>, parse: F) -> Result<Allowed<T>, E>
+where
+    E: de::Error,
+    F: Fn(&str) -> Option<T>,
+{
+    if values.iter().any(|value| value == ANY) {
+        return if values.len() == 1 {
+            Ok(Allowed::Any)
+        } else {
+            Err(de::Error::custom(format!(
+                "`*` in `{}` must not be combined with other values",
+                field
+            )))
+        };
+    }
+    if values.is_empty() {
+        return Err(de::Error::custom(format!("`{}` must not be empty", field)));
+    }
+    values
+        .iter()
+        .map(|value| {
+            parse(value).ok_or_else(|| {
+                de::Error::custom(format!("Invalid value `{}` in `{}`", value, field))
+            })
+        })
+        .collect::<Result<_, _>>()
+        .map(Allowed::Only)
+}
+
+/// Almost same as the derive macro generated implementation,
+/// except the values are parsed and wildcards are rejected with credentials,
+/// which browsers do not allow.
+impl<'de> Deserialize<'de> for CorsConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        #[serde(crate = "rucat_common::serde")]
+        struct _CorsConfig {
+            allowed_origins: Vec<String>,
+            #[serde(default = "CorsConfig::default_allowed_methods")]
+            allowed_methods: Vec<String>,
+            #[serde(default = "CorsConfig::default_allowed_headers")]
+            allowed_headers: Vec<String>,
+            #[serde(default)]
+            allow_credentials: bool,
+        }
+
+        let _CorsConfig {
+            allowed_origins,
+            allowed_methods,
+            allowed_headers,
+            allow_credentials,
+        } = _CorsConfig::deserialize(deserializer)?;
+        let config = CorsConfig {
+            allowed_origins: parse_allowed("allowed_origins", allowed_origins, |origin| {
+                HeaderValue::from_str(origin).ok()
+            })?,
+            allowed_methods: parse_allowed("allowed_methods", allowed_methods, |method| {
+                Method::from_bytes(method.as_bytes()).ok()
+            })?,
+            allowed_headers: parse_allowed("allowed_headers", allowed_headers, |name| {
+                HeaderName::from_bytes(name.as_bytes()).ok()
+            })?,
+            allow_credentials,
+        };
+        if config.allow_credentials {
+            for (field, is_any) in [
+                ("allowed_origins", config.allowed_origins == Allowed::Any),
+                ("allowed_methods", config.allowed_methods == Allowed::Any),
+                ("allowed_headers", config.allowed_headers == Allowed::Any),
+            ] {
+                if is_any {
+                    return Err(de::Error::custom(format!(
+                        "`*` in `{}` cannot be combined with `allow_credentials`",
+                        field
+                    )));
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Layer answering the CORS preflight requests and adding the CORS headers to the responses.
+/// The request id is exposed, so that it can be reported by the browser application.
+fn get_cors_layer(config: CorsConfig) -> CorsLayer {
+    let origins = match config.allowed_origins {
+        Allowed::Any => AllowOrigin::any(),
+        Allowed::Only(origins) => AllowOrigin::list(origins),
+    };
+    let methods = match config.allowed_methods {
+        Allowed::Any => AllowMethods::any(),
+        Allowed::Only(methods) => AllowMethods::list(methods),
+    };
+    let headers = match config.allowed_headers {
+        Allowed::Any => AllowHeaders::any(),
+        Allowed::Only(headers) => AllowHeaders::list(headers),
+    };
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .allow_credentials(config.allow_credentials)
+        .expose_headers([
+            HeaderName::from_static("x-request-id"),
+            header::WARNING,
+            header::ETAG,
+        ])
+}
+
+/// Apply CORS to all the routes of the `server`.
+/// It is outside of the authentication, because browsers send the preflight requests without credentials.
+pub fn allow_cors(server: Router, config: CorsConfig) -> Router {
+    server.layer(get_cors_layer(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use ::rucat_common::serde_json::{from_value, json};
+
+    use super::*;
+
+    #[test]
+    fn deserialize_default_cors() {
+        let config = from_value::<CorsConfig>(json!({
+            "allowed_origins": ["https://dashboard.example.com"]
+        }))
+        .unwrap();
+        assert_eq!(
+            config,
+            CorsConfig {
+                allowed_origins: Allowed::Only(vec![HeaderValue::from_static(
+                    "https://dashboard.example.com"
+                )]),
+                allowed_methods: Allowed::Only(vec![
+                    Method::GET,
+                    Method::POST,
+                    Method::PUT,
+                    Method::PATCH,
+                    Method::DELETE
+                ]),
+                allowed_headers: Allowed::Only(vec![
+                    header::AUTHORIZATION,
+                    header::CONTENT_TYPE,
+                    HeaderName::from_static("x-request-id")
+                ]),
+                allow_credentials: false,
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_wildcards() {
+        let config = from_value::<CorsConfig>(json!({
+            "allowed_origins": ["*"],
+            "allowed_methods": ["*"],
+            "allowed_headers": ["*"]
+        }))
+        .unwrap();
+        assert_eq!(config.allowed_origins, Allowed::Any);
+        assert_eq!(config.allowed_methods, Allowed::Any);
+        assert_eq!(config.allowed_headers, Allowed::Any);
+    }
+
+    #[test]
+    fn credentials_with_listed_values() {
+        let config = from_value::<CorsConfig>(json!({
+            "allowed_origins": ["https://a.example.com", "https://b.example.com"],
+            "allowed_methods": ["GET"],
+            "allow_credentials": true
+        }))
+        .unwrap();
+        assert!(config.allow_credentials);
+        assert_eq!(config.allowed_methods, Allowed::Only(vec![Method::GET]));
+        // tower-http panics on wildcards with credentials
+        let _layer = get_cors_layer(config);
+    }
+
+    #[test]
+    fn reject_wildcards_with_credentials() {
+        for (field, other) in [
+            ("allowed_origins", None),
+            ("allowed_methods", Some("allowed_origins")),
+            ("allowed_headers", Some("allowed_origins")),
+        ] {
+            let mut config = json!({
+                "allowed_origins": ["https://dashboard.example.com"],
+                "allow_credentials": true
+            });
+            config[field] = json!(["*"]);
+            if let Some(other) = other {
+                config[other] = json!(["https://dashboard.example.com"]);
+            }
+            let result = from_value::<CorsConfig>(config);
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                format!(
+                    "`*` in `{}` cannot be combined with `allow_credentials`",
+                    field
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn reject_invalid_values() {
+        let result = from_value::<CorsConfig>(json!({
+            "allowed_origins": ["*", "https://dashboard.example.com"]
+        }));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "`*` in `allowed_origins` must not be combined with other values"
+        );
+
+        let result = from_value::<CorsConfig>(json!({
+            "allowed_origins": []
+        }));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "`allowed_origins` must not be empty"
+        );
+
+        let result = from_value::<CorsConfig>(json!({
+            "allowed_origins": ["https://dashboard.example.com"],
+            "allowed_methods": ["GET POST"]
+        }));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid value `GET POST` in `allowed_methods`"
+        );
+
+        let result = from_value::<CorsConfig>(json!({
+            "allowed_origins": ["https://dashboard.example.com"],
+            "allowed_headers": ["bad header"]
+        }));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid value `bad header` in `allowed_headers`"
+        );
+    }
+
+    #[test]
+    fn missing_allowed_origins() {
+        let result = from_value::<CorsConfig>(json!({}));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "missing field `allowed_origins`"
+        );
+    }
+}
@@ -1,126 +1,577 @@
 //! Restful API for engine management.
 
-use ::std::time::SystemTime;
+use ::std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use ::futures::{stream, Stream, StreamExt, TryStreamExt};
 
 use ::rucat_common::{
     anyhow::anyhow,
-    database::{Database, UpdateEngineStateResult},
+    blob_store::{delete_engine_blobs, engine_blob_key, BlobRef, BlobStore, LOG_TAIL_BLOB},
+    database::{
+        Database, EngineFilter, EngineInfoUpdate, EngineSort, IdempotencyKey, RemoveEngineResult,
+        UpdateEngineStateResult,
+    },
     engine::{
-        CreateEngineRequest, EngineId, EngineInfo,
+        get_spark_connect_url, get_spark_service_host, get_spark_ui_url, validate_engine_config,
+        validate_engine_name, AdoptEngineRequest, BatchDeleteEnginesRequest,
+        BatchDeleteEnginesResponse, BatchGetEngineResult, BatchGetEnginesRequest,
+        BatchGetEnginesResponse, CleanupEnginesRequest, CleanupEnginesResponse,
+        CreateEngineRequest, CreateEngineResponse, DeleteEngineResult, EngineConfig,
+        EngineConnectInfo, EngineFeature, EngineHistoryResponse, EngineId, EngineInfo,
+        EngineResourceEventsResponse,
         EngineState::{self, *},
+        EngineStateKind, EngineStateResponse, EngineStatsResponse, EngineTime,
+        ForceDeleteEngineResponse, ListEnginesResponse, RenameEngineRequest, SetEngineAclRequest,
     },
     error::RucatError,
-    tracing::info,
+    serde::Deserialize,
+    tokio,
+    tracing::{info, warn},
 };
 use axum::{
-    extract::{Path, State},
-    routing::{get, post},
-    Json, Router,
+    body::Body,
+    extract::State,
+    http::{
+        header::{CONTENT_TYPE, ETAG, IF_MATCH, WARNING},
+        HeaderMap, HeaderName, HeaderValue,
+    },
+    middleware,
+    response::{
+        sse::{Event, KeepAlive},
+        AppendHeaders, IntoResponse, Response, Sse,
+    },
+    routing::{get, patch, post, put},
+    Extension, Json,
 };
 
-use crate::{error::RucatServerError, state::AppState};
+use crate::{
+    authentication::{
+        role::{require_admin, Role},
+        Principal,
+    },
+    error::RucatServerError,
+    extract::{AppJson, AppPath, AppQuery},
+    metrics::EngineOperation,
+    project::Project,
+    resource_inspector::{LogOptions, ResourceInspector},
+    route_index::IndexedRouter,
+    state::AppState,
+};
 
 type Result<T> = std::result::Result<T, RucatServerError>;
 
+/// `Warning` header carrying the message, with the code of miscellaneous persistent warnings.
+/// Messages that cannot be put in a header are only returned in the response body.
+fn get_warning_header(message: &str) -> Option<HeaderValue> {
+    let quoted = message.replace('\\', "\\\\").replace('"', "\\\"");
+    HeaderValue::from_str(&format!("299 - \"{}\"", quoted)).ok()
+}
+
+/// Header of the key identifying the request creating an engine, see [crate::IdempotencyKeys].
+const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+/// Maximum number of characters in an idempotency key.
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// Get the idempotency key of the request, `None` if the header is not set.
+fn get_idempotency_key(headers: &HeaderMap) -> Result<Option<String>> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY) else {
+        return Ok(None);
+    };
+    let key = value.to_str().map_err(|_| {
+        RucatError::invalid_request(anyhow!(
+            "`Idempotency-Key` must only contain visible ASCII characters"
+        ))
+    })?;
+    if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+        return Err(RucatError::invalid_request(anyhow!(
+            "`Idempotency-Key` must have 1 to {} characters",
+            MAX_IDEMPOTENCY_KEY_LENGTH
+        ))
+        .into());
+    }
+    Ok(Some(key.to_owned()))
+}
+
+/// Get the revision of the engine that the request expects from the `If-Match` header,
+/// so that the engine is only updated if nobody else updated it since the client read it.
+/// `None` if the header is not set or is `*`, which matches any revision.
+fn get_expected_revision(headers: &HeaderMap) -> Result<Option<u64>> {
+    let Some(value) = headers.get(IF_MATCH) else {
+        return Ok(None);
+    };
+    let invalid = || {
+        RucatError::invalid_request(anyhow!(
+            "`If-Match` must be `*` or the `ETag` of the engine, e.g. `\"3\"`, but got {:?}",
+            value
+        ))
+    };
+    let value = value.to_str().map_err(|_| invalid())?.trim();
+    if value == "*" {
+        return Ok(None);
+    }
+    let revision = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .and_then(|revision| revision.parse().ok())
+        .ok_or_else(invalid)?;
+    Ok(Some(revision))
+}
+
+/// `ETag` of the engine at the `revision`, see [EngineInfo::revision].
+fn get_etag(revision: u64) -> HeaderValue {
+    // Use `unwrap` because digits in quotes are always a valid header value.
+    HeaderValue::from_str(&format!("\"{}\"", revision)).unwrap()
+}
+
 /// start an engine with the given configuration
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/engine",
+        request_body = CreateEngineRequest,
+        params(("Idempotency-Key" = Option<String>, Header, description = "Key of the request, retries with the same key return the engine created by the first request")),
+        responses(
+            (status = 200, description = "Engine is created and waits to start, with warnings e.g. the version is deprecated", body = CreateEngineResponse),
+            (status = 400, description = "Invalid `Idempotency-Key`", body = crate::error::ErrorResponse),
+            (status = 403, description = "Engine version is blocked", body = crate::error::ErrorResponse),
+            (status = 422, description = "Invalid request body, e.g. a too long name, an unsupported version or a reserved config", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
 async fn create_engine<DB>(
     State(state): State<AppState<DB>>,
-    Json(body): Json<CreateEngineRequest>,
-) -> Result<Json<EngineId>>
+    project: Project,
+    principal: Principal,
+    headers: HeaderMap,
+    AppJson(body): AppJson<CreateEngineRequest>,
+) -> Result<Response>
 where
     DB: Database,
 {
-    let id = state
+    // Keys of different projects do not collide.
+    let idempotency_key = get_idempotency_key(&headers)?.map(|key| match project.as_deref() {
+        Some(project) => format!("{}/{}", project, key),
+        None => key,
+    });
+    body.validate(
+        state.get_engine_limits().max_name_length,
+        state.get_engine_versions(),
+    )?;
+    if let Some(features) = &body.features {
+        EngineFeature::validate(features)?;
+    }
+    let warnings = Vec::from_iter(
+        state
+            .get_engine_versions()
+            .check(&body.engine_type, &body.version)?,
+    );
+    let id = state.get_id_generator().generate();
+    let now = state.get_clock().now();
+    let mut info = EngineInfo::from_create_request(body, EngineTime::from_system_time(now))?;
+    info.created_by = (!principal.is_anonymous()).then(|| principal.name.clone());
+    info.project = project.0;
+    let idempotency_key = idempotency_key.map(|key| IdempotencyKey {
+        key,
+        expire_time: now + state.get_idempotency_keys().get_ttl(),
+    });
+    // Engines not to be started are ignored by the state monitor until they are restarted.
+    let paused = info.state == Terminated;
+    let next_update_time = (!paused).then_some(now);
+    let added = state
         .get_db()
-        .add_engine(body, Some(SystemTime::now()))
+        .add_engine(&id, info, next_update_time, idempotency_key)
         .await?;
-    info!("Creating engine {}, wait to start", id);
-    Ok(Json(id))
+    if added == id {
+        state
+            .get_metrics()
+            .record_engine_operation(EngineOperation::Create);
+        if paused {
+            info!("Created engine {} by {} without starting it", id, principal);
+        } else {
+            info!("Creating engine {} by {}, wait to start", id, principal);
+        }
+    } else {
+        info!(
+            "Engine {} was created by a request with the same idempotency key",
+            added
+        );
+    }
+    let id = added;
+    let headers = warnings
+        .iter()
+        .filter_map(|warning| get_warning_header(warning))
+        .map(|value| (WARNING, value))
+        .collect::<Vec<_>>();
+    Ok((
+        AppendHeaders(headers),
+        Json(CreateEngineResponse { id, warnings }),
+    )
+        .into_response())
 }
 
-async fn delete_engine<DB>(
-    Path(id): Path<EngineId>,
+/// Adopt an engine deployed without rucat, e.g. a Spark connect pod deployed by Helm.
+/// The engine is added in `Running` state, and its resources are labelled with the engine id.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/engine/adopt",
+        request_body = AdoptEngineRequest,
+        responses(
+            (status = 200, description = "Engine is adopted in `Running` state", body = EngineId),
+            (status = 403, description = "Client is not an admin, or the engine id is reserved", body = crate::error::ErrorResponse),
+            (status = 404, description = "Adoption is disabled, or the pod is not found", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn adopt_engine<DB, RI>(
     State(state): State<AppState<DB>>,
-) -> Result<()>
+    project: Project,
+    Extension(inspector): Extension<Arc<RI>>,
+    AppJson(body): AppJson<AdoptEngineRequest>,
+) -> Result<Json<EngineId>>
 where
     DB: Database,
+    RI: ResourceInspector,
 {
+    let id = match body.id.clone() {
+        Some(id) => EngineId::try_from(id)?,
+        None => state.get_id_generator().generate(),
+    };
+    if RESERVED_IDS.contains(&id.to_string().as_str()) {
+        return Err(RucatError::not_allowed(anyhow!("Engine id `{}` is reserved", id)).into());
+    }
+    let mut info = inspector.inspect(&body).await?;
+    info.project = project.0;
     let db_client = state.get_db();
-    let mut current_state = get_engine_state(&id, db_client).await?;
+    db_client
+        .add_engine(&id, info, Some(state.get_clock().now()), None)
+        .await?;
+    if let Err(e) = inspector.label(&body, &id).await {
+        // The state monitor cannot find the resources without the label.
+        if let Err(remove_error) = db_client
+            .remove_engine(&id, &Running, None, state.get_clock().now())
+            .await
+        {
+            warn!("Failed to remove engine {}: {}", id, remove_error);
+        }
+        return Err(e.into());
+    }
+    info!(
+        "Adopted pod {}/{} as engine {}",
+        body.namespace, body.pod, id
+    );
+    Ok(Json(id))
+}
 
-    loop {
-        match current_state {
-            s @ (WaitToStart | Terminated | ErrorClean(_)) => {
-                let response = db_client
-                    .remove_engine(&id, &s)
-                    .await?
-                    .ok_or_else(|| RucatError::engine_not_found(&id))?;
-                match response {
-                    UpdateEngineStateResult::Success => {
-                        info!("Engine {} is in {:?} state, delete it", id, s);
-                        return Ok(());
-                    }
-                    UpdateEngineStateResult::Fail {
-                        current_state: actual_state,
-                    } => {
-                        info!(
-                            "Engine {} has been updated from {:?} to {:?}, retry to delete",
-                            id, s, actual_state
-                        );
-                        current_state = actual_state;
-                    }
-                }
-            }
-            other => {
-                return Err(RucatError::not_allowed(anyhow!(
-                    "Engine {} is in {:?} state, cannot be deleted",
-                    id,
-                    other
-                ))
-                .into())
-            }
+/// Read the logs of the engine driver as plain text, e.g. to debug why the engine failed without access to Kubernetes.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/engine/{id}/logs",
+        params(
+            ("id" = String, Path, description = "Engine id"),
+            LogOptions
+        ),
+        responses(
+            (status = 200, description = "Lines of the driver logs, streamed until the engine stops if `follow` is set. The archived tail of the logs if the driver is gone", content_type = "text/plain", body = String),
+            (status = 404, description = "Engine is not found, its driver is gone without archived logs, or the logs are disabled", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn get_engine_logs<DB, RI>(
+    AppPath(id): AppPath<EngineId>,
+    AppQuery(options): AppQuery<LogOptions>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+    Extension(inspector): Extension<Arc<RI>>,
+) -> Result<Response>
+where
+    DB: Database,
+    RI: ResourceInspector,
+{
+    project.check_engine(&id, state.get_db()).await?;
+    let current_state = state
+        .get_db()
+        .get_engine_state(&id)
+        .await?
+        .ok_or_else(|| RucatError::engine_not_found(&id))?;
+    let Some(logs) = inspector.get_logs(&id, project.as_deref(), options).await? else {
+        return get_archived_engine_logs(&id, &state, &current_state, options).await;
+    };
+    Ok((
+        [(CONTENT_TYPE, "text/plain; charset=utf-8")],
+        Body::from_stream(logs),
+    )
+        .into_response())
+}
+
+/// Read the tail of the driver logs archived by [archive_engine_logs], for the engine whose driver is gone.
+async fn get_archived_engine_logs<DB>(
+    id: &EngineId,
+    state: &AppState<DB>,
+    current_state: &EngineState,
+    options: LogOptions,
+) -> Result<Response>
+where
+    DB: Database,
+{
+    let driver_gone = || {
+        RucatError::not_found(anyhow!(
+            "Engine {} exists in {:?} state but its driver pod is gone",
+            id,
+            current_state
+        ))
+        .with_engine_id(id)
+    };
+    let blob_store = state.get_blob_store().ok_or_else(driver_gone)?;
+    let blob = state
+        .get_db()
+        .get_engine(id)
+        .await?
+        .and_then(|mut info| info.blobs.remove(LOG_TAIL_BLOB))
+        .ok_or_else(driver_gone)?;
+    let mut logs = vec![];
+    blob_store.get(&blob, &mut logs).await?;
+    let logs = String::from_utf8_lossy(&logs);
+    let lines = logs.split_inclusive('\n');
+    let skipped = options.tail_lines.map_or(0, |tail_lines| {
+        lines
+            .clone()
+            .count()
+            .saturating_sub(usize::try_from(tail_lines).unwrap_or(0))
+    });
+    Ok((
+        [(CONTENT_TYPE, "text/plain; charset=utf-8")],
+        lines.skip(skipped).collect::<String>(),
+    )
+        .into_response())
+}
+
+/// Number of the last lines of the driver logs archived by [archive_engine_logs].
+const ARCHIVED_LOG_TAIL_LINES: i64 = 1000;
+
+/// Archive the tail of the driver logs into the blob store, e.g. before stopping the engine,
+/// so that it can still be read by `GET /engine/{id}/logs` after the driver is gone.
+/// The archived tail is overwritten by archiving again, and deleted with the engine.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/engine/{id}/logs/archive",
+        params(
+            ("id" = String, Path, description = "Engine id"),
+        ),
+        responses(
+            (status = 200, description = "Reference to the archived logs, which is also in the `blobs` of the engine", body = BlobRef),
+            (status = 404, description = "Engine is not found, its driver is gone, or the blob store is not configured", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn archive_engine_logs<DB, RI>(
+    AppPath(id): AppPath<EngineId>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+    principal: Principal,
+    Extension(inspector): Extension<Arc<RI>>,
+) -> Result<Json<BlobRef>>
+where
+    DB: Database,
+    RI: ResourceInspector,
+{
+    let blob_store = state.get_blob_store().ok_or_else(|| {
+        RucatError::not_found(anyhow!(
+            "Engine logs cannot be archived without the blob store"
+        ))
+    })?;
+    let info = get_changeable_engine(&id, state.get_db(), &project, &principal).await?;
+    let options = LogOptions {
+        tail_lines: Some(ARCHIVED_LOG_TAIL_LINES),
+        follow: false,
+    };
+    let logs: String = inspector
+        .get_logs(&id, project.as_deref(), options)
+        .await?
+        .ok_or_else(|| {
+            RucatError::not_found(anyhow!(
+                "Engine {} exists in {:?} state but its driver pod is gone",
+                id,
+                info.state
+            ))
+            .with_engine_id(&id)
+        })?
+        .try_collect()
+        .await
+        .map_err(RucatError::fail_to_read_logs)?;
+    let blob = blob_store
+        .put(&engine_blob_key(&id, LOG_TAIL_BLOB), logs.as_bytes())
+        .await?;
+    let update = EngineInfoUpdate {
+        blobs: Some(BTreeMap::from([(LOG_TAIL_BLOB.to_owned(), blob.clone())])),
+        ..Default::default()
+    };
+    if state
+        .get_db()
+        .update_engine_info(&id, update, None, None)
+        .await?
+        .is_none()
+    {
+        // The engine is deleted meanwhile, with all its blobs except the new one.
+        if let Err(e) = blob_store.delete(&blob.key).await {
+            warn!("Failed to delete the archived logs of engine {}: {}", id, e);
         }
+        return Err(RucatError::engine_not_found(&id).into());
     }
+    info!("Archive {} bytes of the logs of engine {}", blob.size, id);
+    Ok(Json(blob))
 }
 
-/// Stop an engine to release resources. But engine info is still kept in the data store.
-async fn stop_engine<DB>(Path(id): Path<EngineId>, State(state): State<AppState<DB>>) -> Result<()>
+/// List the recent Kubernetes events of the engine resources, e.g. why the driver pod cannot be scheduled.
+/// `GET /engine/{id}/events` streams the engine states instead.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/engine/{id}/k8s-events",
+        params(("id" = String, Path, description = "Engine id")),
+        responses(
+            (status = 200, description = "Events of the driver pod and the service, the most recent first", body = EngineResourceEventsResponse),
+            (status = 404, description = "Engine is not found, or the diagnostics are disabled", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn get_engine_resource_events<DB, RI>(
+    AppPath(id): AppPath<EngineId>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+    Extension(inspector): Extension<Arc<RI>>,
+) -> Result<Json<EngineResourceEventsResponse>>
+where
+    DB: Database,
+    RI: ResourceInspector,
+{
+    project.check_engine(&id, state.get_db()).await?;
+    state
+        .get_db()
+        .get_engine_state(&id)
+        .await?
+        .ok_or_else(|| RucatError::engine_not_found(&id))?;
+    let events = inspector.get_events(&id, project.as_deref()).await?;
+    Ok(Json(EngineResourceEventsResponse { events }))
+}
+
+/// Whether the engine can be deleted without force, i.e. it has no resource to clean.
+fn is_deletable(state: &EngineState) -> bool {
+    matches!(state, WaitToStart | Terminated | ErrorClean(_))
+}
+
+/// Outcome of [try_delete_engine].
+enum Deletion {
+    /// The engine is deleted in `state`.
+    Deleted {
+        state: EngineState,
+    },
+    NotFound,
+    NotAllowed {
+        state: EngineState,
+    },
+}
+
+impl From<Deletion> for DeleteEngineResult {
+    fn from(deletion: Deletion) -> Self {
+        match deletion {
+            Deletion::Deleted { .. } => DeleteEngineResult::Deleted,
+            Deletion::NotFound => DeleteEngineResult::NotFound,
+            Deletion::NotAllowed { state } => DeleteEngineResult::NotAllowed { state },
+        }
+    }
+}
+
+/// Delete the engine if it is not started yet, stopped or cleaned, or in any state if `force` is set,
+/// retrying if its state is changed concurrently.
+/// Fail with 412 if the engine is not at the `expected_revision`, or with 403 if the `principal` is not allowed to change it.
+/// Engines not in the `project` are not found.
+async fn try_delete_engine<DB>(
+    id: &EngineId,
+    state: &AppState<DB>,
+    project: &Project,
+    principal: &Principal,
+    force: bool,
+    expected_revision: Option<u64>,
+) -> Result<Deletion>
 where
     DB: Database,
 {
     let db_client = state.get_db();
-    let mut current_state = get_engine_state(&id, db_client).await?;
+    let Some(info) = db_client
+        .get_engine(id)
+        .await?
+        .filter(|info| project.contains(info))
+    else {
+        return Ok(Deletion::NotFound);
+    };
+    check_allowed(id, &info, principal)?;
+    let mut current_state = info.state;
 
     loop {
-        let (new_state, next_update_time) = match current_state {
-            WaitToStart => (Terminated, None),
-            StartInProgress | Running => (WaitToTerminate, Some(SystemTime::now())),
-            other => {
-                return Err(RucatError::not_allowed(anyhow!(
-                    "Engine {} is in {:?} state, cannot be stopped",
-                    id,
-                    other
-                ))
-                .into())
-            }
-        };
-        let response = db_client
-            .update_engine_state(&id, &current_state, &new_state, next_update_time)
+        if !force && !is_deletable(&current_state) {
+            return Ok(Deletion::NotAllowed {
+                state: current_state,
+            });
+        }
+        let Some(response) = db_client
+            .remove_engine(
+                id,
+                &current_state,
+                expected_revision,
+                state.get_clock().now(),
+            )
             .await?
-            .ok_or_else(|| RucatError::engine_not_found(&id))?;
+        else {
+            return Ok(Deletion::NotFound);
+        };
         match response {
-            UpdateEngineStateResult::Success => {
-                info!(
-                    "Update Engine {} from {:?} to {:?}",
-                    id, current_state, new_state
-                );
-                return Ok(());
+            RemoveEngineResult::Success(removed) => {
+                if is_deletable(&current_state) {
+                    info!(
+                        "Engine {} ({}, {:?}) is in {:?} state, delete it",
+                        id, removed.name, removed.engine_type, current_state
+                    );
+                } else {
+                    warn!(
+                        "Force delete engine {} ({}, {:?}) in {:?} state, its resources may be orphaned",
+                        id, removed.name, removed.engine_type, current_state
+                    );
+                }
+                state
+                    .get_metrics()
+                    .record_engine_operation(EngineOperation::Delete);
+                if let Some(blob_store) = state.get_blob_store() {
+                    // The engine is already deleted, failing to delete its blobs only leaves garbage.
+                    if let Err(e) = delete_engine_blobs(blob_store, id).await {
+                        warn!("Failed to delete blobs of engine {}: {}", id, e);
+                    }
+                }
+                return Ok(Deletion::Deleted {
+                    state: current_state,
+                });
             }
-            UpdateEngineStateResult::Fail {
+            RemoveEngineResult::Fail {
                 current_state: actual_state,
             } => {
                 info!(
-                    "Engine {} has been updated from {:?} to {:?}, retry to stop",
+                    "Engine {} has been updated from {:?} to {:?}, retry to delete",
                     id, current_state, actual_state
                 );
                 current_state = actual_state;
@@ -129,103 +580,1393 @@ where
     }
 }
 
-/// Restart a stopped engine with the same configuration.
-async fn restart_engine<DB>(
-    Path(id): Path<EngineId>,
-    State(state): State<AppState<DB>>,
+/// Stop the engine if it is running, wait until its resources are cleaned, then delete it.
+/// The state is polled without holding any transaction, so the engine can be updated by others meanwhile.
+async fn delete_engine_and_wait<DB>(
+    id: &EngineId,
+    state: &AppState<DB>,
+    project: &Project,
+    principal: &Principal,
+    timeout: Duration,
 ) -> Result<()>
 where
     DB: Database,
 {
     let db_client = state.get_db();
-    let mut current_state = get_engine_state(&id, db_client).await?;
+    check_engine_access(id, db_client, project, principal).await?;
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut current_state = db_client
+        .get_engine_state(id)
+        .await?
+        .ok_or_else(|| RucatError::engine_not_found(id))?;
 
     loop {
-        let new_state = match current_state {
-            WaitToTerminate => Running,
-            Terminated => WaitToStart,
-            other => {
-                return Err(RucatError::not_allowed(anyhow!(
-                    "Engine {} is in {:?} state, cannot be restarted",
+        if is_deletable(&current_state) {
+            match try_delete_engine(id, state, project, principal, false, None).await? {
+                Deletion::Deleted { .. } | Deletion::NotFound => return Ok(()),
+                Deletion::NotAllowed {
+                    state: actual_state,
+                } => {
+                    current_state = actual_state;
+                    continue;
+                }
+            }
+        }
+        if matches!(current_state, StartInProgress | Running) {
+            let Some(response) = db_client
+                .update_engine_state(
                     id,
-                    other
+                    &current_state,
+                    &WaitToTerminate,
+                    Some(state.get_clock().now()),
+                    None,
+                    principal.get_engine_actor().as_ref(),
+                )
+                .await?
+            else {
+                return Ok(());
+            };
+            match response {
+                UpdateEngineStateResult::Success => {
+                    info!(
+                        "Stop engine {} in {:?} state before deleting it",
+                        id, current_state
+                    );
+                    state
+                        .get_metrics()
+                        .record_engine_operation(EngineOperation::Stop);
+                    state.notify_state_change(id, &current_state, &WaitToTerminate);
+                    current_state = WaitToTerminate;
+                }
+                UpdateEngineStateResult::Fail {
+                    current_state: actual_state,
+                } => {
+                    current_state = actual_state;
+                    continue;
+                }
+            }
+        }
+
+        let now = tokio::time::Instant::now();
+        if now >= deadline {
+            return Err(RucatError::timeout(anyhow!(
+                "Engine {} is still in {:?} state after waiting {} seconds to delete it",
+                id,
+                current_state,
+                timeout.as_secs()
+            ))
+            .with_engine_id(id)
+            .into());
+        }
+        let poll_interval = state.get_engine_events().get_poll_interval();
+        tokio::time::sleep(poll_interval.min(deadline - now)).await;
+        match db_client.get_engine_state(id).await? {
+            Some(actual_state) => current_state = actual_state,
+            // deleted by others
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Default seconds of waiting for the engine to be deleted with `wait`.
+const DEFAULT_DELETE_WAIT_SECS: u64 = 60;
+/// Maximum seconds of waiting for the engine to be deleted with `wait`.
+const MAX_DELETE_WAIT_SECS: u64 = 600;
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(crate = "rucat_common::serde")]
+struct DeleteEngineQuery {
+    /// Delete the engine in any state without cleaning its resources,
+    /// e.g. when the engine is stuck because its cluster is gone.
+    #[serde(default)]
+    force: bool,
+    /// Stop the engine if it is running and respond after its resources are cleaned and it is deleted,
+    /// e.g. to recreate an engine right after deleting it.
+    #[serde(default)]
+    wait: bool,
+    /// Seconds to wait with `wait`, 60 by default and at most 600.
+    timeout_secs: Option<u64>,
+}
+
+impl DeleteEngineQuery {
+    /// Get the time to wait for the deletion, `None` if `wait` is not set.
+    fn get_wait_timeout(&self) -> Result<Option<Duration>> {
+        if !self.wait {
+            if self.timeout_secs.is_some() {
+                return Err(RucatError::invalid_request(anyhow!(
+                    "`timeout_secs` can only be set with `wait`"
                 ))
-                .into())
+                .into());
             }
-        };
+            return Ok(None);
+        }
+        if self.force {
+            return Err(RucatError::invalid_request(anyhow!(
+                "`force` and `wait` cannot be set together"
+            ))
+            .into());
+        }
+        let timeout_secs = self.timeout_secs.unwrap_or(DEFAULT_DELETE_WAIT_SECS);
+        if !(1..=MAX_DELETE_WAIT_SECS).contains(&timeout_secs) {
+            return Err(RucatError::invalid_argument(anyhow!(
+                "`timeout_secs` must be between 1 and {}, but got {}",
+                MAX_DELETE_WAIT_SECS,
+                timeout_secs
+            ))
+            .into());
+        }
+        Ok(Some(Duration::from_secs(timeout_secs)))
+    }
+}
+
+/// Return an empty body, or a [ForceDeleteEngineResponse] if `force` is set,
+/// telling whether the cleanup of the engine resources was skipped.
+/// With `wait`, the engine in any state is stopped and deleted after its resources are cleaned.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        delete,
+        path = "/engine/{id}",
+        params(
+            ("id" = String, Path, description = "Engine id"),
+            DeleteEngineQuery,
+            ("If-Match" = Option<String>, Header, description = "`ETag` of the engine, the request fails with 412 if the engine is updated since then")
+        ),
+        responses(
+            (status = 200, description = "Engine is deleted, with a body only if `force` is set", body = ForceDeleteEngineResponse),
+            (status = 400, description = "Invalid combination of the query parameters, or invalid `If-Match`", body = crate::error::ErrorResponse),
+            (status = 403, description = "Engine cannot be deleted in its state without `force` or `wait`", body = crate::error::ErrorResponse),
+            (status = 404, description = "Engine is not found", body = crate::error::ErrorResponse),
+            (status = 422, description = "`timeout_secs` is out of range", body = crate::error::ErrorResponse),
+            (status = 504, description = "Engine is not deleted before the timeout of `wait`", body = crate::error::ErrorResponse),
+            (status = 412, description = "Engine is not at the revision of `If-Match`", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn delete_engine<DB>(
+    AppPath(id): AppPath<EngineId>,
+    AppQuery(query): AppQuery<DeleteEngineQuery>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+    principal: Principal,
+    headers: HeaderMap,
+) -> Result<Response>
+where
+    DB: Database,
+{
+    let expected_revision = get_expected_revision(&headers)?;
+    if let Some(timeout) = query.get_wait_timeout()? {
+        // The engine is updated by the state monitor while waiting, so its revision cannot be kept.
+        if expected_revision.is_some() {
+            return Err(RucatError::invalid_request(anyhow!(
+                "`If-Match` cannot be set together with `wait`"
+            ))
+            .into());
+        }
+        delete_engine_and_wait(&id, &state, &project, &principal, timeout).await?;
+        info!("Deleted engine {} by {}", id, principal);
+        return Ok(().into_response());
+    }
+    let force = query.force;
+    let deletion =
+        try_delete_engine(&id, &state, &project, &principal, force, expected_revision).await?;
+    if let Deletion::Deleted { .. } = deletion {
+        info!("Deleted engine {} by {}", id, principal);
+    }
+    match deletion {
+        Deletion::Deleted { state } if force => Ok(Json(ForceDeleteEngineResponse {
+            forced: !is_deletable(&state),
+            state,
+        })
+        .into_response()),
+        Deletion::Deleted { .. } => Ok(().into_response()),
+        Deletion::NotFound => Err(RucatError::engine_not_found(&id).into()),
+        Deletion::NotAllowed { state } => Err(RucatError::not_allowed(anyhow!(
+            "Engine {} is in {:?} state, cannot be deleted",
+            id,
+            state
+        ))
+        .with_engine_id(&id)
+        .into()),
+    }
+}
+
+/// Maximum number of engines deleted by [batch_delete_engines] in one request.
+const MAX_BATCH_DELETE_SIZE: usize = 100;
+
+/// Delete engines one by one in the same way as [delete_engine],
+/// engines that cannot be deleted are reported in the response without aborting the batch.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/engine/batch-delete",
+        request_body = BatchDeleteEnginesRequest,
+        responses(
+            (status = 200, description = "Result of each engine", body = BatchDeleteEnginesResponse),
+            (status = 422, description = "Too many engines in the batch", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn batch_delete_engines<DB>(
+    State(state): State<AppState<DB>>,
+    project: Project,
+    principal: Principal,
+    AppJson(BatchDeleteEnginesRequest { ids }): AppJson<BatchDeleteEnginesRequest>,
+) -> Result<Json<BatchDeleteEnginesResponse>>
+where
+    DB: Database,
+{
+    if ids.len() > MAX_BATCH_DELETE_SIZE {
+        return Err(RucatError::invalid_argument(anyhow!(
+            "At most {} engines can be deleted in a batch, but got {}",
+            MAX_BATCH_DELETE_SIZE,
+            ids.len()
+        ))
+        .into());
+    }
+    let mut results = BTreeMap::new();
+    // duplicated ids are deleted once
+    for id in ids.into_iter().collect::<BTreeSet<_>>() {
+        let result = try_delete_engine(&id, &state, &project, &principal, false, None)
+            .await?
+            .into();
+        results.insert(id.to_string(), result);
+    }
+    Ok(Json(BatchDeleteEnginesResponse { results }))
+}
+
+/// Maximum number of engines got by [batch_get_engines] in one request.
+const MAX_BATCH_GET_SIZE: usize = 100;
+
+/// Get engines in one database query, in the order of the ids in the request.
+/// Engines that are not found, including the engines of other projects, have no info.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/engine/batch-get",
+        request_body = BatchGetEnginesRequest,
+        responses(
+            (status = 200, description = "Info of each engine", body = BatchGetEnginesResponse),
+            (status = 422, description = "Too many engines in the batch", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn batch_get_engines<DB>(
+    State(state): State<AppState<DB>>,
+    project: Project,
+    AppJson(BatchGetEnginesRequest { ids }): AppJson<BatchGetEnginesRequest>,
+) -> Result<Json<BatchGetEnginesResponse>>
+where
+    DB: Database,
+{
+    if ids.len() > MAX_BATCH_GET_SIZE {
+        return Err(RucatError::invalid_argument(anyhow!(
+            "At most {} engines can be got in a batch, but got {}",
+            MAX_BATCH_GET_SIZE,
+            ids.len()
+        ))
+        .into());
+    }
+    let engines = state
+        .get_db()
+        .get_engines(&ids)
+        .await?
+        .into_iter()
+        .map(|(id, info)| BatchGetEngineResult {
+            id: id.to_string(),
+            info: info.filter(|info| project.contains(info)),
+        })
+        .collect();
+    Ok(Json(BatchGetEnginesResponse { engines }))
+}
+
+/// Remove all the engines in the stable states of the request in one database query,
+/// if they have been in the state for longer than `older_than_secs`.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/engine/cleanup",
+        request_body = CleanupEnginesRequest,
+        responses(
+            (status = 200, description = "Number of the removed engines", body = CleanupEnginesResponse),
+            (status = 422, description = "Empty `states`, non-stable states or a too large `older_than_secs`", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn cleanup_engines<DB>(
+    State(state): State<AppState<DB>>,
+    project: Project,
+    AppJson(body): AppJson<CleanupEnginesRequest>,
+) -> Result<Json<CleanupEnginesResponse>>
+where
+    DB: Database,
+{
+    body.validate()?;
+    let now = state.get_clock().now();
+    let older_than = now
+        .checked_sub(Duration::from_secs(body.older_than_secs))
+        .ok_or_else(|| {
+            RucatError::invalid_argument(anyhow!(
+                "`older_than_secs` {} is too large",
+                body.older_than_secs
+            ))
+        })?;
+    let ids = state
+        .get_db()
+        .remove_engines_in_states(&body.states, older_than, project.as_deref(), now)
+        .await?;
+    info!(
+        "Cleaned up {} engines in {:?} states for longer than {} seconds",
+        ids.len(),
+        body.states,
+        body.older_than_secs
+    );
+    // the engines removed now are purged after the ttl, by a later cleanup in the same project
+    let history_ttl = state.get_engine_history().get_ttl();
+    match now.checked_sub(history_ttl) {
+        Some(deleted_before) => match state
+            .get_db()
+            .purge_engine_events(deleted_before, project.as_deref())
+            .await
+        {
+            Ok(purged) => info!(
+                "Purged {} events of the engines deleted for longer than {} seconds",
+                purged,
+                history_ttl.as_secs()
+            ),
+            Err(e) => warn!("Failed to purge the events of deleted engines: {}", e),
+        },
+        None => warn!(
+            "History ttl {} seconds is too large, no events are purged",
+            history_ttl.as_secs()
+        ),
+    }
+    for id in &ids {
+        state
+            .get_metrics()
+            .record_engine_operation(EngineOperation::Delete);
+        if let Some(blob_store) = state.get_blob_store() {
+            if let Err(e) = delete_engine_blobs(blob_store, id).await {
+                warn!("Failed to delete blobs of engine {}: {}", id, e);
+            }
+        }
+    }
+    Ok(Json(CleanupEnginesResponse { removed: ids.len() }))
+}
+
+/// Maximum attempts of the compare-and-swap of the engine state in [update_engine_state_with_retry].
+const MAX_STATE_UPDATE_ATTEMPTS: u32 = 5;
+/// Backoff before the first retry of the compare-and-swap, doubled before each of the following retries.
+const STATE_UPDATE_RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Update the engine from its `current_state` to the state given by `get_new_state` for the `principal`,
+/// retrying with the actual state if it is changed concurrently, e.g. by a state monitor.
+/// Fail with 409 if the state keeps changing in [MAX_STATE_UPDATE_ATTEMPTS] attempts,
+/// or with 412 if the engine is not at the `expected_revision`.
+async fn update_engine_state_with_retry<DB, F>(
+    id: &EngineId,
+    state: &AppState<DB>,
+    principal: &Principal,
+    mut current_state: EngineState,
+    expected_revision: Option<u64>,
+    operation: EngineOperation,
+    get_new_state: F,
+) -> Result<()>
+where
+    DB: Database,
+    F: Fn(&EngineState) -> Result<(EngineState, Option<SystemTime>)>,
+{
+    let db_client = state.get_db();
+    let actor = principal.get_engine_actor();
+    let mut backoff = STATE_UPDATE_RETRY_BACKOFF;
+    for attempt in 1..=MAX_STATE_UPDATE_ATTEMPTS {
+        let (new_state, next_update_time) = get_new_state(&current_state)?;
         let response = db_client
-            // For Running state, we set next_update_time to current time to trigger the state monitor immediately because
-            // rucat server does not know the check interval of the state monitor.
-            .update_engine_state(&id, &current_state, &new_state, Some(SystemTime::now()))
+            .update_engine_state(
+                id,
+                &current_state,
+                &new_state,
+                next_update_time,
+                expected_revision,
+                actor.as_ref(),
+            )
             .await?
-            .ok_or_else(|| RucatError::engine_not_found(&id))?;
+            .ok_or_else(|| RucatError::engine_not_found(id))?;
         match response {
             UpdateEngineStateResult::Success => {
                 info!(
                     "Update Engine {} from {:?} to {:?}",
                     id, current_state, new_state
                 );
+                state.get_metrics().record_engine_operation(operation);
+                state.notify_state_change(id, &current_state, &new_state);
                 return Ok(());
             }
             UpdateEngineStateResult::Fail {
                 current_state: actual_state,
             } => {
                 info!(
-                    "Engine {} has been updated from {:?} to {:?}, retry to restart",
-                    id, current_state, actual_state
+                    "Engine {} has been updated from {:?} to {:?}, {:?} attempt {}/{} failed",
+                    id, current_state, actual_state, operation, attempt, MAX_STATE_UPDATE_ATTEMPTS
                 );
                 current_state = actual_state;
+                if attempt < MAX_STATE_UPDATE_ATTEMPTS {
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
             }
         }
     }
+    Err(RucatError::conflict(anyhow!(
+        "Engine {} keeps changing its state, last observed in {:?} state after {} attempts",
+        id,
+        current_state,
+        MAX_STATE_UPDATE_ATTEMPTS
+    ))
+    .with_engine_id(id)
+    .into())
 }
 
-async fn get_engine<DB>(
-    Path(id): Path<EngineId>,
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(crate = "rucat_common::serde")]
+struct StopEngineQuery {
+    /// Also stop the engine acquired by a state monitor, e.g. when the monitor is down.
+    #[serde(default)]
+    force: bool,
+}
+
+/// Stop an engine to release resources. But engine info is still kept in the data store.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/engine/{id}/stop",
+        params(
+            ("id" = String, Path, description = "Engine id"),
+            StopEngineQuery,
+            ("If-Match" = Option<String>, Header, description = "`ETag` of the engine, the request fails with 412 if the engine is updated since then")
+        ),
+        responses(
+            (status = 200, description = "Engine is stopping"),
+            (status = 403, description = "Engine cannot be stopped in its state", body = crate::error::ErrorResponse),
+            (status = 404, description = "Engine is not found", body = crate::error::ErrorResponse),
+            (status = 409, description = "Engine state keeps changing concurrently", body = crate::error::ErrorResponse),
+            (status = 412, description = "Engine is not at the revision of `If-Match`", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn stop_engine<DB>(
+    AppPath(id): AppPath<EngineId>,
+    AppQuery(StopEngineQuery { force }): AppQuery<StopEngineQuery>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+    principal: Principal,
+    headers: HeaderMap,
+) -> Result<()>
+where
+    DB: Database,
+{
+    let expected_revision = get_expected_revision(&headers)?;
+    let info = get_changeable_engine(&id, state.get_db(), &project, &principal).await?;
+    update_engine_state_with_retry(
+        &id,
+        &state,
+        &principal,
+        info.state,
+        expected_revision,
+        EngineOperation::Stop,
+        |current_state| match current_state {
+            WaitToStart => Ok((Terminated, None)),
+            StartInProgress | Running => Ok((WaitToTerminate, Some(state.get_clock().now()))),
+            // The monitor holding the engine skips releasing it, and its resources are cleaned
+            // once the engine is acquired again.
+            TriggerStart | TriggerTermination if force => {
+                Ok((WaitToTerminate, Some(state.get_clock().now())))
+            }
+            other => Err(RucatError::not_allowed(anyhow!(
+                "Engine {} is in {:?} state, cannot be stopped",
+                id,
+                other
+            ))
+            .with_engine_id(&id)
+            .into()),
+        },
+    )
+    .await
+}
+
+/// Restart a stopped or failed engine with the same configuration.
+/// Stopped engines of blocked versions cannot be restarted.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        post,
+        path = "/engine/{id}/restart",
+        params(
+            ("id" = String, Path, description = "Engine id"),
+            ("If-Match" = Option<String>, Header, description = "`ETag` of the engine, the request fails with 412 if the engine is updated since then")
+        ),
+        responses(
+            (status = 200, description = "Engine is restarting"),
+            (status = 403, description = "Engine cannot be restarted in its state, or its version is blocked", body = crate::error::ErrorResponse),
+            (status = 404, description = "Engine is not found", body = crate::error::ErrorResponse),
+            (status = 409, description = "Engine state keeps changing concurrently", body = crate::error::ErrorResponse),
+            (status = 412, description = "Engine is not at the revision of `If-Match`", body = crate::error::ErrorResponse),
+            (status = 422, description = "Engine version is no longer in the allow-list", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn restart_engine<DB>(
+    AppPath(id): AppPath<EngineId>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+    principal: Principal,
+    headers: HeaderMap,
+) -> Result<()>
+where
+    DB: Database,
+{
+    let expected_revision = get_expected_revision(&headers)?;
+    let info = get_changeable_engine(&id, state.get_db(), &project, &principal).await?;
+    update_engine_state_with_retry(
+        &id,
+        &state,
+        &principal,
+        info.state,
+        expected_revision,
+        EngineOperation::Restart,
+        |current_state| match current_state {
+            // For Running state, we set next_update_time to current time to trigger the state monitor immediately because
+            // rucat server does not know the check interval of the state monitor.
+            WaitToTerminate => Ok((Running, Some(state.get_clock().now()))),
+            // The error message of `ErrorClean` is kept as the last error of the engine.
+            Terminated | ErrorClean(_) => {
+                state
+                    .get_engine_versions()
+                    .check(&info.engine_type, &info.version)?;
+                Ok((WaitToStart, Some(state.get_clock().now())))
+            }
+            other => Err(RucatError::not_allowed(anyhow!(
+                "Engine {} is in {:?} state, cannot be restarted",
+                id,
+                other
+            ))
+            .with_engine_id(&id)
+            .into()),
+        },
+    )
+    .await
+}
+
+/// States in which the config of an engine can be updated, i.e. the engine is not started yet or stopped.
+const CONFIG_UPDATABLE_STATES: [EngineState; 2] = [WaitToStart, Terminated];
+
+/// Merge the config into the config of a stopped engine, which takes effect when the engine starts.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        patch,
+        path = "/engine/{id}/config",
+        params(
+            ("id" = String, Path, description = "Engine id"),
+            ("If-Match" = Option<String>, Header, description = "`ETag` of the engine, the request fails with 412 if the engine is updated since then")
+        ),
+        request_body(content = BTreeMap<String, String>, description = "Config merged into the config of the engine"),
+        responses(
+            (status = 200, description = "Config is updated"),
+            (status = 403, description = "Engine config cannot be updated in its state", body = crate::error::ErrorResponse),
+            (status = 404, description = "Engine is not found", body = crate::error::ErrorResponse),
+            (status = 412, description = "Engine is not at the revision of `If-Match`", body = crate::error::ErrorResponse),
+            (status = 422, description = "Config reserved by rucat is set", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn update_engine_config<DB>(
+    AppPath(id): AppPath<EngineId>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+    principal: Principal,
+    headers: HeaderMap,
+    AppJson(config): AppJson<EngineConfig>,
+) -> Result<()>
+where
+    DB: Database,
+{
+    let expected_revision = get_expected_revision(&headers)?;
+    let info = get_changeable_engine(&id, state.get_db(), &project, &principal).await?;
+    // The config of the engine is validated when it is created, so only the merged keys are checked.
+    validate_engine_config(&info.engine_type, &config)?;
+    let response = state
+        .get_db()
+        .update_engine_info(
+            &id,
+            EngineInfoUpdate {
+                config: Some(config),
+                ..Default::default()
+            },
+            Some(&CONFIG_UPDATABLE_STATES),
+            expected_revision,
+        )
+        .await?
+        .ok_or_else(|| RucatError::engine_not_found(&id))?;
+    match response {
+        UpdateEngineStateResult::Success => {
+            info!("Update config of engine {}", id);
+            Ok(())
+        }
+        UpdateEngineStateResult::Fail { current_state } => Err(RucatError::not_allowed(anyhow!(
+            "Engine {} is in {:?} state, cannot update its config",
+            id,
+            current_state
+        ))
+        .with_engine_id(&id)
+        .into()),
+    }
+}
+
+/// Set the name of an engine in any state, as the name is not used by the resource manager.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        put,
+        path = "/engine/{id}/name",
+        params(
+            ("id" = String, Path, description = "Engine id"),
+            ("If-Match" = Option<String>, Header, description = "`ETag` of the engine, the request fails with 412 if the engine is updated since then")
+        ),
+        request_body = RenameEngineRequest,
+        responses(
+            (status = 200, description = "Engine is renamed"),
+            (status = 404, description = "Engine is not found", body = crate::error::ErrorResponse),
+            (status = 422, description = "Engine name is empty or too long", body = crate::error::ErrorResponse),
+            (status = 412, description = "Engine is not at the revision of `If-Match`", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn rename_engine<DB>(
+    AppPath(id): AppPath<EngineId>,
     State(state): State<AppState<DB>>,
-) -> Result<Json<EngineInfo>>
+    project: Project,
+    headers: HeaderMap,
+    AppJson(RenameEngineRequest { name }): AppJson<RenameEngineRequest>,
+) -> Result<()>
 where
     DB: Database,
 {
+    let expected_revision = get_expected_revision(&headers)?;
+    validate_engine_name(&name, state.get_engine_limits().max_name_length)?;
+    project.check_engine(&id, state.get_db()).await?;
     state
+        .get_db()
+        .update_engine_info(
+            &id,
+            EngineInfoUpdate {
+                name: Some(name),
+                ..Default::default()
+            },
+            None,
+            expected_revision,
+        )
+        .await?
+        .ok_or_else(|| RucatError::engine_not_found(&id))?;
+    info!("Rename engine {}", id);
+    Ok(())
+}
+
+/// Set the principals allowed to stop, restart, update and delete an engine besides its creator,
+/// replacing the principals set before. Only the creator and admins are allowed to set them.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        put,
+        path = "/engine/{id}/acl",
+        params(
+            ("id" = String, Path, description = "Engine id"),
+            ("If-Match" = Option<String>, Header, description = "`ETag` of the engine, the request fails with 412 if the engine is updated since then")
+        ),
+        request_body = SetEngineAclRequest,
+        responses(
+            (status = 200, description = "Principals are set"),
+            (status = 403, description = "Client is neither the creator of the engine nor an admin", body = crate::error::ErrorResponse),
+            (status = 404, description = "Engine is not found", body = crate::error::ErrorResponse),
+            (status = 422, description = "Principal name is empty", body = crate::error::ErrorResponse),
+            (status = 412, description = "Engine is not at the revision of `If-Match`", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn set_engine_acl<DB>(
+    AppPath(id): AppPath<EngineId>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+    principal: Principal,
+    headers: HeaderMap,
+    AppJson(SetEngineAclRequest { allowed_principals }): AppJson<SetEngineAclRequest>,
+) -> Result<()>
+where
+    DB: Database,
+{
+    let expected_revision = get_expected_revision(&headers)?;
+    if allowed_principals.iter().any(String::is_empty) {
+        return Err(RucatError::invalid_argument(anyhow!("Principal name cannot be empty")).into());
+    }
+    let info = state
         .get_db()
         .get_engine(&id)
         .await?
-        .map(Json)
-        .ok_or(RucatError::engine_not_found(&id).into())
+        .filter(|info| project.contains(info))
+        .ok_or_else(|| RucatError::engine_not_found(&id))?;
+    if !principal.has_role(Role::Admin) && info.created_by.as_ref() != Some(&principal.name) {
+        return Err(RucatError::not_allowed(anyhow!(
+            "`{}` is not allowed to share engine {}, which is only allowed to its creator and admins",
+            principal.name,
+            id
+        ))
+        .with_engine_id(&id)
+        .into());
+    }
+    info!(
+        "Allow {:?} to change engine {} by {}",
+        allowed_principals, id, principal
+    );
+    state
+        .get_db()
+        .set_engine_acl(&id, allowed_principals, expected_revision)
+        .await?
+        .ok_or_else(|| RucatError::engine_not_found(&id))?;
+    Ok(())
 }
 
-async fn list_engines<DB>(State(state): State<AppState<DB>>) -> Result<Json<Vec<EngineId>>>
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/engine/{id}",
+        params(("id" = String, Path, description = "Engine id")),
+        responses(
+            (status = 200, description = "Engine info, with its revision in the `ETag` header", body = EngineInfo),
+            (status = 404, description = "Engine is not found", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn get_engine<DB>(
+    AppPath(id): AppPath<EngineId>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+) -> Result<Response>
 where
     DB: Database,
 {
+    let info = state
+        .get_db()
+        .get_engine(&id)
+        .await?
+        .filter(|info| project.contains(info))
+        .ok_or_else(|| RucatError::engine_not_found(&id))?;
+    Ok(([(ETAG, get_etag(info.revision))], Json(info)).into_response())
+}
+
+/// Only the state of the engine, for dashboards polling it frequently.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/engine/{id}/state",
+        params(("id" = String, Path, description = "Engine id")),
+        responses(
+            (status = 200, description = "Engine state", body = EngineStateResponse),
+            (status = 404, description = "Engine is not found", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn get_engine_state<DB>(
+    AppPath(id): AppPath<EngineId>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+) -> Result<Json<EngineStateResponse>>
+where
+    DB: Database,
+{
+    project.check_engine(&id, state.get_db()).await?;
     state
         .get_db()
-        .list_engines()
-        .await
-        .map(Json)
-        .map_err(|e| e.into())
+        .get_engine_state(&id)
+        .await?
+        .map(|state| Json(EngineStateResponse { state }))
+        .ok_or(RucatError::engine_not_found(&id).into())
 }
 
-/// helper function to get the engine state
-async fn get_engine_state<DB>(id: &EngineId, db_client: &DB) -> Result<EngineState>
+/// Number of events returned by [get_engine_history] if `limit` is not set.
+const DEFAULT_HISTORY_LIMIT: i64 = 100;
+/// Maximum number of events returned by [get_engine_history].
+const MAX_HISTORY_LIMIT: i64 = 1000;
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(crate = "rucat_common::serde")]
+struct EngineHistoryQuery {
+    /// Maximum number of events to return, between 1 and [MAX_HISTORY_LIMIT].
+    limit: Option<i64>,
+}
+
+/// The latest state transitions of the engine, newest first.
+/// The history of a deleted engine is kept for `engine_history.ttl_secs`,
+/// but it is not found if the engines are isolated in projects, as its project is unknown.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/engine/{id}/history",
+        params(("id" = String, Path, description = "Engine id"), EngineHistoryQuery),
+        responses(
+            (status = 200, description = "State transitions of the engine", body = EngineHistoryResponse),
+            (status = 404, description = "Engine is not found and has no history", body = crate::error::ErrorResponse),
+            (status = 422, description = "Invalid `limit`", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn get_engine_history<DB>(
+    AppPath(id): AppPath<EngineId>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+    AppQuery(query): AppQuery<EngineHistoryQuery>,
+) -> Result<Json<EngineHistoryResponse>>
 where
     DB: Database,
 {
-    db_client.get_engine(id).await?.map_or_else(
-        || Err(RucatError::engine_not_found(id).into()),
-        |info| Ok(info.state),
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    if !(1..=MAX_HISTORY_LIMIT).contains(&limit) {
+        return Err(RucatError::invalid_argument(anyhow!(
+            "`limit` must be between 1 and {}, but got {}",
+            MAX_HISTORY_LIMIT,
+            limit
+        ))
+        .into());
+    }
+    project.check_engine(&id, state.get_db()).await?;
+    let events = state
+        .get_db()
+        .get_engine_events(&id, limit as usize)
+        .await?;
+    if events.is_empty() && state.get_db().get_engine_state(&id).await?.is_none() {
+        return Err(RucatError::engine_not_found(&id).into());
+    }
+    Ok(Json(EngineHistoryResponse { events }))
+}
+
+/// How to connect to the engine, derived from the names of the resources created by rucat.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/engine/{id}/connect-info",
+        params(("id" = String, Path, description = "Engine id")),
+        responses(
+            (status = 200, description = "Addresses of the running engine", body = EngineConnectInfo),
+            (status = 404, description = "Engine is not found", body = crate::error::ErrorResponse),
+            (status = 409, description = "Engine is not running", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
     )
+)]
+async fn get_connect_info<DB>(
+    AppPath(id): AppPath<EngineId>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+) -> Result<Json<EngineConnectInfo>>
+where
+    DB: Database,
+{
+    project.check_engine(&id, state.get_db()).await?;
+    let engine_state = state
+        .get_db()
+        .get_engine_state(&id)
+        .await?
+        .ok_or_else(|| RucatError::engine_not_found(&id))?;
+    if engine_state != Running {
+        return Err(RucatError::conflict(anyhow!(
+            "Engine {} is in {} state, connect info is only available when it is Running",
+            id,
+            EngineStateKind::from(&engine_state)
+        ))
+        .with_engine_id(&id)
+        .into());
+    }
+    Ok(Json(EngineConnectInfo {
+        spark_connect_url: get_spark_connect_url(&id, project.as_deref()),
+        spark_ui_url: get_spark_ui_url(&id, project.as_deref()),
+        driver_host: get_spark_service_host(&id, project.as_deref()),
+    }))
+}
+
+/// Events of a watched engine, ended by [EngineEvent::End] when the engine cannot change any more.
+enum EngineEvent<'a> {
+    /// The engine is in the state, sent when the stream starts and whenever the state changes.
+    State(&'a EngineState),
+    /// The engine reaches the final state, e.g. `Terminated`, or is `Deleted`.
+    End(&'static str),
+}
+
+impl EngineEvent<'_> {
+    fn into_sse_event(self) -> std::result::Result<Event, axum::Error> {
+        match self {
+            EngineEvent::State(state) => Event::default().event("state").json_data(state),
+            EngineEvent::End(reason) => Ok(Event::default().event("end").data(reason)),
+        }
+    }
+}
+
+/// Events of the engine in `state`, and whether the engine can still change.
+fn get_state_events(state: &EngineState) -> (Vec<std::result::Result<Event, axum::Error>>, bool) {
+    let end = match state {
+        Terminated => Some("Terminated"),
+        ErrorClean(_) => Some("ErrorClean"),
+        _ => None,
+    };
+    let mut events = vec![EngineEvent::State(state).into_sse_event()];
+    events.extend(end.map(|reason| EngineEvent::End(reason).into_sse_event()));
+    (events, end.is_none())
 }
 
+/// Poll the engine until its state differs from `last_state`, return the events of the change
+/// and the new state if the engine can still change.
+/// Polling stops without events when the server is shutting down, so that the stream does not block the draining.
+async fn poll_engine_events<DB>(
+    state: &AppState<DB>,
+    id: &EngineId,
+    last_state: &EngineState,
+) -> (
+    Vec<std::result::Result<Event, axum::Error>>,
+    Option<EngineState>,
+)
+where
+    DB: Database,
+{
+    let shutdown_handle = state.get_shutdown_handle();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(state.get_engine_events().get_poll_interval()) => {}
+            _ = shutdown_handle.wait_for_trigger() => return (vec![], None),
+        }
+        match state.get_db().get_engine_state(id).await {
+            Ok(Some(engine_state)) if engine_state == *last_state => continue,
+            Ok(Some(engine_state)) => {
+                let (events, can_change) = get_state_events(&engine_state);
+                return (events, can_change.then_some(engine_state));
+            }
+            Ok(None) => {
+                return (vec![EngineEvent::End("Deleted").into_sse_event()], None);
+            }
+            Err(e) => {
+                warn!("Fail to watch engine {}: {}", id, e);
+                return (vec![Err(axum::Error::new(e))], None);
+            }
+        }
+    }
+}
+
+/// Stream the state of the engine as server-sent events, instead of polling `GET /engine/{id}`.
+/// The stream starts with the current state, and ends after the `end` event.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/engine/{id}/events",
+        params(("id" = String, Path, description = "Engine id")),
+        responses(
+            (status = 200, description = "Stream of `state` events with the engine state, ended by an `end` event with `Terminated`, `ErrorClean` or `Deleted`", content_type = "text/event-stream", body = String),
+            (status = 404, description = "Engine is not found", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn watch_engine<DB>(
+    AppPath(id): AppPath<EngineId>,
+    State(state): State<AppState<DB>>,
+    project: Project,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, axum::Error>>>>
+where
+    DB: Database,
+{
+    project.check_engine(&id, state.get_db()).await?;
+    let engine_state = state
+        .get_db()
+        .get_engine_state(&id)
+        .await?
+        .ok_or_else(|| RucatError::engine_not_found(&id))?;
+    let (first_events, can_change) = get_state_events(&engine_state);
+    let changes = stream::unfold(can_change.then_some(engine_state), move |last_state| {
+        let state = state.clone();
+        let id = id.clone();
+        async move {
+            let last_state = last_state?;
+            let (events, next_state) = poll_engine_events(&state, &id, &last_state).await;
+            Some((stream::iter(events), next_state))
+        }
+    })
+    .flatten();
+    Ok(Sse::new(stream::iter(first_events).chain(changes)).keep_alive(KeepAlive::default()))
+}
+
+/// Number of engines returned by [list_engines] if `limit` is not set.
+const DEFAULT_LIST_LIMIT: i64 = 100;
+/// Maximum number of engines returned by [list_engines].
+const MAX_LIST_LIMIT: i64 = 1000;
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(crate = "rucat_common::serde")]
+struct ListEnginesQuery {
+    /// Maximum number of engines to return, between 1 and [MAX_LIST_LIMIT].
+    limit: Option<i64>,
+    /// Number of engines to skip.
+    offset: Option<i64>,
+    /// Comma separated [EngineStateKind]s, only engines in these states are listed if set.
+    state: Option<String>,
+    /// Only engines whose name contains it case-insensitively are listed if set.
+    name_contains: Option<String>,
+    /// Only engines created by this user are listed if set, `me` for the authenticated client.
+    /// Not supported if authentication is disabled.
+    owner: Option<String>,
+    /// Key of sorting the engines, one of `id`, `create_time`, `name` and `state`, `id` by default.
+    sort: Option<String>,
+    /// Order of sorting the engines, `asc` or `desc`, `asc` by default.
+    order: Option<String>,
+    /// How much detail of each engine to list.
+    detail: Option<ListEnginesDetail>,
+}
+
+#[derive(Deserialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(crate = "rucat_common::serde")]
+#[serde(rename_all = "lowercase")]
+enum ListEnginesDetail {
+    /// List the [EngineId]s.
+    #[default]
+    Id,
+    /// List the [rucat_common::engine::EngineSummary]s.
+    Summary,
+}
+
+/// Make the page from the engines got from the database,
+/// which include one more engine than `limit` if there is a next page.
+fn get_engine_page<T>(mut engines: Vec<T>, offset: usize, limit: usize) -> ListEnginesResponse<T> {
+    let next_offset = (engines.len() > limit).then(|| {
+        engines.truncate(limit);
+        offset + limit
+    });
+    ListEnginesResponse {
+        engines,
+        next_offset,
+    }
+}
+
+/// Parse the comma separated engine states, e.g. `Running,ErrorClean`.
+fn parse_states(states: Option<&str>) -> Result<BTreeSet<EngineStateKind>> {
+    states.map_or_else(
+        || Ok(BTreeSet::new()),
+        |states| {
+            states
+                .split(',')
+                .map(|state| state.trim().parse().map_err(RucatServerError::from))
+                .collect()
+        },
+    )
+}
+
+/// Parse the sort key and order of listing engines, engines with the same key are sorted by id.
+fn parse_sort(key: Option<&str>, order: Option<&str>) -> Result<EngineSort> {
+    let key = key.map(str::parse).transpose()?.unwrap_or_default();
+    let descending = match order {
+        None | Some("asc") => false,
+        Some("desc") => true,
+        Some(order) => {
+            return Err(RucatError::invalid_argument(anyhow!(
+                "Unknown sort order `{}`, expected `asc` or `desc`",
+                order
+            ))
+            .into())
+        }
+    };
+    Ok(EngineSort { key, descending })
+}
+
+/// List engine ids or summaries page by page, use the `next_offset` in the response to get the next page.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/engine",
+        params(ListEnginesQuery),
+        responses(
+            (status = 200, description = "Page of engine ids, or of engine summaries if `detail=summary`", body = ListEnginesResponse<EngineId>),
+            (status = 400, description = "Filtering by `owner` while authentication is disabled", body = crate::error::ErrorResponse),
+            (status = 422, description = "Invalid query, e.g. unknown engine states or sort keys", body = crate::error::ErrorResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn list_engines<DB>(
+    State(state): State<AppState<DB>>,
+    project: Project,
+    principal: Principal,
+    AppQuery(query): AppQuery<ListEnginesQuery>,
+) -> Result<Response>
+where
+    DB: Database,
+{
+    let limit = query.limit.unwrap_or(DEFAULT_LIST_LIMIT);
+    if !(1..=MAX_LIST_LIMIT).contains(&limit) {
+        return Err(RucatError::invalid_argument(anyhow!(
+            "`limit` must be between 1 and {}, but got {}",
+            MAX_LIST_LIMIT,
+            limit
+        ))
+        .into());
+    }
+    let limit = limit as usize;
+    let offset = query.offset.unwrap_or(0);
+    let offset = usize::try_from(offset).map_err(|_| {
+        RucatError::invalid_argument(anyhow!("`offset` must not be negative, but got {}", offset))
+    })?;
+    let created_by = match query.owner {
+        None => None,
+        Some(_) if principal.is_anonymous() => {
+            return Err(RucatError::invalid_request(anyhow!(
+                "Filtering by `owner` requires authentication to be enabled"
+            ))
+            .into())
+        }
+        // the impersonated user if an admin acts on behalf of it
+        Some(owner) if owner == "me" => Some(principal.name),
+        Some(owner) => Some(owner),
+    };
+    let filter = EngineFilter {
+        states: parse_states(query.state.as_deref())?,
+        name_contains: query.name_contains.unwrap_or_default(),
+        created_by,
+        project: project.0,
+    };
+    let sort = parse_sort(query.sort.as_deref(), query.order.as_deref())?;
+    let db = state.get_db();
+    // Get one more engine to know whether there is a next page.
+    let response = match query.detail.unwrap_or_default() {
+        ListEnginesDetail::Id => {
+            let engines = db.list_engines(offset, limit + 1, &filter, sort).await?;
+            Json(get_engine_page(engines, offset, limit)).into_response()
+        }
+        ListEnginesDetail::Summary => {
+            let engines = db
+                .list_engine_summaries(offset, limit + 1, &filter, sort)
+                .await?;
+            Json(get_engine_page(engines, offset, limit)).into_response()
+        }
+    };
+    Ok(response)
+}
+
+/// Number of engines in each state, counted by the database without loading the engines.
+#[cfg_attr(
+    feature = "openapi",
+    utoipa::path(
+        get,
+        path = "/engine/stats",
+        responses(
+            (status = 200, description = "Number of engines in each state", body = EngineStatsResponse),
+        ),
+        tag = "engine"
+    )
+)]
+async fn get_engine_stats<DB>(
+    State(state): State<AppState<DB>>,
+    project: Project,
+) -> Result<Json<EngineStatsResponse>>
+where
+    DB: Database,
+{
+    let counts = state
+        .get_db()
+        .count_engines_by_state(&[], project.as_deref())
+        .await?;
+    Ok(Json(EngineStatsResponse::from(counts)))
+}
+
+/// Check the `principal` is allowed to change the engine, i.e. it is an admin or [EngineInfo::allows] it.
+fn check_allowed(id: &EngineId, info: &EngineInfo, principal: &Principal) -> Result<()> {
+    if principal.has_role(Role::Admin) || info.allows(&principal.name) {
+        return Ok(());
+    }
+    Err(RucatError::not_allowed(anyhow!(
+        "`{}` is not allowed to change engine {}, which is not shared with it",
+        principal.name,
+        id
+    ))
+    .with_engine_id(id)
+    .into())
+}
+
+/// helper function to get the engine in the `project` that the `principal` is allowed to change
+async fn get_changeable_engine<DB>(
+    id: &EngineId,
+    db_client: &DB,
+    project: &Project,
+    principal: &Principal,
+) -> Result<EngineInfo>
+where
+    DB: Database,
+{
+    let info = db_client
+        .get_engine(id)
+        .await?
+        .filter(|info| project.contains(info))
+        .ok_or_else(|| RucatError::engine_not_found(id))?;
+    check_allowed(id, &info, principal)?;
+    Ok(info)
+}
+
+/// Same as [get_changeable_engine] without returning the engine,
+/// which is not read if the `principal` is an admin and the engines are not isolated in projects.
+async fn check_engine_access<DB>(
+    id: &EngineId,
+    db_client: &DB,
+    project: &Project,
+    principal: &Principal,
+) -> Result<()>
+where
+    DB: Database,
+{
+    if principal.has_role(Role::Admin) {
+        return Ok(project.check_engine(id, db_client).await?);
+    }
+    get_changeable_engine(id, db_client, project, principal)
+        .await
+        .map(|_| ())
+}
+
+/// Path of adopting engines.
+const ADOPT_PATH: &str = "adopt";
+/// Path of deleting engines in a batch.
+const BATCH_DELETE_PATH: &str = "batch-delete";
+/// Path of getting engines in a batch, the only `POST` request allowed to viewers.
+pub(crate) const BATCH_GET_PATH: &str = "batch-get";
+/// Path of removing old engines in stable states.
+const CLEANUP_PATH: &str = "cleanup";
+/// Path of counting engines by state.
+const STATS_PATH: &str = "stats";
+/// Paths under `/engine` that cannot be used as engine ids.
+const RESERVED_IDS: [&str; 5] = [
+    ADOPT_PATH,
+    BATCH_DELETE_PATH,
+    BATCH_GET_PATH,
+    CLEANUP_PATH,
+    STATS_PATH,
+];
+
+/// Router for adopting engines, merged into the engine router only if adoption is enabled.
+pub(crate) fn get_inspector_router<DB, RI>(inspector: RI) -> IndexedRouter<AppState<DB>>
+where
+    DB: Database,
+    RI: ResourceInspector,
+{
+    IndexedRouter::new()
+        // The adopted pod is not owned by the client, so only admins are allowed to take it over.
+        .route(
+            &format!("/{}", ADOPT_PATH),
+            post(adopt_engine::<DB, RI>).route_layer(middleware::from_fn(require_admin)),
+        )
+        .route("/{id}/logs", get(get_engine_logs::<DB, RI>))
+        .route("/{id}/logs/archive", post(archive_engine_logs::<DB, RI>))
+        .route(
+            "/{id}/k8s-events",
+            get(get_engine_resource_events::<DB, RI>),
+        )
+        .map(|router| router.layer(Extension(Arc::new(inspector))))
+}
+
+/// OpenAPI documentation of the engine routes.
+#[cfg(feature = "openapi")]
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    paths(
+        create_engine,
+        list_engines,
+        batch_delete_engines,
+        batch_get_engines,
+        cleanup_engines,
+        get_engine_stats,
+        get_engine,
+        get_engine_state,
+        get_engine_history,
+        get_connect_info,
+        watch_engine,
+        delete_engine,
+        stop_engine,
+        restart_engine,
+        update_engine_config,
+        rename_engine,
+        set_engine_acl,
+        adopt_engine,
+        get_engine_logs,
+        archive_engine_logs,
+        get_engine_resource_events
+    ),
+    components(schemas(
+        ListEnginesDetail,
+        ::rucat_common::engine::EngineSummary,
+        ListEnginesResponse<::rucat_common::engine::EngineSummary>
+    ))
+)]
+pub(crate) struct EngineApi;
+
 /// Pass the data store endpoint later
-pub(crate) fn get_engine_router<DB>() -> Router<AppState<DB>>
+pub(crate) fn get_engine_router<DB>() -> IndexedRouter<AppState<DB>>
 where
     DB: Database,
 {
-    Router::new()
+    IndexedRouter::new()
         .route("/", post(create_engine::<DB>).get(list_engines::<DB>))
+        // The engines in batches are not checked one by one for the principal, so only admins are allowed to change them.
+        .route(
+            &format!("/{}", BATCH_DELETE_PATH),
+            post(batch_delete_engines::<DB>).route_layer(middleware::from_fn(require_admin)),
+        )
+        .route(
+            &format!("/{}", BATCH_GET_PATH),
+            post(batch_get_engines::<DB>),
+        )
+        .route(
+            &format!("/{}", CLEANUP_PATH),
+            post(cleanup_engines::<DB>).route_layer(middleware::from_fn(require_admin)),
+        )
+        .route(&format!("/{}", STATS_PATH), get(get_engine_stats::<DB>))
         .route("/{id}", get(get_engine::<DB>).delete(delete_engine::<DB>))
         .route("/{id}/stop", post(stop_engine::<DB>))
         .route("/{id}/restart", post(restart_engine::<DB>))
+        .route("/{id}/config", patch(update_engine_config::<DB>))
+        .route("/{id}/name", put(rename_engine::<DB>))
+        .route("/{id}/acl", put(set_engine_acl::<DB>))
+        .route("/{id}/state", get(get_engine_state::<DB>))
+        .route("/{id}/history", get(get_engine_history::<DB>))
+        .route("/{id}/connect-info", get(get_connect_info::<DB>))
+        .route("/{id}/events", get(watch_engine::<DB>))
 }
@@ -1,25 +1,69 @@
 use ::core::fmt::Display;
 
-use ::axum::response::{IntoResponse, Response};
+use ::axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    response::{IntoResponse, Response},
+    Json,
+};
 use ::http::StatusCode;
-use ::rucat_common::error::{RucatError, RucatErrorType::*};
+use ::rucat_common::{
+    anyhow::anyhow,
+    error::{RucatError, RucatErrorType, RucatErrorType::*},
+    serde::Serialize,
+};
 
 /// [RucatServerError] is a wrapper for [RucatError] to convert it into Axum response
-pub struct RucatServerError(RucatError);
+pub struct RucatServerError {
+    status: StatusCode,
+    error: RucatError,
+}
+
+/// Body of error responses.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(crate = "rucat_common::serde")]
+pub(crate) struct ErrorResponse {
+    /// Stable code of the error type, for clients to match on instead of the message.
+    code: RucatErrorType,
+    /// Message of the error.
+    message: String,
+    /// Id of the engine that the error is about, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    engine_id: Option<String>,
+}
 
 impl RucatServerError {
-    fn get_status_code(&self) -> StatusCode {
-        match self.0.get_error_type() {
+    /// Status code of the response for the error.
+    /// Failures that clients can retry later, e.g. the database is not reachable, are 503,
+    /// and other failures of the server are 500.
+    fn get_status_code(error: &RucatError) -> StatusCode {
+        match error.get_error_type() {
             NotFound => StatusCode::NOT_FOUND,
             Unauthorized => StatusCode::UNAUTHORIZED,
             NotAllowed => StatusCode::FORBIDDEN,
+            MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            Conflict => StatusCode::CONFLICT,
+            PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            InvalidRequest => StatusCode::BAD_REQUEST,
+            InvalidArgument => StatusCode::UNPROCESSABLE_ENTITY,
+            ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            RequestTimeout => StatusCode::REQUEST_TIMEOUT,
+            Timeout => StatusCode::GATEWAY_TIMEOUT,
             FailToStartServer => StatusCode::INTERNAL_SERVER_ERROR,
             FailToStartStateMonitor => StatusCode::INTERNAL_SERVER_ERROR,
             FailToStartEngine => StatusCode::INTERNAL_SERVER_ERROR,
             FailToDeleteEngine => StatusCode::INTERNAL_SERVER_ERROR,
-            FailToConnectDatabase => StatusCode::INTERNAL_SERVER_ERROR,
+            FailToConnectDatabase => StatusCode::SERVICE_UNAVAILABLE,
             FailToUpdateDatabase => StatusCode::INTERNAL_SERVER_ERROR,
             FailToReadDatabase => StatusCode::INTERNAL_SERVER_ERROR,
+            DatabaseTimeout => StatusCode::SERVICE_UNAVAILABLE,
+            FailToWriteBlob => StatusCode::INTERNAL_SERVER_ERROR,
+            FailToReadBlob => StatusCode::INTERNAL_SERVER_ERROR,
+            FailToDeleteBlob => StatusCode::INTERNAL_SERVER_ERROR,
+            FailToReadLogs => StatusCode::INTERNAL_SERVER_ERROR,
+            FailToReadEvents => StatusCode::INTERNAL_SERVER_ERROR,
             FailToLoadConfig => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -27,21 +71,53 @@ impl RucatServerError {
 
 impl From<RucatError> for RucatServerError {
     fn from(error: RucatError) -> Self {
-        Self(error)
+        Self {
+            status: Self::get_status_code(&error),
+            error,
+        }
     }
 }
 
+/// Generate the conversion from an axum extractor rejection,
+/// keeping the status code chosen by axum, e.g. 422 for a JSON body with missing fields.
+/// Bodies exceeding the limit of the request body size are rejected as too large.
+macro_rules! impl_from_rejection {
+    ($($rejection: ty),*) => {
+        $(
+            impl From<$rejection> for RucatServerError {
+                fn from(rejection: $rejection) -> Self {
+                    let status = rejection.status();
+                    let content = anyhow!(rejection.body_text());
+                    let error = if status == StatusCode::PAYLOAD_TOO_LARGE {
+                        RucatError::payload_too_large(content)
+                    } else {
+                        RucatError::invalid_request(content)
+                    };
+                    Self { status, error }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_rejection!(JsonRejection, PathRejection, QueryRejection);
+
 /// [RucatServerError] displays in the same way as [RucatError]
 impl Display for RucatServerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.error)
     }
 }
 
+/// Errors are returned as JSON, so that clients can decode all responses in the same way.
 impl IntoResponse for RucatServerError {
     fn into_response(self) -> Response {
-        let status = self.get_status_code();
-        (status, self.to_string()).into_response()
+        let body = ErrorResponse {
+            code: *self.error.get_error_type(),
+            message: self.error.get_message(),
+            engine_id: self.error.get_engine_id().map(ToString::to_string),
+        };
+        (self.status, Json(body)).into_response()
     }
 }
 
@@ -57,6 +133,139 @@ mod tests {
 
     use super::*;
 
+    /// Construct an error of the type, and get the status code it is expected to be returned with.
+    /// This is a `match` without wildcard, so that new error types cannot be forgotten here.
+    fn error_with_status(error_type: RucatErrorType) -> (RucatError, StatusCode) {
+        let e = anyhow!("err_msg");
+        match error_type {
+            NotFound => (RucatError::not_found(e), StatusCode::NOT_FOUND),
+            Unauthorized => (RucatError::unauthorized(e), StatusCode::UNAUTHORIZED),
+            NotAllowed => (RucatError::not_allowed(e), StatusCode::FORBIDDEN),
+            MethodNotAllowed => (
+                RucatError::method_not_allowed(e),
+                StatusCode::METHOD_NOT_ALLOWED,
+            ),
+            Conflict => (RucatError::conflict(e), StatusCode::CONFLICT),
+            PreconditionFailed => (
+                RucatError::precondition_failed(e),
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            InvalidRequest => (RucatError::invalid_request(e), StatusCode::BAD_REQUEST),
+            InvalidArgument => (
+                RucatError::invalid_argument(e),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            ServiceUnavailable => (
+                RucatError::service_unavailable(e),
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            TooManyRequests => (
+                RucatError::too_many_requests(e),
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
+            PayloadTooLarge => (
+                RucatError::payload_too_large(e),
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+            RequestTimeout => (RucatError::request_timeout(e), StatusCode::REQUEST_TIMEOUT),
+            Timeout => (RucatError::timeout(e), StatusCode::GATEWAY_TIMEOUT),
+            FailToStartServer => (
+                RucatError::fail_to_start_server(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            FailToStartStateMonitor => (
+                RucatError::fail_to_start_state_monitor(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            FailToStartEngine => (
+                RucatError::fail_to_start_engine(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            FailToDeleteEngine => (
+                RucatError::fail_to_delete_engine(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            FailToConnectDatabase => (
+                RucatError::fail_to_connect_database(e),
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            FailToUpdateDatabase => (
+                RucatError::fail_to_update_database(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            FailToReadDatabase => (
+                RucatError::fail_to_read_database(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            DatabaseTimeout => (
+                RucatError::database_timeout(e),
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            FailToWriteBlob => (
+                RucatError::fail_to_write_blob(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            FailToReadBlob => (
+                RucatError::fail_to_read_blob(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            FailToDeleteBlob => (
+                RucatError::fail_to_delete_blob(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            FailToReadLogs => (
+                RucatError::fail_to_read_logs(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            FailToReadEvents => (
+                RucatError::fail_to_read_events(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            FailToLoadConfig => (
+                RucatError::fail_to_load_config(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+        }
+    }
+
+    #[test]
+    fn status_code_of_every_error_type() {
+        for error_type in [
+            NotFound,
+            Unauthorized,
+            NotAllowed,
+            MethodNotAllowed,
+            Conflict,
+            PreconditionFailed,
+            InvalidRequest,
+            InvalidArgument,
+            ServiceUnavailable,
+            TooManyRequests,
+            PayloadTooLarge,
+            RequestTimeout,
+            Timeout,
+            FailToStartServer,
+            FailToStartStateMonitor,
+            FailToStartEngine,
+            FailToDeleteEngine,
+            FailToConnectDatabase,
+            FailToUpdateDatabase,
+            FailToReadDatabase,
+            DatabaseTimeout,
+            FailToWriteBlob,
+            FailToReadBlob,
+            FailToDeleteBlob,
+            FailToReadLogs,
+            FailToReadEvents,
+            FailToLoadConfig,
+        ] {
+            let (error, status) = error_with_status(error_type);
+            assert_eq!(error.get_error_type(), &error_type);
+            let response = RucatServerError::from(error).into_response();
+            assert_eq!(response.status(), status, "status of {:?}", error_type);
+        }
+    }
+
     #[test]
     fn display_error() {
         let error: RucatServerError =
@@ -0,0 +1,21 @@
+//! Extractors rejecting invalid requests with [RucatServerError],
+//! so that the error responses are JSON as the other errors.
+
+use axum::extract::{FromRequest, FromRequestParts};
+
+use crate::error::RucatServerError;
+
+/// Same as [axum::Json] for extracting the request body.
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(RucatServerError))]
+pub(crate) struct AppJson<T>(pub T);
+
+/// Same as [axum::extract::Path].
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(RucatServerError))]
+pub(crate) struct AppPath<T>(pub T);
+
+/// Same as [axum::extract::Query].
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(RucatServerError))]
+pub(crate) struct AppQuery<T>(pub T);
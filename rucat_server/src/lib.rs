@@ -1,64 +1,638 @@
-use ::std::sync::Arc;
+use ::std::{collections::BTreeSet, net::SocketAddr, num::NonZeroU64, sync::Arc, time::Duration};
 
 use ::axum_extra::middleware::option_layer;
 use ::rucat_common::{
-    config::DatabaseVariant, database::Database, error::Result, serde::Deserialize,
+    anyhow::anyhow,
+    blob_store::BlobStoreClient,
+    build_info::BuildInfo,
+    clock::{Clock, SystemClock},
+    config::{BlobStoreVariant, DatabaseVariant},
+    database::Database,
+    engine::{
+        EngineFeature, EngineVersions, IdGenerator, RandomIdGenerator,
+        DEFAULT_MAX_ENGINE_NAME_LENGTH,
+    },
+    error::{Result, RucatError},
+    serde::{Deserialize, Serialize},
+    webhook::WebhookConfig,
+};
+use admin::router::get_admin_router;
+use authentication::{
+    api_key_auth_provider::ApiKeyAuthConfig,
+    auth,
+    htpasswd_auth_provider::HtpasswdAuthConfig,
+    jwt_auth_provider::JwtAuthConfig,
+    ldap_auth_provider::LdapAuthConfig,
+    oidc_auth_provider::OidcAuthConfig,
+    registry::{AuthConfigSource, DynAuthProvider},
+    role::authorize,
+    static_auth_provider::StaticAuthConfig,
+    validate_exempt_paths, AuthState,
 };
-use authentication::{auth, Authenticate};
-use axum::{extract::State, middleware, routing::get, Router};
-use engine::router::get_engine_router;
+use axum::{
+    extract::State,
+    http::{header::ACCEPT, HeaderMap, Method, Uri},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use compression::compress_responses;
+use cors::CorsConfig;
+use engine::router::{get_engine_router, get_inspector_router};
+use error::RucatServerError;
+use login_lockout::LoginLockoutConfig;
+use metrics::serve_metrics;
+use openapi::{serve_openapi, OPENAPI_PATH};
+use path_normalization::PathNormalization;
+use project::validate_projects;
+use rate_limit::{rate_limit, RateLimitConfig};
+use request_id::trace_requests;
+use request_limit::{limit_requests, RequestLimits};
+use resource_inspector::ResourceInspector;
+use route_index::IndexedRouter;
+use shutdown::{reject_mutation_on_shutdown, ShutdownHandle};
 use state::AppState;
-use tower_http::trace::TraceLayer;
+use tls::TlsConfig;
+use ui::UiConfig;
 
+pub(crate) mod admin;
 pub mod authentication;
+pub(crate) mod compression;
+pub mod cors;
 pub(crate) mod engine;
 pub(crate) mod error;
+pub(crate) mod extract;
+pub mod login_lockout;
+pub(crate) mod metrics;
+pub(crate) mod openapi;
+pub mod path_normalization;
+pub(crate) mod project;
+pub mod rate_limit;
+pub(crate) mod request_id;
+pub mod request_limit;
+pub mod resource_inspector;
+pub(crate) mod route_index;
+pub mod shutdown;
 pub(crate) mod state;
+pub mod tls;
+pub mod ui;
 
 #[derive(Debug, Deserialize, PartialEq, Eq)]
 #[serde(crate = "rucat_common::serde")]
 pub enum AuthProviderVariant {
-    StaticAuthProviderConfig {
-        username: String,
-        password: String,
-        bearer_token: String,
-    },
+    StaticAuthProviderConfig(StaticAuthConfig),
+    /// Bearer tokens are JSON web tokens issued by an identity service.
+    JwtAuthProviderConfig(JwtAuthConfig),
+    /// Bearer tokens are JSON web tokens issued by an OpenID Connect issuer, whose keys are discovered.
+    OidcAuthProviderConfig(OidcAuthConfig),
+    /// Services are authenticated by their API keys in the `X-Api-Key` header.
+    ApiKeyAuthProviderConfig(ApiKeyAuthConfig),
+    /// Users are authenticated by binding to an LDAP server with their basic credentials.
+    Ldap(LdapAuthConfig),
+    /// Users are authenticated by their basic credentials in an Apache htpasswd file.
+    Htpasswd(HtpasswdAuthConfig),
+    /// Credentials are accepted by any of the providers, which are tried in order.
+    Any(Vec<AuthProviderVariant>),
 }
 
 /// Configuration for rucat server
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 #[serde(crate = "rucat_common::serde")]
 pub struct ServerConfig {
     pub auth_provider: Option<AuthProviderVariant>,
     pub database: DatabaseVariant,
+    /// Store of the blobs of engines, e.g. the archived driver logs, which are deleted with the engines.
+    /// Logs cannot be archived if it is not set.
+    pub blob_store: Option<BlobStoreVariant>,
+    /// Whether engines deployed without rucat can be adopted by `POST /engine/adopt`.
+    #[serde(default)]
+    pub adopt_engines: bool,
+    /// Whether the driver logs and the resource events of engines are served
+    /// at `GET /engine/{id}/logs` and `GET /engine/{id}/k8s-events`.
+    #[serde(default)]
+    pub engine_diagnostics: bool,
+    /// Normalize request paths before routing, paths are matched exactly if it is not set.
+    pub path_normalization: Option<PathNormalization>,
+    /// Serve the built-in web UI at `/ui`, the UI is not served if it is not set.
+    pub ui: Option<UiConfig>,
+    /// Limits on the engines managed by the server.
+    #[serde(default)]
+    pub engine_limits: EngineLimits,
+    /// Engine versions that can be created and restarted, all the built-in versions are supported if not set.
+    #[serde(default)]
+    pub engine_versions: EngineVersions,
+    /// Socket address that the server listens on, e.g. `0.0.0.0:3000` or `[::]:3000`.
+    #[serde(default = "ServerConfig::default_bind_address")]
+    pub bind_address: String,
+    /// Serve HTTPS with the certificate and key, HTTP is served if it is not set.
+    pub tls: Option<TlsConfig>,
+    /// Maximum seconds to drain the in-flight requests on shutdown, they are dropped after it.
+    #[serde(default = "ServerConfig::default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+    /// Allow browser applications of other origins to call the REST API, only same origin requests are allowed if it is not set.
+    pub cors: Option<CorsConfig>,
+    /// Rate limit of the requests of each client, requests are not limited if it is not set.
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Server-sent events of the engine states at `GET /engine/{id}/events`.
+    #[serde(default)]
+    pub engine_events: EngineEvents,
+    /// Idempotency keys of creating engines.
+    #[serde(default)]
+    pub idempotency_keys: IdempotencyKeys,
+    /// State transition history of the engines at `GET /engine/{id}/history`.
+    #[serde(default)]
+    pub engine_history: EngineHistory,
+    /// Maximum bytes of a request body, larger requests are rejected with 413.
+    #[serde(default = "ServerConfig::default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// Maximum seconds of serving a request, slower requests are rejected with 408.
+    #[serde(default = "ServerConfig::default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    /// Webhooks notified of the engine state transitions, nothing is notified if not set.
+    pub webhooks: Option<WebhookConfig>,
+    /// Projects isolating the engines of teams, selected by the `X-Rucat-Project` header of each engine request.
+    /// The engines of a project run in the Kubernetes namespace named after it.
+    /// Engines are not isolated if it is not set.
+    pub projects: Option<BTreeSet<String>>,
+    /// Whether the responses are compressed if the client accepts gzip or brotli.
+    #[serde(default = "ServerConfig::default_compression")]
+    pub compression: bool,
+    /// Paths not authenticated, matched exactly or as a prefix of path segments,
+    /// e.g. `/admin` exempts `/admin` and `/admin/x`. Paths under `/engine` cannot be exempt.
+    #[serde(default)]
+    pub auth_exempt_paths: Vec<String>,
+    /// Reject the clients failing to log in too many times with 429 for a while, clients are never locked out if it is not set.
+    pub login_lockout: Option<LoginLockoutConfig>,
+}
+
+impl ServerConfig {
+    fn default_bind_address() -> String {
+        "127.0.0.1:3000".to_owned()
+    }
+
+    fn default_shutdown_timeout_secs() -> u64 {
+        30
+    }
+
+    fn default_max_body_bytes() -> usize {
+        RequestLimits::DEFAULT_MAX_BODY_BYTES
+    }
+
+    fn default_request_timeout_secs() -> u64 {
+        RequestLimits::DEFAULT_TIMEOUT_SECS
+    }
+
+    fn default_compression() -> bool {
+        true
+    }
+}
+
+/// Parse the `bind_address` of [ServerConfig].
+pub fn parse_bind_address(bind_address: &str) -> Result<SocketAddr> {
+    bind_address.parse().map_err(|e| {
+        RucatError::fail_to_start_server(anyhow!(
+            "Invalid bind address `{}`, expected `<ip>:<port>` such as `0.0.0.0:3000` or `[::]:3000`: {}",
+            bind_address,
+            e
+        ))
+    })
+}
+
+/// Limits on the engines managed by the server, requests exceeding them are rejected.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct EngineLimits {
+    /// Maximum number of characters in the name of an engine.
+    #[serde(default = "EngineLimits::default_max_name_length")]
+    pub max_name_length: usize,
+}
+
+impl EngineLimits {
+    fn default_max_name_length() -> usize {
+        DEFAULT_MAX_ENGINE_NAME_LENGTH
+    }
+}
+
+impl Default for EngineLimits {
+    fn default() -> Self {
+        Self {
+            max_name_length: Self::default_max_name_length(),
+        }
+    }
+}
+
+/// Server-sent events of the engine states, the state of each watched engine is polled from the database.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct EngineEvents {
+    /// Milliseconds between two polls of the state of a watched engine.
+    #[serde(default = "EngineEvents::default_poll_interval_millis")]
+    pub poll_interval_millis: NonZeroU64,
+}
+
+impl EngineEvents {
+    fn default_poll_interval_millis() -> NonZeroU64 {
+        NonZeroU64::new(1000).unwrap()
+    }
+
+    pub(crate) fn get_poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_millis.get())
+    }
+}
+
+impl Default for EngineEvents {
+    fn default() -> Self {
+        Self {
+            poll_interval_millis: Self::default_poll_interval_millis(),
+        }
+    }
+}
+
+/// Idempotency keys of creating engines, sent in the `Idempotency-Key` header,
+/// so that retried requests return the engine created by the first request instead of creating duplicates.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct IdempotencyKeys {
+    /// Seconds after which a key can be used to create another engine.
+    #[serde(default = "IdempotencyKeys::default_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl IdempotencyKeys {
+    fn default_ttl_secs() -> u64 {
+        24 * 60 * 60
+    }
+
+    pub(crate) fn get_ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+}
+
+impl Default for IdempotencyKeys {
+    fn default() -> Self {
+        Self {
+            ttl_secs: Self::default_ttl_secs(),
+        }
+    }
+}
+
+/// State transition history of the engines, recorded by the server and the state monitors.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct EngineHistory {
+    /// Seconds to keep the history of a deleted engine, after which it is purged by `POST /engine/cleanup`.
+    #[serde(default = "EngineHistory::default_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl EngineHistory {
+    fn default_ttl_secs() -> u64 {
+        7 * 24 * 60 * 60
+    }
+
+    pub(crate) fn get_ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+}
+
+impl Default for EngineHistory {
+    fn default() -> Self {
+        Self {
+            ttl_secs: Self::default_ttl_secs(),
+        }
+    }
+}
+
+/// Rules that the engines managed by the server and the clients managing them must follow.
+#[derive(Debug, Clone)]
+pub struct EnginePolicy {
+    pub limits: EngineLimits,
+    /// Status of the engine versions, e.g. deprecated versions are created with warnings.
+    pub versions: EngineVersions,
+    /// Rate limit of the requests of each client, requests are not limited if it is not set.
+    pub rate_limit: Option<RateLimitConfig>,
+    /// How the engine state events are watched.
+    pub events: EngineEvents,
+    /// How long the idempotency keys of creating engines are kept.
+    pub idempotency_keys: IdempotencyKeys,
+    /// How long the history of deleted engines is kept.
+    pub history: EngineHistory,
+    /// Limits on the body size and the duration of each request.
+    pub request_limits: RequestLimits,
+    /// Webhooks notified of the engine state transitions, nothing is notified if not set.
+    pub webhooks: Option<WebhookConfig>,
+    /// Projects isolating the engines of teams, engines are not isolated if it is not set.
+    pub projects: Option<BTreeSet<String>>,
+    /// Whether the responses are compressed if the client accepts gzip or brotli.
+    pub compression: bool,
+    /// Paths not authenticated, in addition to the probes, e.g. `/capabilities`. Paths under `/engine` cannot be exempt.
+    pub auth_exempt_paths: Vec<String>,
+    /// Lockout of the clients failing to log in too many times, clients are never locked out if it is not set.
+    pub login_lockout: Option<LoginLockoutConfig>,
+}
+
+impl Default for EnginePolicy {
+    fn default() -> Self {
+        Self {
+            limits: EngineLimits::default(),
+            versions: EngineVersions::default(),
+            rate_limit: None,
+            events: EngineEvents::default(),
+            idempotency_keys: IdempotencyKeys::default(),
+            history: EngineHistory::default(),
+            request_limits: RequestLimits::default(),
+            webhooks: None,
+            projects: None,
+            compression: true,
+            auth_exempt_paths: vec![],
+            login_lockout: None,
+        }
+    }
+}
+
+/// Sources of the current time, engine ids and the auth config, which are replaced in tests for deterministic results.
+#[derive(Clone)]
+pub struct Sources {
+    /// Source of the current time, e.g. the create time of engines.
+    pub clock: Arc<dyn Clock>,
+    /// Generator of the ids of created engines and adopted engines without an id.
+    pub id_generator: Arc<dyn IdGenerator>,
+    /// Source of the auth provider config read by `POST /admin/reload-auth`, the auth provider cannot be reloaded if it is not set.
+    pub auth_config: Option<Arc<dyn AuthConfigSource>>,
+}
+
+impl Default for Sources {
+    fn default() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            id_generator: Arc::new(RandomIdGenerator),
+            auth_config: None,
+        }
+    }
+}
+
+/// Description of the service returned by `GET /`, with links to the entry points of the API.
+#[derive(Serialize)]
+#[serde(crate = "rucat_common::serde")]
+struct ServiceDescriptor {
+    name: &'static str,
+    version: &'static str,
+    links: ServiceLinks,
+    /// Paths of all the routes served by the server.
+    routes: Vec<String>,
+}
+
+/// Links are only listed if their routes are served, e.g. `/metrics` with the `metrics` feature.
+#[derive(Serialize)]
+#[serde(crate = "rucat_common::serde")]
+struct ServiceLinks {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    engine: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    capabilities: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    liveness: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    readiness: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    openapi: Option<&'static str>,
+}
+
+impl ServiceDescriptor {
+    fn new(routes: Vec<String>) -> Self {
+        let link = |path: &'static str| routes.iter().any(|route| route == path).then_some(path);
+        let links = ServiceLinks {
+            engine: link("/engine"),
+            capabilities: link("/capabilities"),
+            liveness: link("/healthz"),
+            readiness: link("/readyz"),
+            version: link("/version"),
+            metrics: link("/metrics"),
+            openapi: link(OPENAPI_PATH),
+        };
+        Self {
+            name: "rucat",
+            version: env!("CARGO_PKG_VERSION"),
+            links,
+            routes,
+        }
+    }
+}
+
+/// Greeting returned by `GET /` to clients only accepting plain text, e.g. old smoke tests.
+const GREETING: &str = "welcome to rucat";
+
+async fn describe_service(
+    Extension(descriptor): Extension<Arc<ServiceDescriptor>>,
+    headers: HeaderMap,
+) -> Response {
+    let accepts_only_text = headers.get(ACCEPT).is_some_and(|accept| {
+        accept.to_str().is_ok_and(|accept| {
+            accept.contains("text/plain") && !accept.contains("json") && !accept.contains("*/*")
+        })
+    });
+    if accepts_only_text {
+        GREETING.into_response()
+    } else {
+        Json(descriptor).into_response()
+    }
+}
+
+/// Cargo features of the server enabled in the build.
+const ENABLED_FEATURES: &[&str] = &[
+    #[cfg(feature = "metrics")]
+    "metrics",
+    #[cfg(feature = "openapi")]
+    "openapi",
+    #[cfg(feature = "swagger-ui")]
+    "swagger-ui",
+    #[cfg(feature = "ui")]
+    "ui",
+];
+
+/// Version and build of the server.
+pub fn get_build_info() -> BuildInfo {
+    BuildInfo::new(env!("CARGO_PKG_VERSION"), ENABLED_FEATURES)
+}
+
+async fn version() -> Json<BuildInfo> {
+    Json(get_build_info())
+}
+
+/// Unknown routes get the same JSON error body as other errors.
+async fn route_not_found(uri: Uri) -> RucatServerError {
+    RucatError::not_found(anyhow!("Route {} not found", uri.path())).into()
+}
+
+/// Known routes with a wrong method, axum adds the `Allow` header listing the methods of the route.
+async fn method_not_allowed(method: Method, uri: Uri) -> RucatServerError {
+    RucatError::method_not_allowed(anyhow!(
+        "Method {} is not allowed for route {}",
+        method,
+        uri.path()
+    ))
+    .into()
+}
+
+/// Liveness of the server, which is always alive if it can respond.
+async fn liveness() -> &'static str {
+    "ok"
+}
+
+/// Readiness of the server.
+/// The server is not ready once the shutdown is triggered, so that load balancers stop routing to it,
+/// or if the database cannot be reached.
+async fn readiness<DB>(
+    State(state): State<AppState<DB>>,
+) -> std::result::Result<&'static str, RucatServerError>
+where
+    DB: Database,
+{
+    if state.get_shutdown_handle().is_shutting_down() {
+        return Err(RucatError::service_unavailable(anyhow!("Server is shutting down")).into());
+    }
+    state.get_db().health_check().await.map_err(|e| {
+        RucatError::service_unavailable(anyhow!("Database is not reachable: {}", e))
+    })?;
+    Ok("ready")
+}
+
+/// Capabilities of the server, e.g. the engine features that can be enabled.
+#[derive(Serialize)]
+#[serde(crate = "rucat_common::serde")]
+struct Capabilities {
+    features: &'static [EngineFeature],
+    /// Status of each engine version.
+    versions: EngineVersions,
+}
+
+async fn capabilities<DB>(State(state): State<AppState<DB>>) -> Json<Capabilities>
+where
+    DB: Database,
+{
+    Json(Capabilities {
+        features: EngineFeature::ALL,
+        versions: state.get_engine_versions().clone(),
+    })
 }
 
 /// This is the only entry for users to get the rucat server.
+/// # Parameters
+/// - `blob_store`: store of engine blobs, e.g. the archived driver logs, which are deleted together with the engine.
+/// - `resource_inspector`: inspector for adopting engines and reading their logs and events, both are disabled if it is `None`.
+/// - `shutdown_handle`: handle to notify the server that it is shutting down.
+/// - `sources`: sources of the current time and engine ids.
+/// - `engine_policy`: limits and version statuses of the engines managed by the server, the rate limit of the clients and the limits on each request.
 /// # Return the router for the server
-pub fn get_server<DB, AuthProvider>(
+pub fn get_server<DB, Inspector>(
     db_client: DB,
-    auth_provider: Option<AuthProvider>,
+    auth_provider: Option<DynAuthProvider>,
+    blob_store: Option<BlobStoreClient>,
+    resource_inspector: Option<Inspector>,
+    shutdown_handle: ShutdownHandle,
+    sources: Sources,
+    engine_policy: EnginePolicy,
 ) -> Result<Router>
 where
     DB: Database,
-    AuthProvider: Authenticate,
+    Inspector: ResourceInspector,
 {
-    let app_state = AppState::new(db_client);
+    if let Some(rate_limit) = &engine_policy.rate_limit {
+        rate_limit.validate()?;
+    }
+    if let Some(login_lockout) = &engine_policy.login_lockout {
+        login_lockout.validate()?;
+    }
+    if let Some(projects) = &engine_policy.projects {
+        validate_projects(projects)?;
+    }
+    let request_limits = engine_policy.request_limits;
+    let compression = engine_policy.compression;
+    validate_exempt_paths(&engine_policy.auth_exempt_paths)?;
+    let auth_exempt_paths = engine_policy.auth_exempt_paths.clone();
+    let app_state = AppState::new(
+        db_client,
+        auth_provider.clone(),
+        blob_store,
+        shutdown_handle.clone(),
+        sources,
+        engine_policy,
+    )?;
+    let engine_router = match resource_inspector {
+        Some(inspector) => get_engine_router().merge(get_inspector_router(inspector)),
+        None => get_engine_router(),
+    }
+    // Inside the authentication, which provides the roles of the client.
+    .map(|router| router.route_layer(middleware::from_fn(authorize)));
+
+    // The probes, the service description and the version are not authenticated, so that they can be called by Kubernetes.
+    // Neither is the API specification, which contains no engine data.
+    let public_router = serve_openapi(
+        IndexedRouter::new()
+            .route("/", get(describe_service))
+            .route("/healthz", get(liveness))
+            .route("/readyz", get(readiness::<DB>))
+            .route("/version", get(version)),
+    )
+    .map(|router| router.method_not_allowed_fallback(method_not_allowed));
 
     // go through the router from outer to inner
-    let router = Router::new()
-        .route(
-            "/",
-            get(|_: State<AppState<DB>>| async { "welcome to rucat" }),
-        )
-        .nest("/engine", get_engine_router())
-        // TODO: use tower::ServiceBuilder to build the middleware stack
-        // but need to be careful with the order of the middleware and the compatibility with axum::option_layer
-        .layer(option_layer(auth_provider.map(|auth_provider| {
-            middleware::from_fn_with_state(Arc::new(auth_provider), auth)
-        })))
-        .layer(TraceLayer::new_for_http())
+    let protected_router = IndexedRouter::new()
+        .route("/capabilities", get(capabilities::<DB>))
+        .nest("/engine", engine_router)
+        .nest("/admin", get_admin_router())
+        .map(|router| {
+            router
+                .fallback(route_not_found)
+                .method_not_allowed_fallback(method_not_allowed)
+                .layer(middleware::from_fn_with_state(
+                    shutdown_handle,
+                    reject_mutation_on_shutdown,
+                ))
+                // Inside the authentication, so that clients are limited by the authenticated user.
+                .layer(option_layer(app_state.get_rate_limiter().map(
+                    |rate_limiter| middleware::from_fn_with_state(rate_limiter.clone(), rate_limit),
+                )))
+                // TODO: use tower::ServiceBuilder to build the middleware stack
+                // but need to be careful with the order of the middleware and the compatibility with axum::option_layer
+                .layer(option_layer(auth_provider.map(|auth_provider| {
+                    middleware::from_fn_with_state(
+                        AuthState::new(
+                            auth_provider,
+                            auth_exempt_paths,
+                            app_state.get_login_lockout().cloned(),
+                            Some(app_state.get_metrics().clone()),
+                        ),
+                        auth,
+                    )
+                })))
+        });
+
+    // Metrics are not authenticated either, so that they can be scraped by Prometheus.
+    let (router, routes) = serve_metrics(
+        public_router
+            .merge(protected_router)
+            .map(|router| limit_requests(router, request_limits)),
+        app_state.get_metrics().clone(),
+    )
+    .into_parts();
+    let router = if compression {
+        compress_responses(router)
+    } else {
+        router
+    };
+    let router = trace_requests(router)
+        .layer(Extension(Arc::new(ServiceDescriptor::new(routes))))
         .with_state(app_state);
     Ok(router)
 }
@@ -67,8 +641,15 @@ where
 mod tests {
     use ::rucat_common::{
         anyhow::Result,
+        config::{ConnectRetry, SurrealConfig},
+        engine::{EngineType, VersionStatus},
         serde_json::{from_value, json},
     };
+    use authentication::{
+        ldap_auth_provider::LdapSearchConfig, role::Role, static_auth_provider::SingleUserConfig,
+    };
+    use axum::http::{header::AUTHORIZATION, HeaderValue};
+    use cors::Allowed;
 
     use super::*;
 
@@ -89,12 +670,68 @@ mod tests {
             result,
             ServerConfig {
                 auth_provider: None,
-                database: DatabaseVariant::Surreal {
+                database: DatabaseVariant::Surreal(Box::new(SurrealConfig {
                     credentials: None,
-                    uri: "".to_string()
+                    uri: "".to_string(),
+                    connect_retry: ConnectRetry::default(),
+                    namespace: "rucat".to_owned(),
+                    database: "rucat".to_owned(),
+                    table: "engines".to_owned(),
+                    tls: false,
+                    ca_path: None,
+                    query_timeout_secs: 30
+                })),
+                blob_store: None,
+                adopt_engines: false,
+                engine_diagnostics: false,
+                path_normalization: None,
+                ui: None,
+                engine_limits: EngineLimits::default(),
+                engine_versions: EngineVersions::default(),
+                bind_address: "127.0.0.1:3000".to_owned(),
+                tls: None,
+                shutdown_timeout_secs: 30,
+                cors: None,
+                rate_limit: None,
+                engine_events: EngineEvents::default(),
+                idempotency_keys: IdempotencyKeys::default(),
+                engine_history: EngineHistory::default(),
+                max_body_bytes: 1024 * 1024,
+                request_timeout_secs: 60,
+                webhooks: None,
+                projects: None,
+                compression: true,
+                auth_exempt_paths: vec![],
+                login_lockout: None,
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_blob_store() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "blob_store": {
+                    "FileSystem": {
+                        "root": "/tmp/rucat"
+                    }
                 }
             }
         );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.blob_store,
+            Some(BlobStoreVariant::FileSystem {
+                root: "/tmp/rucat".to_string()
+            })
+        );
+        Ok(())
     }
 
     #[test]
@@ -130,7 +767,7 @@ mod tests {
         let result = from_value::<ServerConfig>(config);
         assert_eq!(
             result.unwrap_err().to_string(),
-            "unknown field `unknown_field`, expected `auth_provider` or `database`"
+            "unknown field `unknown_field`, expected one of `auth_provider`, `database`, `blob_store`, `adopt_engines`, `engine_diagnostics`, `path_normalization`, `ui`, `engine_limits`, `engine_versions`, `bind_address`, `tls`, `shutdown_timeout_secs`, `cors`, `rate_limit`, `engine_events`, `idempotency_keys`, `engine_history`, `max_body_bytes`, `request_timeout_secs`, `webhooks`, `projects`, `compression`, `auth_exempt_paths`, `login_lockout`"
         );
     }
 
@@ -157,17 +794,732 @@ mod tests {
         assert_eq!(
             result,
             ServerConfig {
-                auth_provider: Some(AuthProviderVariant::StaticAuthProviderConfig {
-                    username: "admin".to_string(),
-                    password: "123".to_string(),
-                    bearer_token: "abc".to_string()
-                }),
-                database: DatabaseVariant::Surreal {
+                auth_provider: Some(AuthProviderVariant::StaticAuthProviderConfig(
+                    StaticAuthConfig::SingleUser(SingleUserConfig {
+                        username: "admin".to_string(),
+                        password: Some("123".into()),
+                        password_hash: None,
+                        bearer_token: Some("abc".into()),
+                        bearer_token_hash: None,
+                        role: Role::Admin,
+                    })
+                )),
+                database: DatabaseVariant::Surreal(Box::new(SurrealConfig {
                     credentials: None,
-                    uri: "".to_string()
+                    uri: "".to_string(),
+                    connect_retry: ConnectRetry::default(),
+                    namespace: "rucat".to_owned(),
+                    database: "rucat".to_owned(),
+                    table: "engines".to_owned(),
+                    tls: false,
+                    ca_path: None,
+                    query_timeout_secs: 30
+                })),
+                blob_store: None,
+                adopt_engines: false,
+                engine_diagnostics: false,
+                path_normalization: None,
+                ui: None,
+                engine_limits: EngineLimits::default(),
+                engine_versions: EngineVersions::default(),
+                bind_address: "127.0.0.1:3000".to_owned(),
+                tls: None,
+                shutdown_timeout_secs: 30,
+                cors: None,
+                rate_limit: None,
+                engine_events: EngineEvents::default(),
+                idempotency_keys: IdempotencyKeys::default(),
+                engine_history: EngineHistory::default(),
+                max_body_bytes: 1024 * 1024,
+                request_timeout_secs: 60,
+                webhooks: None,
+                projects: None,
+                compression: true,
+                auth_exempt_paths: vec![],
+                login_lockout: None,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_jwt_auth_provider() -> Result<()> {
+        let config = json!(
+            {
+                "auth_provider": {
+                    "JwtAuthProviderConfig": {
+                        "algorithm": "RS256",
+                        "public_key_path": "/etc/rucat/jwt.pem",
+                        "issuer": "https://id.example.com"
+                    }
+                },
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.auth_provider,
+            Some(AuthProviderVariant::JwtAuthProviderConfig(JwtAuthConfig {
+                algorithm: jsonwebtoken::Algorithm::RS256,
+                secret: None,
+                public_key_path: Some("/etc/rucat/jwt.pem".to_owned()),
+                audience: None,
+                issuer: Some("https://id.example.com".to_owned()),
+            }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_any_auth_provider() -> Result<()> {
+        let config = json!(
+            {
+                "auth_provider": {
+                    "Any": [
+                        {
+                            "StaticAuthProviderConfig": {
+                                "username": "admin",
+                                "password": "admin",
+                                "bearer_token": "admin"
+                            }
+                        },
+                        {
+                            "Any": [
+                                {
+                                    "JwtAuthProviderConfig": {
+                                        "algorithm": "HS256",
+                                        "secret": "secret"
+                                    }
+                                }
+                            ]
+                        }
+                    ]
+                },
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.auth_provider,
+            Some(AuthProviderVariant::Any(vec![
+                AuthProviderVariant::StaticAuthProviderConfig(StaticAuthConfig::SingleUser(
+                    SingleUserConfig {
+                        username: "admin".to_owned(),
+                        password: Some("admin".into()),
+                        password_hash: None,
+                        bearer_token: Some("admin".into()),
+                        bearer_token_hash: None,
+                        role: Role::Admin,
+                    }
+                )),
+                AuthProviderVariant::Any(vec![AuthProviderVariant::JwtAuthProviderConfig(
+                    JwtAuthConfig {
+                        algorithm: jsonwebtoken::Algorithm::HS256,
+                        secret: Some("secret".to_owned()),
+                        public_key_path: None,
+                        audience: None,
+                        issuer: None,
+                    }
+                )]),
+            ]))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_htpasswd_auth_provider() -> Result<()> {
+        let config = json!(
+            {
+                "auth_provider": {
+                    "Htpasswd": {
+                        "path": "/etc/rucat/htpasswd",
+                        "reload_interval_secs": 30
+                    }
+                },
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.auth_provider,
+            Some(AuthProviderVariant::Htpasswd(HtpasswdAuthConfig {
+                path: "/etc/rucat/htpasswd".to_owned(),
+                reload_interval_secs: Some(30),
+                role: Role::Admin,
+            }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_ldap_auth_provider() -> Result<()> {
+        let config = json!(
+            {
+                "auth_provider": {
+                    "Ldap": {
+                        "url": "ldap://ldap.example.com",
+                        "user_dn_template": "uid={username},ou=people,dc=example,dc=com",
+                        "starttls": true,
+                        "bind_timeout_secs": 3,
+                        "role": "viewer"
+                    }
+                },
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.auth_provider,
+            Some(AuthProviderVariant::Ldap(LdapAuthConfig {
+                url: "ldap://ldap.example.com".to_owned(),
+                user_dn_template: Some("uid={username},ou=people,dc=example,dc=com".to_owned()),
+                search: None,
+                starttls: true,
+                bind_timeout_secs: 3,
+                role: Role::Viewer,
+            }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_ldap_auth_provider_with_search() -> Result<()> {
+        let config = json!(
+            {
+                "auth_provider": {
+                    "Ldap": {
+                        "url": "ldaps://ldap.example.com",
+                        "search": {
+                            "base": "ou=people,dc=example,dc=com",
+                            "filter": "(&(objectClass=person)(mail={username}))",
+                            "bind_dn": "cn=rucat,dc=example,dc=com",
+                            "bind_password": "pwd"
+                        }
+                    }
+                },
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.auth_provider,
+            Some(AuthProviderVariant::Ldap(LdapAuthConfig {
+                url: "ldaps://ldap.example.com".to_owned(),
+                user_dn_template: None,
+                search: Some(LdapSearchConfig {
+                    base: "ou=people,dc=example,dc=com".to_owned(),
+                    filter: "(&(objectClass=person)(mail={username}))".to_owned(),
+                    bind_dn: Some("cn=rucat,dc=example,dc=com".to_owned()),
+                    bind_password: Some("pwd".to_owned()),
+                }),
+                starttls: false,
+                bind_timeout_secs: 5,
+                role: Role::Admin,
+            }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_path_normalization() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "path_normalization": {
+                    "lowercase_prefix": true,
+                    "mode": "Redirect"
                 }
             }
         );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.path_normalization,
+            Some(PathNormalization {
+                lowercase_prefix: true,
+                mode: path_normalization::NormalizationMode::Redirect,
+            })
+        );
+        let result = from_value::<PathNormalization>(json!({}))?;
+        assert_eq!(result, PathNormalization::default());
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_engine_limits() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "engine_limits": {
+                    "max_name_length": 16
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.engine_limits,
+            EngineLimits {
+                max_name_length: 16
+            }
+        );
+        let result = from_value::<EngineLimits>(json!({}))?;
+        assert_eq!(result, EngineLimits::default());
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_engine_versions() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "engine_versions": {
+                    "Spark": {
+                        "3.5.4": "supported"
+                    }
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result
+                .engine_versions
+                .get_status(&EngineType::Spark, "3.5.4"),
+            Some(&VersionStatus::Supported)
+        );
+        // versions not listed are not supported
+        assert_eq!(
+            result
+                .engine_versions
+                .get_status(&EngineType::Spark, "3.5.3"),
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_bind_address() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "bind_address": "0.0.0.0:8080"
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(result.bind_address, "0.0.0.0:8080");
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_request_limits() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "max_body_bytes": 4096,
+                "request_timeout_secs": 10
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(result.max_body_bytes, 4096);
+        assert_eq!(result.request_timeout_secs, 10);
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_tls() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "tls": {
+                    "cert_path": "/etc/rucat/tls.crt",
+                    "key_path": "/etc/rucat/tls.key"
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.tls,
+            Some(TlsConfig {
+                cert_path: "/etc/rucat/tls.crt".to_owned(),
+                key_path: "/etc/rucat/tls.key".to_owned(),
+                client_ca_path: None,
+                require_client_cert: false,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_mutual_tls() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "tls": {
+                    "cert_path": "/etc/rucat/tls.crt",
+                    "key_path": "/etc/rucat/tls.key",
+                    "client_ca_path": "/etc/rucat/client-ca.crt",
+                    "require_client_cert": true
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.tls,
+            Some(TlsConfig {
+                cert_path: "/etc/rucat/tls.crt".to_owned(),
+                key_path: "/etc/rucat/tls.key".to_owned(),
+                client_ca_path: Some("/etc/rucat/client-ca.crt".to_owned()),
+                require_client_cert: true,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_shutdown_timeout() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "shutdown_timeout_secs": 5
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(result.shutdown_timeout_secs, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_cors() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "cors": {
+                    "allowed_origins": ["https://dashboard.example.com"],
+                    "allowed_methods": ["GET", "POST"],
+                    "allowed_headers": ["authorization"],
+                    "allow_credentials": true
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.cors,
+            Some(CorsConfig {
+                allowed_origins: Allowed::Only(vec![HeaderValue::from_static(
+                    "https://dashboard.example.com"
+                )]),
+                allowed_methods: Allowed::Only(vec![Method::GET, Method::POST]),
+                allowed_headers: Allowed::Only(vec![AUTHORIZATION]),
+                allow_credentials: true,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_rate_limit() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "rate_limit": {
+                    "requests_per_second": 0.5
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.rate_limit,
+            Some(RateLimitConfig {
+                requests_per_second: 0.5,
+                burst: 20,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_login_lockout() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "login_lockout": {
+                    "max_failures": 3
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.login_lockout,
+            Some(LoginLockoutConfig {
+                max_failures: 3,
+                window_secs: 60,
+                lockout_secs: 300,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_engine_events() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "engine_events": {
+                    "poll_interval_millis": 200
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(
+            result.engine_events.get_poll_interval(),
+            Duration::from_millis(200)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_idempotency_keys() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "idempotency_keys": {
+                    "ttl_secs": 600
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(result.idempotency_keys.get_ttl(), Duration::from_secs(600));
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_engine_history() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "engine_history": {
+                    "ttl_secs": 3600
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(result.engine_history.get_ttl(), Duration::from_secs(3600));
+        let result = from_value::<ServerConfig>(json!({"database": {"Surreal": {"uri": ""}}}))?;
+        assert_eq!(result.engine_history, EngineHistory::default());
+        assert_eq!(result.engine_history.ttl_secs, 7 * 24 * 60 * 60);
+        Ok(())
+    }
+
+    #[test]
+    fn engine_events_zero_poll_interval() {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "engine_events": {
+                    "poll_interval_millis": 0
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid value: integer `0`, expected a nonzero u64"
+        );
+    }
+
+    #[test]
+    fn cors_wildcard_origin_with_credentials() {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "cors": {
+                    "allowed_origins": ["*"],
+                    "allow_credentials": true
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "`*` in `allowed_origins` cannot be combined with `allow_credentials`"
+        );
+    }
+
+    #[test]
+    fn tls_missing_key_path() {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "tls": {
+                    "cert_path": "/etc/rucat/tls.crt"
+                }
+            }
+        );
+        let result = from_value::<ServerConfig>(config);
+        assert_eq!(result.unwrap_err().to_string(), "missing field `key_path`");
+    }
+
+    #[test]
+    fn parse_valid_bind_addresses() -> Result<()> {
+        assert_eq!(
+            parse_bind_address(&ServerConfig::default_bind_address())?,
+            SocketAddr::from(([127, 0, 0, 1], 3000))
+        );
+        assert_eq!(
+            parse_bind_address("0.0.0.0:3000")?,
+            SocketAddr::from(([0, 0, 0, 0], 3000))
+        );
+        assert_eq!(
+            parse_bind_address("[::]:3000")?,
+            SocketAddr::from(([0u16; 8], 3000))
+        );
+        assert_eq!(
+            parse_bind_address("[::1]:8080")?,
+            SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], 8080))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_invalid_bind_addresses() {
+        for address in ["", "localhost:3000", "0.0.0.0", "0.0.0.0:70000", "::1:3000"] {
+            let result = parse_bind_address(address);
+            assert!(
+                result.is_err_and(|e| e.to_string().starts_with(&format!(
+                    "Fail to start server: Invalid bind address `{}`, expected `<ip>:<port>`",
+                    address
+                ))),
+                "{}",
+                address
+            );
+        }
+    }
+
+    #[test]
+    fn deserialize_ui() -> Result<()> {
+        let config = json!(
+            {
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri": ""
+                    }
+                },
+                "ui": {}
+            }
+        );
+        let result = from_value::<ServerConfig>(config)?;
+        assert_eq!(result.ui, Some(UiConfig::default()));
+        let result = from_value::<UiConfig>(json!({"require_auth": true}))?;
+        assert!(result.require_auth);
         Ok(())
     }
 }
@@ -0,0 +1,265 @@
+//! Lockout of the clients failing to log in too many times, so that passwords cannot be brute forced.
+//!
+//! Failures are counted for each username and for each remote IP. Once either of them fails too many times within the window,
+//! the requests of it are rejected with 429 until the lockout ends, even if their credentials are correct.
+
+use ::std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use ::rucat_common::{
+    anyhow::anyhow,
+    error::{Result, RucatError},
+    serde::Deserialize,
+    tokio::time::Instant,
+};
+
+/// Configuration of the lockout of the clients failing to log in.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct LoginLockoutConfig {
+    /// Number of failed logins within the window that locks the client out.
+    #[serde(default = "LoginLockoutConfig::default_max_failures")]
+    pub max_failures: u32,
+    /// Seconds within which the failed logins are counted.
+    #[serde(default = "LoginLockoutConfig::default_window_secs")]
+    pub window_secs: u64,
+    /// Seconds that the locked out client is rejected for.
+    #[serde(default = "LoginLockoutConfig::default_lockout_secs")]
+    pub lockout_secs: u64,
+}
+
+impl LoginLockoutConfig {
+    fn default_max_failures() -> u32 {
+        5
+    }
+
+    fn default_window_secs() -> u64 {
+        60
+    }
+
+    fn default_lockout_secs() -> u64 {
+        300
+    }
+
+    /// Check the client is allowed at least one failure, and the window and the lockout are not empty.
+    pub fn validate(&self) -> Result<()> {
+        if self.max_failures == 0 || self.window_secs == 0 || self.lockout_secs == 0 {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "Login lockout needs a positive `max_failures`, `window_secs` and `lockout_secs`, but got max_failures {}, window_secs {} and lockout_secs {}",
+                self.max_failures,
+                self.window_secs,
+                self.lockout_secs
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Failed logins of a client.
+#[derive(Debug)]
+struct Failures {
+    /// Number of failures since the start of the window.
+    count: u32,
+    window_start: Instant,
+    /// Time when the lockout of the client ends, if it is locked out.
+    locked_until: Option<Instant>,
+}
+
+impl Failures {
+    /// Whether the failures are forgotten at `now`, which is the same as no failure.
+    fn is_expired(&self, now: Instant, window: Duration) -> bool {
+        match self.locked_until {
+            Some(locked_until) => locked_until <= now,
+            None => self.window_start + window <= now,
+        }
+    }
+}
+
+/// Failed logins of each client, keyed by `user:{username}` or `ip:{address}`.
+pub(crate) struct LoginLockout {
+    max_failures: u32,
+    window: Duration,
+    lockout: Duration,
+    failures: Mutex<HashMap<String, Failures>>,
+}
+
+impl LoginLockout {
+    /// Clients whose failures are expired are forgotten once there are this many clients.
+    const MAX_CLIENTS: usize = 10_000;
+
+    pub(crate) fn new(config: &LoginLockoutConfig) -> Self {
+        Self {
+            max_failures: config.max_failures,
+            window: Duration::from_secs(config.window_secs),
+            lockout: Duration::from_secs(config.lockout_secs),
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check none of the `clients` is locked out, return the time to wait before retrying if any is.
+    pub(crate) fn check<'a>(
+        &self,
+        clients: impl IntoIterator<Item = &'a str>,
+    ) -> std::result::Result<(), Duration> {
+        let now = Instant::now();
+        let failures = self.failures.lock().unwrap();
+        let wait = clients
+            .into_iter()
+            .filter_map(|client| failures.get(client)?.locked_until)
+            .map(|locked_until| locked_until.saturating_duration_since(now))
+            .max()
+            .unwrap_or_default();
+        if wait.is_zero() {
+            Ok(())
+        } else {
+            Err(wait)
+        }
+    }
+
+    /// Count a failed login of the `clients`, locking out the ones failing too many times within the window.
+    pub(crate) fn record_failure<'a>(&self, clients: impl IntoIterator<Item = &'a str>) {
+        let now = Instant::now();
+        let mut failures = self.failures.lock().unwrap();
+        if failures.len() >= Self::MAX_CLIENTS {
+            failures.retain(|_, failures| !failures.is_expired(now, self.window));
+        }
+        for client in clients {
+            let client_failures = failures.entry(client.to_owned()).or_insert(Failures {
+                count: 0,
+                window_start: now,
+                locked_until: None,
+            });
+            if client_failures.is_expired(now, self.window) {
+                *client_failures = Failures {
+                    count: 0,
+                    window_start: now,
+                    locked_until: None,
+                };
+            }
+            client_failures.count += 1;
+            if client_failures.count >= self.max_failures {
+                client_failures.locked_until = Some(now + self.lockout);
+            }
+        }
+    }
+
+    /// Forget the failed logins of the `clients`, which have logged in successfully.
+    pub(crate) fn record_success<'a>(&self, clients: impl IntoIterator<Item = &'a str>) {
+        let mut failures = self.failures.lock().unwrap();
+        for client in clients {
+            failures.remove(client);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::rucat_common::tokio;
+
+    use super::*;
+
+    fn get_login_lockout() -> LoginLockout {
+        LoginLockout::new(&LoginLockoutConfig {
+            max_failures: 3,
+            window_secs: 60,
+            lockout_secs: 300,
+        })
+    }
+
+    #[test]
+    fn validate_config() {
+        assert!(LoginLockoutConfig {
+            max_failures: 1,
+            window_secs: 1,
+            lockout_secs: 1
+        }
+        .validate()
+        .is_ok());
+        for (max_failures, window_secs, lockout_secs) in [(0, 1, 1), (1, 0, 1), (1, 1, 0)] {
+            let result = LoginLockoutConfig {
+                max_failures,
+                window_secs,
+                lockout_secs,
+            }
+            .validate();
+            assert!(result.is_err_and(|e| {
+                e.to_string().starts_with(
+                    "Fail to load config: Login lockout needs a positive `max_failures`, `window_secs` and `lockout_secs`",
+                )
+            }));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lock_out_after_max_failures() {
+        let login_lockout = get_login_lockout();
+        for _ in 0..2 {
+            login_lockout.record_failure(["user:a"]);
+            assert_eq!(login_lockout.check(["user:a"]), Ok(()));
+        }
+        login_lockout.record_failure(["user:a"]);
+        assert_eq!(
+            login_lockout.check(["user:a"]),
+            Err(Duration::from_secs(300))
+        );
+        // other clients are not locked out
+        assert_eq!(login_lockout.check(["user:b", "ip:127.0.0.1"]), Ok(()));
+
+        tokio::time::advance(Duration::from_secs(100)).await;
+        assert_eq!(
+            login_lockout.check(["user:b", "user:a"]),
+            Err(Duration::from_secs(200))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lockout_expires() {
+        let login_lockout = get_login_lockout();
+        for _ in 0..3 {
+            login_lockout.record_failure(["user:a"]);
+        }
+        tokio::time::advance(Duration::from_secs(300)).await;
+        assert_eq!(login_lockout.check(["user:a"]), Ok(()));
+
+        // the failures before the lockout are not counted again
+        login_lockout.record_failure(["user:a"]);
+        assert_eq!(login_lockout.check(["user:a"]), Ok(()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn forget_failures_out_of_window() {
+        let login_lockout = get_login_lockout();
+        for _ in 0..2 {
+            login_lockout.record_failure(["user:a"]);
+        }
+        tokio::time::advance(Duration::from_secs(60)).await;
+        login_lockout.record_failure(["user:a"]);
+        assert_eq!(login_lockout.check(["user:a"]), Ok(()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn success_resets_failures() {
+        let login_lockout = get_login_lockout();
+        for _ in 0..2 {
+            login_lockout.record_failure(["user:a", "ip:127.0.0.1"]);
+        }
+        login_lockout.record_success(["user:a", "ip:127.0.0.1"]);
+        for _ in 0..2 {
+            login_lockout.record_failure(["user:a", "ip:127.0.0.1"]);
+        }
+        assert_eq!(login_lockout.check(["user:a", "ip:127.0.0.1"]), Ok(()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lock_out_each_key_of_failure() {
+        let login_lockout = get_login_lockout();
+        login_lockout.record_failure(["user:a", "ip:127.0.0.1"]);
+        login_lockout.record_failure(["user:b", "ip:127.0.0.1"]);
+        login_lockout.record_failure(["user:c", "ip:127.0.0.1"]);
+        assert_eq!(
+            login_lockout.check(["user:d", "ip:127.0.0.1"]),
+            Err(Duration::from_secs(300))
+        );
+        assert_eq!(login_lockout.check(["user:a", "ip:127.0.0.2"]), Ok(()));
+    }
+}
@@ -1,59 +1,285 @@
+use ::std::{future::IntoFuture, net::SocketAddr, sync::Arc, time::Duration};
+
 use ::rucat_common::{
+    anyhow::anyhow,
+    blob_store::BlobStoreClient,
     config::{load_config, DatabaseVariant},
-    database::surrealdb_client::SurrealDBClient,
+    database::{
+        in_memory::InMemoryDatabase, postgres_client::PostgresClient, redis_client::RedisClient,
+        sqlite_client::SqliteClient, surrealdb_client::SurrealDBClient, Database,
+    },
     error::RucatError,
+    logging::init_tracing,
     tokio,
-    tracing::info,
-    tracing_subscriber,
+    tracing::{info, warn},
 };
 use ::rucat_server::{
-    authentication::static_auth_provider::StaticAuthProvider, get_server,
-    AuthProviderVariant::StaticAuthProviderConfig, ServerConfig,
+    authentication::registry::{build_auth_provider, AuthConfigFile},
+    cors::allow_cors,
+    get_build_info, get_server, parse_bind_address,
+    path_normalization::normalize_paths,
+    request_limit::RequestLimits,
+    resource_inspector::k8s_inspector::K8sInspector,
+    shutdown::{drain_with_timeout, shutdown_on, termination_signal, ShutdownHandle},
+    tls::{load_tls_config, ClientCertAcceptor},
+    ui::serve_ui,
+    EnginePolicy, ServerConfig, Sources,
 };
 use rucat_common::{config::Args, error::Result};
-use std::net::{Ipv4Addr, SocketAddrV4};
+
+/// Source of the engine state changes made by the server, see [rucat_common::engine::EngineEvent].
+const EVENT_SOURCE: &str = "server";
 
 #[tokio::main]
 /// Start Rucat server
 async fn main() -> Result<()> {
     // setup tracing
-    tracing_subscriber::fmt::init();
+    let _logging = init_tracing();
+    info!("Start rucat server, {}", get_build_info());
 
-    let Args { config_path } = Args::parse_args();
-    let endpoint = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 3000);
+    let Args {
+        config_path,
+        migrate_legacy,
+        dry_run,
+    } = Args::parse_args();
+    let config: ServerConfig = load_config(&config_path)?;
+    if migrate_legacy && !matches!(config.database, DatabaseVariant::Surreal(_)) {
+        // engines are never stored in the legacy schema in other databases
+        return Err(RucatError::fail_to_load_config(anyhow!(
+            "`--migrate-legacy` is only supported by SurrealDB"
+        )));
+    }
+    match &config.database {
+        DatabaseVariant::Surreal(surreal_config) => {
+            let db_client = SurrealDBClient::new(surreal_config)
+                .await?
+                .with_event_source(EVENT_SOURCE);
+            if migrate_legacy {
+                let summary = db_client.migrate_legacy_engines(dry_run).await?;
+                info!(
+                    "{}Legacy migration summary: {}",
+                    if dry_run { "[dry run] " } else { "" },
+                    summary
+                );
+                return Ok(());
+            }
+            run_server(db_client, config, config_path).await
+        }
+        DatabaseVariant::Postgres {
+            uri,
+            credentials,
+            pool_size,
+        } => {
+            let db_client = PostgresClient::new(credentials.as_ref(), uri, *pool_size)
+                .await?
+                .with_event_source(EVENT_SOURCE);
+            run_server(db_client, config, config_path).await
+        }
+        DatabaseVariant::Sqlite { path } => {
+            let db_client = SqliteClient::new(path)
+                .await?
+                .with_event_source(EVENT_SOURCE);
+            run_server(db_client, config, config_path).await
+        }
+        DatabaseVariant::Redis { uri, credentials } => {
+            let db_client = RedisClient::new(credentials.as_ref(), uri)
+                .await?
+                .with_event_source(EVENT_SOURCE);
+            run_server(db_client, config, config_path).await
+        }
+        DatabaseVariant::InMemory => {
+            warn!("Engines are stored in memory, which are lost when the server exits and not seen by any state monitor");
+            run_server(
+                InMemoryDatabase::new().with_event_source(EVENT_SOURCE),
+                config,
+                config_path,
+            )
+            .await
+        }
+    }
+}
+
+/// Serve the engines in `db_client` until the server is shut down.
+async fn run_server<DB: Database>(
+    db_client: DB,
+    config: ServerConfig,
+    config_path: String,
+) -> Result<()> {
     let ServerConfig {
         auth_provider,
-        database: DatabaseVariant::Surreal { credentials, uri },
-    } = load_config(&config_path)?;
+        // connected by the caller
+        database: _,
+        blob_store,
+        adopt_engines,
+        engine_diagnostics,
+        path_normalization,
+        ui,
+        engine_limits,
+        engine_versions,
+        bind_address,
+        tls,
+        shutdown_timeout_secs,
+        cors,
+        rate_limit,
+        engine_events,
+        idempotency_keys,
+        engine_history,
+        max_body_bytes,
+        request_timeout_secs,
+        webhooks,
+        projects,
+        compression,
+        auth_exempt_paths,
+        login_lockout,
+    } = config;
+    let bind_address = parse_bind_address(&bind_address)?;
+    let tls_config = match tls {
+        Some(config) => {
+            if let Some(client_ca_path) = &config.client_ca_path {
+                info!(
+                    "Client certificates are verified by `{}`, required: {}",
+                    client_ca_path, config.require_client_cert
+                );
+            }
+            Some(load_tls_config(&config).await?)
+        }
+        None => None,
+    };
 
-    let db_client = SurrealDBClient::new(credentials.as_ref(), uri).await?;
-    let app = match auth_provider {
+    let blob_store = match blob_store {
+        Some(variant) => Some(BlobStoreClient::new(variant).await?),
         None => {
-            info!("Authentication is disabled");
-            get_server(db_client, None::<StaticAuthProvider>)?
-        }
-        Some(StaticAuthProviderConfig {
-            username,
-            password,
-            bearer_token,
-        }) => {
-            info!("Static authentication is enabled");
-            let auth_provider = StaticAuthProvider::new(username, password, bearer_token);
-            get_server(db_client, Some(auth_provider))?
+            info!("Blob store is disabled");
+            None
         }
     };
+    if adopt_engines {
+        info!("Engine adoption is enabled");
+    }
+    if engine_diagnostics {
+        info!("Engine diagnostics are enabled");
+    }
+    let resource_inspector = if adopt_engines || engine_diagnostics {
+        Some(K8sInspector::new(adopt_engines, engine_diagnostics).await?)
+    } else {
+        None
+    };
+    let shutdown_handle = ShutdownHandle::new();
+    let auth_provider = build_auth_provider(auth_provider.as_ref()).await?;
+    let auth_config = AuthConfigFile::new(config_path);
+    if let Some(config) = &rate_limit {
+        info!("Rate limit is enabled: {:?}", config);
+    }
+    if let Some(config) = &login_lockout {
+        info!("Login lockout is enabled: {:?}", config);
+    }
+    if !compression {
+        info!("Response compression is disabled");
+    }
+    if !auth_exempt_paths.is_empty() {
+        info!("Paths exempt from authentication: {:?}", auth_exempt_paths);
+    }
+    if let Some(projects) = &projects {
+        info!("Engines are isolated in projects: {:?}", projects);
+    }
+    let app = get_server(
+        db_client,
+        auth_provider.clone(),
+        blob_store,
+        resource_inspector,
+        shutdown_handle.clone(),
+        Sources {
+            auth_config: Some(Arc::new(auth_config)),
+            ..Sources::default()
+        },
+        EnginePolicy {
+            limits: engine_limits,
+            versions: engine_versions,
+            rate_limit,
+            events: engine_events,
+            idempotency_keys,
+            history: engine_history,
+            request_limits: RequestLimits {
+                max_body_bytes,
+                timeout: Duration::from_secs(request_timeout_secs),
+            },
+            webhooks,
+            projects,
+            compression,
+            auth_exempt_paths,
+            login_lockout,
+        },
+    )?;
+
+    let app = match ui {
+        Some(config) => {
+            info!(
+                "Web UI is served at /ui, authentication required: {}",
+                config.require_auth
+            );
+            serve_ui(app, config, auth_provider)?
+        }
+        None => app,
+    };
+
+    let app = match cors {
+        Some(config) => {
+            info!("CORS is enabled: {:?}", config);
+            allow_cors(app, config)
+        }
+        None => app,
+    };
+
+    let app = match path_normalization {
+        Some(config) => {
+            info!("Path normalization is enabled: {:?}", config);
+            normalize_paths(app, config)
+        }
+        None => app,
+    };
+
+    let shutdown_signal = shutdown_on(termination_signal(), shutdown_handle.clone());
+    let shutdown_timeout = Duration::from_secs(shutdown_timeout_secs);
 
     // run it
-    let listener = tokio::net::TcpListener::bind(endpoint)
-        .await
-        .map_err(RucatError::fail_to_start_server)?;
-    info!(
-        "Rucat server is listening on {}",
-        listener
-            .local_addr()
-            .map_err(RucatError::fail_to_start_server)?
-    );
-    axum::serve(listener, app)
-        .await
-        .map_err(RucatError::fail_to_start_server)
+    match tls_config {
+        Some(tls_config) => {
+            let handle = axum_server::Handle::new();
+            tokio::spawn({
+                let handle = handle.clone();
+                async move {
+                    shutdown_signal.await;
+                    handle.graceful_shutdown(None);
+                }
+            });
+            info!("Rucat server is listening on {} with TLS", bind_address);
+            let serve = axum_server::bind(bind_address)
+                .acceptor(ClientCertAcceptor::new(tls_config))
+                .handle(handle)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>());
+            drain_with_timeout(serve, &shutdown_handle, shutdown_timeout)
+                .await
+                .map_err(RucatError::fail_to_start_server)
+        }
+        None => {
+            let listener = tokio::net::TcpListener::bind(bind_address)
+                .await
+                .map_err(RucatError::fail_to_start_server)?;
+            info!(
+                "Rucat server is listening on {}",
+                listener
+                    .local_addr()
+                    .map_err(RucatError::fail_to_start_server)?
+            );
+            let serve = axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown_signal)
+            .into_future();
+            drain_with_timeout(serve, &shutdown_handle, shutdown_timeout)
+                .await
+                .map_err(RucatError::fail_to_start_server)
+        }
+    }
 }
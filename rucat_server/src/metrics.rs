@@ -0,0 +1,269 @@
+//! Prometheus metrics of the server, served at `/metrics`.
+//! Metrics are only recorded and served if the server is built with the `metrics` feature.
+
+#[cfg(not(feature = "metrics"))]
+use ::std::sync::Arc;
+
+#[cfg(not(feature = "metrics"))]
+use crate::route_index::IndexedRouter;
+
+/// Operations on engines counted by [Metrics::record_engine_operation].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EngineOperation {
+    Create,
+    Delete,
+    Stop,
+    Restart,
+}
+
+/// Scheme of the credentials of an authentication attempt counted by [Metrics::record_auth_attempt].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AuthScheme {
+    Basic,
+    Bearer,
+    ApiKey,
+    ClientCert,
+}
+
+impl AuthScheme {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            AuthScheme::Basic => "basic",
+            AuthScheme::Bearer => "bearer",
+            AuthScheme::ApiKey => "api-key",
+            AuthScheme::ClientCert => "client-cert",
+        }
+    }
+}
+
+/// Whether the credentials of an authentication attempt are accepted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AuthOutcome {
+    Success,
+    Failure,
+}
+
+impl AuthOutcome {
+    #[cfg(feature = "metrics")]
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuthOutcome::Success => "success",
+            AuthOutcome::Failure => "failure",
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+mod prometheus {
+    use ::std::{
+        sync::{atomic::AtomicU64, Arc},
+        time::Instant,
+    };
+
+    use ::rucat_common::logging::get_dropped_lines;
+    use axum::{
+        extract::{MatchedPath, Request, State},
+        http::header::CONTENT_TYPE,
+        middleware::{self, Next},
+        response::{IntoResponse, Response},
+        routing::get,
+    };
+    use prometheus_client::{
+        encoding::{text::encode, EncodeLabelSet},
+        metrics::{
+            counter::Counter,
+            family::Family,
+            gauge::Gauge,
+            histogram::{exponential_buckets, Histogram},
+        },
+        registry::Registry,
+    };
+
+    use super::{AuthOutcome, AuthScheme, EngineOperation};
+    use crate::route_index::IndexedRouter;
+
+    const CONTENT_TYPE_OPENMETRICS: &str =
+        "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+    /// Route label of requests not matching any route, so that unknown paths do not add label values.
+    const UNMATCHED_ROUTE: &str = "unmatched";
+
+    #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+    struct HttpLabels {
+        method: String,
+        /// Route template, e.g. `/engine/{id}`, rather than the requested path.
+        route: String,
+        status: u16,
+    }
+
+    type HttpHistogram = Family<HttpLabels, Histogram, fn() -> Histogram>;
+
+    #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+    struct AuthLabels {
+        outcome: &'static str,
+        scheme: &'static str,
+    }
+
+    /// Latency buckets in seconds, from 5ms to about 10s.
+    fn new_latency_histogram() -> Histogram {
+        Histogram::new(exponential_buckets(0.005, 2.0, 12))
+    }
+
+    pub(crate) struct Metrics {
+        registry: Registry,
+        http_requests: HttpHistogram,
+        engines_created: Counter,
+        engines_deleted: Counter,
+        engines_stopped: Counter,
+        engines_restarted: Counter,
+        auth_attempts: Family<AuthLabels, Counter>,
+        dropped_log_lines: Gauge<u64, AtomicU64>,
+    }
+
+    impl Metrics {
+        pub(crate) fn new() -> Self {
+            let mut registry = Registry::default();
+            let http_requests = HttpHistogram::new_with_constructor(new_latency_histogram);
+            registry.register(
+                "rucat_http_request_duration_seconds",
+                "Latency of HTTP requests by method, route and status code",
+                http_requests.clone(),
+            );
+            let mut register_counter = |name: &str, help: &str| {
+                let counter = Counter::default();
+                registry.register(name, help, counter.clone());
+                counter
+            };
+            let engines_created = register_counter("rucat_engines_created", "Engines created");
+            let engines_deleted = register_counter("rucat_engines_deleted", "Engines deleted");
+            let engines_stopped = register_counter("rucat_engines_stopped", "Engines stopped");
+            let engines_restarted =
+                register_counter("rucat_engines_restarted", "Engines restarted");
+            let auth_attempts = Family::<AuthLabels, Counter>::default();
+            registry.register(
+                "rucat_auth_attempts",
+                "Authentication attempts by outcome and credentials scheme",
+                auth_attempts.clone(),
+            );
+            let dropped_log_lines = Gauge::default();
+            registry.register(
+                "rucat_dropped_log_lines",
+                "Log lines dropped because the log output is too slow",
+                dropped_log_lines.clone(),
+            );
+            Self {
+                registry,
+                http_requests,
+                engines_created,
+                engines_deleted,
+                engines_stopped,
+                engines_restarted,
+                auth_attempts,
+                dropped_log_lines,
+            }
+        }
+
+        pub(crate) fn record_engine_operation(&self, operation: EngineOperation) {
+            let counter = match operation {
+                EngineOperation::Create => &self.engines_created,
+                EngineOperation::Delete => &self.engines_deleted,
+                EngineOperation::Stop => &self.engines_stopped,
+                EngineOperation::Restart => &self.engines_restarted,
+            };
+            counter.inc();
+        }
+
+        pub(crate) fn record_auth_attempt(&self, scheme: AuthScheme, outcome: AuthOutcome) {
+            self.auth_attempts
+                .get_or_create(&AuthLabels {
+                    outcome: outcome.as_str(),
+                    scheme: scheme.as_str(),
+                })
+                .inc();
+        }
+
+        /// Metrics in the OpenMetrics text format.
+        fn encode(&self) -> String {
+            // The logging counts the dropped lines itself, they are copied when the metrics are scraped.
+            self.dropped_log_lines.set(get_dropped_lines() as u64);
+            let mut buffer = String::new();
+            // Writing to a `String` never fails.
+            encode(&mut buffer, &self.registry).expect("Failed to encode metrics");
+            buffer
+        }
+    }
+
+    async fn get_metrics(State(metrics): State<Arc<Metrics>>) -> Response {
+        ([(CONTENT_TYPE, CONTENT_TYPE_OPENMETRICS)], metrics.encode()).into_response()
+    }
+
+    async fn record_http_request(
+        State(metrics): State<Arc<Metrics>>,
+        request: Request,
+        next: Next,
+    ) -> Response {
+        let method = request.method().to_string();
+        let route = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map_or(UNMATCHED_ROUTE, MatchedPath::as_str)
+            .to_owned();
+        let start = Instant::now();
+        let response = next.run(request).await;
+        let labels = HttpLabels {
+            method,
+            route,
+            status: response.status().as_u16(),
+        };
+        metrics
+            .http_requests
+            .get_or_create(&labels)
+            .observe(start.elapsed().as_secs_f64());
+        response
+    }
+
+    /// Serve the metrics at `/metrics`, and record the requests of all the routes of the `router`.
+    pub(crate) fn serve_metrics<S>(
+        router: IndexedRouter<S>,
+        metrics: Arc<Metrics>,
+    ) -> IndexedRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        router
+            .route(
+                "/metrics",
+                get(get_metrics).with_state::<S>(metrics.clone()),
+            )
+            .map(|router| {
+                router.layer(middleware::from_fn_with_state(metrics, record_http_request))
+            })
+    }
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) use prometheus::{serve_metrics, Metrics};
+
+/// Metrics are not recorded without the `metrics` feature.
+#[cfg(not(feature = "metrics"))]
+pub(crate) struct Metrics;
+
+#[cfg(not(feature = "metrics"))]
+impl Metrics {
+    pub(crate) fn new() -> Self {
+        Self
+    }
+
+    pub(crate) fn record_engine_operation(&self, _operation: EngineOperation) {}
+
+    pub(crate) fn record_auth_attempt(&self, _scheme: AuthScheme, _outcome: AuthOutcome) {}
+}
+
+/// `/metrics` is not served without the `metrics` feature.
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn serve_metrics<S>(
+    router: IndexedRouter<S>,
+    _metrics: Arc<Metrics>,
+) -> IndexedRouter<S> {
+    router
+}
@@ -0,0 +1,149 @@
+//! OpenAPI specification of the engine API, served at `/api-doc/openapi.json`.
+//! The specification is only served if the server is built with the `openapi` feature,
+//! and the Swagger UI at `/swagger-ui` with the `swagger-ui` feature.
+
+#[cfg(not(feature = "openapi"))]
+use crate::route_index::IndexedRouter;
+
+/// Path of the OpenAPI specification.
+pub(crate) const OPENAPI_PATH: &str = "/api-doc/openapi.json";
+
+#[cfg(feature = "openapi")]
+mod spec {
+    #[cfg(not(feature = "swagger-ui"))]
+    use axum::{routing::get, Json};
+    use utoipa::{
+        openapi::{
+            path::{ParameterBuilder, ParameterIn},
+            security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+            Object, OpenApi as OpenApiSpec, Required, Type,
+        },
+        Modify, OpenApi,
+    };
+
+    #[cfg(feature = "swagger-ui")]
+    use utoipa_swagger_ui::SwaggerUi;
+
+    use super::OPENAPI_PATH;
+    use crate::{engine::router::EngineApi, route_index::IndexedRouter};
+
+    /// Basic, bearer and API key authentication, needed by all the engine routes if authentication is enabled.
+    struct Authentication;
+
+    impl Modify for Authentication {
+        fn modify(&self, openapi: &mut OpenApiSpec) {
+            let components = openapi.components.get_or_insert_with(Default::default);
+            components.add_security_scheme(
+                "basic",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Basic).build()),
+            );
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key"))),
+            );
+        }
+    }
+
+    /// Headers accepted by all the engine routes:
+    /// `X-Rucat-Project`, required if the server is configured with projects,
+    /// and `X-Rucat-Impersonate-User`, only allowed to admins.
+    struct EngineHeaders;
+
+    impl Modify for EngineHeaders {
+        fn modify(&self, openapi: &mut OpenApiSpec) {
+            let parameters = [
+                (
+                    "X-Rucat-Project",
+                    "Project of the engines, required if the server is configured with projects",
+                ),
+                (
+                    "X-Rucat-Impersonate-User",
+                    "User that an admin acts on behalf of, as an operator owning the engines it creates",
+                ),
+            ]
+            .map(|(name, description)| {
+                ParameterBuilder::new()
+                    .name(name)
+                    .parameter_in(ParameterIn::Header)
+                    .required(Required::False)
+                    .description(Some(description))
+                    .schema(Some(Object::with_type(Type::String)))
+                    .build()
+            });
+            for item in openapi.paths.paths.values_mut() {
+                for operation in [
+                    &mut item.get,
+                    &mut item.put,
+                    &mut item.post,
+                    &mut item.delete,
+                    &mut item.patch,
+                ]
+                .into_iter()
+                .flatten()
+                {
+                    operation
+                        .parameters
+                        .get_or_insert_with(Vec::new)
+                        .extend(parameters.iter().cloned());
+                }
+            }
+        }
+    }
+
+    #[derive(OpenApi)]
+    #[openapi(
+        info(
+            title = "Rucat",
+            description = "Management of Rucat engines",
+            license(name = "Apache-2.0")
+        ),
+        modifiers(&Authentication),
+        security((), ("basic" = []), ("bearer" = []), ("api_key" = []))
+    )]
+    struct ApiDoc;
+
+    /// OpenAPI specification of the server.
+    fn get_openapi() -> OpenApiSpec {
+        let mut openapi = ApiDoc::openapi();
+        openapi.merge(EngineApi::openapi());
+        EngineHeaders.modify(&mut openapi);
+        openapi
+    }
+
+    /// Serve the OpenAPI specification and the Swagger UI.
+    #[cfg(feature = "swagger-ui")]
+    pub(crate) fn serve_openapi<S>(router: IndexedRouter<S>) -> IndexedRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        router.merge_unindexed(
+            &["/swagger-ui", OPENAPI_PATH],
+            SwaggerUi::new("/swagger-ui")
+                .url(OPENAPI_PATH, get_openapi())
+                .into(),
+        )
+    }
+
+    /// Serve the OpenAPI specification.
+    #[cfg(not(feature = "swagger-ui"))]
+    pub(crate) fn serve_openapi<S>(router: IndexedRouter<S>) -> IndexedRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let openapi = get_openapi();
+        router.route(OPENAPI_PATH, get(move || async move { Json(openapi) }))
+    }
+}
+
+#[cfg(feature = "openapi")]
+pub(crate) use spec::serve_openapi;
+
+/// The OpenAPI specification is not served without the `openapi` feature.
+#[cfg(not(feature = "openapi"))]
+pub(crate) fn serve_openapi<S>(router: IndexedRouter<S>) -> IndexedRouter<S> {
+    router
+}
@@ -0,0 +1,125 @@
+//! Normalization of request paths before routing, for clients behind gateways that
+//! append trailing slashes or change the case of the path, e.g. `/Engine/123/`.
+
+use ::std::sync::Arc;
+
+use ::rucat_common::serde::Deserialize;
+use axum::{
+    extract::{Request, State},
+    http::{header::LOCATION, uri::PathAndQuery, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use tower::Layer;
+
+/// How to handle requests whose path is not canonical.
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(crate = "rucat_common::serde")]
+pub enum NormalizationMode {
+    /// Route the request as if the canonical path was requested.
+    #[default]
+    Rewrite,
+    /// Respond `308 Permanent Redirect` to the canonical path.
+    Redirect,
+}
+
+/// Configuration of the path normalization.
+/// Empty segments are always removed, e.g. `/engine//123/` is normalized to `/engine/123`.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct PathNormalization {
+    /// Lowercase the first segment of the path, e.g. `/Engine/ABC` is normalized to `/engine/ABC`.
+    /// Other segments are kept as they are because engine ids are case sensitive.
+    #[serde(default)]
+    pub lowercase_prefix: bool,
+    #[serde(default)]
+    pub mode: NormalizationMode,
+}
+
+/// Normalize the paths of requests before they are routed by `router`.
+pub fn normalize_paths(router: Router, config: PathNormalization) -> Router {
+    let normalized = middleware::from_fn_with_state(Arc::new(config), normalize).layer(router);
+    Router::new().fallback_service(normalized)
+}
+
+async fn normalize(
+    State(config): State<Arc<PathNormalization>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let path = get_canonical_path(request.uri().path(), config.lowercase_prefix);
+    if path == request.uri().path() {
+        return next.run(request).await;
+    }
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+    match config.mode {
+        NormalizationMode::Redirect => {
+            (StatusCode::PERMANENT_REDIRECT, [(LOCATION, path_and_query)]).into_response()
+        }
+        NormalizationMode::Rewrite => {
+            // The canonical path is always valid as it only consists of segments of a valid path.
+            if let Some(uri) = rewrite_uri(request.uri(), path_and_query) {
+                *request.uri_mut() = uri;
+            }
+            next.run(request).await
+        }
+    }
+}
+
+fn rewrite_uri(uri: &Uri, path_and_query: String) -> Option<Uri> {
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+/// Remove empty segments of the path, and lowercase the first segment if `lowercase_prefix` is set.
+fn get_canonical_path(path: &str, lowercase_prefix: bool) -> String {
+    let mut canonical = String::with_capacity(path.len());
+    for (i, segment) in path.split('/').filter(|s| !s.is_empty()).enumerate() {
+        canonical.push('/');
+        if i == 0 && lowercase_prefix {
+            canonical.push_str(&segment.to_lowercase());
+        } else {
+            canonical.push_str(segment);
+        }
+    }
+    if canonical.is_empty() {
+        canonical.push('/');
+    }
+    canonical
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_paths_are_unchanged() {
+        for path in ["/", "/engine", "/engine/123", "/engine/ABC/stop"] {
+            assert_eq!(get_canonical_path(path, true), path);
+        }
+    }
+
+    #[test]
+    fn remove_empty_segments() {
+        assert_eq!(get_canonical_path("//", false), "/");
+        assert_eq!(get_canonical_path("/engine/", false), "/engine");
+        assert_eq!(get_canonical_path("/engine//123", false), "/engine/123");
+        assert_eq!(
+            get_canonical_path("//engine/123//stop/", false),
+            "/engine/123/stop"
+        );
+    }
+
+    #[test]
+    fn lowercase_prefix() {
+        assert_eq!(get_canonical_path("/Engine/ABC", true), "/engine/ABC");
+        assert_eq!(get_canonical_path("/ENGINE/", true), "/engine");
+        assert_eq!(get_canonical_path("/Engine/ABC", false), "/Engine/ABC");
+    }
+}
@@ -0,0 +1,147 @@
+//! Projects isolating the engines of the teams sharing a server.
+//!
+//! If the server is configured with projects, each engine request selects its project by the `X-Rucat-Project` header,
+//! and only sees the engines of the project. Engines of other projects are not found rather than forbidden,
+//! so that their existence is not leaked.
+
+use ::std::collections::BTreeSet;
+
+use ::rucat_common::{
+    anyhow::anyhow,
+    database::Database,
+    engine::{EngineId, EngineInfo},
+    error::{Result, RucatError},
+};
+use axum::{
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderName},
+};
+
+use crate::{error::RucatServerError, state::AppState};
+
+/// Header selecting the project of a request.
+pub(crate) const PROJECT_HEADER: HeaderName = HeaderName::from_static("x-rucat-project");
+
+/// Maximum length of a project name, which is the limit of a Kubernetes namespace.
+const MAX_PROJECT_NAME_LENGTH: usize = 63;
+
+/// Check each project can be used as the Kubernetes namespace of its engines,
+/// i.e. it is a DNS label of lowercase alphanumeric characters or `-`.
+pub fn validate_projects(projects: &BTreeSet<String>) -> Result<()> {
+    if projects.is_empty() {
+        return Err(RucatError::fail_to_load_config(anyhow!(
+            "`projects` cannot be empty, remove it to disable projects"
+        )));
+    }
+    for project in projects {
+        let valid = !project.is_empty()
+            && project.len() <= MAX_PROJECT_NAME_LENGTH
+            && project
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            && !project.starts_with('-')
+            && !project.ends_with('-');
+        if !valid {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "Invalid project `{}`, expected at most {} lowercase alphanumeric characters or `-`, starting and ending with an alphanumeric character",
+                project,
+                MAX_PROJECT_NAME_LENGTH
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Project selected by the request, `None` if the server is not configured with projects.
+pub(crate) struct Project(pub(crate) Option<String>);
+
+impl Project {
+    pub(crate) fn as_deref(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+
+    /// Whether the engine can be seen by the request.
+    pub(crate) fn contains(&self, info: &EngineInfo) -> bool {
+        self.0.is_none() || info.project == self.0
+    }
+
+    /// Fail with 404 if the engine does not exist in the project.
+    /// Nothing is read from the database if the server is not configured with projects.
+    pub(crate) async fn check_engine<DB>(&self, id: &EngineId, db: &DB) -> Result<()>
+    where
+        DB: Database,
+    {
+        if self.0.is_none() {
+            return Ok(());
+        }
+        match db.get_engine(id).await? {
+            Some(info) if self.contains(&info) => Ok(()),
+            _ => Err(RucatError::engine_not_found(id)),
+        }
+    }
+}
+
+impl<DB> FromRequestParts<AppState<DB>> for Project
+where
+    DB: Database,
+{
+    type Rejection = RucatServerError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState<DB>,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let project = parts
+            .headers
+            .get(&PROJECT_HEADER)
+            .map(|value| {
+                value.to_str().map_err(|_| {
+                    RucatError::invalid_request(anyhow!(
+                        "`{}` must be a visible ASCII string",
+                        PROJECT_HEADER
+                    ))
+                })
+            })
+            .transpose()?;
+        match (state.get_projects(), project) {
+            (None, None) => Ok(Self(None)),
+            (None, Some(_)) => Err(RucatError::invalid_request(anyhow!(
+                "`{}` is set, but the server is not configured with projects",
+                PROJECT_HEADER
+            ))
+            .into()),
+            (Some(_), None) => Err(RucatError::invalid_request(anyhow!(
+                "`{}` is required, as the server is configured with projects",
+                PROJECT_HEADER
+            ))
+            .into()),
+            (Some(projects), Some(project)) if projects.contains(project) => {
+                Ok(Self(Some(project.to_owned())))
+            }
+            (Some(_), Some(project)) => {
+                Err(RucatError::not_found(anyhow!("Project `{}` is not found", project)).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_project_names() {
+        assert!(validate_projects(&BTreeSet::from(["team-a".to_owned(), "b2".to_owned()])).is_ok());
+        assert!(validate_projects(&BTreeSet::new()).is_err());
+        for project in ["", "Team", "team_a", "-team", "team-", &"a".repeat(64)] {
+            let result = validate_projects(&BTreeSet::from([project.to_owned()]));
+            assert!(
+                result.is_err_and(|e| e
+                    .to_string()
+                    .starts_with("Fail to load config: Invalid project")),
+                "{:?} should be invalid",
+                project
+            );
+        }
+    }
+}
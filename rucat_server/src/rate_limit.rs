@@ -0,0 +1,228 @@
+//! Rate limiting of the requests of each client, so that a buggy client cannot flood the server,
+//! e.g. by creating engines in a loop.
+//!
+//! Clients are identified by the authenticated user, or by the remote IP if authentication is disabled.
+
+use ::std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ::rucat_common::{
+    anyhow::anyhow,
+    error::{Result, RucatError},
+    serde::Deserialize,
+    tokio::time::Instant,
+};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::RETRY_AFTER, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{authentication::ClientIdentity, error::RucatServerError};
+
+/// Configuration of the rate limit of each client.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct RateLimitConfig {
+    /// Sustained number of requests per second of each client.
+    #[serde(default = "RateLimitConfig::default_requests_per_second")]
+    pub requests_per_second: f64,
+    /// Number of requests allowed at once above the sustained rate.
+    #[serde(default = "RateLimitConfig::default_burst")]
+    pub burst: u32,
+}
+
+impl RateLimitConfig {
+    fn default_requests_per_second() -> f64 {
+        10.0
+    }
+
+    fn default_burst() -> u32 {
+        20
+    }
+
+    /// Check the rate is positive and the burst allows at least one request.
+    pub fn validate(&self) -> Result<()> {
+        if !(self.requests_per_second.is_finite() && self.requests_per_second > 0.0)
+            || self.burst == 0
+        {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "Rate limit needs a positive `requests_per_second` and `burst`, but got requests_per_second {} and burst {}",
+                self.requests_per_second,
+                self.burst
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Token bucket of each client, in the form of the generic cell rate algorithm:
+/// instead of counting tokens, track the time when the bucket of the client would be full again.
+pub(crate) struct RateLimiter {
+    interval: Duration,
+    /// Requests allowed at once are the ones within this time ahead of now.
+    burst_tolerance: Duration,
+    /// Time when the next request of each client is allowed if no burst was allowed.
+    next_request_times: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    /// Clients whose buckets are full are forgotten once there are this many clients.
+    const MAX_CLIENTS: usize = 10_000;
+
+    pub(crate) fn new(config: &RateLimitConfig) -> Self {
+        let interval = Duration::from_secs_f64(1.0 / config.requests_per_second);
+        Self {
+            interval,
+            burst_tolerance: interval * config.burst.saturating_sub(1),
+            next_request_times: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a request of the `client`, return the time to wait before retrying if it exceeds the limit.
+    fn try_acquire(&self, client: &str) -> std::result::Result<(), Duration> {
+        let now = Instant::now();
+        let mut next_request_times = self.next_request_times.lock().unwrap();
+        if next_request_times.len() >= Self::MAX_CLIENTS {
+            // A full bucket is the same as a new one, so forgetting it changes nothing.
+            next_request_times.retain(|_, next_request_time| *next_request_time > now);
+        }
+        let next_request_time = next_request_times
+            .get(client)
+            .map_or(now, |time| (*time).max(now));
+        let wait = next_request_time.saturating_duration_since(now);
+        if wait > self.burst_tolerance {
+            return Err(wait - self.burst_tolerance);
+        }
+        next_request_times.insert(client.to_owned(), next_request_time + self.interval);
+        Ok(())
+    }
+}
+
+/// Key of the client of the request.
+/// Requests without authentication or remote address, e.g. in tests, share the same key.
+fn get_client_key(request: &Request) -> String {
+    if let Some(ClientIdentity(identity)) = request.extensions().get::<ClientIdentity>() {
+        return identity.clone();
+    }
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or_else(
+            || "unknown".to_owned(),
+            |ConnectInfo(address)| format!("ip:{}", address.ip()),
+        )
+}
+
+/// Middleware rejecting requests with 429 if their client exceeds the rate limit.
+pub(crate) async fn rate_limit(
+    State(rate_limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = get_client_key(&request);
+    match rate_limiter.try_acquire(&client) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => reject_too_many_requests("Rate limit exceeded", wait),
+    }
+}
+
+/// Response of 429 to the request rejected for the `reason`, telling the client to retry after `wait`.
+pub(crate) fn reject_too_many_requests(reason: &str, wait: Duration) -> Response {
+    // Round up, so that the request is allowed after waiting `Retry-After` seconds.
+    let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    let mut response = RucatServerError::from(RucatError::too_many_requests(anyhow!(
+        "{}, retry after {} seconds",
+        reason,
+        retry_after
+    )))
+    .into_response();
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use ::rucat_common::tokio;
+
+    use super::*;
+
+    fn get_rate_limiter(requests_per_second: f64, burst: u32) -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig {
+            requests_per_second,
+            burst,
+        })
+    }
+
+    #[test]
+    fn validate_config() {
+        assert!(RateLimitConfig {
+            requests_per_second: 0.5,
+            burst: 1
+        }
+        .validate()
+        .is_ok());
+        for (requests_per_second, burst) in [(0.0, 1), (-1.0, 1), (f64::NAN, 1), (1.0, 0)] {
+            let result = RateLimitConfig {
+                requests_per_second,
+                burst,
+            }
+            .validate();
+            assert!(result.is_err_and(|e| {
+                e.to_string().starts_with(
+                "Fail to load config: Rate limit needs a positive `requests_per_second` and `burst`"
+            )
+            }));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn allow_burst_then_sustained_rate() {
+        let rate_limiter = get_rate_limiter(2.0, 3);
+        for _ in 0..3 {
+            assert_eq!(rate_limiter.try_acquire("a"), Ok(()));
+        }
+        assert_eq!(
+            rate_limiter.try_acquire("a"),
+            Err(Duration::from_millis(500))
+        );
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        assert_eq!(rate_limiter.try_acquire("a"), Ok(()));
+        assert_eq!(
+            rate_limiter.try_acquire("a"),
+            Err(Duration::from_millis(500))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limit_each_client() {
+        let rate_limiter = get_rate_limiter(1.0, 1);
+        assert_eq!(rate_limiter.try_acquire("a"), Ok(()));
+        assert!(rate_limiter.try_acquire("a").is_err());
+        assert_eq!(rate_limiter.try_acquire("b"), Ok(()));
+        assert!(rate_limiter.try_acquire("b").is_err());
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(rate_limiter.try_acquire("a"), Ok(()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn forget_clients_with_full_buckets() {
+        let rate_limiter = get_rate_limiter(1.0, 1);
+        for i in 0..RateLimiter::MAX_CLIENTS {
+            assert_eq!(rate_limiter.try_acquire(&i.to_string()), Ok(()));
+        }
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(rate_limiter.try_acquire("new"), Ok(()));
+        assert_eq!(rate_limiter.next_request_times.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,100 @@
+//! Request ids to correlate the logs of a request.
+//!
+//! The id is taken from the `x-request-id` header of the request, or generated if it is missing.
+//! It is recorded in the tracing span of the request, and returned in the `x-request-id` header of the response.
+
+use ::rucat_common::tracing::{info_span, Span};
+use axum::{extract::Request, Router};
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, RequestId, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+
+/// Span of a request, in which all the logs of the handlers are written.
+/// It is at the info level so that the logs of the handlers carry the request id by default.
+fn make_request_span(request: &Request) -> Span {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or_default();
+    info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        request_id = %request_id,
+    )
+}
+
+/// Trace the requests of all the routes of the `router` in spans carrying the request ids.
+pub(crate) fn trace_requests<S>(router: Router<S>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    // go through the layers from outer to inner:
+    // the id is set before the span is created, and copied into the response of the inner service.
+    router
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(TraceLayer::new_for_http().make_span_with(make_request_span))
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::sync::{Arc, Mutex};
+
+    use ::rucat_common::{
+        tokio,
+        tracing::{self, info},
+        tracing_subscriber,
+    };
+    use axum::{body::Body, routing::get};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    /// Writer of the logs of a test.
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl ::std::io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> ::std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn logs_of_handlers_carry_request_id() {
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let router = trace_requests(Router::<()>::new().route(
+            "/",
+            get(|| async {
+                info!("handled");
+            }),
+        ));
+        let request = Request::get("/")
+            .header("x-request-id", "abc-123")
+            .body(Body::empty())
+            .unwrap();
+        router.oneshot(request).await.unwrap();
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let handled = logs.lines().find(|line| line.contains("handled")).unwrap();
+        assert!(handled.contains("request_id=abc-123"), "{}", handled);
+    }
+}
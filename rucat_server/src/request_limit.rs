@@ -0,0 +1,127 @@
+//! Limits on the body size and the duration of each request, so that a client cannot tie up the server,
+//! e.g. by posting a multi-megabyte engine config.
+
+use ::std::time::Duration;
+
+use ::rucat_common::{anyhow::anyhow, error::RucatError};
+use axum::{
+    extract::{DefaultBodyLimit, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    Router,
+};
+use tower_http::{limit::RequestBodyLimitLayer, timeout::TimeoutLayer};
+
+use crate::error::RucatServerError;
+
+/// Limits on each request to the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestLimits {
+    /// Maximum bytes of a request body.
+    pub max_body_bytes: usize,
+    /// Maximum duration of serving a request, until the response starts.
+    /// Streamed responses, e.g. the engine events, are not limited once they start.
+    pub timeout: Duration,
+}
+
+impl RequestLimits {
+    pub(crate) const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+    pub(crate) const DEFAULT_TIMEOUT_SECS: u64 = 60;
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: Self::DEFAULT_MAX_BODY_BYTES,
+            timeout: Duration::from_secs(Self::DEFAULT_TIMEOUT_SECS),
+        }
+    }
+}
+
+/// Replace the plain responses of the limit layers with the JSON error body.
+/// Rejections of the extractors, e.g. a body without `Content-Length` exceeding the limit, are JSON already.
+async fn into_error_response(State(limits): State<RequestLimits>, response: Response) -> Response {
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value == "application/json");
+    if is_json {
+        return response;
+    }
+    match response.status() {
+        StatusCode::PAYLOAD_TOO_LARGE => {
+            RucatServerError::from(RucatError::payload_too_large(anyhow!(
+                "Request body is larger than {} bytes",
+                limits.max_body_bytes
+            )))
+            .into_response()
+        }
+        StatusCode::REQUEST_TIMEOUT => {
+            RucatServerError::from(RucatError::request_timeout(anyhow!(
+                "Request is not served in {} seconds",
+                limits.timeout.as_secs()
+            )))
+            .into_response()
+        }
+        _ => response,
+    }
+}
+
+/// Reject the requests to the `router` with 413 if their bodies exceed the limit, and with 408 if they time out.
+pub(crate) fn limit_requests<S>(router: Router<S>, limits: RequestLimits) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    // The last layer is the outermost, so that it sees the plain responses of the limit layers.
+    // The default body limit of axum is disabled, otherwise the smaller of the two limits applies.
+    router
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(limits.max_body_bytes))
+        .layer(TimeoutLayer::with_status_code(
+            StatusCode::REQUEST_TIMEOUT,
+            limits.timeout,
+        ))
+        .layer(middleware::map_response_with_state(
+            limits,
+            into_error_response,
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use ::rucat_common::{serde_json::Value, tokio};
+    use axum::{
+        body::{to_bytes, Body},
+        extract::Request,
+        http::header::CONTENT_LENGTH,
+        routing::post,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reject_content_length_larger_than_limit() {
+        let router = limit_requests(
+            Router::new().route("/", post(|body: String| async { body })),
+            RequestLimits {
+                max_body_bytes: 10,
+                ..RequestLimits::default()
+            },
+        );
+        let request = Request::post("/")
+            .header(CONTENT_LENGTH, 11)
+            .body(Body::from("a".repeat(11)))
+            .unwrap();
+
+        let response = router.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = ::rucat_common::serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "PAYLOAD_TOO_LARGE");
+        assert_eq!(body["message"], "Request body is larger than 10 bytes");
+    }
+}
@@ -0,0 +1,508 @@
+use ::std::{borrow::Cow, cmp::Reverse, time::SystemTime};
+
+use ::futures::{AsyncBufReadExt, StreamExt, TryStreamExt};
+use ::k8s_openapi::api::core::v1::{Container, Event, Pod, Service};
+use ::kube::{
+    api::{ListParams, LogParams, Patch, PatchParams},
+    Api, Client, ResourceExt,
+};
+use ::rucat_common::{
+    anyhow::anyhow,
+    engine::{
+        get_spark_driver_name, get_spark_namespace, get_spark_service_name, AdoptEngineRequest,
+        EngineConfig, EngineId, EngineInfo, EngineResourceEvent, EngineState, EngineTime,
+        EngineType, ENGINE_ID_LABEL,
+    },
+    error::{Result, RucatError},
+    serde_json::json,
+};
+
+use super::{LogOptions, LogStream, ResourceInspector};
+
+/// Port of the spark connect server.
+const SPARK_CONNECT_PORT: i32 = 15002;
+/// Repository of the supported spark images.
+const SPARK_IMAGE_REPOSITORY: &str = "apache/spark";
+
+/// Inspector of Spark connect pods in Kubernetes.
+pub struct K8sInspector {
+    client: Client,
+    /// Whether pods deployed without rucat can be adopted.
+    adopt_engines: bool,
+    /// Whether the logs and the events of the engine resources can be read.
+    engine_diagnostics: bool,
+}
+
+impl K8sInspector {
+    pub async fn new(adopt_engines: bool, engine_diagnostics: bool) -> Result<Self> {
+        let client = Client::try_default()
+            .await
+            .map_err(RucatError::fail_to_start_server)?;
+        Ok(Self {
+            client,
+            adopt_engines,
+            engine_diagnostics,
+        })
+    }
+
+    fn check_diagnostics_enabled(&self) -> Result<()> {
+        if self.engine_diagnostics {
+            Ok(())
+        } else {
+            Err(RucatError::not_found(anyhow!(
+                "Engine diagnostics are disabled"
+            )))
+        }
+    }
+
+    /// Get the spark driver pod of the engine.
+    /// Fall back to the engine id label for engines adopted by rucat, whose pods are named by others.
+    async fn get_spark_driver(
+        &self,
+        id: &EngineId,
+        project: Option<&str>,
+    ) -> ::kube::Result<Option<Pod>> {
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), get_spark_namespace(project));
+        if let Some(pod) = pods.get_opt(&get_spark_driver_name(id)).await? {
+            return Ok(Some(pod));
+        }
+        let pods: Api<Pod> = Api::all(self.client.clone());
+        let list_params = ListParams::default().labels(&format!("{}={}", ENGINE_ID_LABEL, id));
+        let labelled_pods = pods.list(&list_params).await?;
+        Ok(labelled_pods.items.into_iter().next())
+    }
+
+    /// List the events of the object in the namespace.
+    async fn list_events(&self, namespace: &str, name: &str) -> Result<Vec<Event>> {
+        let events: Api<Event> = Api::namespaced(self.client.clone(), namespace);
+        let list_params = ListParams::default().fields(&format!("involvedObject.name={}", name));
+        Ok(events
+            .list(&list_params)
+            .await
+            .map_err(RucatError::fail_to_read_events)?
+            .items)
+    }
+}
+
+impl ResourceInspector for K8sInspector {
+    async fn inspect(&self, request: &AdoptEngineRequest) -> Result<EngineInfo> {
+        if !self.adopt_engines {
+            return Err(RucatError::not_found(anyhow!(
+                "Adopting engines is disabled"
+            )));
+        }
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), &request.namespace);
+        let pod = pods
+            .get_opt(&request.pod)
+            .await
+            .map_err(RucatError::fail_to_start_engine)?
+            .ok_or_else(|| {
+                RucatError::not_found(anyhow!(
+                    "Pod {}/{} not found",
+                    request.namespace,
+                    request.pod
+                ))
+            })?;
+        if let Some(service) = &request.service {
+            let services: Api<Service> = Api::namespaced(self.client.clone(), &request.namespace);
+            services
+                .get_opt(service)
+                .await
+                .map_err(RucatError::fail_to_start_engine)?
+                .ok_or_else(|| {
+                    RucatError::not_found(anyhow!(
+                        "Service {}/{} not found",
+                        request.namespace,
+                        service
+                    ))
+                })?;
+        }
+        get_engine_info(&pod)
+    }
+
+    async fn label(&self, request: &AdoptEngineRequest, id: &EngineId) -> Result<()> {
+        let patch = Patch::Merge(json!({
+            "metadata": {
+                "labels": {
+                    ENGINE_ID_LABEL: id.to_string(),
+                },
+            },
+        }));
+        let params = PatchParams::default();
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), &request.namespace);
+        pods.patch(&request.pod, &params, &patch)
+            .await
+            .map_err(RucatError::fail_to_start_engine)?;
+        if let Some(service) = &request.service {
+            let services: Api<Service> = Api::namespaced(self.client.clone(), &request.namespace);
+            services
+                .patch(service, &params, &patch)
+                .await
+                .map_err(RucatError::fail_to_start_engine)?;
+        }
+        Ok(())
+    }
+
+    async fn get_logs(
+        &self,
+        id: &EngineId,
+        project: Option<&str>,
+        options: LogOptions,
+    ) -> Result<Option<LogStream>> {
+        self.check_diagnostics_enabled()?;
+        let pod = self
+            .get_spark_driver(id, project)
+            .await
+            .map_err(RucatError::fail_to_read_logs)?;
+        let Some(pod) = pod else {
+            return Ok(None);
+        };
+        let namespace = pod
+            .namespace()
+            .unwrap_or_else(|| get_spark_namespace(project).to_owned());
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), &namespace);
+        let params = LogParams {
+            follow: options.follow,
+            tail_lines: options.tail_lines,
+            ..LogParams::default()
+        };
+        let logs = pods
+            .log_stream(&pod.name_any(), &params)
+            .await
+            .map_err(RucatError::fail_to_read_logs)?;
+        Ok(Some(
+            logs.lines()
+                .map_ok(|mut line| {
+                    line.push('\n');
+                    line
+                })
+                .boxed(),
+        ))
+    }
+
+    async fn get_events(
+        &self,
+        id: &EngineId,
+        project: Option<&str>,
+    ) -> Result<Vec<EngineResourceEvent>> {
+        self.check_diagnostics_enabled()?;
+        let namespace = get_spark_namespace(project);
+        let pod = self
+            .get_spark_driver(id, project)
+            .await
+            .map_err(RucatError::fail_to_read_events)?;
+        // Events of the pod are kept for a while after the pod is deleted, so they are listed by the name.
+        let (pod_namespace, pod_name) = pod.map_or_else(
+            || (namespace.to_owned(), get_spark_driver_name(id).into_owned()),
+            |pod| {
+                (
+                    pod.namespace().unwrap_or_else(|| namespace.to_owned()),
+                    pod.name_any(),
+                )
+            },
+        );
+        let mut events = self.list_events(&pod_namespace, &pod_name).await?;
+        events.extend(
+            self.list_events(namespace, &get_spark_service_name(id))
+                .await?,
+        );
+        events.sort_by_key(|event| Reverse(get_last_time(event)));
+        Ok(events.iter().map(to_engine_resource_event).collect())
+    }
+}
+
+/// Time of the last occurrence of the event.
+/// Events reported by the newer API only have the `eventTime`.
+fn get_last_time(event: &Event) -> Option<SystemTime> {
+    event
+        .last_timestamp
+        .as_ref()
+        .map(|time| time.0.into())
+        .or_else(|| event.event_time.as_ref().map(|time| time.0.into()))
+}
+
+fn to_engine_resource_event(event: &Event) -> EngineResourceEvent {
+    let object = &event.involved_object;
+    EngineResourceEvent {
+        object: format!(
+            "{}/{}",
+            object.kind.as_deref().unwrap_or_default(),
+            object.name.as_deref().unwrap_or_default()
+        ),
+        event_type: event.type_.clone().unwrap_or_default(),
+        reason: event.reason.clone().unwrap_or_default(),
+        message: event.message.clone().unwrap_or_default(),
+        count: event.count.unwrap_or(1),
+        last_timestamp: get_last_time(event).map(EngineTime::from_system_time),
+    }
+}
+
+/// Reconstruct the info of a running Spark connect engine from its pod.
+/// The config is best-effort reconstructed from the `--conf` arguments of the spark container.
+fn get_engine_info(pod: &Pod) -> Result<EngineInfo> {
+    let name = pod.metadata.name.clone().unwrap_or_default();
+    let reject = |reason: String| {
+        Err(RucatError::not_allowed(anyhow!(
+            "Pod {} cannot be adopted: {}",
+            name,
+            reason
+        )))
+    };
+
+    if let Some(id) = pod
+        .metadata
+        .labels
+        .as_ref()
+        .and_then(|labels| labels.get(ENGINE_ID_LABEL))
+    {
+        return reject(format!("it is already managed by rucat as engine {}", id));
+    }
+    let phase = pod
+        .status
+        .as_ref()
+        .and_then(|status| status.phase.as_deref());
+    if phase != Some("Running") {
+        return reject(format!(
+            "it is in phase {}, only running pods can be adopted",
+            phase.unwrap_or("Unknown")
+        ));
+    }
+    let containers = pod.spec.as_ref().map_or(&[][..], |spec| &spec.containers);
+    let Some(container) = containers.iter().find(|c| exposes_spark_connect_port(c)) else {
+        return reject(format!(
+            "no container exposes the spark connect port {}",
+            SPARK_CONNECT_PORT
+        ));
+    };
+    let image = container.image.as_deref().unwrap_or_default();
+    let supported_versions = EngineType::Spark.get_supported_versions();
+    let version = match image.split_once(':') {
+        Some((SPARK_IMAGE_REPOSITORY, version)) if supported_versions.contains(&version) => version,
+        _ => {
+            return reject(format!(
+                "unknown image `{}`, expected {}:<version> with version in {:?}",
+                image, SPARK_IMAGE_REPOSITORY, supported_versions
+            ))
+        }
+    };
+
+    Ok(EngineInfo::new(
+        name.clone(),
+        EngineType::Spark,
+        version.to_owned(),
+        EngineState::Running,
+        get_spark_config(container.args.as_deref().unwrap_or_default()),
+        EngineTime::now(),
+    ))
+}
+
+fn exposes_spark_connect_port(container: &Container) -> bool {
+    container
+        .ports
+        .iter()
+        .flatten()
+        .any(|port| port.container_port == SPARK_CONNECT_PORT)
+}
+
+/// Collect `--conf key=value` and `--conf=key=value` arguments, other arguments are ignored.
+fn get_spark_config(args: &[String]) -> EngineConfig {
+    let mut args = args.iter();
+    let mut config = EngineConfig::new();
+    while let Some(arg) = args.next() {
+        let conf = match arg.strip_prefix("--conf") {
+            Some("") => args.next().map(String::as_str),
+            Some(conf) => conf.strip_prefix('='),
+            None => None,
+        };
+        if let Some((key, value)) = conf.and_then(|conf| conf.split_once('=')) {
+            config.insert(Cow::Owned(key.to_owned()), Cow::Owned(value.to_owned()));
+        }
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use ::rucat_common::serde_json::{self, Value};
+
+    use super::*;
+
+    /// Pod of a Spark connect server deployed by Helm.
+    fn spark_connect_pod() -> Value {
+        json!({
+            "metadata": {
+                "name": "spark-connect-0",
+                "namespace": "spark",
+                "labels": {"app": "spark-connect"},
+            },
+            "spec": {
+                "containers": [
+                    {
+                        "name": "sidecar",
+                        "image": "busybox:latest",
+                    },
+                    {
+                        "name": "spark",
+                        "image": "apache/spark:3.5.4",
+                        "ports": [{"containerPort": 4040}, {"containerPort": 15002}],
+                        "command": ["/opt/spark/sbin/start-connect-server.sh"],
+                        "args": [
+                            "--packages",
+                            "org.apache.spark:spark-connect_2.12:3.5.4",
+                            "--conf",
+                            "spark.executor.instances=2",
+                            "--conf=spark.driver.extraJavaOptions=-Dkey=value",
+                            "--conf",
+                        ],
+                    },
+                ],
+            },
+            "status": {"phase": "Running"},
+        })
+    }
+
+    fn get_engine_info_of(pod: Value) -> Result<EngineInfo> {
+        get_engine_info(&serde_json::from_value(pod).unwrap())
+    }
+
+    fn assert_rejected(pod: Value, reason: &str) {
+        let result = get_engine_info_of(pod);
+        assert!(
+            result
+                .as_ref()
+                .is_err_and(|e| e.to_string().starts_with(&format!(
+                    "Not allowed: Pod spark-connect-0 cannot be adopted: {}",
+                    reason
+                ))),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn adopt_spark_connect_pod() {
+        let info = get_engine_info_of(spark_connect_pod()).unwrap();
+        assert_eq!(info.name, "spark-connect-0");
+        assert_eq!(info.engine_type, EngineType::Spark);
+        assert_eq!(info.version, "3.5.4");
+        assert_eq!(info.state, EngineState::Running);
+        assert_eq!(
+            info.config,
+            EngineConfig::from([
+                (
+                    Cow::Borrowed("spark.driver.extraJavaOptions"),
+                    Cow::Borrowed("-Dkey=value")
+                ),
+                (
+                    Cow::Borrowed("spark.executor.instances"),
+                    Cow::Borrowed("2")
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn adopt_pod_without_args() {
+        let mut pod = spark_connect_pod();
+        pod["spec"]["containers"][1]
+            .as_object_mut()
+            .unwrap()
+            .remove("args");
+        let info = get_engine_info_of(pod).unwrap();
+        assert!(info.config.is_empty());
+    }
+
+    #[test]
+    fn reject_pod_managed_by_rucat() {
+        let mut pod = spark_connect_pod();
+        pod["metadata"]["labels"][ENGINE_ID_LABEL] = json!("abc");
+        assert_rejected(pod, "it is already managed by rucat as engine abc");
+    }
+
+    #[test]
+    fn reject_pod_not_running() {
+        let mut pod = spark_connect_pod();
+        pod["status"]["phase"] = json!("Pending");
+        assert_rejected(
+            pod,
+            "it is in phase Pending, only running pods can be adopted",
+        );
+    }
+
+    #[test]
+    fn reject_pod_without_status() {
+        let mut pod = spark_connect_pod();
+        pod.as_object_mut().unwrap().remove("status");
+        assert_rejected(
+            pod,
+            "it is in phase Unknown, only running pods can be adopted",
+        );
+    }
+
+    #[test]
+    fn reject_pod_without_spark_connect_port() {
+        let mut pod = spark_connect_pod();
+        pod["spec"]["containers"][1]["ports"] = json!([{"containerPort": 4040}]);
+        assert_rejected(pod, "no container exposes the spark connect port 15002");
+    }
+
+    #[test]
+    fn reject_pod_with_unknown_image() {
+        for image in ["apache/spark:3.4.0", "bitnami/spark:3.5.4", "apache/spark"] {
+            let mut pod = spark_connect_pod();
+            pod["spec"]["containers"][1]["image"] = json!(image);
+            assert_rejected(
+                pod,
+                &format!(
+                    "unknown image `{}`, expected apache/spark:<version> with version in [\"3.5.3\", \"3.5.4\"]",
+                    image
+                ),
+            );
+        }
+    }
+
+    #[test]
+    fn convert_event_of_pending_pod() {
+        let event: Event = serde_json::from_value(json!({
+            "metadata": {"name": "rucat-spark-abc-driver.1"},
+            "involvedObject": {"kind": "Pod", "name": "rucat-spark-abc-driver"},
+            "type": "Warning",
+            "reason": "FailedScheduling",
+            "message": "0/3 nodes are available: 3 Insufficient cpu.",
+            "count": 4,
+            "lastTimestamp": "2025-01-01T00:00:00Z",
+        }))
+        .unwrap();
+        assert_eq!(
+            to_engine_resource_event(&event),
+            EngineResourceEvent {
+                object: "Pod/rucat-spark-abc-driver".to_owned(),
+                event_type: "Warning".to_owned(),
+                reason: "FailedScheduling".to_owned(),
+                message: "0/3 nodes are available: 3 Insufficient cpu.".to_owned(),
+                count: 4,
+                last_timestamp: Some(EngineTime::from_system_time(
+                    SystemTime::UNIX_EPOCH + ::std::time::Duration::from_secs(1_735_689_600)
+                )),
+            }
+        );
+    }
+
+    #[test]
+    fn last_time_of_event() {
+        let event = |value: Value| -> Event { serde_json::from_value(value).unwrap() };
+        let time = SystemTime::UNIX_EPOCH + ::std::time::Duration::from_secs(1_735_689_600);
+        assert_eq!(
+            get_last_time(&event(json!({
+                "metadata": {},
+                "involvedObject": {},
+                "eventTime": "2025-01-01T00:00:00.000000Z",
+            }))),
+            Some(time)
+        );
+        assert_eq!(
+            get_last_time(&event(json!({"metadata": {}, "involvedObject": {}}))),
+            None
+        );
+    }
+}
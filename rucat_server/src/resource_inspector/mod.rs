@@ -0,0 +1,63 @@
+//! Inspect resources of engines, e.g. resources deployed without rucat so that they can be adopted as engines,
+//! and the logs and the events of the engine resources.
+
+use ::core::future::Future;
+
+use ::futures::stream::BoxStream;
+use ::rucat_common::{
+    engine::{AdoptEngineRequest, EngineId, EngineInfo, EngineResourceEvent},
+    error::Result,
+    serde::Deserialize,
+};
+
+pub mod k8s_inspector;
+
+/// Options of reading the logs of an engine.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+#[serde(crate = "rucat_common::serde")]
+pub struct LogOptions {
+    /// Number of lines from the end of the logs to read, all the lines are read if not set.
+    pub tail_lines: Option<i64>,
+    /// Keep streaming the new lines until the engine stops.
+    #[serde(default)]
+    pub follow: bool,
+}
+
+/// Lines of the logs, each ended by a newline.
+pub type LogStream = BoxStream<'static, std::io::Result<String>>;
+
+pub trait ResourceInspector: Send + Sync + 'static {
+    /// Reconstruct the engine info from the resources in `request`.
+    /// Return an error explaining why if the resources cannot be managed by rucat.
+    fn inspect(
+        &self,
+        request: &AdoptEngineRequest,
+    ) -> impl Future<Output = Result<EngineInfo>> + Send;
+
+    /// Label the resources in `request` with the engine id,
+    /// so that the state monitor can find and clean them.
+    fn label(
+        &self,
+        request: &AdoptEngineRequest,
+        id: &EngineId,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Read the logs of the driver of the engine in the `project`.
+    /// Return `None` if the driver does not exist, e.g. the engine is stopped.
+    fn get_logs(
+        &self,
+        id: &EngineId,
+        project: Option<&str>,
+        options: LogOptions,
+    ) -> impl Future<Output = Result<Option<LogStream>>> + Send;
+
+    /// List the recent events of the resources of the engine in the `project`, the most recent first.
+    /// Return an empty list if the resources never existed.
+    fn get_events(
+        &self,
+        id: &EngineId,
+        project: Option<&str>,
+    ) -> impl Future<Output = Result<Vec<EngineResourceEvent>>> + Send;
+}
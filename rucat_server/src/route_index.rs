@@ -0,0 +1,104 @@
+//! Index of the routes of the server, listed by `GET /`.
+//! axum cannot list the routes of a router, so the paths are recorded when the routes are added.
+
+use axum::{routing::MethodRouter, Router};
+
+/// [Router] recording the paths of its routes.
+pub(crate) struct IndexedRouter<S = ()> {
+    router: Router<S>,
+    paths: Vec<String>,
+}
+
+impl<S> IndexedRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            router: Router::new(),
+            paths: Vec::new(),
+        }
+    }
+
+    /// Same as [Router::route].
+    pub(crate) fn route(mut self, path: &str, method_router: MethodRouter<S>) -> Self {
+        self.router = self.router.route(path, method_router);
+        self.paths.push(path.to_owned());
+        self
+    }
+
+    /// Same as [Router::nest], the paths of `other` are prefixed by `prefix`.
+    pub(crate) fn nest(mut self, prefix: &str, other: Self) -> Self {
+        self.router = self.router.nest(prefix, other.router);
+        self.paths.extend(other.paths.into_iter().map(|path| {
+            // The root of the nested router is served at the prefix, without the trailing slash.
+            if path == "/" {
+                prefix.to_owned()
+            } else {
+                format!("{}{}", prefix, path)
+            }
+        }));
+        self
+    }
+
+    /// Same as [Router::merge].
+    pub(crate) fn merge(mut self, other: Self) -> Self {
+        self.router = self.router.merge(other.router);
+        self.paths.extend(other.paths);
+        self
+    }
+
+    /// Merge a router built elsewhere, e.g. by another crate, whose routes are served under `paths`.
+    #[cfg_attr(not(feature = "swagger-ui"), allow(dead_code))]
+    pub(crate) fn merge_unindexed(mut self, paths: &[&str], router: Router<S>) -> Self {
+        self.router = self.router.merge(router);
+        self.paths
+            .extend(paths.iter().map(|path| (*path).to_owned()));
+        self
+    }
+
+    /// Update the router without adding routes, e.g. to add layers or fallbacks.
+    pub(crate) fn map(mut self, f: impl FnOnce(Router<S>) -> Router<S>) -> Self {
+        self.router = f(self.router);
+        self
+    }
+
+    /// Split into the router and its paths, sorted and deduplicated.
+    pub(crate) fn into_parts(self) -> (Router<S>, Vec<String>) {
+        let mut paths = self.paths;
+        paths.sort();
+        paths.dedup();
+        (self.router, paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::routing::get;
+
+    use super::*;
+
+    #[test]
+    fn record_paths() {
+        let engine = IndexedRouter::<()>::new()
+            .route("/", get(|| async {}))
+            .route("/{id}", get(|| async {}));
+        let (_, paths) = IndexedRouter::new()
+            .route("/healthz", get(|| async {}))
+            .merge(IndexedRouter::new().route("/version", get(|| async {})))
+            .nest("/engine", engine)
+            .merge_unindexed(&["/swagger-ui"], Router::new())
+            .map(|router| router.fallback(|| async {}))
+            .into_parts();
+        assert_eq!(
+            paths,
+            [
+                "/engine",
+                "/engine/{id}",
+                "/healthz",
+                "/swagger-ui",
+                "/version"
+            ]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Shutdown awareness of the server.
+//!
+//! Once shutdown is triggered, the server stops accepting requests that mutate engines,
+//! while read-only requests are still served until the in-flight requests are drained.
+
+use ::std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use ::axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ::http::{header::RETRY_AFTER, HeaderValue, Method};
+use ::rucat_common::{
+    anyhow::anyhow,
+    error::RucatError,
+    tokio::{self, sync::Notify},
+    tracing::{error, info, warn},
+};
+
+use crate::error::RucatServerError;
+
+/// Value of the `Retry-After` header (in seconds) for requests rejected during shutdown.
+const RETRY_AFTER_SECS: &str = "5";
+
+/// Handle shared between the server and whoever decides to shut it down.
+/// Cloning the handle is cheap, and all clones observe the same shutdown state.
+#[derive(Clone, Debug, Default)]
+pub struct ShutdownHandle {
+    shutting_down: Arc<AtomicBool>,
+    triggered: Arc<Notify>,
+}
+
+impl ShutdownHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark the server as shutting down. This is irreversible.
+    pub fn trigger(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        self.triggered.notify_waiters();
+    }
+
+    /// Wait until the shutdown is triggered, return immediately if it is already triggered.
+    pub async fn wait_for_trigger(&self) {
+        // Created before checking the state, so that a trigger in between is not missed.
+        let notified = self.triggered.notified();
+        if self.is_shutting_down() {
+            return;
+        }
+        notified.await;
+    }
+
+    /// Whether the shutdown has been triggered.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+}
+
+/// Wait for SIGTERM, sent by Kubernetes when the pod is deleted, or for ctrl-c.
+pub async fn termination_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+                info!("Received SIGTERM");
+            }
+            Err(e) => {
+                error!(
+                    "Fail to listen for SIGTERM, only ctrl-c shuts down the server: {}",
+                    e
+                );
+                ::std::future::pending::<()>().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = ::std::future::pending::<()>();
+
+    let ctrl_c = async {
+        match tokio::signal::ctrl_c().await {
+            Ok(()) => info!("Received ctrl-c"),
+            Err(e) => {
+                error!("Fail to listen for ctrl-c: {}", e);
+                ::std::future::pending::<()>().await
+            }
+        }
+    };
+
+    tokio::select! {
+        () = terminate => {},
+        () = ctrl_c => {},
+    }
+}
+
+/// Future for the graceful shutdown of the server, which completes once `signal` completes.
+/// The shutdown of `shutdown_handle` is triggered at that time, so that engine mutations are rejected
+/// while the in-flight requests are drained.
+pub async fn shutdown_on<S>(signal: S, shutdown_handle: ShutdownHandle)
+where
+    S: Future<Output = ()>,
+{
+    signal.await;
+    info!("Rucat server is shutting down, stop accepting engine mutations and drain in-flight requests");
+    shutdown_handle.trigger();
+}
+
+/// Run `serve` until it completes, or until `timeout` has elapsed since the shutdown of `shutdown_handle` is triggered.
+/// In-flight requests not drained by then are dropped.
+pub async fn drain_with_timeout<F, E>(
+    serve: F,
+    shutdown_handle: &ShutdownHandle,
+    timeout: Duration,
+) -> Result<(), E>
+where
+    F: Future<Output = Result<(), E>>,
+{
+    let deadline = async {
+        shutdown_handle.wait_for_trigger().await;
+        tokio::time::sleep(timeout).await;
+    };
+    tokio::select! {
+        result = serve => {
+            if shutdown_handle.is_shutting_down() {
+                info!("Rucat server is shut down, all in-flight requests are drained");
+            }
+            result
+        }
+        () = deadline => {
+            warn!(
+                "Rucat server is shut down, in-flight requests are not drained in {} seconds",
+                timeout.as_secs()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Middleware rejecting mutating requests with 503 once the shutdown is triggered.
+/// `GET`, `HEAD` and `OPTIONS` requests keep being served.
+pub(crate) async fn reject_mutation_on_shutdown(
+    State(shutdown_handle): State<ShutdownHandle>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let read_only = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    if read_only || !shutdown_handle.is_shutting_down() {
+        return next.run(request).await;
+    }
+    let mut response = RucatServerError::from(RucatError::service_unavailable(anyhow!(
+        "Server is shutting down, {} {} is rejected",
+        request.method(),
+        request.uri().path()
+    )))
+    .into_response();
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from_static(RETRY_AFTER_SECS));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shutdown_handle_is_shared_between_clones() {
+        let handle = ShutdownHandle::new();
+        let cloned = handle.clone();
+        assert!(!cloned.is_shutting_down());
+        handle.trigger();
+        assert!(cloned.is_shutting_down());
+    }
+
+    #[tokio::test]
+    async fn trigger_shutdown_on_signal() {
+        let handle = ShutdownHandle::new();
+        let (sender, receiver) = tokio::sync::oneshot::channel::<()>();
+        let shutdown = tokio::spawn(shutdown_on(
+            async {
+                let _ = receiver.await;
+            },
+            handle.clone(),
+        ));
+        tokio::task::yield_now().await;
+        assert!(!handle.is_shutting_down());
+
+        sender.send(()).unwrap();
+        shutdown.await.unwrap();
+        assert!(handle.is_shutting_down());
+        // already triggered
+        handle.wait_for_trigger().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drain_in_flight_requests() {
+        let handle = ShutdownHandle::new();
+        handle.trigger();
+        let serve = async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Err::<(), _>("serve error")
+        };
+        let result = drain_with_timeout(serve, &handle, Duration::from_secs(10)).await;
+        assert_eq!(result, Err("serve error"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stop_draining_after_timeout() {
+        let handle = ShutdownHandle::new();
+        let serve = ::std::future::pending::<Result<(), ()>>();
+        let start = tokio::time::Instant::now();
+        let drain = drain_with_timeout(serve, &handle, Duration::from_secs(10));
+        let trigger = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            handle.trigger();
+            ::std::future::pending::<()>().await
+        };
+        let result = tokio::select! {
+            result = drain => result,
+            () = trigger => unreachable!(),
+        };
+        assert_eq!(result, Ok(()));
+        // the timeout starts when the shutdown is triggered
+        assert_eq!(start.elapsed(), Duration::from_secs(15));
+    }
+}
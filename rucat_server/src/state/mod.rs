@@ -1,11 +1,46 @@
 //! Shared state between handlers.
 
-use ::std::sync::Arc;
+use ::std::{collections::BTreeSet, sync::Arc};
 
-use rucat_common::database::Database;
+use rucat_common::{
+    blob_store::BlobStoreClient,
+    clock::Clock,
+    database::Database,
+    engine::{EngineId, EngineState, EngineVersions, IdGenerator},
+    error::Result,
+    webhook::WebhookNotifier,
+};
+
+use crate::{
+    authentication::registry::{AuthConfigSource, DynAuthProvider},
+    login_lockout::LoginLockout,
+    metrics::Metrics,
+    rate_limit::RateLimiter,
+    shutdown::ShutdownHandle,
+    EngineEvents, EngineHistory, EngineLimits, EnginePolicy, IdempotencyKeys, Sources,
+};
 
 pub(crate) struct AppState<DB> {
     db: Arc<DB>,
+    blob_store: Option<Arc<BlobStoreClient>>,
+    shutdown_handle: ShutdownHandle,
+    clock: Arc<dyn Clock>,
+    id_generator: Arc<dyn IdGenerator>,
+    /// The same provider as the authentication middleware, which is replaced when it is reloaded.
+    auth_provider: Option<DynAuthProvider>,
+    auth_config: Option<Arc<dyn AuthConfigSource>>,
+    engine_limits: EngineLimits,
+    engine_versions: Arc<EngineVersions>,
+    engine_events: EngineEvents,
+    idempotency_keys: IdempotencyKeys,
+    engine_history: EngineHistory,
+    metrics: Arc<Metrics>,
+    /// Shared by all the clones, so that a client is limited across them.
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// Shared by all the clones, so that the failed logins are counted across them.
+    login_lockout: Option<Arc<LoginLockout>>,
+    webhook_notifier: Option<WebhookNotifier>,
+    projects: Option<Arc<BTreeSet<String>>>,
 }
 
 // Manually implement `Clone` because derive(Clone) requires `DB: Clone`
@@ -13,6 +48,22 @@ impl<DB> Clone for AppState<DB> {
     fn clone(&self) -> Self {
         Self {
             db: self.db.clone(),
+            blob_store: self.blob_store.clone(),
+            shutdown_handle: self.shutdown_handle.clone(),
+            clock: self.clock.clone(),
+            id_generator: self.id_generator.clone(),
+            auth_provider: self.auth_provider.clone(),
+            auth_config: self.auth_config.clone(),
+            engine_limits: self.engine_limits,
+            engine_versions: self.engine_versions.clone(),
+            engine_events: self.engine_events,
+            idempotency_keys: self.idempotency_keys,
+            engine_history: self.engine_history,
+            metrics: self.metrics.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            login_lockout: self.login_lockout.clone(),
+            webhook_notifier: self.webhook_notifier.clone(),
+            projects: self.projects.clone(),
         }
     }
 }
@@ -21,11 +72,128 @@ impl<DB> AppState<DB>
 where
     DB: Database,
 {
-    pub(crate) fn new(db: DB) -> Self {
-        Self { db: Arc::new(db) }
+    pub(crate) fn new(
+        db: DB,
+        auth_provider: Option<DynAuthProvider>,
+        blob_store: Option<BlobStoreClient>,
+        shutdown_handle: ShutdownHandle,
+        Sources {
+            clock,
+            id_generator,
+            auth_config,
+        }: Sources,
+        EnginePolicy {
+            limits,
+            versions,
+            rate_limit,
+            events,
+            idempotency_keys,
+            history,
+            webhooks,
+            projects,
+            login_lockout,
+            // applied by the layers of the router instead of the handlers
+            request_limits: _,
+            compression: _,
+            auth_exempt_paths: _,
+        }: EnginePolicy,
+    ) -> Result<Self> {
+        Ok(Self {
+            db: Arc::new(db),
+            blob_store: blob_store.map(Arc::new),
+            shutdown_handle,
+            clock,
+            id_generator,
+            auth_provider,
+            auth_config,
+            engine_limits: limits,
+            engine_versions: Arc::new(versions),
+            engine_events: events,
+            idempotency_keys,
+            engine_history: history,
+            metrics: Arc::new(Metrics::new()),
+            rate_limiter: rate_limit.map(|config| Arc::new(RateLimiter::new(&config))),
+            login_lockout: login_lockout.map(|config| Arc::new(LoginLockout::new(&config))),
+            webhook_notifier: webhooks.map(WebhookNotifier::new).transpose()?,
+            projects: projects.map(Arc::new),
+        })
+    }
+
+    /// Projects isolating the engines, `None` if the engines are not isolated.
+    pub(crate) fn get_projects(&self) -> Option<&BTreeSet<String>> {
+        self.projects.as_deref()
     }
 
     pub(crate) fn get_db(&self) -> &DB {
         &self.db
     }
+
+    pub(crate) fn get_blob_store(&self) -> Option<&BlobStoreClient> {
+        self.blob_store.as_deref()
+    }
+
+    pub(crate) fn get_shutdown_handle(&self) -> &ShutdownHandle {
+        &self.shutdown_handle
+    }
+
+    pub(crate) fn get_clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    pub(crate) fn get_id_generator(&self) -> &dyn IdGenerator {
+        self.id_generator.as_ref()
+    }
+
+    /// The auth provider, `None` if the authentication is disabled.
+    pub(crate) fn get_auth_provider(&self) -> Option<&DynAuthProvider> {
+        self.auth_provider.as_ref()
+    }
+
+    pub(crate) fn get_auth_config(&self) -> Option<&dyn AuthConfigSource> {
+        self.auth_config.as_deref()
+    }
+
+    pub(crate) fn get_engine_limits(&self) -> &EngineLimits {
+        &self.engine_limits
+    }
+
+    pub(crate) fn get_engine_versions(&self) -> &EngineVersions {
+        &self.engine_versions
+    }
+
+    pub(crate) fn get_engine_events(&self) -> &EngineEvents {
+        &self.engine_events
+    }
+
+    pub(crate) fn get_idempotency_keys(&self) -> &IdempotencyKeys {
+        &self.idempotency_keys
+    }
+
+    pub(crate) fn get_engine_history(&self) -> &EngineHistory {
+        &self.engine_history
+    }
+
+    pub(crate) fn get_metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    pub(crate) fn get_rate_limiter(&self) -> Option<&Arc<RateLimiter>> {
+        self.rate_limiter.as_ref()
+    }
+
+    pub(crate) fn get_login_lockout(&self) -> Option<&Arc<LoginLockout>> {
+        self.login_lockout.as_ref()
+    }
+
+    /// Notify the webhooks of the state change in the background, if they are configured.
+    pub(crate) fn notify_state_change(
+        &self,
+        id: &EngineId,
+        old_state: &EngineState,
+        new_state: &EngineState,
+    ) {
+        if let Some(notifier) = &self.webhook_notifier {
+            notifier.notify(id, old_state, new_state, self.clock.now());
+        }
+    }
 }
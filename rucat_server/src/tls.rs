@@ -0,0 +1,541 @@
+//! TLS termination of the server, so that HTTPS is served without a proxy in front of rucat.
+//! Clients can also be authenticated by their certificates signed by a configured CA.
+
+use ::std::{
+    io,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use ::rucat_common::{
+    anyhow::anyhow,
+    error::{Result, RucatError},
+    serde::Deserialize,
+    tokio::{self, net::TcpStream},
+    tracing::warn,
+};
+use axum::extract::Request;
+use axum_server::{
+    accept::Accept,
+    tls_rustls::{RustlsAcceptor, RustlsConfig},
+};
+use futures::future::BoxFuture;
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConfig,
+};
+use tokio_rustls::server::TlsStream;
+use tower::Service;
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+use crate::authentication::ClientCertificate;
+
+/// Paths of the PEM files of the server certificate and its private key,
+/// and of the CA verifying the client certificates.
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct TlsConfig {
+    /// Certificate chain, beginning with the certificate of the server.
+    pub cert_path: String,
+    /// Private key of the server certificate.
+    pub key_path: String,
+    /// CA certificates signing the client certificates. Client certificates are not requested if it is not set.
+    pub client_ca_path: Option<String>,
+    /// Whether connections without a client certificate signed by the CA are rejected in the TLS handshake.
+    /// Otherwise, the clients without certificates are authenticated by the headers.
+    #[serde(default)]
+    pub require_client_cert: bool,
+}
+
+/// Read and parse the certificate and the key of `config`,
+/// so that a wrong TLS config fails the server on startup rather than on the first connection.
+pub async fn load_tls_config(config: &TlsConfig) -> Result<RustlsConfig> {
+    let read = |path: &str, name: &'static str| {
+        let path = path.to_owned();
+        async move {
+            tokio::fs::read(&path).await.map_err(|e| {
+                RucatError::fail_to_start_server(anyhow!(
+                    "Fail to read TLS {} `{}`: {}",
+                    name,
+                    path,
+                    e
+                ))
+            })
+        }
+    };
+    let cert = read(&config.cert_path, "certificate").await?;
+    let key = read(&config.key_path, "key").await?;
+    let Some(client_ca_path) = &config.client_ca_path else {
+        if config.require_client_cert {
+            return Err(RucatError::fail_to_start_server(anyhow!(
+                "`require_client_cert` needs `client_ca_path` to verify the client certificates"
+            )));
+        }
+        return RustlsConfig::from_pem(cert, key).await.map_err(|e| {
+            RucatError::fail_to_start_server(anyhow!(
+                "Fail to parse TLS certificate `{}` and key `{}`: {}",
+                config.cert_path,
+                config.key_path,
+                e
+            ))
+        });
+    };
+    let client_ca = read(client_ca_path, "client CA").await?;
+    let verifier =
+        get_client_cert_verifier(&client_ca, config.require_client_cert).map_err(|e| {
+            RucatError::fail_to_start_server(anyhow!(
+                "Fail to parse TLS client CA `{}`: {}",
+                client_ca_path,
+                e
+            ))
+        })?;
+    let server_config = get_server_config(&cert, &key, verifier).map_err(|e| {
+        RucatError::fail_to_start_server(anyhow!(
+            "Fail to parse TLS certificate `{}` and key `{}`: {}",
+            config.cert_path,
+            config.key_path,
+            e
+        ))
+    })?;
+    Ok(RustlsConfig::from_config(Arc::new(server_config)))
+}
+
+/// Verifier of the client certificates signed by the CA certificates in `client_ca`,
+/// connections without certificates are allowed unless `required`.
+fn get_client_cert_verifier(
+    client_ca: &[u8],
+    required: bool,
+) -> ::std::result::Result<Arc<dyn rustls::server::danger::ClientCertVerifier>, String> {
+    let mut roots = RootCertStore::empty();
+    for ca in CertificateDer::pem_slice_iter(client_ca) {
+        roots
+            .add(ca.map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+    }
+    if roots.is_empty() {
+        return Err("no CA certificate".to_owned());
+    }
+    let builder = WebPkiClientVerifier::builder(Arc::new(roots));
+    let builder = if required {
+        builder
+    } else {
+        builder.allow_unauthenticated()
+    };
+    builder.build().map_err(|e| e.to_string())
+}
+
+fn get_server_config(
+    cert: &[u8],
+    key: &[u8],
+    verifier: Arc<dyn rustls::server::danger::ClientCertVerifier>,
+) -> ::std::result::Result<ServerConfig, String> {
+    let cert = CertificateDer::pem_slice_iter(cert)
+        .collect::<::std::result::Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let key = PrivateKeyDer::from_pem_slice(key).map_err(|e| e.to_string())?;
+    let mut config = ServerConfig::builder()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(cert, key)
+        .map_err(|e| e.to_string())?;
+    // same as `RustlsConfig::from_pem`
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// Name of the client in its certificate, `None` if the certificate cannot be parsed or has no name.
+fn get_client_certificate(cert: &CertificateDer) -> Option<ClientCertificate> {
+    let (_, cert) = X509Certificate::from_der(cert).ok()?;
+    let common_name = cert
+        .subject()
+        .iter_common_name()
+        .find_map(|name| name.as_str().ok().map(str::to_owned));
+    let name = common_name.or_else(|| {
+        cert.subject_alternative_name()
+            .ok()
+            .flatten()?
+            .value
+            .general_names
+            .iter()
+            .find_map(|name| match name {
+                GeneralName::DNSName(name) | GeneralName::URI(name) => Some((*name).to_owned()),
+                _ => None,
+            })
+    })?;
+    Some(ClientCertificate { name })
+}
+
+/// Acceptor of the TLS connections, which passes the client certificate of each connection to its requests.
+#[derive(Clone)]
+pub struct ClientCertAcceptor(RustlsAcceptor);
+
+impl ClientCertAcceptor {
+    pub fn new(config: RustlsConfig) -> Self {
+        Self(RustlsAcceptor::new(config))
+    }
+}
+
+impl<S> Accept<TcpStream, S> for ClientCertAcceptor
+where
+    S: Send + 'static,
+{
+    type Stream = TlsStream<TcpStream>;
+    type Service = WithClientCertificate<S>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: TcpStream, service: S) -> Self::Future {
+        let accept = self.0.accept(stream, service);
+        Box::pin(async move {
+            let (stream, service) = accept.await?;
+            let certificate = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(|cert| {
+                    let certificate = get_client_certificate(cert);
+                    if certificate.is_none() {
+                        warn!("Client certificate has neither a common name nor a DNS or URI alternative name");
+                    }
+                    certificate
+                });
+            Ok((
+                stream,
+                WithClientCertificate {
+                    inner: service,
+                    certificate,
+                },
+            ))
+        })
+    }
+}
+
+/// Service inserting the client certificate of its connection in the request extensions.
+#[derive(Clone)]
+pub struct WithClientCertificate<S> {
+    inner: S,
+    certificate: Option<ClientCertificate>,
+}
+
+impl<S, B> Service<Request<B>> for WithClientCertificate<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<::std::result::Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        if let Some(certificate) = &self.certificate {
+            request.extensions_mut().insert(certificate.clone());
+        }
+        self.inner.call(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::{io::Write, net::SocketAddr};
+
+    use axum::{routing::get, Extension, Router};
+    use rcgen::{
+        BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType,
+        ExtendedKeyUsagePurpose, IsCa, KeyPair,
+    };
+    use rustls::{pki_types::ServerName, ClientConfig};
+    use tempfile::NamedTempFile;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    use super::*;
+
+    /// Certificate and its key in PEM.
+    struct Pem {
+        cert: String,
+        key: String,
+    }
+
+    struct Ca {
+        cert: Certificate,
+        key: KeyPair,
+    }
+
+    impl Ca {
+        fn new() -> Self {
+            let key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::default();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "rucat test CA");
+            let cert = params.self_signed(&key).unwrap();
+            Self { cert, key }
+        }
+
+        /// Certificate signed by the CA.
+        /// - `common_name`: the certificate has no common name if it is `None`.
+        fn sign(
+            &self,
+            common_name: Option<&str>,
+            alt_names: &[&str],
+            usage: ExtendedKeyUsagePurpose,
+        ) -> Pem {
+            let key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::new(
+                alt_names
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+            params.distinguished_name = DistinguishedName::new();
+            if let Some(common_name) = common_name {
+                params
+                    .distinguished_name
+                    .push(DnType::CommonName, common_name);
+            }
+            params.extended_key_usages = vec![usage];
+            let cert = params.signed_by(&key, &self.cert, &self.key).unwrap();
+            Pem {
+                cert: cert.pem(),
+                key: key.serialize_pem(),
+            }
+        }
+
+        fn sign_client(&self, common_name: Option<&str>, alt_names: &[&str]) -> Pem {
+            self.sign(common_name, alt_names, ExtendedKeyUsagePurpose::ClientAuth)
+        }
+    }
+
+    fn get_name(pem: &Pem) -> Option<String> {
+        let cert = CertificateDer::from_pem_slice(pem.cert.as_bytes()).unwrap();
+        get_client_certificate(&cert).map(|certificate| certificate.name)
+    }
+
+    /// Start a TLS server with the certificate of `localhost` signed by `ca`,
+    /// which responds the name of the client certificate.
+    async fn start_server(ca: &Ca, require_client_cert: bool) -> SocketAddr {
+        let server = ca.sign(
+            Some("localhost"),
+            &["localhost"],
+            ExtendedKeyUsagePurpose::ServerAuth,
+        );
+        let cert = get_pem_file(&server.cert);
+        let key = get_pem_file(&server.key);
+        let client_ca = get_pem_file(&ca.cert.pem());
+        let config = load_tls_config(&TlsConfig {
+            cert_path: cert.path().to_str().unwrap().to_owned(),
+            key_path: key.path().to_str().unwrap().to_owned(),
+            client_ca_path: Some(client_ca.path().to_str().unwrap().to_owned()),
+            require_client_cert,
+        })
+        .await
+        .unwrap();
+        let router = Router::new().route(
+            "/",
+            get(
+                |certificate: Option<Extension<ClientCertificate>>| async move {
+                    certificate.map_or("no certificate".to_owned(), |Extension(certificate)| {
+                        certificate.name
+                    })
+                },
+            ),
+        );
+        let listener = ::std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum_server::from_tcp(listener)
+                .acceptor(ClientCertAcceptor::new(config))
+                .serve(router.into_make_service()),
+        );
+        address
+    }
+
+    /// Send `GET /` to the server with the client certificate, and return the response.
+    async fn send_request(
+        address: SocketAddr,
+        ca: &Ca,
+        client: Option<&Pem>,
+    ) -> io::Result<String> {
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.cert.der().clone()).unwrap();
+        let builder = ClientConfig::builder().with_root_certificates(roots);
+        let config = match client {
+            Some(client) => builder
+                .with_client_auth_cert(
+                    vec![CertificateDer::from_pem_slice(client.cert.as_bytes()).unwrap()],
+                    PrivateKeyDer::from_pem_slice(client.key.as_bytes()).unwrap(),
+                )
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+        let stream = TcpStream::connect(address).await?;
+        let mut stream = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await?;
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    fn get_pem_file(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file
+    }
+
+    #[tokio::test]
+    async fn fail_to_read_missing_files() {
+        let key = get_pem_file("");
+        let result = load_tls_config(&TlsConfig {
+            cert_path: "/not/exist/cert.pem".to_owned(),
+            key_path: key.path().to_str().unwrap().to_owned(),
+            client_ca_path: None,
+            require_client_cert: false,
+        })
+        .await;
+        assert!(result.unwrap_err().to_string().starts_with(
+            "Fail to start server: Fail to read TLS certificate `/not/exist/cert.pem`:"
+        ));
+
+        let cert = get_pem_file("");
+        let result = load_tls_config(&TlsConfig {
+            cert_path: cert.path().to_str().unwrap().to_owned(),
+            key_path: "/not/exist/key.pem".to_owned(),
+            client_ca_path: None,
+            require_client_cert: false,
+        })
+        .await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("Fail to start server: Fail to read TLS key `/not/exist/key.pem`:"));
+    }
+
+    #[tokio::test]
+    async fn fail_to_parse_invalid_files() {
+        let cert = get_pem_file("not a certificate");
+        let key = get_pem_file("not a key");
+        let config = TlsConfig {
+            cert_path: cert.path().to_str().unwrap().to_owned(),
+            key_path: key.path().to_str().unwrap().to_owned(),
+            client_ca_path: None,
+            require_client_cert: false,
+        };
+        let result = load_tls_config(&config).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!(
+                "Fail to start server: Fail to parse TLS certificate `{}` and key `{}`: private key format not supported",
+                config.cert_path, config.key_path
+            )
+        );
+    }
+
+    #[test]
+    fn get_name_of_client_certificate() {
+        let ca = Ca::new();
+        assert_eq!(
+            get_name(&ca.sign_client(Some("ci"), &["ci.example.com"])),
+            Some("ci".to_owned())
+        );
+        assert_eq!(
+            get_name(&ca.sign_client(None, &["ci.example.com"])),
+            Some("ci.example.com".to_owned())
+        );
+        assert_eq!(
+            get_name(&ca.sign_client(None, &["spiffe://example.com/ci"])),
+            Some("spiffe://example.com/ci".to_owned())
+        );
+        assert_eq!(get_name(&ca.sign_client(None, &[])), None);
+    }
+
+    #[tokio::test]
+    async fn client_ca_is_needed_to_require_client_cert() {
+        let ca = Ca::new();
+        let server = ca.sign(
+            Some("localhost"),
+            &["localhost"],
+            ExtendedKeyUsagePurpose::ServerAuth,
+        );
+        let cert = get_pem_file(&server.cert);
+        let key = get_pem_file(&server.key);
+        let result = load_tls_config(&TlsConfig {
+            cert_path: cert.path().to_str().unwrap().to_owned(),
+            key_path: key.path().to_str().unwrap().to_owned(),
+            client_ca_path: None,
+            require_client_cert: true,
+        })
+        .await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Fail to start server: `require_client_cert` needs `client_ca_path` to verify the client certificates"
+        );
+
+        let client_ca = get_pem_file("not a certificate");
+        let config = TlsConfig {
+            cert_path: cert.path().to_str().unwrap().to_owned(),
+            key_path: key.path().to_str().unwrap().to_owned(),
+            client_ca_path: Some(client_ca.path().to_str().unwrap().to_owned()),
+            require_client_cert: true,
+        };
+        let result = load_tls_config(&config).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!(
+                "Fail to start server: Fail to parse TLS client CA `{}`: no CA certificate",
+                config.client_ca_path.unwrap()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn require_client_cert_signed_by_ca() {
+        let ca = Ca::new();
+        let address = start_server(&ca, true).await;
+
+        let response = send_request(address, &ca, Some(&ca.sign_client(Some("ci"), &[])))
+            .await
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("\r\n\r\nci"), "{}", response);
+
+        // rejected in the TLS handshake
+        assert!(send_request(address, &ca, None).await.is_err());
+        let other_ca = Ca::new();
+        assert!(
+            send_request(address, &ca, Some(&other_ca.sign_client(Some("ci"), &[])))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn optional_client_cert() {
+        let ca = Ca::new();
+        let address = start_server(&ca, false).await;
+
+        let response = send_request(address, &ca, Some(&ca.sign_client(Some("ci"), &[])))
+            .await
+            .unwrap();
+        assert!(response.ends_with("\r\n\r\nci"), "{}", response);
+        let response = send_request(address, &ca, None).await.unwrap();
+        assert!(response.ends_with("\r\n\r\nno certificate"), "{}", response);
+        // Certificates are still verified if they are presented.
+        let other_ca = Ca::new();
+        assert!(
+            send_request(address, &ca, Some(&other_ca.sign_client(Some("ci"), &[])))
+                .await
+                .is_err()
+        );
+    }
+}
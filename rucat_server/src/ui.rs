@@ -0,0 +1,195 @@
+//! Built-in web UI for engine management, served at `/ui`.
+//! The UI is only static assets, which call the engine API with the credentials entered in the browser.
+
+use ::axum_extra::middleware::option_layer;
+use ::rucat_common::{error::Result, serde::Deserialize};
+use axum::{middleware, Router};
+use tower_http::trace::TraceLayer;
+
+use crate::authentication::{auth, registry::DynAuthProvider, AuthState};
+
+/// Configuration of the built-in web UI, which needs the server to be built with the `ui` feature.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct UiConfig {
+    /// Whether requests of the UI assets are authenticated as the API requests.
+    /// The assets contain no engine data, so they are served without authentication by default,
+    /// which lets the browser load the UI before the credentials are entered.
+    #[serde(default)]
+    pub require_auth: bool,
+}
+
+#[cfg(feature = "ui")]
+mod assets {
+    use ::rucat_common::{anyhow::anyhow, error::RucatError};
+    use axum::{
+        extract::State,
+        http::header::{CACHE_CONTROL, CONTENT_TYPE},
+        response::{IntoResponse, Response},
+        routing::get,
+        Router,
+    };
+    use include_dir::{include_dir, Dir};
+
+    use super::UiConfig;
+    use crate::{error::RucatServerError, extract::AppPath};
+
+    static ASSETS: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/ui");
+
+    const INDEX: &str = "index.html";
+
+    /// Assets other than the index are cached for a while, they are not renamed when they change.
+    const MAX_AGE_SECS: u32 = 3600;
+
+    #[derive(Clone, Copy)]
+    struct CachePolicy {
+        /// Authenticated assets must not be stored by shared caches.
+        private: bool,
+    }
+
+    impl CachePolicy {
+        /// The index is always revalidated so that a new version of the UI is loaded after an upgrade.
+        fn get_cache_control(&self, path: &str) -> String {
+            let visibility = if self.private { "private" } else { "public" };
+            if path == INDEX {
+                format!("{}, no-cache", visibility)
+            } else {
+                format!("{}, max-age={}", visibility, MAX_AGE_SECS)
+            }
+        }
+    }
+
+    fn get_content_type(path: &str) -> &'static str {
+        match path.rsplit_once('.').map(|(_, extension)| extension) {
+            Some("html") => "text/html; charset=utf-8",
+            Some("js") => "text/javascript; charset=utf-8",
+            Some("css") => "text/css; charset=utf-8",
+            Some("svg") => "image/svg+xml",
+            Some("png") => "image/png",
+            Some("ico") => "image/x-icon",
+            _ => "application/octet-stream",
+        }
+    }
+
+    fn serve_asset(path: &str, cache: CachePolicy) -> Result<Response, RucatServerError> {
+        let file = ASSETS
+            .get_file(path)
+            .ok_or_else(|| RucatError::not_found(anyhow!("UI asset {} not found", path)))?;
+        Ok((
+            [
+                (CONTENT_TYPE, get_content_type(path).to_owned()),
+                (CACHE_CONTROL, cache.get_cache_control(path)),
+            ],
+            file.contents(),
+        )
+            .into_response())
+    }
+
+    async fn get_index(State(cache): State<CachePolicy>) -> Result<Response, RucatServerError> {
+        serve_asset(INDEX, cache)
+    }
+
+    async fn get_asset(
+        AppPath(path): AppPath<String>,
+        State(cache): State<CachePolicy>,
+    ) -> Result<Response, RucatServerError> {
+        serve_asset(&path, cache)
+    }
+
+    pub(super) fn get_assets_router<S>(config: &UiConfig) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        Router::new()
+            .route("/ui", get(get_index))
+            .route("/ui/", get(get_index))
+            .route("/ui/{*path}", get(get_asset))
+            .with_state(CachePolicy {
+                private: config.require_auth,
+            })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn content_type_of_assets() {
+            assert_eq!(get_content_type("index.html"), "text/html; charset=utf-8");
+            assert_eq!(get_content_type("app.js"), "text/javascript; charset=utf-8");
+            assert_eq!(get_content_type("style.css"), "text/css; charset=utf-8");
+            assert_eq!(get_content_type("LICENSE"), "application/octet-stream");
+        }
+
+        #[test]
+        fn all_assets_have_known_content_types() {
+            for file in ASSETS.files() {
+                let path = file.path().to_str().unwrap();
+                assert_ne!(
+                    get_content_type(path),
+                    "application/octet-stream",
+                    "{}",
+                    path
+                );
+            }
+        }
+    }
+}
+
+/// Router serving the UI assets.
+#[cfg(feature = "ui")]
+pub(crate) fn get_ui_router<S>(config: &UiConfig) -> Result<Router<S>>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Ok(assets::get_assets_router(config))
+}
+
+/// The UI cannot be served without the assets.
+#[cfg(not(feature = "ui"))]
+pub(crate) fn get_ui_router<S>(_config: &UiConfig) -> Result<Router<S>>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    use ::rucat_common::{anyhow::anyhow, error::RucatError};
+
+    Err(RucatError::fail_to_start_server(anyhow!(
+        "The UI is configured, but rucat server is built without the `ui` feature"
+    )))
+}
+
+/// Serve the built-in web UI at `/ui` in front of the `server` got from [crate::get_server].
+/// Requests not for the UI go to the `server`, so they are still authenticated by it.
+/// # Parameters
+/// - `server`: router of the rucat server.
+/// - `config`: configuration of the UI.
+/// - `auth_provider`: the same authentication provider as the `server`,
+///   it is only used for the UI assets if `config.require_auth` is set.
+pub fn serve_ui(
+    server: Router,
+    config: UiConfig,
+    auth_provider: Option<DynAuthProvider>,
+) -> Result<Router> {
+    let auth_provider = auth_provider.filter(|_| config.require_auth);
+    let ui = get_ui_router(&config)?
+        .route_layer(option_layer(auth_provider.map(|auth_provider| {
+            middleware::from_fn_with_state(AuthState::new(auth_provider, vec![], None, None), auth)
+        })))
+        .route_layer(TraceLayer::new_for_http())
+        .fallback_service(server);
+    Ok(ui)
+}
+
+#[cfg(all(test, not(feature = "ui")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_needs_the_feature() {
+        let result = get_ui_router::<()>(&UiConfig::default());
+        assert!(result.is_err_and(|e| e.to_string().starts_with(
+            "Fail to start server: The UI is configured, but rucat server is built without the `ui` feature"
+        )));
+    }
+}
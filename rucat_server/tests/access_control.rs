@@ -0,0 +1,291 @@
+mod common;
+
+use ::std::collections::BTreeSet;
+
+use ::mockall::predicate;
+use ::rucat_common::{
+    database::{RemoveEngineResult, UpdateEngineStateResult},
+    engine::{EngineConfig, EngineInfo, EngineState::*, EngineTime, EngineType},
+    error::Result,
+    serde_json::{from_value, json},
+    tokio,
+};
+use ::rucat_server::authentication::static_auth_provider::{StaticAuthConfig, StaticAuthProvider};
+use axum_extra::headers::authorization::Credentials as _;
+use axum_test::{TestRequest, TestServer};
+use common::{get_error_code, get_error_message, get_test_server, MockDB};
+use headers::Authorization;
+use http::header::{HeaderName, AUTHORIZATION};
+
+const IMPERSONATE_USER: HeaderName = HeaderName::from_static("x-rucat-impersonate-user");
+
+/// Stopped engine created by `alice` and shared with `bob`.
+fn get_engine_info() -> EngineInfo {
+    let mut info = EngineInfo::new(
+        "test".to_owned(),
+        EngineType::Spark,
+        "3.5.4".to_owned(),
+        Terminated,
+        EngineConfig::new(),
+        EngineTime::now(),
+    );
+    info.created_by = Some("alice".to_owned());
+    info.allowed_principals = Some(BTreeSet::from(["bob".to_owned()]));
+    info
+}
+
+/// Database of the engine [get_engine_info], which can be changed in any way.
+fn get_db() -> MockDB {
+    let mut db = MockDB::new();
+    db.expect_get_engine()
+        .returning(|_| Ok(Some(get_engine_info())));
+    db.expect_update_engine_state()
+        .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    db.expect_update_engine_info()
+        .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    db.expect_remove_engine().returning(|_, _, _, _| {
+        Ok(Some(RemoveEngineResult::Success(Box::new(
+            get_engine_info(),
+        ))))
+    });
+    db.expect_set_engine_acl().returning(|_, _, _| Ok(Some(())));
+    db
+}
+
+/// Server with the owner `alice`, the shared user `bob`, the stranger `carol` and the admin `ops`.
+async fn get_server(db: MockDB) -> Result<TestServer> {
+    let auth_provider = StaticAuthProvider::from_config(
+        from_value::<StaticAuthConfig>(json!({
+            "users": [
+                {"username": "alice", "password": "pwd", "role": "operator"},
+                {"username": "bob", "password": "pwd", "role": "operator"},
+                {"username": "carol", "password": "pwd", "role": "operator"},
+                {"username": "ops", "password": "pwd", "role": "admin"}
+            ],
+            "bearer_tokens": []
+        }))
+        .unwrap(),
+    )?;
+    get_test_server(db, Some(auth_provider)).await
+}
+
+fn as_user(request: TestRequest, username: &str) -> TestRequest {
+    request.add_header(
+        AUTHORIZATION,
+        Authorization::basic(username, "pwd").0.encode(),
+    )
+}
+
+/// Requests restarting, updating and deleting the stopped engine `123`.
+fn change_engine(server: &TestServer) -> [TestRequest; 3] {
+    [
+        server.post("/engine/123/restart"),
+        server
+            .patch("/engine/123/config")
+            .json(&json!({"spark.executor.instances": "4"})),
+        server.delete("/engine/123"),
+    ]
+}
+
+#[tokio::test]
+async fn owner_shared_user_and_admin_can_change_engine() -> Result<()> {
+    let server = get_server(get_db()).await?;
+    for username in ["alice", "bob", "ops"] {
+        for request in change_engine(&server) {
+            as_user(request, username).await.assert_status_ok();
+        }
+        // rejected by the state of the engine, rather than the principal
+        let response = as_user(server.post("/engine/123/stop"), username).await;
+        assert_eq!(
+            get_error_message(&response),
+            "Engine 123 is in Terminated state, cannot be stopped"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn stranger_cannot_change_engine() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine()
+        .returning(|_| Ok(Some(get_engine_info())));
+    db.expect_update_engine_state().never();
+    db.expect_update_engine_info().never();
+    db.expect_remove_engine().never();
+    let server = get_server(db).await?;
+
+    let requests = change_engine(&server)
+        .into_iter()
+        .chain([server.post("/engine/123/stop")]);
+    for request in requests {
+        let response = as_user(request, "carol").await;
+        response.assert_status_forbidden();
+        assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+        assert_eq!(
+            get_error_message(&response),
+            "`carol` is not allowed to change engine 123, which is not shared with it"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn admin_impersonates_user_for_ownership_checks() -> Result<()> {
+    let server = get_server(get_db()).await?;
+    for request in change_engine(&server) {
+        as_user(request, "ops")
+            .add_header(IMPERSONATE_USER, "bob")
+            .await
+            .assert_status_ok();
+    }
+    for request in change_engine(&server) {
+        let response = as_user(request, "ops")
+            .add_header(IMPERSONATE_USER, "carol")
+            .await;
+        response.assert_status_forbidden();
+        assert_eq!(
+            get_error_message(&response),
+            "`carol` is not allowed to change engine 123, which is not shared with it"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_admins_impersonate_users() -> Result<()> {
+    let server = get_server(get_db()).await?;
+    let response = as_user(server.post("/engine/123/restart"), "alice")
+        .add_header(IMPERSONATE_USER, "bob")
+        .await;
+    response.assert_status_forbidden();
+    assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+    assert_eq!(
+        get_error_message(&response),
+        "`alice` is not allowed to impersonate `bob`, which needs the `admin` role"
+    );
+
+    let response = as_user(server.get("/engine/123"), "ops")
+        .add_header(IMPERSONATE_USER, "")
+        .await;
+    response.assert_status_bad_request();
+    assert_eq!(
+        get_error_message(&response),
+        "`x-rucat-impersonate-user` must be a non-empty visible ASCII string"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn impersonated_admin_is_not_admin() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_remove_engines_in_states().never();
+    let server = get_server(db).await?;
+
+    let response = as_user(server.post("/engine/cleanup"), "ops")
+        .add_header(IMPERSONATE_USER, "ops")
+        .json(&json!({"states": ["Terminated"], "older_than_secs": 0}))
+        .await;
+    response.assert_status_forbidden();
+    assert_eq!(
+        get_error_message(&response),
+        "`ops` is not allowed to POST /engine/cleanup, which needs the `admin` role"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn owner_and_admin_can_share_engine() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine()
+        .returning(|_| Ok(Some(get_engine_info())));
+    db.expect_set_engine_acl()
+        .with(
+            predicate::always(),
+            predicate::eq(BTreeSet::from(["bob".to_owned(), "carol".to_owned()])),
+            predicate::eq(None),
+        )
+        .times(2)
+        .returning(|_, _, _| Ok(Some(())));
+    let server = get_server(db).await?;
+
+    for username in ["alice", "ops"] {
+        as_user(server.put("/engine/123/acl"), username)
+            .json(&json!({"allowed_principals": ["bob", "carol"]}))
+            .await
+            .assert_status_ok();
+    }
+    for username in ["bob", "carol"] {
+        let response = as_user(server.put("/engine/123/acl"), username)
+            .json(&json!({"allowed_principals": ["bob", "carol"]}))
+            .await;
+        response.assert_status_forbidden();
+        assert_eq!(
+            get_error_message(&response),
+            format!(
+                "`{}` is not allowed to share engine 123, which is only allowed to its creator and admins",
+                username
+            )
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn share_engine_with_empty_principal() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_set_engine_acl().never();
+    let server = get_server(db).await?;
+    let response = as_user(server.put("/engine/123/acl"), "alice")
+        .json(&json!({"allowed_principals": [""]}))
+        .await;
+    response.assert_status_unprocessable_entity();
+    assert_eq!(
+        get_error_message(&response),
+        "Principal name cannot be empty"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_admins_delete_engines_in_batches() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_remove_engines_in_states()
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![]));
+    db.expect_purge_engine_events()
+        .times(1)
+        .returning(|_, _| Ok(0));
+    let server = get_server(db).await?;
+
+    let response = as_user(server.post("/engine/cleanup"), "alice")
+        .json(&json!({"states": ["Terminated"], "older_than_secs": 0}))
+        .await;
+    response.assert_status_forbidden();
+    assert_eq!(
+        get_error_message(&response),
+        "`alice` is not allowed to POST /engine/cleanup, which needs the `admin` role"
+    );
+    let response = as_user(server.post("/engine/batch-delete"), "alice")
+        .json(&json!({"ids": ["123"]}))
+        .await;
+    response.assert_status_forbidden();
+
+    as_user(server.post("/engine/cleanup"), "ops")
+        .json(&json!({"states": ["Terminated"], "older_than_secs": 0}))
+        .await
+        .assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn not_checked_without_auth() -> Result<()> {
+    let server = get_test_server(get_db(), None).await?;
+    server.post("/engine/123/restart").await.assert_status_ok();
+    server.delete("/engine/123").await.assert_status_ok();
+    server
+        .put("/engine/123/acl")
+        .json(&json!({"allowed_principals": []}))
+        .await
+        .assert_status_ok();
+    Ok(())
+}
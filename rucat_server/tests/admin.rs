@@ -0,0 +1,197 @@
+mod common;
+
+use ::std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use ::rucat_common::{
+    clock::MockClock,
+    database::TriggeredEngine,
+    engine::{
+        EngineId, EngineInfo, EngineState, EngineState::*, EngineTime, EngineType,
+        RandomIdGenerator,
+    },
+    error::*,
+    serde_json::{json, Value},
+    tokio,
+};
+use ::rucat_server::shutdown::ShutdownHandle;
+use axum_test::TestServer;
+use common::{
+    get_error_code, get_error_message, get_test_server, get_test_server_with_sources, MockDB,
+};
+
+/// Current time of the mock clock of the test server.
+fn now() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+}
+
+fn triggered_engine(
+    id: &'static str,
+    state: EngineState,
+    state_update_time: Option<SystemTime>,
+    trigger_timeout_time: SystemTime,
+) -> Result<TriggeredEngine> {
+    Ok(TriggeredEngine {
+        id: EngineId::try_from(id)?,
+        info: EngineInfo::new(
+            "engine".to_owned(),
+            EngineType::Spark,
+            "3.5.3".to_owned(),
+            state,
+            BTreeMap::new(),
+            EngineTime::from_system_time(now()),
+        ),
+        state_update_time,
+        trigger_timeout_time,
+    })
+}
+
+fn seeded_engines() -> Result<Vec<TriggeredEngine>> {
+    let now = now();
+    Ok(vec![
+        triggered_engine(
+            "1",
+            TriggerStart,
+            Some(now - Duration::from_secs(1000)),
+            now + Duration::from_secs(1000),
+        )?,
+        triggered_engine(
+            "2",
+            TriggerTermination,
+            Some(now - Duration::from_secs(5000)),
+            now - Duration::from_secs(1000),
+        )?,
+        triggered_engine(
+            "3",
+            ErrorTriggerClean(Cow::Borrowed("error")),
+            None,
+            now - Duration::from_secs(1000),
+        )?,
+    ])
+}
+
+fn list_triggered_engines_db(times: usize) -> MockDB {
+    let mut db = MockDB::new();
+    db.expect_list_triggered_engines()
+        .times(times)
+        .returning(seeded_engines);
+    db
+}
+
+async fn get_in_flight_server(db: MockDB, clock: Arc<MockClock>) -> Result<TestServer> {
+    get_test_server_with_sources(
+        db,
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        clock,
+        Arc::new(RandomIdGenerator),
+    )
+    .await
+}
+
+fn get_ids(engines: &Value) -> Vec<Value> {
+    engines
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|engine| engine["id"]["id"].clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn list_in_flight_engines() -> Result<()> {
+    let clock = Arc::new(MockClock::new(now()));
+    let server = get_in_flight_server(list_triggered_engines_db(1), clock).await?;
+
+    let response = server.get("/admin/in-flight").await;
+
+    response.assert_status_ok();
+    let engines = response.json::<Value>();
+    let engines = engines.as_array().unwrap();
+    assert_eq!(engines.len(), 3);
+
+    assert_eq!(engines[0]["id"], json!({"id": "1"}));
+    assert_eq!(engines[0]["state"], json!("TriggerStart"));
+    assert_eq!(engines[0]["age_secs"], json!(1000));
+    assert_eq!(engines[0]["remaining_timeout_secs"], json!(1000));
+    assert_eq!(engines[0]["stale"], json!(false));
+
+    assert_eq!(engines[1]["id"], json!({"id": "2"}));
+    assert_eq!(engines[1]["age_secs"], json!(5000));
+    assert_eq!(engines[1]["remaining_timeout_secs"], json!(0));
+    assert_eq!(engines[1]["stale"], json!(true));
+
+    assert_eq!(engines[2]["id"], json!({"id": "3"}));
+    assert_eq!(engines[2]["state"], json!({"ErrorTriggerClean": "error"}));
+    assert_eq!(engines[2]["age_secs"], json!(null));
+    assert_eq!(engines[2]["stale"], json!(true));
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_stale_in_flight_engines() -> Result<()> {
+    let clock = Arc::new(MockClock::new(now()));
+    let server = get_in_flight_server(list_triggered_engines_db(1), clock).await?;
+
+    let response = server.get("/admin/in-flight?stale=true").await;
+
+    response.assert_status_ok();
+    assert_eq!(
+        get_ids(&response.json::<Value>()),
+        vec![json!("2"), json!("3")]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_not_stale_in_flight_engines() -> Result<()> {
+    let clock = Arc::new(MockClock::new(now()));
+    let server = get_in_flight_server(list_triggered_engines_db(1), clock).await?;
+
+    let response = server.get("/admin/in-flight?stale=false").await;
+
+    response.assert_status_ok();
+    assert_eq!(get_ids(&response.json::<Value>()), vec![json!("1")]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn in_flight_engine_becomes_stale_after_trigger_timeout() -> Result<()> {
+    let clock = Arc::new(MockClock::new(now()));
+    let server = get_in_flight_server(list_triggered_engines_db(2), clock.clone()).await?;
+
+    clock.advance(Duration::from_secs(999));
+    let response = server.get("/admin/in-flight?stale=false").await;
+    response.assert_status_ok();
+    let engines = response.json::<Value>();
+    assert_eq!(get_ids(&engines), vec![json!("1")]);
+    assert_eq!(engines[0]["age_secs"], json!(1999));
+    assert_eq!(engines[0]["remaining_timeout_secs"], json!(1));
+
+    clock.advance(Duration::from_secs(2));
+    let response = server.get("/admin/in-flight?stale=true").await;
+    response.assert_status_ok();
+    assert_eq!(
+        get_ids(&response.json::<Value>()),
+        vec![json!("1"), json!("2"), json!("3")]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_in_flight_engines_with_invalid_query() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server.get("/admin/in-flight?stale=maybe").await;
+
+    response.assert_status_bad_request();
+    assert_eq!(get_error_code(&response), "INVALID_REQUEST");
+    assert!(get_error_message(&response).starts_with("Failed to deserialize query string"));
+    Ok(())
+}
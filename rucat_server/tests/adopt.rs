@@ -0,0 +1,289 @@
+mod common;
+
+use ::std::{borrow::Cow, collections::BTreeMap, sync::Arc};
+
+use ::mockall::predicate;
+use ::rucat_common::{
+    anyhow::anyhow,
+    clock::SystemClock,
+    database::RemoveEngineResult,
+    engine::{
+        AdoptEngineRequest, EngineId, EngineInfo, EngineState::*, EngineTime, EngineType,
+        SequentialIdGenerator,
+    },
+    error::*,
+    serde_json::{from_value, json},
+    tokio,
+};
+use ::rucat_server::{
+    authentication::static_auth_provider::{StaticAuthConfig, StaticAuthProvider},
+    shutdown::ShutdownHandle,
+};
+use axum_extra::headers::authorization::Credentials as _;
+use axum_test::TestServer;
+use common::{
+    get_error_code, get_error_message, get_test_server, get_test_server_with_sources, MockDB,
+    MockInspector,
+};
+use headers::Authorization;
+use http::{header::AUTHORIZATION, StatusCode};
+
+fn adopt_request(id: Option<&str>) -> AdoptEngineRequest {
+    AdoptEngineRequest {
+        pod: "spark-connect-0".to_owned(),
+        service: Some("spark-connect".to_owned()),
+        namespace: "spark".to_owned(),
+        id: id.map(str::to_owned),
+    }
+}
+
+fn adopted_engine_info() -> EngineInfo {
+    EngineInfo::new(
+        "spark-connect-0".to_owned(),
+        EngineType::Spark,
+        "3.5.4".to_owned(),
+        Running,
+        BTreeMap::new(),
+        EngineTime::now(),
+    )
+}
+
+async fn get_adopt_server(db: MockDB, inspector: MockInspector) -> Result<TestServer> {
+    get_adopt_server_with_auth(db, inspector, None).await
+}
+
+async fn get_adopt_server_with_auth(
+    db: MockDB,
+    inspector: MockInspector,
+    auth_provider: Option<StaticAuthProvider>,
+) -> Result<TestServer> {
+    get_test_server_with_sources(
+        db,
+        auth_provider,
+        None,
+        Some(inspector),
+        ShutdownHandle::new(),
+        Arc::new(SystemClock),
+        Arc::new(SequentialIdGenerator::new(Cow::Borrowed("engine-"), 0)),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn adopt_engine() -> Result<()> {
+    let mut inspector = MockInspector::new();
+    inspector
+        .expect_inspect()
+        .with(predicate::eq(adopt_request(None)))
+        .times(1)
+        .returning(|_| Ok(adopted_engine_info()));
+    inspector
+        .expect_label()
+        .with(
+            predicate::eq(adopt_request(None)),
+            predicate::eq(EngineId::try_from("engine-0")?),
+        )
+        .times(1)
+        .returning(|_, _| Ok(()));
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .withf(|id, info, next_update_time, idempotency_key| {
+            id.to_string() == "engine-0"
+                && info.state == Running
+                && next_update_time.is_some()
+                && idempotency_key.is_none()
+        })
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let server = get_adopt_server(db, inspector).await?;
+
+    let response = server
+        .post("/engine/adopt")
+        .json(&json!({
+            "pod": "spark-connect-0",
+            "service": "spark-connect",
+            "namespace": "spark"
+        }))
+        .await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({"id": "engine-0"}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn adopt_engine_with_id() -> Result<()> {
+    let mut inspector = MockInspector::new();
+    inspector
+        .expect_inspect()
+        .times(1)
+        .returning(|_| Ok(adopted_engine_info()));
+    inspector
+        .expect_label()
+        .with(
+            predicate::always(),
+            predicate::eq(EngineId::try_from("my-engine")?),
+        )
+        .times(1)
+        .returning(|_, _| Ok(()));
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .withf(|id, _, _, _| id.to_string() == "my-engine")
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let server = get_adopt_server(db, inspector).await?;
+
+    let response = server
+        .post("/engine/adopt")
+        .json(&adopt_request(Some("my-engine")))
+        .await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({"id": "my-engine"}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn adopt_engine_with_reserved_id() -> Result<()> {
+    let server = get_adopt_server(MockDB::new(), MockInspector::new()).await?;
+
+    for id in ["adopt", "batch-delete", "batch-get", "cleanup", "stats"] {
+        let response = server
+            .post("/engine/adopt")
+            .json(&adopt_request(Some(id)))
+            .await;
+
+        response.assert_status_forbidden();
+        assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+        assert_eq!(
+            get_error_message(&response),
+            format!("Engine id `{}` is reserved", id)
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn adopt_engine_rejected_by_inspector() -> Result<()> {
+    let mut inspector = MockInspector::new();
+    inspector.expect_inspect().times(1).returning(|_| {
+        Err(RucatError::not_allowed(anyhow!(
+            "Pod spark-connect-0 cannot be adopted: no container exposes the spark connect port 15002"
+        )))
+    });
+    let server = get_adopt_server(MockDB::new(), inspector).await?;
+
+    let response = server
+        .post("/engine/adopt")
+        .json(&adopt_request(None))
+        .await;
+
+    response.assert_status_forbidden();
+    assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+    assert!(get_error_message(&response).starts_with(
+        "Pod spark-connect-0 cannot be adopted: no container exposes the spark connect port 15002"
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn remove_adopted_engine_if_labelling_fails() -> Result<()> {
+    let mut inspector = MockInspector::new();
+    inspector
+        .expect_inspect()
+        .times(1)
+        .returning(|_| Ok(adopted_engine_info()));
+    inspector
+        .expect_label()
+        .times(1)
+        .returning(|_, _| Err(RucatError::fail_to_start_engine(anyhow!("forbidden"))));
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    db.expect_remove_engine()
+        .with(
+            predicate::eq(EngineId::try_from("engine-0")?),
+            predicate::eq(Running),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .returning(|_, _, _, _| {
+            Ok(Some(RemoveEngineResult::Success(Box::new(
+                adopted_engine_info(),
+            ))))
+        });
+    let server = get_adopt_server(db, inspector).await?;
+
+    let response = server
+        .post("/engine/adopt")
+        .json(&adopt_request(None))
+        .await;
+
+    response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(get_error_code(&response), "FAIL_TO_START_ENGINE");
+    assert!(get_error_message(&response).starts_with("forbidden"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_admins_adopt_engines() -> Result<()> {
+    let mut inspector = MockInspector::new();
+    inspector
+        .expect_inspect()
+        .times(1)
+        .returning(|_| Ok(adopted_engine_info()));
+    inspector.expect_label().times(1).returning(|_, _| Ok(()));
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let auth_provider = StaticAuthProvider::from_config(
+        from_value::<StaticAuthConfig>(json!({
+            "users": [
+                {"username": "alice", "password": "pwd", "role": "operator"},
+                {"username": "ops", "password": "pwd", "role": "admin"}
+            ],
+            "bearer_tokens": []
+        }))
+        .unwrap(),
+    )?;
+    let server = get_adopt_server_with_auth(db, inspector, Some(auth_provider)).await?;
+    let adopt_as = |username: &str| {
+        server
+            .post("/engine/adopt")
+            .add_header(
+                AUTHORIZATION,
+                Authorization::basic(username, "pwd").0.encode(),
+            )
+            .json(&adopt_request(None))
+    };
+
+    // the pod is neither inspected nor labelled for operators
+    let response = adopt_as("alice").await;
+    response.assert_status_forbidden();
+    assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+    assert_eq!(
+        get_error_message(&response),
+        "`alice` is not allowed to POST /engine/adopt, which needs the `admin` role"
+    );
+
+    let response = adopt_as("ops").await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"id": "engine-0"}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn adoption_is_disabled_by_default() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server
+        .post("/engine/adopt")
+        .json(&adopt_request(None))
+        .await;
+
+    response.assert_status(StatusCode::METHOD_NOT_ALLOWED);
+    Ok(())
+}
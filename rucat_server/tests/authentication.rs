@@ -1,15 +1,90 @@
 mod common;
 
-use ::rucat_common::{error::Result, tokio};
-use ::rucat_server::authentication::static_auth_provider::StaticAuthProvider;
+use ::std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ::mockall::predicate;
+use ::rucat_common::{
+    anyhow::anyhow,
+    database::{DatabaseHealth, EngineFilter, EngineSort, RemoveEngineResult},
+    engine::{EngineId, EngineInfo, EngineState::WaitToStart, EngineTime, EngineType},
+    error::{Result, RucatError},
+    serde_json::{from_value, json},
+    tokio, tracing, tracing_subscriber,
+};
+use ::rucat_server::{
+    authentication::{
+        api_key_auth_provider::{ApiKeyAuthConfig, ApiKeyAuthProvider},
+        registry::DynAuthProvider,
+        role::Role,
+        static_auth_provider::{StaticAuthConfig, StaticAuthProvider},
+        Authenticate, ClientCertificate, Credentials, Principal,
+    },
+    get_server,
+    shutdown::ShutdownHandle,
+    EnginePolicy, Sources,
+};
+use axum::{extract::Request, middleware, middleware::Next};
 use axum_extra::headers::authorization::Credentials as _;
-use common::{get_test_server, MockDB};
+use axum_test::TestServer;
+use common::{
+    get_error_code, get_error_message, get_test_app, get_test_server, MockDB, MockInspector,
+};
 use headers::Authorization;
-use http::{header::AUTHORIZATION, HeaderValue};
+use http::{
+    header::{HeaderName, AUTHORIZATION},
+    HeaderValue,
+};
 
 static USERNAME: &str = "admin";
 static PWD: &str = "admin";
 static TOKEN: &str = "admin"; // Bearer token
+const API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+const IMPERSONATE_USER: HeaderName = HeaderName::from_static("x-rucat-impersonate-user");
+
+/// Test server authenticated by any provider, unlike [get_test_server] only by the static one.
+fn get_server_with_auth_provider<AuthProvider>(
+    db: MockDB,
+    auth_provider: AuthProvider,
+    engine_policy: EnginePolicy,
+) -> Result<TestServer>
+where
+    AuthProvider: Authenticate,
+{
+    let app = get_server(
+        db,
+        Some(DynAuthProvider::new(auth_provider)),
+        None,
+        None::<MockInspector>,
+        ShutdownHandle::new(),
+        Sources::default(),
+        engine_policy,
+    )?;
+    TestServer::new(app).map_err(RucatError::fail_to_start_server)
+}
+
+/// Provider validating the bearer tokens by an identity service, which is down for the token `down`.
+struct AsyncAuthProvider;
+
+impl Authenticate for AsyncAuthProvider {
+    async fn validate(&self, credentials: &Credentials) -> Result<Option<Principal>> {
+        let Credentials::Bearer(bearer) = credentials else {
+            return Ok(None);
+        };
+        tokio::task::yield_now().await;
+        match bearer.token() {
+            "down" => Err(RucatError::service_unavailable(anyhow!(
+                "Identity service is down"
+            ))),
+            token => Ok(token
+                .strip_prefix("user-")
+                .map(|name| Principal::new(name, [Role::Admin]))),
+        }
+    }
+}
 
 #[tokio::test]
 async fn without_auth_header() -> Result<()> {
@@ -21,6 +96,11 @@ async fn without_auth_header() -> Result<()> {
     let response = server.get("/any").await;
 
     response.assert_status_unauthorized();
+    assert_eq!(get_error_code(&response), "UNAUTHORIZED");
+    assert_eq!(
+        get_error_message(&response),
+        "Not found authorization header"
+    );
     Ok(())
 }
 
@@ -32,7 +112,7 @@ async fn with_wrong_basic_auth() -> Result<()> {
     let server = get_test_server(db, Some(auth_provider)).await?;
 
     let response = server
-        .get("/")
+        .get("/capabilities")
         .add_header(
             AUTHORIZATION,
             Authorization::basic("wrong", "wrong").0.encode(),
@@ -40,7 +120,8 @@ async fn with_wrong_basic_auth() -> Result<()> {
         .await;
 
     response.assert_status_unauthorized();
-    response.assert_text("Unauthorized: wrong credentials");
+    assert_eq!(get_error_code(&response), "UNAUTHORIZED");
+    assert_eq!(get_error_message(&response), "wrong credentials");
     Ok(())
 }
 
@@ -60,7 +141,8 @@ async fn with_wrong_bearer_auth() -> Result<()> {
         .await;
 
     response.assert_status_unauthorized();
-    response.assert_text("Unauthorized: wrong credentials");
+    assert_eq!(get_error_code(&response), "UNAUTHORIZED");
+    assert_eq!(get_error_message(&response), "wrong credentials");
     Ok(())
 }
 
@@ -72,7 +154,7 @@ async fn with_unsupported_auth() -> Result<()> {
     let server = get_test_server(db, Some(auth_provider)).await?;
 
     let response = server
-      .get("/")
+      .get("/capabilities")
       // AWS signature
       .add_header(
         AUTHORIZATION,
@@ -82,7 +164,8 @@ async fn with_unsupported_auth() -> Result<()> {
       .await;
 
     response.assert_status_unauthorized();
-    response.assert_text("Unauthorized: Unsupported credentials type");
+    assert_eq!(get_error_code(&response), "UNAUTHORIZED");
+    assert_eq!(get_error_message(&response), "Unsupported credentials type");
     Ok(())
 }
 
@@ -94,7 +177,7 @@ async fn basic_auth_successful() -> Result<()> {
     let server = get_test_server(db, Some(auth_provider)).await?;
 
     let response = server
-        .get("/")
+        .get("/capabilities")
         .add_header(
             AUTHORIZATION,
             Authorization::basic(USERNAME, PWD).0.encode(),
@@ -113,13 +196,633 @@ async fn bearer_auth_successful() -> Result<()> {
     let server = get_test_server(db, Some(auth_provider)).await?;
 
     let response = server
-        .get("/")
+        .get("/capabilities")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::bearer(TOKEN).unwrap().0.encode(),
+        )
+        .await;
+
+    response.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn probes_without_auth() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_health_check().times(1).returning(|| {
+        Ok(DatabaseHealth {
+            latency: Duration::ZERO,
+            version: None,
+        })
+    });
+    let auth_provider =
+        StaticAuthProvider::new(USERNAME.to_owned(), PWD.to_owned(), TOKEN.to_owned());
+    let server = get_test_server(db, Some(auth_provider)).await?;
+
+    server.get("/").await.assert_status_ok();
+    server.get("/healthz").await.assert_status_ok();
+    server.get("/readyz").await.assert_status_ok();
+    server.get("/version").await.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_routes_need_auth() -> Result<()> {
+    let db = MockDB::new();
+    let auth_provider =
+        StaticAuthProvider::new(USERNAME.to_owned(), PWD.to_owned(), TOKEN.to_owned());
+    let server = get_test_server(db, Some(auth_provider)).await?;
+
+    for response in [
+        server.get("/engine").await,
+        server.get("/engine/123").await,
+        server.post("/engine/123/stop").await,
+        server.get("/admin/in-flight").await,
+        server.get("/capabilities").await,
+    ] {
+        response.assert_status_unauthorized();
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_engine_records_creator() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .withf(|_, info, _, _| info.created_by.as_deref() == Some(USERNAME))
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let auth_provider =
+        StaticAuthProvider::new(USERNAME.to_owned(), PWD.to_owned(), TOKEN.to_owned());
+    let server = get_test_server(db, Some(auth_provider)).await?;
+
+    let response = server
+        .post("/engine")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic(USERNAME, PWD).0.encode(),
+        )
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.4",
+        }))
+        .await;
+
+    response.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_owned_by_me() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_list_engines()
+        .with(
+            predicate::always(),
+            predicate::always(),
+            predicate::eq(EngineFilter {
+                created_by: Some(USERNAME.to_owned()),
+                ..EngineFilter::default()
+            }),
+            predicate::eq(EngineSort::default()),
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![EngineId::try_from("1")?]));
+    let auth_provider =
+        StaticAuthProvider::new(USERNAME.to_owned(), PWD.to_owned(), TOKEN.to_owned());
+    let server = get_test_server(db, Some(auth_provider)).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("owner", "me")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic(USERNAME, PWD).0.encode(),
+        )
+        .await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [{"id": "1"}], "next_offset": null}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_owned_by_other_user() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_list_engines()
+        .with(
+            predicate::always(),
+            predicate::always(),
+            predicate::eq(EngineFilter {
+                created_by: Some("alice".to_owned()),
+                ..EngineFilter::default()
+            }),
+            predicate::eq(EngineSort::default()),
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![]));
+    let auth_provider =
+        StaticAuthProvider::new(USERNAME.to_owned(), PWD.to_owned(), TOKEN.to_owned());
+    let server = get_test_server(db, Some(auth_provider)).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("owner", "alice")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::bearer(TOKEN).unwrap().0.encode(),
+        )
+        .await;
+
+    response.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn authenticate_multiple_users() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .withf(|_, info, _, _| info.created_by.as_deref() == Some("ci"))
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    db.expect_list_engines()
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![]));
+    let auth_provider = StaticAuthProvider::from_config(
+        from_value::<StaticAuthConfig>(json!({
+            "users": [
+                {"username": "alice", "password": "pwd-a"},
+                {"username": "bob", "password": "pwd-b"}
+            ],
+            "bearer_tokens": [{"name": "ci", "token": "token-ci"}]
+        }))
+        .unwrap(),
+    )?;
+    let server = get_test_server(db, Some(auth_provider)).await?;
+
+    server
+        .get("/engine")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("bob", "pwd-b").0.encode(),
+        )
+        .await
+        .assert_status_ok();
+    server
+        .get("/engine")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("alice", "pwd-b").0.encode(),
+        )
+        .await
+        .assert_status_unauthorized();
+    server
+        .post("/engine")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::bearer("token-ci").unwrap().0.encode(),
+        )
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.4",
+        }))
+        .await
+        .assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn authenticate_api_keys() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .withf(|_, info, _, _| info.created_by.as_deref() == Some("ci"))
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let auth_provider = ApiKeyAuthProvider::new(
+        from_value::<ApiKeyAuthConfig>(json!({
+            "keys": {"key-ci": {"name": "ci", "roles": ["admin"]}}
+        }))
+        .unwrap(),
+    )?;
+    let server = get_server_with_auth_provider(db, auth_provider, EnginePolicy::default())?;
+
+    server
+        .post("/engine")
+        .add_header(API_KEY, "key-ci")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.4",
+        }))
+        .await
+        .assert_status_ok();
+
+    let response = server.get("/engine").add_header(API_KEY, "key-cd").await;
+    response.assert_status_unauthorized();
+    assert_eq!(get_error_message(&response), "wrong credentials");
+
+    let response = server
+        .get("/engine")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("ci", "key-ci").0.encode(),
+        )
+        .await;
+    response.assert_status_unauthorized();
+    assert_eq!(get_error_message(&response), "wrong credentials");
+    Ok(())
+}
+
+#[tokio::test]
+async fn api_key_is_checked_before_authorization_header() -> Result<()> {
+    let auth_provider =
+        StaticAuthProvider::new(USERNAME.to_owned(), PWD.to_owned(), TOKEN.to_owned());
+    let server = get_test_server(MockDB::new(), Some(auth_provider)).await?;
+
+    let response = server
+        .get("/engine")
+        .add_header(API_KEY, TOKEN)
         .add_header(
             AUTHORIZATION,
             Authorization::bearer(TOKEN).unwrap().0.encode(),
         )
         .await;
 
+    response.assert_status_unauthorized();
+    assert_eq!(get_error_message(&response), "wrong credentials");
+    Ok(())
+}
+
+/// Test server with the static provider, exempting `auth_exempt_paths` from authentication.
+fn get_server_with_exempt_paths(db: MockDB, auth_exempt_paths: Vec<String>) -> Result<TestServer> {
+    let auth_provider =
+        StaticAuthProvider::new(USERNAME.to_owned(), PWD.to_owned(), TOKEN.to_owned());
+    get_server_with_auth_provider(
+        db,
+        auth_provider,
+        EnginePolicy {
+            auth_exempt_paths,
+            ..EnginePolicy::default()
+        },
+    )
+}
+
+#[tokio::test]
+async fn exempt_paths_without_auth() -> Result<()> {
+    let server = get_server_with_exempt_paths(MockDB::new(), vec!["/capabilities".to_owned()])?;
+
+    server.get("/capabilities").await.assert_status_ok();
+    let response = server.get("/engine").await;
+    response.assert_status_unauthorized();
+    assert_eq!(get_error_code(&response), "UNAUTHORIZED");
+    assert_eq!(
+        get_error_message(&response),
+        "Not found authorization header"
+    );
+    server
+        .get("/admin/in-flight")
+        .await
+        .assert_status_unauthorized();
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_paths_cannot_be_exempt() {
+    for path in ["/", "/engine", "/engine/123/logs"] {
+        let result = get_server_with_exempt_paths(MockDB::new(), vec![path.to_owned()]);
+        assert!(
+            result.is_err_and(|e| e.to_string()
+                == format!(
+                    "Fail to load config: Auth exempt path `{}` covers `/engine`, whose routes are always authenticated",
+                    path
+                )),
+            "{}",
+            path
+        );
+    }
+}
+
+#[tokio::test]
+async fn authenticate_by_async_provider() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .withf(|_, info, _, _| info.created_by.as_deref() == Some("alice"))
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let server = get_server_with_auth_provider(db, AsyncAuthProvider, EnginePolicy::default())?;
+
+    server
+        .post("/engine")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::bearer("user-alice").unwrap().0.encode(),
+        )
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.4",
+        }))
+        .await
+        .assert_status_ok();
+
+    let response = server
+        .get("/engine")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::bearer("alice").unwrap().0.encode(),
+        )
+        .await;
+    response.assert_status_unauthorized();
+    assert_eq!(get_error_message(&response), "wrong credentials");
+
+    let response = server
+        .get("/engine")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::bearer("down").unwrap().0.encode(),
+        )
+        .await;
+    response.assert_status_service_unavailable();
+    assert_eq!(get_error_code(&response), "SERVICE_UNAVAILABLE");
+    assert_eq!(get_error_message(&response), "Identity service is down");
+    Ok(())
+}
+
+/// Writer of the logs of a test.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl ::std::io::Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> ::std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Logs {
+    /// Capture the logs of the current thread until the guard is dropped.
+    fn capture(&self) -> tracing::subscriber::DefaultGuard {
+        let logs = self.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || logs.clone())
+            .with_ansi(false)
+            .finish();
+        tracing::subscriber::set_default(subscriber)
+    }
+
+    fn get(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+/// Database deleting any engine.
+fn get_db_deleting_engines() -> MockDB {
+    let mut db = MockDB::new();
+    db.expect_get_engine().returning(|_| {
+        Ok(Some(EngineInfo::new(
+            "engine1".to_owned(),
+            EngineType::Spark,
+            "3.5.3".to_owned(),
+            WaitToStart,
+            BTreeMap::new(),
+            EngineTime::now(),
+        )))
+    });
+    db.expect_remove_engine().returning(|_, state, _, _| {
+        Ok(Some(RemoveEngineResult::Success(Box::new(
+            EngineInfo::new(
+                "engine1".to_owned(),
+                EngineType::Spark,
+                "3.5.3".to_owned(),
+                state.clone(),
+                BTreeMap::new(),
+                EngineTime::now(),
+            ),
+        ))))
+    });
+    db
+}
+
+#[tokio::test]
+async fn log_principal_of_creation_and_deletion() -> Result<()> {
+    let mut db = get_db_deleting_engines();
+    db.expect_add_engine()
+        .withf(|_, info, _, _| info.created_by.as_deref() == Some("alice"))
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let auth_provider = StaticAuthProvider::from_config(
+        from_value::<StaticAuthConfig>(json!({
+            "users": [{"username": "alice", "password": "pwd-a"}],
+            "bearer_tokens": []
+        }))
+        .unwrap(),
+    )?;
+    let server = get_test_server(db, Some(auth_provider)).await?;
+    let credentials = Authorization::basic("alice", "pwd-a").0.encode();
+    let logs = Logs::default();
+    let _guard = logs.capture();
+
+    let response = server
+        .post("/engine")
+        .add_header(AUTHORIZATION, credentials.clone())
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3",
+            "config": {}
+        }))
+        .await;
+    response.assert_status_ok();
+    let id = response.json::<::rucat_common::serde_json::Value>()["id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    server
+        .delete(&format!("/engine/{}", id))
+        .add_header(AUTHORIZATION, credentials)
+        .await
+        .assert_status_ok();
+
+    let logs = logs.get();
+    assert!(
+        logs.contains(&format!("Creating engine {} by alice, wait to start", id)),
+        "{}",
+        logs
+    );
+    assert!(
+        logs.contains(&format!("Deleted engine {} by alice", id)),
+        "{}",
+        logs
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn log_impersonator_of_creation() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .withf(|_, info, _, _| info.created_by.as_deref() == Some("bob"))
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let auth_provider = StaticAuthProvider::from_config(
+        from_value::<StaticAuthConfig>(json!({
+            "users": [{"username": "alice", "password": "pwd-a", "role": "admin"}],
+            "bearer_tokens": []
+        }))
+        .unwrap(),
+    )?;
+    let server = get_test_server(db, Some(auth_provider)).await?;
+    let logs = Logs::default();
+    let _guard = logs.capture();
+
+    let response = server
+        .post("/engine")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("alice", "pwd-a").0.encode(),
+        )
+        .add_header(IMPERSONATE_USER, "bob")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3",
+            "config": {}
+        }))
+        .await;
     response.assert_status_ok();
+    let id = response.json::<::rucat_common::serde_json::Value>()["id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let logs = logs.get();
+    assert!(
+        logs.contains("bob (impersonated by alice) sends POST /engine"),
+        "{}",
+        logs
+    );
+    assert!(
+        logs.contains(&format!(
+            "Creating engine {} by bob (impersonated by alice), wait to start",
+            id
+        )),
+        "{}",
+        logs
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn log_anonymous_principal_without_auth() -> Result<()> {
+    let mut db = get_db_deleting_engines();
+    db.expect_add_engine()
+        .withf(|_, info, _, _| info.created_by.is_none())
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let server = get_test_server(db, None).await?;
+    let logs = Logs::default();
+    let _guard = logs.capture();
+
+    server
+        .post("/engine")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3",
+            "config": {}
+        }))
+        .await
+        .assert_status_ok();
+    server.delete("/engine/123").await.assert_status_ok();
+
+    let logs = logs.get();
+    assert!(logs.contains("by anonymous, wait to start"), "{}", logs);
+    assert!(logs.contains("Deleted engine 123 by anonymous"), "{}", logs);
+    Ok(())
+}
+
+#[tokio::test]
+async fn log_failed_authentication_without_credentials() -> Result<()> {
+    let auth_provider =
+        StaticAuthProvider::new(USERNAME.to_owned(), PWD.to_owned(), TOKEN.to_owned());
+    let server = get_test_server(MockDB::new(), Some(auth_provider)).await?;
+    let logs = Logs::default();
+    let _guard = logs.capture();
+
+    server
+        .get("/engine/123")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::bearer("secret-guess").unwrap().0.encode(),
+        )
+        .await
+        .assert_status_unauthorized();
+
+    let logs = logs.get();
+    assert!(
+        logs.contains("WARN") && logs.contains("Failed to authenticate bearer credentials from"),
+        "{}",
+        logs
+    );
+    assert!(!logs.contains("secret-guess"), "{}", logs);
+    Ok(())
+}
+
+/// Test server whose clients have the certificate `name`, as if it is verified by the TLS layer.
+fn get_server_with_client_cert(db: MockDB, name: &'static str) -> Result<TestServer> {
+    let auth_provider =
+        StaticAuthProvider::new(USERNAME.to_owned(), PWD.to_owned(), TOKEN.to_owned());
+    let app = get_test_app(
+        db,
+        Some(auth_provider),
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources::default(),
+        EnginePolicy::default(),
+    )?
+    .layer(middleware::from_fn(
+        move |mut request: Request, next: Next| async move {
+            request.extensions_mut().insert(ClientCertificate {
+                name: name.to_owned(),
+            });
+            next.run(request).await
+        },
+    ));
+    TestServer::new(app).map_err(RucatError::fail_to_start_server)
+}
+
+#[tokio::test]
+async fn client_cert_takes_precedence_over_headers() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .withf(|_, info, _, _| info.created_by.as_deref() == Some("ci"))
+        .times(2)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let server = get_server_with_client_cert(db, "ci")?;
+    let body = json!({
+        "name": "test",
+        "engine_type": "Spark",
+        "version": "3.5.3",
+        "config": {}
+    });
+
+    server.post("/engine").json(&body).await.assert_status_ok();
+    // The wrong header is ignored, as the client is authenticated by its certificate.
+    server
+        .post("/engine")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic(USERNAME, "wrong").0.encode(),
+        )
+        .json(&body)
+        .await
+        .assert_status_ok();
     Ok(())
 }
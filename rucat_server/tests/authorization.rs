@@ -0,0 +1,162 @@
+mod common;
+
+use ::rucat_common::{
+    error::Result,
+    serde_json::{from_value, json},
+    tokio,
+};
+use ::rucat_server::{
+    authentication::static_auth_provider::{StaticAuthConfig, StaticAuthProvider},
+    shutdown::ShutdownHandle,
+};
+use axum_extra::headers::authorization::Credentials as _;
+use axum_test::TestServer;
+use common::{
+    get_error_code, get_error_message, get_test_server, get_test_server_with_options, MockDB,
+    MockInspector,
+};
+use headers::Authorization;
+use http::{header::AUTHORIZATION, Method, StatusCode};
+
+/// Every engine route, including the routes of the resource inspector.
+const ENGINE_ROUTES: [(Method, &str); 22] = [
+    (Method::GET, "/engine"),
+    (Method::POST, "/engine"),
+    (Method::POST, "/engine/batch-delete"),
+    (Method::POST, "/engine/batch-get"),
+    (Method::POST, "/engine/cleanup"),
+    (Method::GET, "/engine/stats"),
+    (Method::POST, "/engine/adopt"),
+    (Method::GET, "/engine/123"),
+    (Method::DELETE, "/engine/123"),
+    (Method::POST, "/engine/123/stop"),
+    (Method::POST, "/engine/123/restart"),
+    (Method::PATCH, "/engine/123/config"),
+    (Method::PUT, "/engine/123/name"),
+    (Method::PUT, "/engine/123/acl"),
+    (Method::GET, "/engine/123/state"),
+    (Method::GET, "/engine/123/history"),
+    (Method::GET, "/engine/123/connect-info"),
+    (Method::GET, "/engine/123/events"),
+    (Method::GET, "/engine/123/logs"),
+    (Method::POST, "/engine/123/logs/archive"),
+    (Method::GET, "/engine/123/k8s-events"),
+    (Method::HEAD, "/engine/123"),
+];
+
+/// Server with the viewer `analyst` and the admin `ops`, which finds no engine.
+async fn get_server() -> Result<TestServer> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().returning(|_| Ok(None));
+    db.expect_get_engines().returning(|_| Ok(vec![]));
+    db.expect_get_engine_state().returning(|_| Ok(None));
+    db.expect_get_engine_events().returning(|_, _| Ok(vec![]));
+    db.expect_purge_engine_events().returning(|_, _| Ok(0));
+    db.expect_list_engines().returning(|_, _, _, _| Ok(vec![]));
+    db.expect_list_engine_summaries()
+        .returning(|_, _, _, _| Ok(vec![]));
+    db.expect_count_engines_by_state()
+        .returning(|_, _| Ok(Default::default()));
+    db.expect_remove_engines_in_states()
+        .returning(|_, _, _, _| Ok(vec![]));
+    let auth_provider = StaticAuthProvider::from_config(
+        from_value::<StaticAuthConfig>(json!({
+            "users": [{"username": "analyst", "password": "pwd-a", "role": "viewer"}],
+            "bearer_tokens": [{"name": "ops", "token": "token-ops", "role": "admin"}]
+        }))
+        .unwrap(),
+    )?;
+    get_test_server_with_options(
+        db,
+        Some(auth_provider),
+        None,
+        Some(MockInspector::new()),
+        ShutdownHandle::new(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn viewer_can_only_read_engines() -> Result<()> {
+    let server = get_server().await?;
+    let credentials = Authorization::basic("analyst", "pwd-a").0.encode();
+
+    for (method, path) in ENGINE_ROUTES {
+        let response = server
+            .method(method.clone(), path)
+            .add_header(AUTHORIZATION, credentials.clone())
+            .await;
+        if [Method::GET, Method::HEAD].contains(&method) || path == "/engine/batch-get" {
+            assert_ne!(
+                response.status_code(),
+                StatusCode::FORBIDDEN,
+                "{} {}",
+                method,
+                path
+            );
+        } else {
+            response.assert_status_forbidden();
+            assert_eq!(
+                get_error_code(&response),
+                "NOT_ALLOWED",
+                "{} {}",
+                method,
+                path
+            );
+            assert_eq!(
+                get_error_message(&response),
+                format!(
+                    "`analyst` is not allowed to {} {}, which needs the `operator` role",
+                    method, path
+                )
+            );
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn admin_can_send_every_engine_request() -> Result<()> {
+    let server = get_server().await?;
+    let credentials = Authorization::bearer("token-ops").unwrap().0.encode();
+
+    for (method, path) in ENGINE_ROUTES {
+        let response = server
+            .method(method.clone(), path)
+            .add_header(AUTHORIZATION, credentials.clone())
+            .await;
+        assert_ne!(
+            response.status_code(),
+            StatusCode::FORBIDDEN,
+            "{} {}",
+            method,
+            path
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn clients_without_role_are_admins() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().returning(|_| Ok(None));
+    db.expect_get_engine_state().returning(|_| Ok(None));
+    let auth_provider = StaticAuthProvider::from_config(
+        from_value::<StaticAuthConfig>(json!({
+            "users": [{"username": "alice", "password": "pwd-a"}],
+            "bearer_tokens": []
+        }))
+        .unwrap(),
+    )?;
+    let server = get_test_server(db, Some(auth_provider)).await?;
+
+    let response = server
+        .post("/engine/123/stop")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("alice", "pwd-a").0.encode(),
+        )
+        .await;
+    response.assert_status_not_found();
+    Ok(())
+}
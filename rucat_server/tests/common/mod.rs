@@ -1,37 +1,216 @@
-use ::std::time::SystemTime;
+use ::std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
+use ::axum::{extract::Request, middleware, middleware::Next, response::Response, Router};
+use ::http::header::{ACCEPT, CONTENT_TYPE};
+use ::http_body::Body;
 use ::mockall::mock;
 use ::rucat_common::{
-    database::{Database, EngineIdAndInfo, UpdateEngineStateResult},
-    engine::{CreateEngineRequest, EngineId, EngineInfo, EngineState},
+    blob_store::BlobStoreClient,
+    clock::{Clock, SystemClock},
+    database::{
+        Database, DatabaseHealth, EngineFilter, EngineIdAndInfo, EngineInfoUpdate, EngineSort,
+        IdempotencyKey, RemoveEngineResult, TriggeredEngine, UpdateEngineStateResult,
+    },
+    engine::{
+        AdoptEngineRequest, EngineActor, EngineEndpoints, EngineEvent, EngineId, EngineInfo,
+        EngineResourceEvent, EngineState, EngineStateKind, EngineSummary, EngineType, IdGenerator,
+        RandomIdGenerator,
+    },
     error::*,
 };
-use ::rucat_server::{authentication::static_auth_provider::StaticAuthProvider, get_server};
-use axum_test::TestServer;
+use ::rucat_server::{
+    authentication::{registry::DynAuthProvider, static_auth_provider::StaticAuthProvider},
+    get_server,
+    resource_inspector::{LogOptions, LogStream, ResourceInspector},
+    shutdown::ShutdownHandle,
+    EnginePolicy, Sources,
+};
+use axum_test::{TestResponse, TestServer};
 
 mock! {
     pub DB{}
     impl Database for DB {
-        async fn add_engine(&self, engine: CreateEngineRequest, next_update_time: Option<SystemTime>) -> Result<EngineId>;
-        async fn remove_engine(&self, id: &EngineId, current_state: &EngineState) -> Result<Option<UpdateEngineStateResult>>;
-        async fn update_engine_state(
+        async fn add_engine(&self, id: &EngineId, info: EngineInfo, next_update_time: Option<SystemTime>, idempotency_key: Option<IdempotencyKey>) -> Result<EngineId>;
+        async fn remove_engine(&self, id: &EngineId, current_state: &EngineState, expected_revision: Option<u64>, deleted_time: SystemTime) -> Result<Option<RemoveEngineResult>>;
+        async fn update_engine_state<'a>(
             &self,
             id: &EngineId,
             before: &EngineState,
             after: &EngineState,
             next_update_time: Option<SystemTime>,
+            expected_revision: Option<u64>,
+            actor: Option<&'a EngineActor>,
         ) -> Result<Option<UpdateEngineStateResult>>;
+        async fn update_engine_info<'a>(&self, id: &EngineId, update: EngineInfoUpdate, expected_states: Option<&'a [EngineState]>, expected_revision: Option<u64>) -> Result<Option<UpdateEngineStateResult>>;
+        async fn set_engine_acl(&self, id: &EngineId, allowed_principals: BTreeSet<String>, expected_revision: Option<u64>) -> Result<Option<()>>;
+        async fn update_engine_endpoints(&self, id: &EngineId, endpoints: EngineEndpoints) -> Result<Option<()>>;
         async fn get_engine(&self, id: &EngineId) -> Result<Option<EngineInfo>>;
-        async fn list_engines(&self) -> Result<Vec<EngineId>>;
-        async fn list_engines_need_update(&self) -> Result<Vec<EngineIdAndInfo>>;
+        async fn get_engines(&self, ids: &[EngineId]) -> Result<Vec<(EngineId, Option<EngineInfo>)>>;
+        async fn get_engine_state(&self, id: &EngineId) -> Result<Option<EngineState>>;
+        async fn list_engines(&self, offset: usize, limit: usize, filter: &EngineFilter, sort: EngineSort) -> Result<Vec<EngineId>>;
+        async fn list_engine_summaries(&self, offset: usize, limit: usize, filter: &EngineFilter, sort: EngineSort) -> Result<Vec<EngineSummary>>;
+        async fn list_engines_need_update(&self, engine_types: &[EngineType], limit: usize) -> Result<Vec<EngineIdAndInfo>>;
+        async fn claim_engines_need_update(&self, engine_types: &[EngineType], limit: usize, now: SystemTime, claim_duration: Duration) -> Result<Vec<EngineIdAndInfo>>;
+        async fn count_engines_by_state<'a>(&self, engine_types: &[EngineType], project: Option<&'a str>) -> Result<BTreeMap<EngineStateKind, usize>>;
+        async fn remove_engines_in_states<'a>(&self, states: &BTreeSet<EngineStateKind>, older_than: SystemTime, project: Option<&'a str>, deleted_time: SystemTime) -> Result<Vec<EngineId>>;
+        async fn list_triggered_engines(&self) -> Result<Vec<TriggeredEngine>>;
+        async fn append_engine_event(&self, id: &EngineId, old_state: &EngineState, new_state: &EngineState, source: &str, timestamp: SystemTime) -> Result<()>;
+        async fn get_engine_events(&self, id: &EngineId, limit: usize) -> Result<Vec<EngineEvent>>;
+        async fn purge_engine_events<'a>(&self, deleted_before: SystemTime, project: Option<&'a str>) -> Result<usize>;
+        async fn health_check(&self) -> Result<DatabaseHealth>;
+    }
+}
+
+mock! {
+    pub Inspector{}
+    impl ResourceInspector for Inspector {
+        async fn inspect(&self, request: &AdoptEngineRequest) -> Result<EngineInfo>;
+        async fn label(&self, request: &AdoptEngineRequest, id: &EngineId) -> Result<()>;
+        async fn get_logs<'a>(&self, id: &EngineId, project: Option<&'a str>, options: LogOptions) -> Result<Option<LogStream>>;
+        async fn get_events<'a>(&self, id: &EngineId, project: Option<&'a str>) -> Result<Vec<EngineResourceEvent>>;
     }
 }
 
 // TODO: mock auth provider
-pub async fn get_test_server(
-    db: MockDB,
+/// Test server on `db`, which is a [MockDB] in most tests.
+pub async fn get_test_server<DB: Database>(
+    db: DB,
+    auth_provider: Option<StaticAuthProvider>,
+) -> Result<TestServer> {
+    get_test_server_with_options(db, auth_provider, None, None, ShutdownHandle::new()).await
+}
+
+pub async fn get_test_server_with_options<DB: Database>(
+    db: DB,
+    auth_provider: Option<StaticAuthProvider>,
+    blob_store: Option<BlobStoreClient>,
+    resource_inspector: Option<MockInspector>,
+    shutdown_handle: ShutdownHandle,
+) -> Result<TestServer> {
+    get_test_server_with_sources(
+        db,
+        auth_provider,
+        blob_store,
+        resource_inspector,
+        shutdown_handle,
+        Arc::new(SystemClock),
+        Arc::new(RandomIdGenerator),
+    )
+    .await
+}
+
+/// Test server with the given clock and id generator, for tests asserting exact times and ids.
+pub async fn get_test_server_with_sources<DB: Database>(
+    db: DB,
     auth_provider: Option<StaticAuthProvider>,
+    blob_store: Option<BlobStoreClient>,
+    resource_inspector: Option<MockInspector>,
+    shutdown_handle: ShutdownHandle,
+    clock: Arc<dyn Clock>,
+    id_generator: Arc<dyn IdGenerator>,
 ) -> Result<TestServer> {
-    let app = get_server(db, auth_provider)?;
+    let app = get_test_app(
+        db,
+        auth_provider,
+        blob_store,
+        resource_inspector,
+        shutdown_handle,
+        Sources {
+            clock,
+            id_generator,
+            auth_config: None,
+        },
+        EnginePolicy::default(),
+    )?;
     TestServer::new(app).map_err(RucatError::fail_to_start_server)
 }
+
+/// Router of the test server, for tests wrapping it with more layers.
+pub fn get_test_app<DB: Database>(
+    db: DB,
+    auth_provider: Option<StaticAuthProvider>,
+    blob_store: Option<BlobStoreClient>,
+    resource_inspector: Option<MockInspector>,
+    shutdown_handle: ShutdownHandle,
+    sources: Sources,
+    engine_policy: EnginePolicy,
+) -> Result<Router> {
+    Ok(get_server(
+        db,
+        auth_provider.map(DynAuthProvider::new),
+        blob_store,
+        resource_inspector,
+        shutdown_handle,
+        sources,
+        engine_policy,
+    )?
+    .layer(middleware::from_fn(validate_content_type)))
+}
+
+/// Assert the `Content-Type` of every response with a body sent by the test server.
+/// Plain text is only used by the probes, the metrics, the engine logs and the greeting of `GET /`, event streams by the engine events, everything else is JSON.
+async fn validate_content_type(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+    let accepts_text = request
+        .headers()
+        .get(ACCEPT)
+        .is_some_and(|accept| accept == "text/plain");
+    let response = next.run(request).await;
+    // assets of the Swagger UI have the content types of their files
+    if path.starts_with("/swagger-ui/") && response.status().is_success() {
+        return response;
+    }
+    if response.body().size_hint().exact() != Some(0) {
+        let expected = match path.as_str() {
+            "/healthz" | "/readyz" if response.status().is_success() => "text/plain; charset=utf-8",
+            "/" if accepts_text && response.status().is_success() => "text/plain; charset=utf-8",
+            "/metrics" if response.status().is_success() => {
+                "application/openmetrics-text; version=1.0.0; charset=utf-8"
+            }
+            _ if path.ends_with("/events") && response.status().is_success() => "text/event-stream",
+            _ if path.ends_with("/logs") && response.status().is_success() => {
+                "text/plain; charset=utf-8"
+            }
+            _ => "application/json",
+        };
+        assert_eq!(
+            response
+                .headers()
+                .get(CONTENT_TYPE)
+                .map(|value| value.to_str().unwrap()),
+            Some(expected),
+            "Content-Type of {} {} with status {}",
+            method,
+            path,
+            response.status()
+        );
+    }
+    response
+}
+
+/// Get the message of an error response.
+pub fn get_error_message(response: &TestResponse) -> String {
+    get_error_field(response, "message")
+}
+
+/// Code of the error type in the error response, e.g. `NOT_FOUND`.
+pub fn get_error_code(response: &TestResponse) -> String {
+    get_error_field(response, "code")
+}
+
+fn get_error_field(response: &TestResponse, field: &str) -> String {
+    response.json::<::rucat_common::serde_json::Value>()[field]
+        .as_str()
+        .unwrap_or_else(|| {
+            panic!(
+                "error response should be a JSON object with a `{}` field",
+                field
+            )
+        })
+        .to_owned()
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use ::std::{borrow::Cow, io::Read};
+
+use ::flate2::read::GzDecoder;
+use ::mockall::predicate;
+use ::rucat_common::{
+    engine::{EngineId, EngineState::*},
+    error::{Result, RucatError},
+    serde_json::{from_slice, from_value, json, Value},
+    tokio,
+};
+use ::rucat_server::{shutdown::ShutdownHandle, EngineEvents, EnginePolicy, Sources};
+use axum_test::TestServer;
+use common::{get_error_code, get_error_message, get_test_app, get_test_server, MockDB};
+use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+
+/// Database listing 100 engines, so that the list is large enough to be compressed.
+fn get_db_with_engines() -> MockDB {
+    let mut db = MockDB::new();
+    db.expect_list_engines().times(1).returning(|_, _, _, _| {
+        (0..100)
+            .map(|i| EngineId::try_from(format!("engine-{}", i)))
+            .collect()
+    });
+    db
+}
+
+fn get_server_with_compression(db: MockDB, compression: bool) -> Result<TestServer> {
+    let app = get_test_app(
+        db,
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources::default(),
+        EnginePolicy {
+            events: from_value::<EngineEvents>(json!({"poll_interval_millis": 1}))
+                .map_err(RucatError::fail_to_load_config)?,
+            compression,
+            ..EnginePolicy::default()
+        },
+    )?;
+    TestServer::new(app).map_err(RucatError::fail_to_start_server)
+}
+
+#[tokio::test]
+async fn compress_engine_list_with_gzip() -> Result<()> {
+    let server = get_test_server(get_db_with_engines(), None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_header(ACCEPT_ENCODING, "gzip")
+        .await;
+
+    response.assert_status_ok();
+    assert_eq!(response.header(CONTENT_ENCODING), "gzip");
+    let mut body = Vec::new();
+    GzDecoder::new(response.as_bytes().as_ref())
+        .read_to_end(&mut body)
+        .unwrap();
+    let engines = from_slice::<Value>(&body).unwrap();
+    assert_eq!(engines["engines"].as_array().map(Vec::len), Some(100));
+    Ok(())
+}
+
+#[tokio::test]
+async fn compress_engine_list_with_brotli() -> Result<()> {
+    let server = get_test_server(get_db_with_engines(), None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_header(ACCEPT_ENCODING, "br")
+        .await;
+
+    response.assert_status_ok();
+    assert_eq!(response.header(CONTENT_ENCODING), "br");
+    Ok(())
+}
+
+#[tokio::test]
+async fn no_compression_without_accept_encoding() -> Result<()> {
+    let server = get_test_server(get_db_with_engines(), None).await?;
+
+    let response = server.get("/engine").await;
+
+    response.assert_status_ok();
+    assert!(response.maybe_header(CONTENT_ENCODING).is_none());
+    assert_eq!(
+        response.json::<Value>()["engines"][0],
+        json!({"id": "engine-0"})
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn no_compression_with_unsupported_encoding() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server
+        .get("/any")
+        .add_header(ACCEPT_ENCODING, "deflate")
+        .await;
+
+    response.assert_status_not_found();
+    assert!(response.maybe_header(CONTENT_ENCODING).is_none());
+    assert_eq!(get_error_code(&response), "NOT_FOUND");
+    assert_eq!(get_error_message(&response), "Route /any not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn compression_disabled() -> Result<()> {
+    let server = get_server_with_compression(get_db_with_engines(), false)?;
+
+    let response = server
+        .get("/engine")
+        .add_header(ACCEPT_ENCODING, "gzip")
+        .await;
+
+    response.assert_status_ok();
+    assert!(response.maybe_header(CONTENT_ENCODING).is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_events_are_not_compressed() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine_state()
+        .with(predicate::eq(EngineId::new(Cow::Borrowed("123"))?))
+        .returning(|_| Ok(Some(Terminated)));
+    let server = get_server_with_compression(db, true)?;
+
+    let response = server
+        .get("/engine/123/events")
+        .add_header(ACCEPT_ENCODING, "gzip")
+        .await;
+
+    response.assert_status_ok();
+    assert!(response.maybe_header(CONTENT_ENCODING).is_none());
+    assert_eq!(
+        response.text(),
+        "event: state\ndata: \"Terminated\"\n\nevent: end\ndata: Terminated\n\n"
+    );
+    Ok(())
+}
@@ -0,0 +1,149 @@
+mod common;
+
+use ::rucat_common::{
+    error::{Result, RucatError},
+    serde_json::{from_value, json},
+    tokio,
+};
+use ::rucat_server::{
+    authentication::static_auth_provider::StaticAuthProvider,
+    cors::{allow_cors, CorsConfig},
+    shutdown::ShutdownHandle,
+    EnginePolicy, Sources,
+};
+use axum_test::TestServer;
+use common::{get_error_code, get_error_message, get_test_app, get_test_server, MockDB};
+use http::{
+    header::{
+        ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+        ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS,
+        ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+    },
+    Method,
+};
+
+static ORIGIN_DASHBOARD: &str = "https://dashboard.example.com";
+
+fn get_cors_server(cors: CorsConfig) -> Result<TestServer> {
+    let auth_provider =
+        StaticAuthProvider::new("admin".to_owned(), "admin".to_owned(), "admin".to_owned());
+    let app = get_test_app(
+        MockDB::new(),
+        Some(auth_provider),
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources::default(),
+        EnginePolicy::default(),
+    )?;
+    TestServer::new(allow_cors(app, cors)).map_err(RucatError::fail_to_start_server)
+}
+
+fn get_cors_config() -> CorsConfig {
+    from_value(json!({
+        "allowed_origins": [ORIGIN_DASHBOARD],
+        "allow_credentials": true
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn preflight_without_credentials() -> Result<()> {
+    let server = get_cors_server(get_cors_config())?;
+    let response = server
+        .method(Method::OPTIONS, "/engine")
+        .add_header(ORIGIN, ORIGIN_DASHBOARD)
+        .add_header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+        .add_header(ACCESS_CONTROL_REQUEST_HEADERS, "authorization,content-type")
+        .await;
+    response.assert_status_ok();
+    assert_eq!(
+        response.header(ACCESS_CONTROL_ALLOW_ORIGIN),
+        ORIGIN_DASHBOARD
+    );
+    assert_eq!(response.header(ACCESS_CONTROL_ALLOW_CREDENTIALS), "true");
+    assert_eq!(
+        response.header(ACCESS_CONTROL_ALLOW_METHODS),
+        "GET,POST,PUT,PATCH,DELETE"
+    );
+    assert_eq!(
+        response.header(ACCESS_CONTROL_ALLOW_HEADERS),
+        "authorization,content-type,x-request-id"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn cors_headers_of_responses() -> Result<()> {
+    let server = get_cors_server(get_cors_config())?;
+
+    let response = server
+        .get("/engine")
+        .add_header(ORIGIN, ORIGIN_DASHBOARD)
+        .await;
+    response.assert_status_unauthorized();
+    assert_eq!(
+        response.header(ACCESS_CONTROL_ALLOW_ORIGIN),
+        ORIGIN_DASHBOARD
+    );
+    assert_eq!(
+        response.header(ACCESS_CONTROL_EXPOSE_HEADERS),
+        "x-request-id,warning,etag"
+    );
+
+    let response = server
+        .get("/healthz")
+        .add_header(ORIGIN, ORIGIN_DASHBOARD)
+        .await;
+    response.assert_status_ok();
+    assert_eq!(
+        response.header(ACCESS_CONTROL_ALLOW_ORIGIN),
+        ORIGIN_DASHBOARD
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn origin_not_allowed() -> Result<()> {
+    let server = get_cors_server(get_cors_config())?;
+    let response = server
+        .method(Method::OPTIONS, "/engine")
+        .add_header(ORIGIN, "https://evil.example.com")
+        .add_header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+        .await;
+    assert!(response.maybe_header(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn any_origin() -> Result<()> {
+    let server = get_cors_server(from_value(json!({"allowed_origins": ["*"]})).unwrap())?;
+    let response = server
+        .get("/healthz")
+        .add_header(ORIGIN, "https://other.example.com")
+        .await;
+    response.assert_status_ok();
+    assert_eq!(response.header(ACCESS_CONTROL_ALLOW_ORIGIN), "*");
+    assert!(response
+        .maybe_header(ACCESS_CONTROL_ALLOW_CREDENTIALS)
+        .is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn cors_disabled_by_default() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+    let response = server
+        .method(Method::OPTIONS, "/engine")
+        .add_header(ORIGIN, ORIGIN_DASHBOARD)
+        .add_header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+        .await;
+    response.assert_status(http::StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(get_error_code(&response), "METHOD_NOT_ALLOWED");
+    assert_eq!(
+        get_error_message(&response),
+        "Method OPTIONS is not allowed for route /engine"
+    );
+    assert!(response.maybe_header(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    Ok(())
+}
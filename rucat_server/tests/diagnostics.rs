@@ -0,0 +1,425 @@
+mod common;
+
+use ::std::collections::BTreeMap;
+
+use ::futures::{stream, StreamExt};
+use ::mockall::predicate;
+use ::rucat_common::{
+    anyhow::anyhow,
+    blob_store::{
+        engine_blob_key, fs_blob_store::FsBlobStore, BlobRef, BlobStore, BlobStoreClient,
+        LOG_TAIL_BLOB,
+    },
+    database::{EngineInfoUpdate, UpdateEngineStateResult},
+    engine::{EngineId, EngineInfo, EngineResourceEvent, EngineState::*, EngineTime, EngineType},
+    error::*,
+    serde_json::{json, Value},
+    tokio,
+};
+use ::rucat_server::{resource_inspector::LogOptions, shutdown::ShutdownHandle};
+use axum_test::TestServer;
+use common::{
+    get_error_code, get_error_message, get_test_server, get_test_server_with_options, MockDB,
+    MockInspector,
+};
+use http::StatusCode;
+use tempfile::TempDir;
+
+async fn get_diagnostics_server(db: MockDB, inspector: MockInspector) -> Result<TestServer> {
+    get_test_server_with_options(db, None, None, Some(inspector), ShutdownHandle::new()).await
+}
+
+async fn get_diagnostics_server_with_blob_store(
+    db: MockDB,
+    inspector: MockInspector,
+    blob_store: FsBlobStore,
+) -> Result<TestServer> {
+    get_test_server_with_options(
+        db,
+        None,
+        Some(BlobStoreClient::FileSystem(blob_store)),
+        Some(inspector),
+        ShutdownHandle::new(),
+    )
+    .await
+}
+
+/// Engine `123` in `state`, whose logs are archived as `blobs`.
+fn get_engine_with_blobs(
+    state: rucat_common::engine::EngineState,
+    blobs: BTreeMap<String, BlobRef>,
+) -> EngineInfo {
+    let mut info = EngineInfo::new(
+        "engine1".to_owned(),
+        EngineType::Spark,
+        "3.5.3".to_owned(),
+        state,
+        BTreeMap::new(),
+        EngineTime::now(),
+    );
+    info.blobs = blobs;
+    info
+}
+
+fn get_db_with_engine_in_state(state: rucat_common::engine::EngineState) -> MockDB {
+    let mut db = MockDB::new();
+    db.expect_get_engine_state()
+        .with(predicate::eq(EngineId::try_from("123").unwrap()))
+        .times(1)
+        .returning(move |_| Ok(Some(state.clone())));
+    db
+}
+
+#[tokio::test]
+async fn get_engine_logs() -> Result<()> {
+    let db = get_db_with_engine_in_state(Running);
+    let mut inspector = MockInspector::new();
+    inspector
+        .expect_get_logs()
+        .withf(|id, project, options| {
+            id.to_string() == "123"
+                && project.is_none()
+                && *options
+                    == LogOptions {
+                        tail_lines: Some(2),
+                        follow: true,
+                    }
+        })
+        .times(1)
+        .returning(|_, _, _| {
+            Ok(Some(
+                stream::iter(["line 1\n", "line 2\n"].map(|line| Ok(line.to_owned()))).boxed(),
+            ))
+        });
+    let server = get_diagnostics_server(db, inspector).await?;
+
+    let response = server
+        .get("/engine/123/logs?tail_lines=2&follow=true")
+        .await;
+
+    response.assert_status_ok();
+    response.assert_text("line 1\nline 2\n");
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_logs_of_engine_not_found() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine_state()
+        .times(1)
+        .returning(|_| Ok(None));
+    let mut inspector = MockInspector::new();
+    inspector.expect_get_logs().never();
+    let server = get_diagnostics_server(db, inspector).await?;
+
+    let response = server.get("/engine/123/logs").await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_code(&response), "NOT_FOUND");
+    assert_eq!(get_error_message(&response), "Engine 123 not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_logs_of_engine_without_pod() -> Result<()> {
+    let db = get_db_with_engine_in_state(Terminated);
+    let mut inspector = MockInspector::new();
+    inspector
+        .expect_get_logs()
+        .times(1)
+        .returning(|_, _, _| Ok(None));
+    let server = get_diagnostics_server(db, inspector).await?;
+
+    let response = server.get("/engine/123/logs").await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_code(&response), "NOT_FOUND");
+    assert_eq!(
+        get_error_message(&response),
+        "Engine 123 exists in Terminated state but its driver pod is gone"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_archived_logs_of_engine_without_pod() -> Result<()> {
+    let dir = TempDir::new().map_err(RucatError::fail_to_write_blob)?;
+    let blob_store = FsBlobStore::new(dir.path()).await?;
+    let id = EngineId::try_from("123")?;
+    let blob = blob_store
+        .put(
+            &engine_blob_key(&id, LOG_TAIL_BLOB),
+            &b"line 1\nline 2\nline 3\n"[..],
+        )
+        .await?;
+    let mut db = MockDB::new();
+    db.expect_get_engine_state()
+        .times(2)
+        .returning(|_| Ok(Some(Terminated)));
+    db.expect_get_engine().times(2).returning(move |_| {
+        Ok(Some(get_engine_with_blobs(
+            Terminated,
+            BTreeMap::from([(LOG_TAIL_BLOB.to_owned(), blob.clone())]),
+        )))
+    });
+    let mut inspector = MockInspector::new();
+    inspector
+        .expect_get_logs()
+        .times(2)
+        .returning(|_, _, _| Ok(None));
+    let server = get_diagnostics_server_with_blob_store(db, inspector, blob_store).await?;
+
+    let response = server.get("/engine/123/logs").await;
+    response.assert_status_ok();
+    response.assert_text("line 1\nline 2\nline 3\n");
+
+    let response = server.get("/engine/123/logs?tail_lines=2").await;
+    response.assert_status_ok();
+    response.assert_text("line 2\nline 3\n");
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_engine_logs() -> Result<()> {
+    let dir = TempDir::new().map_err(RucatError::fail_to_write_blob)?;
+    let blob_store = FsBlobStore::new(dir.path()).await?;
+    let mut db = MockDB::new();
+    db.expect_get_engine()
+        .times(1)
+        .returning(|_| Ok(Some(get_engine_with_blobs(Running, BTreeMap::new()))));
+    db.expect_update_engine_info()
+        .withf(|id, update, expected_states, expected_revision| {
+            id.to_string() == "123"
+                && update.blobs.as_ref().is_some_and(|blobs| {
+                    blobs.len() == 1
+                        && blobs[LOG_TAIL_BLOB].key == "engines/123/log_tail"
+                        && blobs[LOG_TAIL_BLOB].size == 14
+                })
+                && *update
+                    == EngineInfoUpdate {
+                        blobs: update.blobs.clone(),
+                        ..Default::default()
+                    }
+                && expected_states.is_none()
+                && expected_revision.is_none()
+        })
+        .times(1)
+        .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    let mut inspector = MockInspector::new();
+    inspector
+        .expect_get_logs()
+        .withf(|_, _, options| {
+            *options
+                == LogOptions {
+                    tail_lines: Some(1000),
+                    follow: false,
+                }
+        })
+        .times(1)
+        .returning(|_, _, _| {
+            Ok(Some(
+                stream::iter(["line 1\n", "line 2\n"].map(|line| Ok(line.to_owned()))).boxed(),
+            ))
+        });
+    let server =
+        get_diagnostics_server_with_blob_store(db, inspector, FsBlobStore::new(dir.path()).await?)
+            .await?;
+
+    let response = server.post("/engine/123/logs/archive").await;
+
+    response.assert_status_ok();
+    let blob = response.json::<BlobRef>();
+    assert_eq!(blob.key, "engines/123/log_tail");
+    let mut logs = vec![];
+    blob_store.get(&blob, &mut logs).await?;
+    assert_eq!(logs, b"line 1\nline 2\n");
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_logs_of_engine_deleted_meanwhile() -> Result<()> {
+    let dir = TempDir::new().map_err(RucatError::fail_to_write_blob)?;
+    let blob_store = FsBlobStore::new(dir.path()).await?;
+    let mut db = MockDB::new();
+    db.expect_get_engine()
+        .times(1)
+        .returning(|_| Ok(Some(get_engine_with_blobs(Running, BTreeMap::new()))));
+    db.expect_update_engine_info()
+        .times(1)
+        .returning(|_, _, _, _| Ok(None));
+    let mut inspector = MockInspector::new();
+    inspector.expect_get_logs().times(1).returning(|_, _, _| {
+        Ok(Some(
+            stream::iter(["line 1\n"].map(|line| Ok(line.to_owned()))).boxed(),
+        ))
+    });
+    let server =
+        get_diagnostics_server_with_blob_store(db, inspector, FsBlobStore::new(dir.path()).await?)
+            .await?;
+
+    let response = server.post("/engine/123/logs/archive").await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_message(&response), "Engine 123 not found");
+    // the archived logs are not left behind
+    assert!(blob_store
+        .get(
+            &BlobRef {
+                key: "engines/123/log_tail".to_owned(),
+                size: 7,
+                checksum: String::new(),
+            },
+            &mut vec![],
+        )
+        .await
+        .is_err_and(|e| e.get_error_type() == &RucatErrorType::NotFound));
+    Ok(())
+}
+
+#[tokio::test]
+async fn archive_logs_without_blob_store() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().never();
+    let server = get_diagnostics_server(db, MockInspector::new()).await?;
+
+    let response = server.post("/engine/123/logs/archive").await;
+
+    response.assert_status_not_found();
+    assert_eq!(
+        get_error_message(&response),
+        "Engine logs cannot be archived without the blob store"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn fail_to_read_logs() -> Result<()> {
+    let db = get_db_with_engine_in_state(Running);
+    let mut inspector = MockInspector::new();
+    inspector
+        .expect_get_logs()
+        .times(1)
+        .returning(|_, _, _| Err(RucatError::fail_to_read_logs(anyhow!("forbidden"))));
+    let server = get_diagnostics_server(db, inspector).await?;
+
+    let response = server.get("/engine/123/logs").await;
+
+    response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(get_error_code(&response), "FAIL_TO_READ_LOGS");
+    assert_eq!(get_error_message(&response), "forbidden");
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_tail_lines() -> Result<()> {
+    let server = get_diagnostics_server(MockDB::new(), MockInspector::new()).await?;
+
+    let response = server.get("/engine/123/logs?tail_lines=abc").await;
+
+    response.assert_status_bad_request();
+    assert_eq!(get_error_code(&response), "INVALID_REQUEST");
+    Ok(())
+}
+
+#[tokio::test]
+async fn logs_disabled_without_inspector() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server.get("/engine/123/logs").await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_code(&response), "NOT_FOUND");
+    Ok(())
+}
+
+fn pending_event() -> EngineResourceEvent {
+    EngineResourceEvent {
+        object: "Pod/rucat-spark-123-driver".to_owned(),
+        event_type: "Warning".to_owned(),
+        reason: "FailedScheduling".to_owned(),
+        message: "0/3 nodes are available: 3 Insufficient cpu.".to_owned(),
+        count: 4,
+        last_timestamp: Some(EngineTime::from_system_time(std::time::UNIX_EPOCH)),
+    }
+}
+
+#[tokio::test]
+async fn get_engine_k8s_events() -> Result<()> {
+    let db = get_db_with_engine_in_state(StartInProgress);
+    let mut inspector = MockInspector::new();
+    inspector
+        .expect_get_events()
+        .withf(|id, project| id.to_string() == "123" && project.is_none())
+        .times(1)
+        .returning(|_, _| Ok(vec![pending_event()]));
+    let server = get_diagnostics_server(db, inspector).await?;
+
+    let response = server.get("/engine/123/k8s-events").await;
+
+    response.assert_status_ok();
+    assert_eq!(
+        response.json::<Value>(),
+        json!({
+            "events": [{
+                "object": "Pod/rucat-spark-123-driver",
+                "event_type": "Warning",
+                "reason": "FailedScheduling",
+                "message": "0/3 nodes are available: 3 Insufficient cpu.",
+                "count": 4,
+                "last_timestamp": "1970-01-01 00:00:00 +00:00:00"
+            }]
+        })
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_k8s_events_of_engine_without_resources() -> Result<()> {
+    let db = get_db_with_engine_in_state(WaitToStart);
+    let mut inspector = MockInspector::new();
+    inspector
+        .expect_get_events()
+        .times(1)
+        .returning(|_, _| Ok(vec![]));
+    let server = get_diagnostics_server(db, inspector).await?;
+
+    let response = server.get("/engine/123/k8s-events").await;
+
+    response.assert_status_ok();
+    assert_eq!(response.json::<Value>(), json!({"events": []}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_k8s_events_of_engine_not_found() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine_state()
+        .times(1)
+        .returning(|_| Ok(None));
+    let mut inspector = MockInspector::new();
+    inspector.expect_get_events().never();
+    let server = get_diagnostics_server(db, inspector).await?;
+
+    let response = server.get("/engine/123/k8s-events").await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_message(&response), "Engine 123 not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn fail_to_read_k8s_events() -> Result<()> {
+    let db = get_db_with_engine_in_state(Running);
+    let mut inspector = MockInspector::new();
+    inspector
+        .expect_get_events()
+        .times(1)
+        .returning(|_, _| Err(RucatError::fail_to_read_events(anyhow!("forbidden"))));
+    let server = get_diagnostics_server(db, inspector).await?;
+
+    let response = server.get("/engine/123/k8s-events").await;
+
+    response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(get_error_code(&response), "FAIL_TO_READ_EVENTS");
+    Ok(())
+}
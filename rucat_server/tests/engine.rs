@@ -1,17 +1,49 @@
 mod common;
 
-use ::std::{borrow::Cow, collections::BTreeMap};
+use ::std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
 
-use ::mockall::predicate;
+use ::mockall::{predicate, Sequence};
 use ::rucat_common::{
-    database::UpdateEngineStateResult,
-    engine::{CreateEngineRequest, EngineId, EngineInfo, EngineState::*, EngineTime, EngineType},
+    anyhow::anyhow,
+    blob_store::{engine_blob_key, fs_blob_store::FsBlobStore, BlobStore, BlobStoreClient},
+    build_info::BuildInfo,
+    clock::MockClock,
+    database::{
+        EngineFilter, EngineInfoUpdate, EngineSort, EngineSortKey, IdempotencyKey,
+        RemoveEngineResult, UpdateEngineStateResult,
+    },
+    engine::{
+        CreateEngineRequest, EngineEvent, EngineId, EngineInfo,
+        EngineState::{self, *},
+        EngineStateKind, EngineStatsResponse, EngineSummary, EngineTime, EngineType,
+        SequentialIdGenerator,
+    },
     error::*,
-    serde_json::json,
+    serde_json::{from_value, json, Value},
     tokio,
+    webhook::WebhookConfig,
+};
+use ::rucat_server::{
+    get_build_info, shutdown::ShutdownHandle, EngineEvents, EnginePolicy, Sources,
+};
+use ::tempfile::TempDir;
+use axum_test::TestServer;
+use common::{
+    get_error_code, get_error_message, get_test_app, get_test_server, get_test_server_with_options,
+    get_test_server_with_sources, MockDB,
+};
+use http::{
+    header::{HeaderName, ACCEPT, ALLOW, ETAG, IF_MATCH, WARNING},
+    StatusCode,
 };
-use common::{get_test_server, MockDB};
-use http::StatusCode;
+use httpmock::prelude::*;
+
+const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
 
 #[tokio::test]
 async fn undefined_handler() -> Result<()> {
@@ -21,6 +53,46 @@ async fn undefined_handler() -> Result<()> {
     let response = server.get("/any").await;
 
     response.assert_status_not_found();
+    assert_eq!(get_error_code(&response), "NOT_FOUND");
+    assert_eq!(get_error_message(&response), "Route /any not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn undefined_nested_handler() -> Result<()> {
+    let db = MockDB::new();
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/123/unknown").await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_code(&response), "NOT_FOUND");
+    assert_eq!(
+        get_error_message(&response),
+        "Route /engine/123/unknown not found"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn method_not_allowed() -> Result<()> {
+    let db = MockDB::new();
+    let server = get_test_server(db, None).await?;
+
+    let response = server.put("/engine/123").await;
+
+    response.assert_status(StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header(ALLOW), "GET,HEAD,DELETE");
+    assert_eq!(get_error_code(&response), "METHOD_NOT_ALLOWED");
+    assert_eq!(
+        get_error_message(&response),
+        "Method PUT is not allowed for route /engine/123"
+    );
+
+    let response = server.get("/engine/123/stop").await;
+
+    response.assert_status(StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.header(ALLOW), "POST");
     Ok(())
 }
 
@@ -31,11 +103,114 @@ async fn root_get_request() -> Result<()> {
 
     let response = server.get("/").await;
 
+    response.assert_status_ok();
+    // the metrics and the API specification are only served with their features
+    let mut links = json!({
+        "engine": "/engine",
+        "capabilities": "/capabilities",
+        "liveness": "/healthz",
+        "readiness": "/readyz",
+        "version": "/version"
+    });
+    let mut routes = vec![
+        "/",
+        "/admin/in-flight",
+        "/admin/reload-auth",
+        "/capabilities",
+        "/engine",
+        "/engine/batch-delete",
+        "/engine/batch-get",
+        "/engine/cleanup",
+        "/engine/stats",
+        "/engine/{id}",
+        "/engine/{id}/acl",
+        "/engine/{id}/config",
+        "/engine/{id}/connect-info",
+        "/engine/{id}/events",
+        "/engine/{id}/history",
+        "/engine/{id}/name",
+        "/engine/{id}/restart",
+        "/engine/{id}/state",
+        "/engine/{id}/stop",
+        "/healthz",
+        "/readyz",
+        "/version",
+    ];
+    if cfg!(feature = "metrics") {
+        links["metrics"] = json!("/metrics");
+        routes.push("/metrics");
+    }
+    if cfg!(feature = "openapi") {
+        links["openapi"] = json!("/api-doc/openapi.json");
+        routes.push("/api-doc/openapi.json");
+    }
+    if cfg!(feature = "swagger-ui") {
+        routes.push("/swagger-ui");
+    }
+    routes.sort();
+    response.assert_json(&json!({
+        "name": "rucat",
+        "version": env!("CARGO_PKG_VERSION"),
+        "links": links,
+        "routes": routes
+    }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn root_get_request_accepting_plain_text() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server.get("/").add_header(ACCEPT, "text/plain").await;
+
     response.assert_status_ok();
     response.assert_text("welcome to rucat");
     Ok(())
 }
 
+#[tokio::test]
+async fn get_version() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server.get("/version").await;
+
+    response.assert_status_ok();
+    let info = response.json::<BuildInfo>();
+    assert_eq!(info, get_build_info());
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(
+        info.features.contains(&Cow::Borrowed("metrics")),
+        cfg!(feature = "metrics")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_capabilities() -> Result<()> {
+    let db = MockDB::new();
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/capabilities").await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({
+        "features": [
+            {
+                "name": "readiness_probe",
+                "stability": "Experimental",
+                "description": "Engine is Running only after it is ready to serve requests."
+            }
+        ],
+        "versions": {
+            "Spark": {
+                "3.5.3": "supported",
+                "3.5.4": "supported"
+            }
+        }
+    }));
+    Ok(())
+}
+
 #[tokio::test]
 async fn get_engine_not_found() -> Result<()> {
     let mut db = MockDB::new();
@@ -66,7 +241,7 @@ async fn create_engine_with_missing_field() -> Result<()> {
         .await;
 
     response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
-    assert!(response.text().contains("missing field `name`"));
+    assert!(get_error_message(&response).contains("missing field `name`"));
     Ok(())
 }
 
@@ -84,8 +259,8 @@ async fn create_engine_with_unknown_field() -> Result<()> {
         .await;
 
     response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
-    assert!(response.text().contains(
-        "invalid: unknown field `invalid`, expected one of `name`, `engine_type`, `version`, `config`"
+    assert!(get_error_message(&response).contains(
+        "invalid: unknown field `invalid`, expected one of `name`, `engine_type`, `version`, `config`, `start_in_progress_timeout_secs`, `features`"
     ));
     Ok(())
 }
@@ -105,31 +280,48 @@ async fn create_engine_with_unsupported_engine_type() -> Result<()> {
         .await;
 
     response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
-    assert!(response
-        .text()
-        .contains("engine_type: unknown variant `foo`"));
+    assert!(get_error_message(&response).contains("engine_type: unknown variant `foo`"));
     Ok(())
 }
 
 #[tokio::test]
 async fn create_engine() -> Result<()> {
+    let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let expected_info = EngineInfo::from_create_request(
+        CreateEngineRequest {
+            name: "test".to_owned(),
+            engine_type: EngineType::Spark,
+            version: "3.5.3".to_owned(),
+            config: Some(BTreeMap::from([(
+                Cow::Borrowed("spark.executor.instances"),
+                Cow::Borrowed("1"),
+            )])),
+            start_in_progress_timeout_secs: None,
+            features: None,
+            start: None,
+        },
+        EngineTime::from_system_time(now),
+    )?;
     let mut db = MockDB::new();
     db.expect_add_engine()
         .with(
-            predicate::eq(CreateEngineRequest {
-                name: "test".to_owned(),
-                engine_type: EngineType::Spark,
-                version: "3.5.3".to_owned(),
-                config: Some(BTreeMap::from([(
-                    Cow::Borrowed("spark.executor.instances"),
-                    Cow::Borrowed("1"),
-                )])),
-            }),
-            predicate::always(),
+            predicate::eq(EngineId::try_from("engine-123")?),
+            predicate::eq(expected_info),
+            predicate::eq(Some(now)),
+            predicate::eq(None),
         )
         .times(1)
-        .returning(|_, _| Ok(EngineId::new(Cow::Borrowed("123"))?));
-    let server = get_test_server(db, None).await?;
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let server = get_test_server_with_sources(
+        db,
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        Arc::new(MockClock::new(now)),
+        Arc::new(SequentialIdGenerator::new(Cow::Borrowed("engine-"), 123)),
+    )
+    .await?;
 
     let response = server
         .post("/engine")
@@ -144,9 +336,295 @@ async fn create_engine() -> Result<()> {
         .await;
 
     response.assert_json(&json!({
-        "id": "123"
+        "id": "engine-123"
+    }));
+    assert!(response.maybe_header(WARNING).is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_engine_without_start() -> Result<()> {
+    let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let expected_info = EngineInfo::from_create_request(
+        CreateEngineRequest {
+            name: "test".to_owned(),
+            engine_type: EngineType::Spark,
+            version: "3.5.3".to_owned(),
+            config: None,
+            start_in_progress_timeout_secs: None,
+            features: None,
+            start: Some(false),
+        },
+        EngineTime::from_system_time(now),
+    )?;
+    assert_eq!(expected_info.state, Terminated);
+    let mut db = MockDB::new();
+    // the state monitor ignores engines without next update time
+    db.expect_add_engine()
+        .with(
+            predicate::eq(EngineId::try_from("engine-123")?),
+            predicate::eq(expected_info),
+            predicate::eq(None),
+            predicate::eq(None),
+        )
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let server = get_test_server_with_sources(
+        db,
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        Arc::new(MockClock::new(now)),
+        Arc::new(SequentialIdGenerator::new(Cow::Borrowed("engine-"), 123)),
+    )
+    .await?;
+
+    let response = server
+        .post("/engine")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3",
+            "start": false
+        }))
+        .await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({
+        "id": "engine-123"
+    }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_engine_with_idempotency_key() -> Result<()> {
+    let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .withf(move |id, _, _, idempotency_key| {
+            id == &EngineId::try_from("engine-123").unwrap()
+                && idempotency_key
+                    == &Some(IdempotencyKey {
+                        key: "retry-1".to_owned(),
+                        expire_time: now + Duration::from_secs(86400),
+                    })
+        })
+        .times(1)
+        // the key was used by a previous request
+        .returning(|_, _, _, _| EngineId::try_from("engine-100"));
+    let server = get_test_server_with_sources(
+        db,
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        Arc::new(MockClock::new(now)),
+        Arc::new(SequentialIdGenerator::new(Cow::Borrowed("engine-"), 123)),
+    )
+    .await?;
+
+    let response = server
+        .post("/engine")
+        .add_header(IDEMPOTENCY_KEY, "retry-1")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3"
+        }))
+        .await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({
+        "id": "engine-100"
     }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_engine_with_invalid_idempotency_key() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+    for key in [String::new(), "a".repeat(256)] {
+        let response = server
+            .post("/engine")
+            .add_header(IDEMPOTENCY_KEY, key)
+            .json(&json!({
+                "name": "test",
+                "engine_type": "Spark",
+                "version": "3.5.3"
+            }))
+            .await;
+
+        response.assert_status_bad_request();
+        assert_eq!(get_error_code(&response), "INVALID_REQUEST");
+        assert_eq!(
+            get_error_message(&response),
+            "`Idempotency-Key` must have 1 to 255 characters"
+        );
+    }
+    Ok(())
+}
+
+/// Test server with the deprecated Spark 3.5.3 and the blocked Spark 3.5.4.
+fn get_test_server_with_versions(db: MockDB) -> Result<TestServer> {
+    let versions = from_value(json!({
+        "Spark": {
+            "3.5.3": {"deprecated": {"message": "upgrade to 3.5.4", "sunset_date": "2025-06-30"}},
+            "3.5.4": {"blocked": {"message": "3.5.4 has a known bug"}}
+        }
+    }))
+    .map_err(RucatError::fail_to_load_config)?;
+    let app = get_test_app(
+        db,
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources::default(),
+        EnginePolicy {
+            versions,
+            ..EnginePolicy::default()
+        },
+    )?;
+    TestServer::new(app).map_err(RucatError::fail_to_start_server)
+}
+
+const DEPRECATION_WARNING: &str =
+    "Spark version 3.5.3 is deprecated and will be blocked after 2025-06-30: upgrade to 3.5.4";
+
+#[tokio::test]
+async fn create_engine_of_deprecated_version() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let server = get_test_server_with_versions(db)?;
+
+    let response = server
+        .post("/engine")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3"
+        }))
+        .await;
 
+    response.assert_status_ok();
+    assert_eq!(
+        response.header(WARNING),
+        format!("299 - \"{}\"", DEPRECATION_WARNING)
+    );
+    let body = response.json::<Value>();
+    assert_eq!(body["warnings"], json!([DEPRECATION_WARNING]));
+    assert!(body["id"].is_string());
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_engine_of_blocked_version() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine().never();
+    let server = get_test_server_with_versions(db)?;
+
+    let response = server
+        .post("/engine")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.4"
+        }))
+        .await;
+
+    response.assert_status_forbidden();
+    assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+    assert_eq!(
+        get_error_message(&response),
+        "Spark version 3.5.4 is blocked: 3.5.4 has a known bug"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_engine_of_unsupported_version() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine().never();
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .post("/engine")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.5"
+        }))
+        .await;
+
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert_eq!(
+        get_error_message(&response),
+        "Spark version 3.5.5 is not supported. Supported versions: [\"3.5.3\", \"3.5.4\"]"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_engine_with_invalid_name() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine().never();
+    let server = get_test_server(db, None).await?;
+    for (name, message) in [
+        ("".to_owned(), "Engine name must not be empty".to_owned()),
+        (
+            "a".repeat(257),
+            "Engine name must be at most 256 characters, but got 257".to_owned(),
+        ),
+        (
+            "a/b".to_owned(),
+            "Engine name must only contain letters, digits, spaces, `-`, `_` or `.`, but got '/'"
+                .to_owned(),
+        ),
+    ] {
+        let response = server
+            .post("/engine")
+            .json(&json!({
+                "name": name,
+                "engine_type": "Spark",
+                "version": "3.5.3"
+            }))
+            .await;
+
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+        assert_eq!(get_error_message(&response), message);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_engine_with_reserved_config() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine().never();
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .post("/engine")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3",
+            "config": {
+                "spark.executor.instances": "1",
+                "spark.kubernetes.container.image": "my-image"
+            }
+        }))
+        .await;
+
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert!(get_error_message(&response)
+        .starts_with("Engine config `spark.kubernetes.container.image` is reserved by rucat."));
     Ok(())
 }
 
@@ -168,9 +646,63 @@ async fn get_engine() -> Result<()> {
         .returning(move |_| Ok(Some(engine_info.clone())));
     let server = get_test_server(db, None).await?;
 
-    let response: EngineInfo = server.get("/engine/123").await.json();
-    assert_eq!(response, engine_info_cloned);
+    let response = server.get("/engine/123").await;
+    assert_eq!(response.header(ETAG), "\"0\"");
+    assert_eq!(response.json::<EngineInfo>(), engine_info_cloned);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_engine_with_unknown_feature() -> Result<()> {
+    // `add_engine` is not expected to be called
+    let db = MockDB::new();
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .post("/engine")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3",
+            "features": ["readiness_prob"]
+        }))
+        .await;
+
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert!(get_error_message(&response)
+        .starts_with("Unknown engine feature `readiness_prob`. Did you mean `readiness_probe`?"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_engine_with_features() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine()
+        .with(predicate::eq(EngineId::new(Cow::Borrowed("123"))?))
+        .times(1)
+        .returning(|_| {
+            let mut engine_info = EngineInfo::new(
+                "engine1".to_owned(),
+                EngineType::Spark,
+                "3.5.3".to_owned(),
+                Running,
+                BTreeMap::new(),
+                EngineTime::now(),
+            );
+            engine_info.features = BTreeSet::from(["readiness_probe".to_owned()]);
+            Ok(Some(engine_info))
+        });
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/123").await;
 
+    response.assert_status_ok();
+    assert_eq!(
+        response.json::<Value>()["features"],
+        json!(["readiness_probe"])
+    );
     Ok(())
 }
 
@@ -207,9 +739,22 @@ async fn delete_engine() -> Result<()> {
         .with(
             predicate::eq(EngineId::new(Cow::Borrowed("123"))?),
             predicate::eq(&WaitToStart),
+            predicate::eq(None),
+            predicate::always(),
         )
         .times(1)
-        .returning(|_, _| Ok(Some(UpdateEngineStateResult::Success)));
+        .returning(|_, state, _, _| {
+            Ok(Some(RemoveEngineResult::Success(Box::new(
+                EngineInfo::new(
+                    "engine1".to_owned(),
+                    EngineType::Spark,
+                    "3.5.3".to_owned(),
+                    state.clone(),
+                    BTreeMap::new(),
+                    EngineTime::now(),
+                ),
+            ))))
+        });
     let server = get_test_server(db, None).await?;
 
     let response = server.delete("/engine/123").await;
@@ -218,99 +763,595 @@ async fn delete_engine() -> Result<()> {
 }
 
 #[tokio::test]
-async fn stop_wait_to_start_engine() -> Result<()> {
+async fn delete_engine_with_blobs() -> Result<()> {
+    let id = EngineId::new(Cow::Borrowed("123"))?;
+    let other_id = EngineId::new(Cow::Borrowed("456"))?;
+    let dir = TempDir::new().map_err(RucatError::fail_to_write_blob)?;
+    let blob_store = FsBlobStore::new(dir.path()).await?;
+    let blob = blob_store
+        .put(&engine_blob_key(&id, "log_tail"), &b"log"[..])
+        .await?;
+    let other_blob = blob_store
+        .put(&engine_blob_key(&other_id, "log_tail"), &b"log"[..])
+        .await?;
+
     let mut db = MockDB::new();
     db.expect_get_engine()
-        .with(predicate::eq(EngineId::new(Cow::Borrowed("123"))?))
+        .with(predicate::eq(id.clone()))
         .times(1)
         .returning(|_| {
             Ok(Some(EngineInfo::new(
                 "engine1".to_owned(),
                 EngineType::Spark,
                 "3.5.3".to_owned(),
-                WaitToStart,
+                Terminated,
                 BTreeMap::new(),
                 EngineTime::now(),
             )))
         });
-    db.expect_update_engine_state()
+    db.expect_remove_engine()
         .with(
-            predicate::eq(EngineId::new(Cow::Borrowed("123"))?),
-            predicate::eq(&WaitToStart),
+            predicate::eq(id.clone()),
             predicate::eq(&Terminated),
             predicate::eq(None),
+            predicate::always(),
         )
         .times(1)
-        .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
-    let server = get_test_server(db, None).await?;
+        .returning(|_, state, _, _| {
+            Ok(Some(RemoveEngineResult::Success(Box::new(
+                EngineInfo::new(
+                    "engine1".to_owned(),
+                    EngineType::Spark,
+                    "3.5.3".to_owned(),
+                    state.clone(),
+                    BTreeMap::new(),
+                    EngineTime::now(),
+                ),
+            ))))
+        });
+    let server = get_test_server_with_options(
+        db,
+        None,
+        Some(BlobStoreClient::FileSystem(blob_store)),
+        None,
+        ShutdownHandle::new(),
+    )
+    .await?;
 
-    let response = server.post("/engine/123/stop").await;
+    let response = server.delete("/engine/123").await;
     response.assert_status_ok();
 
+    let blob_store = FsBlobStore::new(dir.path()).await?;
+    assert!(blob_store
+        .get(&blob, &mut vec![])
+        .await
+        .is_err_and(|e| e.to_string().starts_with("Not found")));
+    blob_store.get(&other_blob, &mut vec![]).await?;
     Ok(())
 }
 
 #[tokio::test]
-async fn stop_nonexistent_engine() -> Result<()> {
+async fn delete_engine_in_progress_without_force() -> Result<()> {
     let mut db = MockDB::new();
-    db.expect_get_engine()
-        .with(predicate::eq(EngineId::new(Cow::Borrowed("123"))?))
-        .times(1)
-        .returning(|_| Ok(None));
+    db.expect_get_engine().times(1).returning(|_| {
+        Ok(Some(EngineInfo::new(
+            "engine1".to_owned(),
+            EngineType::Spark,
+            "3.5.3".to_owned(),
+            TerminateInProgress,
+            BTreeMap::new(),
+            EngineTime::now(),
+        )))
+    });
+    db.expect_remove_engine().never();
     let server = get_test_server(db, None).await?;
-    let response = server.post("/engine/123/stop").await;
-    response.assert_status_not_found();
+
+    let response = server
+        .delete("/engine/123")
+        .add_query_param("force", false)
+        .await;
+    response.assert_status_forbidden();
+    assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+    assert_eq!(
+        get_error_message(&response),
+        "Engine 123 is in TerminateInProgress state, cannot be deleted"
+    );
     Ok(())
 }
 
 #[tokio::test]
-async fn restart_terminated_engine() -> Result<()> {
+async fn force_delete_engine_in_progress() -> Result<()> {
     let mut db = MockDB::new();
     db.expect_get_engine()
-        .with(predicate::eq(EngineId::new(Cow::Borrowed("123"))?))
+        .with(predicate::eq(EngineId::try_from("123")?))
         .times(1)
         .returning(|_| {
             Ok(Some(EngineInfo::new(
                 "engine1".to_owned(),
                 EngineType::Spark,
                 "3.5.3".to_owned(),
-                Terminated,
+                TerminateInProgress,
                 BTreeMap::new(),
                 EngineTime::now(),
             )))
         });
-    db.expect_update_engine_state()
+    let mut sequence = Sequence::new();
+    db.expect_remove_engine()
         .with(
-            predicate::eq(EngineId::new(Cow::Borrowed("123"))?),
+            predicate::eq(EngineId::try_from("123")?),
+            predicate::eq(&TerminateInProgress),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .in_sequence(&mut sequence)
+        .returning(|_, _, _, _| {
+            Ok(Some(RemoveEngineResult::Fail {
+                current_state: ErrorCleanInProgress(Cow::Borrowed("cluster is gone")),
+            }))
+        });
+    db.expect_remove_engine()
+        .with(
+            predicate::eq(EngineId::try_from("123")?),
+            predicate::eq(&ErrorCleanInProgress(Cow::Borrowed("cluster is gone"))),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .in_sequence(&mut sequence)
+        .returning(|_, state, _, _| {
+            Ok(Some(RemoveEngineResult::Success(Box::new(
+                EngineInfo::new(
+                    "engine1".to_owned(),
+                    EngineType::Spark,
+                    "3.5.3".to_owned(),
+                    state.clone(),
+                    BTreeMap::new(),
+                    EngineTime::now(),
+                ),
+            ))))
+        });
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .delete("/engine/123")
+        .add_query_param("force", true)
+        .await;
+    response.assert_status_ok();
+    response.assert_json(&json!({
+        "forced": true,
+        "state": {"ErrorCleanInProgress": "cluster is gone"}
+    }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn force_delete_engine_without_resources() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().times(1).returning(|_| {
+        Ok(Some(EngineInfo::new(
+            "engine1".to_owned(),
+            EngineType::Spark,
+            "3.5.3".to_owned(),
+            Terminated,
+            BTreeMap::new(),
+            EngineTime::now(),
+        )))
+    });
+    db.expect_remove_engine()
+        .with(
+            predicate::always(),
             predicate::eq(&Terminated),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .returning(|_, state, _, _| {
+            Ok(Some(RemoveEngineResult::Success(Box::new(
+                EngineInfo::new(
+                    "engine1".to_owned(),
+                    EngineType::Spark,
+                    "3.5.3".to_owned(),
+                    state.clone(),
+                    BTreeMap::new(),
+                    EngineTime::now(),
+                ),
+            ))))
+        });
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .delete("/engine/123")
+        .add_query_param("force", true)
+        .await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"forced": false, "state": "Terminated"}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn force_delete_nonexistent_engine() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().times(1).returning(|_| {
+        Ok(Some(EngineInfo::new(
+            "engine1".to_owned(),
+            EngineType::Spark,
+            "3.5.3".to_owned(),
+            Running,
+            BTreeMap::new(),
+            EngineTime::now(),
+        )))
+    });
+    // removed concurrently
+    db.expect_remove_engine()
+        .times(1)
+        .returning(|_, _, _, _| Ok(None));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .delete("/engine/123")
+        .add_query_param("force", true)
+        .await;
+    response.assert_status_not_found();
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete_engine_with_invalid_force() -> Result<()> {
+    let db = MockDB::new();
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .delete("/engine/123")
+        .add_query_param("force", "yes")
+        .await;
+    response.assert_status_bad_request();
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_engine_stats() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_count_engines_by_state()
+        .withf(|engine_types, project| engine_types.is_empty() && project.is_none())
+        .times(1)
+        .returning(|_, _| {
+            Ok(BTreeMap::from([
+                (EngineStateKind::WaitToStart, 2),
+                (EngineStateKind::Running, 3),
+                (EngineStateKind::ErrorClean, 4),
+                (EngineStateKind::ErrorWaitToClean, 1),
+            ]))
+        });
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/stats").await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({
+        "states": {
+            "WaitToStart": 2,
+            "TriggerStart": 0,
+            "StartInProgress": 0,
+            "Running": 3,
+            "WaitToTerminate": 0,
+            "TriggerTermination": 0,
+            "TerminateInProgress": 0,
+            "Terminated": 0,
+            "Error": 5
+        },
+        "errors": {
+            "ErrorWaitToClean": 1,
+            "ErrorTriggerClean": 0,
+            "ErrorCleanInProgress": 0,
+            "ErrorClean": 4
+        },
+        "total": 10
+    }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_engine_stats_without_engines() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_count_engines_by_state()
+        .times(1)
+        .returning(|_, _| Ok(BTreeMap::new()));
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/stats").await;
+
+    response.assert_status_ok();
+    let stats = response.json::<EngineStatsResponse>();
+    assert_eq!(stats.total, 0);
+    assert!(stats.states.values().all(|count| *count == 0));
+    assert!(stats.errors.values().all(|count| *count == 0));
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_engine_stats_fails_on_database_error() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_count_engines_by_state()
+        .times(1)
+        .returning(|_, _| Err(RucatError::fail_to_read_database(anyhow!("timeout"))));
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/stats").await;
+
+    response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_engine_stats_fails_on_unreachable_database() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_count_engines_by_state()
+        .times(1)
+        .returning(|_, _| Err(RucatError::fail_to_connect_database(anyhow!("refused"))));
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/stats").await;
+
+    response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(get_error_code(&response), "FAIL_TO_CONNECT_DATABASE");
+    Ok(())
+}
+
+#[tokio::test]
+async fn batch_delete_engines() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().times(3).returning(|id| {
+        let state = match id.to_string().as_str() {
+            "1" => WaitToStart,
+            "3" => Running,
+            _ => return Ok(None),
+        };
+        Ok(Some(EngineInfo::new(
+            "engine1".to_owned(),
+            EngineType::Spark,
+            "3.5.3".to_owned(),
+            state,
+            BTreeMap::new(),
+            EngineTime::now(),
+        )))
+    });
+    db.expect_remove_engine()
+        .with(
+            predicate::eq(EngineId::try_from("1")?),
             predicate::eq(&WaitToStart),
+            predicate::eq(None),
             predicate::always(),
         )
         .times(1)
-        .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+        .returning(|_, state, _, _| {
+            Ok(Some(RemoveEngineResult::Success(Box::new(
+                EngineInfo::new(
+                    "engine1".to_owned(),
+                    EngineType::Spark,
+                    "3.5.3".to_owned(),
+                    state.clone(),
+                    BTreeMap::new(),
+                    EngineTime::now(),
+                ),
+            ))))
+        });
     let server = get_test_server(db, None).await?;
 
-    let response = server.post("/engine/123/restart").await;
+    let response = server
+        .post("/engine/batch-delete")
+        .json(&json!({"ids": [{"id": "1"}, {"id": "2"}, {"id": "3"}, {"id": "1"}]}))
+        .await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({
+        "results": {
+            "1": "deleted",
+            "2": "not_found",
+            "3": {"not_allowed": {"state": "Running"}}
+        }
+    }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn batch_delete_engine_removed_concurrently() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().times(1).returning(|_| {
+        Ok(Some(EngineInfo::new(
+            "engine1".to_owned(),
+            EngineType::Spark,
+            "3.5.3".to_owned(),
+            Terminated,
+            BTreeMap::new(),
+            EngineTime::now(),
+        )))
+    });
+    db.expect_remove_engine()
+        .times(1)
+        .returning(|_, _, _, _| Ok(None));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .post("/engine/batch-delete")
+        .json(&json!({"ids": [{"id": "1"}]}))
+        .await;
+
     response.assert_status_ok();
+    response.assert_json(&json!({"results": {"1": "not_found"}}));
+    Ok(())
+}
 
+#[tokio::test]
+async fn batch_delete_too_many_engines() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+    let ids = (0..101)
+        .map(|i| json!({"id": i.to_string()}))
+        .collect::<Vec<_>>();
+
+    let response = server
+        .post("/engine/batch-delete")
+        .json(&json!({ "ids": ids }))
+        .await;
+
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert_eq!(
+        get_error_message(&response),
+        "At most 100 engines can be deleted in a batch, but got 101"
+    );
     Ok(())
 }
 
 #[tokio::test]
-async fn restart_nonexistent_engine() -> Result<()> {
+async fn batch_get_engines() -> Result<()> {
     let mut db = MockDB::new();
-    db.expect_get_engine()
-        .with(predicate::eq(EngineId::new(Cow::Borrowed("123"))?))
+    db.expect_get_engines()
+        .with(predicate::eq(vec![
+            EngineId::try_from("2")?,
+            EngineId::try_from("1")?,
+        ]))
         .times(1)
-        .returning(|_| Ok(None));
+        .returning(|ids| {
+            Ok(vec![
+                (ids[0].clone(), None),
+                (
+                    ids[1].clone(),
+                    Some(EngineInfo::new(
+                        "engine1".to_owned(),
+                        EngineType::Spark,
+                        "3.5.3".to_owned(),
+                        Running,
+                        BTreeMap::new(),
+                        EngineTime::now(),
+                    )),
+                ),
+            ])
+        });
     let server = get_test_server(db, None).await?;
-    let response = server.post("/engine/123/restart").await;
-    response.assert_status_not_found();
+
+    let response = server
+        .post("/engine/batch-get")
+        .json(&json!({"ids": [{"id": "2"}, {"id": "1"}]}))
+        .await;
+
+    response.assert_status_ok();
+    let body = response.json::<::rucat_common::serde_json::Value>();
+    assert_eq!(body["engines"][0], json!({"id": "2", "info": null}));
+    assert_eq!(body["engines"][1]["id"], "1");
+    assert_eq!(body["engines"][1]["info"]["name"], "engine1");
+    assert_eq!(body["engines"][1]["info"]["state"], "Running");
     Ok(())
 }
 
 #[tokio::test]
-async fn cannot_restart_engine() -> Result<()> {
+async fn batch_get_too_many_engines() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+    let ids = (0..101)
+        .map(|i| json!({"id": i.to_string()}))
+        .collect::<Vec<_>>();
+
+    let response = server
+        .post("/engine/batch-get")
+        .json(&json!({ "ids": ids }))
+        .await;
+
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert_eq!(
+        get_error_message(&response),
+        "At most 100 engines can be got in a batch, but got 101"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn cleanup_engines() -> Result<()> {
+    let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let mut db = MockDB::new();
+    db.expect_remove_engines_in_states()
+        .withf(move |states, older_than, project, deleted_time| {
+            *states == BTreeSet::from([EngineStateKind::Terminated, EngineStateKind::ErrorClean])
+                && *older_than == now - Duration::from_secs(86400)
+                && project.is_none()
+                && *deleted_time == now
+        })
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![EngineId::try_from("1")?, EngineId::try_from("2")?]));
+    // the events of the engines deleted for longer than 7 days by default
+    db.expect_purge_engine_events()
+        .withf(move |deleted_before, project| {
+            *deleted_before == now - Duration::from_secs(7 * 24 * 60 * 60) && project.is_none()
+        })
+        .times(1)
+        .returning(|_, _| Ok(3));
+    let server = get_test_server_with_sources(
+        db,
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        Arc::new(MockClock::new(now)),
+        Arc::new(SequentialIdGenerator::new(Cow::Borrowed("engine-"), 0)),
+    )
+    .await?;
+
+    let response = server
+        .post("/engine/cleanup")
+        .json(&json!({"states": ["Terminated", "ErrorClean"], "older_than_secs": 86400}))
+        .await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({"removed": 2}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn cannot_cleanup_engines_in_unstable_states() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server
+        .post("/engine/cleanup")
+        .json(&json!({"states": ["Terminated", "Running"], "older_than_secs": 0}))
+        .await;
+
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert_eq!(
+        get_error_message(&response),
+        "Only engines in stable states can be cleaned up, but got [Running]. \
+        Stable states: [Terminated, ErrorClean]"
+    );
+
+    let response = server
+        .post("/engine/cleanup")
+        .json(&json!({"states": [], "older_than_secs": 0}))
+        .await;
+
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cleanup_engines_older_than_too_long() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server
+        .post("/engine/cleanup")
+        .json(&json!({"states": ["Terminated"], "older_than_secs": u64::MAX}))
+        .await;
+
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert_eq!(
+        get_error_message(&response),
+        format!("`older_than_secs` {} is too large", u64::MAX)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn stop_wait_to_start_engine() -> Result<()> {
     let mut db = MockDB::new();
     db.expect_get_engine()
         .with(predicate::eq(EngineId::new(Cow::Borrowed("123"))?))
@@ -325,44 +1366,1600 @@ async fn cannot_restart_engine() -> Result<()> {
                 EngineTime::now(),
             )))
         });
+    db.expect_update_engine_state()
+        .with(
+            predicate::eq(EngineId::new(Cow::Borrowed("123"))?),
+            predicate::eq(&WaitToStart),
+            predicate::eq(&Terminated),
+            predicate::eq(None),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
     let server = get_test_server(db, None).await?;
 
-    let response = server.post("/engine/123/restart").await;
-    response.assert_status_forbidden();
-    response
-        .text()
-        .contains("Not allowed: Engine 123 is in WaitToStart state, cannot be restarted");
+    let response = server.post("/engine/123/stop").await;
+    response.assert_status_ok();
 
     Ok(())
 }
 
 #[tokio::test]
-async fn list_engines_empty() -> Result<()> {
+async fn stop_nonexistent_engine() -> Result<()> {
     let mut db = MockDB::new();
-    db.expect_list_engines().times(1).returning(|| Ok(vec![]));
+    db.expect_get_engine()
+        .with(predicate::eq(EngineId::new(Cow::Borrowed("123"))?))
+        .times(1)
+        .returning(|_| Ok(None));
     let server = get_test_server(db, None).await?;
-    let response = server.get("/engine").await;
-    response.assert_status_ok();
-    response.assert_text("[]");
+    let response = server.post("/engine/123/stop").await;
+    response.assert_status_not_found();
     Ok(())
 }
 
-#[tokio::test]
-async fn list_2_engines() -> Result<()> {
-    let ids = [
-        EngineId::new(Cow::Borrowed("1"))?,
-        EngineId::new(Cow::Borrowed("2"))?,
-    ];
-    let ids_cloned = ids.clone();
+/// Mock database with the engine 123 in the `state`.
+fn get_db_with_engine_in_state(state: EngineState) -> MockDB {
     let mut db = MockDB::new();
-    db.expect_list_engines()
-        .times(1)
-        .returning(move || Ok(ids_cloned.to_vec()));
-    let server = get_test_server(db, None).await?;
+    db.expect_get_engine().times(1).returning(move |_| {
+        Ok(Some(EngineInfo::new(
+            "engine1".to_owned(),
+            EngineType::Spark,
+            "3.5.3".to_owned(),
+            state.clone(),
+            BTreeMap::new(),
+            EngineTime::now(),
+        )))
+    });
+    db
+}
 
-    let response = server.get("/engine").await;
-    response.assert_status_ok();
-    response.assert_json(&json!([ids[0], ids[1]]));
+#[tokio::test]
+async fn cannot_stop_triggered_engine_without_force() -> Result<()> {
+    for (state, query) in [(TriggerStart, ""), (TriggerTermination, "?force=false")] {
+        let db = get_db_with_engine_in_state(state.clone());
+        let server = get_test_server(db, None).await?;
+
+        let response = server.post(&format!("/engine/123/stop{}", query)).await;
+        response.assert_status_forbidden();
+        assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+        assert_eq!(
+            get_error_message(&response),
+            format!("Engine 123 is in {:?} state, cannot be stopped", state)
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn force_stop_triggered_engine() -> Result<()> {
+    for state in [TriggerStart, TriggerTermination] {
+        let mut db = get_db_with_engine_in_state(state.clone());
+        db.expect_update_engine_state()
+            .with(
+                predicate::eq(EngineId::try_from("123")?),
+                predicate::eq(state),
+                predicate::eq(&WaitToTerminate),
+                predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
+            )
+            .times(1)
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+        let server = get_test_server(db, None).await?;
+
+        let response = server.post("/engine/123/stop?force=true").await;
+        response.assert_status_ok();
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn force_stop_engine_retry_on_conflict() -> Result<()> {
+    let mut db = get_db_with_engine_in_state(TriggerStart);
+    let mut seq = Sequence::new();
+    db.expect_update_engine_state()
+        .with(
+            predicate::eq(EngineId::try_from("123")?),
+            predicate::eq(&TriggerStart),
+            predicate::eq(&WaitToTerminate),
+            predicate::always(),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .in_sequence(&mut seq)
+        .returning(|_, _, _, _, _, _| {
+            Ok(Some(UpdateEngineStateResult::Fail {
+                current_state: StartInProgress,
+            }))
+        });
+    db.expect_update_engine_state()
+        .with(
+            predicate::eq(EngineId::try_from("123")?),
+            predicate::eq(&StartInProgress),
+            predicate::eq(&WaitToTerminate),
+            predicate::always(),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .in_sequence(&mut seq)
+        .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    let server = get_test_server(db, None).await?;
+
+    let response = server.post("/engine/123/stop?force=true").await;
+    response.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn stop_engine_conflict_after_max_attempts() -> Result<()> {
+    let mut db = get_db_with_engine_in_state(Running);
+    // the state keeps flipping between `StartInProgress` and `Running`
+    db.expect_update_engine_state()
+        .times(5)
+        .returning(|_, before, _, _, _, _| {
+            let current_state = if *before == Running {
+                StartInProgress
+            } else {
+                Running
+            };
+            Ok(Some(UpdateEngineStateResult::Fail { current_state }))
+        });
+    let server = get_test_server(db, None).await?;
+
+    let response = server.post("/engine/123/stop").await;
+
+    response.assert_status(StatusCode::CONFLICT);
+    assert_eq!(get_error_code(&response), "CONFLICT");
+    assert_eq!(
+        get_error_message(&response),
+        "Engine 123 keeps changing its state, last observed in StartInProgress state after 5 attempts"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn notify_webhook_of_stopped_engine() -> Result<()> {
+    let webhook = MockServer::start_async().await;
+    let mock = webhook
+        .mock_async(|when, then| {
+            when.method(POST).path("/hook").json_body(json!({
+                "engine_id": "123",
+                "old_state": "Running",
+                "new_state": "WaitToTerminate",
+                "timestamp": "1970-01-01 00:16:40 +00:00:00"
+            }));
+            then.status(200);
+        })
+        .await;
+    let mut db = get_db_with_engine_in_state(Running);
+    db.expect_update_engine_state()
+        .times(1)
+        .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    let app = get_test_app(
+        db,
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources {
+            clock: Arc::new(MockClock::new(UNIX_EPOCH + Duration::from_secs(1000))),
+            ..Sources::default()
+        },
+        EnginePolicy {
+            webhooks: Some(WebhookConfig {
+                urls: vec![webhook.url("/hook")],
+                states: [EngineStateKind::WaitToTerminate].into(),
+                timeout_secs: 1,
+                max_retries: 0,
+                retry_backoff_millis: 0,
+            }),
+            ..EnginePolicy::default()
+        },
+    )?;
+    let server = TestServer::new(app).map_err(RucatError::fail_to_start_server)?;
+
+    server.post("/engine/123/stop").await.assert_status_ok();
+
+    // The delivery runs in the background.
+    for _ in 0..100 {
+        if mock.hits_async().await > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    mock.assert_async().await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn restart_terminated_engine() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine()
+        .with(predicate::eq(EngineId::new(Cow::Borrowed("123"))?))
+        .times(1)
+        .returning(|_| {
+            Ok(Some(EngineInfo::new(
+                "engine1".to_owned(),
+                EngineType::Spark,
+                "3.5.3".to_owned(),
+                Terminated,
+                BTreeMap::new(),
+                EngineTime::now(),
+            )))
+        });
+    db.expect_update_engine_state()
+        .with(
+            predicate::eq(EngineId::new(Cow::Borrowed("123"))?),
+            predicate::eq(&Terminated),
+            predicate::eq(&WaitToStart),
+            predicate::always(),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    let server = get_test_server(db, None).await?;
+
+    let response = server.post("/engine/123/restart").await;
+    response.assert_status_ok();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn restart_error_clean_engine() -> Result<()> {
+    let error = ErrorClean(Cow::Borrowed("start timed out"));
+    let mut db = get_db_with_engine_in_state(error.clone());
+    db.expect_update_engine_state()
+        .with(
+            predicate::eq(EngineId::try_from("123")?),
+            predicate::eq(error),
+            predicate::eq(&WaitToStart),
+            predicate::always(),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    let server = get_test_server(db, None).await?;
+
+    let response = server.post("/engine/123/restart").await;
+    response.assert_status_ok();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cannot_restart_error_engine_before_it_is_cleaned() -> Result<()> {
+    for state in [
+        ErrorWaitToClean(Cow::Borrowed("error")),
+        ErrorTriggerClean(Cow::Borrowed("error")),
+        ErrorCleanInProgress(Cow::Borrowed("error")),
+    ] {
+        let db = get_db_with_engine_in_state(state.clone());
+        let server = get_test_server(db, None).await?;
+
+        let response = server.post("/engine/123/restart").await;
+        response.assert_status_forbidden();
+        assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+        assert_eq!(
+            get_error_message(&response),
+            format!("Engine 123 is in {:?} state, cannot be restarted", state)
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn restart_nonexistent_engine() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine()
+        .with(predicate::eq(EngineId::new(Cow::Borrowed("123"))?))
+        .times(1)
+        .returning(|_| Ok(None));
+    let server = get_test_server(db, None).await?;
+    let response = server.post("/engine/123/restart").await;
+    response.assert_status_not_found();
+    Ok(())
+}
+
+#[tokio::test]
+async fn cannot_restart_engine() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine()
+        .with(predicate::eq(EngineId::new(Cow::Borrowed("123"))?))
+        .times(1)
+        .returning(|_| {
+            Ok(Some(EngineInfo::new(
+                "engine1".to_owned(),
+                EngineType::Spark,
+                "3.5.3".to_owned(),
+                WaitToStart,
+                BTreeMap::new(),
+                EngineTime::now(),
+            )))
+        });
+    let server = get_test_server(db, None).await?;
+
+    let response = server.post("/engine/123/restart").await;
+    response.assert_status_forbidden();
+    assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+    assert!(get_error_message(&response)
+        .contains("Engine 123 is in WaitToStart state, cannot be restarted"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn restart_engine_conflict_after_max_attempts() -> Result<()> {
+    let mut db = get_db_with_engine_in_state(Terminated);
+    db.expect_update_engine_state()
+        .with(
+            predicate::eq(EngineId::try_from("123")?),
+            predicate::eq(&Terminated),
+            predicate::eq(&WaitToStart),
+            predicate::always(),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(5)
+        .returning(|_, _, _, _, _, _| {
+            Ok(Some(UpdateEngineStateResult::Fail {
+                current_state: Terminated,
+            }))
+        });
+    let server = get_test_server(db, None).await?;
+
+    let response = server.post("/engine/123/restart").await;
+
+    response.assert_status(StatusCode::CONFLICT);
+    assert_eq!(get_error_code(&response), "CONFLICT");
+    assert_eq!(
+        get_error_message(&response),
+        "Engine 123 keeps changing its state, last observed in Terminated state after 5 attempts"
+    );
+    Ok(())
+}
+
+/// Mock database with a `Terminated` engine 123 of the Spark version.
+fn get_db_with_terminated_engine(version: &'static str) -> MockDB {
+    let mut db = MockDB::new();
+    db.expect_get_engine().times(1).returning(move |_| {
+        Ok(Some(EngineInfo::new(
+            "engine1".to_owned(),
+            EngineType::Spark,
+            version.to_owned(),
+            Terminated,
+            BTreeMap::new(),
+            EngineTime::now(),
+        )))
+    });
+    db
+}
+
+#[tokio::test]
+async fn restart_engine_of_deprecated_version() -> Result<()> {
+    let mut db = get_db_with_terminated_engine("3.5.3");
+    db.expect_update_engine_state()
+        .with(
+            predicate::eq(EngineId::try_from("123")?),
+            predicate::eq(&Terminated),
+            predicate::eq(&WaitToStart),
+            predicate::always(),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    let server = get_test_server_with_versions(db)?;
+
+    let response = server.post("/engine/123/restart").await;
+    response.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn restart_engine_of_blocked_version() -> Result<()> {
+    let mut db = get_db_with_terminated_engine("3.5.4");
+    db.expect_update_engine_state().never();
+    let server = get_test_server_with_versions(db)?;
+
+    let response = server.post("/engine/123/restart").await;
+    response.assert_status_forbidden();
+    assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+    assert_eq!(
+        get_error_message(&response),
+        "Spark version 3.5.4 is blocked: 3.5.4 has a known bug"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn restart_engine_of_unlisted_version() -> Result<()> {
+    let mut db = get_db_with_terminated_engine("3.5.5");
+    db.expect_update_engine_state().never();
+    let server = get_test_server_with_versions(db)?;
+
+    let response = server.post("/engine/123/restart").await;
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert_eq!(
+        get_error_message(&response),
+        "Spark version 3.5.5 is not supported. Supported versions: [\"3.5.3\"]"
+    );
+    Ok(())
+}
+
+/// Expect reading the engine `123` in `state` before updating its config.
+fn expect_get_engine_for_config(db: &mut MockDB, state: EngineState) {
+    db.expect_get_engine().times(1).returning(move |_| {
+        Ok(Some(EngineInfo::new(
+            "engine1".to_owned(),
+            EngineType::Spark,
+            "3.5.3".to_owned(),
+            state.clone(),
+            BTreeMap::new(),
+            EngineTime::now(),
+        )))
+    });
+}
+
+#[tokio::test]
+async fn update_engine_config() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_get_engine_for_config(&mut db, Terminated);
+    let id = EngineId::try_from("123")?;
+    db.expect_update_engine_info()
+        .withf(
+            move |engine_id, update, expected_states, expected_revision| {
+                *engine_id == id
+                    && *update
+                        == EngineInfoUpdate {
+                            config: Some(BTreeMap::from([(
+                                Cow::Borrowed("spark.executor.instances"),
+                                Cow::Borrowed("4"),
+                            )])),
+                            ..Default::default()
+                        }
+                    && *expected_states == Some([WaitToStart, Terminated].as_slice())
+                    && expected_revision.is_none()
+            },
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .patch("/engine/123/config")
+        .json(&json!({"spark.executor.instances": "4"}))
+        .await;
+
+    response.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn cannot_update_config_of_running_engine() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_get_engine_for_config(&mut db, Running);
+    db.expect_update_engine_info()
+        .times(1)
+        .returning(|_, _, _, _| {
+            Ok(Some(UpdateEngineStateResult::Fail {
+                current_state: Running,
+            }))
+        });
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .patch("/engine/123/config")
+        .json(&json!({"spark.executor.instances": "4"}))
+        .await;
+
+    response.assert_status_forbidden();
+    assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+    assert!(get_error_message(&response)
+        .starts_with("Engine 123 is in Running state, cannot update its config"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn update_config_of_nonexistent_engine() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().times(1).returning(|_| Ok(None));
+    db.expect_update_engine_info().never();
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .patch("/engine/123/config")
+        .json(&json!({"spark.executor.instances": "4"}))
+        .await;
+
+    response.assert_status_not_found();
+    Ok(())
+}
+
+#[tokio::test]
+async fn update_engine_config_with_non_string_value() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server
+        .patch("/engine/123/config")
+        .json(&json!({"spark.executor.instances": 4}))
+        .await;
+
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn update_engine_config_with_reserved_config() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_get_engine_for_config(&mut db, Terminated);
+    db.expect_update_engine_info().never();
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .patch("/engine/123/config")
+        .json(&json!({
+            "spark.executor.instances": "4",
+            "spark.kubernetes.container.image": "my-image"
+        }))
+        .await;
+
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert!(get_error_message(&response)
+        .starts_with("Engine config `spark.kubernetes.container.image` is reserved by rucat."));
+    Ok(())
+}
+
+#[tokio::test]
+async fn rename_engine() -> Result<()> {
+    let mut db = MockDB::new();
+    let id = EngineId::try_from("123")?;
+    db.expect_update_engine_info()
+        .withf(
+            move |engine_id, update, expected_states, expected_revision| {
+                *engine_id == id
+                && *update
+                    == EngineInfoUpdate {
+                        name: Some("new name".to_owned()),
+                        ..Default::default()
+                    }
+                // renamed in any state
+                && expected_states.is_none()
+                && expected_revision.is_none()
+            },
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .put("/engine/123/name")
+        .json(&json!({"name": "new name"}))
+        .await;
+
+    response.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn rename_engine_if_match() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_update_engine_info()
+        .with(
+            predicate::eq(EngineId::try_from("123")?),
+            predicate::always(),
+            predicate::always(),
+            predicate::eq(Some(3)),
+        )
+        .times(1)
+        .returning(|id, _, _, _| {
+            Err(RucatError::precondition_failed(anyhow!(
+                "Engine {} is at revision 4, but revision Some(3) is expected",
+                id
+            )))
+        });
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .put("/engine/123/name")
+        .add_header(IF_MATCH, "\"3\"")
+        .json(&json!({"name": "new name"}))
+        .await;
+
+    response.assert_status(StatusCode::PRECONDITION_FAILED);
+    assert_eq!(get_error_code(&response), "PRECONDITION_FAILED");
+    Ok(())
+}
+
+#[tokio::test]
+async fn update_engine_config_if_match_any() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_get_engine_for_config(&mut db, WaitToStart);
+    db.expect_update_engine_info()
+        .with(
+            predicate::eq(EngineId::try_from("123")?),
+            predicate::always(),
+            predicate::always(),
+            predicate::eq(None),
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .patch("/engine/123/config")
+        .add_header(IF_MATCH, "*")
+        .json(&json!({"spark.executor.instances": "2"}))
+        .await;
+
+    response.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn stop_engine_if_match() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().times(1).returning(|_| {
+        Ok(Some(EngineInfo::new(
+            "engine1".to_owned(),
+            EngineType::Spark,
+            "3.5.3".to_owned(),
+            WaitToStart,
+            BTreeMap::new(),
+            EngineTime::now(),
+        )))
+    });
+    db.expect_update_engine_state()
+        .with(
+            predicate::eq(EngineId::try_from("123")?),
+            predicate::eq(&WaitToStart),
+            predicate::eq(&Terminated),
+            predicate::eq(None),
+            predicate::eq(Some(7)),
+            predicate::always(),
+        )
+        .times(1)
+        .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .post("/engine/123/stop")
+        .add_header(IF_MATCH, "\"7\"")
+        .await;
+
+    response.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn update_engine_with_invalid_if_match() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    for if_match in ["3", "W/\"3\"", "\"three\"", "\"1\", \"2\""] {
+        let response = server
+            .post("/engine/123/restart")
+            .add_header(IF_MATCH, if_match)
+            .await;
+        response.assert_status_bad_request();
+        assert_eq!(get_error_code(&response), "INVALID_REQUEST");
+        assert!(get_error_message(&response)
+            .starts_with("`If-Match` must be `*` or the `ETag` of the engine"));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete_engine_with_wait_if_match() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server
+        .delete("/engine/123")
+        .add_query_param("wait", true)
+        .add_header(IF_MATCH, "\"1\"")
+        .await;
+
+    response.assert_status_bad_request();
+    assert_eq!(
+        get_error_message(&response),
+        "`If-Match` cannot be set together with `wait`"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn rename_nonexistent_engine() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_update_engine_info()
+        .times(1)
+        .returning(|_, _, _, _| Ok(None));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .put("/engine/123/name")
+        .json(&json!({"name": "new name"}))
+        .await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_code(&response), "NOT_FOUND");
+    assert_eq!(get_error_message(&response), "Engine 123 not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn rename_engine_with_invalid_name() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server
+        .put("/engine/123/name")
+        .json(&json!({"name": ""}))
+        .await;
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert_eq!(
+        get_error_message(&response),
+        "Engine name must not be empty"
+    );
+
+    let response = server
+        .put("/engine/123/name")
+        .json(&json!({"name": "a".repeat(257)}))
+        .await;
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert_eq!(
+        get_error_message(&response),
+        "Engine name must be at most 256 characters, but got 257"
+    );
+
+    let response = server
+        .put("/engine/123/name")
+        .json(&json!({"name": "new name", "unknown": 1}))
+        .await;
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_empty() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_list_engines()
+        .with(
+            predicate::eq(0),
+            predicate::eq(101),
+            predicate::eq(EngineFilter::default()),
+            predicate::eq(EngineSort::default()),
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![]));
+    let server = get_test_server(db, None).await?;
+    let response = server.get("/engine").await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [], "next_offset": null}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_2_engines() -> Result<()> {
+    let ids = [
+        EngineId::new(Cow::Borrowed("1"))?,
+        EngineId::new(Cow::Borrowed("2"))?,
+    ];
+    let ids_cloned = ids.clone();
+    let mut db = MockDB::new();
+    db.expect_list_engines()
+        .times(1)
+        .returning(move |_, _, _, _| Ok(ids_cloned.to_vec()));
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine").await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [ids[0], ids[1]], "next_offset": null}));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_with_next_page() -> Result<()> {
+    let mut db = MockDB::new();
+    // one more engine is read to know whether there is a next page
+    db.expect_list_engines()
+        .with(
+            predicate::eq(4),
+            predicate::eq(3),
+            predicate::eq(EngineFilter::default()),
+            predicate::eq(EngineSort::default()),
+        )
+        .times(1)
+        .returning(|_, _, _, _| {
+            ["4", "5", "6"]
+                .into_iter()
+                .map(EngineId::try_from)
+                .collect()
+        });
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("offset", 4)
+        .add_query_param("limit", 2)
+        .await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [{"id": "4"}, {"id": "5"}], "next_offset": 6}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_beyond_the_end() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_list_engines()
+        .with(
+            predicate::eq(1000),
+            predicate::eq(11),
+            predicate::eq(EngineFilter::default()),
+            predicate::eq(EngineSort::default()),
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![]));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("offset", 1000)
+        .add_query_param("limit", 10)
+        .await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [], "next_offset": null}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_with_invalid_limit() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    for limit in [0, -1, 1001] {
+        let response = server.get("/engine").add_query_param("limit", limit).await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+        assert_eq!(
+            get_error_message(&response),
+            format!("`limit` must be between 1 and 1000, but got {}", limit)
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_with_negative_offset() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server.get("/engine").add_query_param("offset", -1).await;
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert_eq!(
+        get_error_message(&response),
+        "`offset` must not be negative, but got -1"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_with_non_integer_limit() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server.get("/engine").add_query_param("limit", "ten").await;
+    response.assert_status_bad_request();
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_by_states() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_list_engines()
+        .with(
+            predicate::always(),
+            predicate::always(),
+            predicate::eq(EngineFilter {
+                states: BTreeSet::from([EngineStateKind::Running, EngineStateKind::ErrorClean]),
+                ..EngineFilter::default()
+            }),
+            predicate::eq(EngineSort::default()),
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![EngineId::try_from("1")?]));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("state", "Running, ErrorClean,Running")
+        .await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [{"id": "1"}], "next_offset": null}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_by_unknown_state() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    for state in ["Pending", "running", "Running,", ""] {
+        let response = server.get("/engine").add_query_param("state", state).await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+        assert!(get_error_message(&response).starts_with("Unknown engine state"));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_sorted() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_list_engines()
+        .with(
+            predicate::always(),
+            predicate::always(),
+            predicate::eq(EngineFilter::default()),
+            predicate::eq(EngineSort {
+                key: EngineSortKey::CreateTime,
+                descending: true,
+            }),
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![EngineId::try_from("2")?, EngineId::try_from("1")?]));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("sort", "create_time")
+        .add_query_param("order", "desc")
+        .await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [{"id": "2"}, {"id": "1"}], "next_offset": null}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_by_unknown_sort_key() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    for key in ["info.create_time", "CreateTime", "id DESC", ""] {
+        let response = server.get("/engine").add_query_param("sort", key).await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+        assert!(get_error_message(&response).starts_with("Unknown sort key"));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_by_unknown_sort_order() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("sort", "name")
+        .add_query_param("order", "DESC")
+        .await;
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_ARGUMENT");
+    assert_eq!(
+        get_error_message(&response),
+        "Unknown sort order `DESC`, expected `asc` or `desc`"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_by_name_without_match() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_list_engines()
+        .with(
+            predicate::eq(0),
+            predicate::eq(101),
+            predicate::eq(EngineFilter {
+                name_contains: "etl".to_owned(),
+                ..EngineFilter::default()
+            }),
+            predicate::eq(EngineSort::default()),
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![]));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("name_contains", "etl")
+        .await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [], "next_offset": null}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_by_name_with_multiple_matches() -> Result<()> {
+    let mut db = MockDB::new();
+    // the search string is passed as is, matching case-insensitively is done by the database
+    db.expect_list_engines()
+        .with(
+            predicate::always(),
+            predicate::always(),
+            predicate::eq(EngineFilter {
+                name_contains: "Nightly ETL".to_owned(),
+                ..EngineFilter::default()
+            }),
+            predicate::eq(EngineSort::default()),
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![EngineId::try_from("1")?, EngineId::try_from("2")?]));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("name_contains", "Nightly ETL")
+        .await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [{"id": "1"}, {"id": "2"}], "next_offset": null}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_by_owner_without_auth() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server.get("/engine").add_query_param("owner", "me").await;
+    response.assert_status_bad_request();
+    assert_eq!(get_error_code(&response), "INVALID_REQUEST");
+    assert_eq!(
+        get_error_message(&response),
+        "Filtering by `owner` requires authentication to be enabled"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_by_name_and_states() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_list_engines()
+        .with(
+            predicate::eq(2),
+            predicate::eq(2),
+            predicate::eq(EngineFilter {
+                states: BTreeSet::from([EngineStateKind::Running]),
+                name_contains: "etl".to_owned(),
+                ..EngineFilter::default()
+            }),
+            predicate::eq(EngineSort::default()),
+        )
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![EngineId::try_from("3")?, EngineId::try_from("4")?]));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("name_contains", "etl")
+        .add_query_param("state", "Running")
+        .add_query_param("offset", 2)
+        .add_query_param("limit", 1)
+        .await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [{"id": "3"}], "next_offset": 3}));
+    Ok(())
+}
+
+fn get_engine_summary(id: &'static str, name: &str) -> Result<EngineSummary> {
+    Ok(EngineSummary {
+        id: EngineId::try_from(id)?,
+        name: name.to_owned(),
+        engine_type: EngineType::Spark,
+        state: Running,
+        create_time: EngineTime::now(),
+    })
+}
+
+#[tokio::test]
+async fn list_engine_summaries() -> Result<()> {
+    let summaries = [
+        get_engine_summary("1", "engine1")?,
+        get_engine_summary("2", "engine2")?,
+    ];
+    let summaries_cloned = summaries.clone();
+    let mut db = MockDB::new();
+    db.expect_list_engine_summaries()
+        .with(
+            predicate::eq(0),
+            predicate::eq(101),
+            predicate::eq(EngineFilter::default()),
+            predicate::eq(EngineSort::default()),
+        )
+        .times(1)
+        .returning(move |_, _, _, _| Ok(summaries_cloned.to_vec()));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("detail", "summary")
+        .await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": summaries, "next_offset": null}));
+    let engine = &response.json::<Value>()["engines"][0];
+    assert_eq!(engine["id"], json!({"id": "1"}));
+    assert_eq!(engine["name"], json!("engine1"));
+    assert_eq!(engine["engine_type"], json!("Spark"));
+    assert_eq!(engine["state"], json!("Running"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engine_summaries_with_filters_sort_and_next_page() -> Result<()> {
+    let summaries = [
+        get_engine_summary("3", "etl")?,
+        get_engine_summary("4", "ETL")?,
+    ];
+    let summaries_cloned = summaries.clone();
+    let mut db = MockDB::new();
+    db.expect_list_engine_summaries()
+        .with(
+            predicate::eq(2),
+            predicate::eq(2),
+            predicate::eq(EngineFilter {
+                states: BTreeSet::from([EngineStateKind::Running]),
+                name_contains: "etl".to_owned(),
+                ..EngineFilter::default()
+            }),
+            predicate::eq(EngineSort {
+                key: EngineSortKey::Name,
+                descending: false,
+            }),
+        )
+        .times(1)
+        .returning(move |_, _, _, _| Ok(summaries_cloned.to_vec()));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("detail", "summary")
+        .add_query_param("name_contains", "etl")
+        .add_query_param("state", "Running")
+        .add_query_param("sort", "name")
+        .add_query_param("order", "asc")
+        .add_query_param("offset", 2)
+        .add_query_param("limit", 1)
+        .await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [summaries[0]], "next_offset": 3}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engine_ids_explicitly() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_list_engines()
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![EngineId::try_from("1")?]));
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine").add_query_param("detail", "id").await;
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [{"id": "1"}], "next_offset": null}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_with_unknown_detail() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server
+        .get("/engine")
+        .add_query_param("detail", "full")
+        .await;
+    response.assert_status_bad_request();
+    Ok(())
+}
+
+/// Test server polling the watched engines every millisecond.
+fn get_test_server_with_events(db: MockDB) -> Result<TestServer> {
+    let app = get_test_app(
+        db,
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources::default(),
+        EnginePolicy {
+            events: from_value::<EngineEvents>(json!({"poll_interval_millis": 1}))
+                .map_err(RucatError::fail_to_load_config)?,
+            ..EnginePolicy::default()
+        },
+    )?;
+    TestServer::new(app).map_err(RucatError::fail_to_start_server)
+}
+
+/// Expect the state of the engine `123` to be got in the `states` one after another, `None` if it is deleted.
+fn expect_engine_states(db: &mut MockDB, states: Vec<Option<EngineState>>) {
+    let mut seq = Sequence::new();
+    for state in states {
+        db.expect_get_engine_state()
+            .with(predicate::eq(EngineId::new(Cow::Borrowed("123")).unwrap()))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(move |_| Ok(state.clone()));
+    }
+}
+
+#[tokio::test]
+async fn get_engine_state() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_engine_states(&mut db, vec![Some(Running)]);
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/123/state").await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({"state": "Running"}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_engine_state_not_found() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_engine_states(&mut db, vec![None]);
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/123/state").await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_code(&response), "NOT_FOUND");
+    assert_eq!(get_error_message(&response), "Engine 123 not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_engine_history() -> Result<()> {
+    let time = EngineTime::from_system_time(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    let event = EngineEvent {
+        old_state: WaitToStart,
+        new_state: TriggerStart,
+        source: "server".to_owned(),
+        actor: Some("alice".to_owned()),
+        impersonator: Some("admin".to_owned()),
+        time: time.clone(),
+    };
+    let mut db = MockDB::new();
+    db.expect_get_engine_events()
+        .with(
+            predicate::eq(EngineId::new(Cow::Borrowed("123"))?),
+            predicate::eq(10),
+        )
+        .times(1)
+        .returning(move |_, _| Ok(vec![event.clone()]));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .get("/engine/123/history")
+        .add_query_param("limit", 10)
+        .await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({
+        "events": [{
+            "old_state": "WaitToStart",
+            "new_state": "TriggerStart",
+            "source": "server",
+            "actor": "alice",
+            "impersonator": "admin",
+            "time": time
+        }]
+    }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_empty_engine_history() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine_events()
+        .with(predicate::always(), predicate::eq(100))
+        .times(1)
+        .returning(|_, _| Ok(vec![]));
+    expect_engine_states(&mut db, vec![Some(WaitToStart)]);
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/123/history").await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({"events": []}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_engine_history_not_found() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine_events()
+        .times(1)
+        .returning(|_, _| Ok(vec![]));
+    expect_engine_states(&mut db, vec![None]);
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/123/history").await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_message(&response), "Engine 123 not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_engine_history_with_invalid_limit() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    for limit in [0, -1, 1001] {
+        let response = server
+            .get("/engine/123/history")
+            .add_query_param("limit", limit)
+            .await;
+        response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            get_error_message(&response),
+            format!("`limit` must be between 1 and 1000, but got {}", limit)
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_connect_info_of_running_engine() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_engine_states(&mut db, vec![Some(Running)]);
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/123/connect-info").await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({
+        "spark_connect_url": "sc://rucat-spark-123.default.svc.cluster.local:15002",
+        "spark_ui_url": "http://rucat-spark-123.default.svc.cluster.local:4040",
+        "driver_host": "rucat-spark-123.default.svc.cluster.local"
+    }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_connect_info_of_engine_not_running() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_engine_states(&mut db, vec![Some(StartInProgress)]);
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/123/connect-info").await;
+
+    response.assert_status(StatusCode::CONFLICT);
+    assert_eq!(get_error_code(&response), "CONFLICT");
+    assert_eq!(
+        get_error_message(&response),
+        "Engine 123 is in StartInProgress state, connect info is only available when it is Running"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_connect_info_not_found() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_engine_states(&mut db, vec![None]);
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/engine/123/connect-info").await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_code(&response), "NOT_FOUND");
+    assert_eq!(get_error_message(&response), "Engine 123 not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn watch_engine_until_terminated() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_engine_states(
+        &mut db,
+        vec![
+            Some(Running),
+            Some(Running),
+            Some(WaitToTerminate),
+            Some(WaitToTerminate),
+            Some(Terminated),
+        ],
+    );
+    let server = get_test_server_with_events(db)?;
+
+    let response = server.get("/engine/123/events").await;
+
+    response.assert_status_ok();
+    assert_eq!(
+        response.text(),
+        "event: state\ndata: \"Running\"\n\n\
+         event: state\ndata: \"WaitToTerminate\"\n\n\
+         event: state\ndata: \"Terminated\"\n\n\
+         event: end\ndata: Terminated\n\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn watch_engine_in_error_clean() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_engine_states(
+        &mut db,
+        vec![Some(ErrorClean(Cow::Borrowed("fail to start")))],
+    );
+    let server = get_test_server_with_events(db)?;
+
+    let response = server.get("/engine/123/events").await;
+
+    response.assert_status_ok();
+    assert_eq!(
+        response.text(),
+        "event: state\ndata: {\"ErrorClean\":\"fail to start\"}\n\n\
+         event: end\ndata: ErrorClean\n\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn watch_deleted_engine() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_engine_states(&mut db, vec![Some(StartInProgress), None]);
+    let server = get_test_server_with_events(db)?;
+
+    let response = server.get("/engine/123/events").await;
+
+    response.assert_status_ok();
+    assert_eq!(
+        response.text(),
+        "event: state\ndata: \"StartInProgress\"\n\n\
+         event: end\ndata: Deleted\n\n"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn watch_engine_not_found() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_engine_states(&mut db, vec![None]);
+    let server = get_test_server_with_events(db)?;
+
+    let response = server.get("/engine/123/events").await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_code(&response), "NOT_FOUND");
+    assert_eq!(get_error_message(&response), "Engine 123 not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete_running_engine_and_wait() -> Result<()> {
+    let mut db = get_db_with_engine_in_state(Terminated);
+    expect_engine_states(
+        &mut db,
+        vec![Some(Running), Some(TerminateInProgress), Some(Terminated)],
+    );
+    db.expect_update_engine_state()
+        .with(
+            predicate::eq(EngineId::try_from("123")?),
+            predicate::eq(&Running),
+            predicate::eq(&WaitToTerminate),
+            predicate::always(),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    db.expect_remove_engine()
+        .with(
+            predicate::eq(EngineId::try_from("123")?),
+            predicate::eq(&Terminated),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .returning(|_, state, _, _| {
+            Ok(Some(RemoveEngineResult::Success(Box::new(
+                EngineInfo::new(
+                    "engine1".to_owned(),
+                    EngineType::Spark,
+                    "3.5.3".to_owned(),
+                    state.clone(),
+                    BTreeMap::new(),
+                    EngineTime::now(),
+                ),
+            ))))
+        });
+    let server = get_test_server_with_events(db)?;
+
+    let response = server.delete("/engine/123?wait=true").await;
+
+    response.assert_status_ok();
+    assert_eq!(response.text(), "");
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete_engine_and_wait_until_deleted_by_others() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_engine_states(&mut db, vec![Some(TerminateInProgress), None]);
+    let server = get_test_server_with_events(db)?;
+
+    let response = server.delete("/engine/123?wait=true").await;
+
+    response.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete_nonexistent_engine_and_wait() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_engine_states(&mut db, vec![None]);
+    let server = get_test_server_with_events(db)?;
+
+    let response = server.delete("/engine/123?wait=true").await;
+
+    response.assert_status_not_found();
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete_engine_and_wait_timeout() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine_state()
+        .returning(|_| Ok(Some(TerminateInProgress)));
+    let server = get_test_server_with_events(db)?;
+
+    let response = server.delete("/engine/123?wait=true&timeout_secs=1").await;
+
+    response.assert_status(StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(get_error_code(&response), "TIMEOUT");
+    assert_eq!(
+        get_error_message(&response),
+        "Engine 123 is still in TerminateInProgress state after waiting 1 seconds to delete it"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete_engine_with_invalid_wait() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+    for (query, status, code, message) in [
+        (
+            "force=true&wait=true",
+            StatusCode::BAD_REQUEST,
+            "INVALID_REQUEST",
+            "`force` and `wait` cannot be set together",
+        ),
+        (
+            "timeout_secs=10",
+            StatusCode::BAD_REQUEST,
+            "INVALID_REQUEST",
+            "`timeout_secs` can only be set with `wait`",
+        ),
+        (
+            "wait=true&timeout_secs=0",
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "INVALID_ARGUMENT",
+            "`timeout_secs` must be between 1 and 600, but got 0",
+        ),
+        (
+            "wait=true&timeout_secs=601",
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "INVALID_ARGUMENT",
+            "`timeout_secs` must be between 1 and 600, but got 601",
+        ),
+    ] {
+        let response = server.delete(&format!("/engine/123?{}", query)).await;
 
+        response.assert_status(status);
+        assert_eq!(get_error_code(&response), code);
+        assert_eq!(get_error_message(&response), message);
+    }
     Ok(())
 }
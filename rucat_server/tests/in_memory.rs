@@ -0,0 +1,199 @@
+//! Requests served end-to-end by [InMemoryDatabase] instead of a mocked database.
+
+mod common;
+
+use ::rucat_common::{
+    database::{in_memory::InMemoryDatabase, Database},
+    engine::{EngineId, EngineInfo, EngineState, EngineSummary},
+    error::*,
+    serde_json::{from_value, json, Value},
+    tokio,
+};
+use ::rucat_server::authentication::static_auth_provider::{StaticAuthConfig, StaticAuthProvider};
+use axum_extra::headers::authorization::Credentials as _;
+use axum_test::{TestRequest, TestServer};
+use common::{get_error_code, get_error_message, get_test_server, MockDB};
+use headers::Authorization;
+use http::header::{HeaderName, AUTHORIZATION, ETAG, IF_MATCH};
+
+async fn create_engine(server: &TestServer, name: &str) -> EngineId {
+    let response = server
+        .post("/engine")
+        .json(&json!({
+            "name": name,
+            "engine_type": "Spark",
+            "version": "3.5.3"
+        }))
+        .await;
+    response.assert_status_ok();
+    EngineId::try_from(response.json::<Value>()["id"].as_str().unwrap().to_owned()).unwrap()
+}
+
+#[tokio::test]
+async fn engine_lifecycle() -> Result<()> {
+    let server = get_test_server(InMemoryDatabase::new(), None).await?;
+    let id = create_engine(&server, "test").await;
+
+    let response = server.get(&format!("/engine/{}", id)).await;
+    response.assert_status_ok();
+    assert_eq!(response.header(ETAG), "\"0\"");
+    let info = response.json::<EngineInfo>();
+    assert_eq!(info.name, "test");
+    assert_eq!(info.state, EngineState::WaitToStart);
+
+    server
+        .post(&format!("/engine/{}/stop", id))
+        .await
+        .assert_status_ok();
+    let response = server.get(&format!("/engine/{}", id)).await;
+    assert_eq!(response.header(ETAG), "\"1\"");
+    assert_eq!(response.json::<EngineInfo>().state, EngineState::Terminated);
+
+    // the engine is updated since revision 0
+    let response = server
+        .post(&format!("/engine/{}/restart", id))
+        .add_header(IF_MATCH, "\"0\"")
+        .await;
+    response.assert_status(http::StatusCode::PRECONDITION_FAILED);
+    assert_eq!(get_error_code(&response), "PRECONDITION_FAILED");
+
+    server
+        .post(&format!("/engine/{}/restart", id))
+        .add_header(IF_MATCH, "\"1\"")
+        .await
+        .assert_status_ok();
+    let response = server.get(&format!("/engine/{}", id)).await;
+    assert_eq!(
+        response.json::<EngineInfo>().state,
+        EngineState::WaitToStart
+    );
+
+    server
+        .delete(&format!("/engine/{}", id))
+        .await
+        .assert_status_ok();
+    server
+        .get(&format!("/engine/{}", id))
+        .await
+        .assert_status_not_found();
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines() -> Result<()> {
+    let server = get_test_server(InMemoryDatabase::new(), None).await?;
+    let etl = create_engine(&server, "etl").await;
+    let adhoc = create_engine(&server, "adhoc").await;
+    server
+        .post(&format!("/engine/{}/stop", adhoc))
+        .await
+        .assert_status_ok();
+
+    let response = server
+        .get("/engine")
+        .add_query_param("detail", "summary")
+        .add_query_param("sort", "name")
+        .await;
+    response.assert_status_ok();
+    let engines: Vec<EngineSummary> =
+        from_value(response.json::<Value>()["engines"].clone()).unwrap();
+    assert_eq!(
+        engines
+            .iter()
+            .map(|engine| (&engine.id, &engine.state))
+            .collect::<Vec<_>>(),
+        vec![
+            (&adhoc, &EngineState::Terminated),
+            (&etl, &EngineState::WaitToStart)
+        ]
+    );
+
+    let response = server
+        .get("/engine")
+        .add_query_param("state", "Terminated")
+        .await;
+    response.assert_json(&json!({"engines": [adhoc], "next_offset": null}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_not_found_same_as_mock() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().times(1).returning(|_| Ok(None));
+    let mock_server = get_test_server(db, None).await?;
+    let server = get_test_server(InMemoryDatabase::new(), None).await?;
+
+    let mock_response = mock_server.get("/engine/123").await;
+    let response = server.get("/engine/123").await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_code(&response), get_error_code(&mock_response));
+    assert_eq!(get_error_message(&response), "Engine 123 not found");
+    assert_eq!(
+        get_error_message(&response),
+        get_error_message(&mock_response)
+    );
+    Ok(())
+}
+
+fn as_user(request: TestRequest, username: &str) -> TestRequest {
+    request.add_header(
+        AUTHORIZATION,
+        Authorization::basic(username, "pwd").0.encode(),
+    )
+}
+
+#[tokio::test]
+async fn record_actors_in_engine_history() -> Result<()> {
+    let auth_provider = StaticAuthProvider::from_config(
+        from_value::<StaticAuthConfig>(json!({
+            "users": [
+                {"username": "alice", "password": "pwd", "role": "operator"},
+                {"username": "ops", "password": "pwd", "role": "admin"}
+            ],
+            "bearer_tokens": []
+        }))
+        .unwrap(),
+    )?;
+    let db = InMemoryDatabase::new();
+    let server = get_test_server(db.clone(), Some(auth_provider)).await?;
+    let response = as_user(
+        server.post("/engine").json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3"
+        })),
+        "alice",
+    )
+    .await;
+    response.assert_status_ok();
+    let id = EngineId::try_from(response.json::<Value>()["id"].as_str().unwrap().to_owned())?;
+
+    as_user(server.post(&format!("/engine/{}/stop", id)), "alice")
+        .await
+        .assert_status_ok();
+    as_user(server.post(&format!("/engine/{}/restart", id)), "ops")
+        .add_header(HeaderName::from_static("x-rucat-impersonate-user"), "alice")
+        .await
+        .assert_status_ok();
+
+    let events = db.get_engine_events(&id, 10).await?;
+    let actors = events
+        .iter()
+        .map(|event| {
+            (
+                &event.new_state,
+                event.actor.as_deref(),
+                event.impersonator.as_deref(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        actors,
+        [
+            (&EngineState::WaitToStart, Some("alice"), Some("ops")),
+            (&EngineState::Terminated, Some("alice"), None),
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use ::std::{net::SocketAddr, time::Duration};
+
+use ::rucat_common::{
+    error::{Result, RucatError},
+    tokio,
+};
+use ::rucat_server::{
+    authentication::static_auth_provider::StaticAuthProvider, login_lockout::LoginLockoutConfig,
+    shutdown::ShutdownHandle, EnginePolicy, Sources,
+};
+use axum::Router;
+use axum_extra::headers::authorization::Credentials as _;
+use axum_test::TestServer;
+use common::{get_error_code, get_error_message, get_test_app, get_test_server, MockDB};
+use headers::Authorization;
+use http::{
+    header::{AUTHORIZATION, RETRY_AFTER},
+    StatusCode,
+};
+
+/// App with the user `admin` of password `admin`, locking out the clients after `max_failures` failed logins.
+fn get_app(max_failures: u32, lockout_secs: u64) -> Result<Router> {
+    let auth_provider =
+        StaticAuthProvider::new("admin".to_owned(), "admin".to_owned(), "admin".to_owned());
+    get_test_app(
+        MockDB::new(),
+        Some(auth_provider),
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources::default(),
+        EnginePolicy {
+            login_lockout: Some(LoginLockoutConfig {
+                max_failures,
+                window_secs: 60,
+                lockout_secs,
+            }),
+            ..EnginePolicy::default()
+        },
+    )
+}
+
+fn get_server(max_failures: u32, lockout_secs: u64) -> Result<TestServer> {
+    TestServer::new(get_app(max_failures, lockout_secs)?).map_err(RucatError::fail_to_start_server)
+}
+
+async fn login(server: &TestServer, password: &str) -> StatusCode {
+    server
+        .get("/capabilities")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("admin", password).0.encode(),
+        )
+        .await
+        .status_code()
+}
+
+#[tokio::test]
+async fn lock_out_after_max_failures() -> Result<()> {
+    let server = get_server(3, 300)?;
+    for _ in 0..3 {
+        assert_eq!(login(&server, "wrong").await, StatusCode::UNAUTHORIZED);
+    }
+
+    // rejected even with the correct password
+    let response = server
+        .get("/capabilities")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("admin", "admin").0.encode(),
+        )
+        .await;
+    response.assert_status(StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.header(RETRY_AFTER), "300");
+    assert_eq!(get_error_code(&response), "TOO_MANY_REQUESTS");
+    assert_eq!(
+        get_error_message(&response),
+        "Too many failed logins, retry after 300 seconds"
+    );
+
+    // other users are not locked out
+    server
+        .get("/capabilities")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("other", "wrong").0.encode(),
+        )
+        .await
+        .assert_status_unauthorized();
+    Ok(())
+}
+
+#[tokio::test]
+async fn lockout_expires() -> Result<()> {
+    let server = get_server(1, 1)?;
+    assert_eq!(login(&server, "wrong").await, StatusCode::UNAUTHORIZED);
+    assert_eq!(login(&server, "admin").await, StatusCode::TOO_MANY_REQUESTS);
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(login(&server, "admin").await, StatusCode::OK);
+    Ok(())
+}
+
+#[tokio::test]
+async fn success_resets_failures() -> Result<()> {
+    let server = get_server(3, 300)?;
+    for _ in 0..2 {
+        assert_eq!(login(&server, "wrong").await, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(login(&server, "admin").await, StatusCode::OK);
+    for _ in 0..2 {
+        assert_eq!(login(&server, "wrong").await, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(login(&server, "admin").await, StatusCode::OK);
+    Ok(())
+}
+
+#[tokio::test]
+async fn lock_out_remote_ip_of_wrong_tokens() -> Result<()> {
+    let server =
+        TestServer::new(get_app(2, 300)?.into_make_service_with_connect_info::<SocketAddr>())
+            .map_err(RucatError::fail_to_start_server)?;
+    for token in ["wrong-1", "wrong-2"] {
+        server
+            .get("/capabilities")
+            .add_header(
+                AUTHORIZATION,
+                Authorization::bearer(token).unwrap().0.encode(),
+            )
+            .await
+            .assert_status_unauthorized();
+    }
+    assert_eq!(login(&server, "admin").await, StatusCode::TOO_MANY_REQUESTS);
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_login_lockout() {
+    let result = get_server(0, 300);
+    assert!(result.is_err_and(|e| e
+        .to_string()
+        .starts_with("Fail to load config: Login lockout needs a positive")));
+}
+
+#[tokio::test]
+async fn not_locked_out_by_default() -> Result<()> {
+    let server = get_test_server(
+        MockDB::new(),
+        Some(StaticAuthProvider::new(
+            "admin".to_owned(),
+            "admin".to_owned(),
+            "admin".to_owned(),
+        )),
+    )
+    .await?;
+    for _ in 0..10 {
+        assert_eq!(login(&server, "wrong").await, StatusCode::UNAUTHORIZED);
+    }
+    assert_eq!(login(&server, "admin").await, StatusCode::OK);
+    Ok(())
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "metrics")]
+
+mod common;
+
+use ::mockall::predicate;
+use ::rucat_common::{
+    database::UpdateEngineStateResult,
+    engine::{EngineConfig, EngineId, EngineInfo, EngineState::*, EngineTime, EngineType},
+    error::Result,
+    serde_json::json,
+    tokio,
+};
+use ::rucat_server::authentication::static_auth_provider::StaticAuthProvider;
+use axum_extra::headers::authorization::Credentials as _;
+use axum_test::TestServer;
+use common::{get_error_code, get_error_message, get_test_server, MockDB};
+use headers::Authorization;
+use http::header::{AUTHORIZATION, CONTENT_TYPE};
+
+/// Get the metrics of the server in the text format.
+async fn get_metrics(server: &TestServer) -> String {
+    let response = server.get("/metrics").await;
+    response.assert_status_ok();
+    response.text()
+}
+
+#[tokio::test]
+async fn metrics_without_auth() -> Result<()> {
+    let auth_provider =
+        StaticAuthProvider::new("admin".to_owned(), "admin".to_owned(), "admin".to_owned());
+    let server = get_test_server(MockDB::new(), Some(auth_provider)).await?;
+
+    let response = server.get("/engine").await;
+    response.assert_status_unauthorized();
+    assert_eq!(get_error_code(&response), "UNAUTHORIZED");
+    assert_eq!(
+        get_error_message(&response),
+        "Not found authorization header"
+    );
+
+    let response = server.get("/metrics").await;
+    response.assert_status_ok();
+    assert_eq!(
+        response.header(CONTENT_TYPE),
+        "application/openmetrics-text; version=1.0.0; charset=utf-8"
+    );
+    assert!(response.text().contains(
+        r#"rucat_http_request_duration_seconds_count{method="GET",route="/engine",status="401"} 1"#
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn record_requests_by_route_template() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().times(2).returning(|_| Ok(None));
+    let server = get_test_server(db, None).await?;
+
+    server.get("/engine/abc").await.assert_status_not_found();
+    server.get("/engine/def").await.assert_status_not_found();
+    server.get("/unknown/path").await.assert_status_not_found();
+
+    let metrics = get_metrics(&server).await;
+    assert!(metrics.contains(
+        r#"rucat_http_request_duration_seconds_count{method="GET",route="/engine/{id}",status="404"} 2"#
+    ));
+    assert!(metrics.contains(
+        r#"rucat_http_request_duration_seconds_count{method="GET",route="unmatched",status="404"} 1"#
+    ));
+    assert!(!metrics.contains("/engine/abc"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn count_engine_operations() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    db.expect_get_engine().times(1).returning(|_| {
+        Ok(Some(EngineInfo::new(
+            "test".to_owned(),
+            EngineType::Spark,
+            "3.5.4".to_owned(),
+            WaitToStart,
+            EngineConfig::new(),
+            EngineTime::now(),
+        )))
+    });
+    let id = EngineId::try_from("abc")?;
+    db.expect_update_engine_state()
+        .with(
+            predicate::eq(id.clone()),
+            predicate::eq(WaitToStart),
+            predicate::eq(Terminated),
+            predicate::always(),
+            predicate::eq(None),
+            predicate::always(),
+        )
+        .times(1)
+        .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+    let server = get_test_server(db, None).await?;
+
+    let metrics = get_metrics(&server).await;
+    assert!(metrics.contains("rucat_engines_created_total 0"));
+
+    server
+        .post("/engine")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.4"
+        }))
+        .await
+        .assert_status_ok();
+    server.post("/engine/abc/stop").await.assert_status_ok();
+
+    let metrics = get_metrics(&server).await;
+    assert!(metrics.contains("rucat_engines_created_total 1"));
+    assert!(metrics.contains("rucat_engines_stopped_total 1"));
+    assert!(metrics.contains("rucat_engines_deleted_total 0"));
+    assert!(metrics.contains("rucat_engines_restarted_total 0"));
+    // logs are not set up by the tests
+    assert!(metrics.contains("rucat_dropped_log_lines 0"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn count_auth_attempts() -> Result<()> {
+    let auth_provider =
+        StaticAuthProvider::new("admin".to_owned(), "admin".to_owned(), "admin".to_owned());
+    let server = get_test_server(MockDB::new(), Some(auth_provider)).await?;
+
+    server
+        .get("/capabilities")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("admin", "wrong").0.encode(),
+        )
+        .await
+        .assert_status_unauthorized();
+    server
+        .get("/capabilities")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::bearer("admin").unwrap().0.encode(),
+        )
+        .await
+        .assert_status_ok();
+
+    let metrics = get_metrics(&server).await;
+    assert!(metrics.contains(r#"rucat_auth_attempts_total{outcome="failure",scheme="basic"} 1"#));
+    assert!(metrics.contains(r#"rucat_auth_attempts_total{outcome="success",scheme="bearer"} 1"#));
+    assert!(!metrics.contains(r#"rucat_auth_attempts_total{outcome="success",scheme="basic"}"#));
+    Ok(())
+}
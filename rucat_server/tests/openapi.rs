@@ -0,0 +1,153 @@
+#![cfg(feature = "openapi")]
+
+mod common;
+
+use ::rucat_common::{error::Result, serde_json::Value, tokio};
+use ::rucat_server::authentication::static_auth_provider::StaticAuthProvider;
+use common::{get_error_code, get_error_message, get_test_server, MockDB};
+#[cfg(feature = "swagger-ui")]
+use http::header::CONTENT_TYPE;
+
+#[tokio::test]
+async fn openapi_without_auth() -> Result<()> {
+    let auth_provider =
+        StaticAuthProvider::new("admin".to_owned(), "admin".to_owned(), "admin".to_owned());
+    let server = get_test_server(MockDB::new(), Some(auth_provider)).await?;
+
+    let response = server.get("/engine").await;
+    response.assert_status_unauthorized();
+    assert_eq!(get_error_code(&response), "UNAUTHORIZED");
+    assert_eq!(
+        get_error_message(&response),
+        "Not found authorization header"
+    );
+
+    server.get("/api-doc/openapi.json").await.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn openapi_covers_engine_routes() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+    let spec = server.get("/api-doc/openapi.json").await.json::<Value>();
+
+    let mut operations = spec["paths"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(path, item)| {
+            item.as_object()
+                .unwrap()
+                .keys()
+                .map(move |method| format!("{} {}", method, path))
+        })
+        .collect::<Vec<_>>();
+    operations.sort();
+    assert_eq!(
+        operations,
+        [
+            "delete /engine/{id}",
+            "get /engine",
+            "get /engine/stats",
+            "get /engine/{id}",
+            "get /engine/{id}/connect-info",
+            "get /engine/{id}/events",
+            "get /engine/{id}/history",
+            "get /engine/{id}/k8s-events",
+            "get /engine/{id}/logs",
+            "get /engine/{id}/state",
+            "patch /engine/{id}/config",
+            "post /engine",
+            "post /engine/adopt",
+            "post /engine/batch-delete",
+            "post /engine/batch-get",
+            "post /engine/cleanup",
+            "post /engine/{id}/logs/archive",
+            "post /engine/{id}/restart",
+            "post /engine/{id}/stop",
+            "put /engine/{id}/acl",
+            "put /engine/{id}/name",
+        ]
+    );
+
+    // every referenced schema is defined
+    let schemas = &spec["components"]["schemas"];
+    let text = spec.to_string();
+    for reference in text.split("\"#/components/schemas/").skip(1) {
+        let name = &reference[..reference.find('"').unwrap()];
+        assert!(
+            schemas.get(name).is_some(),
+            "schema {} is not defined",
+            name
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn openapi_schemas_of_engine_types() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+    let spec = server.get("/api-doc/openapi.json").await.json::<Value>();
+    let schemas = &spec["components"]["schemas"];
+
+    let create_request = &schemas["CreateEngineRequest"];
+    assert_eq!(
+        create_request["required"],
+        Value::from(vec!["name", "engine_type", "version"])
+    );
+    let mut fields = create_request["properties"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    fields.sort();
+    assert_eq!(
+        fields,
+        [
+            "config",
+            "engine_type",
+            "features",
+            "name",
+            "start",
+            "start_in_progress_timeout_secs",
+            "version"
+        ]
+    );
+    assert_eq!(
+        schemas["EngineId"]["properties"]["id"]["type"],
+        Value::from("string")
+    );
+    assert_eq!(
+        schemas["EngineInfo"]["properties"]["create_time"]["$ref"],
+        Value::from("#/components/schemas/EngineTime")
+    );
+    assert_eq!(
+        schemas["ErrorResponse"]["required"],
+        Value::from(vec!["code", "message"])
+    );
+    assert_eq!(
+        spec["paths"]["/engine/{id}"]["get"]["responses"]["404"]["content"]["application/json"]
+            ["schema"]["$ref"],
+        Value::from("#/components/schemas/ErrorResponse")
+    );
+    for header in ["X-Rucat-Project", "X-Rucat-Impersonate-User"] {
+        assert!(spec["paths"]["/engine"]["post"]["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|parameter| parameter["name"] == header && parameter["in"] == "header"));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "swagger-ui")]
+#[tokio::test]
+async fn swagger_ui() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+    let response = server.get("/swagger-ui/").await;
+    response.assert_status_ok();
+    assert_eq!(response.header(CONTENT_TYPE), "text/html");
+    assert!(response.text().contains("swagger"));
+    Ok(())
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use ::std::{borrow::Cow, collections::BTreeMap};
+
+use ::mockall::predicate;
+use ::rucat_common::{
+    engine::{EngineId, EngineInfo, EngineState::*, EngineTime, EngineType},
+    error::*,
+    serde_json::json,
+    tokio,
+};
+use ::rucat_server::{
+    path_normalization::{normalize_paths, NormalizationMode, PathNormalization},
+    shutdown::ShutdownHandle,
+    EnginePolicy, Sources,
+};
+use axum_test::TestServer;
+use common::{get_error_code, get_error_message, get_test_app, get_test_server, MockDB};
+use http::{header::LOCATION, StatusCode};
+
+async fn get_test_server_with_path_normalization(
+    db: MockDB,
+    config: PathNormalization,
+) -> Result<TestServer> {
+    let app = get_test_app(
+        db,
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources::default(),
+        EnginePolicy::default(),
+    )?;
+    TestServer::new(normalize_paths(app, config)).map_err(RucatError::fail_to_start_server)
+}
+
+fn expect_get_engine(db: &mut MockDB, id: &'static str) -> Result<()> {
+    db.expect_get_engine()
+        .with(predicate::eq(EngineId::new(Cow::Borrowed(id))?))
+        .times(1)
+        .returning(|_| {
+            Ok(Some(EngineInfo::new(
+                "engine1".to_owned(),
+                EngineType::Spark,
+                "3.5.4".to_owned(),
+                Running,
+                BTreeMap::new(),
+                EngineTime::now(),
+            )))
+        });
+    Ok(())
+}
+
+#[tokio::test]
+async fn paths_are_matched_exactly_by_default() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    server.get("/engine/").await.assert_status_not_found();
+    server.get("/Engine/123").await.assert_status_not_found();
+    Ok(())
+}
+
+#[tokio::test]
+async fn rewrite_trailing_slash() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_list_engines()
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![]));
+    let server = get_test_server_with_path_normalization(db, PathNormalization::default()).await?;
+
+    let response = server.get("/engine/").await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [], "next_offset": null}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn rewrite_empty_segments() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_get_engine(&mut db, "123")?;
+    let server = get_test_server_with_path_normalization(db, PathNormalization::default()).await?;
+
+    let response = server.get("/engine//123").await;
+
+    response.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn errors_of_rewritten_requests() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().times(1).returning(|_| Ok(None));
+    let server = get_test_server_with_path_normalization(db, PathNormalization::default()).await?;
+
+    let response = server.get("/engine/123/").await;
+
+    response.assert_status_not_found();
+    assert_eq!(get_error_code(&response), "NOT_FOUND");
+    assert_eq!(get_error_message(&response), "Engine 123 not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn rewrite_mixed_case_prefix() -> Result<()> {
+    let mut db = MockDB::new();
+    // only the prefix is lowercased
+    expect_get_engine(&mut db, "ABC")?;
+    let server = get_test_server_with_path_normalization(
+        db,
+        PathNormalization {
+            lowercase_prefix: true,
+            mode: NormalizationMode::Rewrite,
+        },
+    )
+    .await?;
+
+    let response = server.get("/Engine/ABC/").await;
+
+    response.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn keep_case_of_prefix_by_default() -> Result<()> {
+    let server =
+        get_test_server_with_path_normalization(MockDB::new(), PathNormalization::default())
+            .await?;
+
+    server.get("/Engine/123").await.assert_status_not_found();
+    Ok(())
+}
+
+#[tokio::test]
+async fn redirect_to_canonical_path() -> Result<()> {
+    let server = get_test_server_with_path_normalization(
+        MockDB::new(),
+        PathNormalization {
+            lowercase_prefix: true,
+            mode: NormalizationMode::Redirect,
+        },
+    )
+    .await?;
+
+    let response = server.get("/Engine//123/").add_query_param("a", "b").await;
+
+    response.assert_status(StatusCode::PERMANENT_REDIRECT);
+    response.assert_header(LOCATION, "/engine/123?a=b");
+    Ok(())
+}
+
+#[tokio::test]
+async fn do_not_redirect_canonical_path() -> Result<()> {
+    let mut db = MockDB::new();
+    expect_get_engine(&mut db, "123")?;
+    let server = get_test_server_with_path_normalization(
+        db,
+        PathNormalization {
+            lowercase_prefix: true,
+            mode: NormalizationMode::Redirect,
+        },
+    )
+    .await?;
+
+    let response = server.get("/engine/123").await;
+
+    response.assert_status_ok();
+    Ok(())
+}
@@ -0,0 +1,293 @@
+mod common;
+
+use ::std::collections::{BTreeMap, BTreeSet};
+
+use ::rucat_common::{
+    database::EngineFilter,
+    engine::{
+        BatchGetEnginesResponse, EngineId, EngineInfo, EngineState::*, EngineStateKind, EngineTime,
+        EngineType,
+    },
+    error::*,
+    serde_json::json,
+    tokio,
+};
+use ::rucat_server::{shutdown::ShutdownHandle, EnginePolicy, Sources};
+use axum_test::TestServer;
+use common::{get_error_code, get_error_message, get_test_app, get_test_server, MockDB};
+use http::header::HeaderName;
+
+const PROJECT: HeaderName = HeaderName::from_static("x-rucat-project");
+
+/// Test server isolating the engines in the projects `team-a` and `team-b`.
+fn get_project_server(db: MockDB) -> Result<TestServer> {
+    let app = get_test_app(
+        db,
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources::default(),
+        EnginePolicy {
+            projects: Some(BTreeSet::from(["team-a".to_owned(), "team-b".to_owned()])),
+            ..EnginePolicy::default()
+        },
+    )?;
+    TestServer::new(app).map_err(RucatError::fail_to_start_server)
+}
+
+fn get_engine_in_project(project: &str) -> EngineInfo {
+    let mut info = EngineInfo::new(
+        "engine1".to_owned(),
+        EngineType::Spark,
+        "3.5.3".to_owned(),
+        Running,
+        BTreeMap::new(),
+        EngineTime::now(),
+    );
+    info.project = Some(project.to_owned());
+    info
+}
+
+#[tokio::test]
+async fn projects_must_be_valid_namespaces() {
+    let result = get_test_app(
+        MockDB::new(),
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources::default(),
+        EnginePolicy {
+            projects: Some(BTreeSet::from(["Team_A".to_owned()])),
+            ..EnginePolicy::default()
+        },
+    );
+    assert!(result.is_err_and(|e| e
+        .to_string()
+        .starts_with("Fail to load config: Invalid project `Team_A`")));
+}
+
+#[tokio::test]
+async fn project_is_required() -> Result<()> {
+    let server = get_project_server(MockDB::new())?;
+
+    let response = server.get("/engine").await;
+
+    response.assert_status_bad_request();
+    assert_eq!(get_error_code(&response), "INVALID_REQUEST");
+    assert_eq!(
+        get_error_message(&response),
+        "`x-rucat-project` is required, as the server is configured with projects"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_project() -> Result<()> {
+    let server = get_project_server(MockDB::new())?;
+
+    let response = server.get("/engine").add_header(PROJECT, "team-c").await;
+
+    response.assert_status_not_found();
+    assert_eq!(
+        get_error_message(&response),
+        "Project `team-c` is not found"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn project_without_configured_projects() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server.get("/engine").add_header(PROJECT, "team-a").await;
+
+    response.assert_status_bad_request();
+    assert_eq!(
+        get_error_message(&response),
+        "`x-rucat-project` is set, but the server is not configured with projects"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_engine_in_project() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_add_engine()
+        .withf(|_, info, _, idempotency_key| {
+            info.project.as_deref() == Some("team-a")
+                && idempotency_key
+                    .as_ref()
+                    .is_some_and(|key| key.key == "team-a/key-1")
+        })
+        .times(1)
+        .returning(|id, _, _, _| Ok(id.clone()));
+    let server = get_project_server(db)?;
+
+    let response = server
+        .post("/engine")
+        .add_header(PROJECT, "team-a")
+        .add_header(HeaderName::from_static("idempotency-key"), "key-1")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3"
+        }))
+        .await;
+
+    response.assert_status_success();
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_engine_in_same_project() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine()
+        .times(1)
+        .returning(|_| Ok(Some(get_engine_in_project("team-a"))));
+    let server = get_project_server(db)?;
+
+    let response = server
+        .get("/engine/123")
+        .add_header(PROJECT, "team-a")
+        .await;
+
+    response.assert_status_ok();
+    assert_eq!(
+        response.json::<EngineInfo>().project.as_deref(),
+        Some("team-a")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_of_other_project_is_not_found() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine()
+        .returning(|_| Ok(Some(get_engine_in_project("team-b"))));
+    db.expect_update_engine_state().never();
+    db.expect_remove_engine().never();
+    let server = get_project_server(db)?;
+
+    for response in [
+        server
+            .get("/engine/123")
+            .add_header(PROJECT, "team-a")
+            .await,
+        server
+            .get("/engine/123/state")
+            .add_header(PROJECT, "team-a")
+            .await,
+        server
+            .post("/engine/123/stop")
+            .add_header(PROJECT, "team-a")
+            .await,
+        server
+            .delete("/engine/123")
+            .add_header(PROJECT, "team-a")
+            .await,
+    ] {
+        response.assert_status_not_found();
+        assert_eq!(get_error_message(&response), "Engine 123 not found");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn batch_get_engines_in_project() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engines().times(1).returning(|ids| {
+        Ok(ids
+            .iter()
+            .zip(["team-a", "team-b"])
+            .map(|(id, project)| (id.clone(), Some(get_engine_in_project(project))))
+            .collect())
+    });
+    let server = get_project_server(db)?;
+
+    let response = server
+        .post("/engine/batch-get")
+        .add_header(PROJECT, "team-a")
+        .json(&json!({"ids": [{"id": "1"}, {"id": "2"}]}))
+        .await;
+
+    response.assert_status_ok();
+    let engines = response.json::<BatchGetEnginesResponse>().engines;
+    assert_eq!(
+        engines
+            .iter()
+            .map(|engine| (engine.id.as_str(), engine.info.is_some()))
+            .collect::<Vec<_>>(),
+        [("1", true), ("2", false)]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_engines_in_project() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_list_engines()
+        .withf(|_, _, filter, _| {
+            *filter
+                == EngineFilter {
+                    project: Some("team-a".to_owned()),
+                    ..EngineFilter::default()
+                }
+        })
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![EngineId::try_from("1")?]));
+    let server = get_project_server(db)?;
+
+    let response = server.get("/engine").add_header(PROJECT, "team-a").await;
+
+    response.assert_status_ok();
+    response.assert_json(&json!({"engines": [{"id": "1"}], "next_offset": null}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_engine_stats_in_project() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_count_engines_by_state()
+        .withf(|_, project| *project == Some("team-b"))
+        .times(1)
+        .returning(|_, _| Ok(BTreeMap::from([(EngineStateKind::Running, 1)])));
+    let server = get_project_server(db)?;
+
+    let response = server
+        .get("/engine/stats")
+        .add_header(PROJECT, "team-b")
+        .await;
+
+    response.assert_status_ok();
+    assert_eq!(
+        response.json::<::rucat_common::serde_json::Value>()["total"],
+        1
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn cleanup_engines_in_project() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_remove_engines_in_states()
+        .withf(|_, _, project, _| *project == Some("team-a"))
+        .times(1)
+        .returning(|_, _, _, _| Ok(vec![]));
+    // only the history of the engines in the project is purged
+    db.expect_purge_engine_events()
+        .withf(|_, project| *project == Some("team-a"))
+        .times(1)
+        .returning(|_, _| Ok(0));
+    let server = get_project_server(db)?;
+
+    let response = server
+        .post("/engine/cleanup")
+        .add_header(PROJECT, "team-a")
+        .json(&json!({"states": ["Terminated"], "older_than_secs": 0}))
+        .await;
+
+    response.assert_status_ok();
+    Ok(())
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use ::rucat_common::{
+    error::{Result, RucatError},
+    tokio,
+};
+use ::rucat_server::{
+    authentication::static_auth_provider::StaticAuthProvider, rate_limit::RateLimitConfig,
+    shutdown::ShutdownHandle, EnginePolicy, Sources,
+};
+use axum_extra::headers::authorization::Credentials as _;
+use axum_test::TestServer;
+use common::{get_error_code, get_error_message, get_test_app, get_test_server, MockDB};
+use headers::Authorization;
+use http::header::{AUTHORIZATION, RETRY_AFTER};
+
+fn get_rate_limited_server(requests_per_second: f64, burst: u32) -> Result<TestServer> {
+    let auth_provider =
+        StaticAuthProvider::new("admin".to_owned(), "admin".to_owned(), "admin".to_owned());
+    let app = get_test_app(
+        MockDB::new(),
+        Some(auth_provider),
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources::default(),
+        EnginePolicy {
+            rate_limit: Some(RateLimitConfig {
+                requests_per_second,
+                burst,
+            }),
+            ..EnginePolicy::default()
+        },
+    )?;
+    TestServer::new(app).map_err(RucatError::fail_to_start_server)
+}
+
+#[tokio::test]
+async fn reject_requests_exceeding_burst() -> Result<()> {
+    let server = get_rate_limited_server(0.1, 2)?;
+    for _ in 0..2 {
+        server
+            .get("/capabilities")
+            .add_header(
+                AUTHORIZATION,
+                Authorization::basic("admin", "admin").0.encode(),
+            )
+            .await
+            .assert_status_ok();
+    }
+
+    let response = server
+        .get("/capabilities")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("admin", "admin").0.encode(),
+        )
+        .await;
+    response.assert_status(http::StatusCode::TOO_MANY_REQUESTS);
+    let retry_after = response
+        .header(RETRY_AFTER)
+        .to_str()
+        .unwrap()
+        .parse::<u64>();
+    assert!(retry_after.is_ok_and(|secs| (1..=10).contains(&secs)));
+    assert_eq!(get_error_code(&response), "TOO_MANY_REQUESTS");
+    assert!(get_error_message(&response).starts_with("Rate limit exceeded, retry after"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn limit_each_client_separately() -> Result<()> {
+    let server = get_rate_limited_server(0.1, 1)?;
+    server
+        .get("/capabilities")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("admin", "admin").0.encode(),
+        )
+        .await
+        .assert_status_ok();
+    server
+        .get("/capabilities")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("admin", "admin").0.encode(),
+        )
+        .await
+        .assert_status(http::StatusCode::TOO_MANY_REQUESTS);
+
+    // the bearer token is another client
+    server
+        .get("/capabilities")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::bearer("admin").unwrap().0.encode(),
+        )
+        .await
+        .assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn probes_are_not_limited() -> Result<()> {
+    let server = get_rate_limited_server(0.1, 1)?;
+    for _ in 0..3 {
+        server.get("/healthz").await.assert_status_ok();
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn unauthenticated_requests_are_not_counted() -> Result<()> {
+    let server = get_rate_limited_server(0.1, 1)?;
+    for _ in 0..3 {
+        server
+            .get("/capabilities")
+            .await
+            .assert_status_unauthorized();
+    }
+    server
+        .get("/capabilities")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic("admin", "admin").0.encode(),
+        )
+        .await
+        .assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_rate_limit() {
+    let result = get_rate_limited_server(0.0, 1);
+    assert!(result.is_err_and(|e| e
+        .to_string()
+        .starts_with("Fail to load config: Rate limit needs a positive")));
+}
+
+#[tokio::test]
+async fn not_limited_by_default() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+    for _ in 0..30 {
+        server.get("/capabilities").await.assert_status_ok();
+    }
+    Ok(())
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use ::std::sync::{Arc, Mutex};
+
+use ::rucat_common::{
+    error::{Result, RucatError},
+    serde_json::{from_value, json, Value},
+    tokio,
+};
+use ::rucat_server::{
+    authentication::{
+        registry::AuthConfigSource,
+        static_auth_provider::{StaticAuthConfig, StaticAuthProvider},
+    },
+    shutdown::ShutdownHandle,
+    AuthProviderVariant, EnginePolicy, Sources,
+};
+use axum_extra::headers::authorization::Credentials as _;
+use axum_test::{TestResponse, TestServer};
+use common::{get_error_code, get_error_message, get_test_app, get_test_server, MockDB};
+use headers::Authorization;
+use http::{header::AUTHORIZATION, StatusCode};
+
+/// Auth provider config which is changed by the tests, as if the config file is edited.
+struct TestAuthConfig(Mutex<Value>);
+
+impl AuthConfigSource for TestAuthConfig {
+    fn load(&self) -> Result<Option<AuthProviderVariant>> {
+        from_value(self.0.lock().unwrap().clone()).map_err(RucatError::fail_to_load_config)
+    }
+}
+
+/// Static auth config of the admin `ops` and the viewer `bob`, whose bearer tokens are `{token}-ops` and `{token}-bob`.
+fn get_static_config(token: &str) -> Value {
+    json!({
+        "users": [],
+        "bearer_tokens": [
+            {"name": "ops", "token": format!("{}-ops", token)},
+            {"name": "bob", "token": format!("{}-bob", token), "role": "viewer"}
+        ]
+    })
+}
+
+/// Server authenticated by the static config of the `old` tokens, reloaded from the returned config.
+fn get_server() -> Result<(TestServer, Arc<TestAuthConfig>)> {
+    let config = Arc::new(TestAuthConfig(Mutex::new(
+        json!({"StaticAuthProviderConfig": get_static_config("old")}),
+    )));
+    let auth_provider = StaticAuthProvider::from_config(
+        from_value::<StaticAuthConfig>(get_static_config("old")).unwrap(),
+    )?;
+    let app = get_test_app(
+        MockDB::new(),
+        Some(auth_provider),
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources {
+            auth_config: Some(config.clone()),
+            ..Sources::default()
+        },
+        EnginePolicy::default(),
+    )?;
+    let server = TestServer::new(app).map_err(RucatError::fail_to_start_server)?;
+    Ok((server, config))
+}
+
+async fn get_capabilities(server: &TestServer, token: &str) -> StatusCode {
+    server
+        .get("/capabilities")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::bearer(token).unwrap().0.encode(),
+        )
+        .await
+        .status_code()
+}
+
+async fn reload_auth(server: &TestServer, token: &str) -> TestResponse {
+    server
+        .post("/admin/reload-auth")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::bearer(token).unwrap().0.encode(),
+        )
+        .await
+}
+
+#[tokio::test]
+async fn reload_rotated_tokens() -> Result<()> {
+    let (server, config) = get_server()?;
+    assert_eq!(get_capabilities(&server, "old-bob").await, StatusCode::OK);
+
+    *config.0.lock().unwrap() = json!({"StaticAuthProviderConfig": get_static_config("new")});
+    // not used until the provider is reloaded
+    assert_eq!(
+        get_capabilities(&server, "new-bob").await,
+        StatusCode::UNAUTHORIZED
+    );
+    reload_auth(&server, "old-ops").await.assert_status_ok();
+
+    assert_eq!(
+        get_capabilities(&server, "old-bob").await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(get_capabilities(&server, "new-bob").await, StatusCode::OK);
+    reload_auth(&server, "new-ops").await.assert_status_ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn keep_current_provider_if_reload_fails() -> Result<()> {
+    let (server, config) = get_server()?;
+    for (new_config, message) in [
+        (
+            json!({"StaticAuthProviderConfig": {"users": [], "bearer_tokens": []}}),
+            "The auth provider is not reloaded, the current one is kept: Static authentication needs at least one user or bearer token",
+        ),
+        (
+            json!({"Unknown": {}}),
+            "The auth provider is not reloaded, the current one is kept: unknown variant `Unknown`",
+        ),
+        (
+            Value::Null,
+            "The auth provider is not reloaded, the authentication cannot be enabled or disabled without restarting the server",
+        ),
+    ] {
+        *config.0.lock().unwrap() = new_config;
+        let response = reload_auth(&server, "old-ops").await;
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(
+            get_error_message(&response).starts_with(message),
+            "{}",
+            get_error_message(&response)
+        );
+        assert_eq!(get_capabilities(&server, "old-bob").await, StatusCode::OK);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn only_admins_reload_auth() -> Result<()> {
+    let (server, config) = get_server()?;
+    *config.0.lock().unwrap() = json!({"StaticAuthProviderConfig": get_static_config("new")});
+    let response = reload_auth(&server, "old-bob").await;
+    response.assert_status_forbidden();
+    assert_eq!(get_error_code(&response), "NOT_ALLOWED");
+    assert_eq!(
+        get_error_message(&response),
+        "`bob` is not allowed to POST /admin/reload-auth, which needs the `admin` role"
+    );
+    assert_eq!(get_capabilities(&server, "old-bob").await, StatusCode::OK);
+
+    reload_auth(&server, "wrong")
+        .await
+        .assert_status_unauthorized();
+    Ok(())
+}
+
+#[tokio::test]
+async fn reload_auth_without_config_source() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+    let response = server.post("/admin/reload-auth").await;
+    response.assert_status_not_found();
+    assert_eq!(
+        get_error_message(&response),
+        "The auth provider cannot be reloaded, as the server has no source of the auth config"
+    );
+    Ok(())
+}
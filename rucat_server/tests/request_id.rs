@@ -0,0 +1,63 @@
+mod common;
+
+use ::rucat_common::{error::Result, tokio};
+use ::rucat_server::authentication::static_auth_provider::StaticAuthProvider;
+use common::{get_error_code, get_error_message, get_test_server, MockDB};
+
+const X_REQUEST_ID: &str = "x-request-id";
+
+#[tokio::test]
+async fn echo_request_id() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine().times(1).returning(|_| Ok(None));
+    let server = get_test_server(db, None).await?;
+
+    let response = server
+        .get("/engine/abc")
+        .add_header(X_REQUEST_ID, "my-request-1")
+        .await;
+    response.assert_status_not_found();
+    assert_eq!(response.header(X_REQUEST_ID), "my-request-1");
+    Ok(())
+}
+
+#[tokio::test]
+async fn generate_request_id_if_absent() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let first = server.get("/healthz").await;
+    first.assert_status_ok();
+    let first = first.header(X_REQUEST_ID);
+    let first = first.to_str().unwrap();
+    // UUID in the hyphenated form
+    assert_eq!(first.len(), 36);
+    assert_eq!(first.matches('-').count(), 4);
+
+    let second = server.get("/healthz").await.header(X_REQUEST_ID);
+    assert_ne!(first, second.to_str().unwrap());
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_id_of_rejected_requests() -> Result<()> {
+    let auth_provider =
+        StaticAuthProvider::new("admin".to_owned(), "admin".to_owned(), "admin".to_owned());
+    let server = get_test_server(MockDB::new(), Some(auth_provider)).await?;
+
+    let response = server
+        .get("/engine")
+        .add_header(X_REQUEST_ID, "my-request-2")
+        .await;
+    response.assert_status_unauthorized();
+    assert_eq!(get_error_code(&response), "UNAUTHORIZED");
+    assert_eq!(
+        get_error_message(&response),
+        "Not found authorization header"
+    );
+    assert_eq!(response.header(X_REQUEST_ID), "my-request-2");
+
+    let response = server.get("/unknown").await;
+    response.assert_status_unauthorized();
+    assert_eq!(response.header(X_REQUEST_ID).len(), 36);
+    Ok(())
+}
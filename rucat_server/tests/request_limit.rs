@@ -0,0 +1,110 @@
+mod common;
+
+use ::std::time::Duration;
+
+use ::rucat_common::{
+    engine::EngineState::TerminateInProgress,
+    error::{Result, RucatError},
+    serde_json::{from_value, json},
+    tokio,
+};
+use ::rucat_server::{
+    request_limit::RequestLimits, shutdown::ShutdownHandle, EngineEvents, EnginePolicy, Sources,
+};
+use axum_test::TestServer;
+use common::{get_error_code, get_error_message, get_test_app, get_test_server, MockDB};
+use http::StatusCode;
+
+fn get_limited_server(db: MockDB, max_body_bytes: usize, timeout_secs: u64) -> Result<TestServer> {
+    let app = get_test_app(
+        db,
+        None,
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources::default(),
+        EnginePolicy {
+            events: from_value::<EngineEvents>(json!({"poll_interval_millis": 1}))
+                .map_err(RucatError::fail_to_load_config)?,
+            request_limits: RequestLimits {
+                max_body_bytes,
+                timeout: Duration::from_secs(timeout_secs),
+            },
+            ..EnginePolicy::default()
+        },
+    )?;
+    TestServer::new(app).map_err(RucatError::fail_to_start_server)
+}
+
+#[tokio::test]
+async fn reject_body_larger_than_limit() -> Result<()> {
+    let server = get_limited_server(MockDB::new(), 100, 60)?;
+
+    let response = server
+        .post("/engine")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3",
+            "config": {"spark.executor.instances": "1".repeat(100)}
+        }))
+        .await;
+
+    response.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(get_error_code(&response), "PAYLOAD_TOO_LARGE");
+    assert_eq!(
+        get_error_message(&response),
+        "Failed to buffer the request body: length limit exceeded"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn reject_body_larger_than_default_limit() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server
+        .post("/engine")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3",
+            "config": {"spark.executor.instances": "1".repeat(1024 * 1024)}
+        }))
+        .await;
+
+    response.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(get_error_code(&response), "PAYLOAD_TOO_LARGE");
+    Ok(())
+}
+
+#[tokio::test]
+async fn accept_body_within_limit() -> Result<()> {
+    let server = get_limited_server(MockDB::new(), 1000, 60)?;
+
+    // rejected by the handler rather than the limit
+    let response = server.post("/engine").json(&json!({"name": "test"})).await;
+
+    response.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(get_error_code(&response), "INVALID_REQUEST");
+    Ok(())
+}
+
+#[tokio::test]
+async fn reject_request_timeout() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_get_engine_state()
+        .returning(|_| Ok(Some(TerminateInProgress)));
+    let server = get_limited_server(db, 1000, 1)?;
+
+    // waiting longer than the request timeout
+    let response = server.delete("/engine/123?wait=true&timeout_secs=10").await;
+
+    response.assert_status(StatusCode::REQUEST_TIMEOUT);
+    assert_eq!(get_error_code(&response), "REQUEST_TIMEOUT");
+    assert_eq!(
+        get_error_message(&response),
+        "Request is not served in 1 seconds"
+    );
+    Ok(())
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use ::std::{borrow::Cow, collections::BTreeMap, time::Duration};
+
+use ::mockall::predicate;
+use ::rucat_common::{
+    anyhow::anyhow,
+    database::DatabaseHealth,
+    engine::{EngineId, EngineInfo, EngineState::*, EngineTime, EngineType},
+    error::*,
+    serde_json::json,
+    tokio,
+};
+use ::rucat_server::shutdown::ShutdownHandle;
+use common::{
+    get_error_code, get_error_message, get_test_server, get_test_server_with_options, MockDB,
+};
+use http::{header::RETRY_AFTER, StatusCode};
+
+#[tokio::test]
+async fn ready_before_shutdown() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_health_check().times(1).returning(|| {
+        Ok(DatabaseHealth {
+            latency: Duration::ZERO,
+            version: None,
+        })
+    });
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/readyz").await;
+
+    response.assert_status_ok();
+    response.assert_text("ready");
+    Ok(())
+}
+
+#[tokio::test]
+async fn not_ready_after_shutdown() -> Result<()> {
+    // the database is not checked once the server is shutting down
+    let db = MockDB::new();
+    let shutdown_handle = ShutdownHandle::new();
+    let server =
+        get_test_server_with_options(db, None, None, None, shutdown_handle.clone()).await?;
+
+    shutdown_handle.trigger();
+    let response = server.get("/readyz").await;
+
+    response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    Ok(())
+}
+
+#[tokio::test]
+async fn not_ready_if_database_is_not_reachable() -> Result<()> {
+    let mut db = MockDB::new();
+    db.expect_health_check().times(1).returning(|| {
+        Err(RucatError::fail_to_connect_database(anyhow!(
+            "connection refused"
+        )))
+    });
+    let server = get_test_server(db, None).await?;
+
+    let response = server.get("/readyz").await;
+
+    response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(get_error_code(&response), "SERVICE_UNAVAILABLE");
+    assert!(get_error_message(&response)
+        .starts_with("Database is not reachable: Fail to connect to database: connection refused"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn alive_even_if_not_ready() -> Result<()> {
+    // liveness does not depend on the database
+    let db = MockDB::new();
+    let shutdown_handle = ShutdownHandle::new();
+    let server =
+        get_test_server_with_options(db, None, None, None, shutdown_handle.clone()).await?;
+
+    shutdown_handle.trigger();
+    let response = server.get("/healthz").await;
+
+    response.assert_status_ok();
+    response.assert_text("ok");
+    Ok(())
+}
+
+#[tokio::test]
+async fn reject_create_engine_after_shutdown() -> Result<()> {
+    // `add_engine` is not expected to be called
+    let db = MockDB::new();
+    let shutdown_handle = ShutdownHandle::new();
+    let server =
+        get_test_server_with_options(db, None, None, None, shutdown_handle.clone()).await?;
+
+    shutdown_handle.trigger();
+    let response = server
+        .post("/engine")
+        .json(&json!({
+            "name": "test",
+            "engine_type": "Spark",
+            "version": "3.5.3",
+        }))
+        .await;
+
+    response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.header(RETRY_AFTER), "5");
+    assert_eq!(get_error_code(&response), "SERVICE_UNAVAILABLE");
+    assert!(get_error_message(&response)
+        .starts_with("Server is shutting down, POST /engine is rejected"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn reject_stop_engine_after_shutdown() -> Result<()> {
+    let db = MockDB::new();
+    let shutdown_handle = ShutdownHandle::new();
+    let server =
+        get_test_server_with_options(db, None, None, None, shutdown_handle.clone()).await?;
+
+    shutdown_handle.trigger();
+    let response = server.post("/engine/123/stop").await;
+
+    response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.header(RETRY_AFTER), "5");
+    Ok(())
+}
+
+#[tokio::test]
+async fn in_flight_get_completes_when_shutdown_is_triggered() -> Result<()> {
+    let shutdown_handle = ShutdownHandle::new();
+    let shutdown_handle_cloned = shutdown_handle.clone();
+    let mut db = MockDB::new();
+    db.expect_get_engine()
+        .with(predicate::eq(EngineId::new(Cow::Borrowed("123"))?))
+        .times(1)
+        .returning(move |_| {
+            // the shutdown signal fires while the request is being handled
+            shutdown_handle_cloned.trigger();
+            Ok(Some(EngineInfo::new(
+                "engine1".to_owned(),
+                EngineType::Spark,
+                "3.5.3".to_owned(),
+                Running,
+                BTreeMap::new(),
+                EngineTime::now(),
+            )))
+        });
+    let server =
+        get_test_server_with_options(db, None, None, None, shutdown_handle.clone()).await?;
+
+    let response = server.get("/engine/123").await;
+    response.assert_status_ok();
+    assert!(shutdown_handle.is_shutting_down());
+
+    let response = server.delete("/engine/123").await;
+    response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    Ok(())
+}
@@ -0,0 +1,148 @@
+#![cfg(feature = "ui")]
+
+mod common;
+
+use ::rucat_common::{
+    error::{Result, RucatError},
+    tokio,
+};
+use ::rucat_server::{
+    authentication::{registry::DynAuthProvider, static_auth_provider::StaticAuthProvider},
+    shutdown::ShutdownHandle,
+    ui::{serve_ui, UiConfig},
+    EnginePolicy, Sources,
+};
+use axum_extra::headers::authorization::Credentials as _;
+use axum_test::{TestResponse, TestServer};
+use common::{get_error_code, get_error_message, get_test_app, get_test_server, MockDB};
+use headers::Authorization;
+use http::header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE};
+
+static USERNAME: &str = "admin";
+static PWD: &str = "admin";
+static TOKEN: &str = "admin"; // Bearer token
+
+fn get_auth_provider() -> StaticAuthProvider {
+    StaticAuthProvider::new(USERNAME.to_owned(), PWD.to_owned(), TOKEN.to_owned())
+}
+
+fn get_ui_server(auth_provider: Option<StaticAuthProvider>, ui: UiConfig) -> Result<TestServer> {
+    let server = get_test_app(
+        MockDB::new(),
+        auth_provider.clone(),
+        None,
+        None,
+        ShutdownHandle::new(),
+        Sources::default(),
+        EnginePolicy::default(),
+    )?;
+    let app = serve_ui(server, ui, auth_provider.map(DynAuthProvider::new))?;
+    TestServer::new(app).map_err(RucatError::fail_to_start_server)
+}
+
+fn assert_headers(response: &TestResponse, content_type: &str, cache_control: &str) {
+    assert_eq!(response.header(CONTENT_TYPE), content_type);
+    assert_eq!(response.header(CACHE_CONTROL), cache_control);
+}
+
+#[tokio::test]
+async fn ui_is_not_served_by_default() -> Result<()> {
+    let server = get_test_server(MockDB::new(), None).await?;
+
+    let response = server.get("/ui").await;
+
+    response.assert_status_not_found();
+    Ok(())
+}
+
+#[tokio::test]
+async fn serve_index() -> Result<()> {
+    let server = get_ui_server(None, UiConfig::default())?;
+
+    for path in ["/ui", "/ui/", "/ui/index.html"] {
+        let response = server.get(path).await;
+
+        response.assert_status_ok();
+        assert_headers(&response, "text/html; charset=utf-8", "public, no-cache");
+        assert!(response.text().contains("<title>Rucat</title>"));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn serve_assets() -> Result<()> {
+    let server = get_ui_server(None, UiConfig::default())?;
+
+    let response = server.get("/ui/app.js").await;
+    response.assert_status_ok();
+    assert_headers(
+        &response,
+        "text/javascript; charset=utf-8",
+        "public, max-age=3600",
+    );
+
+    let response = server.get("/ui/style.css").await;
+    response.assert_status_ok();
+    assert_headers(&response, "text/css; charset=utf-8", "public, max-age=3600");
+    Ok(())
+}
+
+#[tokio::test]
+async fn asset_not_found() -> Result<()> {
+    let server = get_ui_server(None, UiConfig::default())?;
+
+    for path in [
+        "/ui/unknown.js",
+        "/ui/../Cargo.toml",
+        "/ui/%2E%2E/Cargo.toml",
+    ] {
+        let response = server.get(path).await;
+
+        response.assert_status_not_found();
+    }
+    let response = server.get("/ui/unknown.js").await;
+    assert_eq!(get_error_code(&response), "NOT_FOUND");
+    assert_eq!(
+        get_error_message(&response),
+        "UI asset unknown.js not found"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn ui_is_excluded_from_auth_by_default() -> Result<()> {
+    let server = get_ui_server(Some(get_auth_provider()), UiConfig::default())?;
+
+    let response = server.get("/ui").await;
+    response.assert_status_ok();
+    let response = server.get("/ui/app.js").await;
+    response.assert_status_ok();
+
+    // the API called by the UI is still authenticated
+    let response = server.get("/engine").await;
+    response.assert_status_unauthorized();
+    Ok(())
+}
+
+#[tokio::test]
+async fn ui_requires_auth_if_configured() -> Result<()> {
+    let server = get_ui_server(Some(get_auth_provider()), UiConfig { require_auth: true })?;
+
+    let response = server.get("/ui").await;
+    response.assert_status_unauthorized();
+
+    let response = server
+        .get("/ui/app.js")
+        .add_header(
+            AUTHORIZATION,
+            Authorization::basic(USERNAME, PWD).0.encode(),
+        )
+        .await;
+    response.assert_status_ok();
+    assert_headers(
+        &response,
+        "text/javascript; charset=utf-8",
+        "private, max-age=3600",
+    );
+    Ok(())
+}
@@ -0,0 +1,275 @@
+"use strict";
+
+// Minimal engine management UI calling the JSON API of rucat server
+// with the credentials entered in the browser.
+
+const PAGE_SIZE = 50;
+const CREDENTIALS_KEY = "rucat-credentials";
+
+// Lanes of the engine state machine, each engine goes through the states of a lane in order.
+const STATE_LANES = [
+  ["Start", ["WaitToStart", "TriggerStart", "StartInProgress", "Running"]],
+  ["Stop", ["WaitToTerminate", "TriggerTermination", "TerminateInProgress", "Terminated"]],
+  ["Error", ["ErrorWaitToClean", "ErrorTriggerClean", "ErrorCleanInProgress", "ErrorClean"]],
+];
+
+const element = (id) => document.getElementById(id);
+
+let nextOffset = 0;
+
+// Credentials are kept for the browser tab only.
+function loadCredentials() {
+  return JSON.parse(sessionStorage.getItem(CREDENTIALS_KEY) || '{"type": "none"}');
+}
+
+function authorizationHeader() {
+  const credentials = loadCredentials();
+  switch (credentials.type) {
+    case "basic":
+      return "Basic " + btoa(credentials.username + ":" + credentials.password);
+    case "bearer":
+      return "Bearer " + credentials.token;
+    default:
+      return null;
+  }
+}
+
+async function callApi(method, path) {
+  const headers = {};
+  const authorization = authorizationHeader();
+  if (authorization) {
+    headers["Authorization"] = authorization;
+  }
+  const response = await fetch(path, { method, headers });
+  if (!response.ok) {
+    const body = await response.json().catch(() => null);
+    throw new Error(body && body.message ? body.code + ": " + body.message : response.status + " " + response.statusText);
+  }
+  const text = await response.text();
+  return text ? JSON.parse(text) : null;
+}
+
+function showError(error) {
+  const message = element("error");
+  message.textContent = error ? error.message : "";
+  message.hidden = !error;
+}
+
+// Error states are serialized as `{"<state>": "<message>"}`.
+function stateName(state) {
+  return typeof state === "string" ? state : Object.keys(state)[0];
+}
+
+function stateMessage(state) {
+  return typeof state === "string" ? null : Object.values(state)[0];
+}
+
+// Engine times are formatted as `2025-01-31 08:00:00 +00:00:00`.
+function parseEngineTime(time) {
+  const match = /^(\S+) (\S+) ([+-]\d\d:\d\d):\d\d$/.exec(time);
+  return match ? new Date(match[1] + "T" + match[2] + match[3]) : null;
+}
+
+function formatAge(time) {
+  const created = parseEngineTime(time);
+  if (!created) {
+    return "unknown";
+  }
+  const seconds = Math.max(0, Math.floor((Date.now() - created.getTime()) / 1000));
+  const units = [["d", 86400], ["h", 3600], ["m", 60]];
+  for (const [unit, size] of units) {
+    if (seconds >= size) {
+      return Math.floor(seconds / size) + unit;
+    }
+  }
+  return seconds + "s";
+}
+
+function cell(row, content) {
+  const td = row.insertCell();
+  if (content instanceof Node) {
+    td.appendChild(content);
+  } else {
+    td.textContent = content;
+  }
+  return td;
+}
+
+function actionButton(label, action) {
+  const button = document.createElement("button");
+  button.type = "button";
+  button.textContent = label;
+  button.addEventListener("click", async () => {
+    try {
+      await action();
+      showError(null);
+      await loadEngines(true);
+    } catch (error) {
+      showError(error);
+    }
+  });
+  return button;
+}
+
+function engineRow(summary, info) {
+  const id = summary.id.id;
+  const row = document.createElement("tr");
+  const name = document.createElement("a");
+  name.textContent = summary.name;
+  name.addEventListener("click", () => showDetail(id));
+  cell(row, name);
+  cell(row, summary.engine_type);
+  cell(row, info ? info.version : "");
+  const state = cell(row, stateName(summary.state));
+  if (stateMessage(summary.state)) {
+    state.classList.add("state-error");
+    state.title = stateMessage(summary.state);
+  }
+  cell(row, formatAge(summary.create_time));
+  const actions = cell(row, "");
+  const path = "/engine/" + encodeURIComponent(id);
+  actions.appendChild(actionButton("Stop", () => callApi("POST", path + "/stop")));
+  actions.appendChild(actionButton("Restart", () => callApi("POST", path + "/restart")));
+  actions.appendChild(
+    actionButton("Delete", async () => {
+      if (confirm("Delete engine " + summary.name + " (" + id + ")?")) {
+        await callApi("DELETE", path);
+      }
+    })
+  );
+  return row;
+}
+
+// Load the first page of engines if `reset`, otherwise the next page.
+async function loadEngines(reset) {
+  if (reset) {
+    nextOffset = 0;
+    element("engines").replaceChildren();
+  }
+  const query = new URLSearchParams({
+    detail: "summary",
+    limit: PAGE_SIZE,
+    offset: nextOffset,
+    name_contains: element("name-contains").value,
+  });
+  const page = await callApi("GET", "/engine?" + query);
+  // The summary has no version, get it from the engine info.
+  const infos = await Promise.all(
+    page.engines.map((summary) =>
+      callApi("GET", "/engine/" + encodeURIComponent(summary.id.id)).catch(() => null)
+    )
+  );
+  page.engines.forEach((summary, i) => element("engines").appendChild(engineRow(summary, infos[i])));
+  nextOffset = page.next_offset;
+  element("more").hidden = page.next_offset === null;
+}
+
+function renderStateMachine(current) {
+  const list = element("state-machine");
+  list.replaceChildren();
+  for (const [lane, states] of STATE_LANES) {
+    const item = document.createElement("li");
+    item.append(lane + ": ");
+    states.forEach((state, i) => {
+      const span = document.createElement("span");
+      span.textContent = state;
+      if (state === current) {
+        item.classList.add("current");
+        span.style.textDecoration = "underline";
+      }
+      item.append(span);
+      if (i < states.length - 1) {
+        item.append(" \u2192 ");
+      }
+    });
+    list.appendChild(item);
+  }
+}
+
+async function showDetail(id) {
+  try {
+    const info = await callApi("GET", "/engine/" + encodeURIComponent(id));
+    element("detail-name").textContent = info.name;
+    const fields = element("detail-fields");
+    fields.replaceChildren();
+    const state = stateName(info.state);
+    const entries = [
+      ["Id", id],
+      ["Type", info.engine_type],
+      ["Version", info.version],
+      ["State", stateMessage(info.state) ? state + ": " + stateMessage(info.state) : state],
+      ["Created", info.create_time],
+      ["Features", (info.features || []).join(", ") || "none"],
+    ];
+    for (const [key, value] of entries) {
+      const dt = document.createElement("dt");
+      dt.textContent = key;
+      const dd = document.createElement("dd");
+      dd.textContent = value;
+      fields.append(dt, dd);
+    }
+    renderStateMachine(state);
+    const config = element("detail-config");
+    config.replaceChildren();
+    for (const [key, value] of Object.entries(info.config)) {
+      const row = config.insertRow();
+      cell(row, key);
+      cell(row, value);
+    }
+    element("list-view").hidden = true;
+    element("detail-view").hidden = false;
+    showError(null);
+  } catch (error) {
+    showError(error);
+  }
+}
+
+function showCredentialInputs() {
+  const type = element("auth-type").value;
+  element("username").hidden = type !== "basic";
+  element("password").hidden = type !== "basic";
+  element("token").hidden = type !== "bearer";
+}
+
+async function refresh() {
+  try {
+    await loadEngines(true);
+    showError(null);
+  } catch (error) {
+    showError(error);
+  }
+}
+
+document.addEventListener("DOMContentLoaded", () => {
+  const credentials = loadCredentials();
+  element("auth-type").value = credentials.type;
+  element("username").value = credentials.username || "";
+  element("token").value = credentials.token || "";
+  showCredentialInputs();
+
+  element("auth-type").addEventListener("change", showCredentialInputs);
+  element("credentials").addEventListener("submit", (event) => {
+    event.preventDefault();
+    sessionStorage.setItem(
+      CREDENTIALS_KEY,
+      JSON.stringify({
+        type: element("auth-type").value,
+        username: element("username").value,
+        password: element("password").value,
+        token: element("token").value,
+      })
+    );
+    refresh();
+  });
+  element("filter").addEventListener("submit", (event) => {
+    event.preventDefault();
+    refresh();
+  });
+  element("refresh").addEventListener("click", refresh);
+  element("more").addEventListener("click", () => loadEngines(false).catch(showError));
+  element("back").addEventListener("click", () => {
+    element("detail-view").hidden = true;
+    element("list-view").hidden = false;
+  });
+  refresh();
+});
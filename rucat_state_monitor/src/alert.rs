@@ -0,0 +1,280 @@
+//! Alerts on the number of engines in a state, e.g. too many engines failed to be cleaned,
+//! evaluated by the housekeeping loop of the state monitor.
+
+use ::core::time::Duration;
+use ::std::{collections::BTreeMap, time::SystemTime};
+
+use ::rucat_common::{
+    anyhow::anyhow,
+    engine::EngineStateKind,
+    error::{Result, RucatError},
+    serde::Deserialize,
+};
+
+/// Rule firing an alert when the number of engines in `state` stays above `threshold`.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct AlertRule {
+    /// Name of the rule, which is carried by the alert events.
+    pub name: String,
+    pub state: EngineStateKind,
+    /// The alert fires when the number of engines is above the threshold.
+    pub threshold: usize,
+    /// Time in second the number must stay above the threshold before the alert fires.
+    #[serde(default)]
+    pub sustain_secs: u32,
+    /// Minimum time in second between two firings of the alert, to prevent flapping.
+    #[serde(default)]
+    pub cooldown_secs: u32,
+}
+
+impl AlertRule {
+    /// Check that the rule names are unique, so that the alert events can be told apart.
+    pub fn validate(rules: &[Self]) -> Result<()> {
+        for (i, rule) in rules.iter().enumerate() {
+            if rule.name.is_empty() {
+                return Err(RucatError::fail_to_load_config(anyhow!(
+                    "Name of alert rules must not be empty"
+                )));
+            }
+            if rules[..i].iter().any(|other| other.name == rule.name) {
+                return Err(RucatError::fail_to_load_config(anyhow!(
+                    "Alert rule `{}` is defined more than once",
+                    rule.name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Change of the status of an alert.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlertEvent {
+    /// The number of engines has been above the threshold for the sustain duration.
+    Firing { rule: String, value: usize },
+    /// The number of engines of a firing alert is back to the threshold or below.
+    Resolved { rule: String, value: usize },
+}
+
+/// Status of an alert between two evaluations.
+#[derive(Debug, Default)]
+struct AlertStatus {
+    /// Time of the first evaluation above the threshold in the current sequence.
+    above_since: Option<SystemTime>,
+    firing: bool,
+    last_fired: Option<SystemTime>,
+}
+
+/// Evaluate the alert rules against the per-state engine counts of each housekeeping round.
+pub struct AlertEvaluator {
+    alerts: Vec<(AlertRule, AlertStatus)>,
+}
+
+impl AlertEvaluator {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self {
+            alerts: rules
+                .into_iter()
+                .map(|rule| (rule, AlertStatus::default()))
+                .collect(),
+        }
+    }
+
+    /// Whether there is no rule to evaluate.
+    pub fn is_empty(&self) -> bool {
+        self.alerts.is_empty()
+    }
+
+    /// Return the events of alerts that fire or resolve at `now`.
+    /// States missing in `counts` have no engine.
+    pub fn evaluate(
+        &mut self,
+        counts: &BTreeMap<EngineStateKind, usize>,
+        now: SystemTime,
+    ) -> Vec<AlertEvent> {
+        let elapsed_since = |time: SystemTime| now.duration_since(time).unwrap_or_default();
+        let mut events = vec![];
+        for (rule, status) in &mut self.alerts {
+            let value = counts.get(&rule.state).copied().unwrap_or_default();
+            if value > rule.threshold {
+                let above_since = *status.above_since.get_or_insert(now);
+                let sustained =
+                    elapsed_since(above_since) >= Duration::from_secs(rule.sustain_secs as u64);
+                let cooled_down = status.last_fired.is_none_or(|last_fired| {
+                    elapsed_since(last_fired) >= Duration::from_secs(rule.cooldown_secs as u64)
+                });
+                if !status.firing && sustained && cooled_down {
+                    status.firing = true;
+                    status.last_fired = Some(now);
+                    events.push(AlertEvent::Firing {
+                        rule: rule.name.clone(),
+                        value,
+                    });
+                }
+            } else {
+                status.above_since = None;
+                if status.firing {
+                    status.firing = false;
+                    events.push(AlertEvent::Resolved {
+                        rule: rule.name.clone(),
+                        value,
+                    });
+                }
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    fn rule(sustain_secs: u32, cooldown_secs: u32) -> AlertRule {
+        AlertRule {
+            name: "too many errors".to_owned(),
+            state: EngineStateKind::ErrorClean,
+            threshold: 20,
+            sustain_secs,
+            cooldown_secs,
+        }
+    }
+
+    /// Evaluate the counts of `ErrorClean` engines at the given seconds, and return the events of each evaluation.
+    fn evaluate(evaluator: &mut AlertEvaluator, sequence: &[(u64, usize)]) -> Vec<Vec<AlertEvent>> {
+        sequence
+            .iter()
+            .map(|&(secs, count)| {
+                let counts = BTreeMap::from([(EngineStateKind::ErrorClean, count)]);
+                evaluator.evaluate(&counts, UNIX_EPOCH + Duration::from_secs(secs))
+            })
+            .collect()
+    }
+
+    fn firing(value: usize) -> Vec<AlertEvent> {
+        vec![AlertEvent::Firing {
+            rule: "too many errors".to_owned(),
+            value,
+        }]
+    }
+
+    fn resolved(value: usize) -> Vec<AlertEvent> {
+        vec![AlertEvent::Resolved {
+            rule: "too many errors".to_owned(),
+            value,
+        }]
+    }
+
+    #[test]
+    fn fire_above_threshold_and_resolve() {
+        let mut evaluator = AlertEvaluator::new(vec![rule(0, 0)]);
+        let events = evaluate(
+            &mut evaluator,
+            &[(0, 20), (10, 21), (20, 25), (30, 20), (40, 0)],
+        );
+        assert_eq!(events, [vec![], firing(21), vec![], resolved(20), vec![]]);
+    }
+
+    #[test]
+    fn fire_after_sustained_above_threshold() {
+        let mut evaluator = AlertEvaluator::new(vec![rule(600, 0)]);
+        let events = evaluate(
+            &mut evaluator,
+            &[(0, 30), (300, 30), (599, 30), (600, 31), (900, 31)],
+        );
+        assert_eq!(events, [vec![], vec![], vec![], firing(31), vec![]]);
+    }
+
+    #[test]
+    fn sustain_restarts_when_below_threshold() {
+        let mut evaluator = AlertEvaluator::new(vec![rule(600, 0)]);
+        let events = evaluate(
+            &mut evaluator,
+            &[(0, 30), (500, 10), (600, 30), (1100, 30), (1200, 30)],
+        );
+        // below the threshold without firing resolves nothing
+        assert_eq!(events, [vec![], vec![], vec![], vec![], firing(30)]);
+    }
+
+    #[test]
+    fn no_refire_during_cooldown() {
+        let mut evaluator = AlertEvaluator::new(vec![rule(0, 300)]);
+        let events = evaluate(
+            &mut evaluator,
+            &[
+                (0, 30),
+                (60, 10),
+                (120, 30),
+                (240, 30),
+                (300, 30),
+                (360, 10),
+            ],
+        );
+        assert_eq!(
+            events,
+            [
+                firing(30),
+                resolved(10),
+                vec![],
+                vec![],
+                firing(30),
+                resolved(10)
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_state_has_no_engine() {
+        let mut rule = rule(0, 0);
+        rule.threshold = 0;
+        let mut evaluator = AlertEvaluator::new(vec![rule]);
+        let now = UNIX_EPOCH;
+        assert_eq!(evaluator.evaluate(&BTreeMap::new(), now), vec![]);
+        let counts = BTreeMap::from([(EngineStateKind::Running, 5)]);
+        assert_eq!(evaluator.evaluate(&counts, now), vec![]);
+    }
+
+    #[test]
+    fn rules_are_evaluated_independently() {
+        let running = AlertRule {
+            name: "too many running".to_owned(),
+            state: EngineStateKind::Running,
+            threshold: 1,
+            sustain_secs: 0,
+            cooldown_secs: 0,
+        };
+        let mut evaluator = AlertEvaluator::new(vec![rule(0, 0), running]);
+        let counts = BTreeMap::from([
+            (EngineStateKind::Running, 2),
+            (EngineStateKind::ErrorClean, 1),
+        ]);
+        assert_eq!(
+            evaluator.evaluate(&counts, UNIX_EPOCH),
+            vec![AlertEvent::Firing {
+                rule: "too many running".to_owned(),
+                value: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn validate_rule_names() {
+        assert!(AlertRule::validate(&[rule(0, 0)]).is_ok());
+        assert!(
+            AlertRule::validate(&[rule(0, 0), rule(1, 1)]).is_err_and(|e| e
+                .to_string()
+                .starts_with(
+                    "Fail to load config: Alert rule `too many errors` is defined more than once"
+                ))
+        );
+        let mut unnamed = rule(0, 0);
+        unnamed.name = String::new();
+        assert!(AlertRule::validate(&[unnamed]).is_err_and(|e| e
+            .to_string()
+            .starts_with("Fail to load config: Name of alert rules must not be empty")));
+    }
+}
@@ -1,22 +1,96 @@
-use ::rucat_common::{config::DatabaseVariant, serde::Deserialize};
+use ::rucat_common::{
+    anyhow::anyhow,
+    config::DatabaseVariant,
+    engine::{EngineType, EngineVersions},
+    error::{Result, RucatError},
+    serde::Deserialize,
+    webhook::WebhookConfig,
+};
+
+use crate::{alert::AlertRule, resource_manager::rate_limiter::RateLimiterConfig};
 
 /// Configuration for rucat state monitor
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 #[serde(crate = "rucat_common::serde")]
 pub struct StateMonitorConfig {
     /// Time interval in second for checking engine state
-    pub check_interval_secs: u8,
+    pub check_interval_secs: u32,
     /// Time interval in second for checking trigger state timeout
     /// *Trigger* states are expected to exist only for a very short time,
     /// and then be updated to *InProgress* or *Error* states. However, there is a possibility that
     /// the state monitor is down when the engine is in *Trigger* state, so we need to set a timeout
     /// to avoid the engine being stuck in *Trigger* state. State monitor will pick up those timed out engines
     /// and retrigger them.
-    pub trigger_state_timeout_secs: u16,
+    /// It must be at least twice `check_interval_secs`, otherwise engines acquired by a running
+    /// state monitor could be retriggered before they are released.
+    pub trigger_state_timeout_secs: u32,
+    /// Maximum number of engines updated in one round of checking engine state,
+    /// the most overdue first, so that a backlog, e.g. after an outage, is worked off over several rounds.
+    #[serde(default = "StateMonitorConfig::default_max_engines_per_round")]
+    pub max_engines_per_round: usize,
+    /// Claim the engines to update in one database operation in each round, so that other state monitors skip them,
+    /// instead of listing them and racing with other state monitors on each of them.
+    /// Claimed engines that fail to be updated are retried after `trigger_state_timeout_secs`.
+    #[serde(default)]
+    pub claim_mode: bool,
+    /// Timeout in second for engines to be running after they start, e.g. when the image cannot be pulled.
+    /// Timed out engines are cleaned and moved to the error state.
+    /// Engines can override it by setting their own timeout. No timeout if not set.
+    pub start_in_progress_timeout_secs: Option<u32>,
+    /// Client side rate limit of the Kubernetes API calls.
+    #[serde(default)]
+    pub k8s_rate_limiter: RateLimiterConfig,
+    /// Only monitor engines of these types, so that engines of different types can be monitored
+    /// by different state monitors. Monitor engines of all types if not set.
+    pub engine_type_filter: Option<Vec<EngineType>>,
+    /// Rules of alerts on the number of monitored engines in each state.
+    #[serde(default)]
+    pub alerts: Vec<AlertRule>,
+    /// Engine versions that can be started, all the built-in versions are supported if not set.
+    #[serde(default)]
+    pub engine_versions: EngineVersions,
+    /// Webhooks notified of the engine state transitions, nothing is notified if not set.
+    pub webhooks: Option<WebhookConfig>,
     pub database: DatabaseVariant,
 }
 
+impl StateMonitorConfig {
+    fn default_max_engines_per_round() -> usize {
+        1000
+    }
+
+    /// Check the intervals and timeouts against each other.
+    pub fn validate(&self) -> Result<()> {
+        if (self.trigger_state_timeout_secs as u64) < 2 * self.check_interval_secs as u64 {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "`trigger_state_timeout_secs` ({}) must be at least twice `check_interval_secs` ({})",
+                self.trigger_state_timeout_secs,
+                self.check_interval_secs
+            )));
+        }
+        if self.max_engines_per_round == 0 {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "`max_engines_per_round` must be positive"
+            )));
+        }
+        if self
+            .engine_type_filter
+            .as_ref()
+            .is_some_and(|engine_types| engine_types.is_empty())
+        {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "`engine_type_filter` must not be empty, remove it to monitor engines of all types"
+            )));
+        }
+        AlertRule::validate(&self.alerts)?;
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.validate()?;
+        }
+        self.k8s_rate_limiter.validate()
+    }
+}
+
 /// Load the configuration from the file
 /// Unlike rucat server, we don't allow users to specify the config file path
 /// because state monitor is a background service.
@@ -27,6 +101,8 @@ mod tests {
     use super::*;
     use ::rucat_common::{
         anyhow::Result,
+        config::{ConnectRetry, SurrealConfig},
+        engine::EngineStateKind,
         serde_json::{from_value, json},
     };
 
@@ -78,7 +154,7 @@ mod tests {
         let result = from_value::<StateMonitorConfig>(config);
         assert_eq!(
             result.unwrap_err().to_string(),
-            "unknown field `unknown_field`, expected one of `check_interval_secs`, `trigger_state_timeout_secs`, `database`"
+            "unknown field `unknown_field`, expected one of `check_interval_secs`, `trigger_state_timeout_secs`, `max_engines_per_round`, `claim_mode`, `start_in_progress_timeout_secs`, `k8s_rate_limiter`, `engine_type_filter`, `alerts`, `engine_versions`, `webhooks`, `database`"
         );
     }
 
@@ -102,12 +178,315 @@ mod tests {
             StateMonitorConfig {
                 check_interval_secs: 1,
                 trigger_state_timeout_secs: 60,
-                database: DatabaseVariant::Surreal {
+                max_engines_per_round: 1000,
+                claim_mode: false,
+                start_in_progress_timeout_secs: None,
+                k8s_rate_limiter: RateLimiterConfig::default(),
+                engine_type_filter: None,
+                alerts: vec![],
+                engine_versions: EngineVersions::default(),
+                webhooks: None,
+                database: DatabaseVariant::Surreal(Box::new(SurrealConfig {
                     credentials: None,
-                    uri: "".to_string()
+                    uri: "".to_string(),
+                    connect_retry: ConnectRetry::default(),
+                    namespace: "rucat".to_owned(),
+                    database: "rucat".to_owned(),
+                    table: "engines".to_owned(),
+                    tls: false,
+                    ca_path: None,
+                    query_timeout_secs: 30
+                }))
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_start_in_progress_timeout() -> Result<()> {
+        let config = json!(
+            {
+                "check_interval_secs": 1,
+                "trigger_state_timeout_secs": 60,
+                "start_in_progress_timeout_secs": 600,
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri":""
+                    }
+                }
+            }
+        );
+        let result = from_value::<StateMonitorConfig>(config)?;
+        assert_eq!(result.start_in_progress_timeout_secs, Some(600));
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_max_engines_per_round() -> Result<()> {
+        let config = json!(
+            {
+                "check_interval_secs": 1,
+                "trigger_state_timeout_secs": 60,
+                "max_engines_per_round": 50,
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri":""
+                    }
+                }
+            }
+        );
+        let result = from_value::<StateMonitorConfig>(config)?;
+        assert_eq!(result.max_engines_per_round, 50);
+        result.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_claim_mode() -> Result<()> {
+        let config = json!(
+            {
+                "check_interval_secs": 1,
+                "trigger_state_timeout_secs": 60,
+                "claim_mode": true,
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri":""
+                    }
+                }
+            }
+        );
+        let result = from_value::<StateMonitorConfig>(config)?;
+        assert!(result.claim_mode);
+        Ok(())
+    }
+
+    #[test]
+    fn validate_max_engines_per_round() {
+        let mut config = config_with_intervals(1, 60);
+        config.max_engines_per_round = 0;
+        let result = config.validate();
+        assert!(result.is_err_and(|e| e
+            .to_string()
+            .starts_with("Fail to load config: `max_engines_per_round` must be positive")));
+    }
+
+    #[test]
+    fn deserialize_alerts() -> Result<()> {
+        let config = json!(
+            {
+                "check_interval_secs": 1,
+                "trigger_state_timeout_secs": 60,
+                "alerts": [
+                    {
+                        "name": "too many errors",
+                        "state": "ErrorClean",
+                        "threshold": 20,
+                        "sustain_secs": 600
+                    }
+                ],
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri":""
+                    }
                 }
             }
         );
+        let result = from_value::<StateMonitorConfig>(config)?;
+        assert_eq!(
+            result.alerts,
+            vec![AlertRule {
+                name: "too many errors".to_owned(),
+                state: EngineStateKind::ErrorClean,
+                threshold: 20,
+                sustain_secs: 600,
+                cooldown_secs: 0,
+            }]
+        );
+        result.validate()?;
         Ok(())
     }
+
+    fn config_with_intervals(
+        check_interval_secs: u32,
+        trigger_state_timeout_secs: u32,
+    ) -> StateMonitorConfig {
+        StateMonitorConfig {
+            check_interval_secs,
+            trigger_state_timeout_secs,
+            max_engines_per_round: 1000,
+            claim_mode: false,
+            start_in_progress_timeout_secs: None,
+            k8s_rate_limiter: RateLimiterConfig::default(),
+            engine_type_filter: None,
+            alerts: vec![],
+            engine_versions: EngineVersions::default(),
+            webhooks: None,
+            database: DatabaseVariant::Surreal(Box::new(SurrealConfig {
+                credentials: None,
+                uri: "".to_string(),
+                connect_retry: ConnectRetry::default(),
+                namespace: "rucat".to_owned(),
+                database: "rucat".to_owned(),
+                table: "engines".to_owned(),
+                tls: false,
+                ca_path: None,
+                query_timeout_secs: 30,
+            })),
+        }
+    }
+
+    #[test]
+    fn deserialize_intervals_larger_than_u16() -> Result<()> {
+        let config = json!(
+            {
+                "check_interval_secs": 600,
+                "trigger_state_timeout_secs": 86400,
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri":""
+                    }
+                }
+            }
+        );
+        let result = from_value::<StateMonitorConfig>(config)?;
+        assert_eq!(result, config_with_intervals(600, 86400));
+        result.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn validate_trigger_state_timeout() -> Result<()> {
+        config_with_intervals(5, 10).validate()?;
+        config_with_intervals(0, 0).validate()?;
+        config_with_intervals(u32::MAX / 2, u32::MAX - 1).validate()?;
+        let result = config_with_intervals(5, 9).validate();
+        assert!(result.is_err_and(|e| e.to_string().starts_with(
+            "Fail to load config: `trigger_state_timeout_secs` (9) must be at least twice `check_interval_secs` (5)"
+        )));
+        let result = config_with_intervals(u32::MAX, u32::MAX).validate();
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_k8s_rate_limiter() -> Result<()> {
+        let config = json!(
+            {
+                "check_interval_secs": 1,
+                "trigger_state_timeout_secs": 60,
+                "k8s_rate_limiter": {
+                    "qps": 2.5,
+                    "burst": 5
+                },
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri":""
+                    }
+                }
+            }
+        );
+        let result = from_value::<StateMonitorConfig>(config)?;
+        assert_eq!(
+            result.k8s_rate_limiter,
+            RateLimiterConfig {
+                qps: 2.5,
+                burst: 5,
+                ..Default::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn validate_k8s_rate_limiter() {
+        let mut config = config_with_intervals(1, 60);
+        config.k8s_rate_limiter.burst = 0;
+        let result = config.validate();
+        assert!(result.is_err_and(|e| e.to_string().starts_with(
+            "Fail to load config: Rate limiter needs a positive `qps` and `burst`, but got qps 20 and burst 0"
+        )));
+    }
+
+    #[test]
+    fn deserialize_engine_type_filter() -> Result<()> {
+        let config = json!(
+            {
+                "check_interval_secs": 1,
+                "trigger_state_timeout_secs": 60,
+                "engine_type_filter": ["Spark"],
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri":""
+                    }
+                }
+            }
+        );
+        let result = from_value::<StateMonitorConfig>(config)?;
+        assert_eq!(result.engine_type_filter, Some(vec![EngineType::Spark]));
+        result.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn validate_empty_engine_type_filter() {
+        let mut config = config_with_intervals(1, 60);
+        config.engine_type_filter = Some(vec![]);
+        let result = config.validate();
+        assert!(result.is_err_and(|e| e
+            .to_string()
+            .starts_with("Fail to load config: `engine_type_filter` must not be empty")));
+    }
+
+    #[test]
+    fn deserialize_webhooks() -> Result<()> {
+        let config = json!(
+            {
+                "check_interval_secs": 1,
+                "trigger_state_timeout_secs": 60,
+                "webhooks": {
+                    "urls": ["http://localhost:8080/hook"],
+                    "states": ["Running", "ErrorClean"]
+                },
+                "database": {
+                    "Surreal": {
+                        "credentials": null,
+                        "uri":""
+                    }
+                }
+            }
+        );
+        let result = from_value::<StateMonitorConfig>(config)?;
+        let webhooks = result.webhooks.as_ref().expect("webhooks should be set");
+        assert_eq!(webhooks.urls, vec!["http://localhost:8080/hook"]);
+        assert_eq!(
+            webhooks.states,
+            [EngineStateKind::Running, EngineStateKind::ErrorClean].into()
+        );
+        assert_eq!(webhooks.timeout_secs, 5);
+        result.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn validate_webhooks() {
+        let mut config = config_with_intervals(1, 60);
+        config.webhooks = Some(WebhookConfig {
+            urls: vec!["not a url".to_owned()],
+            states: [EngineStateKind::Running].into(),
+            timeout_secs: 5,
+            max_retries: 3,
+            retry_backoff_millis: 1000,
+        });
+        let result = config.validate();
+        assert!(result.is_err_and(|e| e
+            .to_string()
+            .starts_with("Fail to load config: Invalid webhook URL `not a url`")));
+    }
 }
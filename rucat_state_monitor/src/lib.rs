@@ -1,29 +1,76 @@
 use ::core::time::Duration;
 use ::std::{
     borrow::Cow,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     time::{Instant, SystemTime},
 };
 
 use ::rucat_common::{
-    database::{Database, EngineIdAndInfo, UpdateEngineStateResult},
+    anyhow::anyhow,
+    clock::Clock,
+    database::{Database, EngineIdAndInfo, TriggeredEngine, UpdateEngineStateResult},
     engine::{
-        EngineId,
+        EngineEndpoints, EngineFeature, EngineId,
         EngineState::{self, *},
+        EngineType,
     },
+    error::{Result, RucatError},
+    logging::get_dropped_lines,
     tokio,
     tracing::{debug, error, info, warn},
+    webhook::WebhookNotifier,
 };
+use alert::{AlertEvaluator, AlertEvent, AlertRule};
 use resource_manager::{ResourceManager, ResourceState};
 
+pub mod alert;
 pub mod config;
 pub mod resource_manager;
 
 /// State monitor to monitor the state of engines.
+/// Clones of the state monitor are cheap handles sharing the database client, resource manager and
+/// the sync progress, so that the loops of the state monitor can be driven concurrently.
 pub struct StateMonitor<DB, RSManager> {
-    db_client: DB,
-    resource_manager: RSManager,
+    db_client: Arc<DB>,
+    resource_manager: Arc<RSManager>,
     check_interval: Duration,
     trigger_state_timeout: Duration,
+    /// Default timeout of `StartInProgress` state, `None` means no timeout.
+    start_in_progress_timeout: Option<Duration>,
+    /// Types of engines monitored by this state monitor, `None` means all types.
+    engine_type_filter: Option<Vec<EngineType>>,
+    /// Maximum number of engines updated in one round of the sync loop.
+    max_engines_per_round: usize,
+    /// Whether to claim the engines to update, instead of listing them, see [StateMonitor::with_claim_mode].
+    claim_mode: bool,
+    /// Number of finished rounds of the sync loop, for the heartbeat to detect a stuck sync loop.
+    sync_rounds: Arc<AtomicU64>,
+    /// Source of the time of state updates and timeouts.
+    clock: Arc<dyn Clock>,
+    /// Notifier of the state transitions, nothing is notified if not set.
+    webhook_notifier: Option<WebhookNotifier>,
+}
+
+// Manually implement `Clone` because derive(Clone) requires `DB: Clone` and `RSManager: Clone`
+impl<DB, RSManager> Clone for StateMonitor<DB, RSManager> {
+    fn clone(&self) -> Self {
+        Self {
+            db_client: self.db_client.clone(),
+            resource_manager: self.resource_manager.clone(),
+            check_interval: self.check_interval,
+            trigger_state_timeout: self.trigger_state_timeout,
+            start_in_progress_timeout: self.start_in_progress_timeout,
+            engine_type_filter: self.engine_type_filter.clone(),
+            max_engines_per_round: self.max_engines_per_round,
+            claim_mode: self.claim_mode,
+            sync_rounds: self.sync_rounds.clone(),
+            clock: self.clock.clone(),
+            webhook_notifier: self.webhook_notifier.clone(),
+        }
+    }
 }
 
 impl<DB, RSManager> StateMonitor<DB, RSManager>
@@ -34,39 +81,92 @@ where
     pub fn new(
         db_client: DB,
         resource_manager: RSManager,
-        check_interval_secs: u8,
-        trigger_state_timeout_secs: u16,
+        check_interval: Duration,
+        trigger_state_timeout: Duration,
+        start_in_progress_timeout: Option<Duration>,
+        engine_type_filter: Option<Vec<EngineType>>,
+        clock: Arc<dyn Clock>,
     ) -> Self {
-        let check_interval = Duration::from_secs(check_interval_secs as u64);
-        let trigger_state_timeout = Duration::from_secs(trigger_state_timeout_secs as u64);
         info!(
-            "Create state monitor with check interval {:?} and trigger state timeout {:?}",
-            check_interval, trigger_state_timeout
+            "Create state monitor with check interval {:?}, trigger state timeout {:?} \
+                and start in progress timeout {:?}",
+            check_interval, trigger_state_timeout, start_in_progress_timeout
         );
+        match &engine_type_filter {
+            Some(engine_types) => info!("Only monitor engines of types {:?}", engine_types),
+            None => info!("Monitor engines of all types"),
+        }
         Self {
-            db_client,
-            resource_manager,
+            db_client: Arc::new(db_client),
+            resource_manager: Arc::new(resource_manager),
             check_interval,
             trigger_state_timeout,
+            start_in_progress_timeout,
+            engine_type_filter,
+            max_engines_per_round: Self::DEFAULT_MAX_ENGINES_PER_ROUND,
+            claim_mode: false,
+            sync_rounds: Arc::new(AtomicU64::new(0)),
+            clock,
+            webhook_notifier: None,
         }
     }
 
+    /// Notify the webhooks of the state transitions of the engines.
+    pub fn with_webhook_notifier(mut self, webhook_notifier: WebhookNotifier) -> Self {
+        info!("Notify webhooks of engine state transitions");
+        self.webhook_notifier = Some(webhook_notifier);
+        self
+    }
+
+    /// Update at most `max_engines_per_round` engines in one round of the sync loop,
+    /// the rest are left to the following rounds.
+    pub fn with_max_engines_per_round(mut self, max_engines_per_round: usize) -> Self {
+        info!("Update at most {} engines per round", max_engines_per_round);
+        self.max_engines_per_round = max_engines_per_round;
+        self
+    }
+
+    /// Claim the engines to update in one database operation in each round of the sync loop,
+    /// so that other state monitors skip them, instead of racing on every engine to update.
+    /// Claimed engines that fail to be updated are retried after `trigger_state_timeout`.
+    pub fn with_claim_mode(mut self) -> Self {
+        info!("Claim the engines to update");
+        self.claim_mode = true;
+        self
+    }
+
+    /// Maximum number of engines updated in one round of the sync loop if not set.
+    const DEFAULT_MAX_ENGINES_PER_ROUND: usize = 1000;
+
+    /// Minimum interval between two heartbeats.
+    const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Whether engines of the type are monitored by this state monitor.
+    fn is_monitored(&self, engine_type: &EngineType) -> bool {
+        self.engine_type_filter
+            .as_ref()
+            .is_none_or(|engine_types| engine_types.contains(engine_type))
+    }
+
+    /// Check that the database can be reached before the loops start,
+    /// so that the state monitor fails fast instead of failing in every round.
+    pub async fn check_database(&self) -> Result<()> {
+        let health = self.db_client.health_check().await.map_err(|e| {
+            RucatError::fail_to_connect_database(anyhow!("Database is not reachable: {}", e))
+        })?;
+        info!(
+            "Database is reachable in {:?}, version {}",
+            health.latency,
+            health.version.as_deref().unwrap_or("unknown")
+        );
+        Ok(())
+    }
+
     /// This function runs forever to monitor the state of engines.
-    pub async fn run(&self) -> ! {
+    pub async fn sync_loop(&self) -> ! {
         loop {
             let start_time = Instant::now();
-            match self.db_client.list_engines_need_update().await {
-                Ok(engines) => {
-                    info!("Detect {} engines need to update", engines.len());
-                    // TODO: make this execute in parallel
-                    for e in engines {
-                        self.sync_engine(e).await;
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to get engine list: {}", e);
-                }
-            }
+            self.sync_round().await;
             let elapsed = start_time.elapsed();
             let sleep_duration = self.check_interval.checked_sub(elapsed).unwrap_or_default();
             debug!(
@@ -77,18 +177,183 @@ where
         }
     }
 
+    /// Sync all the engines that need to be updated once.
+    async fn sync_round(&self) {
+        let engine_types = self.engine_type_filter.as_deref().unwrap_or_default();
+        let engines = if self.claim_mode {
+            self.db_client
+                .claim_engines_need_update(
+                    engine_types,
+                    self.max_engines_per_round,
+                    self.clock.now(),
+                    self.trigger_state_timeout,
+                )
+                .await
+        } else {
+            self.db_client
+                .list_engines_need_update(engine_types, self.max_engines_per_round)
+                .await
+        };
+        match engines {
+            Ok(engines) => {
+                info!("Detect {} engines need to update", engines.len());
+                if engines.len() >= self.max_engines_per_round {
+                    warn!(
+                        "Reach the limit of {} engines per round, \
+                            the other engines that need to update are left to the following rounds",
+                        self.max_engines_per_round
+                    );
+                }
+                // TODO: make this execute in parallel
+                for e in engines {
+                    self.sync_engine(e).await;
+                }
+            }
+            Err(e) => {
+                error!("Failed to get engine list: {}", e);
+            }
+        }
+        self.sync_rounds.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// This function runs forever to report engines that stay in `Trigger*` states after the timeout,
+    /// which should have been retried by the sync loop, and to evaluate the `alert_rules`.
+    pub async fn housekeeping_loop(&self, alert_rules: Vec<AlertRule>) -> ! {
+        let interval = self.trigger_state_timeout.max(self.check_interval);
+        let mut alerts = AlertEvaluator::new(alert_rules);
+        loop {
+            self.report_stale_triggered_engines().await;
+            self.evaluate_alerts(&mut alerts).await;
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    async fn report_stale_triggered_engines(&self) {
+        match self.db_client.list_triggered_engines().await {
+            Ok(engines) => {
+                let now = self.clock.now();
+                for TriggeredEngine {
+                    id,
+                    info,
+                    trigger_timeout_time,
+                    ..
+                } in engines
+                {
+                    // Engines of other types are retried by other state monitors.
+                    if !self.is_monitored(&info.engine_type) {
+                        continue;
+                    }
+                    // Timed out engines are retried in the next round of the sync loop.
+                    if let Ok(overdue) = now.duration_since(trigger_timeout_time) {
+                        if overdue > self.check_interval {
+                            warn!(
+                                "Engine {} in state {:?} timed out {:?} ago, but has not been retried",
+                                id, info.state, overdue
+                            );
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to get triggered engines: {}", e);
+            }
+        }
+    }
+
+    /// Count the monitored engines in each state, and report the alerts that fire or resolve.
+    async fn evaluate_alerts(&self, alerts: &mut AlertEvaluator) {
+        if alerts.is_empty() {
+            return;
+        }
+        let engine_types = self.engine_type_filter.as_deref().unwrap_or_default();
+        match self
+            .db_client
+            .count_engines_by_state(engine_types, None)
+            .await
+        {
+            Ok(counts) => {
+                for event in alerts.evaluate(&counts, self.clock.now()) {
+                    match event {
+                        AlertEvent::Firing { rule, value } => {
+                            warn!("Alert `{}` is firing, current value: {}", rule, value)
+                        }
+                        AlertEvent::Resolved { rule, value } => {
+                            info!("Alert `{}` is resolved, current value: {}", rule, value)
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to count engines by state: {}", e);
+            }
+        }
+    }
+
+    /// This function runs forever to report whether the sync loop is making progress,
+    /// and the log lines dropped because of a slow output.
+    pub async fn heartbeat_loop(&self) -> ! {
+        // One round of the sync loop takes at least `check_interval`.
+        let interval = Self::HEARTBEAT_INTERVAL.max(self.check_interval * 2);
+        let mut last_rounds = self.sync_rounds.load(Ordering::Relaxed);
+        let mut last_dropped_lines = get_dropped_lines();
+        loop {
+            tokio::time::sleep(interval).await;
+            let rounds = self.sync_rounds.load(Ordering::Relaxed);
+            if rounds == last_rounds {
+                warn!(
+                    "No sync round finished in the last {:?}, the sync loop may be stuck",
+                    interval
+                );
+            } else {
+                info!(
+                    "State monitor is alive, {} sync rounds finished in the last {:?}",
+                    rounds - last_rounds,
+                    interval
+                );
+            }
+            let dropped_lines = get_dropped_lines();
+            if dropped_lines > last_dropped_lines {
+                warn!(
+                    "{} log lines are dropped in the last {:?} because the output is too slow",
+                    dropped_lines - last_dropped_lines,
+                    interval
+                );
+            }
+            last_dropped_lines = dropped_lines;
+            last_rounds = rounds;
+        }
+    }
+
     /// Sync the engine state with the resource manager.
     /// And update the engine state in the database.
     async fn sync_engine(&self, engine: EngineIdAndInfo) {
-        let EngineIdAndInfo { id, info } = engine;
+        let EngineIdAndInfo {
+            id,
+            info,
+            state_update_time,
+        } = engine;
         match info.state {
             WaitToStart => {
                 if self.acquire_engine(&id, &WaitToStart).await {
                     info!("Create engine {}", id);
+                    // Only the summary of the engine is listed, get the whole info to create the resource.
+                    let info = match self.db_client.get_engine(&id).await {
+                        Ok(Some(info)) => info,
+                        Ok(None) => {
+                            warn!("Engine {} is removed before creating its resource", id);
+                            return;
+                        }
+                        Err(e) => {
+                            // The engine is retried after the trigger times out.
+                            error!("Failed to get engine {}: {}", id, e);
+                            return;
+                        }
+                    };
                     // create engine resource
                     let err_msg = match self.resource_manager.create_resource(&id, &info).await {
-                        Ok(()) => {
+                        Ok(endpoints) => {
                             info!("Create engine resource for {}", id);
+                            self.update_engine_endpoints(&id, endpoints).await;
                             None
                         }
                         Err(e) => {
@@ -103,7 +368,11 @@ where
                 if self.acquire_engine(&id, &WaitToTerminate).await {
                     info!("Terminate engine {}", id);
                     // clean engine resource
-                    let err_msg = match self.resource_manager.clean_resource(&id).await {
+                    let err_msg = match self
+                        .resource_manager
+                        .clean_resource(&id, info.project.as_deref())
+                        .await
+                    {
                         Ok(()) => {
                             info!("Clean engine resource for {}", id);
                             None
@@ -120,7 +389,11 @@ where
                 if self.acquire_engine(&id, &ErrorWaitToClean(s.clone())).await {
                     info!("Clean resource for error state engine {}", id);
                     // clean engine resource
-                    let err_msg = match self.resource_manager.clean_resource(&id).await {
+                    let err_msg = match self
+                        .resource_manager
+                        .clean_resource(&id, info.project.as_deref())
+                        .await
+                    {
                         Ok(()) => {
                             info!("Clean engine resource for {}", id);
                             None
@@ -139,10 +412,37 @@ where
             | StartInProgress
             | TerminateInProgress
             | ErrorCleanInProgress(_)) => {
-                let resource_state = self.resource_manager.get_resource_state(&id).await;
+                let resource_state = self
+                    .resource_manager
+                    .get_resource_state(&id, info.project.as_deref())
+                    .await;
                 let new_state = resource_state
                     .get_new_engine_state(&in_progress_state)
-                    .unwrap_or(in_progress_state.clone());
+                    .filter(|new_state| {
+                        // With readiness probe, the engine is not running until it is ready.
+                        *new_state != Running
+                            || !info.features.contains(EngineFeature::READINESS_PROBE)
+                            || resource_state.is_ready()
+                    })
+                    .unwrap_or_else(|| {
+                        let timeout = info
+                            .start_in_progress_timeout_secs
+                            .map(|secs| Duration::from_secs(secs as u64))
+                            .or(self.start_in_progress_timeout);
+                        if in_progress_state == StartInProgress
+                            && is_start_in_progress_timed_out(
+                                state_update_time,
+                                self.clock.now(),
+                                timeout,
+                            )
+                        {
+                            let summary = resource_state.get_summary();
+                            warn!("Engine {} times out to start: {}", id, summary);
+                            ErrorWaitToClean(Cow::Owned(format!("start timed out: {}", summary)))
+                        } else {
+                            in_progress_state.clone()
+                        }
+                    });
                 self.inspect_engine_state_updating(&id, &in_progress_state, &new_state)
                     .await;
             }
@@ -161,6 +461,19 @@ where
         }
     }
 
+    /// Record the endpoints of the engine whose resource is created.
+    /// Failing to record them does not fail the engine, which can be connected without them.
+    async fn update_engine_endpoints(&self, id: &EngineId, endpoints: EngineEndpoints) {
+        if endpoints.is_empty() {
+            return;
+        }
+        match self.db_client.update_engine_endpoints(id, endpoints).await {
+            Ok(Some(())) => debug!("Update endpoints of engine {}", id),
+            Ok(None) => warn!("Engine {} is removed before updating its endpoints", id),
+            Err(e) => error!("Failed to update endpoints of engine {}: {}", id, e),
+        }
+    }
+
     /// For timed out Trigger* states, retry triggering the engine by updating its state to WaitTo*.
     async fn retry_triggering_engine(&self, id: &EngineId, current_state: &EngineState) {
         let new_state = match current_state {
@@ -202,7 +515,7 @@ where
         let next_update_time = self.get_next_update_time(&new_state);
         let response = self
             .db_client
-            .update_engine_state(id, current_state, &new_state, next_update_time)
+            .update_engine_state(id, current_state, &new_state, next_update_time, None, None)
             .await;
         match response {
             Ok(Some(response)) => match response {
@@ -211,6 +524,16 @@ where
                         "Engine {} state updated from {:?} to {:?}",
                         id, current_state, new_state
                     );
+                    self.notify_state_change(id, current_state, &new_state);
+                }
+                // The engine is force stopped by the server, it will be terminated by monitors later.
+                UpdateEngineStateResult::Fail {
+                    current_state: WaitToTerminate,
+                } => {
+                    warn!(
+                        "Engine {} in {:?} is force stopped, skip updating it to {:?}",
+                        id, current_state, new_state
+                    );
                 }
                 UpdateEngineStateResult::Fail {
                     current_state: actual_state,
@@ -221,10 +544,11 @@ where
                     );
                 }
             },
+            // The engine is force deleted by the server, its resources may be orphaned.
             Ok(None) => {
-                unreachable!(
-                    "Bug: engine {} in {:?} state is removed by others",
-                    id, current_state
+                warn!(
+                    "Engine {} in {:?} is force deleted, skip updating it to {:?}",
+                    id, current_state, new_state
                 );
             }
             Err(e) => {
@@ -267,7 +591,7 @@ where
         let next_update_time = self.get_next_update_time(new_state);
         let response = self
             .db_client
-            .update_engine_state(id, old_state, new_state, next_update_time)
+            .update_engine_state(id, old_state, new_state, next_update_time, None, None)
             .await;
         match response {
             Ok(Some(response)) => match response {
@@ -276,6 +600,7 @@ where
                         "Engine {} state updated from {:?} to {:?}",
                         id, old_state, new_state
                     );
+                    self.notify_state_change(id, old_state, new_state);
                     true
                 }
                 UpdateEngineStateResult::Fail {
@@ -303,16 +628,38 @@ where
         }
     }
 
+    /// Notify the webhooks of the state change in the background, without waiting for the delivery.
+    fn notify_state_change(&self, id: &EngineId, old_state: &EngineState, new_state: &EngineState) {
+        if let Some(notifier) = &self.webhook_notifier {
+            notifier.notify(id, old_state, new_state, self.clock.now());
+        }
+    }
+
     fn get_next_update_time(&self, state: &EngineState) -> Option<SystemTime> {
         get_next_update_time(
             state,
-            SystemTime::now(),
+            self.clock.now(),
             self.check_interval,
             self.trigger_state_timeout,
         )
     }
 }
 
+/// Whether the engine has been in `StartInProgress` state for longer than the `timeout`.
+/// Engines without the state update time recorded never time out.
+fn is_start_in_progress_timed_out(
+    state_update_time: Option<SystemTime>,
+    now: SystemTime,
+    timeout: Option<Duration>,
+) -> bool {
+    match (state_update_time, timeout) {
+        (Some(state_update_time), Some(timeout)) => now
+            .duration_since(state_update_time)
+            .is_ok_and(|elapsed| elapsed > timeout),
+        _ => false,
+    }
+}
+
 /// Get the next update time of the engine.
 /// # Parameters
 /// - `state`: The state of the engine.
@@ -342,18 +689,66 @@ fn get_next_update_time(
 
 #[cfg(test)]
 mod tests {
-    use ::std::collections::BTreeMap;
+    use ::std::collections::{BTreeMap, BTreeSet};
 
     use super::*;
     use crate::resource_manager::k8s_client::K8sPodState;
+    use ::httpmock::prelude::*;
     use ::mockall::{mock, predicate};
     use ::rucat_common::{
-        anyhow::anyhow,
-        database::UpdateEngineStateResult,
-        engine::{CreateEngineRequest, EngineInfo, EngineTime, EngineType::Spark, EngineVersion},
-        error::{Result, RucatError},
+        clock::MockClock,
+        database::{
+            DatabaseHealth, EngineFilter, EngineInfoUpdate, EngineSort, IdempotencyKey,
+            RemoveEngineResult, TriggeredEngine, UpdateEngineStateResult,
+        },
+        engine::{
+            EngineActor, EngineEvent, EngineInfo, EngineStateKind, EngineSummary, EngineTime,
+            EngineType::Spark, EngineVersion,
+        },
+        error::RucatErrorType,
+        logging::non_blocking,
+        serde_json::json,
+        webhook::WebhookConfig,
     };
 
+    #[test]
+    fn test_is_start_in_progress_timed_out() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let timeout = Some(Duration::from_secs(600));
+        let started_secs_ago = |secs| Some(now - Duration::from_secs(secs));
+
+        assert!(!is_start_in_progress_timed_out(
+            started_secs_ago(599),
+            now,
+            timeout
+        ));
+        // the timeout is not exceeded at the boundary
+        assert!(!is_start_in_progress_timed_out(
+            started_secs_ago(600),
+            now,
+            timeout
+        ));
+        assert!(is_start_in_progress_timed_out(
+            started_secs_ago(601),
+            now,
+            timeout
+        ));
+        // no timeout
+        assert!(!is_start_in_progress_timed_out(
+            started_secs_ago(1000),
+            now,
+            None
+        ));
+        // unknown state update time
+        assert!(!is_start_in_progress_timed_out(None, now, timeout));
+        // state update time in the future because of clock skew
+        assert!(!is_start_in_progress_timed_out(
+            Some(now + Duration::from_secs(10)),
+            now,
+            timeout
+        ));
+    }
+
     #[test]
     fn test_get_next_update_time() {
         let now = SystemTime::UNIX_EPOCH;
@@ -394,33 +789,91 @@ mod tests {
     mock! {
         DB{}
         impl Database for DB {
-            async fn add_engine(&self, engine: CreateEngineRequest, next_update_time: Option<SystemTime>) -> Result<EngineId>;
-            async fn remove_engine(&self, id: &EngineId, current_state: &EngineState) -> Result<Option<UpdateEngineStateResult>>;
-            async fn update_engine_state(
+            async fn add_engine(&self, id: &EngineId, info: EngineInfo, next_update_time: Option<SystemTime>, idempotency_key: Option<IdempotencyKey>) -> Result<EngineId>;
+            async fn remove_engine(&self, id: &EngineId, current_state: &EngineState, expected_revision: Option<u64>, deleted_time: SystemTime) -> Result<Option<RemoveEngineResult>>;
+            async fn update_engine_state<'a>(
                 &self,
                 id: &EngineId,
                 before: &EngineState,
                 after: &EngineState,
                 next_update_time: Option<SystemTime>,
+                expected_revision: Option<u64>,
+                actor: Option<&'a EngineActor>,
             ) -> Result<Option<UpdateEngineStateResult>>;
+            async fn update_engine_info<'a>(&self, id: &EngineId, update: EngineInfoUpdate, expected_states: Option<&'a [EngineState]>, expected_revision: Option<u64>) -> Result<Option<UpdateEngineStateResult>>;
+            async fn set_engine_acl(&self, id: &EngineId, allowed_principals: BTreeSet<String>, expected_revision: Option<u64>) -> Result<Option<()>>;
+            async fn update_engine_endpoints(&self, id: &EngineId, endpoints: EngineEndpoints) -> Result<Option<()>>;
             async fn get_engine(&self, id: &EngineId) -> Result<Option<EngineInfo>>;
-            async fn list_engines(&self) -> Result<Vec<EngineId>>;
-            async fn list_engines_need_update(&self) -> Result<Vec<EngineIdAndInfo>>;
+            async fn get_engines(&self, ids: &[EngineId]) -> Result<Vec<(EngineId, Option<EngineInfo>)>>;
+            async fn get_engine_state(&self, id: &EngineId) -> Result<Option<EngineState>>;
+            async fn list_engines(&self, offset: usize, limit: usize, filter: &EngineFilter, sort: EngineSort) -> Result<Vec<EngineId>>;
+            async fn list_engine_summaries(&self, offset: usize, limit: usize, filter: &EngineFilter, sort: EngineSort) -> Result<Vec<EngineSummary>>;
+            async fn list_engines_need_update(&self, engine_types: &[EngineType], limit: usize) -> Result<Vec<EngineIdAndInfo>>;
+            async fn claim_engines_need_update(&self, engine_types: &[EngineType], limit: usize, now: SystemTime, claim_duration: Duration) -> Result<Vec<EngineIdAndInfo>>;
+            async fn count_engines_by_state<'a>(&self, engine_types: &[EngineType], project: Option<&'a str>) -> Result<BTreeMap<EngineStateKind, usize>>;
+            async fn remove_engines_in_states<'a>(&self, states: &BTreeSet<EngineStateKind>, older_than: SystemTime, project: Option<&'a str>, deleted_time: SystemTime) -> Result<Vec<EngineId>>;
+            async fn list_triggered_engines(&self) -> Result<Vec<TriggeredEngine>>;
+            async fn append_engine_event(&self, id: &EngineId, old_state: &EngineState, new_state: &EngineState, source: &str, timestamp: SystemTime) -> Result<()>;
+            async fn get_engine_events(&self, id: &EngineId, limit: usize) -> Result<Vec<EngineEvent>>;
+            async fn purge_engine_events<'a>(&self, deleted_before: SystemTime, project: Option<&'a str>) -> Result<usize>;
+            async fn health_check(&self) -> Result<DatabaseHealth>;
         }
     }
     mock! {
         RM{}
         impl ResourceManager for RM {
             type ResourceState = K8sPodState;
-            async fn create_resource(&self, id: &EngineId, info: &EngineInfo) -> Result<()>;
-            async fn clean_resource(&self, id: &EngineId) -> Result<()>;
-            async fn get_resource_state(&self, id: &EngineId) -> K8sPodState;
+            async fn create_resource(&self, id: &EngineId, info: &EngineInfo) -> Result<EngineEndpoints>;
+            async fn clean_resource<'a>(&self, id: &EngineId, project: Option<&'a str>) -> Result<()>;
+            async fn get_resource_state<'a>(&self, id: &EngineId, project: Option<&'a str>) -> K8sPodState;
         }
     }
 
+    /// Current time of the mock clock of state monitors in tests.
+    fn test_now() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
     fn create_mock_state_monitor(db: MockDB, rm: MockRM) -> StateMonitor<MockDB, MockRM> {
         // check intervals are not tested.
-        StateMonitor::new(db, rm, 0, 0)
+        StateMonitor::new(
+            db,
+            rm,
+            Duration::ZERO,
+            Duration::ZERO,
+            None,
+            None,
+            Arc::new(MockClock::new(test_now())),
+        )
+    }
+
+    #[tokio::test]
+    async fn check_reachable_database() -> Result<()> {
+        let mut db = MockDB::new();
+        db.expect_health_check().times(1).returning(|| {
+            Ok(DatabaseHealth {
+                latency: Duration::from_millis(1),
+                version: Some("2.1.4".to_owned()),
+            })
+        });
+        let monitor = create_mock_state_monitor(db, MockRM::new());
+        monitor.check_database().await
+    }
+
+    #[tokio::test]
+    async fn fail_fast_on_unreachable_database() {
+        let mut db = MockDB::new();
+        db.expect_health_check().times(1).returning(|| {
+            Err(RucatError::database_timeout(anyhow!(
+                "Query is not done in 5 seconds"
+            )))
+        });
+        let monitor = create_mock_state_monitor(db, MockRM::new());
+        let result = monitor.check_database().await;
+        assert!(result.is_err_and(|e| {
+            e.get_error_type() == &RucatErrorType::FailToConnectDatabase
+                && e.to_string().contains("Query is not done in 5 seconds")
+        }));
     }
 
     #[tokio::test]
@@ -433,16 +886,17 @@ mod tests {
                 predicate::eq(&WaitToStart),
                 predicate::eq(&Terminated),
                 predicate::eq(None),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         let rm = MockRM::new();
         let monitor = create_mock_state_monitor(db, rm);
-        assert_eq!(
+        assert!(
             monitor
                 .inspect_engine_state_updating(&engine_id, &WaitToStart, &Terminated)
-                .await,
-            true
+                .await
         );
     }
 
@@ -456,20 +910,21 @@ mod tests {
                 predicate::eq(&WaitToStart),
                 predicate::eq(&Terminated),
                 predicate::eq(None),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| {
+            .returning(|_, _, _, _, _, _| {
                 Ok(Some(UpdateEngineStateResult::Fail {
                     current_state: TriggerStart,
                 }))
             });
         let rm = MockRM::new();
         let monitor = create_mock_state_monitor(db, rm);
-        assert_eq!(
-            monitor
+        assert!(
+            !monitor
                 .inspect_engine_state_updating(&engine_id, &WaitToStart, &Terminated)
-                .await,
-            false
+                .await
         );
     }
 
@@ -483,16 +938,17 @@ mod tests {
                 predicate::eq(&WaitToStart),
                 predicate::eq(&Terminated),
                 predicate::eq(None),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(None));
+            .returning(|_, _, _, _, _, _| Ok(None));
         let rm = MockRM::new();
         let monitor = create_mock_state_monitor(db, rm);
-        assert_eq!(
-            monitor
+        assert!(
+            !monitor
                 .inspect_engine_state_updating(&engine_id, &WaitToStart, &Terminated)
-                .await,
-            false
+                .await
         );
     }
 
@@ -506,16 +962,17 @@ mod tests {
                 predicate::eq(&WaitToStart),
                 predicate::eq(&Terminated),
                 predicate::eq(None),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Err(RucatError::fail_to_connect_database(anyhow!(""))));
+            .returning(|_, _, _, _, _, _| Err(RucatError::fail_to_connect_database(anyhow!(""))));
         let rm = MockRM::new();
         let monitor = create_mock_state_monitor(db, rm);
-        assert_eq!(
-            monitor
+        assert!(
+            !monitor
                 .inspect_engine_state_updating(&engine_id, &WaitToStart, &Terminated)
-                .await,
-            false
+                .await
         );
     }
 
@@ -528,13 +985,15 @@ mod tests {
                 predicate::eq(engine_id.clone()),
                 predicate::eq(&WaitToStart),
                 predicate::eq(&TriggerStart),
+                predicate::eq(Some(test_now())),
+                predicate::eq(None),
                 predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         let rm = MockRM::new();
         let monitor = create_mock_state_monitor(db, rm);
-        assert_eq!(monitor.acquire_engine(&engine_id, &WaitToStart).await, true);
+        assert!(monitor.acquire_engine(&engine_id, &WaitToStart).await);
     }
 
     #[tokio::test]
@@ -557,9 +1016,11 @@ mod tests {
                 predicate::eq(&TriggerStart),
                 predicate::eq(&StartInProgress),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         let rm = MockRM::new();
         let monitor = create_mock_state_monitor(db, rm);
         // this should not panic
@@ -578,9 +1039,11 @@ mod tests {
                 predicate::eq(&TriggerStart),
                 predicate::eq(&ErrorClean(Cow::Borrowed("error"))),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         let rm = MockRM::new();
         let monitor = create_mock_state_monitor(db, rm);
         // this should not panic
@@ -612,9 +1075,11 @@ mod tests {
                 predicate::eq(&TriggerStart),
                 predicate::eq(&StartInProgress),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| {
+            .returning(|_, _, _, _, _, _| {
                 Ok(Some(UpdateEngineStateResult::Fail {
                     current_state: StartInProgress,
                 }))
@@ -627,24 +1092,81 @@ mod tests {
     }
 
     #[tokio::test]
-    #[should_panic(expected = "Bug: engine 123 in TriggerStart state is removed by others")]
-    async fn release_engine_panic_on_unexpected_conflict_2() {
-        let engine_id = EngineId::try_from("123").unwrap();
+    async fn release_engine_not_panic_on_force_delete() {
+        let removed = EngineId::try_from("123").unwrap();
+        let other = EngineId::try_from("456").unwrap();
         let mut db = MockDB::new();
+        db.expect_update_engine_state()
+            .withf({
+                let removed = removed.clone();
+                move |id, _, _, _, _, _| *id == removed
+            })
+            .times(3)
+            .returning(|_, _, _, _, _, _| Ok(None));
         db.expect_update_engine_state()
             .with(
-                predicate::eq(engine_id.clone()),
+                predicate::eq(other.clone()),
                 predicate::eq(&TriggerStart),
                 predicate::eq(&StartInProgress),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(None));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         let rm = MockRM::new();
         let monitor = create_mock_state_monitor(db, rm);
-        monitor
-            .release_engine(&engine_id, &TriggerStart, None)
-            .await
+        for current_state in [
+            TriggerStart,
+            TriggerTermination,
+            ErrorTriggerClean(Cow::Borrowed("error")),
+        ] {
+            monitor.release_engine(&removed, &current_state, None).await;
+        }
+        // the monitor carries on with other engines
+        monitor.release_engine(&other, &TriggerStart, None).await;
+    }
+
+    #[tokio::test]
+    async fn release_engine_not_panic_on_force_stop() {
+        let engine_id = EngineId::try_from("123").unwrap();
+        for (current_state, err_msg, new_state) in [
+            (TriggerStart, None, StartInProgress),
+            (
+                TriggerStart,
+                Some(Cow::Borrowed("error")),
+                ErrorClean(Cow::Borrowed("error")),
+            ),
+            (TriggerTermination, None, TerminateInProgress),
+            (
+                TriggerTermination,
+                Some(Cow::Borrowed("error")),
+                ErrorWaitToClean(Cow::Borrowed("error")),
+            ),
+        ] {
+            let mut db = MockDB::new();
+            db.expect_update_engine_state()
+                .with(
+                    predicate::eq(engine_id.clone()),
+                    predicate::eq(current_state.clone()),
+                    predicate::eq(new_state),
+                    predicate::always(),
+                    predicate::eq(None),
+                    predicate::always(),
+                )
+                .times(1)
+                .returning(|_, _, _, _, _, _| {
+                    Ok(Some(UpdateEngineStateResult::Fail {
+                        current_state: WaitToTerminate,
+                    }))
+                });
+            let rm = MockRM::new();
+            let monitor = create_mock_state_monitor(db, rm);
+            // this should not panic
+            monitor
+                .release_engine(&engine_id, &current_state, err_msg)
+                .await
+        }
     }
 
     #[tokio::test]
@@ -657,9 +1179,11 @@ mod tests {
                 predicate::eq(&TriggerStart),
                 predicate::eq(&StartInProgress),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Err(RucatError::fail_to_connect_database(anyhow!(""))));
+            .returning(|_, _, _, _, _, _| Err(RucatError::fail_to_connect_database(anyhow!(""))));
         let rm = MockRM::new();
         let monitor = create_mock_state_monitor(db, rm);
         // this should not panic
@@ -678,9 +1202,11 @@ mod tests {
                 predicate::eq(&TriggerStart),
                 predicate::eq(&WaitToStart),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         let rm = MockRM::new();
         let monitor = create_mock_state_monitor(db, rm);
         // this should not panic
@@ -701,8 +1227,16 @@ mod tests {
             .await
     }
 
-    #[tokio::test]
-    async fn sync_wait_to_start_engine_success() {
+    fn connect_internal_endpoints() -> EngineEndpoints {
+        EngineEndpoints::from([(
+            "connect-internal".to_owned(),
+            "sc://engine:15002".to_owned(),
+        )])
+    }
+
+    /// Sync an engine in `WaitToStart` whose resource is created,
+    /// with the `result` of updating its endpoints in the database.
+    async fn sync_wait_to_start_engine(update_endpoints_result: fn() -> Result<Option<()>>) {
         let engine_id = EngineId::try_from("123").unwrap();
         let engine_info = EngineInfo::new(
             "abc".to_owned(),
@@ -720,9 +1254,23 @@ mod tests {
                 predicate::eq(&WaitToStart),
                 predicate::eq(&TriggerStart),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+        let info = engine_info.clone();
+        db.expect_get_engine()
+            .with(predicate::eq(engine_id.clone()))
+            .times(1)
+            .returning(move |_| Ok(Some(info.clone())));
+        db.expect_update_engine_endpoints()
+            .with(
+                predicate::eq(engine_id.clone()),
+                predicate::eq(connect_internal_endpoints()),
+            )
+            .times(1)
+            .returning(move |_, _| update_endpoints_result());
         // release engine
         db.expect_update_engine_state()
             .with(
@@ -730,9 +1278,11 @@ mod tests {
                 predicate::eq(&TriggerStart),
                 predicate::eq(&StartInProgress),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         let mut rm = MockRM::new();
         rm.expect_create_resource()
             .with(
@@ -740,17 +1290,30 @@ mod tests {
                 predicate::eq(engine_info.clone()),
             )
             .times(1)
-            .returning(|_, _| Ok(()));
+            .returning(|_, _| Ok(connect_internal_endpoints()));
         let monitor = create_mock_state_monitor(db, rm);
         // this should not panic
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
             })
             .await
     }
 
+    #[tokio::test]
+    async fn sync_wait_to_start_engine_success() {
+        sync_wait_to_start_engine(|| Ok(Some(()))).await
+    }
+
+    #[tokio::test]
+    async fn sync_wait_to_start_engine_fail_to_update_endpoints() {
+        // The engine is started even if its endpoints are not recorded.
+        sync_wait_to_start_engine(|| Err(RucatError::fail_to_update_database(anyhow!("error"))))
+            .await
+    }
+
     #[tokio::test]
     async fn sync_wait_to_start_engine_error() {
         let engine_id = EngineId::try_from("123").unwrap();
@@ -770,9 +1333,16 @@ mod tests {
                 predicate::eq(&WaitToStart),
                 predicate::eq(&TriggerStart),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+        let info = engine_info.clone();
+        db.expect_get_engine()
+            .with(predicate::eq(engine_id.clone()))
+            .times(1)
+            .returning(move |_| Ok(Some(info.clone())));
         // release engine
         db.expect_update_engine_state()
             .with(
@@ -780,9 +1350,11 @@ mod tests {
                 predicate::eq(&TriggerStart),
                 predicate::function(|s| matches!(s, ErrorClean(_))),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         let mut rm = MockRM::new();
         rm.expect_create_resource()
             .with(
@@ -796,7 +1368,48 @@ mod tests {
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn sync_wait_to_start_engine_fail_to_get_engine() {
+        let engine_id = EngineId::try_from("123").unwrap();
+        let engine_info = EngineInfo::new(
+            "abc".to_owned(),
+            Spark,
+            EngineVersion::from("3.5.4"),
+            WaitToStart,
+            BTreeMap::new(),
+            EngineTime::now(),
+        );
+        let mut db = MockDB::new();
+        // acquire engine
+        db.expect_update_engine_state()
+            .with(
+                predicate::eq(engine_id.clone()),
+                predicate::eq(&WaitToStart),
+                predicate::eq(&TriggerStart),
+                predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
+            )
+            .times(1)
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+        db.expect_get_engine()
+            .with(predicate::eq(engine_id.clone()))
+            .times(1)
+            .returning(|_| Err(RucatError::fail_to_read_database(anyhow!("timeout"))));
+        // neither create the resource nor release the engine, which is retried after the trigger times out
+        let rm = MockRM::new();
+        let monitor = create_mock_state_monitor(db, rm);
+        monitor
+            .sync_engine(EngineIdAndInfo {
+                id: engine_id,
+                info: engine_info.into(),
+                state_update_time: None,
             })
             .await
     }
@@ -820,9 +1433,11 @@ mod tests {
                 predicate::eq(&WaitToStart),
                 predicate::eq(&TriggerStart),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| {
+            .returning(|_, _, _, _, _, _| {
                 Ok(Some(UpdateEngineStateResult::Fail {
                     current_state: TriggerStart,
                 }))
@@ -833,7 +1448,8 @@ mod tests {
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
             })
             .await
     }
@@ -857,9 +1473,11 @@ mod tests {
                 predicate::eq(&WaitToTerminate),
                 predicate::eq(&TriggerTermination),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         // release engine
         db.expect_update_engine_state()
             .with(
@@ -867,20 +1485,26 @@ mod tests {
                 predicate::eq(&TriggerTermination),
                 predicate::eq(&TerminateInProgress),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         let mut rm = MockRM::new();
         rm.expect_clean_resource()
-            .with(predicate::eq(engine_id.clone()))
+            .withf({
+                let engine_id = engine_id.clone();
+                move |id, project| *id == engine_id && project.is_none()
+            })
             .times(1)
-            .returning(|_| Ok(()));
+            .returning(|_, _| Ok(()));
         let monitor = create_mock_state_monitor(db, rm);
         // this should not panic
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
             })
             .await
     }
@@ -904,9 +1528,11 @@ mod tests {
                 predicate::eq(&WaitToTerminate),
                 predicate::eq(&TriggerTermination),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         // release engine
         db.expect_update_engine_state()
             .with(
@@ -914,20 +1540,26 @@ mod tests {
                 predicate::eq(&TriggerTermination),
                 predicate::function(|s| matches!(s, ErrorWaitToClean(_))),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         let mut rm = MockRM::new();
         rm.expect_clean_resource()
-            .with(predicate::eq(engine_id.clone()))
+            .withf({
+                let engine_id = engine_id.clone();
+                move |id, project| *id == engine_id && project.is_none()
+            })
             .times(1)
-            .returning(|_| Err(RucatError::fail_to_delete_engine(anyhow!("some error"))));
+            .returning(|_, _| Err(RucatError::fail_to_delete_engine(anyhow!("some error"))));
         let monitor = create_mock_state_monitor(db, rm);
         // this should not panic
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
             })
             .await
     }
@@ -951,9 +1583,11 @@ mod tests {
                 predicate::eq(&WaitToTerminate),
                 predicate::eq(&TriggerTermination),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| {
+            .returning(|_, _, _, _, _, _| {
                 Ok(Some(UpdateEngineStateResult::Fail {
                     current_state: TriggerTermination,
                 }))
@@ -964,7 +1598,39 @@ mod tests {
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn clean_resource_in_project_of_engine() {
+        let engine_id = EngineId::try_from("123").unwrap();
+        let mut engine_info = EngineInfo::new(
+            "abc".to_owned(),
+            Spark,
+            EngineVersion::from("3.5.4"),
+            WaitToTerminate,
+            BTreeMap::new(),
+            EngineTime::now(),
+        );
+        engine_info.project = Some("team-a".to_owned());
+        let mut db = MockDB::new();
+        db.expect_update_engine_state()
+            .times(2)
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+        let mut rm = MockRM::new();
+        rm.expect_clean_resource()
+            .withf(|_, project| *project == Some("team-a"))
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let monitor = create_mock_state_monitor(db, rm);
+        monitor
+            .sync_engine(EngineIdAndInfo {
+                id: engine_id,
+                info: engine_info.into(),
+                state_update_time: None,
             })
             .await
     }
@@ -988,9 +1654,11 @@ mod tests {
                 predicate::eq(ErrorWaitToClean(Cow::Borrowed("error"))),
                 predicate::eq(ErrorTriggerClean(Cow::Borrowed("error"))),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         // release engine
         db.expect_update_engine_state()
             .with(
@@ -998,20 +1666,26 @@ mod tests {
                 predicate::eq(ErrorTriggerClean(Cow::Borrowed("error"))),
                 predicate::eq(ErrorCleanInProgress(Cow::Borrowed("error"))),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         let mut rm = MockRM::new();
         rm.expect_clean_resource()
-            .with(predicate::eq(engine_id.clone()))
+            .withf({
+                let engine_id = engine_id.clone();
+                move |id, project| *id == engine_id && project.is_none()
+            })
             .times(1)
-            .returning(|_| Ok(()));
+            .returning(|_, _| Ok(()));
         let monitor = create_mock_state_monitor(db, rm);
         // this should not panic
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
             })
             .await
     }
@@ -1035,9 +1709,11 @@ mod tests {
                 predicate::eq(ErrorWaitToClean(Cow::Borrowed("error"))),
                 predicate::eq(ErrorTriggerClean(Cow::Borrowed("error"))),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         // release engine
         db.expect_update_engine_state()
             .with(
@@ -1045,20 +1721,26 @@ mod tests {
                 predicate::eq(ErrorTriggerClean(Cow::Borrowed("error"))),
                 predicate::function(|s| matches!(s, ErrorWaitToClean(_))),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
         let mut rm = MockRM::new();
         rm.expect_clean_resource()
-            .with(predicate::eq(engine_id.clone()))
+            .withf({
+                let engine_id = engine_id.clone();
+                move |id, project| *id == engine_id && project.is_none()
+            })
             .times(1)
-            .returning(|_| Err(RucatError::fail_to_delete_engine(anyhow!("some error"))));
+            .returning(|_, _| Err(RucatError::fail_to_delete_engine(anyhow!("some error"))));
         let monitor = create_mock_state_monitor(db, rm);
         // this should not panic
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
             })
             .await
     }
@@ -1082,9 +1764,11 @@ mod tests {
                 predicate::eq(ErrorWaitToClean(Cow::Borrowed("error"))),
                 predicate::eq(ErrorTriggerClean(Cow::Borrowed("error"))),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| {
+            .returning(|_, _, _, _, _, _| {
                 Ok(Some(UpdateEngineStateResult::Fail {
                     current_state: ErrorTriggerClean(Cow::Borrowed("error")),
                 }))
@@ -1095,7 +1779,8 @@ mod tests {
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
             })
             .await
     }
@@ -1113,9 +1798,12 @@ mod tests {
         );
         let mut rm = MockRM::new();
         rm.expect_get_resource_state()
-            .with(predicate::eq(engine_id.clone()))
+            .withf({
+                let engine_id = engine_id.clone();
+                move |id, project| *id == engine_id && project.is_none()
+            })
             .times(1)
-            .returning(|_| K8sPodState::Running);
+            .returning(|_, _| K8sPodState::Running { ready: true });
         let mut db = MockDB::new();
 
         db.expect_update_engine_state()
@@ -1124,16 +1812,19 @@ mod tests {
                 predicate::eq(&StartInProgress),
                 predicate::eq(&Running),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
 
         let monitor = create_mock_state_monitor(db, rm);
         // this should not panic
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
             })
             .await
     }
@@ -1151,9 +1842,12 @@ mod tests {
         );
         let mut rm = MockRM::new();
         rm.expect_get_resource_state()
-            .with(predicate::eq(engine_id.clone()))
+            .withf({
+                let engine_id = engine_id.clone();
+                move |id, project| *id == engine_id && project.is_none()
+            })
             .times(1)
-            .returning(|_| K8sPodState::Pending);
+            .returning(|_, _| K8sPodState::Pending(None));
         let mut db = MockDB::new();
 
         db.expect_update_engine_state()
@@ -1162,20 +1856,305 @@ mod tests {
                 predicate::eq(&StartInProgress),
                 predicate::eq(&StartInProgress),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
 
         let monitor = create_mock_state_monitor(db, rm);
         // this should not panic
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
+            })
+            .await
+    }
+
+    /// Sync a `StartInProgress` engine that has been in the state for `started_secs_ago` seconds,
+    /// with the state monitor's start in progress timeout set to 600 seconds.
+    async fn sync_start_in_progress_engine(
+        started_secs_ago: u64,
+        engine_timeout_secs: Option<u32>,
+        features: &[&str],
+        pod_state: fn() -> K8sPodState,
+        expected_state: EngineState,
+    ) {
+        let engine_id = EngineId::try_from("123").unwrap();
+        let mut engine_info = EngineInfo::new(
+            "abc".to_owned(),
+            Spark,
+            EngineVersion::from("3.5.4"),
+            StartInProgress,
+            BTreeMap::new(),
+            EngineTime::now(),
+        );
+        engine_info.start_in_progress_timeout_secs = engine_timeout_secs;
+        engine_info.features = features.iter().map(|f| f.to_string()).collect();
+        let mut rm = MockRM::new();
+        rm.expect_get_resource_state()
+            .withf({
+                let engine_id = engine_id.clone();
+                move |id, project| *id == engine_id && project.is_none()
+            })
+            .times(1)
+            .returning(move |_, _| pod_state());
+        let mut db = MockDB::new();
+        db.expect_update_engine_state()
+            .with(
+                predicate::eq(engine_id.clone()),
+                predicate::eq(&StartInProgress),
+                predicate::eq(expected_state),
+                predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
+            )
+            .times(1)
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+
+        let monitor = StateMonitor::new(
+            db,
+            rm,
+            Duration::ZERO,
+            Duration::ZERO,
+            Some(Duration::from_secs(600)),
+            None,
+            Arc::new(MockClock::new(test_now())),
+        );
+        monitor
+            .sync_engine(EngineIdAndInfo {
+                id: engine_id,
+                info: engine_info.into(),
+                state_update_time: Some(test_now() - Duration::from_secs(started_secs_ago)),
             })
             .await
     }
 
+    #[tokio::test]
+    async fn sync_timed_out_start_in_progress_engine() {
+        sync_start_in_progress_engine(
+            1000,
+            None,
+            &[],
+            || K8sPodState::Pending(Some("ImagePullBackOff: Back-off pulling image".to_owned())),
+            ErrorWaitToClean(Cow::Borrowed(
+                "start timed out: ImagePullBackOff: Back-off pulling image",
+            )),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn sync_timed_out_start_in_progress_engine_without_pod_summary() {
+        sync_start_in_progress_engine(
+            1000,
+            None,
+            &[],
+            || K8sPodState::Unknown,
+            ErrorWaitToClean(Cow::Borrowed("start timed out: pod state is unknown")),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn sync_running_engine_after_start_in_progress_timeout() {
+        // the pod becomes running before the monitor checks it, so the timeout does not matter.
+        sync_start_in_progress_engine(
+            1000,
+            None,
+            &[],
+            || K8sPodState::Running { ready: true },
+            Running,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn sync_start_in_progress_engine_with_longer_engine_timeout() {
+        sync_start_in_progress_engine(
+            1000,
+            Some(2000),
+            &[],
+            || K8sPodState::Pending(None),
+            StartInProgress,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn sync_start_in_progress_engine_at_timeout() {
+        // the timeout is not exceeded at the boundary
+        sync_start_in_progress_engine(
+            600,
+            None,
+            &[],
+            || K8sPodState::Pending(None),
+            StartInProgress,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn sync_start_in_progress_engine_just_after_timeout() {
+        sync_start_in_progress_engine(
+            601,
+            None,
+            &[],
+            || K8sPodState::Pending(None),
+            ErrorWaitToClean(Cow::Borrowed("start timed out: pod is pending")),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn sync_start_in_progress_engine_with_shorter_engine_timeout() {
+        sync_start_in_progress_engine(
+            1000,
+            Some(10),
+            &[],
+            || K8sPodState::Pending(None),
+            ErrorWaitToClean(Cow::Borrowed("start timed out: pod is pending")),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn sync_not_ready_engine_with_readiness_probe() {
+        sync_start_in_progress_engine(
+            0,
+            None,
+            &[EngineFeature::READINESS_PROBE],
+            || K8sPodState::Running { ready: false },
+            StartInProgress,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn sync_ready_engine_with_readiness_probe() {
+        sync_start_in_progress_engine(
+            0,
+            None,
+            &[EngineFeature::READINESS_PROBE],
+            || K8sPodState::Running { ready: true },
+            Running,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn sync_not_ready_engine_without_readiness_probe() {
+        sync_start_in_progress_engine(
+            0,
+            None,
+            &[],
+            || K8sPodState::Running { ready: false },
+            Running,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn sync_timed_out_not_ready_engine_with_readiness_probe() {
+        sync_start_in_progress_engine(
+            1000,
+            None,
+            &[EngineFeature::READINESS_PROBE],
+            || K8sPodState::Running { ready: false },
+            ErrorWaitToClean(Cow::Borrowed(
+                "start timed out: pod is running but not ready",
+            )),
+        )
+        .await
+    }
+
+    /// Sync a starting engine to `Running` with the webhooks notified of the `states`,
+    /// and return the hits of the webhook once the delivery is expected to be finished.
+    async fn sync_engine_with_webhook(states: &[EngineStateKind]) -> usize {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.method(POST).path("/hook").json_body(json!({
+                    "engine_id": "123",
+                    "old_state": "StartInProgress",
+                    "new_state": "Running",
+                    "timestamp": "2023-11-14 22:13:20 +00:00:00"
+                }));
+                then.status(200);
+            })
+            .await;
+        let engine_id = EngineId::try_from("123").unwrap();
+        let engine_info = EngineInfo::new(
+            "abc".to_owned(),
+            Spark,
+            EngineVersion::from("3.5.4"),
+            StartInProgress,
+            BTreeMap::new(),
+            EngineTime::now(),
+        );
+        let mut rm = MockRM::new();
+        rm.expect_get_resource_state()
+            .times(1)
+            .returning(|_, _| K8sPodState::Running { ready: true });
+        let mut db = MockDB::new();
+        db.expect_update_engine_state()
+            .times(1)
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+        let notifier = WebhookNotifier::new(WebhookConfig {
+            urls: vec![server.url("/hook")],
+            states: states.iter().copied().collect(),
+            timeout_secs: 1,
+            max_retries: 0,
+            retry_backoff_millis: 0,
+        })
+        .unwrap();
+
+        let monitor = StateMonitor::new(
+            db,
+            rm,
+            Duration::ZERO,
+            Duration::ZERO,
+            None,
+            None,
+            Arc::new(MockClock::new(test_now())),
+        )
+        .with_webhook_notifier(notifier);
+        monitor
+            .sync_engine(EngineIdAndInfo {
+                id: engine_id,
+                info: engine_info.into(),
+                state_update_time: Some(test_now()),
+            })
+            .await;
+
+        // The delivery runs in the background.
+        for _ in 0..100 {
+            if mock.hits_async().await > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        mock.hits_async().await
+    }
+
+    #[tokio::test]
+    async fn notify_webhook_of_state_transition() {
+        assert_eq!(
+            sync_engine_with_webhook(&[EngineStateKind::Running]).await,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn skip_webhook_of_state_not_configured() {
+        assert_eq!(
+            sync_engine_with_webhook(&[EngineStateKind::ErrorClean]).await,
+            0
+        );
+    }
+
     #[tokio::test]
     async fn sync_timed_out_trigger_state_engine() {
         let engine_id = EngineId::try_from("123").unwrap();
@@ -1195,16 +2174,19 @@ mod tests {
                 predicate::eq(&TriggerStart),
                 predicate::eq(&WaitToStart),
                 predicate::always(),
+                predicate::eq(None),
+                predicate::always(),
             )
             .times(1)
-            .returning(|_, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
 
         let monitor = create_mock_state_monitor(db, rm);
         // this should not panic
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
             })
             .await
     }
@@ -1228,8 +2210,251 @@ mod tests {
         monitor
             .sync_engine(EngineIdAndInfo {
                 id: engine_id,
-                info: engine_info,
+                info: engine_info.into(),
+                state_update_time: None,
             })
             .await
     }
+
+    /// Log output that takes a while to write each line, e.g. a container log under pressure.
+    struct SlowWriter;
+
+    impl ::std::io::Write for SlowWriter {
+        fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+            ::std::thread::sleep(Duration::from_millis(100));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> ::std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_round_is_not_blocked_by_slow_log_output() {
+        let (writer, _guard) = non_blocking(SlowWriter, 2);
+        let dropped = writer.error_counter();
+        let _subscriber = ::rucat_common::tracing::subscriber::set_default(
+            ::rucat_common::tracing_subscriber::fmt()
+                .with_writer(writer)
+                .finish(),
+        );
+        let engines = (0..20)
+            .map(|i| EngineIdAndInfo {
+                id: EngineId::try_from(i.to_string()).unwrap(),
+                info: EngineInfo::new(
+                    "abc".to_owned(),
+                    Spark,
+                    EngineVersion::from("3.5.4"),
+                    TriggerStart,
+                    BTreeMap::new(),
+                    EngineTime::now(),
+                )
+                .into(),
+                state_update_time: None,
+            })
+            .collect::<Vec<_>>();
+        let mut db = MockDB::new();
+        db.expect_list_engines_need_update()
+            .times(1)
+            .return_once(move |_, _| Ok(engines));
+        db.expect_update_engine_state()
+            .times(20)
+            .returning(|_, _, _, _, _, _| Ok(Some(UpdateEngineStateResult::Success)));
+        let check_interval = Duration::from_secs(1);
+        let monitor = StateMonitor::new(
+            db,
+            MockRM::new(),
+            check_interval,
+            Duration::ZERO,
+            None,
+            None,
+            Arc::new(MockClock::new(test_now())),
+        );
+
+        // Writing the logs of the round takes several seconds.
+        tokio::time::timeout(check_interval, monitor.sync_round())
+            .await
+            .expect("the sync round is blocked by the log output");
+        assert!(dropped.dropped_lines() > 0);
+        assert_eq!(monitor.sync_rounds.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn run_sync_and_housekeeping_loops_concurrently() {
+        let sync_calls = Arc::new(AtomicU64::new(0));
+        let housekeeping_calls = Arc::new(AtomicU64::new(0));
+        let mut db = MockDB::new();
+        let calls = sync_calls.clone();
+        db.expect_list_engines_need_update().returning(move |_, _| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Ok(vec![])
+        });
+        let calls = housekeeping_calls.clone();
+        db.expect_list_triggered_engines().returning(move || {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Ok(vec![])
+        });
+        let monitor = StateMonitor::new(
+            db,
+            MockRM::new(),
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            None,
+            None,
+            Arc::new(MockClock::new(test_now())),
+        );
+        let housekeeping = monitor.clone();
+
+        let result = tokio::time::timeout(Duration::from_millis(1500), async {
+            tokio::select! {
+                never = monitor.sync_loop() => never,
+                never = housekeeping.housekeeping_loop(vec![]) => never,
+            }
+        })
+        .await;
+
+        // the loops run forever
+        assert!(result.is_err());
+        assert!(sync_calls.load(Ordering::Relaxed) >= 1);
+        assert!(housekeeping_calls.load(Ordering::Relaxed) >= 1);
+        // the sync progress is shared between handles
+        assert_eq!(
+            housekeeping.sync_rounds.load(Ordering::Relaxed),
+            sync_calls.load(Ordering::Relaxed)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn housekeeping_loop_counts_monitored_engines_for_alerts() {
+        let count_calls = Arc::new(AtomicU64::new(0));
+        let mut db = MockDB::new();
+        db.expect_list_triggered_engines().returning(|| Ok(vec![]));
+        let calls = count_calls.clone();
+        db.expect_count_engines_by_state()
+            .withf(|engine_types, project| engine_types == [EngineType::Spark] && project.is_none())
+            .returning(move |_, _| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Ok(BTreeMap::from([(EngineStateKind::ErrorClean, 30)]))
+            });
+        let monitor = StateMonitor::new(
+            db,
+            MockRM::new(),
+            Duration::from_secs(1),
+            Duration::from_secs(2),
+            None,
+            Some(vec![EngineType::Spark]),
+            Arc::new(MockClock::new(test_now())),
+        );
+        let rule = AlertRule {
+            name: "too many errors".to_owned(),
+            state: EngineStateKind::ErrorClean,
+            threshold: 20,
+            sustain_secs: 0,
+            cooldown_secs: 0,
+        };
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(3),
+            monitor.housekeeping_loop(vec![rule]),
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(count_calls.load(Ordering::Relaxed), 2);
+    }
+
+    async fn run_sync_loop_with_engine_type_filter(
+        engine_type_filter: Option<Vec<EngineType>>,
+        expected_engine_types: &'static [EngineType],
+    ) {
+        let mut db = MockDB::new();
+        db.expect_list_engines_need_update()
+            .withf(move |engine_types, _| engine_types == expected_engine_types)
+            .times(1)
+            .returning(|_, _| Ok(vec![]));
+        let monitor = StateMonitor::new(
+            db,
+            MockRM::new(),
+            Duration::from_secs(1),
+            Duration::from_secs(2),
+            None,
+            engine_type_filter,
+            Arc::new(MockClock::new(test_now())),
+        );
+
+        let result = tokio::time::timeout(Duration::from_millis(500), monitor.sync_loop()).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn sync_engines_of_all_types() {
+        run_sync_loop_with_engine_type_filter(None, &[]).await;
+    }
+
+    #[tokio::test]
+    async fn sync_engines_of_filtered_types() {
+        run_sync_loop_with_engine_type_filter(Some(vec![Spark]), &[Spark]).await;
+    }
+
+    #[tokio::test]
+    async fn sync_claimed_engines_in_claim_mode() {
+        let mut db = MockDB::new();
+        db.expect_list_engines_need_update().never();
+        db.expect_claim_engines_need_update()
+            .withf(|engine_types, limit, now, claim_duration| {
+                engine_types.is_empty()
+                    && *limit == 2
+                    && *now == test_now()
+                    && *claim_duration == Duration::from_secs(2)
+            })
+            .times(1)
+            .returning(|_, _, _, _| Ok(vec![]));
+        let monitor = StateMonitor::new(
+            db,
+            MockRM::new(),
+            Duration::from_secs(1),
+            Duration::from_secs(2),
+            None,
+            None,
+            Arc::new(MockClock::new(test_now())),
+        )
+        .with_max_engines_per_round(2)
+        .with_claim_mode();
+
+        monitor.sync_round().await;
+
+        assert_eq!(monitor.sync_rounds.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn sync_limited_engines_per_round() {
+        let mut db = MockDB::new();
+        db.expect_list_engines_need_update()
+            .withf(|_, limit| *limit == 2)
+            .times(1)
+            .returning(|_, _| Ok(vec![]));
+        let monitor = create_mock_state_monitor(db, MockRM::new()).with_max_engines_per_round(2);
+
+        monitor.sync_round().await;
+
+        assert_eq!(monitor.sync_rounds.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn monitored_engine_types() {
+        let monitor = create_mock_state_monitor(MockDB::new(), MockRM::new());
+        assert!(monitor.is_monitored(&Spark));
+        let monitor = StateMonitor::new(
+            MockDB::new(),
+            MockRM::new(),
+            Duration::ZERO,
+            Duration::ZERO,
+            None,
+            Some(vec![Spark]),
+            Arc::new(MockClock::new(test_now())),
+        );
+        assert!(monitor.is_monitored(&Spark));
+    }
 }
@@ -1,10 +1,19 @@
+use ::core::time::Duration;
+use ::std::sync::Arc;
+
 use ::rucat_common::{
+    build_info::BuildInfo,
+    clock::SystemClock,
     config::{load_config, DatabaseVariant},
-    database::surrealdb_client::SurrealDBClient,
+    database::{
+        in_memory::InMemoryDatabase, postgres_client::PostgresClient, redis_client::RedisClient,
+        sqlite_client::SqliteClient, surrealdb_client::SurrealDBClient, Database,
+    },
     error::Result,
+    logging::init_tracing,
     tokio,
-    tracing::info,
-    tracing_subscriber,
+    tracing::{info, warn},
+    webhook::WebhookNotifier,
 };
 use ::rucat_state_monitor::{
     config::{StateMonitorConfig, CONFIG_FILE_PATH},
@@ -12,27 +21,103 @@ use ::rucat_state_monitor::{
     StateMonitor,
 };
 
+/// Source of the engine state changes made by the state monitor, see [rucat_common::engine::EngineEvent].
+const EVENT_SOURCE: &str = "state_monitor";
+
 // TODO: Convert the return type to `Result<!>` when it's stable
 // See <https://github.com/rust-lang/rust/issues/35121>
 #[tokio::main]
 async fn main() -> Result<()> {
     // setup tracing
-    tracing_subscriber::fmt::init();
-    info!("Start rucat state monitor");
+    let _logging = init_tracing();
+    info!(
+        "Start rucat state monitor, {}",
+        BuildInfo::new(env!("CARGO_PKG_VERSION"), &[])
+    );
+
+    let config: StateMonitorConfig = load_config(CONFIG_FILE_PATH)?;
+    config.validate()?;
+    match &config.database {
+        DatabaseVariant::Surreal(surreal_config) => {
+            let db_client = SurrealDBClient::new(surreal_config)
+                .await?
+                .with_event_source(EVENT_SOURCE);
+            run(db_client, config).await
+        }
+        DatabaseVariant::Postgres {
+            uri,
+            credentials,
+            pool_size,
+        } => {
+            let db_client = PostgresClient::new(credentials.as_ref(), uri, *pool_size)
+                .await?
+                .with_event_source(EVENT_SOURCE);
+            run(db_client, config).await
+        }
+        DatabaseVariant::Sqlite { path } => {
+            let db_client = SqliteClient::new(path)
+                .await?
+                .with_event_source(EVENT_SOURCE);
+            run(db_client, config).await
+        }
+        DatabaseVariant::Redis { uri, credentials } => {
+            let db_client = RedisClient::new(credentials.as_ref(), uri)
+                .await?
+                .with_event_source(EVENT_SOURCE);
+            run(db_client, config).await
+        }
+        DatabaseVariant::InMemory => {
+            warn!("Engines are stored in memory, which are lost when the state monitor exits and not seen by any server");
+            run(
+                InMemoryDatabase::new().with_event_source(EVENT_SOURCE),
+                config,
+            )
+            .await
+        }
+    }
+}
 
+/// Run the state monitor on the engines in `db_client`, which never returns unless it fails to start.
+async fn run<DB: Database>(db_client: DB, config: StateMonitorConfig) -> Result<()> {
     let StateMonitorConfig {
         check_interval_secs,
         trigger_state_timeout_secs,
-        database: DatabaseVariant::Surreal { credentials, uri },
-    } = load_config(CONFIG_FILE_PATH)?;
+        max_engines_per_round,
+        claim_mode,
+        start_in_progress_timeout_secs,
+        k8s_rate_limiter,
+        engine_type_filter,
+        alerts,
+        engine_versions,
+        webhooks,
+        // connected by the caller
+        database: _,
+    } = config;
 
-    let db_client = SurrealDBClient::new(credentials.as_ref(), uri).await?;
-    let resource_manager = K8sClient::new().await?;
-    let state_monitor = StateMonitor::new(
+    let resource_manager = K8sClient::new(&k8s_rate_limiter, engine_versions).await?;
+    let mut state_monitor = StateMonitor::new(
         db_client,
         resource_manager,
-        check_interval_secs,
-        trigger_state_timeout_secs,
-    );
-    state_monitor.run().await
+        Duration::from_secs(check_interval_secs as u64),
+        Duration::from_secs(trigger_state_timeout_secs as u64),
+        start_in_progress_timeout_secs.map(|secs| Duration::from_secs(secs as u64)),
+        engine_type_filter,
+        Arc::new(SystemClock),
+    )
+    .with_max_engines_per_round(max_engines_per_round);
+    if claim_mode {
+        state_monitor = state_monitor.with_claim_mode();
+    }
+    if let Some(webhooks) = webhooks {
+        state_monitor = state_monitor.with_webhook_notifier(WebhookNotifier::new(webhooks)?);
+    }
+    state_monitor.check_database().await?;
+    let housekeeping = state_monitor.clone();
+    let heartbeat = state_monitor.clone();
+    // All loops run forever, so `select` never returns.
+    tokio::select! {
+        never = state_monitor.sync_loop() => never,
+        never = housekeeping.housekeeping_loop(alerts) => never,
+        never = heartbeat.heartbeat_loop() => never,
+    }
 }
@@ -1,27 +1,44 @@
-use ::std::{borrow::Cow, collections::BTreeMap};
+use ::std::{borrow::Cow, collections::BTreeMap, fmt::Debug};
 
-use ::k8s_openapi::api::core::v1::{Pod, Service};
-use ::kube::{api::PostParams, Api, Client};
+use ::k8s_openapi::{
+    api::core::v1::{Pod, PodStatus, Service},
+    NamespaceResourceScope,
+};
+use ::kube::{
+    api::{ListParams, PostParams},
+    Api, Client, Resource, ResourceExt,
+};
 use ::rucat_common::{
-    anyhow::anyhow,
-    engine::{EngineConfig, EngineId, EngineInfo, EngineState, EngineType, EngineVersion},
+    anyhow::{self, anyhow},
+    engine::{
+        get_spark_app_id, get_spark_connect_url, get_spark_driver_name, get_spark_namespace,
+        get_spark_service_name, EndpointKind, EngineConfig, EngineEndpoints, EngineFeature,
+        EngineFeatures, EngineId, EngineInfo, EngineState, EngineType, EngineVersion,
+        EngineVersions, ENGINE_ID_LABEL, SPARK_CONNECT_PORT, SPARK_RESERVED_CONFIGS, SPARK_UI_PORT,
+    },
     error::{Result, RucatError},
+    serde::de::DeserializeOwned,
     serde_json::{self, json},
-    tracing::{debug, warn},
+    tracing::{debug, info, warn},
 };
 
-use super::{ResourceManager, ResourceState};
-
-fn get_spark_app_id(id: &EngineId) -> Cow<'static, str> {
-    Cow::Owned(format!("rucat-spark-{}", id))
-}
+use super::{
+    rate_limiter::{RateLimiter, RateLimiterConfig},
+    ResourceManager, ResourceState,
+};
 
-fn get_spark_driver_name(id: &EngineId) -> Cow<'static, str> {
-    Cow::Owned(format!("{}-driver", get_spark_app_id(id)))
+/// Endpoints of the spark engine, the spark connect server is exposed by the service.
+fn get_spark_endpoints(id: &EngineId, project: Option<&str>) -> EngineEndpoints {
+    EngineEndpoints::from([(
+        EndpointKind::ConnectInternal.as_str().to_owned(),
+        get_spark_connect_url(id, project),
+    )])
 }
 
-fn get_spark_service_name(id: &EngineId) -> Cow<'static, str> {
-    get_spark_app_id(id)
+/// Label selector of the resources of the engine,
+/// including the resources of engines adopted by rucat, which can be in any namespace.
+fn get_engine_label_selector(id: &EngineId) -> String {
+    format!("{}={}", ENGINE_ID_LABEL, id)
 }
 
 fn get_spark_docker_image_name(spark_version: &EngineVersion) -> Cow<'static, str> {
@@ -41,18 +58,33 @@ pub enum K8sPodState {
     /// This is for the case when pod does not exists which is actually not a valid state in K8s.
     /// We define it to avoid using `Option<K8sPodState>` in `ResourceState`.
     NotExisted,
-    Pending,
-    Running,
+    /// Pending with the reason why the pod is not running yet, if any.
+    Pending(Option<String>),
+    /// Running, and whether the pod is ready to serve requests.
+    Running {
+        ready: bool,
+    },
     Succeeded,
     Failed,
     Unknown,
 }
 
 impl K8sPodState {
+    pub fn from_status(status: Option<PodStatus>) -> Self {
+        let summary = status.as_ref().and_then(summarize_pending_pod);
+        let ready = status.as_ref().is_some_and(is_pod_ready);
+        match Self::from_phase(status.and_then(|s| s.phase)) {
+            Self::Pending(_) => Self::Pending(summary),
+            Self::Running { .. } => Self::Running { ready },
+            state => state,
+        }
+    }
+
     pub fn from_phase(phase: Option<String>) -> Self {
         match phase.as_deref() {
-            Some("Pending") => Self::Pending,
-            Some("Running") => Self::Running,
+            Some("Pending") => Self::Pending(None),
+            // Readiness is unknown from the phase only.
+            Some("Running") => Self::Running { ready: false },
             Some("Succeeded") => Self::Succeeded,
             Some("Failed") => Self::Failed,
             Some("Unknown") | None => Self::Unknown,
@@ -62,21 +94,54 @@ impl K8sPodState {
     }
 }
 
+/// Whether the `Ready` condition of the pod is true.
+fn is_pod_ready(status: &PodStatus) -> bool {
+    status
+        .conditions
+        .iter()
+        .flatten()
+        .any(|condition| condition.type_ == "Ready" && condition.status == "True")
+}
+
+/// Summarize why the pod is pending, from the waiting reasons of containers,
+/// or from the conditions that are not satisfied, e.g. `ImagePullBackOff: Back-off pulling image "xxx"`.
+fn summarize_pending_pod(status: &PodStatus) -> Option<String> {
+    let join = |reason: &Option<String>, message: &Option<String>| match (reason, message) {
+        (Some(reason), Some(message)) => Some(format!("{}: {}", reason, message)),
+        (Some(s), None) | (None, Some(s)) => Some(s.clone()),
+        (None, None) => None,
+    };
+    let container_waiting_reason = status
+        .container_statuses
+        .iter()
+        .flatten()
+        .filter_map(|s| s.state.as_ref()?.waiting.as_ref())
+        .find_map(|waiting| join(&waiting.reason, &waiting.message));
+    container_waiting_reason.or_else(|| {
+        status
+            .conditions
+            .iter()
+            .flatten()
+            .filter(|condition| condition.status == "False")
+            .find_map(|condition| join(&condition.reason, &condition.message))
+    })
+}
+
 impl ResourceState for K8sPodState {
     fn get_new_engine_state(&self, old_state: &EngineState) -> Option<EngineState> {
         match (old_state, self) {
-            (EngineState::StartInProgress, Self::Pending | Self::Unknown) => None,
-            (EngineState::StartInProgress, Self::Running) => Some(EngineState::Running),
+            (EngineState::StartInProgress, Self::Pending(_) | Self::Unknown) => None,
+            (EngineState::StartInProgress, Self::Running { .. }) => Some(EngineState::Running),
             (EngineState::StartInProgress, Self::Succeeded | Self::Failed | Self::NotExisted) => {
                 Some(EngineState::ErrorClean(Cow::Borrowed(
                     "Engine fails to start.",
                 )))
             }
 
-            (EngineState::Running, Self::Pending) => Some(EngineState::ErrorCleanInProgress(
+            (EngineState::Running, Self::Pending(_)) => Some(EngineState::ErrorCleanInProgress(
                 Cow::Borrowed("Engine restarts unexpected."),
             )),
-            (EngineState::Running, Self::Running | Self::Unknown) => None,
+            (EngineState::Running, Self::Running { .. } | Self::Unknown) => None,
             (EngineState::Running, Self::Succeeded | Self::Failed | Self::NotExisted) => Some(
                 EngineState::ErrorClean(Cow::Borrowed("Engine terminates during running.")),
             ),
@@ -93,16 +158,36 @@ impl ResourceState for K8sPodState {
             }
         }
     }
+
+    fn is_ready(&self) -> bool {
+        matches!(self, Self::Running { ready: true })
+    }
+
+    fn get_summary(&self) -> Cow<'_, str> {
+        match self {
+            Self::NotExisted => Cow::Borrowed("pod does not exist"),
+            Self::Pending(Some(summary)) => Cow::Borrowed(summary),
+            Self::Pending(None) => Cow::Borrowed("pod is pending"),
+            Self::Running { ready: true } => Cow::Borrowed("pod is running"),
+            Self::Running { ready: false } => Cow::Borrowed("pod is running but not ready"),
+            Self::Succeeded => Cow::Borrowed("pod has succeeded"),
+            Self::Failed => Cow::Borrowed("pod has failed"),
+            Self::Unknown => Cow::Borrowed("pod state is unknown"),
+        }
+    }
 }
 
 /// Client to interact with the Kubernetes cluster.
+/// All calls of the Kubernetes API share the same rate limiter.
 pub struct K8sClient {
     client: Client,
+    rate_limiter: RateLimiter,
+    /// Engines of blocked versions are not started.
+    engine_versions: EngineVersions,
 }
 
 impl K8sClient {
     const SPARK_SERVICE_SELECTOR: &str = "rucat-engine-selector";
-    const SPARK_VERSIONS: [&'static str; 2] = ["3.5.3", "3.5.4"];
     // convert engine configurations to Spark submit format
     fn to_spark_submit_format(
         id: &EngineId,
@@ -110,7 +195,8 @@ impl K8sClient {
         user_config: &EngineConfig,
     ) -> Result<Vec<Cow<'static, str>>> {
         // Preset configurations for Spark on Kubernetes.
-        // Users are not allowed to set these configurations.
+        // Users are not allowed to set these configurations, they must be the `SPARK_RESERVED_CONFIGS`
+        // so that the server rejects them before the engine is created.
         // make the map ordered for easier testing
         let preset_config = BTreeMap::from([
             (Cow::Borrowed("spark.app.id"), get_spark_app_id(id)),
@@ -136,7 +222,10 @@ impl K8sClient {
             ),
         ]);
 
-        match preset_config.keys().find(|k| user_config.contains_key(*k)) {
+        match SPARK_RESERVED_CONFIGS
+            .iter()
+            .find(|k| user_config.contains_key(**k))
+        {
             Some(key) => Err(RucatError::not_allowed(anyhow!(
                 "The config {} is not allowed as it is reserved.",
                 key
@@ -163,25 +252,53 @@ impl K8sClient {
         }
     }
 
-    pub async fn new() -> Result<Self> {
+    pub async fn new(
+        rate_limiter_config: &RateLimiterConfig,
+        engine_versions: EngineVersions,
+    ) -> Result<Self> {
         let client = Client::try_default()
             .await
             .map_err(RucatError::fail_to_delete_engine)?;
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            rate_limiter: RateLimiter::new(rate_limiter_config),
+            engine_versions,
+        })
+    }
+
+    /// Number of Kubernetes API calls waiting for the rate limiter.
+    pub fn get_rate_limiter_queue_depth(&self) -> usize {
+        self.rate_limiter.queue_depth()
+    }
+
+    /// Readiness probe of the spark driver container, checking the spark connect port.
+    /// Only set when the engine enables [EngineFeature::READINESS_PROBE].
+    fn get_spark_driver_readiness_probe(features: &EngineFeatures) -> serde_json::Value {
+        if features.contains(EngineFeature::READINESS_PROBE) {
+            json!({
+                "tcpSocket": { "port": SPARK_CONNECT_PORT },
+                "periodSeconds": 5,
+            })
+        } else {
+            serde_json::Value::Null
+        }
     }
 
+    /// Create the resources of the spark engine in the namespace of `project`, see [get_spark_namespace].
     pub async fn create_spark_resource(
         &self,
         id: &EngineId,
         spark_version: &EngineVersion,
         config: &EngineConfig,
+        features: &EngineFeatures,
+        project: Option<&str>,
     ) -> Result<()> {
-        if !Self::SPARK_VERSIONS.contains(&spark_version.as_str()) {
-            return Err(RucatError::not_allowed(anyhow!(
-                "Spark version {} is not supported. Supported versions: {:?}",
-                spark_version,
-                Self::SPARK_VERSIONS
-            )));
+        // Engines of deprecated versions keep starting until the versions are blocked.
+        if let Some(warning) = self
+            .engine_versions
+            .check(&EngineType::Spark, spark_version)?
+        {
+            warn!("Start engine {}: {}", id, warning);
         }
         let spark_app_id = get_spark_app_id(id);
         let spark_driver_name = get_spark_driver_name(id);
@@ -195,6 +312,7 @@ impl K8sClient {
                 "name": spark_driver_name,
                 "labels": {
                     Self::SPARK_SERVICE_SELECTOR: spark_app_id,
+                    ENGINE_ID_LABEL: id.to_string(),
                 },
             },
             "spec": {
@@ -204,10 +322,10 @@ impl K8sClient {
                         "name": "spark-driver",
                         "image": get_spark_docker_image_name(spark_version),
                         "ports": [
-                            { "containerPort": 4040 },
+                            { "containerPort": SPARK_UI_PORT },
                             { "containerPort": 7078 },
                             { "containerPort": 7079 },
-                            { "containerPort": 15002 },
+                            { "containerPort": SPARK_CONNECT_PORT },
                         ],
                         "env": [
                             // let connect server run in the foreground
@@ -215,6 +333,7 @@ impl K8sClient {
                         ],
                         "command": ["/opt/spark/sbin/start-connect-server.sh"],
                         "args": args,
+                        "readinessProbe": Self::get_spark_driver_readiness_probe(features),
                     }
                 ]
             }
@@ -222,12 +341,14 @@ impl K8sClient {
         .map_err(RucatError::fail_to_start_engine)?;
 
         // Create a Pod API instance
-        let pods: Api<Pod> = Api::namespaced(self.client.clone(), "default");
+        let namespace = get_spark_namespace(project);
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), namespace);
 
         // Create the Pod
         let pp = PostParams::default();
-        let _pod = pods
-            .create(&pp, &pod)
+        let _pod = self
+            .rate_limiter
+            .throttle(pods.create(&pp, &pod))
             .await
             .map_err(RucatError::fail_to_start_engine)?;
         // Define your Headless Service manifest
@@ -236,6 +357,9 @@ impl K8sClient {
             "kind": "Service",
             "metadata": {
                 "name": spark_service_name,
+                "labels": {
+                    ENGINE_ID_LABEL: id.to_string(),
+                },
             },
             "spec": {
                 "type": "ClusterIP",
@@ -246,8 +370,8 @@ impl K8sClient {
                 "ports": [
                     {
                         "protocol": "TCP",
-                        "port": 4040,
-                        "targetPort": 4040,
+                        "port": SPARK_UI_PORT,
+                        "targetPort": SPARK_UI_PORT,
                         "name": "spark-ui",
                     },
                     {
@@ -264,8 +388,8 @@ impl K8sClient {
                     },
                     {
                         "protocol": "TCP",
-                        "port": 15002,
-                        "targetPort": 15002,
+                        "port": SPARK_CONNECT_PORT,
+                        "targetPort": SPARK_CONNECT_PORT,
                         "name": "spark-connect",
                     },
                 ]
@@ -274,10 +398,11 @@ impl K8sClient {
         .map_err(RucatError::fail_to_start_engine)?;
 
         // Create a Service API instance
-        let services: Api<Service> = Api::namespaced(self.client.clone(), "default");
+        let services: Api<Service> = Api::namespaced(self.client.clone(), namespace);
         // Create the Service
-        let _service = services
-            .create(&pp, &service)
+        let _service = self
+            .rate_limiter
+            .throttle(services.create(&pp, &service))
             .await
             .map_err(RucatError::fail_to_start_engine)?;
 
@@ -285,28 +410,94 @@ impl K8sClient {
     }
 }
 
+impl K8sClient {
+    /// Get the spark driver pod of the engine.
+    /// Fall back to the engine id label for engines adopted by rucat, whose pods are named by others.
+    async fn get_spark_driver(
+        &self,
+        id: &EngineId,
+        project: Option<&str>,
+    ) -> anyhow::Result<Option<Pod>> {
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), get_spark_namespace(project));
+        let spark_driver_name = get_spark_driver_name(id);
+        if let Some(pod) = self
+            .rate_limiter
+            .throttle(pods.get_opt(&spark_driver_name))
+            .await?
+        {
+            return Ok(Some(pod));
+        }
+        let pods: Api<Pod> = Api::all(self.client.clone());
+        let list_params = ListParams::default().labels(&get_engine_label_selector(id));
+        let labelled_pods = self.rate_limiter.throttle(pods.list(&list_params)).await?;
+        Ok(labelled_pods.items.into_iter().next())
+    }
+
+    /// Delete the resources labelled with the engine id in all namespaces.
+    /// Return the number of deleted resources.
+    async fn delete_labelled_resources<K>(&self, id: &EngineId) -> Result<usize>
+    where
+        K: Resource<Scope = NamespaceResourceScope> + Clone + DeserializeOwned + Debug,
+        K::DynamicType: Default,
+    {
+        let resources: Api<K> = Api::all(self.client.clone());
+        let list_params = ListParams::default().labels(&get_engine_label_selector(id));
+        let labelled_resources = self
+            .rate_limiter
+            .throttle(resources.list(&list_params))
+            .await
+            .map_err(RucatError::fail_to_delete_engine)?;
+        for resource in &labelled_resources.items {
+            let namespace = resource.namespace().unwrap_or_else(|| "default".to_owned());
+            let name = resource.name_any();
+            debug!(
+                "Deleting {} {}/{}",
+                K::kind(&K::DynamicType::default()),
+                namespace,
+                name
+            );
+            let resources: Api<K> = Api::namespaced(self.client.clone(), &namespace);
+            self.rate_limiter
+                .throttle(resources.delete(&name, &Default::default()))
+                .await
+                .map_err(RucatError::fail_to_delete_engine)?;
+        }
+        Ok(labelled_resources.items.len())
+    }
+}
+
 impl ResourceManager for K8sClient {
     type ResourceState = K8sPodState;
 
-    async fn create_resource(&self, id: &EngineId, info: &EngineInfo) -> Result<()> {
+    async fn create_resource(&self, id: &EngineId, info: &EngineInfo) -> Result<EngineEndpoints> {
         match info.engine_type {
             EngineType::Spark => {
-                self.create_spark_resource(id, &info.version, &info.config)
-                    .await
+                let project = info.project.as_deref();
+                self.create_spark_resource(
+                    id,
+                    &info.version,
+                    &info.config,
+                    &info.features,
+                    project,
+                )
+                .await?;
+                Ok(get_spark_endpoints(id, project))
             }
         }
     }
 
-    async fn get_resource_state(&self, id: &EngineId) -> Self::ResourceState {
+    async fn get_resource_state(
+        &self,
+        id: &EngineId,
+        project: Option<&str>,
+    ) -> Self::ResourceState {
         let spark_driver_name = get_spark_driver_name(id);
-        // Create a Pod API instance
-        let pods: Api<Pod> = Api::namespaced(self.client.clone(), "default");
         // Get the Pod phase
-        pods.get_opt(&spark_driver_name)
+        self.get_spark_driver(id, project)
             .await
             .map(|pod| {
                 let state = pod.map_or(K8sPodState::NotExisted, |pod| {
-                    K8sPodState::from_phase(pod.status.and_then(|s| s.phase))
+                    K8sPodState::from_status(pod.status)
                 });
                 debug!("Get Pod: {} state: {:?}", spark_driver_name, state);
                 state
@@ -320,24 +511,38 @@ impl ResourceManager for K8sClient {
             })
     }
 
-    async fn clean_resource(&self, id: &EngineId) -> Result<()> {
+    async fn clean_resource(&self, id: &EngineId, project: Option<&str>) -> Result<()> {
+        let deleted_pods = self.delete_labelled_resources::<Pod>(id).await?;
+        let deleted_services = self.delete_labelled_resources::<Service>(id).await?;
+        if deleted_pods > 0 {
+            info!(
+                "Deleted {} pod(s) and {} service(s) of engine {}",
+                deleted_pods, deleted_services, id
+            );
+            return Ok(());
+        }
+
+        // Resources created before they were labelled can only be found by names.
         let spark_driver_name = get_spark_driver_name(id);
         debug!("Deleting Pod: {}", spark_driver_name);
         let spark_service_name = get_spark_service_name(id);
 
         // Create a Pod API instance
-        let pods: Api<Pod> = Api::namespaced(self.client.clone(), "default");
+        let namespace = get_spark_namespace(project);
+        let pods: Api<Pod> = Api::namespaced(self.client.clone(), namespace);
         // Delete the Pod
-        let _pod = pods
-            .delete(&spark_driver_name, &Default::default())
+        let _pod = self
+            .rate_limiter
+            .throttle(pods.delete(&spark_driver_name, &Default::default()))
             .await
             .map_err(RucatError::fail_to_delete_engine)?;
 
         // Create a Service API instance
-        let services: Api<Service> = Api::namespaced(self.client.clone(), "default");
+        let services: Api<Service> = Api::namespaced(self.client.clone(), namespace);
         // Delete the Service
-        let _service = services
-            .delete(&spark_service_name, &Default::default())
+        let _service = self
+            .rate_limiter
+            .throttle(services.delete(&spark_service_name, &Default::default()))
             .await
             .map_err(RucatError::fail_to_delete_engine)?;
 
@@ -349,24 +554,122 @@ impl ResourceManager for K8sClient {
 mod tests {
     use super::*;
 
+    fn get_pod_state(status: serde_json::Value) -> K8sPodState {
+        K8sPodState::from_status(Some(serde_json::from_value(status).unwrap()))
+    }
+
     #[test]
-    fn test_get_spark_app_id() -> Result<()> {
-        let id = EngineId::try_from("abc")?;
-        assert_eq!(get_spark_app_id(&id), "rucat-spark-abc");
-        Ok(())
+    fn pending_pod_with_image_pull_back_off() {
+        let state = get_pod_state(json!({
+            "phase": "Pending",
+            "conditions": [
+                {"type": "PodScheduled", "status": "True"},
+                {"type": "ContainersReady", "status": "False", "reason": "ContainersNotReady"},
+            ],
+            "containerStatuses": [{
+                "name": "spark-driver",
+                "image": "apache/spark:3.5.9",
+                "imageID": "",
+                "ready": false,
+                "restartCount": 0,
+                "state": {
+                    "waiting": {
+                        "reason": "ImagePullBackOff",
+                        "message": "Back-off pulling image \"apache/spark:3.5.9\"",
+                    }
+                },
+            }],
+        }));
+        assert_eq!(
+            state.get_summary(),
+            "ImagePullBackOff: Back-off pulling image \"apache/spark:3.5.9\""
+        );
     }
 
     #[test]
-    fn test_get_spark_driver_name() -> Result<()> {
-        let id = EngineId::try_from("abc")?;
-        assert_eq!(get_spark_driver_name(&id), "rucat-spark-abc-driver");
-        Ok(())
+    fn pending_pod_with_unsatisfied_condition() {
+        let state = get_pod_state(json!({
+            "phase": "Pending",
+            "conditions": [{
+                "type": "PodScheduled",
+                "status": "False",
+                "reason": "Unschedulable",
+                "message": "0/3 nodes are available: 3 Insufficient cpu.",
+            }],
+        }));
+        assert_eq!(
+            state.get_summary(),
+            "Unschedulable: 0/3 nodes are available: 3 Insufficient cpu."
+        );
+    }
+
+    #[test]
+    fn pending_pod_without_reason() {
+        let state = get_pod_state(json!({"phase": "Pending"}));
+        assert_eq!(state.get_summary(), "pod is pending");
+    }
+
+    #[test]
+    fn running_pod() {
+        let state = get_pod_state(json!({
+            "phase": "Running",
+            "conditions": [{"type": "Ready", "status": "True"}],
+        }));
+        assert!(matches!(state, K8sPodState::Running { ready: true }));
+        assert!(state.is_ready());
+        assert_eq!(state.get_summary(), "pod is running");
+    }
+
+    #[test]
+    fn running_pod_not_ready() {
+        let state = get_pod_state(json!({
+            "phase": "Running",
+            "conditions": [{"type": "Ready", "status": "False"}],
+        }));
+        assert!(matches!(state, K8sPodState::Running { ready: false }));
+        assert!(!state.is_ready());
+        assert_eq!(state.get_summary(), "pod is running but not ready");
+    }
+
+    #[test]
+    fn engine_label_selector() {
+        assert_eq!(
+            get_engine_label_selector(&EngineId::try_from("abc").unwrap()),
+            "rucat.io/engine-id=abc"
+        );
+    }
+
+    #[test]
+    fn spark_driver_readiness_probe() {
+        assert_eq!(
+            K8sClient::get_spark_driver_readiness_probe(&EngineFeatures::new()),
+            json!(null)
+        );
+        assert_eq!(
+            K8sClient::get_spark_driver_readiness_probe(&EngineFeatures::from([
+                EngineFeature::READINESS_PROBE.to_owned()
+            ])),
+            json!({"tcpSocket": {"port": 15002}, "periodSeconds": 5})
+        );
     }
 
     #[test]
-    fn test_get_spark_service_name() -> Result<()> {
+    fn test_get_spark_endpoints() -> Result<()> {
         let id = EngineId::try_from("abc")?;
-        assert_eq!(get_spark_service_name(&id), "rucat-spark-abc");
+        assert_eq!(
+            get_spark_endpoints(&id, None),
+            EngineEndpoints::from([(
+                "connect-internal".to_owned(),
+                "sc://rucat-spark-abc.default.svc.cluster.local:15002".to_owned()
+            )])
+        );
+        assert_eq!(
+            get_spark_endpoints(&id, Some("team-a")),
+            EngineEndpoints::from([(
+                "connect-internal".to_owned(),
+                "sc://rucat-spark-abc.team-a.svc.cluster.local:15002".to_owned()
+            )])
+        );
         Ok(())
     }
 
@@ -390,6 +693,21 @@ mod tests {
         check_preset_config("spark.kubernetes.executor.podNamePrefix");
     }
 
+    #[test]
+    fn preset_config_are_reserved() -> Result<()> {
+        let spark_submit_format = K8sClient::to_spark_submit_format(
+            &EngineId::try_from("abc")?,
+            &"3.5.3".to_owned(),
+            &BTreeMap::new(),
+        )?;
+        let preset_keys = spark_submit_format
+            .iter()
+            .filter_map(|arg| arg.split_once('=').map(|(key, _)| key))
+            .collect::<Vec<_>>();
+        assert_eq!(preset_keys, SPARK_RESERVED_CONFIGS);
+        Ok(())
+    }
+
     #[test]
     fn empty_engine_config() -> Result<()> {
         let spark_submit_format = K8sClient::to_spark_submit_format(
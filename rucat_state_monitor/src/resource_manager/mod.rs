@@ -1,9 +1,11 @@
 pub mod k8s_client;
+pub mod rate_limiter;
 
 use ::core::future::Future;
+use ::std::borrow::Cow;
 
 use ::rucat_common::{
-    engine::{EngineId, EngineInfo, EngineState},
+    engine::{EngineEndpoints, EngineId, EngineInfo, EngineState},
     error::Result,
 };
 
@@ -11,17 +13,36 @@ pub trait ResourceState {
     /// get the new engine state based on old engine state and resource state.
     /// if new state is same as old state, return None.
     fn get_new_engine_state(&self, old_state: &EngineState) -> Option<EngineState>;
+
+    /// Whether the resource is ready to serve requests.
+    fn is_ready(&self) -> bool;
+
+    /// Human readable summary of the resource state, e.g. why the resource is not running.
+    fn get_summary(&self) -> Cow<'_, str>;
 }
 
 pub trait ResourceManager {
     type ResourceState: ResourceState;
 
-    /// Create Engine and associated resources
-    fn create_resource(&self, id: &EngineId, info: &EngineInfo)
-        -> impl Future<Output = Result<()>>;
+    /// Create Engine and associated resources.
+    /// Return the endpoints to connect to the engine once it is running.
+    fn create_resource(
+        &self,
+        id: &EngineId,
+        info: &EngineInfo,
+    ) -> impl Future<Output = Result<EngineEndpoints>>;
 
-    fn get_resource_state(&self, id: &EngineId) -> impl Future<Output = Self::ResourceState>;
+    /// Get the state of the resources of the engine in `project`, see [EngineInfo::project].
+    fn get_resource_state(
+        &self,
+        id: &EngineId,
+        project: Option<&str>,
+    ) -> impl Future<Output = Self::ResourceState>;
 
-    /// Remove all resources related to the Engine
-    fn clean_resource(&self, id: &EngineId) -> impl Future<Output = Result<()>>;
+    /// Remove all resources related to the Engine in `project`, see [EngineInfo::project].
+    fn clean_resource(
+        &self,
+        id: &EngineId,
+        project: Option<&str>,
+    ) -> impl Future<Output = Result<()>>;
 }
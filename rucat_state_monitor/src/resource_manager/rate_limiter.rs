@@ -0,0 +1,346 @@
+//! Client side rate limiter of the Kubernetes API, so that large monitoring rounds
+//! do not exceed the priority-and-fairness budget of the API server.
+
+use ::core::{future::Future, time::Duration};
+use ::std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use ::rucat_common::{
+    anyhow::{self, anyhow},
+    error::{Result, RucatError},
+    serde::Deserialize,
+    tokio::time::{sleep_until, Instant},
+    tracing::warn,
+};
+
+/// Configuration of the rate limiter of the Kubernetes API calls.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+#[serde(crate = "rucat_common::serde")]
+pub struct RateLimiterConfig {
+    /// Sustained number of calls per second.
+    #[serde(default = "RateLimiterConfig::default_qps")]
+    pub qps: f64,
+    /// Number of calls allowed at once above the sustained rate.
+    #[serde(default = "RateLimiterConfig::default_burst")]
+    pub burst: u32,
+    /// Maximum time in second for a call to wait for the rate limiter, after which the call fails.
+    #[serde(default = "RateLimiterConfig::default_timeout_secs")]
+    pub timeout_secs: u32,
+    /// Time in second to slow down after the API server responds 429 Too Many Requests.
+    #[serde(default = "RateLimiterConfig::default_cooldown_secs")]
+    pub cooldown_secs: u32,
+}
+
+impl RateLimiterConfig {
+    fn default_qps() -> f64 {
+        20.0
+    }
+
+    fn default_burst() -> u32 {
+        40
+    }
+
+    fn default_timeout_secs() -> u32 {
+        30
+    }
+
+    fn default_cooldown_secs() -> u32 {
+        60
+    }
+
+    /// Check the rate is positive and the burst allows at least one call.
+    pub fn validate(&self) -> Result<()> {
+        if !(self.qps.is_finite() && self.qps > 0.0) || self.burst == 0 {
+            return Err(RucatError::fail_to_load_config(anyhow!(
+                "Rate limiter needs a positive `qps` and `burst`, but got qps {} and burst {}",
+                self.qps,
+                self.burst
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for RateLimiterConfig {
+    fn default() -> Self {
+        Self {
+            qps: Self::default_qps(),
+            burst: Self::default_burst(),
+            timeout_secs: Self::default_timeout_secs(),
+            cooldown_secs: Self::default_cooldown_secs(),
+        }
+    }
+}
+
+/// Token bucket shared by all calls, in the form of the generic cell rate algorithm:
+/// instead of counting tokens, track the time when the bucket would be full again.
+/// Calls are queued in the order they acquire the limiter.
+pub struct RateLimiter {
+    interval: Duration,
+    /// Calls allowed at once are the ones within this time ahead of now.
+    burst_tolerance: Duration,
+    timeout: Duration,
+    cooldown: Duration,
+    state: Mutex<RateLimiterState>,
+    queue_depth: AtomicUsize,
+}
+
+struct RateLimiterState {
+    /// Time when the next call is allowed if no burst was allowed.
+    next_call_time: Instant,
+    /// Calls are slowed down until this time after a 429 response.
+    slow_down_until: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Rate is divided by this factor while slowing down.
+    const SLOW_DOWN_FACTOR: u32 = 2;
+
+    pub fn new(config: &RateLimiterConfig) -> Self {
+        let interval = Duration::from_secs_f64(1.0 / config.qps);
+        Self {
+            interval,
+            burst_tolerance: interval * config.burst.saturating_sub(1),
+            timeout: Duration::from_secs(config.timeout_secs as u64),
+            cooldown: Duration::from_secs(config.cooldown_secs as u64),
+            state: Mutex::new(RateLimiterState {
+                next_call_time: Instant::now(),
+                slow_down_until: None,
+            }),
+            queue_depth: AtomicUsize::new(0),
+        }
+    }
+
+    /// Number of calls waiting for the rate limiter.
+    pub fn queue_depth(&self) -> usize {
+        self.queue_depth.load(Ordering::Relaxed)
+    }
+
+    /// Wait until the call is allowed.
+    /// Fail without waiting if the call would wait longer than the timeout.
+    pub async fn acquire(&self) -> Result<()> {
+        let now = Instant::now();
+        let call_time = {
+            let mut state = self.state.lock().unwrap();
+            let slowing_down = state.slow_down_until.is_some_and(|until| now < until);
+            // No burst is allowed while slowing down.
+            let (interval, burst_tolerance) = if slowing_down {
+                (self.interval * Self::SLOW_DOWN_FACTOR, Duration::ZERO)
+            } else {
+                (self.interval, self.burst_tolerance)
+            };
+            let next_call_time = state.next_call_time.max(now);
+            let call_time = next_call_time
+                .checked_sub(burst_tolerance)
+                .map_or(now, |time| time.max(now));
+            if call_time - now > self.timeout {
+                return Err(RucatError::service_unavailable(anyhow!(
+                    "Kubernetes API calls are throttled, the call would wait {:?} longer than {:?}",
+                    call_time - now,
+                    self.timeout
+                )));
+            }
+            state.next_call_time = next_call_time + interval;
+            call_time
+        };
+        if call_time > now {
+            let _queued = QueuedCall::new(&self.queue_depth);
+            sleep_until(call_time).await;
+        }
+        Ok(())
+    }
+
+    /// Slow down calls for the cooldown period.
+    pub fn slow_down(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.slow_down_until = Some(Instant::now() + self.cooldown);
+    }
+
+    /// Make the call once it is allowed by the rate limiter,
+    /// and slow down if the API server responds 429 Too Many Requests.
+    pub async fn throttle<T>(
+        &self,
+        call: impl Future<Output = kube::Result<T>>,
+    ) -> anyhow::Result<T> {
+        self.acquire().await?;
+        let result = call.await;
+        if let Err(kube::Error::Api(response)) = &result {
+            if response.code == 429 {
+                warn!(
+                    "Kubernetes API server is overloaded, slow down for {:?}: {}",
+                    self.cooldown, response.message
+                );
+                self.slow_down();
+            }
+        }
+        Ok(result?)
+    }
+}
+
+/// Count the call in the queue until it is dropped, including when the waiting call is cancelled.
+struct QueuedCall<'a>(&'a AtomicUsize);
+
+impl<'a> QueuedCall<'a> {
+    fn new(queue_depth: &'a AtomicUsize) -> Self {
+        queue_depth.fetch_add(1, Ordering::Relaxed);
+        Self(queue_depth)
+    }
+}
+
+impl Drop for QueuedCall<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::kube::core::ErrorResponse;
+    use ::rucat_common::tokio;
+
+    use super::*;
+
+    fn rate_limiter(qps: f64, burst: u32, timeout_secs: u32) -> RateLimiter {
+        RateLimiter::new(&RateLimiterConfig {
+            qps,
+            burst,
+            timeout_secs,
+            cooldown_secs: 10,
+        })
+    }
+
+    /// Acquire the limiter `n` times in sequence, return the elapsed time of each call.
+    async fn acquire_times(limiter: &RateLimiter, n: usize) -> Vec<Duration> {
+        let start = Instant::now();
+        let mut times = Vec::with_capacity(n);
+        for _ in 0..n {
+            limiter.acquire().await.unwrap();
+            times.push(start.elapsed());
+        }
+        times
+    }
+
+    fn millis(times: &[u64]) -> Vec<Duration> {
+        times.iter().copied().map(Duration::from_millis).collect()
+    }
+
+    fn api_error(code: u16) -> kube::Error {
+        kube::Error::Api(ErrorResponse {
+            status: "Failure".to_owned(),
+            message: "Too many requests".to_owned(),
+            reason: "TooManyRequests".to_owned(),
+            code,
+        })
+    }
+
+    #[test]
+    fn validate_config() {
+        assert!(RateLimiterConfig::default().validate().is_ok());
+        for (qps, burst) in [(0.0, 1), (-1.0, 1), (f64::NAN, 1), (1.0, 0)] {
+            let config = RateLimiterConfig {
+                qps,
+                burst,
+                ..Default::default()
+            };
+            assert!(config.validate().is_err_and(|e| e.to_string().starts_with(
+                "Fail to load config: Rate limiter needs a positive `qps` and `burst`"
+            )));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn allow_burst_then_pace_calls() {
+        let limiter = rate_limiter(10.0, 3, 10);
+        assert_eq!(
+            acquire_times(&limiter, 6).await,
+            millis(&[0, 0, 0, 100, 200, 300])
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refill_burst_over_time() {
+        let limiter = rate_limiter(10.0, 2, 10);
+        acquire_times(&limiter, 2).await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(acquire_times(&limiter, 3).await, millis(&[0, 0, 100]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fail_calls_waiting_longer_than_timeout() {
+        let limiter = rate_limiter(1.0, 1, 1);
+        let start = Instant::now();
+        limiter.acquire().await.unwrap();
+
+        // the second call waits 1 second, the third one would wait 2 seconds.
+        let (second, third) = tokio::join!(limiter.acquire(), limiter.acquire());
+
+        assert!(second.is_ok());
+        assert!(third.is_err_and(|e| e.to_string().starts_with(
+            "Service unavailable: Kubernetes API calls are throttled, the call would wait 2s longer than 1s"
+        )));
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        // the failed call does not take a token
+        limiter.acquire().await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn count_queued_calls() {
+        let limiter = rate_limiter(10.0, 1, 10);
+        limiter.acquire().await.unwrap();
+        assert_eq!(limiter.queue_depth(), 0);
+
+        let (_, _, queue_depth) = tokio::join!(limiter.acquire(), limiter.acquire(), async {
+            tokio::task::yield_now().await;
+            limiter.queue_depth()
+        });
+        assert_eq!(queue_depth, 2);
+        assert_eq!(limiter.queue_depth(), 0);
+
+        // cancelled calls leave the queue
+        let result = tokio::time::timeout(Duration::from_millis(50), limiter.acquire()).await;
+        assert!(result.is_err());
+        assert_eq!(limiter.queue_depth(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_down_after_too_many_requests() {
+        let limiter = rate_limiter(10.0, 3, 10);
+
+        let result = limiter
+            .throttle(async { Err::<(), _>(api_error(429)) })
+            .await;
+
+        assert!(result.is_err());
+        // half of the rate and no burst during the cooldown
+        assert_eq!(acquire_times(&limiter, 3).await, millis(&[100, 300, 500]));
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(acquire_times(&limiter, 4).await, millis(&[0, 0, 0, 100]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn do_not_slow_down_after_other_errors() {
+        let limiter = rate_limiter(10.0, 3, 10);
+
+        let result = limiter
+            .throttle(async { Err::<(), _>(api_error(500)) })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(acquire_times(&limiter, 3).await, millis(&[0, 0, 100]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn throttle_successful_calls() {
+        let limiter = rate_limiter(10.0, 1, 10);
+        let start = Instant::now();
+
+        assert_eq!(limiter.throttle(async { Ok(1) }).await.unwrap(), 1);
+        assert_eq!(limiter.throttle(async { Ok(2) }).await.unwrap(), 2);
+
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+    }
+}